@@ -0,0 +1,107 @@
+// Token Amounts
+// Fixed-point display for raw integer amounts with a per-token number of
+// decimals, e.g. raw 123456 with 3 decimals is "123.456".
+
+use crate::ghost::{Error, Result};
+
+/// Largest `decimals` for which 10^decimals fits in a u64
+pub const MAX_DECIMALS: u8 = 19;
+
+fn check_decimals(decimals: u8) -> Result<()> {
+    if decimals > MAX_DECIMALS {
+        return Err(Error::InvalidInput(format!("At most {} decimals supported", MAX_DECIMALS)));
+    }
+    Ok(())
+}
+
+/// Fails above `MAX_DECIMALS`, like `parse_amount`, so every formatted
+/// amount parses back
+pub fn format_amount(raw: u64, decimals: u8) -> Result<String> {
+    check_decimals(decimals)?;
+    if decimals == 0 {
+        return Ok(raw.to_string());
+    }
+    let decimals = decimals as usize;
+    let digits = format!("{:0width$}", raw, width = decimals + 1);
+    let (whole, fraction) = digits.split_at(digits.len() - decimals);
+    Ok(format!("{}.{}", whole, fraction))
+}
+
+pub fn parse_amount(s: &str, decimals: u8) -> Result<u64> {
+    check_decimals(decimals)?;
+    let (whole, fraction) = match s.split_once('.') {
+        Some((whole, fraction)) => (whole, fraction),
+        None => (s, ""),
+    };
+    let is_digits = |part: &str| part.bytes().all(|b| b.is_ascii_digit());
+    if whole.is_empty() || !is_digits(whole) || !is_digits(fraction) || (s.contains('.') && fraction.is_empty()) {
+        return Err(Error::InvalidInput(format!("Malformed amount: {:?}", s)));
+    }
+    if fraction.len() > decimals as usize {
+        return Err(Error::InvalidInput(format!(
+            "Amount {:?} has more than {} fractional digits",
+            s, decimals
+        )));
+    }
+    let overflow = || Error::InvalidInput(format!("Amount {:?} overflows u64", s));
+    let mut raw: u64 = 0;
+    let padding = decimals as usize - fraction.len();
+    for digit in whole.bytes().chain(fraction.bytes()).chain(std::iter::repeat_n(b'0', padding)) {
+        raw = raw
+            .checked_mul(10)
+            .and_then(|r| r.checked_add((digit - b'0') as u64))
+            .ok_or_else(overflow)?;
+    }
+    Ok(raw)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[test]
+    fn test_format_amount() {
+        assert_eq!(format_amount(123456, 3).unwrap(), "123.456");
+        assert_eq!(format_amount(5, 3).unwrap(), "0.005");
+        assert_eq!(format_amount(1000, 2).unwrap(), "10.00");
+        assert_eq!(format_amount(42, 0).unwrap(), "42");
+        assert_eq!(format_amount(u64::MAX, 19).unwrap(), "1.8446744073709551615");
+    }
+    #[test]
+    fn test_decimals_limit_shared() {
+        for decimals in [MAX_DECIMALS + 1, 40, u8::MAX] {
+            assert!(format_amount(7, decimals).is_err());
+            assert!(parse_amount("7", decimals).is_err());
+        }
+    }
+    #[test]
+    fn test_parse_round_trip() {
+        for (raw, decimals) in [(123456, 3), (5, 3), (0, 8), (u64::MAX, 0), (u64::MAX, 19), (1000, 2)] {
+            assert_eq!(parse_amount(&format_amount(raw, decimals).unwrap(), decimals).unwrap(), raw);
+        }
+        for decimals in 0..=MAX_DECIMALS {
+            for raw in [0, 1, 10u64.pow(decimals as u32), u64::MAX] {
+                assert_eq!(parse_amount(&format_amount(raw, decimals).unwrap(), decimals).unwrap(), raw);
+            }
+        }
+        assert_eq!(parse_amount("1.5", 8).unwrap(), 150_000_000);
+        assert_eq!(parse_amount("7", 2).unwrap(), 700);
+    }
+    #[test]
+    fn test_parse_rejects_excess_precision() {
+        assert!(parse_amount("1.234", 2).is_err());
+        assert!(parse_amount("1.2", 0).is_err());
+    }
+    #[test]
+    fn test_parse_rejects_overflow() {
+        assert!(parse_amount("18446744073709551616", 0).is_err());
+        assert!(parse_amount("184467440737095516.16", 2).is_err());
+        assert_eq!(parse_amount("1844674407370955.1615", 4).unwrap(), u64::MAX);
+        assert!(parse_amount("1", 20).is_err());
+    }
+    #[test]
+    fn test_parse_rejects_malformed() {
+        for s in ["", ".", "1.", ".5", "-1", "+1", "1.2.3", "1,5", " 1"] {
+            assert!(parse_amount(s, 3).is_err(), "{:?}", s);
+        }
+    }
+}
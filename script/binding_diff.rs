@@ -0,0 +1,211 @@
+// Reconstruction Diff - debugging aid for hashOutputs mismatches
+//
+// The guard rebuilds hashOutputs as double_sha256(app_outputs || change_outputs)
+// and OP_EQUALVERIFYs it against the preimage. When that fails the script gives
+// no insight into which output differs; this module reproduces the exact
+// serialization off-chain and reports where it deviates.
+
+use crate::ghost::crypto::double_sha256;
+use super::{read_varint, take_bytes, to_hex, SighashPreimage, varint};
+
+/// Number of bytes kept at each end of a hex snippet
+const SNIPPET_BYTES: usize = 16;
+
+/// A single deviation between the supplied outputs and a reference
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum OutputDeviation {
+    /// Output count differs from the count that reproduces hashOutputs
+    Count { expected: usize, actual: usize },
+    /// The value of output `index` differs
+    Value { index: usize, expected: u64, actual: u64 },
+    /// The locking script of output `index` differs
+    Script { index: usize, expected_hex: String, actual_hex: String },
+}
+
+/// Result of comparing reconstructed outputs against a preimage
+#[derive(Clone, Debug)]
+pub struct ReconstructionDiff {
+    /// hashOutputs committed in the preimage
+    pub expected_hash: [u8; 32],
+    /// hashOutputs computed from the supplied outputs
+    pub computed_hash: [u8; 32],
+    /// Outputs in serialization order (app first, then change)
+    pub outputs: Vec<(u64, Vec<u8>)>,
+    /// Hex snippet of the serialized outputs blob
+    pub serialized_hex: String,
+    /// Deviations found without a reference (e.g. trailing extra outputs)
+    pub deviations: Vec<OutputDeviation>,
+}
+
+impl ReconstructionDiff {
+    pub fn is_match(&self) -> bool {
+        self.expected_hash == self.computed_hash
+    }
+
+    /// Align the supplied outputs against a known-good reference list
+    /// and report every index whose value or script deviates
+    pub fn align(&self, reference: &[(u64, Vec<u8>)]) -> Vec<OutputDeviation> {
+        let mut deviations = Vec::new();
+        if reference.len() != self.outputs.len() {
+            deviations.push(OutputDeviation::Count {
+                expected: reference.len(),
+                actual: self.outputs.len(),
+            });
+        }
+        for (index, ((exp_value, exp_script), (value, script))) in
+            reference.iter().zip(self.outputs.iter()).enumerate()
+        {
+            if exp_value != value {
+                deviations.push(OutputDeviation::Value {
+                    index,
+                    expected: *exp_value,
+                    actual: *value,
+                });
+            }
+            if exp_script != script {
+                deviations.push(OutputDeviation::Script {
+                    index,
+                    expected_hex: hex_snippet(exp_script),
+                    actual_hex: hex_snippet(script),
+                });
+            }
+        }
+        deviations
+    }
+}
+
+/// Serialize outputs exactly as the guard does: value (8 LE) || varint(len) || script
+pub fn serialize_outputs(outputs: &[(u64, Vec<u8>)]) -> Vec<u8> {
+    let mut bytes = Vec::new();
+    for (value, script) in outputs {
+        bytes.extend_from_slice(&value.to_le_bytes());
+        bytes.extend(varint(script.len()));
+        bytes.extend_from_slice(script);
+    }
+    bytes
+}
+
+/// Inverse of `serialize_outputs`; `None` if a frame is truncated
+pub fn parse_outputs(mut bytes: &[u8]) -> Option<Vec<(u64, Vec<u8>)>> {
+    let mut outputs = Vec::new();
+    while !bytes.is_empty() {
+        let value = u64::from_le_bytes(take_bytes(&mut bytes, 8)?.try_into().ok()?);
+        let len = usize::try_from(read_varint(&mut bytes)?).ok()?;
+        outputs.push((value, take_bytes(&mut bytes, len)?.to_vec()));
+    }
+    Some(outputs)
+}
+
+/// Reconstruct hashOutputs from the supplied outputs and compare it against
+/// the preimage. When the hashes differ, every shorter prefix of the outputs
+/// is tried so that trailing extra outputs are reported as a count mismatch.
+pub fn diff_hash_outputs(
+    expected_preimage: &SighashPreimage,
+    app: &[(u64, Vec<u8>)],
+    change: &[(u64, Vec<u8>)],
+) -> ReconstructionDiff {
+    let outputs: Vec<(u64, Vec<u8>)> = app.iter().chain(change.iter()).cloned().collect();
+    let serialized = serialize_outputs(&outputs);
+    let computed_hash = double_sha256(&serialized);
+    let expected_hash = expected_preimage.hash_outputs;
+
+    let mut deviations = Vec::new();
+    if computed_hash != expected_hash {
+        let prefix = (0..outputs.len())
+            .rev()
+            .find(|&n| double_sha256(&serialize_outputs(&outputs[..n])) == expected_hash);
+        if let Some(n) = prefix {
+            deviations.push(OutputDeviation::Count {
+                expected: n,
+                actual: outputs.len(),
+            });
+        }
+    }
+
+    ReconstructionDiff {
+        expected_hash,
+        computed_hash,
+        outputs,
+        serialized_hex: hex_snippet(&serialized),
+        deviations,
+    }
+}
+
+fn hex_snippet(bytes: &[u8]) -> String {
+    if bytes.len() <= SNIPPET_BYTES * 2 {
+        return to_hex(bytes);
+    }
+    format!(
+        "{}..{} ({} bytes)",
+        to_hex(&bytes[..SNIPPET_BYTES]),
+        to_hex(&bytes[bytes.len() - SNIPPET_BYTES..]),
+        bytes.len()
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ghost::script::SighashPreimageBuilder;
+
+    fn p2pkh(tag: u8) -> Vec<u8> {
+        let mut script = vec![0x76, 0xa9, 20];
+        script.extend([tag; 20]);
+        script.extend([0x88, 0xac]);
+        script
+    }
+
+    fn preimage_for(outputs: &[(u64, Vec<u8>)]) -> SighashPreimage {
+        SighashPreimageBuilder::new().hash_outputs(double_sha256(&serialize_outputs(outputs))).build()
+    }
+
+    type Outputs = Vec<(u64, Vec<u8>)>;
+
+    fn reference() -> (Outputs, Outputs) {
+        (vec![(1000, p2pkh(1))], vec![(250, p2pkh(2))])
+    }
+
+    #[test]
+    fn test_matching_outputs() {
+        let (app, change) = reference();
+        let all: Vec<_> = app.iter().chain(change.iter()).cloned().collect();
+        let diff = diff_hash_outputs(&preimage_for(&all), &app, &change);
+        assert!(diff.is_match());
+        assert!(diff.deviations.is_empty());
+        assert_eq!(parse_outputs(&serialize_outputs(&all)), Some(all.clone()));
+        assert_eq!(parse_outputs(&serialize_outputs(&all)[..20]), None);
+    }
+
+    #[test]
+    fn test_wrong_amount() {
+        let (app, change) = reference();
+        let all: Vec<_> = app.iter().chain(change.iter()).cloned().collect();
+        let bad_change = vec![(251, p2pkh(2))];
+        let diff = diff_hash_outputs(&preimage_for(&all), &app, &bad_change);
+        assert!(!diff.is_match());
+        assert_eq!(diff.align(&all), vec![OutputDeviation::Value { index: 1, expected: 250, actual: 251 }]);
+    }
+
+    #[test]
+    fn test_wrong_script() {
+        let (app, change) = reference();
+        let all: Vec<_> = app.iter().chain(change.iter()).cloned().collect();
+        let bad_app = vec![(1000, p2pkh(9))];
+        let diff = diff_hash_outputs(&preimage_for(&all), &bad_app, &change);
+        assert!(!diff.is_match());
+        let deviations = diff.align(&all);
+        assert_eq!(deviations.len(), 1);
+        assert!(matches!(deviations[0], OutputDeviation::Script { index: 0, .. }));
+    }
+
+    #[test]
+    fn test_extra_output() {
+        let (app, change) = reference();
+        let all: Vec<_> = app.iter().chain(change.iter()).cloned().collect();
+        let mut extra_change = change.clone();
+        extra_change.push((1, vec![0x6a]));
+        let diff = diff_hash_outputs(&preimage_for(&all), &app, &extra_change);
+        assert!(!diff.is_match());
+        assert_eq!(diff.deviations, vec![OutputDeviation::Count { expected: 2, actual: 3 }]);
+    }
+}
@@ -0,0 +1,283 @@
+// Mullet Script Builder
+// Fluent entry point for production outputs: picks the guard, attaches the
+// tail and optional identity/metadata commitments, then validates the
+// assembled locking script against size limits and relay policy.
+//
+// HEADER:
+// app_id, version and the metadata commitment are embedded ahead of the
+// guard as `<data> OP_DROP` pairs, so they are covered by the script hash
+// without touching the stack the guard sees.
+
+use crate::ghost::crypto::sha256;
+use crate::ghost::{size, Error};
+use super::opcodes::*;
+use super::{push_bytes, Guard, GuardConfig, MulletScript, PolicyLimits, ScriptErrorKind, Tail, TailBinding, UniversalGuard, WitnessLayout};
+
+/// Data carried in a companion `OP_FALSE OP_RETURN` output
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct OpReturnData {
+    pub pushes: Vec<Vec<u8>>,
+}
+
+impl OpReturnData {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    pub fn push(mut self, data: impl Into<Vec<u8>>) -> Self {
+        self.pushes.push(data.into());
+        self
+    }
+    /// Output script: `OP_FALSE OP_RETURN <push>...`
+    pub fn to_script(&self) -> Vec<u8> {
+        let mut script = vec![OP_FALSE, OP_RETURN];
+        for data in &self.pushes {
+            script.extend(push_bytes(data));
+        }
+        script
+    }
+    /// Value committed in the locking script header
+    pub fn commitment(&self) -> [u8; 32] {
+        sha256(&self.to_script())
+    }
+}
+
+#[derive(Debug)]
+pub enum BuildError {
+    MissingTail,
+    /// `guard_config` failed `UniversalGuard::validate`
+    InvalidGuardConfig(ScriptErrorKind),
+    /// `ScriptTooLarge` for a guard over GUARD_MAX, `HintCountMismatch` for
+    /// IPA, then Poseidon, hint rounds the witness layout disagrees with
+    Script(ScriptErrorKind),
+    /// Assembled locking script rejected by relay policy
+    Policy(Error),
+    /// `output_value_cap` set alongside a prebuilt guard, which cannot take it
+    OutputCapWithPrebuiltGuard,
+}
+
+#[derive(Default)]
+pub struct MulletScriptBuilder {
+    guard: Option<Guard>,
+    guard_config: Option<GuardConfig>,
+    tail: Option<Box<dyn Tail>>,
+    app_id: Option<[u8; 32]>,
+    version: Option<u8>,
+    metadata: Option<OpReturnData>,
+    policy: Option<PolicyLimits>,
+    witness_layout: Option<WitnessLayout>,
+    output_value_cap: Option<u64>,
+}
+
+impl MulletScriptBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    /// Use a prebuilt guard; defaults to `Guard::universal()`
+    pub fn guard(mut self, guard: Guard) -> Self {
+        self.guard = Some(guard);
+        self.guard_config = None;
+        self
+    }
+    /// Build the guard from `config` with `UniversalGuard`
+    pub fn guard_config(mut self, config: GuardConfig) -> Self {
+        self.guard = None;
+        self.guard_config = Some(config);
+        self
+    }
+    pub fn tail(mut self, tail: impl Tail + 'static) -> Self {
+        self.tail = Some(Box::new(tail));
+        self
+    }
+    pub fn app_id(mut self, app_id: [u8; 32]) -> Self {
+        self.app_id = Some(app_id);
+        self
+    }
+    pub fn version(mut self, version: u8) -> Self {
+        self.version = Some(version);
+        self
+    }
+    pub fn metadata(mut self, metadata: OpReturnData) -> Self {
+        self.metadata = Some(metadata);
+        self
+    }
+    /// Relay policy to validate against; defaults to mainnet limits
+    pub fn policy(mut self, policy: &PolicyLimits) -> Self {
+        self.policy = Some(policy.clone());
+        self
+    }
+    /// Witness shape the spender will provide, checked against the guard's hints
+    pub fn witness_layout(mut self, layout: WitnessLayout) -> Self {
+        self.witness_layout = Some(layout);
+        self
+    }
+    /// Cap the total value the spend may pay out. Applied to the guard
+    /// config when one is set; the default guard becomes
+    /// `Guard::paymaster_with_output_cap`.
+    pub fn output_value_cap(mut self, max_total: u64) -> Self {
+        self.output_value_cap = Some(max_total);
+        self
+    }
+    pub fn build(self) -> Result<MulletScript, BuildError> {
+        let tail = self.tail.ok_or(BuildError::MissingTail)?;
+        let guard = match &self.guard_config {
+            Some(config) => {
+                let mut config = config.clone();
+                if let Some(max_total) = self.output_value_cap {
+                    config = config.with_output_value_cap(max_total);
+                }
+                let mut universal = UniversalGuard::new(config.clone());
+                if config.bind_tail {
+                    universal = universal.with_tail_binding(TailBinding::new(&tail.locking_script()));
+                }
+                universal.validate().map_err(BuildError::InvalidGuardConfig)?;
+                check_hints(&config, self.witness_layout.as_ref())?;
+                Guard::from_universal(&universal).map_err(BuildError::InvalidGuardConfig)?
+            }
+            None => match (self.guard, self.output_value_cap) {
+                (Some(_), Some(_)) => return Err(BuildError::OutputCapWithPrebuiltGuard),
+                (Some(guard), None) => guard,
+                (None, Some(max_total)) => Guard::paymaster_with_output_cap(max_total),
+                (None, None) => Guard::universal(),
+            },
+        };
+        if !guard.is_valid_size() {
+            return Err(BuildError::Script(ScriptErrorKind::ScriptTooLarge { size: guard.size(), max: size::GUARD_MAX }));
+        }
+        let mut header = Vec::new();
+        if let Some(app_id) = self.app_id {
+            header.extend(push_bytes(&app_id));
+            header.push(OP_DROP);
+        }
+        if let Some(version) = self.version {
+            header.extend(push_number(version as i64));
+            header.push(OP_DROP);
+        }
+        if let Some(metadata) = &self.metadata {
+            header.extend(push_bytes(&metadata.commitment()));
+            header.push(OP_DROP);
+        }
+        let script = MulletScript::from_parts(header, guard, tail);
+        self.policy
+            .unwrap_or_default()
+            .check_script(&script.locking_script())
+            .map_err(BuildError::Policy)?;
+        Ok(script)
+    }
+}
+
+fn check_hints(config: &GuardConfig, layout: Option<&WitnessLayout>) -> Result<(), BuildError> {
+    let Some((ipa, poseidon)) = layout.and_then(|l| l.hints) else {
+        return Ok(());
+    };
+    let guard = [
+        (config.ipa_hints.as_ref().map_or(ipa, |h| h.num_rounds()), ipa),
+        (config.poseidon_hints.as_ref().map_or(poseidon, |h| h.num_rounds()), poseidon),
+    ];
+    match guard.into_iter().find(|(expected, got)| expected != got) {
+        Some((expected, got)) => Err(BuildError::Script(ScriptErrorKind::HintCountMismatch { expected, got })),
+        None => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ghost::script::{EcdsaTail, IpaHints, PoseidonHints, TailWitnessLayout};
+    fn tail() -> EcdsaTail {
+        EcdsaTail::from_pubkey_hash(&[0x11; 20])
+    }
+    #[test]
+    fn test_missing_tail() {
+        let err = MulletScript::builder().guard(Guard::minimal()).build().unwrap_err();
+        assert!(matches!(err, BuildError::MissingTail));
+    }
+    #[test]
+    fn test_matches_direct_constructors() {
+        let built = MulletScript::builder().tail(tail()).build().unwrap();
+        assert_eq!(built.locking_script(), MulletScript::universal(tail()).locking_script());
+        let built = MulletScript::builder().guard(Guard::minimal()).tail(tail()).build().unwrap();
+        assert_eq!(built.locking_script(), MulletScript::minimal(tail()).locking_script());
+        let config = GuardConfig::new(1, 1).paymaster(1000);
+        let built = MulletScript::builder().guard_config(config.clone()).tail(tail()).build().unwrap();
+        assert_eq!(built.locking_script(), MulletScript::new(Guard::from_config(&config).unwrap(), tail()).locking_script());
+        assert_eq!(built.size(), built.locking_script().len());
+    }
+    #[test]
+    fn test_header_commitments() {
+        let metadata = OpReturnData::new().push(b"app".to_vec());
+        let built = MulletScript::builder()
+            .guard(Guard::minimal())
+            .tail(tail())
+            .app_id([0x42; 32])
+            .version(2)
+            .metadata(metadata.clone())
+            .build()
+            .unwrap();
+        let plain = MulletScript::minimal(tail()).locking_script();
+        let script = built.locking_script();
+        assert!(script.ends_with(&plain));
+        assert_eq!(script.len(), plain.len() + (33 + 1) + (1 + 1) + (33 + 1));
+        let commitment = metadata.commitment();
+        assert!(script.windows(32).any(|w| w == commitment));
+        assert_eq!(built.size(), script.len());
+    }
+    #[test]
+    fn test_invalid_guard_config() {
+        let err = MulletScript::builder().guard_config(GuardConfig::new(0, 1)).tail(tail()).build().unwrap_err();
+        assert!(matches!(err, BuildError::InvalidGuardConfig(ScriptErrorKind::InvalidGuardConfig { .. })));
+    }
+    #[test]
+    #[cfg(feature = "poseidon-script")]
+    fn test_oversized_guard() {
+        let guard = Guard::universal_full_unsized(3);
+        let err = MulletScript::builder().guard(guard.clone()).tail(tail()).build().unwrap_err();
+        assert!(matches!(
+            err,
+            BuildError::Script(ScriptErrorKind::ScriptTooLarge { size, max }) if size == guard.size() && max == size::GUARD_MAX
+        ));
+    }
+    #[test]
+    fn test_hint_mismatch() {
+        let config = GuardConfig::new(1, 1)
+            .paymaster(1000)
+            .with_ipa_hints(IpaHints::placeholder(10))
+            .with_poseidon_hints(PoseidonHints::placeholder(4));
+        let rounds = config.ipa_rounds();
+        let matching = WitnessLayout::fallback(rounds, 4, TailWitnessLayout::Ecdsa);
+        assert!(MulletScript::builder().guard_config(config.clone()).tail(tail()).witness_layout(matching).build().is_ok());
+        let wrong = WitnessLayout::fallback(rounds + 1, 4, TailWitnessLayout::Ecdsa);
+        let err = MulletScript::builder().guard_config(config).tail(tail()).witness_layout(wrong).build().unwrap_err();
+        assert!(matches!(
+            err,
+            BuildError::Script(ScriptErrorKind::HintCountMismatch { expected, got }) if expected == rounds && got == rounds + 1
+        ));
+        let wrong = WitnessLayout::fallback(rounds, 5, TailWitnessLayout::Ecdsa);
+        let config = GuardConfig::new(1, 1).paymaster(1000).with_poseidon_hints(PoseidonHints::placeholder(4));
+        let err = MulletScript::builder().guard_config(config).tail(tail()).witness_layout(wrong).build().unwrap_err();
+        assert!(matches!(err, BuildError::Script(ScriptErrorKind::HintCountMismatch { expected: 4, got: 5 })));
+    }
+    #[test]
+    fn test_bind_tail_embeds_tail_hash() {
+        let config = GuardConfig::new(1, 1).paymaster(1000).bind_tail(true);
+        let built = MulletScript::builder().guard_config(config).tail(tail()).build().unwrap();
+        let hash = built.guard_bound_tail_hash();
+        assert!(built.guard().to_bytes().windows(32).any(|w| w == hash));
+    }
+    #[test]
+    fn test_output_value_cap_threaded() {
+        let config = GuardConfig::new(1, 1).paymaster(1000);
+        let built = MulletScript::builder().guard_config(config.clone()).tail(tail()).output_value_cap(10_000).build().unwrap();
+        let expected = Guard::from_config(&config.with_output_value_cap(10_000)).unwrap();
+        assert_eq!(built.guard().to_bytes(), expected.to_bytes());
+        let built = MulletScript::builder().tail(tail()).output_value_cap(10_000).build().unwrap();
+        assert_eq!(built.guard().to_bytes(), Guard::paymaster_with_output_cap(10_000).to_bytes());
+        let err = MulletScript::builder().guard(Guard::minimal()).tail(tail()).output_value_cap(10_000).build().unwrap_err();
+        assert!(matches!(err, BuildError::OutputCapWithPrebuiltGuard));
+    }
+    #[test]
+    fn test_policy_rejects_oversized_script() {
+        let tight = PolicyLimits { max_script_size: 10, max_push_size: 10, max_tx_size: 100 };
+        let err = MulletScript::builder().guard(Guard::minimal()).tail(tail()).policy(&tight).build().unwrap_err();
+        assert!(matches!(err, BuildError::Policy(_)));
+    }
+}
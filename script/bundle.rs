@@ -0,0 +1,176 @@
+// Witness Bundles
+// Compact binary hand-off from the prover service to the wallet: everything
+// needed to assemble a scriptSig except the preimage and signature.
+//
+// FORMAT (v2, per-intent Poseidon hints):
+//   version (1) || config_fingerprint (32)
+//   || varint(len) ipa_hints || varint(len) poseidon_hints
+//   || varint(len) app_fields (32 bytes each) || varint(len) proof_bytes
+//   || checksum (first 4 bytes of double_sha256 over everything before it)
+// Unknown versions, bad checksums, malformed sections and trailing bytes are
+// rejected; decoding never panics on arbitrary input.
+
+use crate::ghost::crypto::{double_sha256, Fp, FieldExt};
+use super::field::bytes_to_fp;
+use super::{read_varint, varint, GuardConfig, IpaHints, PoseidonHints};
+
+pub const BUNDLE_VERSION: u8 = 2;
+const CHECKSUM_LEN: usize = 4;
+
+#[derive(Clone, Debug)]
+pub struct WitnessBundle {
+    pub version: u8,
+    /// `GuardConfig::fingerprint` of the guard the hints were produced for
+    pub config_fingerprint: [u8; 32],
+    pub ipa_hints: IpaHints,
+    pub poseidon_hints: PoseidonHints,
+    pub app_fields: Vec<Fp>,
+    pub proof_bytes: Vec<u8>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum BundleError {
+    Truncated,
+    UnknownVersion(u8),
+    BadChecksum,
+    MalformedSection(&'static str),
+    TrailingBytes,
+}
+
+impl WitnessBundle {
+    pub fn new(
+        config: &GuardConfig,
+        ipa_hints: IpaHints,
+        poseidon_hints: PoseidonHints,
+        app_fields: Vec<Fp>,
+        proof_bytes: Vec<u8>,
+    ) -> Self {
+        Self {
+            version: BUNDLE_VERSION,
+            config_fingerprint: config.fingerprint(),
+            ipa_hints,
+            poseidon_hints,
+            app_fields,
+            proof_bytes,
+        }
+    }
+    pub fn matches_config(&self, config: &GuardConfig) -> bool {
+        self.config_fingerprint == config.fingerprint()
+    }
+    pub fn encode(&self) -> Vec<u8> {
+        let mut bytes = vec![self.version];
+        bytes.extend(&self.config_fingerprint);
+        let app_fields: Vec<u8> = self.app_fields.iter().flat_map(|f| f.to_bytes()).collect();
+        for section in [
+            self.ipa_hints.to_bytes(),
+            self.poseidon_hints.to_bytes(),
+            app_fields,
+            self.proof_bytes.clone(),
+        ] {
+            bytes.extend(varint(section.len()));
+            bytes.extend(section);
+        }
+        let checksum = double_sha256(&bytes);
+        bytes.extend(&checksum[..CHECKSUM_LEN]);
+        bytes
+    }
+    pub fn decode(bytes: &[u8]) -> Result<Self, BundleError> {
+        let body_len = bytes.len().checked_sub(CHECKSUM_LEN).ok_or(BundleError::Truncated)?;
+        let (body, checksum) = bytes.split_at(body_len);
+        let mut reader = body;
+        let version = take(&mut reader, 1)?[0];
+        if version != BUNDLE_VERSION {
+            return Err(BundleError::UnknownVersion(version));
+        }
+        if double_sha256(body)[..CHECKSUM_LEN] != *checksum {
+            return Err(BundleError::BadChecksum);
+        }
+        let config_fingerprint = take(&mut reader, 32)?.try_into().expect("32-byte slice");
+        let ipa_hints = IpaHints::from_bytes(section(&mut reader)?)
+            .map_err(|_| BundleError::MalformedSection("ipa_hints"))?;
+        let poseidon_hints = PoseidonHints::from_bytes(section(&mut reader)?)
+            .map_err(|_| BundleError::MalformedSection("poseidon_hints"))?;
+        let app_fields = section(&mut reader)?;
+        if app_fields.len() % 32 != 0 {
+            return Err(BundleError::MalformedSection("app_fields"));
+        }
+        let app_fields = app_fields
+            .chunks(32)
+            .map(|c| bytes_to_fp(c.try_into().expect("32-byte chunk")))
+            .collect::<Option<Vec<_>>>()
+            .ok_or(BundleError::MalformedSection("app_fields"))?;
+        let proof_bytes = section(&mut reader)?.to_vec();
+        if !reader.is_empty() {
+            return Err(BundleError::TrailingBytes);
+        }
+        Ok(Self { version, config_fingerprint, ipa_hints, poseidon_hints, app_fields, proof_bytes })
+    }
+}
+
+fn take<'a>(reader: &mut &'a [u8], n: usize) -> Result<&'a [u8], BundleError> {
+    if reader.len() < n {
+        return Err(BundleError::Truncated);
+    }
+    let (head, rest) = reader.split_at(n);
+    *reader = rest;
+    Ok(head)
+}
+
+fn section<'a>(reader: &mut &'a [u8]) -> Result<&'a [u8], BundleError> {
+    let len = read_varint(reader).ok_or(BundleError::Truncated)?;
+    let len = usize::try_from(len).map_err(|_| BundleError::Truncated)?;
+    take(reader, len)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::Rng;
+    fn bundle() -> WitnessBundle {
+        WitnessBundle::new(
+            &GuardConfig::new(1, 2).paymaster(1000),
+            IpaHints::placeholder(10),
+            PoseidonHints::placeholder(4).with_output(Fp::from_u64(77)),
+            vec![Fp::from_u64(1), Fp::from_u64(2)],
+            vec![0xAB; 400],
+        )
+    }
+    #[test]
+    fn test_round_trip() {
+        let original = bundle();
+        let encoded = original.encode();
+        let decoded = WitnessBundle::decode(&encoded).unwrap();
+        assert_eq!(decoded.encode(), encoded);
+        assert_eq!(decoded.app_fields, original.app_fields);
+        assert_eq!(decoded.ipa_hints.num_rounds(), 10);
+        assert_eq!(decoded.poseidon_hints.outputs(), vec![Fp::from_u64(77)]);
+        assert!(decoded.matches_config(&GuardConfig::new(1, 2).paymaster(1000)));
+        assert!(!decoded.matches_config(&GuardConfig::new(1, 3).paymaster(1000)));
+    }
+    #[test]
+    fn test_rejects_unknown_version_and_bad_checksum() {
+        let mut encoded = bundle().encode();
+        let last = encoded.len() - 1;
+        encoded[last] ^= 1;
+        assert_eq!(WitnessBundle::decode(&encoded).unwrap_err(), BundleError::BadChecksum);
+        let mut other = bundle();
+        other.version = BUNDLE_VERSION + 1;
+        assert_eq!(WitnessBundle::decode(&other.encode()).unwrap_err(), BundleError::UnknownVersion(BUNDLE_VERSION + 1));
+    }
+    #[test]
+    fn test_truncated_and_corrupted_never_panic() {
+        let encoded = bundle().encode();
+        for len in 0..encoded.len() {
+            assert!(WitnessBundle::decode(&encoded[..len]).is_err());
+        }
+        let mut rng = rand::thread_rng();
+        for _ in 0..500 {
+            let mut corrupted = encoded.clone();
+            let i = rng.gen_range(0..corrupted.len());
+            corrupted[i] = rng.gen();
+            let _ = WitnessBundle::decode(&corrupted);
+            let junk: Vec<u8> = (0..rng.gen_range(0..64)).map(|_| rng.gen()).collect();
+            let _ = WitnessBundle::decode(&junk);
+        }
+    }
+}
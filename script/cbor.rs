@@ -0,0 +1,566 @@
+// Canonical CBOR
+// Archival encoding of hints, step witnesses and witness bundles. The wire
+// formats (`to_bytes`, `WitnessBundle::encode`) may change between releases;
+// these bytes may not, so the witness of a historical spend can be rebuilt
+// from an archive years later.
+//
+// ENCODING:
+// RFC 8949 core deterministic encoding, restricted to unsigned integers,
+// byte strings and definite-length arrays. Heads use the shortest argument
+// form and there are no maps, so there is no key order to get wrong. Decoding
+// rejects over-long heads, indefinite lengths, other major types,
+// non-canonical field elements and trailing bytes: every document
+// `from_cbor` accepts re-encodes to the same bytes. Hand-rolled so the
+// `cbor` feature pulls in no dependency.
+//
+// FORMAT (v1): [CBOR_FORMAT_VERSION, kind, body]
+//   IpaHints (1):       [[[l_u, r_u_inv, c_next, challenge]...], final_scalar, final_commitment]
+//   PoseidonHints (2):  [[[[after_sbox x3, after_mds x3]...], output]...]
+//   IPAStepWitness (3): [operator_binding, [public_input...], [[lx, ly]...], [[rx, ry]...],
+//                        a_scalar, [b_scalar?], [new_app_state?], next_transcript_hash]
+//   WitnessBundle (4):  [version, config_fingerprint, IpaHints body, PoseidonHints body,
+//                        [app_field...], proof_bytes]
+// Points, field elements and hashes are byte strings, Fp as its 32-byte
+// little-endian repr; an optional value is an array of zero or one item.
+
+use crate::ghost::crypto::Fp;
+use super::field::{bytes_to_fp, fp_to_bytes, FieldElement};
+use super::hints::{FoldingRound, IntentHashHints, PoseidonRoundHint};
+use super::point::CompressedPoint;
+use super::{IPAStepWitness, IpaHints, PoseidonHints, WitnessBundle};
+
+pub const CBOR_FORMAT_VERSION: u64 = 1;
+const MAJOR_UNSIGNED: u8 = 0;
+const MAJOR_BYTES: u8 = 2;
+const MAJOR_ARRAY: u8 = 4;
+const KIND_IPA_HINTS: u64 = 1;
+const KIND_POSEIDON_HINTS: u64 = 2;
+const KIND_STEP_WITNESS: u64 = 3;
+const KIND_WITNESS_BUNDLE: u64 = 4;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum CborError {
+    Truncated,
+    /// Over-long or indefinite-length head at `offset`
+    NonCanonical { offset: usize },
+    UnexpectedType { offset: usize, expected: u8, found: u8 },
+    UnknownVersion(u64),
+    WrongKind { expected: u64, found: u64 },
+    Malformed(&'static str),
+    TrailingBytes,
+}
+
+impl IpaHints {
+    /// Byte-stable archival encoding; equal hints give equal bytes in every release
+    pub fn to_canonical_cbor(&self) -> Vec<u8> {
+        document(KIND_IPA_HINTS, self)
+    }
+    /// Inverse of `to_canonical_cbor`; rejects any other encoding of the same hints
+    pub fn from_cbor(bytes: &[u8]) -> Result<Self, CborError> {
+        from_document(KIND_IPA_HINTS, bytes)
+    }
+}
+
+impl PoseidonHints {
+    /// Byte-stable archival encoding; equal hints give equal bytes in every release
+    pub fn to_canonical_cbor(&self) -> Vec<u8> {
+        document(KIND_POSEIDON_HINTS, self)
+    }
+    /// Inverse of `to_canonical_cbor`; rejects any other encoding of the same hints
+    pub fn from_cbor(bytes: &[u8]) -> Result<Self, CborError> {
+        from_document(KIND_POSEIDON_HINTS, bytes)
+    }
+}
+
+impl IPAStepWitness {
+    /// Byte-stable archival encoding; equal witnesses give equal bytes in every release
+    pub fn to_canonical_cbor(&self) -> Vec<u8> {
+        document(KIND_STEP_WITNESS, self)
+    }
+    /// Inverse of `to_canonical_cbor`; rejects any other encoding of the same witness
+    pub fn from_cbor(bytes: &[u8]) -> Result<Self, CborError> {
+        from_document(KIND_STEP_WITNESS, bytes)
+    }
+}
+
+impl WitnessBundle {
+    /// Byte-stable archival encoding; equal bundles give equal bytes in every release
+    pub fn to_canonical_cbor(&self) -> Vec<u8> {
+        document(KIND_WITNESS_BUNDLE, self)
+    }
+    /// Inverse of `to_canonical_cbor`; rejects any other encoding of the same bundle
+    pub fn from_cbor(bytes: &[u8]) -> Result<Self, CborError> {
+        from_document(KIND_WITNESS_BUNDLE, bytes)
+    }
+}
+
+trait CborBody: Sized {
+    fn encode_body(&self, out: &mut Vec<u8>);
+    fn decode_body(reader: &mut Reader) -> Result<Self, CborError>;
+}
+
+fn document<T: CborBody>(kind: u64, value: &T) -> Vec<u8> {
+    let mut out = Vec::new();
+    write_array(&mut out, 3);
+    write_head(&mut out, MAJOR_UNSIGNED, CBOR_FORMAT_VERSION);
+    write_head(&mut out, MAJOR_UNSIGNED, kind);
+    value.encode_body(&mut out);
+    out
+}
+
+fn from_document<T: CborBody>(kind: u64, bytes: &[u8]) -> Result<T, CborError> {
+    let mut reader = Reader { bytes, pos: 0 };
+    reader.array_of(3, "document")?;
+    let version = reader.uint()?;
+    if version != CBOR_FORMAT_VERSION {
+        return Err(CborError::UnknownVersion(version));
+    }
+    let found = reader.uint()?;
+    if found != kind {
+        return Err(CborError::WrongKind { expected: kind, found });
+    }
+    let value = T::decode_body(&mut reader)?;
+    if reader.pos != bytes.len() {
+        return Err(CborError::TrailingBytes);
+    }
+    Ok(value)
+}
+
+impl CborBody for IpaHints {
+    fn encode_body(&self, out: &mut Vec<u8>) {
+        write_array(out, 3);
+        write_array(out, self.rounds.len());
+        for round in &self.rounds {
+            write_array(out, 4);
+            write_bytes(out, round.l_u.as_bytes());
+            write_bytes(out, round.r_u_inv.as_bytes());
+            write_bytes(out, round.c_next.as_bytes());
+            write_fp(out, &round.challenge);
+        }
+        write_fp(out, &self.final_scalar);
+        write_bytes(out, &self.final_commitment);
+    }
+    fn decode_body(reader: &mut Reader) -> Result<Self, CborError> {
+        reader.array_of(3, "ipa_hints")?;
+        let mut rounds = Vec::new();
+        for _ in 0..reader.array()? {
+            reader.array_of(4, "ipa_round")?;
+            let l_u = reader.point("l_u")?;
+            let r_u_inv = reader.point("r_u_inv")?;
+            let c_next = reader.point("c_next")?;
+            rounds.push(FoldingRound::new(l_u, r_u_inv, c_next, reader.fp("challenge")?));
+        }
+        let final_scalar = reader.fp("final_scalar")?;
+        let final_commitment = reader.fixed("final_commitment")?;
+        Ok(Self::new(rounds, final_scalar, final_commitment))
+    }
+}
+
+impl CborBody for PoseidonHints {
+    fn encode_body(&self, out: &mut Vec<u8>) {
+        write_array(out, self.intents.len());
+        for intent in &self.intents {
+            write_array(out, 2);
+            write_array(out, intent.rounds.len());
+            for round in &intent.rounds {
+                write_array(out, 6);
+                for elem in round.after_sbox.iter().chain(&round.after_mds) {
+                    write_fp(out, elem);
+                }
+            }
+            write_fp(out, &intent.output);
+        }
+    }
+    fn decode_body(reader: &mut Reader) -> Result<Self, CborError> {
+        let mut intents = Vec::new();
+        for _ in 0..reader.array()? {
+            reader.array_of(2, "intent")?;
+            let mut rounds = Vec::new();
+            for _ in 0..reader.array()? {
+                reader.array_of(6, "poseidon_round")?;
+                let mut elems = [Fp::zero(); 6];
+                for elem in &mut elems {
+                    *elem = reader.fp("poseidon_round")?;
+                }
+                rounds.push(PoseidonRoundHint::new([elems[0], elems[1], elems[2]], [elems[3], elems[4], elems[5]]));
+            }
+            intents.push(IntentHashHints::new(rounds, reader.fp("intent_output")?));
+        }
+        Ok(Self::from_intents(intents))
+    }
+}
+
+impl CborBody for IPAStepWitness {
+    fn encode_body(&self, out: &mut Vec<u8>) {
+        write_array(out, 8);
+        write_bytes(out, &self.operator_binding);
+        write_array(out, self.public_inputs.len());
+        for input in &self.public_inputs {
+            write_bytes(out, input);
+        }
+        for terms in [&self.l_terms, &self.r_terms] {
+            write_array(out, terms.len());
+            for [x, y] in terms {
+                write_array(out, 2);
+                write_bytes(out, x);
+                write_bytes(out, y);
+            }
+        }
+        write_bytes(out, &self.a_scalar);
+        for optional in [&self.b_scalar, &self.new_app_state] {
+            write_array(out, optional.is_some() as usize);
+            if let Some(value) = optional {
+                write_bytes(out, value);
+            }
+        }
+        write_bytes(out, &self.next_transcript_hash);
+    }
+    fn decode_body(reader: &mut Reader) -> Result<Self, CborError> {
+        reader.array_of(8, "step_witness")?;
+        let operator_binding = reader.fixed("operator_binding")?;
+        let mut public_inputs = Vec::new();
+        for _ in 0..reader.array()? {
+            public_inputs.push(reader.fixed("public_input")?);
+        }
+        let l_terms = reader.terms()?;
+        let r_terms = reader.terms()?;
+        let a_scalar = reader.fixed("a_scalar")?;
+        let b_scalar = reader.optional("b_scalar")?;
+        let new_app_state = reader.optional("new_app_state")?;
+        let next_transcript_hash = reader.fixed("next_transcript_hash")?;
+        Ok(Self {
+            operator_binding,
+            public_inputs,
+            l_terms,
+            r_terms,
+            a_scalar,
+            b_scalar,
+            new_app_state,
+            next_transcript_hash,
+        })
+    }
+}
+
+impl CborBody for WitnessBundle {
+    fn encode_body(&self, out: &mut Vec<u8>) {
+        write_array(out, 6);
+        write_head(out, MAJOR_UNSIGNED, self.version as u64);
+        write_bytes(out, &self.config_fingerprint);
+        self.ipa_hints.encode_body(out);
+        self.poseidon_hints.encode_body(out);
+        write_array(out, self.app_fields.len());
+        for field in &self.app_fields {
+            write_fp(out, field);
+        }
+        write_bytes(out, &self.proof_bytes);
+    }
+    fn decode_body(reader: &mut Reader) -> Result<Self, CborError> {
+        reader.array_of(6, "witness_bundle")?;
+        let version = u8::try_from(reader.uint()?).map_err(|_| CborError::Malformed("version"))?;
+        let config_fingerprint = reader.fixed("config_fingerprint")?;
+        let ipa_hints = IpaHints::decode_body(reader)?;
+        let poseidon_hints = PoseidonHints::decode_body(reader)?;
+        let mut app_fields = Vec::new();
+        for _ in 0..reader.array()? {
+            app_fields.push(reader.fp("app_field")?);
+        }
+        let proof_bytes = reader.bytes()?.to_vec();
+        Ok(Self { version, config_fingerprint, ipa_hints, poseidon_hints, app_fields, proof_bytes })
+    }
+}
+
+fn write_head(out: &mut Vec<u8>, major: u8, arg: u64) {
+    let initial = major << 5;
+    match arg {
+        0..=23 => out.push(initial | arg as u8),
+        24..=0xff => out.extend([initial | 24, arg as u8]),
+        0x100..=0xffff => {
+            out.push(initial | 25);
+            out.extend((arg as u16).to_be_bytes());
+        }
+        0x1_0000..=0xffff_ffff => {
+            out.push(initial | 26);
+            out.extend((arg as u32).to_be_bytes());
+        }
+        _ => {
+            out.push(initial | 27);
+            out.extend(arg.to_be_bytes());
+        }
+    }
+}
+
+fn write_array(out: &mut Vec<u8>, len: usize) {
+    write_head(out, MAJOR_ARRAY, len as u64);
+}
+
+fn write_bytes(out: &mut Vec<u8>, bytes: &[u8]) {
+    write_head(out, MAJOR_BYTES, bytes.len() as u64);
+    out.extend(bytes);
+}
+
+fn write_fp(out: &mut Vec<u8>, fp: &Fp) {
+    write_bytes(out, &fp_to_bytes(fp));
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn head(&mut self, major: u8) -> Result<u64, CborError> {
+        let offset = self.pos;
+        let initial = self.take(1)?[0];
+        if initial >> 5 != major {
+            return Err(CborError::UnexpectedType { offset, expected: major, found: initial >> 5 });
+        }
+        let (len, min) = match initial & 0x1f {
+            info @ 0..=23 => return Ok(info as u64),
+            24 => (1, 24),
+            25 => (2, 0x100),
+            26 => (4, 0x1_0000),
+            27 => (8, 0x1_0000_0000),
+            _ => return Err(CborError::NonCanonical { offset }),
+        };
+        let arg = self.take(len)?.iter().fold(0u64, |acc, b| (acc << 8) | u64::from(*b));
+        if arg < min {
+            return Err(CborError::NonCanonical { offset });
+        }
+        Ok(arg)
+    }
+    fn take(&mut self, n: usize) -> Result<&'a [u8], CborError> {
+        let end = self.pos.checked_add(n).filter(|end| *end <= self.bytes.len()).ok_or(CborError::Truncated)?;
+        let slice = &self.bytes[self.pos..end];
+        self.pos = end;
+        Ok(slice)
+    }
+    fn uint(&mut self) -> Result<u64, CborError> {
+        self.head(MAJOR_UNSIGNED)
+    }
+    // Lengths come from the input, so callers never preallocate from them
+    fn array(&mut self) -> Result<usize, CborError> {
+        usize::try_from(self.head(MAJOR_ARRAY)?).map_err(|_| CborError::Truncated)
+    }
+    fn array_of(&mut self, len: usize, what: &'static str) -> Result<(), CborError> {
+        if self.array()? != len {
+            return Err(CborError::Malformed(what));
+        }
+        Ok(())
+    }
+    fn bytes(&mut self) -> Result<&'a [u8], CborError> {
+        let len = usize::try_from(self.head(MAJOR_BYTES)?).map_err(|_| CborError::Truncated)?;
+        self.take(len)
+    }
+    fn fixed<const N: usize>(&mut self, what: &'static str) -> Result<[u8; N], CborError> {
+        self.bytes()?.try_into().map_err(|_| CborError::Malformed(what))
+    }
+    fn fp(&mut self, what: &'static str) -> Result<Fp, CborError> {
+        bytes_to_fp(&self.fixed(what)?).ok_or(CborError::Malformed(what))
+    }
+    fn point(&mut self, what: &'static str) -> Result<CompressedPoint, CborError> {
+        Ok(CompressedPoint::from(self.fixed::<33>(what)?))
+    }
+    fn optional(&mut self, what: &'static str) -> Result<Option<FieldElement>, CborError> {
+        match self.array()? {
+            0 => Ok(None),
+            1 => Ok(Some(self.fixed(what)?)),
+            _ => Err(CborError::Malformed(what)),
+        }
+    }
+    fn terms(&mut self) -> Result<Vec<[FieldElement; 2]>, CborError> {
+        let mut terms = Vec::new();
+        for _ in 0..self.array()? {
+            self.array_of(2, "lr_term")?;
+            terms.push([self.fixed("lr_term")?, self.fixed("lr_term")?]);
+        }
+        Ok(terms)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ghost::crypto::FieldExt;
+    use ff::Field;
+    use super::super::bundle::BUNDLE_VERSION;
+    use proptest::collection::vec;
+    use proptest::option;
+    use proptest::prelude::*;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+    fn golden(hex_file: &str) -> Vec<u8> {
+        hex::decode(hex_file.split_whitespace().collect::<String>()).expect("golden file is hex")
+    }
+    fn point(prefix: u8, fill: u8) -> CompressedPoint {
+        let mut bytes = [fill; 33];
+        bytes[0] = prefix;
+        bytes.into()
+    }
+    fn ipa_fixture() -> IpaHints {
+        let round = FoldingRound::new(point(0x02, 0x11), point(0x03, 0x22), CompressedPoint::IDENTITY, Fp::from_u64(5));
+        IpaHints::new(vec![round], Fp::from_u64(7), [0x02; 33])
+    }
+    fn poseidon_fixture() -> PoseidonHints {
+        let round = PoseidonRoundHint::new(
+            [Fp::from_u64(1), Fp::from_u64(2), Fp::from_u64(3)],
+            [Fp::from_u64(4), Fp::from_u64(5), Fp::from_u64(6)],
+        );
+        PoseidonHints::from_intents(vec![
+            IntentHashHints::new(vec![round], Fp::from_u64(77)),
+            IntentHashHints::new(Vec::new(), Fp::from_u64(78)),
+        ])
+    }
+    fn step_fixture() -> IPAStepWitness {
+        IPAStepWitness {
+            operator_binding: [0xAA; 32],
+            public_inputs: vec![[0x01; 32]],
+            l_terms: vec![[[0x02; 32], [0x03; 32]]],
+            r_terms: vec![[[0x04; 32], [0x05; 32]]],
+            a_scalar: [0x06; 32],
+            b_scalar: None,
+            new_app_state: Some([0x07; 32]),
+            next_transcript_hash: [0x08; 32],
+        }
+    }
+    fn bundle_fixture() -> WitnessBundle {
+        WitnessBundle {
+            version: BUNDLE_VERSION,
+            config_fingerprint: [0x11; 32],
+            ipa_hints: IpaHints::placeholder(1),
+            poseidon_hints: PoseidonHints::placeholder(1).with_output(Fp::from_u64(77)),
+            app_fields: vec![Fp::from_u64(1)],
+            proof_bytes: vec![0xAB; 4],
+        }
+    }
+    fn assert_step_eq(a: &IPAStepWitness, b: &IPAStepWitness) {
+        assert_eq!(a.operator_binding, b.operator_binding);
+        assert_eq!(a.public_inputs, b.public_inputs);
+        assert_eq!(a.l_terms, b.l_terms);
+        assert_eq!(a.r_terms, b.r_terms);
+        assert_eq!(a.a_scalar, b.a_scalar);
+        assert_eq!(a.b_scalar, b.b_scalar);
+        assert_eq!(a.new_app_state, b.new_app_state);
+        assert_eq!(a.next_transcript_hash, b.next_transcript_hash);
+    }
+    // A failure here is a format change: archived documents would no longer
+    // reproduce. Bump CBOR_FORMAT_VERSION instead of regenerating the files.
+    #[test]
+    fn test_golden_bytes() {
+        assert_eq!(ipa_fixture().to_canonical_cbor(), golden(include_str!("testdata/cbor/ipa_hints.hex")));
+        assert_eq!(poseidon_fixture().to_canonical_cbor(), golden(include_str!("testdata/cbor/poseidon_hints.hex")));
+        assert_eq!(step_fixture().to_canonical_cbor(), golden(include_str!("testdata/cbor/step_witness.hex")));
+        assert_eq!(bundle_fixture().to_canonical_cbor(), golden(include_str!("testdata/cbor/witness_bundle.hex")));
+    }
+    #[test]
+    fn test_golden_bytes_decode() {
+        let ipa = IpaHints::from_cbor(&golden(include_str!("testdata/cbor/ipa_hints.hex"))).unwrap();
+        assert_eq!(ipa.to_bytes(), ipa_fixture().to_bytes());
+        let poseidon = PoseidonHints::from_cbor(&golden(include_str!("testdata/cbor/poseidon_hints.hex"))).unwrap();
+        assert_eq!(poseidon.to_bytes(), poseidon_fixture().to_bytes());
+        let step = IPAStepWitness::from_cbor(&golden(include_str!("testdata/cbor/step_witness.hex"))).unwrap();
+        assert_step_eq(&step, &step_fixture());
+        let bundle = WitnessBundle::from_cbor(&golden(include_str!("testdata/cbor/witness_bundle.hex"))).unwrap();
+        assert_eq!(bundle.encode(), bundle_fixture().encode());
+    }
+    fn arb_fp() -> impl Strategy<Value = Fp> {
+        any::<u64>().prop_map(|seed| Fp::random(StdRng::seed_from_u64(seed)))
+    }
+    fn arb_point() -> impl Strategy<Value = CompressedPoint> {
+        (prop_oneof![Just(0x02u8), Just(0x03u8)], any::<[u8; 32]>()).prop_map(|(prefix, x)| {
+            let mut bytes = [prefix; 33];
+            bytes[1..].copy_from_slice(&x);
+            bytes.into()
+        })
+    }
+    fn arb_step() -> impl Strategy<Value = IPAStepWitness> {
+        let terms = || vec(any::<[[u8; 32]; 2]>(), 0..4);
+        (
+            any::<[u8; 32]>(),
+            vec(any::<[u8; 32]>(), 0..4),
+            terms(),
+            terms(),
+            any::<[u8; 32]>(),
+            option::of(any::<[u8; 32]>()),
+            option::of(any::<[u8; 32]>()),
+            any::<[u8; 32]>(),
+        )
+            .prop_map(|(operator_binding, public_inputs, l_terms, r_terms, a_scalar, b_scalar, new_app_state, next_transcript_hash)| {
+                IPAStepWitness { operator_binding, public_inputs, l_terms, r_terms, a_scalar, b_scalar, new_app_state, next_transcript_hash }
+            })
+    }
+    fn arb_ipa_hints() -> impl Strategy<Value = IpaHints> {
+        let round = (arb_point(), arb_point(), arb_point(), arb_fp())
+            .prop_map(|(l, r, a, challenge)| FoldingRound::new(l, r, a, challenge));
+        (vec(round, 0..30), arb_fp(), arb_point()).prop_map(|(rounds, scalar, point)| IpaHints::new(rounds, scalar, point.into()))
+    }
+    fn arb_poseidon_hints() -> impl Strategy<Value = PoseidonHints> {
+        let round = vec(arb_fp(), 6).prop_map(|e| PoseidonRoundHint::new([e[0], e[1], e[2]], [e[3], e[4], e[5]]));
+        let intent = (vec(round, 0..30), arb_fp()).prop_map(|(rounds, output)| IntentHashHints::new(rounds, output));
+        vec(intent, 0..3).prop_map(PoseidonHints::from_intents)
+    }
+    fn arb_bundle() -> impl Strategy<Value = WitnessBundle> {
+        (any::<[u8; 32]>(), arb_ipa_hints(), arb_poseidon_hints(), vec(arb_fp(), 0..5), vec(any::<u8>(), 0..300))
+            .prop_map(|(config_fingerprint, ipa_hints, poseidon_hints, app_fields, proof_bytes)| WitnessBundle {
+                version: BUNDLE_VERSION,
+                config_fingerprint,
+                ipa_hints,
+                poseidon_hints,
+                app_fields,
+                proof_bytes,
+            })
+    }
+    proptest! {
+        #[test]
+        fn test_step_round_trip(step in arb_step()) {
+            let encoded = step.to_canonical_cbor();
+            let decoded = IPAStepWitness::from_cbor(&encoded).unwrap();
+            assert_step_eq(&decoded, &step);
+            prop_assert_eq!(decoded.to_canonical_cbor(), encoded);
+        }
+        #[test]
+        fn test_hints_round_trip(ipa in arb_ipa_hints(), poseidon in arb_poseidon_hints()) {
+            let decoded = IpaHints::from_cbor(&ipa.to_canonical_cbor()).unwrap();
+            prop_assert_eq!(decoded.to_bytes(), ipa.to_bytes());
+            let decoded = PoseidonHints::from_cbor(&poseidon.to_canonical_cbor()).unwrap();
+            prop_assert_eq!(decoded.to_bytes(), poseidon.to_bytes());
+        }
+        #[test]
+        fn test_bundle_round_trip(bundle in arb_bundle()) {
+            let encoded = bundle.to_canonical_cbor();
+            let decoded = WitnessBundle::from_cbor(&encoded).unwrap();
+            prop_assert_eq!(decoded.encode(), bundle.encode());
+            prop_assert_eq!(decoded.to_canonical_cbor(), encoded);
+        }
+    }
+    #[test]
+    fn test_rejects_non_canonical() {
+        let encoded = ipa_fixture().to_canonical_cbor();
+        // Version 1 as a one-byte argument instead of the immediate form
+        let mut long_head = vec![0x83, 0x18, 0x01];
+        long_head.extend(&encoded[2..]);
+        assert_eq!(IpaHints::from_cbor(&long_head).unwrap_err(), CborError::NonCanonical { offset: 1 });
+        // Indefinite-length outer array
+        let mut indefinite = vec![0x9f];
+        indefinite.extend(&encoded[1..]);
+        indefinite.push(0xff);
+        assert_eq!(IpaHints::from_cbor(&indefinite).unwrap_err(), CborError::NonCanonical { offset: 0 });
+        let mut trailing = encoded.clone();
+        trailing.push(0x00);
+        assert_eq!(IpaHints::from_cbor(&trailing).unwrap_err(), CborError::TrailingBytes);
+        // Field element at or above the modulus
+        let mut unreduced = encoded.clone();
+        let at = unreduced.len() - 35 - 32;
+        unreduced[at..at + 32].copy_from_slice(&[0xff; 32]);
+        assert_eq!(IpaHints::from_cbor(&unreduced).unwrap_err(), CborError::Malformed("final_scalar"));
+        assert_eq!(
+            PoseidonHints::from_cbor(&encoded).unwrap_err(),
+            CborError::WrongKind { expected: KIND_POSEIDON_HINTS, found: KIND_IPA_HINTS }
+        );
+        let mut future = encoded.clone();
+        future[1] = 0x02;
+        assert_eq!(IpaHints::from_cbor(&future).unwrap_err(), CborError::UnknownVersion(2));
+        for len in 0..encoded.len() {
+            assert!(IpaHints::from_cbor(&encoded[..len]).is_err());
+        }
+    }
+}
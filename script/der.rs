@@ -0,0 +1,136 @@
+// DER Signatures
+// Strict and lax encoding of the ECDSA `SEQUENCE { INTEGER r, INTEGER s }`
+// carried in scriptSig signatures. The curve arithmetic lives in `k256`;
+// this is only the byte layout relay policy checks, plus the lenient reader
+// that lets `EcdsaSignature::normalize` repair non-minimal encodings.
+
+use std::fmt;
+
+/// Strict DER `SEQUENCE { INTEGER r, INTEGER s }`
+pub fn der_encode(r: &[u8; 32], s: &[u8; 32]) -> Vec<u8> {
+    let integer = |v: &[u8; 32]| {
+        let start = v.iter().position(|&b| b != 0).unwrap_or(31);
+        let mut body = v[start..].to_vec();
+        if body[0] & 0x80 != 0 {
+            body.insert(0, 0);
+        }
+        let mut out = vec![0x02, body.len() as u8];
+        out.extend(body);
+        out
+    };
+    let (r, s) = (integer(r), integer(s));
+    let mut der = vec![0x30, (r.len() + s.len()) as u8];
+    der.extend(r);
+    der.extend(s);
+    der
+}
+
+/// Inverse of `der_encode`; rejects anything but minimal encodings
+pub fn der_decode(der: &[u8]) -> Option<([u8; 32], [u8; 32])> {
+    let (&tag, rest) = der.split_first()?;
+    let (&len, mut body) = rest.split_first()?;
+    if tag != 0x30 || len as usize != body.len() {
+        return None;
+    }
+    let mut integer = || -> Option<[u8; 32]> {
+        let [0x02, len, ..] = *body else { return None };
+        let value = body.get(2..2 + len as usize)?;
+        body = &body[2 + len as usize..];
+        let minimal = match value {
+            [] => false,
+            [0, next, ..] => next & 0x80 != 0,
+            [first, ..] => first & 0x80 == 0,
+        };
+        let value = value.strip_prefix(&[0]).unwrap_or(value);
+        if !minimal || value.len() > 32 {
+            return None;
+        }
+        let mut out = [0u8; 32];
+        out[32 - value.len()..].copy_from_slice(value);
+        Some(out)
+    };
+    let r = integer()?;
+    let s = integer()?;
+    body.is_empty().then_some((r, s))
+}
+
+/// Why `der_decode_lax` could not read a signature
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DerError {
+    /// Not a `SEQUENCE` whose short-form length matches the bytes given
+    Malformed,
+    /// An element other than `INTEGER`, or one running past the sequence
+    NotAnInteger,
+    /// More than 32 significant bytes
+    IntegerTooLong,
+    ZeroInteger,
+    TrailingBytes,
+}
+
+impl fmt::Display for DerError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            DerError::Malformed => "malformed DER sequence",
+            DerError::NotAnInteger => "DER element is not an integer",
+            DerError::IntegerTooLong => "DER integer longer than 32 bytes",
+            DerError::ZeroInteger => "DER integer is zero",
+            DerError::TrailingBytes => "trailing bytes after DER integers",
+        })
+    }
+}
+
+/// `der_decode` that also accepts redundant leading zeros and integers
+/// missing the zero byte before a set high bit, as some signing backends
+/// emit; both integers are read as unsigned
+pub fn der_decode_lax(der: &[u8]) -> std::result::Result<([u8; 32], [u8; 32]), DerError> {
+    let [0x30, len, ref rest @ ..] = *der else { return Err(DerError::Malformed) };
+    if len as usize != rest.len() || len & 0x80 != 0 {
+        return Err(DerError::Malformed);
+    }
+    let mut body = rest;
+    let mut integer = || -> std::result::Result<[u8; 32], DerError> {
+        let [0x02, len, ..] = *body else { return Err(DerError::NotAnInteger) };
+        let value = body.get(2..2 + len as usize).ok_or(DerError::NotAnInteger)?;
+        body = &body[2 + len as usize..];
+        let start = value.iter().position(|&b| b != 0).ok_or(DerError::ZeroInteger)?;
+        let value = &value[start..];
+        if value.len() > 32 {
+            return Err(DerError::IntegerTooLong);
+        }
+        let mut out = [0u8; 32];
+        out[32 - value.len()..].copy_from_slice(value);
+        Ok(out)
+    };
+    let r = integer()?;
+    let s = integer()?;
+    if !body.is_empty() {
+        return Err(DerError::TrailingBytes);
+    }
+    Ok((r, s))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    fn scalar(n: u8) -> [u8; 32] {
+        let mut k = [0u8; 32];
+        k[31] = n;
+        k
+    }
+    #[test]
+    fn test_der_round_trip() {
+        let high = [0x80; 32];
+        for (r, s) in [(scalar(1), scalar(2)), (high, scalar(0x7f)), ([0x11; 32], high)] {
+            let der = der_encode(&r, &s);
+            assert!(der.len() <= 72);
+            assert_eq!(der_decode(&der), Some((r, s)));
+            assert_eq!(der_decode_lax(&der), Ok((r, s)));
+        }
+        let mut padded = der_encode(&scalar(1), &scalar(2));
+        padded.push(0);
+        assert_eq!(der_decode(&padded), None);
+        assert_eq!(der_decode(&[0x30, 0x06, 0x02, 0x01, 0x00, 0x02, 0x01, 0x01]), Some((scalar(0), scalar(1))));
+        assert_eq!(der_decode(&[0x30, 0x07, 0x02, 0x02, 0x00, 0x01, 0x02, 0x01, 0x01]), None);
+        assert_eq!(der_decode_lax(&[0x30, 0x07, 0x02, 0x02, 0x00, 0x01, 0x02, 0x01, 0x01]), Ok((scalar(1), scalar(1))));
+    }
+}
@@ -0,0 +1,204 @@
+use super::opcodes::*;
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Instruction<'a> {
+    Push(&'a [u8]),
+    Op(u8),
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum DisasmError {
+    TruncatedPush { offset: usize },
+    /// OP_ELSE or OP_ENDIF with no open OP_IF/OP_NOTIF
+    UnmatchedConditional { offset: usize, opcode: u8 },
+    /// Script ends with `depth` branches still open
+    UnclosedConditional { depth: usize },
+}
+
+pub struct Instructions<'a> {
+    script: &'a [u8],
+    offset: usize,
+}
+
+impl<'a> Instructions<'a> {
+    pub fn new(script: &'a [u8]) -> Self {
+        Self { script, offset: 0 }
+    }
+    fn read_len(&mut self, width: usize) -> Option<usize> {
+        let bytes = self.script.get(self.offset..self.offset + width)?;
+        self.offset += width;
+        let mut len = 0usize;
+        for (i, b) in bytes.iter().enumerate() {
+            len |= (*b as usize) << (8 * i);
+        }
+        Some(len)
+    }
+}
+
+impl<'a> Iterator for Instructions<'a> {
+    type Item = Result<(usize, Instruction<'a>), DisasmError>;
+    fn next(&mut self) -> Option<Self::Item> {
+        let start = self.offset;
+        let opcode = *self.script.get(start)?;
+        self.offset += 1;
+        let len = match opcode {
+            OP_0 => return Some(Ok((start, Instruction::Push(&[])))),
+            0x01..=0x4b => Some(opcode as usize),
+            OP_PUSHDATA1 => self.read_len(1),
+            OP_PUSHDATA2 => self.read_len(2),
+            OP_PUSHDATA4 => self.read_len(4),
+            _ => return Some(Ok((start, Instruction::Op(opcode)))),
+        };
+        let data = len.and_then(|len| self.script.get(self.offset..self.offset + len));
+        match data {
+            Some(data) => {
+                self.offset += data.len();
+                Some(Ok((start, Instruction::Push(data))))
+            }
+            None => {
+                self.offset = self.script.len();
+                Some(Err(DisasmError::TruncatedPush { offset: start }))
+            }
+        }
+    }
+}
+
+pub fn instructions(script: &[u8]) -> Instructions<'_> {
+    Instructions::new(script)
+}
+
+pub fn tokenize(script: &[u8]) -> Result<Vec<Instruction<'_>>, DisasmError> {
+    instructions(script).map(|r| r.map(|(_, ins)| ins)).collect()
+}
+
+/// Payload of every data push in order, e.g. the proof, hint, tail and
+/// preimage items of a scriptSig. Non-push opcodes, including OP_1..OP_16 and
+/// OP_1NEGATE, are skipped; extraction stops at a truncated push.
+pub fn extract_pushes(script: &[u8]) -> Vec<Vec<u8>> {
+    instructions(script)
+        .map_while(Result::ok)
+        .filter_map(|(_, ins)| match ins {
+            Instruction::Push(data) => Some(data.to_vec()),
+            Instruction::Op(_) => None,
+        })
+        .collect()
+}
+
+pub fn scripts_semantically_equal(a: &[u8], b: &[u8]) -> bool {
+    match (tokenize(a), tokenize(b)) {
+        (Ok(a), Ok(b)) => a == b,
+        _ => false,
+    }
+}
+
+pub fn validate_conditionals(script: &[u8]) -> Result<(), DisasmError> {
+    let mut depth = 0usize;
+    for item in instructions(script) {
+        let (offset, ins) = item?;
+        match ins {
+            Instruction::Op(OP_IF | OP_NOTIF) => depth += 1,
+            Instruction::Op(opcode @ (OP_ELSE | OP_ENDIF)) => {
+                if depth == 0 {
+                    return Err(DisasmError::UnmatchedConditional { offset, opcode });
+                }
+                if opcode == OP_ENDIF {
+                    depth -= 1;
+                }
+            }
+            _ => {}
+        }
+    }
+    if depth != 0 {
+        return Err(DisasmError::UnclosedConditional { depth });
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[test]
+    fn test_tokenize_mixed() {
+        let script = vec![OP_DUP, 0x02, 0xaa, 0xbb, OP_EQUAL];
+        let tokens = tokenize(&script).unwrap();
+        assert_eq!(tokens, vec![
+            Instruction::Op(OP_DUP),
+            Instruction::Push(&[0xaa, 0xbb]),
+            Instruction::Op(OP_EQUAL),
+        ]);
+    }
+    #[test]
+    fn test_tokenize_truncated() {
+        let script = vec![OP_PUSHDATA1, 10, 0x01];
+        assert_eq!(tokenize(&script), Err(DisasmError::TruncatedPush { offset: 0 }));
+    }
+    #[test]
+    fn test_extract_pushes_from_script_sig() {
+        use crate::ghost::script::{IpaHints, MulletWitness, PoseidonHints, SighashPreimageBuilder, TailWitness};
+        let witness = MulletWitness {
+            proof: vec![0xAB; 400],
+            ipa_hints: IpaHints::placeholder(2),
+            poseidon_hints: PoseidonHints::placeholder(1),
+            tail_witness: TailWitness::Custom(vec![0x30; 72]),
+            preimage: SighashPreimageBuilder::new().script_code(vec![OP_1; 300]).build(),
+            app_bytes: Some(vec![0x11; 43]),
+            change_bytes: Some(vec![0x22; 34]),
+        };
+        assert_eq!(extract_pushes(&witness.try_to_script_sig(usize::MAX).unwrap()), vec![
+            witness.proof.clone(),
+            witness.app_bytes.clone().unwrap(),
+            witness.change_bytes.clone().unwrap(),
+            witness.preimage.to_bytes(),
+        ]);
+        let mixed = vec![OP_DUP, 0x02, 0xaa, 0xbb, OP_1, OP_0, OP_PUSHDATA1, 5, 0x01];
+        assert_eq!(extract_pushes(&mixed), vec![vec![0xaa, 0xbb], vec![]]);
+    }
+    #[test]
+    fn test_minimal_and_overlong_push_equal() {
+        let data = [0x42u8; 20];
+        let mut minimal = vec![20];
+        minimal.extend(&data);
+        minimal.push(OP_EQUAL);
+        let mut pushdata1 = vec![OP_PUSHDATA1, 20];
+        pushdata1.extend(&data);
+        pushdata1.push(OP_EQUAL);
+        let mut pushdata4 = vec![OP_PUSHDATA4, 20, 0, 0, 0];
+        pushdata4.extend(&data);
+        pushdata4.push(OP_EQUAL);
+        assert_ne!(minimal, pushdata1);
+        assert!(scripts_semantically_equal(&minimal, &pushdata1));
+        assert!(scripts_semantically_equal(&minimal, &pushdata4));
+    }
+    #[test]
+    fn test_different_scripts_not_equal() {
+        let a = vec![0x01, 0x05, OP_EQUAL];
+        let b = vec![0x01, 0x06, OP_EQUAL];
+        let c = vec![0x01, 0x05, OP_EQUALVERIFY];
+        assert!(!scripts_semantically_equal(&a, &b));
+        assert!(!scripts_semantically_equal(&a, &c));
+        assert!(!scripts_semantically_equal(&a, &a[..2]));
+    }
+    #[test]
+    fn test_conditionals_balanced() {
+        let script = vec![OP_IF, OP_NOTIF, OP_1, OP_ELSE, OP_0, OP_ENDIF, OP_ELSE, OP_1, OP_ENDIF];
+        assert_eq!(validate_conditionals(&script), Ok(()));
+        // Opcode bytes inside push data are not conditionals
+        assert_eq!(validate_conditionals(&[0x02, OP_ENDIF, OP_IF, OP_IF, 0x01, OP_ENDIF, OP_ENDIF]), Ok(()));
+    }
+    #[test]
+    fn test_conditionals_unclosed() {
+        let script = vec![OP_IF, OP_IF, OP_1, OP_ENDIF];
+        assert_eq!(validate_conditionals(&script), Err(DisasmError::UnclosedConditional { depth: 1 }));
+    }
+    #[test]
+    fn test_conditionals_extra_endif() {
+        let script = vec![OP_IF, OP_1, OP_ENDIF, OP_ENDIF];
+        assert_eq!(
+            validate_conditionals(&script),
+            Err(DisasmError::UnmatchedConditional { offset: 3, opcode: OP_ENDIF })
+        );
+        assert_eq!(
+            validate_conditionals(&[OP_ELSE]),
+            Err(DisasmError::UnmatchedConditional { offset: 0, opcode: OP_ELSE })
+        );
+    }
+}
@@ -0,0 +1,134 @@
+// Script Error Kinds
+// Structured failures of the script layer with stable numeric codes, for
+// services that report or branch on them instead of matching messages.
+//
+// Functions whose failures are all script-layer kinds return `ScriptResult`,
+// so callers match on the kind or its code. `crate::ghost::Error` is shared
+// with the layers outside `script` and carries these as `InvalidInput(message)`
+// / `BindingMismatch`; `From` converts for callers that `?` into it and keeps
+// the messages they already see. Codes are append-only: never renumber or
+// reuse one.
+
+use std::fmt;
+use crate::ghost::Error;
+use super::to_hex;
+
+/// Part of a witness a builder was not given
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum WitnessComponent {
+    Proof,
+    IpaHints,
+    PoseidonHints,
+    Preimage,
+}
+
+impl fmt::Display for WitnessComponent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            WitnessComponent::Proof => "proof",
+            WitnessComponent::IpaHints => "IPA hints",
+            WitnessComponent::PoseidonHints => "Poseidon hints",
+            WitnessComponent::Preimage => "preimage",
+        })
+    }
+}
+
+pub type ScriptResult<T> = std::result::Result<T, ScriptErrorKind>;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(tag = "kind"))]
+pub enum ScriptErrorKind {
+    WitnessMissingComponent { component: WitnessComponent },
+    /// hashOutputs from the preimage against the hash of the witness outputs
+    BindingMismatch { expected: [u8; 32], computed: [u8; 32] },
+    ScriptTooLarge { size: usize, max: usize },
+    HintCountMismatch { expected: usize, got: usize },
+    /// Position of the signature in the witness's signature order
+    SignatureInvalid { index: usize },
+    /// A stack element the script builds, e.g. by OP_CAT
+    ElementTooLarge { size: usize, max: usize },
+    /// Guard parameters no script can be built for
+    InvalidGuardConfig { reason: String },
+    /// Preimage bytes that are not the serialization a check expects
+    MalformedPreimage { reason: String },
+}
+
+impl ScriptErrorKind {
+    pub fn error_code(&self) -> u16 {
+        match self {
+            ScriptErrorKind::WitnessMissingComponent { .. } => 1001,
+            ScriptErrorKind::BindingMismatch { .. } => 1002,
+            ScriptErrorKind::ScriptTooLarge { .. } => 1003,
+            ScriptErrorKind::HintCountMismatch { .. } => 1004,
+            ScriptErrorKind::SignatureInvalid { .. } => 1005,
+            ScriptErrorKind::ElementTooLarge { .. } => 1006,
+            ScriptErrorKind::InvalidGuardConfig { .. } => 1007,
+            ScriptErrorKind::MalformedPreimage { .. } => 1008,
+        }
+    }
+}
+
+impl fmt::Display for ScriptErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ScriptErrorKind::WitnessMissingComponent { component } => write!(f, "Missing {}", component),
+            ScriptErrorKind::BindingMismatch { expected, computed } => write!(
+                f,
+                "Binding mismatch: expected {}, computed {}",
+                to_hex(expected),
+                to_hex(computed)
+            ),
+            ScriptErrorKind::ScriptTooLarge { size, max } => write!(f, "Script too large: {} bytes (max {})", size, max),
+            ScriptErrorKind::HintCountMismatch { expected, got } => {
+                write!(f, "Hint count mismatch: expected {}, got {}", expected, got)
+            }
+            ScriptErrorKind::SignatureInvalid { index } => write!(f, "Signature {} is invalid", index),
+            ScriptErrorKind::ElementTooLarge { size, max } => {
+                write!(f, "Stack element too large: {} bytes (max {})", size, max)
+            }
+            ScriptErrorKind::InvalidGuardConfig { reason } | ScriptErrorKind::MalformedPreimage { reason } => {
+                f.write_str(reason)
+            }
+        }
+    }
+}
+
+impl From<ScriptErrorKind> for Error {
+    fn from(kind: ScriptErrorKind) -> Self {
+        match kind {
+            ScriptErrorKind::BindingMismatch { .. } => Error::BindingMismatch,
+            kind => Error::InvalidInput(kind.to_string()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[test]
+    fn test_error_codes_stable() {
+        let kinds = [
+            (ScriptErrorKind::WitnessMissingComponent { component: WitnessComponent::Proof }, 1001),
+            (ScriptErrorKind::BindingMismatch { expected: [0; 32], computed: [1; 32] }, 1002),
+            (ScriptErrorKind::ScriptTooLarge { size: 20_000, max: 10_000 }, 1003),
+            (ScriptErrorKind::HintCountMismatch { expected: 10, got: 9 }, 1004),
+            (ScriptErrorKind::SignatureInvalid { index: 2 }, 1005),
+            (ScriptErrorKind::ElementTooLarge { size: 12_000, max: 10_000 }, 1006),
+            (ScriptErrorKind::InvalidGuardConfig { reason: String::new() }, 1007),
+            (ScriptErrorKind::MalformedPreimage { reason: String::new() }, 1008),
+        ];
+        for (kind, code) in kinds {
+            assert_eq!(kind.error_code(), code, "{}", kind);
+        }
+    }
+    #[test]
+    fn test_conversion_keeps_messages() {
+        let missing = ScriptErrorKind::WitnessMissingComponent { component: WitnessComponent::IpaHints };
+        assert!(matches!(Error::from(missing), Error::InvalidInput(msg) if msg == "Missing IPA hints"));
+        let mismatch = ScriptErrorKind::BindingMismatch { expected: [0; 32], computed: [1; 32] };
+        assert!(mismatch.to_string().contains(&hex::encode([1u8; 32])));
+        assert!(matches!(Error::from(mismatch), Error::BindingMismatch));
+    }
+}
@@ -0,0 +1,114 @@
+// Field Elements
+// Pallas field constants, byte encodings and the Poseidon round schedule
+// shared by the hint types, transcript sections and the Poseidon and contract
+// modules. Kept out of `field_script` so builds without `poseidon-script`
+// still decode and check hint elements; `field_script` re-exports all of it.
+
+use crate::ghost::script::{OP_DUP, OP_WITHIN, OP_VERIFY, push_bytes};
+use crate::ghost::crypto::Fp;
+use crate::ghost::crypto::poseidon_constants::MDS_MATRIX;
+use ff::{PrimeField, Field};
+
+pub const FIELD_BYTES: usize = 32;
+
+/// Field elements (Pallas/Vesta scalars), represented as 32 bytes for Script
+pub type FieldElement = [u8; FIELD_BYTES];
+
+/// Pallas prime modulus p
+/// Little-endian with a clear top bit, so this is also its minimal script number
+pub const PALLAS_MODULUS_BYTES: [u8; FIELD_BYTES] = [
+    0x01, 0x00, 0x00, 0x00, 0xed, 0x30, 0x2d, 0x99,
+    0x1b, 0xf9, 0x4c, 0x09, 0xfc, 0x98, 0x46, 0x22,
+    0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+    0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x40,
+];
+
+pub const FULL_ROUNDS: usize = 8;
+pub const PARTIAL_ROUNDS: usize = 56;
+pub const TOTAL_ROUNDS: usize = 64;
+
+/// Pallas Poseidon S-box: x ↦ x^5
+pub const SBOX_EXPONENT: u64 = 5;
+
+/// Full rounds S-box every lane, partial rounds only s0
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RoundType {
+    Full,
+    Partial,
+}
+
+impl RoundType {
+    pub fn is_full(self) -> bool {
+        self == RoundType::Full
+    }
+}
+
+/// Type of round `round`: half the full rounds open the permutation, the
+/// partial rounds follow, the other half close it
+pub fn round_type(round: usize) -> RoundType {
+    let first_partial = FULL_ROUNDS / 2;
+    if round < first_partial || round >= first_partial + PARTIAL_ROUNDS {
+        RoundType::Full
+    } else {
+        RoundType::Partial
+    }
+}
+
+/// `(round_index, type)` for all `TOTAL_ROUNDS` rounds, in order
+pub fn round_types() -> impl Iterator<Item = (usize, RoundType)> {
+    (0..TOTAL_ROUNDS).map(|round| (round, round_type(round)))
+}
+
+pub(crate) fn get_mds_fp() -> [[Fp; 3]; 3] {
+    let mut m = [[Fp::ZERO; 3]; 3];
+    for i in 0..3 {
+        for j in 0..3 {
+            m[i][j] = Fp::from(MDS_MATRIX[i][j]);
+        }
+    }
+    m
+}
+
+#[inline]
+pub fn fp_to_bytes(fp: &Fp) -> [u8; FIELD_BYTES] {
+    fp.to_repr()
+}
+
+#[inline]
+pub fn bytes_to_fp(bytes: &[u8; FIELD_BYTES]) -> Option<Fp> {
+    Fp::from_repr(*bytes).into()
+}
+
+/// Encode a field element as a minimal script number.
+/// Trailing zero bytes are stripped, and a 0x00 sign byte is kept when the
+/// top remaining byte has its high bit set. Zero encodes as the empty string.
+pub fn fp_to_scriptnum_bytes(fp: &Fp) -> Vec<u8> {
+    let mut bytes = fp_to_bytes(fp).to_vec();
+    while bytes.last() == Some(&0) {
+        bytes.pop();
+    }
+    if bytes.last().is_some_and(|b| b & 0x80 != 0) {
+        bytes.push(0);
+    }
+    bytes
+}
+
+/// Generate canonical check: Verify 0 <= top stack element < p
+/// Stack: [x] -> [x] (passes if x in [0, p), fails otherwise)
+///
+/// x is read as a script number. A plain `x < p` also accepts negative
+/// encodings (sign bit set), which reduce to a different residue under
+/// OP_MOD, so the lower bound is checked with OP_WITHIN.
+///
+/// Script numbers are little-endian, the same order as `fp_to_bytes` and
+/// `PALLAS_MODULUS_BYTES`, so the comparison is against the value the field
+/// decodes. A big-endian element is not detected: it reads as some other
+/// number and only fails if that one happens to be >= p.
+pub fn generate_canonical_check() -> Vec<u8> {
+    let mut script = vec![OP_DUP];
+    script.extend(push_bytes(&[]));
+    script.extend(push_bytes(&PALLAS_MODULUS_BYTES));
+    script.push(OP_WITHIN);
+    script.push(OP_VERIFY);
+    script
+}
@@ -2,49 +2,64 @@
 // BSV Native Computation Model - WITNESS PATTERN
 //
 // ARCHITECTURE:
-// The "Witness Pattern" solves the constant-size problem:
-// - Locking Script: Logic + HASH of valid constants (~2.5 KB)
-// - Unlocking Script: Provides constants blob (~3 KB after fusion)
-// - Verification: Hash(witness_constants) == hardcoded_hash
+// The "Witness Pattern" moves the 192 round constants out of the lock:
+// - Locking Script: round logic, p, MDS and the blob's hash (~7.9 KB,
+//   against ~10.3 KB with every constant embedded)
+// - Unlocking Script: the constants blob (6144 bytes) on every spend
+// - Verification: SHA256(blob) == hardcoded hash, then each round splits
+//   its constants off the blob's front
 //
 // OPTIMIZATIONS:
 // 1. Witness Pattern: Constants in unlock, not lock script
-// 2. Fused Constants: Merge partial round constants (saves 3.5 KB)
-// 3. Sparse MDS: 5 muls instead of 9 for partial rounds
-// 4. Single MDS push: 9 elements shared across all rounds
+// 2. Round templates: one skeleton per round type, constants spliced in
+// 3. Single MDS push: 9 elements shared across all rounds
 //
-// TARGET: ~2.5 KB locking script
+// ENCODING:
+// A field element has two byte encodings (see `FieldEncoding`):
+// - LE repr: fixed 32-byte `to_repr()`, used for hashing (constants blob,
+//   transcripts, state commitments)
+// - Script number: minimal little-endian sign-magnitude, required wherever
+//   the element meets numeric opcodes (canonical check, limb arithmetic) or
+//   is compared with OP_EQUAL against an arithmetic result
 
 use crate::ghost::script::{
-    OP_DUP, OP_DROP, OP_SWAP, OP_OVER, OP_PICK, OP_ROLL,
-    OP_ADD, OP_SUB, OP_MUL, OP_MOD,
-    OP_EQUAL, OP_EQUALVERIFY,
+    OP_0, OP_1NEGATE, OP_1, OP_16,
+    OP_DUP, OP_DROP, OP_2DROP, OP_2DUP, OP_3DUP, OP_2OVER, OP_2ROT, OP_2SWAP,
+    OP_NIP, OP_SWAP, OP_OVER, OP_TUCK, OP_ROT, OP_PICK, OP_ROLL, OP_DEPTH, OP_SIZE,
+    OP_CAT, OP_SPLIT, OP_ADD, OP_SUB, OP_MUL, OP_DIV, OP_MOD,
+    OP_1ADD, OP_1SUB, OP_NEGATE, OP_ABS, OP_NOT, OP_0NOTEQUAL,
+    OP_NUMEQUAL, OP_NUMEQUALVERIFY, OP_NUMNOTEQUAL, OP_LESSTHAN, OP_GREATERTHAN,
+    OP_LESSTHANOREQUAL, OP_GREATERTHANOREQUAL, OP_MIN, OP_MAX, OP_BOOLAND, OP_BOOLOR,
+    OP_EQUAL, OP_EQUALVERIFY, OP_VERIFY, OP_WITHIN,
     OP_TOALTSTACK, OP_FROMALTSTACK,
-    OP_SHA256,
-    push_bytes,
+    OP_SHA256, OP_HASH160, OP_HASH256, OP_RIPEMD160,
+    push_bytes, push_number, push_size,
 };
+#[cfg(test)]
+use crate::ghost::script::to_hex;
+use num_bigint::BigUint;
+#[cfg(test)]
+use crate::ghost::script::vm::{decode_num, Interpreter, VmError};
+#[cfg(feature = "contract")]
+use crate::ghost::script::network::ChainParams;
+#[cfg(feature = "contract")]
+use crate::ghost::script::verifier_contract::{contract_lock_size, TranscriptShape};
 use crate::ghost::crypto::Fp;
-use crate::ghost::crypto::poseidon_constants::{MDS_MATRIX, get_round_constant};
-use ff::{PrimeField, Field};
+use crate::ghost::crypto::poseidon_constants::get_round_constant;
+use crate::ghost::Error;
+use ff::Field;
 use sha2::{Sha256, Digest};
+use std::sync::OnceLock;
 
 // ============================================================================
 // CONSTANTS
 // ============================================================================
 
-pub const FIELD_BYTES: usize = 32;
-
-/// Pallas prime modulus p
-pub const PALLAS_MODULUS_BYTES: [u8; FIELD_BYTES] = [
-    0x01, 0x00, 0x00, 0x00, 0xed, 0x30, 0x2d, 0x99,
-    0x1b, 0xf9, 0x4c, 0x09, 0xfc, 0x98, 0x46, 0x22,
-    0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
-    0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x40,
-];
-
-pub const FULL_ROUNDS: usize = 8;
-pub const PARTIAL_ROUNDS: usize = 56;
-pub const TOTAL_ROUNDS: usize = 64;
+pub use crate::ghost::script::field::{
+    FIELD_BYTES, PALLAS_MODULUS_BYTES, FULL_ROUNDS, PARTIAL_ROUNDS, TOTAL_ROUNDS, SBOX_EXPONENT,
+    RoundType, round_type, round_types, fp_to_bytes, bytes_to_fp, fp_to_scriptnum_bytes, generate_canonical_check,
+};
+pub(crate) use crate::ghost::script::field::get_mds_fp;
 
 // ============================================================================
 // FUSED CONSTANTS
@@ -80,60 +95,90 @@ pub struct FusedPoseidonConstants {
 impl FusedPoseidonConstants {
     /// Compute fused constants from standard Poseidon constants
     pub fn compute() -> Self {
+        Self::with_rounds(FULL_ROUNDS, PARTIAL_ROUNDS).expect("default round split is valid")
+    }
+
+    /// Fused constants for a `full_rounds` / `partial_rounds` split of the
+    /// 64-round constant table: half the full rounds, then the partial
+    /// rounds, then the other half. The script generators assume the
+    /// default 8 / 56 split; other splits are for off-chain checks.
+    pub fn with_rounds(full_rounds: usize, partial_rounds: usize) -> crate::ghost::Result<Self> {
+        validate_round_counts(full_rounds, partial_rounds)?;
         let mds = get_mds_fp();
+        let first_partial = full_rounds / 2;
+        let last_full = first_partial + partial_rounds;
+        let round_constants = |r: usize| [
+            get_round_constant(r, 0),
+            get_round_constant(r, 1),
+            get_round_constant(r, 2),
+        ];
         
-        // Full rounds: first 4 and last 4 (no fusion, need all constants)
-        let mut full_round_constants = Vec::with_capacity(8);
-        for r in 0..4 {
-            full_round_constants.push([
-                get_round_constant(r, 0),
-                get_round_constant(r, 1),
-                get_round_constant(r, 2),
-            ]);
-        }
-        for r in 60..64 {
-            full_round_constants.push([
-                get_round_constant(r, 0),
-                get_round_constant(r, 1),
-                get_round_constant(r, 2),
-            ]);
-        }
+        // Full rounds: first and last half (no fusion, need all constants)
+        let mut full_round_constants: Vec<[Fp; 3]> = (0..first_partial)
+            .chain(last_full..TOTAL_ROUNDS)
+            .map(round_constants)
+            .collect();
         
-        // Partial rounds: fuse c1, c2 into next round's c0
-        // For round r: effective_c0[r] = c0[r] + contribution from previous round's c1, c2
-        let mut partial_round_c0 = Vec::with_capacity(56);
+        // Partial rounds: fuse c1, c2 into later rounds
+        // Only s0 passes through the S-box, so the constants added to s1, s2
+        // can be deferred: MDS * (0, c1 + d1, c2 + d2) becomes the deferred
+        // vector d for the next round, and d0 is folded into that round's c0.
+        let mut partial_round_c0 = Vec::with_capacity(partial_rounds);
         
-        // Accumulated contribution from previous round's linear terms
-        let mut acc_c1 = Fp::ZERO;
-        let mut acc_c2 = Fp::ZERO;
+        // Deferred linear constants carried into the current round
+        let mut deferred = [Fp::ZERO; 3];
         
-        for r in 4..60 {
-            let c0 = get_round_constant(r, 0);
-            let c1 = get_round_constant(r, 1);
-            let c2 = get_round_constant(r, 2);
+        for r in first_partial..last_full {
+            let [c0, c1, c2] = round_constants(r);
             
-            // The effective c0 for this round includes the MDS-transformed
-            // accumulated constants from previous linear operations
-            // effective_c0 = c0 + MDS[0][1]*acc_c1 + MDS[0][2]*acc_c2
-            let effective_c0 = c0 + mds[0][1] * acc_c1 + mds[0][2] * acc_c2;
-            partial_round_c0.push(effective_c0);
+            // effective_c0 = c0 + d0
+            partial_round_c0.push(c0 + deferred[0]);
             
-            // Update accumulator for next round:
-            // After this round's MDS, the c1/c2 contributions become:
-            // new_acc_c1 = MDS[1][1]*c1 + MDS[1][2]*c2
-            // new_acc_c2 = MDS[2][1]*c1 + MDS[2][2]*c2
-            acc_c1 = mds[1][1] * c1 + mds[1][2] * c2;
-            acc_c2 = mds[2][1] * c1 + mds[2][2] * c2;
+            // The linear lanes pass through this round's MDS untouched by the S-box
+            let lin1 = c1 + deferred[1];
+            let lin2 = c2 + deferred[2];
+            deferred = [
+                mds[0][1] * lin1 + mds[0][2] * lin2,
+                mds[1][1] * lin1 + mds[1][2] * lin2,
+                mds[2][1] * lin1 + mds[2][2] * lin2,
+            ];
         }
         
-        // The final accumulator needs to be added to round 60's constants
-        // This is handled when we use the constants
+        // The residual after the last partial round lands in the first
+        // closing full round's constants
+        for i in 0..3 {
+            full_round_constants[first_partial][i] += deferred[i];
+        }
         
-        Self {
+        Ok(Self {
             mds,
             full_round_constants,
             partial_round_c0,
+        })
+    }
+    
+    /// Reference Poseidon permutation over the fused constants.
+    ///
+    /// Full rounds add all three constants; partial rounds only add the fused
+    /// c0 to s0, exactly as the witness-pattern rounds do. This must agree with
+    /// `PoseidonHash`, otherwise the fusion math is wrong.
+    pub fn permute(&self, state: [Fp; 3]) -> [Fp; 3] {
+        let first_partial = self.full_round_constants.len() / 2;
+        let last_full = first_partial + self.partial_round_c0.len();
+        let mut s = state;
+        for round in 0..TOTAL_ROUNDS {
+            if round < first_partial || round >= last_full {
+                let idx = if round < first_partial { round } else { round - self.partial_round_c0.len() };
+                let rc = self.full_round_constants[idx];
+                for i in 0..3 {
+                    s[i] = sbox(s[i] + rc[i]);
+                }
+            } else {
+                s[0] = sbox(s[0] + self.partial_round_c0[round - first_partial]);
+            }
+            s = mds_mul(&self.mds, &s);
         }
+        s
     }
     
     /// Serialize all constants to bytes for witness
@@ -164,9 +209,19 @@ impl FusedPoseidonConstants {
     
     /// Compute SHA256 hash of witness bytes (for verification)
     pub fn witness_hash(&self) -> [u8; 32] {
-        let bytes = self.to_witness_bytes();
+        self.witness_hash_streaming()
+    }
+    
+    /// Same digest as hashing `to_witness_bytes()`, fed element by element
+    /// so the ~2.8KB blob is never materialized
+    pub fn witness_hash_streaming(&self) -> [u8; 32] {
         let mut hasher = Sha256::new();
-        hasher.update(&bytes);
+        let elements = self.mds.iter().flatten()
+            .chain(self.full_round_constants.iter().flatten())
+            .chain(self.partial_round_c0.iter());
+        for elem in elements {
+            hasher.update(fp_to_bytes(elem));
+        }
         hasher.finalize().into()
     }
     
@@ -175,10 +230,25 @@ impl FusedPoseidonConstants {
         // MDS: 9 × 32 = 288
         // Full: 8 × 3 × 32 = 768
         // Partial: 56 × 32 = 1792
-        288 + 768 + 1792
+        (9 + self.full_round_constants.len() * 3 + self.partial_round_c0.len()) * FIELD_BYTES
     }
 }
 
+/// A split must cover the 64-round constant table with a non-zero, even
+/// number of full rounds (half on each side of the partial rounds)
+pub fn validate_round_counts(full_rounds: usize, partial_rounds: usize) -> crate::ghost::Result<()> {
+    if full_rounds == 0 || !full_rounds.is_multiple_of(2) {
+        return Err(Error::InvalidInput(format!("Full round count {} must be even and non-zero", full_rounds)));
+    }
+    if full_rounds + partial_rounds != TOTAL_ROUNDS {
+        return Err(Error::InvalidInput(format!(
+            "{} full + {} partial rounds do not cover the {} round constants",
+            full_rounds, partial_rounds, TOTAL_ROUNDS
+        )));
+    }
+    Ok(())
+}
+
 /// Get the constants hash (computed fresh each time, or could be cached)
 pub fn get_constants_hash() -> [u8; 32] {
     FusedPoseidonConstants::compute().witness_hash()
@@ -188,16 +258,35 @@ pub fn get_constants_hash() -> [u8; 32] {
 // FP CONVERSION
 // ============================================================================
 
-#[inline]
-pub fn fp_to_bytes(fp: &Fp) -> [u8; FIELD_BYTES] {
-    fp.to_repr()
+/// `bytes` as an unsigned little-endian integer reduced mod p, the value
+/// `generate_reduce_script` leaves for the same element
+pub fn bytes_to_fp_reduced(bytes: &[u8; FIELD_BYTES]) -> Fp {
+    let modulus = BigUint::from_bytes_le(&PALLAS_MODULUS_BYTES);
+    let reduced = (BigUint::from_bytes_le(bytes) % modulus).to_bytes_le();
+    let mut repr = [0u8; FIELD_BYTES];
+    repr[..reduced.len()].copy_from_slice(&reduced);
+    bytes_to_fp(&repr).expect("reduced below p")
 }
 
-#[inline]
-pub fn bytes_to_fp(bytes: &[u8; FIELD_BYTES]) -> Option<Fp> {
-    Fp::from_repr(*bytes).into()
+/// Byte encoding of a field element
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FieldEncoding {
+    /// Fixed 32-byte little-endian `to_repr()`; for hashing and commitments
+    LeRepr,
+    /// Minimal script number; for numeric opcodes and OP_EQUAL against results
+    ScriptNum,
 }
 
+impl FieldEncoding {
+    pub fn encode(&self, fp: &Fp) -> Vec<u8> {
+        match self {
+            FieldEncoding::LeRepr => fp_to_bytes(fp).to_vec(),
+            FieldEncoding::ScriptNum => fp_to_scriptnum_bytes(fp),
+        }
+    }
+}
+
+
 // ============================================================================
 // SPARSE MDS REPRESENTATION
 // ============================================================================
@@ -228,25 +317,23 @@ impl SparseMdsConstants {
     }
 }
 
-fn get_mds_fp() -> [[Fp; 3]; 3] {
-    let mut m = [[Fp::ZERO; 3]; 3];
+#[inline]
+fn sbox(x: Fp) -> Fp {
+    x.square().square() * x
+}
+
+fn mds_mul(mds: &[[Fp; 3]; 3], s: &[Fp; 3]) -> [Fp; 3] {
+    let mut out = [Fp::ZERO; 3];
     for i in 0..3 {
-        for j in 0..3 {
-            m[i][j] = Fp::from(MDS_MATRIX[i][j]);
-        }
+        out[i] = mds[i][0] * s[0] + mds[i][1] * s[1] + mds[i][2] * s[2];
     }
-    m
+    out
 }
 
-fn get_mds_bytes() -> [[[u8; FIELD_BYTES]; 3]; 3] {
+
+pub(crate) fn get_mds_scriptnums() -> [[Vec<u8>; 3]; 3] {
     let m = get_mds_fp();
-    let mut result = [[[0u8; FIELD_BYTES]; 3]; 3];
-    for i in 0..3 {
-        for j in 0..3 {
-            result[i][j] = fp_to_bytes(&m[i][j]);
-        }
-    }
-    result
+    [0, 1, 2].map(|i| [0, 1, 2].map(|j| fp_to_scriptnum_bytes(&m[i][j])))
 }
 
 // ============================================================================
@@ -263,32 +350,167 @@ fn get_mds_bytes() -> [[[u8; FIELD_BYTES]; 3]; 3] {
 #[derive(Clone, Debug)]
 pub struct OptimizedScriptBuilder {
     script: Vec<u8>,
+    /// Symbolic (main, alt) stack depths; None when untracked
+    depth: Option<(usize, usize)>,
+    /// Record depth errors instead of panicking in debug builds
+    checked: bool,
+    error: Option<StackDepthError>,
+}
+
+/// Stack misuse caught while generating a script
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum StackDepthError {
+    /// `opcode` at script `offset` reads deeper than the `depth` items present
+    Underflow { offset: usize, opcode: u8, depth: usize },
+    /// OP_FROMALTSTACK at `offset` with an empty alt stack
+    AltUnderflow { offset: usize },
+    /// `assert_depth` checkpoint at `offset` found `actual` items
+    Mismatch { offset: usize, expected: usize, actual: usize },
+}
+
+/// (pops, pushes, alt change) for opcodes with a fixed stack effect
+fn stack_effect(opcode: u8) -> Option<(usize, usize, isize)> {
+    Some(match opcode {
+        OP_0 | OP_1NEGATE | OP_1..=OP_16 | OP_DEPTH => (0, 1, 0),
+        OP_DUP | OP_SIZE => (1, 2, 0),
+        OP_DROP | OP_VERIFY => (1, 0, 0),
+        OP_1ADD | OP_1SUB | OP_NEGATE | OP_ABS | OP_NOT | OP_0NOTEQUAL
+        | OP_SHA256 | OP_HASH160 | OP_HASH256 | OP_RIPEMD160 => (1, 1, 0),
+        OP_SWAP | OP_SPLIT => (2, 2, 0),
+        OP_OVER | OP_TUCK => (2, 3, 0),
+        OP_NIP | OP_CAT | OP_ADD | OP_SUB | OP_MUL | OP_DIV | OP_MOD
+        | OP_EQUAL | OP_NUMEQUAL | OP_NUMNOTEQUAL | OP_LESSTHAN | OP_GREATERTHAN
+        | OP_LESSTHANOREQUAL | OP_GREATERTHANOREQUAL | OP_MIN | OP_MAX
+        | OP_BOOLAND | OP_BOOLOR => (2, 1, 0),
+        OP_2DROP | OP_EQUALVERIFY | OP_NUMEQUALVERIFY => (2, 0, 0),
+        OP_2DUP => (2, 4, 0),
+        OP_ROT => (3, 3, 0),
+        OP_3DUP => (3, 6, 0),
+        OP_WITHIN => (3, 1, 0),
+        OP_2SWAP => (4, 4, 0),
+        OP_2OVER => (4, 6, 0),
+        OP_2ROT => (6, 6, 0),
+        OP_TOALTSTACK => (1, 0, 1),
+        OP_FROMALTSTACK => (0, 1, -1),
+        _ => return None,
+    })
 }
 
 impl OptimizedScriptBuilder {
     pub fn new() -> Self {
-        Self { script: Vec::with_capacity(4096) }
+        Self { script: Vec::with_capacity(4096), depth: None, checked: false, error: None }
+    }
+
+    /// Builder that tracks stack depth, starting from `depth` items on the
+    /// main stack and an empty alt stack. Out-of-range PICK/ROLL, alt
+    /// underflow and failed `assert_depth` checkpoints panic in debug builds,
+    /// or are recorded for `try_build` after `checked()`.
+    pub fn with_depth(depth: usize) -> Self {
+        Self { depth: Some((depth, 0)), ..Self::new() }
+    }
+
+    /// Record depth errors instead of panicking
+    pub fn checked(mut self) -> Self {
+        self.checked = true;
+        self
     }
 
     pub fn build(self) -> Vec<u8> {
         self.script
     }
 
+    /// The script, or the first depth error
+    pub fn try_build(self) -> std::result::Result<Vec<u8>, StackDepthError> {
+        match self.error {
+            Some(err) => Err(err),
+            None => Ok(self.script),
+        }
+    }
+
     pub fn size(&self) -> usize {
         self.script.len()
     }
 
+    /// Main stack depth, if tracked. Tracking stops after the first error
+    /// and at opcodes without a fixed stack effect (raw PICK/ROLL, branches).
+    pub fn current_depth(&self) -> Option<usize> {
+        self.depth.map(|(main, _)| main)
+    }
+
+    pub fn error(&self) -> Option<&StackDepthError> {
+        self.error.as_ref()
+    }
+
+    /// Checkpoint: the main stack holds exactly `expected` items
+    pub fn assert_depth(&mut self, expected: usize) -> &mut Self {
+        if let Some(actual) = self.current_depth().filter(|&actual| actual != expected) {
+            self.fail(StackDepthError::Mismatch { offset: self.script.len(), expected, actual });
+        }
+        self
+    }
+
+    /// Account for `count` items pushed by script spliced in later
+    pub fn assume_pushed(&mut self, count: usize) -> &mut Self {
+        if let Some((main, _)) = &mut self.depth {
+            *main += count;
+        }
+        self
+    }
+
+    fn fail(&mut self, err: StackDepthError) {
+        self.depth = None;
+        if !self.checked && cfg!(debug_assertions) {
+            panic!("stack depth error: {:?}", err);
+        }
+        self.error.get_or_insert(err);
+    }
+
+    fn track(&mut self, opcode: u8) {
+        let Some((main, alt)) = self.depth else { return };
+        let Some((pops, pushes, alt_change)) = stack_effect(opcode) else {
+            self.depth = None;
+            return;
+        };
+        let offset = self.script.len();
+        if main < pops {
+            return self.fail(StackDepthError::Underflow { offset, opcode, depth: main });
+        }
+        if alt_change < 0 && alt == 0 {
+            return self.fail(StackDepthError::AltUnderflow { offset });
+        }
+        self.depth = Some((main - pops + pushes, alt.wrapping_add_signed(alt_change)));
+    }
+
+    /// PICK/ROLL argument check: `n` indexes below the pushed argument
+    fn track_index(&mut self, opcode: u8, n: usize) {
+        let Some((main, alt)) = self.depth else { return };
+        if n >= main {
+            let offset = self.script.len();
+            return self.fail(StackDepthError::Underflow { offset, opcode, depth: main });
+        }
+        let main = if opcode == OP_PICK { main + 1 } else { main };
+        self.depth = Some((main, alt));
+    }
+
     // Raw operations
     pub fn op(&mut self, opcode: u8) -> &mut Self {
+        self.track(opcode);
         self.script.push(opcode);
         self
     }
 
     pub fn push_data(&mut self, data: &[u8]) -> &mut Self {
+        self.assume_pushed(1);
         self.script.extend(push_bytes(data));
         self
     }
 
+    pub fn push_number(&mut self, n: i64) -> &mut Self {
+        self.assume_pushed(1);
+        self.script.extend(push_number(n));
+        self
+    }
+
     // Stack ops
     pub fn dup(&mut self) -> &mut Self { self.op(OP_DUP) }
     pub fn drop(&mut self) -> &mut Self { self.op(OP_DROP) }
@@ -298,13 +520,17 @@ impl OptimizedScriptBuilder {
     pub fn from_alt(&mut self) -> &mut Self { self.op(OP_FROMALTSTACK) }
 
     pub fn pick(&mut self, n: usize) -> &mut Self {
-        self.script.extend(crate::ghost::script::push_number(n as i64));
-        self.op(OP_PICK)
+        self.track_index(OP_PICK, n);
+        self.script.extend(push_number(n as i64));
+        self.script.push(OP_PICK);
+        self
     }
 
     pub fn roll(&mut self, n: usize) -> &mut Self {
-        self.script.extend(crate::ghost::script::push_number(n as i64));
-        self.op(OP_ROLL)
+        self.track_index(OP_ROLL, n);
+        self.script.extend(push_number(n as i64));
+        self.script.push(OP_ROLL);
+        self
     }
 
     // Arithmetic
@@ -317,23 +543,24 @@ impl OptimizedScriptBuilder {
     pub fn equal_verify(&mut self) -> &mut Self { self.op(OP_EQUALVERIFY) }
     
     // Logic
-    pub fn less_than(&mut self) -> &mut Self { self.op(crate::ghost::script::OP_LESSTHAN) }
-    pub fn verify(&mut self) -> &mut Self { self.op(crate::ghost::script::OP_VERIFY) }
+    pub fn less_than(&mut self) -> &mut Self { self.op(OP_LESSTHAN) }
+    pub fn within(&mut self) -> &mut Self { self.op(OP_WITHIN) }
+    pub fn verify(&mut self) -> &mut Self { self.op(OP_VERIFY) }
 
     // ========== INITIALIZATION ==========
     
     /// Push modulus and MDS constants to main stack (bottom)
     /// After: Stack = [p] [m00] ... [m22]
     pub fn init_constants(&mut self) -> &mut Self {
-        let mds = get_mds_bytes();
+        let mds = get_mds_scriptnums();
         
         // Push p first (will be at bottom)
         self.push_data(&PALLAS_MODULUS_BYTES);
         
         // Push MDS in order
-        for row in 0..3 {
-            for col in 0..3 {
-                self.push_data(&mds[row][col]);
+        for row in &mds {
+            for entry in row {
+                self.push_data(entry);
             }
         }
         
@@ -360,31 +587,41 @@ impl OptimizedScriptBuilder {
     /// S-box with p at given depth
     /// Stack: [...p at depth...] [x] → [...p...] [x^5]
     pub fn sbox_p_at(&mut self, p_depth: usize) -> &mut Self {
-        // x² = x * x mod p
-        self.dup();
-        self.dup();
-        self.mul();
-        self.pick(p_depth + 1);  // p is now 1 deeper due to x²
-        self.modulo();
-        
-        // x⁴ = x² * x² mod p  
-        self.dup();
+        self.pow_fixed_p_at(SBOX_EXPONENT, p_depth)
+    }
+
+    /// x^exp mod p for a constant exponent, by left-to-right square-and-multiply
+    /// Stack: [...p at depth...] [x] → [...p...] [x^exp]
+    ///
+    /// x stays under the accumulator for the multiply steps, so p sits one
+    /// deeper than `p_depth` throughout. Costs one squaring per bit after the
+    /// leading one plus one multiply per further set bit.
+    pub fn pow_fixed_p_at(&mut self, exp: u64, p_depth: usize) -> &mut Self {
+        if exp == 0 {
+            self.drop();
+            return self.push_number(1);
+        }
+
+        // [x] [acc = x]
         self.dup();
-        self.mul();
-        self.pick(p_depth + 2);  // p is now 2 deeper
-        self.modulo();
-        
-        // x⁵ = x⁴ * x mod p
-        self.roll(2);  // bring x to top
-        self.mul();
-        self.pick(p_depth + 1);
-        self.modulo();
-        
-        // Clean up x²
-        self.swap();
-        self.drop();
-        
-        self
+        for bit in (0..63 - exp.leading_zeros()).rev() {
+            // acc = acc² mod p
+            self.dup();
+            self.mul();
+            self.pick(p_depth + 1);
+            self.modulo();
+
+            // acc = acc * x mod p
+            if exp >> bit & 1 == 1 {
+                self.over();
+                self.mul();
+                self.pick(p_depth + 1);
+                self.modulo();
+            }
+        }
+
+        // Drop x
+        self.op(OP_NIP)
     }
 }
 
@@ -401,592 +638,364 @@ impl Default for OptimizedScriptBuilder {
 /// After init, stack is: [p] [m00..m22 = 9 elements] [s0] [s1] [s2]
 /// Indices from top: s2=0, s1=1, s0=2, m22=3, ..., m00=11, p=12
 const P_DEPTH: usize = 12;
-const M00_DEPTH: usize = 11;
-const M01_DEPTH: usize = 10;
-const M02_DEPTH: usize = 9;
-const M10_DEPTH: usize = 8;
-const M11_DEPTH: usize = 7;
-const M12_DEPTH: usize = 6;
-const M20_DEPTH: usize = 5;
-const M21_DEPTH: usize = 4;
-const M22_DEPTH: usize = 3;
+/// Items on the main stack at every round boundary
+const ROUND_STACK_DEPTH: usize = P_DEPTH + 1;
 
 /// Full round with constants on main stack
 /// Stack: [p, mds..., s0, s1, s2] → [p, mds..., s0', s1', s2']
 pub fn generate_full_round_opt(round: usize) -> Vec<u8> {
-    let mut b = OptimizedScriptBuilder::new();
-    
-    let rc0 = fp_to_bytes(&get_round_constant(round, 0));
-    let rc1 = fp_to_bytes(&get_round_constant(round, 1));
-    let rc2 = fp_to_bytes(&get_round_constant(round, 2));
-    
-    // Add round constants
-    // Stack: [...] [s0] [s1] [s2]
-    
-    // s2 += rc2
-    b.push_data(&rc2);
-    b.field_add_pick_p(P_DEPTH + 1);  // +1 because we pushed rc
-    
-    // s1 += rc1
-    b.swap();
-    b.push_data(&rc1);
-    b.field_add_pick_p(P_DEPTH + 1);
-    b.swap();
-    
-    // s0 += rc0
-    b.roll(2);
-    b.push_data(&rc0);
-    b.field_add_pick_p(P_DEPTH + 1);
-    b.roll(2);
-    b.roll(2);
-    
-    // S-box all three
-    // Stack: [...] [s0'] [s1'] [s2']
-    b.roll(2);                      // [...] [s1'] [s2'] [s0']
-    b.sbox_p_at(P_DEPTH);
-    b.roll(2);                      // [...] [s2'] [s0'^5] [s1']
-    b.sbox_p_at(P_DEPTH);
-    b.roll(2);                      // [...] [s0'^5] [s1'^5] [s2']
-    b.sbox_p_at(P_DEPTH);
-    
-    // MDS matrix multiply
-    generate_dense_mds(&mut b);
-    
-    b.build()
+    let templates = RoundTemplates::get();
+    templates.full.instantiate(&templates.constants[round])
 }
 
 /// Partial round: S-box only on s0
 pub fn generate_partial_round_opt(round: usize) -> Vec<u8> {
-    let mut b = OptimizedScriptBuilder::new();
-    
-    let rc0 = fp_to_bytes(&get_round_constant(round, 0));
-    let rc1 = fp_to_bytes(&get_round_constant(round, 1));
-    let rc2 = fp_to_bytes(&get_round_constant(round, 2));
-    
-    // Add round constants
-    b.push_data(&rc2);
-    b.field_add_pick_p(P_DEPTH + 1);
-    
-    b.swap();
-    b.push_data(&rc1);
-    b.field_add_pick_p(P_DEPTH + 1);
-    b.swap();
-    
-    b.roll(2);
-    b.push_data(&rc0);
-    b.field_add_pick_p(P_DEPTH + 1);
-    
-    // S-box only on s0 (now at top)
-    b.sbox_p_at(P_DEPTH);
-    
-    // Reorder
-    b.roll(2);
-    b.roll(2);  // [s0'^5] [s1'] [s2']
-    
-    // Sparse MDS (optimized for partial rounds)
-    generate_sparse_mds(&mut b);
-    
-    b.build()
+    let templates = RoundTemplates::get();
+    templates.partial.instantiate(&templates.constants[round])
 }
 
-/// Dense MDS: 9 multiplications
-/// Stack: [p, m00..m22, s0, s1, s2] → [p, m00..m22, o0, o1, o2]
-fn generate_dense_mds(b: &mut OptimizedScriptBuilder) {
-    // Save s0, s1, s2 to alt stack
-    b.to_alt();  // s2
-    b.to_alt();  // s1
-    b.to_alt();  // s0
-    
-    // Compute o0 = m00*s0 + m01*s1 + m02*s2
-    b.from_alt(); b.dup(); b.to_alt();  // get s0, keep copy
-    b.pick(M00_DEPTH - 3 + 1);          // m00 (adjusted for s's in alt)
-    b.mul();
-    b.pick(P_DEPTH - 3 + 1);
-    b.modulo();
-    
-    b.from_alt(); b.to_alt();           // rotate: s0 to bottom
-    b.from_alt(); b.dup(); b.to_alt();  // get s1
-    b.from_alt(); b.to_alt();           // put s0 back
-    b.pick(M01_DEPTH - 3 + 2);
-    b.mul();
-    b.pick(P_DEPTH - 3 + 2);
-    b.modulo();
-    b.add();
-    b.pick(P_DEPTH - 3 + 1);
-    b.modulo();
-    
-    // +m02*s2
-    b.from_alt(); b.to_alt();
-    b.from_alt(); b.to_alt();
-    b.from_alt(); b.dup(); b.to_alt();  // get s2
-    b.from_alt(); b.to_alt();
-    b.from_alt(); b.to_alt();
-    b.pick(M02_DEPTH - 3 + 3);
-    b.mul();
-    b.pick(P_DEPTH - 3 + 3);
-    b.modulo();
-    b.add();
-    b.pick(P_DEPTH - 3 + 2);
-    b.modulo();
-    // Stack: [..., o0]
-    
-    // o1 = m10*s0 + m11*s1 + m12*s2
-    b.from_alt(); b.to_alt(); b.from_alt(); b.to_alt(); b.from_alt();
-    b.dup(); b.to_alt(); b.from_alt(); b.to_alt(); b.from_alt(); b.to_alt();
-    b.pick(M10_DEPTH - 3 + 1);
-    b.mul();
-    b.pick(P_DEPTH - 3 + 2);
-    b.modulo();
-    
-    // ... (continuing pattern)
-    // For now, inline the computation
-    b.from_alt(); b.to_alt();
-    b.from_alt(); b.dup(); b.to_alt();
-    b.from_alt(); b.to_alt();
-    b.pick(M11_DEPTH - 3 + 2);
-    b.mul();
-    b.pick(P_DEPTH - 3 + 3);
-    b.modulo();
-    b.add();
-    b.pick(P_DEPTH - 3 + 2);
-    b.modulo();
-    
-    b.from_alt(); b.to_alt();
-    b.from_alt(); b.to_alt();
-    b.from_alt(); b.dup(); b.to_alt();
-    b.from_alt(); b.to_alt();
-    b.from_alt(); b.to_alt();
-    b.pick(M12_DEPTH - 3 + 3);
-    b.mul();
-    b.pick(P_DEPTH - 3 + 4);
-    b.modulo();
-    b.add();
-    b.pick(P_DEPTH - 3 + 3);
-    b.modulo();
-    // Stack: [..., o0, o1]
-    
-    // o2 = m20*s0 + m21*s1 + m22*s2 (consume alt stack values)
-    b.from_alt();  // s0
-    b.pick(M20_DEPTH - 3 + 2);
-    b.mul();
-    b.pick(P_DEPTH - 3 + 3);
-    b.modulo();
-    
-    b.from_alt();  // s1
-    b.pick(M21_DEPTH - 3 + 2);
-    b.mul();
-    b.pick(P_DEPTH - 3 + 4);
-    b.modulo();
-    b.add();
-    b.pick(P_DEPTH - 3 + 3);
-    b.modulo();
-    
-    b.from_alt();  // s2
-    b.pick(M22_DEPTH - 3 + 2);
-    b.mul();
-    b.pick(P_DEPTH - 3 + 4);
-    b.modulo();
-    b.add();
-    b.pick(P_DEPTH - 3 + 3);
-    b.modulo();
-    // Stack: [p, mds..., o0, o1, o2]
+/// Script-number encodings of the three round constants of `round`
+fn round_constant_scriptnums(round: usize) -> [Vec<u8>; 3] {
+    [0, 1, 2].map(|lane| fp_to_scriptnum_bytes(&get_round_constant(round, lane)))
 }
 
-/// Sparse MDS: Only 5 multiplications for partial rounds
-/// o0 = m00*s0 + m01*s1 + m02*s2  (3 muls)
-/// o1 = m10*s0 + s1               (1 mul)
-/// o2 = m20*s0 + s2               (1 mul)
-fn generate_sparse_mds(b: &mut OptimizedScriptBuilder) {
-    // Save s0, s1, s2
-    b.to_alt();  // s2
-    b.to_alt();  // s1
-    b.to_alt();  // s0
-    
-    // o0 = m00*s0 + m01*s1 + m02*s2 (same as dense, 3 muls)
-    b.from_alt(); b.dup(); b.to_alt();
-    b.pick(M00_DEPTH - 3 + 1);
-    b.mul();
-    b.pick(P_DEPTH - 3 + 1);
-    b.modulo();
-    
-    b.from_alt(); b.to_alt();
-    b.from_alt(); b.dup(); b.to_alt();
-    b.from_alt(); b.to_alt();
-    b.pick(M01_DEPTH - 3 + 2);
-    b.mul();
-    b.pick(P_DEPTH - 3 + 2);
-    b.modulo();
-    b.add();
-    b.pick(P_DEPTH - 3 + 1);
-    b.modulo();
-    
-    b.from_alt(); b.to_alt();
-    b.from_alt(); b.to_alt();
-    b.from_alt(); b.dup(); b.to_alt();
-    b.from_alt(); b.to_alt();
-    b.from_alt(); b.to_alt();
-    b.pick(M02_DEPTH - 3 + 3);
-    b.mul();
-    b.pick(P_DEPTH - 3 + 3);
-    b.modulo();
-    b.add();
-    b.pick(P_DEPTH - 3 + 2);
-    b.modulo();
-    // o0 done
-    
-    // o1 = m10*s0 + s1 (just 1 mul!)
-    b.from_alt(); b.to_alt();
-    b.from_alt(); b.to_alt();
-    b.from_alt(); b.dup(); b.to_alt();
-    b.from_alt(); b.to_alt();
-    b.from_alt(); b.to_alt();
-    b.pick(M10_DEPTH - 3 + 2);
-    b.mul();
-    b.pick(P_DEPTH - 3 + 3);
-    b.modulo();
-    
-    // + s1
-    b.from_alt(); b.to_alt();
-    b.from_alt(); b.dup(); b.to_alt();
-    b.from_alt(); b.to_alt();
-    b.add();
-    b.pick(P_DEPTH - 3 + 2);
-    b.modulo();
-    // o1 done
-    
-    // o2 = m20*s0 + s2 (just 1 mul!)
-    b.from_alt();  // s0 (consume)
-    b.pick(M20_DEPTH - 3 + 2);
-    b.mul();
-    b.pick(P_DEPTH - 3 + 3);
-    b.modulo();
-    
-    // + s2
-    b.from_alt();  // s1 (discard)
-    b.drop();
-    b.from_alt();  // s2 (consume)
-    b.add();
-    b.pick(P_DEPTH - 3 + 2);
-    b.modulo();
-    // o2 done
-    
-    // Stack: [p, mds..., o0, o1, o2]
+// ============================================================================
+// ROUND TEMPLATES
+// ============================================================================
+
+/// Constant-free round skeleton plus the offsets where constants are spliced in.
+/// Full rounds differ only in their three embedded constants and partial
+/// rounds likewise, so one skeleton per round type serves all 64 rounds.
+#[derive(Clone, Debug)]
+pub struct RoundTemplate {
+    skeleton: Vec<u8>,
+    /// (offset into skeleton, constant lane), ascending by offset
+    holes: Vec<(usize, usize)>,
+}
+
+impl RoundTemplate {
+    fn record(round_type: RoundType) -> Self {
+        let mut b = OptimizedScriptBuilder::with_depth(ROUND_STACK_DEPTH).checked();
+        let mut holes = Vec::new();
+        emit_plain_round_with(&mut b, round_type, |b, lane| {
+            holes.push((b.size(), lane));
+            b.assume_pushed(1);
+        });
+        b.assert_depth(ROUND_STACK_DEPTH);
+        let skeleton = b.try_build().expect("round skeleton keeps the round stack depth");
+        Self { skeleton, holes }
+    }
+
+    /// Splice pushes of `constants` into a copy of the skeleton
+    pub fn instantiate(&self, constants: &[Vec<u8>; 3]) -> Vec<u8> {
+        let mut script = Vec::with_capacity(self.skeleton.len() + self.holes.len() * 33);
+        let mut cursor = 0;
+        for &(offset, lane) in &self.holes {
+            script.extend_from_slice(&self.skeleton[cursor..offset]);
+            script.extend(push_bytes(&constants[lane]));
+            cursor = offset;
+        }
+        script.extend_from_slice(&self.skeleton[cursor..]);
+        script
+    }
+
+    pub fn skeleton_len(&self) -> usize {
+        self.skeleton.len()
+    }
+}
+
+/// Lazily built, process-wide cache of round skeletons and encoded constants
+#[derive(Clone, Debug)]
+pub struct RoundTemplates {
+    pub full: RoundTemplate,
+    pub partial: RoundTemplate,
+    /// Script-number round constants for all 64 rounds
+    pub constants: Vec<[Vec<u8>; 3]>,
+}
+
+impl RoundTemplates {
+    pub fn get() -> &'static RoundTemplates {
+        static TEMPLATES: OnceLock<RoundTemplates> = OnceLock::new();
+        TEMPLATES.get_or_init(Self::build)
+    }
+
+    fn build() -> Self {
+        Self {
+            full: RoundTemplate::record(RoundType::Full),
+            partial: RoundTemplate::record(RoundType::Partial),
+            constants: (0..TOTAL_ROUNDS).map(round_constant_scriptnums).collect(),
+        }
+    }
 }
 
 // ============================================================================
 // FULL POSEIDON SCRIPT
 // ============================================================================
 
-/// Generate optimized Poseidon script (old style with embedded constants)
+/// The full permutation with embedded constants, spliced from the round
+/// templates. Stack: [s0] [s1] [s2] → [s0'] [s1'] [s2']
 pub fn generate_poseidon_script_opt() -> Vec<u8> {
-    let mut b = OptimizedScriptBuilder::new();
-    
-    // Push constants once
+    let mut b = OptimizedScriptBuilder::with_depth(3).checked();
+    b.to_alt().to_alt().to_alt();
     b.init_constants();
-    
-    let mut script = b.build();
-    
-    // 4 full + 56 partial + 4 full
-    for r in 0..4 { script.extend(generate_full_round_opt(r)); }
-    for r in 4..60 { script.extend(generate_partial_round_opt(r)); }
-    for r in 60..64 { script.extend(generate_full_round_opt(r)); }
-    
+    b.from_alt().from_alt().from_alt();
+    let mut script = b.try_build().expect("constants go under the state");
+    for (round, round_type) in round_types() {
+        script.extend(match round_type {
+            RoundType::Full => generate_full_round_opt(round),
+            RoundType::Partial => generate_partial_round_opt(round),
+        });
+    }
+    let mut b = OptimizedScriptBuilder::with_depth(ROUND_STACK_DEPTH).checked();
+    b.to_alt().to_alt().to_alt();
+    for _ in 0..5 {
+        b.op(OP_2DROP);
+    }
+    b.from_alt().from_alt().from_alt();
+    b.assert_depth(3);
+    script.extend(b.try_build().expect("cleanup leaves the state"));
     script
 }
 
 // ============================================================================
-// WITNESS PATTERN ARCHITECTURE
+// SPONGE SECTIONS
 // ============================================================================
 
-/// Generate the LOCKING SCRIPT for witness pattern.
-/// 
-/// This script:
-/// 1. Expects constants blob on stack (from unlocking script)
-/// 2. Hashes the blob and verifies against hardcoded hash
-/// 3. Uses constants from stack via PICK for Poseidon computation
-/// 4. Verifies computed hash matches expected value
-///
-/// Stack input (from unlocking script):
-///   [constants_blob] [s0] [s1] [s2] [expected_hash]
+/// Two-to-one Poseidon hash as a self-contained script section.
 ///
-/// The constants_blob contains (in order):
-///   - Modulus p (32 bytes)
-///   - MDS matrix (9 × 32 = 288 bytes)  
-///   - Full round constants (8 × 3 × 32 = 768 bytes)
-///   - Partial round constants (56 × 32 = 1792 bytes, fused)
+/// Stack: [a] [b] → [H(a, b)], where H is `PoseidonHash::hash`, i.e. lane 0
+/// of the permutation of [a, b, 0]. Runs the plain (unfused) rounds over the
+/// `init_constants` layout, so only p and the MDS matrix are shared; the
+/// round constants are embedded. The alt stack is used as scratch but left
+/// as it was found, so the section can sit inside transcript sections that
+/// keep their state there.
+pub fn generate_poseidon_hash2_script() -> Vec<u8> {
+    let mut b = OptimizedScriptBuilder::with_depth(2);
+
+    // [a] [b] [0] under the constants
+    b.push_data(&[]);
+    b.to_alt().to_alt().to_alt();
+    b.init_constants();
+    b.from_alt().from_alt().from_alt();
+
+    for (round, round_type) in round_types() {
+        emit_plain_round(&mut b, round, round_type);
+    }
+
+    // Keep o0, drop o1, o2 and the constants
+    b.drop().drop().to_alt();
+    for _ in 0..5 {
+        b.op(OP_2DROP);
+    }
+    b.from_alt();
+    b.assert_depth(1);
+    b.build()
+}
+
+// ============================================================================
+// ROUND DEBUGGING
+// ============================================================================
+// Test-only: these follow the unfused hash2 rounds, so they do not exercise
+// the fused generators, and they pull in the interpreter.
+
+/// The hash2 section's permutation cut short after `upto_round` rounds.
 ///
-/// Total blob: 32 + 288 + 768 + 1792 = 2880 bytes
-pub fn generate_witness_locking_script() -> Vec<u8> {
-    let mut script = Vec::with_capacity(3500);
-    
-    // === PHASE 1: Verify constants blob hash ===
-    // Stack: [constants_blob] [s0] [s1] [s2] [expected]
-    
-    // Save state and expected to alt
-    script.push(OP_TOALTSTACK);  // expected → alt
-    script.push(OP_TOALTSTACK);  // s2 → alt
-    script.push(OP_TOALTSTACK);  // s1 → alt
-    script.push(OP_TOALTSTACK);  // s0 → alt
-    // Stack: [constants_blob]   Alt: [expected, s2, s1, s0]
-    
-    // Hash the blob
-    script.push(OP_SHA256);
-    // Stack: [hash(blob)]
-    
-    // Push expected constants hash and verify
-    let constants_hash = get_constants_hash();
-    script.extend(push_bytes(&constants_hash[..]));
-    script.push(OP_EQUALVERIFY);
-    // Stack: []   (verification passed)
-    
-    // === PHASE 2: Parse constants blob ===
-    // The blob was consumed by hashing. We need a different approach:
-    // The unlocking script should push constants INDIVIDUALLY, not as blob.
-    //
-    // Revised architecture:
-    // Unlocking script pushes: [p] [m00..m22] [rc_full_0..rc_full_23] [rc_partial_0..55] [s0] [s1] [s2] [expected]
-    // Locking script verifies hash of the constant portion, then computes.
-    
-    // For now, generate the LOGIC-ONLY portion (assumes constants on stack)
-    // Stack layout after setup: [p] [mds×9] [s0] [s1] [s2]
-    
-    // Restore state from alt
-    script.push(OP_FROMALTSTACK);  // s0
-    script.push(OP_FROMALTSTACK);  // s1
-    script.push(OP_FROMALTSTACK);  // s2
-    script.push(OP_FROMALTSTACK);  // expected → keep on stack for later
-    script.push(OP_TOALTSTACK);    // expected back to alt for now
-    
-    // === PHASE 3: Poseidon computation (logic only, no embedded constants) ===
-    // Generate round logic that uses PICK to get constants
-    
-    // For each round, the round constants are at known stack positions
-    // This is the key optimization: logic only, no 33-byte pushes
-    
-    script.extend(generate_witness_poseidon_logic());
-    
-    // === PHASE 4: Final verification ===
-    // Stack: [p] [mds] [rc...] [h0] [h1] [h2]
-    // Alt: [expected]
-    
-    // Extract h0 (the hash output)
-    script.push(OP_DROP);  // drop h2
-    script.push(OP_DROP);  // drop h1
-    // Stack: [...] [h0]
-    
-    script.push(OP_FROMALTSTACK);  // get expected
-    script.push(OP_EQUALVERIFY);   // verify h0 == expected
-    
-    // Success: clean up remaining constants (or leave for spending flexibility)
-    
-    script
+/// Stack: [s0] [s1] [s2] → [s0'] [s1'] [s2'], the state after rounds
+/// 0..upto_round; zero rounds returns the input. Lets a failing permutation
+/// be bisected without hand-truncating scripts.
+#[cfg(test)]
+pub(crate) fn generate_poseidon_debug_script(upto_round: usize) -> Vec<u8> {
+    let mut b = OptimizedScriptBuilder::with_depth(3);
+    b.to_alt().to_alt().to_alt();
+    b.init_constants();
+    b.from_alt().from_alt().from_alt();
+    for (round, round_type) in round_types().take(upto_round) {
+        emit_plain_round(&mut b, round, round_type);
+    }
+    b.to_alt().to_alt().to_alt();
+    for _ in 0..5 {
+        b.op(OP_2DROP);
+    }
+    b.from_alt().from_alt().from_alt();
+    b.assert_depth(3);
+    b.build()
 }
 
-/// Generate Poseidon logic that assumes constants are on stack
-/// Uses PICK to reference constants instead of embedding them
-fn generate_witness_poseidon_logic() -> Vec<u8> {
-    let mut script = Vec::with_capacity(2500);
-    
-    // Stack layout:
-    // [p] [m00..m22] [rc_f0_0..rc_f0_2] ... [rc_f7_0..rc_f7_2] [rc_p0..rc_p55] [s0] [s1] [s2]
-    //  0      1-9         10-12        ...       31-33            34-89         90  91  92
-    
-    // For each round, we need to:
-    // 1. PICK the round constants
-    // 2. Add to state
-    // 3. S-box
-    // 4. MDS (PICK matrix elements)
-    
-    // This is significantly more complex stack management
-    // For now, generate a simplified version
-    
-    // The key insight: each PICK is 2 bytes, much smaller than 33-byte push
-    
-    // Generate 8 full rounds + 56 partial rounds + optimized MDS
-    for round in 0..64 {
-        if round < 4 || round >= 60 {
-            script.extend(generate_witness_full_round(round));
-        } else {
-            script.extend(generate_witness_partial_round(round));
+/// Reference state after each of the 64 rounds of the unfused permutation,
+/// `[i]` being the state once round i has run
+#[cfg(test)]
+pub(crate) fn debug_round_states(state: [Fp; 3]) -> Vec<[Fp; 3]> {
+    let mds = get_mds_fp();
+    let mut s = state;
+    round_types()
+        .map(|(round, round_type)| {
+            for (lane, x) in s.iter_mut().enumerate() {
+                *x += get_round_constant(round, lane);
+                if round_type.is_full() || lane == 0 {
+                    *x = sbox(*x);
+                }
+            }
+            s = mds_mul(&mds, &s);
+            s
+        })
+        .collect()
+}
+
+/// First round whose on-chain state differs from the reference
+#[cfg(test)]
+#[derive(Debug)]
+pub(crate) struct RoundDivergence {
+    pub round: usize,
+    pub round_type: RoundType,
+    pub expected: [Fp; 3],
+    /// Stack the debug script left, or the error it stopped with
+    pub got: std::result::Result<Vec<Vec<u8>>, VmError>,
+}
+
+#[cfg(test)]
+impl std::fmt::Display for RoundDivergence {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let kind = match self.round_type {
+            RoundType::Full => "full",
+            RoundType::Partial => "partial",
+        };
+        write!(f, "diverged at round {} ({}), expected {:?}, got ", self.round, kind, self.expected)?;
+        match &self.got {
+            Ok(stack) => write!(f, "{:?}", stack.iter().map(to_hex).collect::<Vec<_>>()),
+            Err(err) => write!(f, "{:?}", err),
         }
     }
-    
-    script
 }
 
-/// Full round using witness constants (PICK-based)
-fn generate_witness_full_round(round: usize) -> Vec<u8> {
-    let mut b = OptimizedScriptBuilder::new();
-    
-    // Calculate PICK indices for this round's constants
-    // This depends on the exact stack layout
-    // For now, use a simplified model
-    
-    // The round constant positions depend on how many are above state
-    // state is [s0, s1, s2] at top, so:
-    // - s2 is at index 0
-    // - s1 is at index 1  
-    // - s0 is at index 2
-    // - constants start at index 3
-    
-    // For full rounds 0-3: rc at positions 3 + round*3 + {0,1,2}
-    // For full rounds 60-63: need to account for partial constants
-    
-    // Simplified: just generate the logic structure
-    // Actual positions will be computed at generation time
-    
-    let base_idx = if round < 4 {
-        3 + round * 3
-    } else {
-        3 + 4 * 3 + 56 + (round - 60) * 3
+/// Binary-search the first round after which `unlock` (pushing the initial
+/// [s0] [s1] [s2]) followed by the debug script disagrees with `reference`,
+/// as produced by `debug_round_states`. Each probe runs on a fresh
+/// interpreter from `interpreter`. Assumes a divergence persists once it
+/// appears, which holds for a permutation.
+#[cfg(test)]
+pub(crate) fn find_divergent_round(
+    interpreter: impl Fn() -> Interpreter,
+    unlock: &[u8],
+    reference: &[[Fp; 3]],
+) -> Option<RoundDivergence> {
+    let run = |rounds: usize| {
+        let mut vm = interpreter();
+        let mut script = unlock.to_vec();
+        script.extend(generate_poseidon_debug_script(rounds));
+        vm.eval(&script).map(|()| vm.stack().to_vec())
     };
-    
-    // Add round constants using PICK
-    // s2 += rc2
-    b.pick(base_idx + 2 + 2);  // +2 for s0,s1 below
-    b.add();
-    b.pick(12 + base_idx);  // p is deeper
-    b.modulo();
-    
-    // s1 += rc1
-    b.swap();
-    b.pick(base_idx + 1 + 2);
-    b.add();
-    b.pick(12 + base_idx);
-    b.modulo();
-    b.swap();
-    
-    // s0 += rc0
-    b.roll(2);
-    b.pick(base_idx + 0 + 2);
-    b.add();
-    b.pick(12 + base_idx);
-    b.modulo();
-    b.roll(2);
-    b.roll(2);
-    
-    // S-boxes (reuse existing logic)
-    b.roll(2);
-    b.sbox_p_at(12 + base_idx);
-    b.roll(2);
-    b.sbox_p_at(12 + base_idx);
-    b.roll(2);
-    b.sbox_p_at(12 + base_idx);
-    
-    // MDS using PICK for matrix elements
-    generate_witness_mds(&mut b, base_idx);
-    
-    b.build()
+    let matches = |rounds: usize| match run(rounds) {
+        Ok(stack) => {
+            let expected = &reference[rounds - 1];
+            stack.len() == 3
+                && stack.iter().zip(expected).all(|(got, want)| decode_num(got) == decode_num(&fp_to_scriptnum_bytes(want)))
+        }
+        Err(_) => false,
+    };
+    // Smallest round count whose state mismatches
+    let (mut lo, mut hi) = (1, reference.len().min(TOTAL_ROUNDS) + 1);
+    while lo < hi {
+        let mid = (lo + hi) / 2;
+        if matches(mid) {
+            lo = mid + 1;
+        } else {
+            hi = mid;
+        }
+    }
+    let rounds = reference.len().min(TOTAL_ROUNDS);
+    if lo > rounds {
+        return None;
+    }
+    let round = lo - 1;
+    Some(RoundDivergence { round, round_type: round_type(round), expected: reference[round], got: run(lo) })
 }
 
-/// Partial round using witness constants
-fn generate_witness_partial_round(round: usize) -> Vec<u8> {
+/// Read the top item as an unsigned little-endian integer, reduced mod p.
+///
+/// Stack: [x] → [x mod p]. Leaves canonical script numbers and LE reprs of
+/// field elements unchanged in value, and keeps 32-byte digests with the
+/// top bit set from reading as negative.
+pub fn generate_reduce_script() -> Vec<u8> {
     let mut b = OptimizedScriptBuilder::new();
-    
-    // Partial rounds only need c0 (fused constants)
-    // Position: 3 + 4*3 + (round - 4) = 15 + round - 4 = 11 + round
-    let c0_idx = 3 + 12 + (round - 4);  // 12 = 4 full rounds × 3 constants
-    
-    // Only add c0 (fused constant handles c1, c2 contribution)
-    b.roll(2);  // bring s0 to top
-    b.pick(c0_idx + 2);
-    b.add();
-    b.pick(12 + c0_idx);  // p
-    b.modulo();
-    
-    // S-box only on s0
-    b.sbox_p_at(12 + c0_idx);
-    
-    // Reorder
-    b.roll(2);
-    b.roll(2);
-    
-    // Sparse MDS
-    generate_witness_sparse_mds(&mut b, c0_idx);
-    
+    b.push_data(&[0x00]).op(OP_CAT);
+    b.push_data(&PALLAS_MODULUS_BYTES).modulo();
     b.build()
 }
 
-/// Dense MDS using PICK for witness constants
-fn generate_witness_mds(b: &mut OptimizedScriptBuilder, _base_idx: usize) {
-    // MDS elements are at fixed positions: 1-9 from bottom
-    // After accounting for state on top, they're at indices 3+...
-    
-    // Simplified: use the same logic as before but with PICK
-    // The MDS positions are fixed regardless of round
-    
-    let m_base = 3;  // MDS starts at index 3 (after p at 0, before rc)
-    
-    b.to_alt(); b.to_alt(); b.to_alt();  // save state
-    
-    // o0 = m00*s0 + m01*s1 + m02*s2
-    b.from_alt(); b.dup(); b.to_alt();
-    b.pick(m_base + 0);  // m00
-    b.mul();
-    b.pick(0);  // p - this needs adjustment
-    b.modulo();
-    
-    // Continue pattern... (abbreviated for clarity)
-    // The full implementation would mirror generate_dense_mds
-    // but use PICK indices instead of embedded constants
-    
-    b.from_alt(); b.from_alt(); b.from_alt();  // restore for now
-}
-
-/// Sparse MDS using PICK
-fn generate_witness_sparse_mds(b: &mut OptimizedScriptBuilder, _base_idx: usize) {
-    // Same as dense but only 5 multiplications
-    let m_base = 3;
-    
-    b.to_alt(); b.to_alt(); b.to_alt();
-    
-    // o0 = m00*s0 + m01*s1 + m02*s2
-    b.from_alt(); b.dup(); b.to_alt();
-    b.pick(m_base + 0);
-    b.mul();
-    b.pick(0);
-    b.modulo();
-    
-    // Abbreviated...
-    b.from_alt(); b.from_alt(); b.from_alt();
+/// Unfused round with its three constants embedded, as run by the hash2
+/// section. Stack: [p, m00..m22, s0, s1, s2] → [p, m00..m22, s0', s1', s2']
+fn emit_plain_round(b: &mut OptimizedScriptBuilder, round: usize, round_type: RoundType) {
+    let templates = RoundTemplates::get();
+    emit_plain_round_with(b, round_type, |b, lane| {
+        b.push_data(&templates.constants[round][lane]);
+    });
 }
 
-/// Generate the UNLOCKING SCRIPT that provides constants
-pub fn generate_witness_unlocking_script(state: [Fp; 3], expected: Fp) -> Vec<u8> {
-    let fused = FusedPoseidonConstants::compute();
-    let mut script = Vec::with_capacity(4096);
-    
-    // Push modulus
-    script.extend(push_bytes(&PALLAS_MODULUS_BYTES));
-    
-    // Push MDS matrix (9 elements)
-    for row in &fused.mds {
-        for elem in row {
-            script.extend(push_bytes(&fp_to_bytes(elem)));
+/// `emit_plain_round` with each lane's constant put on top of the stack by
+/// `constant` instead of embedded
+fn emit_plain_round_with(
+    b: &mut OptimizedScriptBuilder,
+    round_type: RoundType,
+    mut constant: impl FnMut(&mut OptimizedScriptBuilder, usize),
+) {
+    b.assert_depth(ROUND_STACK_DEPTH);
+    // Three ROLL 2s visit s0, s1, s2 in turn and restore the order
+    for lane in 0..3 {
+        b.roll(2);
+        constant(b, lane);
+        b.field_add_pick_p(P_DEPTH);
+        if round_type.is_full() || lane == 0 {
+            b.sbox_p_at(P_DEPTH);
         }
     }
-    
-    // Push full round constants (8 × 3 = 24)
-    for rc in &fused.full_round_constants {
-        for elem in rc {
-            script.extend(push_bytes(&fp_to_bytes(elem)));
+    emit_mds_pick(b);
+}
+
+/// Dense MDS reading every state lane and matrix entry by PICK
+/// Stack: [p, m00..m22, s0, s1, s2] → [p, m00..m22, o0, o1, o2]
+fn emit_mds_pick(b: &mut OptimizedScriptBuilder) {
+    for row in 0..3 {
+        // Products are summed unreduced (< 3p²) and reduced once per row
+        for col in 0..3 {
+            let acc = (col > 0) as usize;
+            b.pick(row + acc + 2 - col);
+            let above = 3 + row + acc + 1;
+            b.pick(above + 8 - (3 * row + col));
+            b.mul();
+            if col > 0 {
+                b.add();
+            }
         }
+        b.pick(3 + row + 1 + 9);
+        b.modulo();
     }
-    
-    // Push partial round constants (56, fused)
-    for c0 in &fused.partial_round_c0 {
-        script.extend(push_bytes(&fp_to_bytes(c0)));
-    }
-    
-    // Push state [s0, s1, s2]
-    script.extend(push_bytes(&fp_to_bytes(&state[0])));
-    script.extend(push_bytes(&fp_to_bytes(&state[1])));
-    script.extend(push_bytes(&fp_to_bytes(&state[2])));
-    
-    // Push expected hash
-    script.extend(push_bytes(&fp_to_bytes(&expected)));
-    
-    script
+    // [s0, s1, s2, o0, o1, o2] → [o0, o1, o2]
+    b.to_alt().to_alt().to_alt();
+    b.op(OP_2DROP).drop();
+    b.from_alt().from_alt().from_alt();
+}
+
+// ============================================================================
+// WITNESS PATTERN ARCHITECTURE
+// ============================================================================
+
+/// Locking script of the witness pattern: the permutation check of
+/// `generate_poseidon_verification` with the round constants supplied by the
+/// spender instead of embedded.
+///
+/// Stack input (from unlocking script):
+///   [constants_blob] [s0] [s1] [s2] [expected]
+///
+/// The blob is `round_constants_blob()`: 64 rounds × 3 lanes of 32-byte LE
+/// constants, 6144 bytes, checked against its SHA-256 before the rounds
+/// split constants off its front. p and the MDS matrix stay embedded.
+pub fn generate_witness_locking_script() -> Vec<u8> {
+    generate_poseidon_verification(StrategyChoice::WitnessConstants).lock
+}
+
+/// Generate the UNLOCKING SCRIPT that provides constants
+pub fn generate_witness_unlocking_script(state: [Fp; 3], expected: Fp) -> Vec<u8> {
+    generate_poseidon_verification(StrategyChoice::WitnessConstants).unlock(state, expected)
 }
 
 // ============================================================================
@@ -1026,32 +1035,15 @@ pub fn estimate_witness_lock_size() -> usize {
 }
 
 pub fn estimate_witness_unlock_size() -> usize {
-    let fused = FusedPoseidonConstants::compute();
-    
-    // Each 32-byte push is 33 bytes (1 length + 32 data)
-    let num_constants = 1 + 9 + 24 + 56;  // p + mds + full_rc + partial_rc
-    let state_and_expected = 4;
-    
-    (num_constants + state_and_expected) * 33
+    // Blob, three state lanes and the expected lane 0, each lane at most a
+    // 32-byte repr plus a sign byte
+    push_size(ROUND_CONSTANTS_BLOB_SIZE) + 4 * push_size(FIELD_BYTES + 1)
 }
 
 // ============================================================================
 // SECURITY CHECKS
 // ============================================================================
 
-/// Generate canonical check: Verify top stack element < p
-/// Stack: [x] -> [x] (passes if x < p, fails otherwise)
-pub fn generate_canonical_check() -> Vec<u8> {
-    let mut b = OptimizedScriptBuilder::new();
-    
-    // Check against modulus
-    b.dup();
-    b.push_data(&PALLAS_MODULUS_BYTES);
-    b.less_than();
-    b.verify();
-    
-    b.build()
-}
 
 /// GENERATE SECURE WITNESS VERIFICATION (Hardened)
 /// 
@@ -1073,6 +1065,214 @@ pub fn generate_secure_witness_verification() -> Vec<u8> {
     script
 }
 
+// ============================================================================
+// STRATEGY SELECTION
+// ============================================================================
+
+/// Where a Poseidon check keeps its 192 round constants
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum StrategyChoice {
+    /// Pushed by the locking script: larger lock, nothing extra to unlock
+    EmbeddedConstants,
+    /// Pushed by every spend as one blob the lock checks by hash: smaller
+    /// lock, `ROUND_CONSTANTS_BLOB_SIZE` more bytes per spend
+    WitnessConstants,
+}
+
+/// Round constants as fixed 32-byte LE reprs, round by round and lane by lane
+pub const ROUND_CONSTANTS_BLOB_SIZE: usize = TOTAL_ROUNDS * 3 * FIELD_BYTES;
+
+/// Locking script and the constant part of the unlocking script for one
+/// `StrategyChoice`
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PoseidonScripts {
+    /// Stack: [template items] [s0] [s1] [s2] [expected] → [], failing
+    /// unless lane 0 of the permutation of [s0, s1, s2] equals expected
+    pub lock: Vec<u8>,
+    /// Pushed ahead of the per-spend state; empty for embedded constants
+    pub unlock_template: Vec<u8>,
+}
+
+impl PoseidonScripts {
+    /// Full unlocking script for `state`, claiming lane 0 is `expected`
+    pub fn unlock(&self, state: [Fp; 3], expected: Fp) -> Vec<u8> {
+        let mut script = self.unlock_template.clone();
+        for element in state.iter().chain([&expected]) {
+            script.extend(push_bytes(&fp_to_scriptnum_bytes(element)));
+        }
+        script
+    }
+}
+
+/// Picks the `StrategyChoice` with the lower fee over a `VerifierContract`
+/// output's lifetime: its lock is paid once, the unlock template on every spend
+#[cfg(feature = "contract")]
+pub struct PoseidonScriptStrategy;
+
+#[cfg(feature = "contract")]
+impl PoseidonScriptStrategy {
+    /// Cheaper strategy for a contract unrolled for `shape` and spent
+    /// `spend_count_estimate` times at `fee_rate` satoshis per 1000 bytes;
+    /// embedded constants on a tie
+    pub fn select(shape: TranscriptShape, spend_count_estimate: u64, fee_rate: u64) -> StrategyChoice {
+        let embedded = Self::lifetime_fee(StrategyChoice::EmbeddedConstants, shape, spend_count_estimate, fee_rate);
+        let witness = Self::lifetime_fee(StrategyChoice::WitnessConstants, shape, spend_count_estimate, fee_rate);
+        if witness < embedded {
+            StrategyChoice::WitnessConstants
+        } else {
+            StrategyChoice::EmbeddedConstants
+        }
+    }
+
+    /// Fee for the contract's lock plus `spend_count` unlock templates. The
+    /// witness-constants lock copies the one blob into each absorb, so its
+    /// saving grows with the shape while the template does not.
+    pub fn lifetime_fee(choice: StrategyChoice, shape: TranscriptShape, spend_count: u64, fee_rate: u64) -> u64 {
+        let params = ChainParams { default_fee_rate: fee_rate, ..ChainParams::mainnet() };
+        let template = generate_poseidon_verification(choice).unlock_template;
+        params.fee_for_size(contract_lock_size(choice, shape)) + spend_count * params.fee_for_size(template.len())
+    }
+}
+
+/// Round constants in the order the witness-constants lock consumes them
+pub fn round_constants_blob() -> Vec<u8> {
+    let mut blob = Vec::with_capacity(ROUND_CONSTANTS_BLOB_SIZE);
+    for round in 0..TOTAL_ROUNDS {
+        for lane in 0..3 {
+            blob.extend(fp_to_bytes(&get_round_constant(round, lane)));
+        }
+    }
+    blob
+}
+
+/// Poseidon permutation check for either strategy. Both run the unfused
+/// rounds of `generate_poseidon_hash2_script` and differ only in where each
+/// round constant comes from.
+pub fn generate_poseidon_verification(strategy: StrategyChoice) -> PoseidonScripts {
+    let witness = strategy == StrategyChoice::WitnessConstants;
+    let mut b = OptimizedScriptBuilder::with_depth(4 + witness as usize);
+    // expected, s2, s1, s0 to alt
+    b.to_alt().to_alt().to_alt().to_alt();
+    if witness {
+        // [blob] checked against its hash, then parked on alt under the
+        // state while the constants go in
+        b.dup().op(OP_SHA256);
+        b.push_data(&Sha256::digest(round_constants_blob()));
+        b.op(OP_EQUALVERIFY);
+    }
+    b.init_constants();
+    b.from_alt().from_alt().from_alt();
+    if witness {
+        b.roll(ROUND_STACK_DEPTH).to_alt();
+    }
+    for (round, round_type) in round_types() {
+        if witness {
+            // Each constant is split off the front of the blob on alt
+            emit_plain_round_with(&mut b, round_type, |b, _| {
+                b.from_alt();
+                b.push_number(FIELD_BYTES as i64);
+                b.op(OP_SPLIT).to_alt();
+            });
+        } else {
+            emit_plain_round(&mut b, round, round_type);
+        }
+    }
+    // Keep o0, drop o1, o2 and the constants
+    b.drop().drop().to_alt();
+    for _ in 0..5 {
+        b.op(OP_2DROP);
+    }
+    b.from_alt();
+    if witness {
+        // The exhausted blob
+        b.from_alt().drop();
+    }
+    b.from_alt().op(OP_NUMEQUALVERIFY);
+    b.assert_depth(0);
+    let unlock_template = if witness { push_bytes(&round_constants_blob()) } else { Vec::new() };
+    PoseidonScripts { lock: b.build(), unlock_template }
+}
+
+/// Transcript absorb reading its round constants from a copy of
+/// `round_constants_blob` instead of embedding them; the caller checks the
+/// blob's hash once and passes a copy per absorb, since the rounds consume it.
+/// Stack: [x] [blob], alt [state] → alt [hash2(state, x)]
+pub fn generate_poseidon_absorb_from_blob() -> Vec<u8> {
+    // The builder tracks alt from empty, so the state comes off first
+    let mut script = vec![OP_FROMALTSTACK];
+    let mut b = OptimizedScriptBuilder::with_depth(3);
+    // [blob] [state] [x], x reduced as `generate_reduce_script` does
+    b.roll(2);
+    b.push_data(&[0x00]).op(OP_CAT);
+    b.push_data(&PALLAS_MODULUS_BYTES).modulo();
+    // [blob] [state] [x] [0], the lanes parked while the constants go in
+    b.push_data(&[]);
+    b.to_alt().to_alt().to_alt();
+    b.init_constants();
+    b.from_alt().from_alt().from_alt();
+    b.roll(ROUND_STACK_DEPTH).to_alt();
+    for (_, round_type) in round_types() {
+        emit_plain_round_with(&mut b, round_type, |b, _| {
+            b.from_alt();
+            b.push_number(FIELD_BYTES as i64);
+            b.op(OP_SPLIT).to_alt();
+        });
+    }
+    // Keep o0, drop o1, o2, the constants and the exhausted blob
+    b.drop().drop().to_alt();
+    for _ in 0..5 {
+        b.op(OP_2DROP);
+    }
+    b.from_alt().from_alt().drop();
+    b.to_alt();
+    b.assert_depth(0);
+    script.extend(b.build());
+    script
+}
+
+/// Byte sizes behind `PoseidonScriptStrategy`, in the same model: the
+/// contract's lock is paid once, the witness pattern's unlock template on
+/// every spend
+#[cfg(feature = "contract")]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SavingsReport {
+    pub embedded_lock: usize,
+    pub witness_lock: usize,
+    /// Extra unlocking bytes each spend of the witness lock pushes
+    pub witness_unlock: usize,
+    /// Bytes the witness pattern saves on an output locked and spent once;
+    /// negative when the blob outweighs the smaller lock
+    pub net_savings_per_spend: i64,
+    /// Most spends the one-time lock saving covers: up to this many, the
+    /// witness pattern costs no more in total; 0 when it loses on the first
+    pub breakeven_spends: u64,
+}
+
+#[cfg(feature = "contract")]
+impl SavingsReport {
+    /// Bytes saved over an output's lifetime of `spend_count` spends
+    pub fn savings_after(&self, spend_count: u64) -> i64 {
+        self.embedded_lock as i64 - self.witness_lock as i64 - spend_count as i64 * self.witness_unlock as i64
+    }
+}
+
+/// Compares the `VerifierContract` locks the two strategies deploy for `shape`
+#[cfg(feature = "contract")]
+pub fn witness_pattern_savings(shape: TranscriptShape) -> SavingsReport {
+    let embedded_lock = contract_lock_size(StrategyChoice::EmbeddedConstants, shape);
+    let witness_lock = contract_lock_size(StrategyChoice::WitnessConstants, shape);
+    let witness_unlock = generate_poseidon_verification(StrategyChoice::WitnessConstants).unlock_template.len()
+        - generate_poseidon_verification(StrategyChoice::EmbeddedConstants).unlock_template.len();
+    let lock_savings = embedded_lock.saturating_sub(witness_lock);
+    SavingsReport {
+        embedded_lock,
+        witness_lock,
+        witness_unlock,
+        net_savings_per_spend: embedded_lock as i64 - witness_lock as i64 - witness_unlock as i64,
+        breakeven_spends: (lock_savings / witness_unlock.max(1)) as u64,
+    }
+}
+
 // ============================================================================
 // TESTS
 // ============================================================================
@@ -1080,7 +1280,6 @@ pub fn generate_secure_witness_verification() -> Vec<u8> {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use rand::Rng;
 
     #[test]
     fn test_fp_roundtrip() {
@@ -1093,6 +1292,291 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_scriptnum_encoding_matches_repr_value() {
+        use crate::ghost::script::vm::{decode_num, encode_num};
+        let mut rng = rand::thread_rng();
+        let samples = [Fp::ZERO, Fp::ONE, Fp::from(0x80u64), -Fp::ONE, Fp::random(&mut rng)];
+        for fp in samples {
+            let num = fp_to_scriptnum_bytes(&fp);
+            assert_eq!(decode_num(&num), decode_num(&fp_to_bytes(&fp)));
+            assert_eq!(encode_num(&decode_num(&num)), num);
+            assert_eq!(FieldEncoding::ScriptNum.encode(&fp), num);
+            assert_eq!(FieldEncoding::LeRepr.encode(&fp), fp_to_bytes(&fp).to_vec());
+        }
+        assert!(fp_to_scriptnum_bytes(&Fp::ZERO).is_empty());
+        assert_eq!(fp_to_scriptnum_bytes(&Fp::from(0x80u64)), vec![0x80, 0x00]);
+    }
+
+    fn run_canonical_check(x: &[u8]) -> Result<Vec<Vec<u8>>, crate::ghost::script::vm::VmError> {
+        let mut vm = crate::ghost::script::vm::Interpreter::new();
+        vm.eval(&push_bytes(x))?;
+        vm.eval(&generate_canonical_check())?;
+        Ok(vm.stack().to_vec())
+    }
+
+    #[test]
+    fn test_canonical_check_accepts_p_minus_one() {
+        let p_minus_one = -Fp::ONE;
+        for x in [fp_to_bytes(&p_minus_one).to_vec(), fp_to_scriptnum_bytes(&p_minus_one)] {
+            assert_eq!(run_canonical_check(&x).unwrap(), vec![x.clone()]);
+        }
+        assert!(run_canonical_check(&[]).is_ok());
+    }
+
+    #[test]
+    fn test_canonical_check_rejects_p_and_above() {
+        use crate::ghost::script::vm::VmErrorKind;
+        let mut p_plus_one = PALLAS_MODULUS_BYTES;
+        p_plus_one[0] += 1;
+        for x in [PALLAS_MODULUS_BYTES, p_plus_one] {
+            let err = run_canonical_check(&x).unwrap_err();
+            assert_eq!(err.kind, VmErrorKind::VerifyFailed);
+        }
+    }
+
+    #[test]
+    fn test_canonical_check_rejects_negative() {
+        // -1 would otherwise pass a bare x < p comparison
+        assert!(run_canonical_check(&[0x81]).is_err());
+        let mut negative_p_minus_one = fp_to_bytes(&-Fp::ONE);
+        negative_p_minus_one[FIELD_BYTES - 1] |= 0x80;
+        assert!(run_canonical_check(&negative_p_minus_one).is_err());
+    }
+
+    #[test]
+    fn test_modulus_byte_order_matches_repr() {
+        use crate::ghost::script::vm::execute;
+        assert!(bytes_to_fp(&PALLAS_MODULUS_BYTES).is_none());
+        // Both little-endian: p - 1 from the field, plus one, is the constant
+        let p_minus_one = fp_to_bytes(&-Fp::ONE);
+        assert_eq!(
+            BigUint::from_bytes_le(&p_minus_one) + 1u32,
+            BigUint::from_bytes_le(&PALLAS_MODULUS_BYTES)
+        );
+        // Under the script's numeric comparison p - 1 < p, and p is not < p
+        let less_than = |x: &[u8]| {
+            let mut script = push_bytes(x);
+            script.extend(push_bytes(&PALLAS_MODULUS_BYTES));
+            script.push(OP_LESSTHAN);
+            execute(&script).unwrap()
+        };
+        assert_eq!(less_than(&p_minus_one), vec![vec![1]]);
+        assert_eq!(less_than(&PALLAS_MODULUS_BYTES), vec![vec![]]);
+        // A big-endian p - 1 decodes to a different element
+        let mut reversed = p_minus_one;
+        reversed.reverse();
+        assert_ne!(bytes_to_fp(&reversed), Some(-Fp::ONE));
+    }
+
+    fn reference_round(round: usize) -> Vec<u8> {
+        let constants = round_constant_scriptnums(round);
+        let mut b = OptimizedScriptBuilder::new();
+        emit_plain_round_with(&mut b, round_type(round), |b, lane| {
+            b.push_data(&constants[lane]);
+        });
+        b.build()
+    }
+
+    #[test]
+    fn test_depth_tracking_rejects_wrong_pick() {
+        let mut b = OptimizedScriptBuilder::with_depth(3).checked();
+        b.pick(2).dup().to_alt();
+        assert_eq!(b.current_depth(), Some(4));
+        b.from_alt().assert_depth(5);
+        let offset = b.size();
+        b.pick(5);
+        assert_eq!(b.error(), Some(&StackDepthError::Underflow { offset, opcode: OP_PICK, depth: 5 }));
+        assert_eq!(b.current_depth(), None);
+        assert!(b.try_build().is_err());
+
+        let mut b = OptimizedScriptBuilder::with_depth(2).checked();
+        b.roll(1).add().from_alt();
+        assert!(matches!(b.try_build(), Err(StackDepthError::AltUnderflow { .. })));
+        let mut b = OptimizedScriptBuilder::with_depth(2).checked();
+        b.op(OP_2DROP).drop();
+        assert!(matches!(b.try_build(), Err(StackDepthError::Underflow { opcode: OP_DROP, depth: 0, .. })));
+        // Untracked builders never complain
+        let mut b = OptimizedScriptBuilder::new();
+        b.pick(40).assert_depth(7);
+        assert!(b.try_build().is_ok());
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    #[should_panic(expected = "stack depth error")]
+    fn test_depth_tracking_panics_unchecked() {
+        OptimizedScriptBuilder::with_depth(1).roll(1);
+    }
+
+    #[test]
+    fn test_depth_tracking_hash2_sections() {
+        let mut b = OptimizedScriptBuilder::with_depth(ROUND_STACK_DEPTH).checked();
+        emit_mds_pick(&mut b);
+        b.assert_depth(ROUND_STACK_DEPTH);
+        assert!(b.try_build().is_ok());
+        let mut b = OptimizedScriptBuilder::with_depth(1).checked();
+        b.sbox_p_at(0).assert_depth(1);
+        assert!(b.try_build().is_ok());
+    }
+
+    #[test]
+    fn test_round_generators_keep_depth_and_run() {
+        use crate::ghost::crypto::PoseidonHash;
+        use crate::ghost::script::vm::execute;
+        for round_type in [RoundType::Full, RoundType::Partial] {
+            let mut b = OptimizedScriptBuilder::with_depth(ROUND_STACK_DEPTH).checked();
+            emit_plain_round(&mut b, 0, round_type);
+            b.assert_depth(ROUND_STACK_DEPTH);
+            assert!(b.try_build().is_ok());
+        }
+        let input = [Fp::from(7u64), Fp::from(11u64), Fp::ZERO];
+        let as_nums = |items: &[Vec<u8>]| items.iter().map(|x| decode_num(x)).collect::<Vec<_>>();
+        let lanes = |state: &[Fp; 3]| as_nums(&state.map(|fp| fp_to_scriptnum_bytes(&fp)));
+        // Rounds 0-3 full and 4 partial over the constants layout
+        let mut b = OptimizedScriptBuilder::with_depth(3);
+        b.to_alt().to_alt().to_alt().init_constants().from_alt().from_alt().from_alt();
+        let mut script = push_state(&input);
+        script.extend(b.build());
+        for round in 0..4 {
+            script.extend(generate_full_round_opt(round));
+        }
+        script.extend(generate_partial_round_opt(4));
+        let stack = execute(&script).unwrap();
+        assert_eq!(stack.len(), ROUND_STACK_DEPTH);
+        assert_eq!(as_nums(&stack[P_DEPTH - 2..]), lanes(&debug_round_states(input)[4]));
+        // The whole permutation
+        let mut script = push_state(&input);
+        script.extend(generate_poseidon_script_opt());
+        let stack = execute(&script).unwrap();
+        assert_eq!(as_nums(&stack), lanes(debug_round_states(input).last().unwrap()));
+        // Witness pattern: blob and state pushed by the spender
+        let expected = PoseidonHash::hash(input[0], input[1]);
+        let mut script = generate_witness_unlocking_script(input, expected);
+        script.extend(generate_witness_locking_script());
+        assert_eq!(execute(&script).unwrap(), Vec::<Vec<u8>>::new());
+        let mut script = generate_witness_unlocking_script(input, expected + Fp::ONE);
+        script.extend(generate_witness_locking_script());
+        assert!(execute(&script).is_err());
+        assert!(generate_witness_unlocking_script(input, expected).len() <= estimate_witness_unlock_size());
+    }
+
+    fn push_state(state: &[Fp; 3]) -> Vec<u8> {
+        state.iter().flat_map(|fp| push_bytes(&fp_to_scriptnum_bytes(fp))).collect()
+    }
+
+    #[test]
+    fn test_debug_script_matches_reference_rounds() {
+        use crate::ghost::crypto::PoseidonHash;
+        use crate::ghost::script::vm::execute;
+        let (a, b) = (Fp::from(7u64), Fp::from(11u64));
+        let input = [a, b, Fp::ZERO];
+        let reference = debug_round_states(input);
+        assert_eq!(reference.len(), TOTAL_ROUNDS);
+        assert_eq!(reference[TOTAL_ROUNDS - 1][0], PoseidonHash::hash(a, b));
+        let run = |rounds: usize| {
+            let mut script = push_state(&input);
+            script.extend(generate_poseidon_debug_script(rounds));
+            execute(&script).unwrap()
+        };
+        assert_eq!(run(0), input.iter().map(fp_to_scriptnum_bytes).collect::<Vec<_>>());
+        for rounds in [1, 4, 5, 37] {
+            let expected: Vec<Vec<u8>> = reference[rounds - 1].iter().map(fp_to_scriptnum_bytes).collect();
+            let got = run(rounds);
+            assert_eq!(got.iter().map(|x| decode_num(x)).collect::<Vec<_>>(), expected.iter().map(|x| decode_num(x)).collect::<Vec<_>>(), "after {} rounds", rounds);
+        }
+    }
+
+    #[test]
+    fn test_find_divergent_round_reports_first_bad_round() {
+        let input = [Fp::from(3u64), Fp::from(5u64), Fp::ZERO];
+        let unlock = push_state(&input);
+        let mut reference = debug_round_states(input);
+        assert!(find_divergent_round(Interpreter::new, &unlock, &reference).is_none());
+        for state in &mut reference[37..] {
+            state[1] += Fp::ONE;
+        }
+        let divergence = find_divergent_round(Interpreter::new, &unlock, &reference).unwrap();
+        assert_eq!((divergence.round, divergence.round_type), (37, RoundType::Partial));
+        assert!(divergence.to_string().starts_with("diverged at round 37 (partial), expected "));
+        // A state the script cannot even start from is caught at round 0
+        let divergence = find_divergent_round(Interpreter::new, &[], &reference).unwrap();
+        assert_eq!(divergence.round, 0);
+        assert!(divergence.got.is_err());
+    }
+
+    #[test]
+    fn test_round_types_split() {
+        let types: Vec<(usize, RoundType)> = round_types().collect();
+        assert_eq!(types.len(), TOTAL_ROUNDS);
+        assert!(types.iter().enumerate().all(|(i, (round, _))| i == *round));
+        let full: Vec<usize> = types.iter().filter(|(_, t)| t.is_full()).map(|(r, _)| *r).collect();
+        assert_eq!(full, vec![0, 1, 2, 3, 60, 61, 62, 63]);
+        assert_eq!(types.iter().filter(|(_, t)| *t == RoundType::Partial).count(), 56);
+        assert_eq!(full.len(), FULL_ROUNDS);
+    }
+
+    /// Constant-free round skeletons and where each lane's constant goes;
+    /// any change to the emitted round code shows up here
+    const FULL_SKELETON: &str = "527a935c79977676955d799776955d799778955d799777527a935c79977676955d799776955d799778955d799777527a935c79977676955d799776955d799778955d79977752795c799552795c79959351795b7995935d799753795a799553795a7995935279597995935e799754795879955479587995935379577995935f79976b6b6b6d756c6c6c";
+    const PARTIAL_SKELETON: &str = "527a935c79977676955d799776955d799778955d799777527a935c7997527a935c799752795c799552795c79959351795b7995935d799753795a799553795a7995935279597995935e799754795879955479587995935379577995935f79976b6b6b6d756c6c6c";
+
+    #[test]
+    fn test_round_templates_golden() {
+        let frozen = |round_type: RoundType| match round_type {
+            RoundType::Full => (hex::decode(FULL_SKELETON).unwrap(), [2, 25, 48]),
+            RoundType::Partial => (hex::decode(PARTIAL_SKELETON).unwrap(), [2, 25, 31]),
+        };
+        for (round, round_type) in round_types() {
+            let (skeleton, offsets) = frozen(round_type);
+            let mut expected = Vec::new();
+            let mut cursor = 0;
+            for (lane, offset) in offsets.into_iter().enumerate() {
+                expected.extend_from_slice(&skeleton[cursor..offset]);
+                expected.extend(push_bytes(&fp_to_scriptnum_bytes(&get_round_constant(round, lane))));
+                cursor = offset;
+            }
+            expected.extend_from_slice(&skeleton[cursor..]);
+            let cached = match round_type {
+                RoundType::Full => generate_full_round_opt(round),
+                RoundType::Partial => generate_partial_round_opt(round),
+            };
+            assert_eq!(cached, expected, "round {} differs", round);
+        }
+    }
+
+    #[test]
+    #[ignore] // timing-sensitive; run with --release -- --ignored
+    fn bench_round_templates() {
+        use std::time::Instant;
+        RoundTemplates::get();
+        let iterations = 20;
+        let start = Instant::now();
+        for _ in 0..iterations {
+            let mut script = Vec::new();
+            for round in 0..TOTAL_ROUNDS {
+                script.extend(reference_round(round));
+            }
+            std::hint::black_box(script);
+        }
+        let uncached = start.elapsed();
+        let start = Instant::now();
+        for _ in 0..iterations {
+            std::hint::black_box(generate_poseidon_script_opt());
+        }
+        let cached = start.elapsed();
+        let speedup = uncached.as_secs_f64() / cached.as_secs_f64();
+        assert!(speedup >= 5.0, "uncached {:?}, cached {:?}: only {:.1}x faster", uncached, cached, speedup);
+    }
+
+    #[test]
+    fn test_witness_hash_streaming_matches_buffered() {
+        let constants = FusedPoseidonConstants::compute();
+        let buffered: [u8; 32] = Sha256::digest(constants.to_witness_bytes()).into();
+        assert_eq!(constants.witness_hash_streaming(), buffered);
+        assert_eq!(constants.witness_hash(), buffered);
+    }
+
     #[test]
     fn test_fused_constants() {
         let fused = FusedPoseidonConstants::compute();
@@ -1120,12 +1604,203 @@ mod tests {
                  100.0 * (unfused - fused_size) as f64 / unfused as f64);
     }
 
+    #[test]
+    fn test_fused_permute_matches_poseidon_hash() {
+        use crate::ghost::crypto::PoseidonHash;
+        let fused = FusedPoseidonConstants::compute();
+        let mut rng = rand::thread_rng();
+        for _ in 0..5 {
+            let a = Fp::random(&mut rng);
+            let b = Fp::random(&mut rng);
+            let out = fused.permute([a, b, Fp::ZERO]);
+            assert_eq!(out[0], PoseidonHash::hash(a, b));
+        }
+    }
+
+    /// Textbook Poseidon: every round adds all three constants
+    fn permute_unfused(state: [Fp; 3], full_rounds: usize, partial_rounds: usize) -> [Fp; 3] {
+        let mds = get_mds_fp();
+        let mut s = state;
+        for round in 0..full_rounds + partial_rounds {
+            for (i, lane) in s.iter_mut().enumerate() {
+                *lane += get_round_constant(round, i);
+            }
+            let full = round < full_rounds / 2 || round >= full_rounds / 2 + partial_rounds;
+            for lane in s.iter_mut().take(if full { 3 } else { 1 }) {
+                *lane = sbox(*lane);
+            }
+            s = mds_mul(&mds, &s);
+        }
+        s
+    }
+
+    #[test]
+    fn test_fused_permute_matches_unfused() {
+        let mut rng = rand::thread_rng();
+        for (full, partial) in [(FULL_ROUNDS, PARTIAL_ROUNDS), (6, 58), (2, 62)] {
+            let fused = FusedPoseidonConstants::with_rounds(full, partial).unwrap();
+            assert_eq!(fused.partial_round_c0.len(), partial);
+            assert_eq!(fused.witness_size(), fused.to_witness_bytes().len());
+            for _ in 0..3 {
+                let state = [Fp::random(&mut rng), Fp::random(&mut rng), Fp::random(&mut rng)];
+                assert_eq!(fused.permute(state), permute_unfused(state, full, partial), "{}/{} split", full, partial);
+            }
+        }
+    }
+
+    #[test]
+    fn test_round_count_validation() {
+        assert!(validate_round_counts(FULL_ROUNDS, PARTIAL_ROUNDS).is_ok());
+        assert!(FusedPoseidonConstants::with_rounds(7, 57).is_err());
+        assert!(FusedPoseidonConstants::with_rounds(0, 64).is_err());
+        assert!(FusedPoseidonConstants::with_rounds(8, 50).is_err());
+    }
+
+    #[test]
+    fn test_poseidon_hash2_script_matches_poseidon_hash() {
+        use crate::ghost::crypto::PoseidonHash;
+        use crate::ghost::script::vm::Interpreter;
+        let mut rng = rand::thread_rng();
+        for (a, b) in [(Fp::ZERO, Fp::ZERO), (Fp::random(&mut rng), Fp::random(&mut rng))] {
+            let mut vm = Interpreter::new();
+            vm.eval_with_stack(vec![fp_to_scriptnum_bytes(&a), fp_to_scriptnum_bytes(&b)], &generate_poseidon_hash2_script())
+                .unwrap();
+            assert_eq!(vm.stack(), &[fp_to_scriptnum_bytes(&PoseidonHash::hash(a, b))]);
+            assert!(vm.alt_stack().is_empty());
+        }
+    }
+
+    #[test]
+    fn test_poseidon_strategies_verify_same_fixture() {
+        use crate::ghost::crypto::PoseidonHash;
+        use crate::ghost::script::vm::{Interpreter, VmErrorKind};
+        let (a, b) = (Fp::from(3u64), Fp::from(4u64));
+        let (state, expected) = ([a, b, Fp::ZERO], PoseidonHash::hash(a, b));
+        let run = |scripts: &PoseidonScripts, unlock: Vec<u8>| {
+            let mut vm = Interpreter::new();
+            vm.eval(&[unlock, scripts.lock.clone()].concat()).map_err(|e| e.kind)?;
+            Ok::<_, VmErrorKind>((vm.stack().len(), vm.alt_stack().len()))
+        };
+        for strategy in [StrategyChoice::EmbeddedConstants, StrategyChoice::WitnessConstants] {
+            let scripts = generate_poseidon_verification(strategy);
+            assert_eq!(run(&scripts, scripts.unlock(state, expected)), Ok((0, 0)), "{:?}", strategy);
+            assert_eq!(run(&scripts, scripts.unlock(state, expected + Fp::ONE)), Err(VmErrorKind::VerifyFailed));
+        }
+        // The blob is held to the constants the lock was built for
+        let witness = generate_poseidon_verification(StrategyChoice::WitnessConstants);
+        let mut blob = round_constants_blob();
+        assert_eq!(blob.len(), ROUND_CONSTANTS_BLOB_SIZE);
+        blob[0] ^= 1;
+        let tampered = PoseidonScripts { unlock_template: push_bytes(&blob), ..witness.clone() };
+        assert_eq!(run(&witness, tampered.unlock(state, expected)), Err(VmErrorKind::EqualVerifyFailed));
+    }
+
+    #[test]
+    #[cfg(feature = "contract")]
+    fn test_poseidon_strategy_crossover() {
+        let embedded = generate_poseidon_verification(StrategyChoice::EmbeddedConstants);
+        let witness = generate_poseidon_verification(StrategyChoice::WitnessConstants);
+        assert!(embedded.unlock_template.is_empty());
+        // One permutation alone: 32 bytes per constant pushed to save 28 in
+        // the lock, so the blob never pays off there
+        assert!(embedded.lock.len() - witness.lock.len() < witness.unlock_template.len());
+        // The contract pays the lock saving once per absorb, and the
+        // template once per spend
+        let shape = TranscriptShape::default();
+        let saving = contract_lock_size(StrategyChoice::EmbeddedConstants, shape)
+            - contract_lock_size(StrategyChoice::WitnessConstants, shape);
+        assert!(saving > 6 * witness.unlock_template.len());
+        assert!(saving < 7 * witness.unlock_template.len());
+        for fee_rate in [50, 1_000] {
+            assert_eq!(PoseidonScriptStrategy::select(shape, 0, fee_rate), StrategyChoice::WitnessConstants);
+            assert_eq!(PoseidonScriptStrategy::select(shape, 6, fee_rate), StrategyChoice::WitnessConstants);
+            assert_eq!(PoseidonScriptStrategy::select(shape, 7, fee_rate), StrategyChoice::EmbeddedConstants);
+        }
+        // More absorbs move the crossover out
+        let wide = TranscriptShape::new(2, 10);
+        assert_eq!(PoseidonScriptStrategy::select(wide, 18, 1_000), StrategyChoice::WitnessConstants);
+        assert_eq!(PoseidonScriptStrategy::select(wide, 19, 1_000), StrategyChoice::EmbeddedConstants);
+        // Free relay makes every choice a tie
+        assert_eq!(PoseidonScriptStrategy::lifetime_fee(StrategyChoice::WitnessConstants, shape, 5, 0), 0);
+        assert_eq!(PoseidonScriptStrategy::select(shape, 0, 0), StrategyChoice::EmbeddedConstants);
+    }
+
+    #[test]
+    #[cfg(feature = "contract")]
+    fn test_witness_pattern_savings() {
+        use crate::ghost::script::verifier_contract::{IPAAccumulator, VerifierContract};
+        let shape = TranscriptShape::default();
+        let report = witness_pattern_savings(shape);
+        let contract = |strategy| {
+            VerifierContract::with_poseidon_strategy([0x11; 20], IPAAccumulator::new([0x22; 32]), strategy)
+                .locking_script()
+                .len()
+        };
+        assert_eq!(report.embedded_lock, contract(StrategyChoice::EmbeddedConstants));
+        assert_eq!(report.witness_lock, contract(StrategyChoice::WitnessConstants));
+        assert_eq!(report.breakeven_spends, 6);
+        assert!(report.witness_lock < report.embedded_lock);
+        assert_eq!(report.net_savings_per_spend, report.savings_after(1));
+        // The lock saving is only ahead until the spends' blobs outweigh it
+        assert!(report.savings_after(0) > 0);
+        assert!(report.savings_after(report.breakeven_spends) >= 0);
+        assert!(report.savings_after(report.breakeven_spends + 1) < 0);
+        // At one satoshi per byte the strategy picks the same side
+        assert_eq!(
+            PoseidonScriptStrategy::select(shape, report.breakeven_spends, 1_000),
+            StrategyChoice::WitnessConstants
+        );
+        assert_eq!(
+            PoseidonScriptStrategy::select(shape, report.breakeven_spends + 1, 1_000),
+            StrategyChoice::EmbeddedConstants
+        );
+    }
+
+    #[test]
+    fn test_pow_fixed_matches_field_pow() {
+        use crate::ghost::script::vm::Interpreter;
+        let mut rng = rand::thread_rng();
+        for exp in [0u64, 1, 2, 3, 5, 7, 16] {
+            let mut b = OptimizedScriptBuilder::new();
+            b.pow_fixed_p_at(exp, 1);
+            let script = b.build();
+            for x in [Fp::ZERO, -Fp::ONE, Fp::random(&mut rng), Fp::random(&mut rng)] {
+                let mut vm = Interpreter::new();
+                vm.eval_with_stack(vec![PALLAS_MODULUS_BYTES.to_vec(), fp_to_scriptnum_bytes(&x)], &script)
+                    .unwrap();
+                assert_eq!(vm.stack()[1], fp_to_scriptnum_bytes(&x.pow_vartime([exp])), "x^{}", exp);
+                assert_eq!(vm.stack().len(), 2);
+            }
+        }
+    }
+
+    #[test]
+    fn test_reduce_script() {
+        let p_minus_one = -Fp::ONE;
+        // Top bit set: a negative script number unless read as unsigned
+        let wide = {
+            let mut bytes = fp_to_bytes(&p_minus_one);
+            bytes[31] |= 0x80;
+            bytes
+        };
+        for (input, expected) in [
+            (fp_to_bytes(&p_minus_one).to_vec(), fp_to_scriptnum_bytes(&p_minus_one)),
+            (fp_to_scriptnum_bytes(&Fp::from(7u64)), vec![7]),
+            (PALLAS_MODULUS_BYTES.to_vec(), Vec::new()),
+            (wide.to_vec(), fp_to_scriptnum_bytes(&(p_minus_one + Fp::from(2u64).pow_vartime([255u64])))),
+        ] {
+            let mut script = push_bytes(&input);
+            script.extend(generate_reduce_script());
+            assert_eq!(crate::ghost::script::vm::execute(&script).unwrap(), vec![expected]);
+        }
+    }
+
     #[test]
     fn test_witness_hash() {
         let fused = FusedPoseidonConstants::compute();
         let hash = fused.witness_hash();
         
-        println!("\nConstants hash: {}", hex::encode(&hash));
+        println!("\nConstants hash: {}", hex::encode(hash));
         
         // Hash should be deterministic
         let hash2 = FusedPoseidonConstants::compute().witness_hash();
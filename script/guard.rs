@@ -1,5 +1,33 @@
 use super::opcodes::*;
-use crate::ghost::size;
+use super::guard_engine::{GuardConfig, UniversalGuard, ScriptCodeBinding, OutputValueCap, OutputCount, extract_hash_outputs_from_preimage, extract_checked_hash_outputs};
+use super::field::FieldElement;
+#[cfg(feature = "poseidon-script")]
+use super::guard_engine::TranscriptKind;
+#[cfg(feature = "poseidon-script")]
+use super::field::generate_canonical_check;
+#[cfg(feature = "poseidon-script")]
+use super::field_script::{generate_secure_witness_verification, round_constants_blob};
+#[cfg(feature = "contract")]
+use super::guard_engine::poseidon_domain;
+#[cfg(feature = "contract")]
+use super::field::fp_to_bytes;
+#[cfg(feature = "contract")]
+use super::proof_generator::TranscriptBuilder;
+#[cfg(feature = "contract")]
+use super::verifier_contract::IPAStepWitness;
+use super::disasm::{tokenize, Instruction};
+use super::{ScriptErrorKind, ScriptResult};
+#[cfg(feature = "poseidon-script")]
+use super::push_bytes;
+use crate::ghost::crypto::sha256;
+use crate::ghost::{size, Error, Result};
+/// Transcript elements `Guard::universal_full` absorbs: operator binding,
+/// one framed public input, two framed IPA rounds and both final scalars
+pub const FULL_GUARD_TRANSCRIPT_ELEMENTS: usize = 14;
+/// Transcript element `Guard::universal_full` binds to the app outputs: the
+/// first public input, after the operator binding and the count frame
+pub const FULL_GUARD_INTENT_ELEMENT: usize = 2;
+const ELEMENT_BYTES: usize = 32;
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum GuardType {
     Universal,
@@ -12,6 +40,10 @@ pub enum GuardType {
 pub struct Guard {
     script: Vec<u8>,
     guard_type: GuardType,
+    /// Script ends by pushing its success flag. Engine guards
+    /// (`from_universal`) end with StackCleanup's output instead: the tail's
+    /// witness items, for the tail to consume.
+    pushes_flag: bool,
 }
 
 impl Guard {
@@ -26,8 +58,79 @@ impl Guard {
         Self {
             script,
             guard_type: GuardType::Universal,
+            pushes_flag: true,
         }
     }
+    /// `universal` with the size placeholder replaced by the Poseidon
+    /// transcript check: the proof item must chain from the domain state
+    /// through `FULL_GUARD_TRANSCRIPT_ELEMENTS` absorbs, one witness-constants
+    /// permutation each, and its first public input must commit to the app
+    /// outputs (`intent_public_input`). Checks transcript consistency, not
+    /// the IPA relation itself.
+    ///
+    /// Each absorb costs a permutation of about 8 KB, so the full transcript
+    /// does not fit GUARD_MAX and this fails with the guard's size. Private
+    /// until it fits; `universal_full_unsized` builds it for measurement.
+    #[cfg(feature = "poseidon-script")]
+    #[allow(dead_code)]
+    fn universal_full() -> ScriptResult<Self> {
+        Self::universal_full_with_elements(FULL_GUARD_TRANSCRIPT_ELEMENTS)
+    }
+    /// `universal_full` over the first `num_elements` transcript elements.
+    /// Fails unless they reach `FULL_GUARD_INTENT_ELEMENT`, or if the guard
+    /// exceeds GUARD_MAX.
+    #[cfg(feature = "poseidon-script")]
+    #[allow(dead_code)]
+    fn universal_full_with_elements(num_elements: usize) -> ScriptResult<Self> {
+        if num_elements <= FULL_GUARD_INTENT_ELEMENT {
+            return Err(ScriptErrorKind::InvalidGuardConfig {
+                reason: format!(
+                    "Full guard over {} elements does not reach the intent element {}",
+                    num_elements, FULL_GUARD_INTENT_ELEMENT
+                ),
+            });
+        }
+        Self::universal_full_unsized(num_elements).checked_size()
+    }
+    /// `universal_full_with_elements` without the GUARD_MAX check, for
+    /// measuring and running the script
+    #[cfg(feature = "poseidon-script")]
+    pub(crate) fn universal_full_unsized(num_elements: usize) -> Self {
+        let script = GuardBuilder::new()
+            .introspection()
+            .paymaster_reconstruction()
+            .paymaster_binding()
+            .transcript_verification(num_elements)
+            .cleanup()
+            .build();
+        Self {
+            script,
+            guard_type: GuardType::Universal,
+            pushes_flag: true,
+        }
+    }
+    /// Public input `universal_full` requires at `FULL_GUARD_INTENT_ELEMENT`:
+    /// SHA-256 of the app output bytes, its last byte zeroed so it is a
+    /// canonical field element
+    pub fn intent_public_input(app_bytes: &[u8]) -> FieldElement {
+        let mut element = sha256(app_bytes);
+        element[ELEMENT_BYTES - 1] = 0x00;
+        element
+    }
+    /// Proof item for `universal_full`: the round constants blob, then each
+    /// transcript element followed by the transcript state after absorbing
+    /// it, 32 bytes each. The last state is the transcript hash.
+    #[cfg(feature = "contract")]
+    pub fn full_proof(witness: &IPAStepWitness) -> Vec<u8> {
+        let mut proof = round_constants_blob();
+        let mut transcript = TranscriptBuilder::new(&fp_to_bytes(&poseidon_domain()));
+        for element in witness.transcript_elements() {
+            transcript.absorb(&element);
+            proof.extend(element);
+            proof.extend(transcript.state_bytes());
+        }
+        proof
+    }
     pub fn paymaster() -> Self {
         let script = GuardBuilder::new()
             .introspection() // Re-enabled
@@ -39,8 +142,53 @@ impl Guard {
         Self {
             script,
             guard_type: GuardType::Paymaster,
+            pushes_flag: true,
         }
     }
+    /// Paymaster guard that also fails when the outputs pay out more than
+    /// `max_total` satoshis in total
+    pub fn paymaster_with_output_cap(max_total: u64) -> Self {
+        let script = GuardBuilder::new()
+            .introspection()
+            .output_value_cap(max_total)
+            .paymaster_reconstruction()
+            .paymaster_binding()
+            .ipa_verification()
+            .cleanup()
+            .build();
+        Self {
+            script,
+            guard_type: GuardType::Paymaster,
+            pushes_flag: true,
+        }
+    }
+    /// Paymaster guard that also fails unless the spend creates exactly
+    /// `exact` outputs, app and change together. Fails if `exact` exceeds
+    /// MAX_COUNTED_OUTPUTS or the guard exceeds GUARD_MAX.
+    pub fn paymaster_with_output_count(exact: u8) -> ScriptResult<Self> {
+        Self::paymaster_counted(OutputCount::exact(exact)?)
+    }
+    /// As `paymaster_with_output_count`, accepting `min..=max` outputs;
+    /// also fails if `min > max`
+    pub fn paymaster_with_output_count_range(min: u8, max: u8) -> ScriptResult<Self> {
+        Self::paymaster_counted(OutputCount::range(min, max)?)
+    }
+    fn paymaster_counted(count: OutputCount) -> ScriptResult<Self> {
+        let script = GuardBuilder::new()
+            .introspection()
+            .output_count(count)
+            .paymaster_reconstruction()
+            .paymaster_binding()
+            .ipa_verification()
+            .cleanup()
+            .build();
+        Self {
+            script,
+            guard_type: GuardType::Paymaster,
+            pushes_flag: true,
+        }
+        .checked_size()
+    }
     pub fn minimal() -> Self {
         let mut script = Vec::new();
         script.push(OP_DUP);
@@ -53,12 +201,60 @@ impl Guard {
         Self {
             script,
             guard_type: GuardType::Minimal,
+            pushes_flag: true,
         }
     }
+    /// Fails for a `bind_tail` config, whose check needs the tail; bind it
+    /// with `UniversalGuard::with_tail_binding` or `MulletScript::builder`
+    pub fn from_config(config: &GuardConfig) -> ScriptResult<Self> {
+        Self::from_universal(&UniversalGuard::new(config.clone()))
+    }
+    pub fn from_universal(guard: &UniversalGuard) -> ScriptResult<Self> {
+        Ok(Self {
+            script: guard.build()?,
+            guard_type: GuardType::Universal,
+            pushes_flag: false,
+        })
+    }
     pub fn custom(script: Vec<u8>) -> Self {
         Self {
             script,
             guard_type: GuardType::Custom,
+            pushes_flag: true,
+        }
+    }
+    /// Run `self` then `other`. `self` must end by pushing its success flag;
+    /// that flag is consumed with OP_VERIFY so `other` starts on the stack
+    /// `self` verified. Engine guards end with the tail's witness items, so
+    /// one can only come last. Fails if the combined guard exceeds GUARD_MAX.
+    pub fn then(self, other: Guard) -> ScriptResult<Guard> {
+        if !self.pushes_flag {
+            return Err(ScriptErrorKind::InvalidGuardConfig {
+                reason: format!(
+                    "{:?} guard ends with the tail's witness items, not a success flag, and can only be the last guard",
+                    self.guard_type
+                ),
+            });
+        }
+        let mut script = self.script;
+        script.push(OP_VERIFY);
+        script.extend(other.script);
+        let combined = Self {
+            script,
+            guard_type: GuardType::Custom,
+            pushes_flag: other.pushes_flag,
+        };
+        combined.checked_size()
+    }
+    /// Prefix `self` with a check that the preimage's script_code is the
+    /// script `binding` was committed over
+    pub fn with_script_code_binding(&self, binding: &ScriptCodeBinding) -> Guard {
+        let mut script = GuardBuilder::new().script_code_binding(binding).build();
+        script.extend(&self.script);
+        Self {
+            script,
+            guard_type: self.guard_type,
+            pushes_flag: self.pushes_flag,
         }
     }
     pub fn to_bytes(&self) -> Vec<u8> {
@@ -73,6 +269,24 @@ impl Guard {
     pub fn is_valid_size(&self) -> bool {
         self.size() <= size::GUARD_MAX
     }
+    /// `self`, or `ScriptTooLarge` if it exceeds GUARD_MAX
+    pub fn checked_size(self) -> ScriptResult<Self> {
+        if !self.is_valid_size() {
+            return Err(ScriptErrorKind::ScriptTooLarge { size: self.size(), max: size::GUARD_MAX });
+        }
+        Ok(self)
+    }
+    /// Fails on guards that still contain a placeholder step, e.g.
+    /// `minimal()` and `universal()`
+    pub fn assert_secure(&self) -> Result<()> {
+        match placeholder_reason(&self.script) {
+            Some(reason) => Err(Error::InvalidInput(format!(
+                "{:?} guard is an insecure placeholder: {}",
+                self.guard_type, reason
+            ))),
+            None => Ok(()),
+        }
+    }
 }
 
 struct GuardBuilder {
@@ -93,6 +307,35 @@ impl GuardBuilder {
         self.script.push(OP_TOALTSTACK);
         self
     }
+    fn script_code_binding(mut self, binding: &ScriptCodeBinding) -> Self {
+        // Stack: [..., Preimage] → [..., Preimage]
+        self.script.extend(binding.build());
+        self
+    }
+    fn output_value_cap(mut self, max_total: u64) -> Self {
+        // Stack: [P, A, C, Pre] → [P, A, C, Pre]
+        // Sums A || C, the bytes paymaster_reconstruction then checks
+        // against hashOutputs, so it must run before C is dropped
+        self.script.push(OP_2);
+        self.script.push(OP_PICK);   // [P, A, C, Pre, A]
+        self.script.push(OP_2);
+        self.script.push(OP_PICK);   // [P, A, C, Pre, A, C]
+        self.script.push(OP_CAT);
+        self.script.extend(OutputValueCap::new(max_total).build());
+        self
+    }
+    fn output_count(mut self, count: OutputCount) -> Self {
+        // Stack: [P, A, C, Pre] → [P, A, C, Pre]
+        // Counts the frames of A || C, the bytes paymaster_reconstruction
+        // then checks against hashOutputs
+        self.script.push(OP_2);
+        self.script.push(OP_PICK);   // [P, A, C, Pre, A]
+        self.script.push(OP_2);
+        self.script.push(OP_PICK);   // [P, A, C, Pre, A, C]
+        self.script.push(OP_CAT);
+        self.script.extend(count.build());
+        self
+    }
     fn poseidon_binding(mut self) -> Self {
         self.script.push(OP_TRUE);
         self.script.push(OP_VERIFY);
@@ -109,6 +352,68 @@ impl GuardBuilder {
         self.script.push(OP_DROP);
         self
     }
+    #[cfg(feature = "poseidon-script")]
+    fn transcript_verification(mut self, num_elements: usize) -> Self {
+        // Stack: [P] → []; Alt: [.., A] unchanged
+        // P = blob || e_0 || h_1 || ... || e_{n-1} || h_n, h_{i+1} = H(h_i, e_i)
+        let blob_len = round_constants_blob().len();
+        self.script.push(OP_SIZE);
+        self.script.extend(push_number((blob_len + 2 * num_elements * ELEMENT_BYTES) as i64));
+        self.script.push(OP_NUMEQUALVERIFY);
+        // Intent commitment from A: [c, P]
+        self.script.push(OP_FROMALTSTACK);
+        self.script.push(OP_DUP);
+        self.script.push(OP_TOALTSTACK);
+        self.script.push(OP_SHA256);
+        self.script.extend(push_number((ELEMENT_BYTES - 1) as i64));
+        self.script.push(OP_SPLIT);
+        self.script.push(OP_DROP);
+        self.script.extend(push_bytes(&[0x00]));
+        self.script.push(OP_CAT);
+        self.script.push(OP_SWAP);
+        // [c, blob, h_0, rest]
+        self.script.extend(push_number(blob_len as i64));
+        self.script.push(OP_SPLIT);
+        self.script.extend(push_bytes(&TranscriptKind::Poseidon.initial_state()));
+        self.script.push(OP_SWAP);
+        for i in 0..num_elements {
+            self.script.extend(push_number(ELEMENT_BYTES as i64));
+            self.script.push(OP_SPLIT);
+            self.script.extend(push_number(ELEMENT_BYTES as i64));
+            self.script.push(OP_SPLIT);
+            self.script.push(OP_TOALTSTACK);
+            self.script.push(OP_DUP);
+            self.script.push(OP_TOALTSTACK);
+            // [c, blob, h, e, h'], Alt: [.., rest, h']
+            self.script.push(OP_SWAP);
+            self.script.extend(generate_canonical_check());
+            self.script.push(OP_SWAP);
+            if i == FULL_GUARD_INTENT_ELEMENT {
+                self.script.push(OP_OVER);
+                self.script.push(OP_5);
+                self.script.push(OP_PICK);
+                self.script.push(OP_EQUALVERIFY);
+            }
+            // [c, blob, blob, h, e, 0, h']
+            self.script.push(OP_3);
+            self.script.push(OP_PICK);
+            self.script.push(OP_3);
+            self.script.push(OP_ROLL);
+            self.script.push(OP_3);
+            self.script.push(OP_ROLL);
+            self.script.push(OP_0);
+            self.script.push(OP_4);
+            self.script.push(OP_ROLL);
+            // Fails unless h' is canonical and lane 0 of permute(h, e, 0)
+            self.script.extend(generate_secure_witness_verification());
+            self.script.push(OP_FROMALTSTACK);
+            self.script.push(OP_FROMALTSTACK);
+        }
+        // [c, blob, h_n, rest], rest empty by the size check
+        self.script.push(OP_2DROP);
+        self.script.push(OP_2DROP);
+        self
+    }
     fn cleanup(mut self) -> Self {
         // CLEANUP FIX (Audit):
         // 1. Recover and Drop AppBytes (from paymaster_binding)
@@ -140,16 +445,7 @@ impl GuardBuilder {
         // 2. Extract real hashOutputs from Preimage
         self.script.push(OP_TOALTSTACK); // [P, A, C, Pre] (Alt: [ComputedHash])
         
-        // BIP-143 Preimage Tail: ... + hashOutputs (32) + locktime (4) + sighashType (4) = 40 bytes
-        self.script.push(OP_SIZE);
-        self.script.extend(push_number(40));
-        self.script.push(OP_SUB);
-        self.script.push(OP_SPLIT);      // [Prefix, Tail40]
-        self.script.push(OP_NIP);        // [Tail40]
-        
-        self.script.extend(push_number(32));
-        self.script.push(OP_SPLIT);      // [HashOutputs, Tail8]
-        self.script.push(OP_DROP);       // [HashOutputs]
+        self.script.extend(extract_hash_outputs_from_preimage()); // [P, A, C, HashOutputs]
         
         // 3. Compare
         self.script.push(OP_FROMALTSTACK); // [P, A, C, HashOutputs, ComputedHash]
@@ -170,6 +466,111 @@ impl GuardBuilder {
     }
 }
 
+/// Which `GuardBuilder` steps a guard script contains
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct GuardFeatures {
+    pub introspection: bool,
+    pub reconstruction: bool,
+    pub binding: bool,
+    pub ipa_verification: bool,
+    pub cleanup: bool,
+}
+
+/// Detect guard steps by the exact opcode sequence each builder step emits
+pub fn detect_guard_features(script: &[u8]) -> GuardFeatures {
+    let contains = |step: GuardBuilder| {
+        let pattern = step.build();
+        script.windows(pattern.len()).any(|w| w == pattern.as_slice())
+    };
+    GuardFeatures {
+        introspection: contains(GuardBuilder::new().introspection()),
+        reconstruction: contains(GuardBuilder::new().paymaster_reconstruction()),
+        binding: contains(GuardBuilder::new().paymaster_binding()),
+        ipa_verification: contains(GuardBuilder::new().ipa_verification()),
+        cleanup: contains(GuardBuilder::new().cleanup()),
+    }
+}
+
+/// Classify a guard script from the steps `detect_guard_features` finds.
+///
+/// `GuardBuilder` guards all bind app || change outputs, so `universal()`
+/// and `paymaster()` are byte-identical and cannot be told apart; both
+/// classify as `Paymaster`. Only the proof step separates them from
+/// `universal_full`: a builder guard whose size-only placeholder was
+/// replaced by the transcript check classifies as `Universal`.
+///
+/// Engine guards (`from_config`) are recognised by the length-checked
+/// hashOutputs read `VerifyBinding` emits and classified by its binding
+/// mode: Partial binding pulls each sponsor change output in with
+/// `<n> OP_PICK OP_CAT` before hashing (`Paymaster`), Strict binding hashes
+/// the app outputs alone (`Universal`). Anything else is `Custom`.
+pub fn classify_guard(script: &[u8]) -> GuardType {
+    if script == Guard::minimal().script {
+        return GuardType::Minimal;
+    }
+    let features = detect_guard_features(script);
+    let cleanup = GuardBuilder::new().cleanup().build();
+    if script.ends_with(&cleanup) && features.introspection && features.reconstruction && features.binding {
+        return if features.ipa_verification {
+            GuardType::Paymaster
+        } else {
+            GuardType::Universal
+        };
+    }
+    let engine_binding = extract_checked_hash_outputs();
+    if !script.windows(engine_binding.len()).any(|w| w == engine_binding.as_slice()) {
+        return GuardType::Custom;
+    }
+    let Ok(instructions) = tokenize(script) else {
+        return GuardType::Custom;
+    };
+    // VerifyPublicData's witness hashing also picks and concatenates, but
+    // always follows with a second OP_CAT
+    let op = |i: usize, opcode: u8| instructions.get(i) == Some(&Instruction::Op(opcode));
+    let partial = (0..instructions.len()).any(|i| {
+        is_number(instructions.get(i)) && op(i + 1, OP_PICK) && op(i + 2, OP_CAT) && !op(i + 3, OP_CAT)
+    });
+    if partial {
+        GuardType::Paymaster
+    } else {
+        GuardType::Universal
+    }
+}
+
+/// True when `script` contains a step that stands in for verification:
+/// `OP_TRUE OP_VERIFY`, or an item checked only by `OP_SIZE <n>
+/// OP_GREATERTHAN OP_VERIFY` and then dropped. Matched on instructions, so
+/// push data that happens to contain these bytes is not flagged. A script
+/// that does not disassemble cannot be audited and is flagged too.
+pub fn is_insecure_placeholder(script: &[u8]) -> bool {
+    placeholder_reason(script).is_some()
+}
+
+fn placeholder_reason(script: &[u8]) -> Option<&'static str> {
+    let Ok(instructions) = tokenize(script) else {
+        return Some("script does not disassemble");
+    };
+    let op = |i: usize, opcode: u8| instructions.get(i) == Some(&Instruction::Op(opcode));
+    let number = |i: usize| is_number(instructions.get(i));
+    for i in 0..instructions.len() {
+        if op(i, OP_TRUE) && op(i + 1, OP_VERIFY) {
+            return Some("OP_TRUE OP_VERIFY in place of a check");
+        }
+        if op(i, OP_SIZE) && number(i + 1) && op(i + 2, OP_GREATERTHAN) && op(i + 3, OP_VERIFY) && op(i + 4, OP_DROP) {
+            return Some("item dropped after a size-only check");
+        }
+    }
+    None
+}
+
+fn is_number(instruction: Option<&Instruction>) -> bool {
+    match instruction {
+        Some(Instruction::Push(_)) => true,
+        Some(Instruction::Op(opcode)) => (OP_1..=OP_16).contains(opcode),
+        None => false,
+    }
+}
+
 pub fn estimate_guard_size(k: u32) -> usize {
     let ipa_size = (k as usize) * 200;
     let poseidon_size = 64 * 30;
@@ -198,6 +599,56 @@ mod tests {
         assert!(guard.size() < 50);
     }
     #[test]
+    fn test_detect_guard_features() {
+        let all = GuardFeatures {
+            introspection: true,
+            reconstruction: true,
+            binding: true,
+            ipa_verification: true,
+            cleanup: true,
+        };
+        assert_eq!(detect_guard_features(&Guard::universal().to_bytes()), all);
+        assert_eq!(detect_guard_features(&Guard::paymaster_with_output_cap(800_000).to_bytes()), all);
+        assert_eq!(detect_guard_features(&Guard::minimal().to_bytes()), GuardFeatures::default());
+        let partial = GuardBuilder::new().introspection().ipa_verification().build();
+        let features = detect_guard_features(&partial);
+        assert!(features.introspection && features.ipa_verification);
+        assert!(!features.reconstruction && !features.binding && !features.cleanup);
+    }
+    #[test]
+    fn test_classify_builtin_guards() {
+        let classify = |guard: Guard| classify_guard(&guard.to_bytes());
+        assert_eq!(classify(Guard::minimal()), GuardType::Minimal);
+        // Byte-identical to paymaster(), so indistinguishable from it
+        assert_eq!(classify(Guard::universal()), GuardType::Paymaster);
+        assert_eq!(classify(Guard::paymaster()), GuardType::Paymaster);
+        assert_eq!(classify(Guard::paymaster_with_output_cap(50_000)), GuardType::Paymaster);
+        assert_eq!(classify(Guard::paymaster_with_output_count(2).unwrap()), GuardType::Paymaster);
+        assert_eq!(classify(Guard::paymaster_with_output_count_range(1, 3).unwrap()), GuardType::Paymaster);
+        assert_eq!(classify(Guard::custom(vec![OP_TRUE])), GuardType::Custom);
+        assert_eq!(classify(Guard::paymaster().then(Guard::minimal()).unwrap()), GuardType::Custom);
+        #[cfg(feature = "poseidon-script")]
+        {
+            let full = Guard::universal_full_unsized(FULL_GUARD_TRANSCRIPT_ELEMENTS);
+            assert_eq!(classify(full.clone()), GuardType::Universal);
+            let binding = ScriptCodeBinding::fit(40, 300);
+            assert_eq!(classify(full.with_script_code_binding(&binding)), GuardType::Universal);
+        }
+    }
+    #[test]
+    fn test_classify_engine_guards_by_binding_mode() {
+        let classify = |config: GuardConfig| classify_guard(&Guard::from_config(&config).unwrap().to_bytes());
+        assert_eq!(classify(GuardConfig::new(1, 1).strict()), GuardType::Universal);
+        assert_eq!(classify(GuardConfig::new(2, 2).strict().with_output_value_cap(10_000)), GuardType::Universal);
+        assert_eq!(classify(GuardConfig::new(1, 2).strict().with_output_count(2).unwrap()), GuardType::Universal);
+        assert_eq!(classify(GuardConfig::new(1, 1).paymaster(1_000)), GuardType::Paymaster);
+        assert_eq!(classify(GuardConfig::new(1, 2).paymaster(1_000).with_change_outputs(2)), GuardType::Paymaster);
+        assert_eq!(
+            classify(GuardConfig::new(1, 1).paymaster(1_000).with_output_count(2).unwrap()),
+            GuardType::Paymaster
+        );
+    }
+    #[test]
     fn test_guard_size_estimation() {
         let size_k10 = estimate_guard_size(10);
         assert!(size_k10 < size::GUARD_TARGET);
@@ -218,4 +669,194 @@ mod tests {
         let minimal = Guard::minimal();
         assert!(guard.size() > minimal.size());
     }
+    #[test]
+    fn test_then_runs_both_guards() {
+        use crate::ghost::script::vm::Interpreter;
+        let run = |guard: &Guard, item_len: usize| {
+            Interpreter::new().eval_with_stack(vec![vec![0x42; item_len]], &guard.to_bytes())
+        };
+        let both = Guard::minimal().then(Guard::minimal()).unwrap();
+        assert_eq!(both.guard_type(), GuardType::Custom);
+        assert_eq!(both.size(), 2 * Guard::minimal().size() + 1);
+        assert!(run(&both, 200).unwrap());
+        assert!(run(&both, 50).is_err());
+        // Second stage rejects what the first accepts
+        let mut at_most_150 = vec![OP_SIZE];
+        at_most_150.extend(push_number(150));
+        at_most_150.extend([OP_LESSTHANOREQUAL, OP_VERIFY, OP_TRUE]);
+        let staged = Guard::minimal().then(Guard::custom(at_most_150)).unwrap();
+        assert!(run(&staged, 120).unwrap());
+        assert!(run(&staged, 200).is_err());
+    }
+    #[test]
+    fn test_then_composes_universal_guards() {
+        use crate::ghost::script::binding_diff::serialize_outputs;
+        use crate::ghost::script::test_support::{preimage_committing_to, run_script};
+        use crate::ghost::script::vm::VmErrorKind;
+        use crate::ghost::script::IpaHints;
+        // One [proof, app, change, preimage] group per guard, the first guard's on top
+        let group = |app_value: u64| {
+            let app = serialize_outputs(&[(app_value, vec![0x51; 32])]);
+            let change = serialize_outputs(&[(500, vec![0x76; 25])]);
+            let preimage = preimage_committing_to(&[app.clone(), change.clone()].concat());
+            vec![vec![0x01; 200], app, change, preimage]
+        };
+        let run = |guard: &Guard, stack: Vec<Vec<u8>>| run_script(stack, &guard.to_bytes());
+        let both = Guard::universal().then(Guard::universal()).unwrap();
+        let stack = [group(2_000), group(1_000)].concat();
+        assert_eq!(run(&both, stack.clone()), Ok(vec![vec![1]]));
+        // Each stage checks its own group
+        for index in [2, 6] {
+            let mut tampered = stack.clone();
+            tampered[index] = serialize_outputs(&[(501, vec![0x76; 25])]);
+            assert_eq!(run(&both, tampered), Err(VmErrorKind::EqualVerifyFailed));
+        }
+        // An engine guard ends with the tail's witness items, so it can only come last
+        let config = GuardConfig::new(1, 1)
+            .with_ipa_hints(IpaHints::placeholder(2))
+            .preserve_message(false);
+        let engine = Guard::from_config(&config).unwrap();
+        let err = engine.clone().then(Guard::universal()).unwrap_err();
+        assert!(matches!(err, ScriptErrorKind::InvalidGuardConfig { .. }));
+        let (value, script) = (vec![0x22; 8], vec![0x33; 32]);
+        let outputs = [value.clone(), vec![0x20], script.clone()].concat();
+        let preimage = preimage_committing_to(&outputs);
+        let mut stack: Vec<Vec<u8>> = (0..6).map(|i| vec![0x10 + i; 4]).collect();
+        stack.extend([script, value, vec![0x30; 71], preimage]);
+        assert_eq!(stack.len(), config.expected_stack_size());
+        stack.extend(group(1_000));
+        let staged = Guard::universal().then(engine.clone()).unwrap();
+        assert_eq!(run(&staged, stack.clone()), Ok(vec![vec![0x30; 71]]));
+        assert!(staged.then(Guard::minimal()).is_err());
+    }
+    #[test]
+    fn test_then_rejects_oversized() {
+        let err = Guard::custom(vec![OP_NOP; size::GUARD_MAX]).then(Guard::minimal()).unwrap_err();
+        assert!(matches!(err, ScriptErrorKind::ScriptTooLarge { max: size::GUARD_MAX, .. }));
+    }
+    #[test]
+    fn test_paymaster_with_output_cap() {
+        use crate::ghost::script::binding_diff::serialize_outputs;
+        use crate::ghost::script::test_support::preimage_committing_to;
+        use crate::ghost::script::vm::{Interpreter, VmErrorKind};
+        // Three values above 2^31, so every sum is a multi-byte script number
+        let value = (1u64 << 31) + 5;
+        let app = serialize_outputs(&[(value, vec![0x51; 32]), (value, vec![0x52; 32])]);
+        let change = serialize_outputs(&[(value, vec![0x76; 25])]);
+        let preimage = preimage_committing_to(&[app.clone(), change.clone()].concat());
+        let run = |max_total: u64| {
+            let stack = vec![vec![0x01; 200], app.clone(), change.clone(), preimage.clone()];
+            let guard = Guard::paymaster_with_output_cap(max_total);
+            Interpreter::new().eval_with_stack(stack, &guard.to_bytes()).map_err(|e| e.kind)
+        };
+        assert_eq!(run(3 * value), Ok(true));
+        assert_eq!(run(3 * value - 1), Err(VmErrorKind::VerifyFailed));
+        assert!(Guard::paymaster_with_output_cap(1).is_valid_size());
+        let features = detect_guard_features(&Guard::paymaster_with_output_cap(1).to_bytes());
+        assert!(features.reconstruction && features.cleanup);
+    }
+    #[test]
+    fn test_paymaster_with_output_count() {
+        use crate::ghost::script::binding_diff::serialize_outputs;
+        use crate::ghost::script::test_support::preimage_committing_to;
+        use crate::ghost::script::vm::{Interpreter, VmErrorKind};
+        use crate::ghost::script::MAX_COUNTED_OUTPUTS;
+        // Change item carrying two outputs: three outputs in total
+        let app = serialize_outputs(&[(1_000, vec![0x51; 32])]);
+        let change = serialize_outputs(&[(500, vec![0x76; 25]), (500, vec![0x6a; 300])]);
+        let preimage = preimage_committing_to(&[app.clone(), change.clone()].concat());
+        let run = |guard: Guard| {
+            let stack = vec![vec![0x01; 200], app.clone(), change.clone(), preimage.clone()];
+            Interpreter::new().eval_with_stack(stack, &guard.to_bytes()).map_err(|e| e.kind)
+        };
+        assert_eq!(run(Guard::paymaster_with_output_count(3).unwrap()), Ok(true));
+        assert_eq!(run(Guard::paymaster_with_output_count(2).unwrap()), Err(VmErrorKind::VerifyFailed));
+        assert_eq!(run(Guard::paymaster_with_output_count_range(2, 3).unwrap()), Ok(true));
+        assert_eq!(run(Guard::paymaster_with_output_count_range(4, 5).unwrap()), Err(VmErrorKind::VerifyFailed));
+        assert!(Guard::paymaster_with_output_count_range(3, 2).is_err());
+        assert!(Guard::paymaster_with_output_count(MAX_COUNTED_OUTPUTS + 1).is_err());
+        assert!(Guard::paymaster_with_output_count_range(0, MAX_COUNTED_OUTPUTS).unwrap().is_valid_size());
+    }
+    #[test]
+    #[cfg(feature = "contract")]
+    fn test_universal_full_checks_transcript() {
+        use crate::ghost::script::binding_diff::serialize_outputs;
+        use crate::ghost::script::proof_generator::generate_mock_proof;
+        use crate::ghost::script::test_support::preimage_committing_to;
+        use crate::ghost::script::vm::{Interpreter, VmErrorKind};
+        let app = serialize_outputs(&[(1_000, vec![0x51; 32])]);
+        let change = serialize_outputs(&[(500, vec![0x76; 25])]);
+        let preimage = preimage_committing_to(&[app.clone(), change.clone()].concat());
+        let domain = fp_to_bytes(&poseidon_domain());
+        let witness = generate_mock_proof(&domain, 2, vec![Guard::intent_public_input(&app)]);
+        assert_eq!(witness.transcript_elements().len(), FULL_GUARD_TRANSCRIPT_ELEMENTS);
+        let guard = Guard::universal_full_unsized(FULL_GUARD_TRANSCRIPT_ELEMENTS);
+        let run = |proof: Vec<u8>| {
+            let stack = vec![proof, app.clone(), change.clone(), preimage.clone()];
+            Interpreter::new().eval_with_stack(stack, &guard.to_bytes()).map_err(|e| e.kind)
+        };
+        let proof = Guard::full_proof(&witness);
+        assert_eq!(proof[proof.len() - ELEMENT_BYTES..], witness.next_transcript_hash);
+        assert_eq!(run(proof.clone()), Ok(true));
+        // Wrong claimed transcript hash
+        let mut wrong_hash = proof.clone();
+        let last = wrong_hash.len() - ELEMENT_BYTES;
+        wrong_hash[last] ^= 0x01;
+        assert_eq!(run(wrong_hash), Err(VmErrorKind::VerifyFailed));
+        // Tampered element, states left as generated
+        let mut wrong_element = proof.clone();
+        wrong_element[round_constants_blob().len()] ^= 0x01;
+        assert_eq!(run(wrong_element), Err(VmErrorKind::VerifyFailed));
+        // A consistent transcript whose public input is not the app outputs
+        let unbound = generate_mock_proof(&domain, 2, vec![Guard::intent_public_input(&change)]);
+        assert_eq!(run(Guard::full_proof(&unbound)), Err(VmErrorKind::EqualVerifyFailed));
+        // Anything over the placeholder's size threshold passes universal, not universal_full
+        let junk = vec![vec![0x01; 200], app.clone(), change.clone(), preimage.clone()];
+        assert_eq!(Interpreter::new().eval_with_stack(junk, &Guard::universal().to_bytes()), Ok(true));
+        assert!(run(vec![0x01; 200]).is_err());
+    }
+    #[test]
+    #[cfg(feature = "poseidon-script")]
+    fn test_universal_full_size() {
+        let full = Guard::universal_full_unsized(FULL_GUARD_TRANSCRIPT_ELEMENTS);
+        let placeholder = GuardBuilder::new().ipa_verification().build().len();
+        let transcript = GuardBuilder::new().transcript_verification(FULL_GUARD_TRANSCRIPT_ELEMENTS).build().len();
+        assert_eq!(full.size(), Guard::universal().size() - placeholder + transcript);
+        // One witness-constants permutation per absorbed element dominates
+        let permutation = generate_secure_witness_verification().len();
+        assert!(transcript > FULL_GUARD_TRANSCRIPT_ELEMENTS * permutation);
+        assert!(!detect_guard_features(&full.to_bytes()).ipa_verification);
+        // Too large for a guard even at the fewest elements that reach the intent
+        assert!(permutation > size::GUARD_MAX / 2);
+        let err = Guard::universal_full().unwrap_err();
+        assert_eq!(err, ScriptErrorKind::ScriptTooLarge { size: full.size(), max: size::GUARD_MAX });
+        assert_eq!(err.error_code(), 1003);
+        assert!(Guard::universal_full_with_elements(FULL_GUARD_INTENT_ELEMENT + 1).is_err());
+        let err = Guard::universal_full_with_elements(FULL_GUARD_INTENT_ELEMENT).unwrap_err();
+        assert!(matches!(err, ScriptErrorKind::InvalidGuardConfig { reason } if reason.contains("intent element")));
+    }
+    #[test]
+    fn test_placeholder_guards_flagged() {
+        assert!(is_insecure_placeholder(&Guard::minimal().to_bytes()));
+        assert!(is_insecure_placeholder(&Guard::universal().to_bytes()));
+        assert!(is_insecure_placeholder(&GuardBuilder::new().poseidon_binding().build()));
+        assert!(is_insecure_placeholder(&[OP_PUSHDATA1, 0x05]));
+        let err = Guard::universal().assert_secure().unwrap_err();
+        assert!(matches!(err, Error::InvalidInput(msg) if msg.contains("size-only")));
+        assert!(Guard::minimal().assert_secure().is_err());
+    }
+    #[test]
+    fn test_full_guard_not_flagged() {
+        #[cfg(feature = "poseidon-script")]
+        {
+            assert!(!is_insecure_placeholder(&Guard::universal_full_unsized(FULL_GUARD_INTENT_ELEMENT + 1).to_bytes()));
+            assert!(Guard::universal_full_unsized(FULL_GUARD_TRANSCRIPT_ELEMENTS).assert_secure().is_ok());
+        }
+        // Placeholder bytes inside push data are not instructions
+        let mut script = crate::ghost::script::push_bytes(&[OP_TRUE, OP_VERIFY]);
+        script.push(OP_DROP);
+        assert!(!is_insecure_placeholder(&script));
+        // A size check whose item is kept is not a stand-in
+        assert!(!is_insecure_placeholder(&GuardBuilder::new().paymaster_binding().build()));
+    }
 }
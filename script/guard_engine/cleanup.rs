@@ -1,13 +1,22 @@
 // Stack cleanup after verification [P.3]
 use crate::ghost::script::{
-    OP_DROP, OP_2DROP,
+    OP_DROP, OP_2DROP, OP_SWAP,
     OP_TOALTSTACK, OP_FROMALTSTACK,
     OP_SHA256,
 }
 ;
+// Entry (top last): [dropped...] [tail witness items] [sighash preimage],
+// the preimage being what VerifyBinding leaves on top.
+// Exit: [SHA256(preimage)] [tail witness items] when preserving the message,
+// else just the tail items. The hash is the single SHA256 of the full BIP-143
+// preimage, i.e. the message the tail's signature is made over; CHECKSIG
+// hashes it once more. It sits beneath the tail items so the tail runs
+// unchanged and leaves it for a downstream consumer.
+// drop_count counts every other item, the preimage included when it is not
+// preserved.
 pub struct StackCleanup {
     drop_count: usize,
-    preserve_tail: bool,
+    tail_items: usize,
     preserve_message: bool,
 }
 
@@ -15,12 +24,17 @@ impl StackCleanup {
     pub fn new(drop_count: usize) -> Self {
         Self {
             drop_count,
-            preserve_tail: true,
+            tail_items: 1,
             preserve_message: false,
         }
     }
     pub fn preserve_tail(mut self, preserve: bool) -> Self {
-        self.preserve_tail = preserve;
+        self.tail_items = usize::from(preserve);
+        self
+    }
+    // e.g. 2 for an Ecdsa tail's [sig] [pubkey]
+    pub fn preserve_tail_items(mut self, count: usize) -> Self {
+        self.tail_items = count;
         self
     }
     pub fn preserve_message(mut self, preserve: bool) -> Self {
@@ -29,36 +43,27 @@ impl StackCleanup {
     }
     pub fn build(&self) -> Vec<u8> {
         let mut script = Vec::new();
-        if self.preserve_tail {
-            script.push(OP_TOALTSTACK);
-        }
+        let mut items_to_drop = self.drop_count;
         if self.preserve_message {
+            // Sink the hash below the tail items on the way to the alt stack
             script.push(OP_SHA256);
+            for _ in 0..self.tail_items {
+                script.push(OP_SWAP);
+                script.push(OP_TOALTSTACK);
+            }
             script.push(OP_TOALTSTACK);
-        }
-        let items_to_drop = if self.preserve_tail { self.drop_count } else { self.drop_count + 1 };
-        let items_to_drop = if self.preserve_message { items_to_drop - 1 } else { items_to_drop };
-        let two_drops = items_to_drop / 2;
-        let single_drops = items_to_drop % 2;
-        for _ in 0..two_drops {
-            script.push(OP_2DROP);
-        }
-        for _ in 0..single_drops {
+        } else {
             script.push(OP_DROP);
+            items_to_drop = items_to_drop.saturating_sub(1);
+            script.extend(std::iter::repeat_n(OP_TOALTSTACK, self.tail_items));
         }
-        if self.preserve_message {
-            script.push(OP_FROMALTSTACK);
-        }
-        if self.preserve_tail {
-            script.push(OP_FROMALTSTACK);
-        }
+        script.extend(std::iter::repeat_n(OP_2DROP, items_to_drop / 2));
+        script.extend(std::iter::repeat_n(OP_DROP, items_to_drop % 2));
+        script.extend(std::iter::repeat_n(OP_FROMALTSTACK, self.remaining_count()));
         script
     }
     pub fn remaining_count(&self) -> usize {
-        let mut count = 0;
-        if self.preserve_tail { count += 1; }
-        if self.preserve_message { count += 1; }
-        count
+        self.tail_items + usize::from(self.preserve_message)
     }
 }
 
@@ -101,5 +106,19 @@ mod tests {
         let script = cleanup.build();
         assert!(script.contains(&OP_2DROP));
     }
+    #[test]
+    fn test_preserves_preimage_hash_below_tail_items() {
+        use crate::ghost::crypto::sha256;
+        use crate::ghost::script::vm::Interpreter;
+        let preimage = vec![0x5a; 180];
+        let stack = vec![vec![1], vec![2], vec![3], b"sig".to_vec(), b"pubkey".to_vec(), preimage.clone()];
+        let cleanup = StackCleanup::new(3).preserve_tail_items(2).preserve_message(true);
+        let mut vm = Interpreter::new();
+        vm.eval_with_stack(stack.clone(), &cleanup.build()).unwrap();
+        assert_eq!(vm.stack(), &[sha256(&preimage).to_vec(), b"sig".to_vec(), b"pubkey".to_vec()]);
+        let cleanup = StackCleanup::new(4).preserve_tail_items(2);
+        vm.eval_with_stack(stack, &cleanup.build()).unwrap();
+        assert_eq!(vm.stack(), &[b"sig".to_vec(), b"pubkey".to_vec()]);
+        assert_eq!(cleanup.remaining_count(), 2);
+    }
 }
-
@@ -0,0 +1,89 @@
+// Input count: raw prevouts hashed against the preimage's hashPrevouts
+use crate::ghost::script::{
+    OP_SWAP, OP_SIZE, OP_OVER, OP_SPLIT, OP_NIP, OP_DROP,
+    OP_SHA256, OP_EQUALVERIFY,
+    push_number,
+}
+;
+// BIP-143 only commits to hash_prevouts, so the input count cannot be read
+// from the preimage directly. The prover supplies the serialized outpoints
+// (txid 32 + vout 4 each); the script fixes their length to num_inputs * 36
+// and checks double_sha256 of them against hash_prevouts. Not usable with
+// ANYONECANPAY, where hash_prevouts is all zeros.
+pub(crate) use crate::ghost::script::tx::OUTPOINT_SIZE;
+const HASH_PREVOUTS_OFFSET: i64 = 4;
+pub struct InputCountBinding {
+    num_inputs: usize,
+}
+
+impl InputCountBinding {
+    pub fn new(num_inputs: usize) -> Self {
+        Self { num_inputs }
+    }
+    pub fn num_inputs(&self) -> usize {
+        self.num_inputs
+    }
+    pub fn prevouts_len(&self) -> usize {
+        self.num_inputs * OUTPOINT_SIZE
+    }
+    // Stack: [prevouts] [preimage] → [preimage]
+    pub fn build(&self) -> Vec<u8> {
+        let mut script = Vec::new();
+        script.push(OP_SWAP);
+        script.push(OP_SIZE);
+        script.extend(push_number(self.prevouts_len() as i64));
+        script.push(OP_EQUALVERIFY);
+        script.push(OP_SHA256);
+        script.push(OP_SHA256);
+        script.push(OP_OVER);
+        script.extend(push_number(HASH_PREVOUTS_OFFSET));
+        script.push(OP_SPLIT);
+        script.push(OP_NIP);
+        script.extend(push_number(32));
+        script.push(OP_SPLIT);
+        script.push(OP_DROP);
+        script.push(OP_EQUALVERIFY);
+        script
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ghost::crypto::double_sha256;
+    use crate::ghost::script::test_support::run_script;
+    use crate::ghost::script::tx::OutPoint;
+    use crate::ghost::script::vm::VmErrorKind;
+    use crate::ghost::script::SighashPreimageBuilder;
+    fn prevouts(num_inputs: usize) -> Vec<u8> {
+        (0..num_inputs).flat_map(|i| {
+            let mut outpoint = vec![i as u8 + 1; 32];
+            outpoint.extend((i as u32).to_le_bytes());
+            outpoint
+        }).collect()
+    }
+    fn preimage(prevouts: &[u8]) -> Vec<u8> {
+        SighashPreimageBuilder::new()
+            .hash_prevouts(double_sha256(prevouts))
+            .outpoint(OutPoint::from_bytes(prevouts[..OUTPOINT_SIZE].try_into().unwrap()))
+            .build()
+            .to_bytes()
+    }
+    fn run(binding: &InputCountBinding, prevouts: Vec<u8>, preimage: Vec<u8>) -> Result<Vec<Vec<u8>>, VmErrorKind> {
+        run_script(vec![prevouts, preimage], &binding.build())
+    }
+    #[test]
+    fn test_matching_input_count() {
+        let pre = preimage(&prevouts(2));
+        assert_eq!(run(&InputCountBinding::new(2), prevouts(2), pre.clone()).unwrap(), vec![pre]);
+    }
+    #[test]
+    fn test_wrong_input_count_rejected() {
+        let pre = preimage(&prevouts(3));
+        // Prover claims two inputs: the truncated prevouts no longer hash to hash_prevouts
+        let claimed = prevouts(3)[..2 * OUTPOINT_SIZE].to_vec();
+        assert_eq!(run(&InputCountBinding::new(2), claimed, pre.clone()), Err(VmErrorKind::EqualVerifyFailed));
+        // Length is fixed by the script regardless of what the prover supplies
+        assert_eq!(run(&InputCountBinding::new(2), prevouts(3), pre), Err(VmErrorKind::EqualVerifyFailed));
+    }
+}
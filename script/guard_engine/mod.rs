@@ -3,7 +3,27 @@ mod universal;
 mod verify_public;
 mod verify_binding;
 mod cleanup;
+mod input_count;
+mod transcript;
+mod tail_binding;
+mod script_code;
+mod output_value;
+mod output_count;
+mod preimage_tail;
 pub use universal::{UniversalGuard, GuardConfig};
-pub use verify_public::VerifyPublicData;
+pub use verify_public::{VerifyPublicData, StackSlot};
 pub use verify_binding::VerifyBinding;
+pub(crate) use verify_binding::extract_checked_hash_outputs;
 pub use cleanup::StackCleanup;
+pub use input_count::InputCountBinding;
+pub use transcript::TranscriptKind;
+#[cfg(feature = "contract")]
+pub(crate) use transcript::{poseidon_domain, squeeze_tag_bytes};
+#[cfg(feature = "contract")]
+pub(crate) use transcript::SQUEEZE_TAG;
+pub use tail_binding::TailBinding;
+pub use script_code::ScriptCodeBinding;
+pub use output_value::OutputValueCap;
+pub use output_count::{OutputCount, MAX_COUNTED_OUTPUTS};
+pub use preimage_tail::{extract_hash_outputs_from_preimage, hash_outputs_offset};
+pub(crate) use preimage_tail::HASH_OUTPUTS_TAIL;
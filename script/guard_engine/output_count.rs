@@ -0,0 +1,126 @@
+// Output count: number of value || varint || script frames in the serialized outputs
+use crate::ghost::script::{
+    OP_SWAP, OP_SIZE, OP_NIP, OP_DROP, OP_SPLIT, OP_1ADD,
+    OP_NOT, OP_VERIFY, OP_NUMEQUALVERIFY, OP_GREATERTHANOREQUAL,
+    OP_IF, OP_ENDIF, OP_0, OP_8, push_number,
+    ScriptErrorKind, ScriptResult,
+}
+;
+use super::output_value::skip_script;
+// Walks the same serialization hashed into hashOutputs, one unrolled slot
+// per allowed output. Bytes left after the last slot, or a frame whose
+// length runs past the end, fail the parse, so the count does not depend on
+// output items having fixed lengths.
+// Each slot costs about 40 bytes; this many keeps a counting guard well
+// inside GUARD_MAX
+pub const MAX_COUNTED_OUTPUTS: u8 = 64;
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct OutputCount {
+    min: u8,
+    max: u8,
+}
+
+impl OutputCount {
+    pub fn exact(count: u8) -> ScriptResult<Self> {
+        Self::range(count, count)
+    }
+    // Fails if `min > max` or `max` exceeds MAX_COUNTED_OUTPUTS
+    pub fn range(min: u8, max: u8) -> ScriptResult<Self> {
+        if min > max {
+            return Err(ScriptErrorKind::InvalidGuardConfig {
+                reason: format!("Output count range {}..={} is empty", min, max),
+            });
+        }
+        if max > MAX_COUNTED_OUTPUTS {
+            return Err(ScriptErrorKind::InvalidGuardConfig {
+                reason: format!("Output count {} exceeds {} counted outputs", max, MAX_COUNTED_OUTPUTS),
+            });
+        }
+        Ok(Self { min, max })
+    }
+    pub fn min(&self) -> u8 {
+        self.min
+    }
+    pub fn max(&self) -> u8 {
+        self.max
+    }
+    // Stack: [outputs] → []
+    pub fn build(&self) -> Vec<u8> {
+        let mut script = vec![OP_0, OP_SWAP];
+        for _ in 0..self.max {
+            script.extend(Self::count_next_output());
+        }
+        script.push(OP_SIZE);
+        script.push(OP_NOT);
+        script.push(OP_VERIFY);
+        script.push(OP_DROP);
+        script.extend(push_number(self.min as i64));
+        if self.min == self.max {
+            script.push(OP_NUMEQUALVERIFY);
+        } else {
+            script.push(OP_GREATERTHANOREQUAL);
+            script.push(OP_VERIFY);
+        }
+        script
+    }
+    // Stack: [count] [rest] → [count + 1] [rest after one output]
+    fn count_next_output() -> Vec<u8> {
+        let mut script = vec![OP_SIZE, OP_IF, OP_8, OP_SPLIT, OP_NIP];
+        script.push(OP_SWAP);
+        script.push(OP_1ADD);
+        script.push(OP_SWAP);
+        script.extend(skip_script());
+        script.push(OP_ENDIF);
+        script
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ghost::script::OP_1;
+    use crate::ghost::script::binding_diff::serialize_outputs;
+    use crate::ghost::script::vm::{Interpreter, VmErrorKind};
+    fn outputs(n: usize) -> Vec<u8> {
+        // Alternate short and 0xfd-prefixed script lengths
+        let outputs: Vec<(u64, Vec<u8>)> = (0..n)
+            .map(|i| (i as u64 + 1, vec![0x51; if i % 2 == 0 { 25 } else { 300 }]))
+            .collect();
+        serialize_outputs(&outputs)
+    }
+    fn run(count: &OutputCount, outputs: Vec<u8>) -> Result<bool, VmErrorKind> {
+        let mut script = count.build();
+        script.push(OP_1);
+        Interpreter::new().eval_with_stack(vec![outputs], &script).map_err(|e| e.kind)
+    }
+    #[test]
+    fn test_exact_count() {
+        assert_eq!(run(&OutputCount::exact(3).unwrap(), outputs(3)), Ok(true));
+        assert_eq!(run(&OutputCount::exact(2).unwrap(), outputs(3)), Err(VmErrorKind::VerifyFailed));
+        assert_eq!(run(&OutputCount::exact(4).unwrap(), outputs(3)), Err(VmErrorKind::VerifyFailed));
+        assert_eq!(run(&OutputCount::exact(0).unwrap(), Vec::new()), Ok(true));
+    }
+    #[test]
+    fn test_count_range() {
+        let count = OutputCount::range(1, 2).unwrap();
+        assert_eq!(run(&count, outputs(1)), Ok(true));
+        assert_eq!(run(&count, outputs(2)), Ok(true));
+        assert_eq!(run(&count, outputs(0)), Err(VmErrorKind::VerifyFailed));
+        assert_eq!(run(&count, outputs(3)), Err(VmErrorKind::VerifyFailed));
+        assert!(OutputCount::range(2, 1).is_err());
+        assert!(OutputCount::exact(MAX_COUNTED_OUTPUTS + 1).is_err());
+        let widest = OutputCount::range(0, MAX_COUNTED_OUTPUTS).unwrap();
+        assert!(widest.build().len() < crate::ghost::size::GUARD_MAX / 2);
+    }
+    #[test]
+    fn test_inflated_script_length_fails_parse() {
+        let mut inflated = outputs(1);
+        inflated[8] += 1;
+        assert_eq!(run(&OutputCount::exact(1).unwrap(), inflated), Err(VmErrorKind::InvalidSplitRange));
+        // A length that swallows the next frame's header leaves its script unparsed
+        let mut swallowed = outputs(2);
+        swallowed[8] = 25 + 8 + 3;
+        assert!(run(&OutputCount::exact(1).unwrap(), swallowed.clone()).is_err());
+        assert!(run(&OutputCount::exact(2).unwrap(), swallowed).is_err());
+    }
+}
@@ -0,0 +1,141 @@
+// Loss cap: total value of the serialized outputs must not exceed a fixed ceiling
+use crate::ghost::script::{
+    OP_DUP, OP_SIZE, OP_NIP, OP_SWAP, OP_ROT, OP_DROP, OP_SPLIT,
+    OP_CAT, OP_BIN2NUM, OP_ADD, OP_EQUAL, OP_NOT, OP_VERIFY,
+    OP_LESSTHAN, OP_LESSTHANOREQUAL, OP_IF, OP_ENDIF,
+    OP_0, OP_1, OP_2, OP_8, push_bytes, push_number,
+}
+;
+// Script has no loops, so the walk is unrolled once per output slot; a slot
+// with nothing left to parse is skipped, and bytes left after the last slot
+// fail the check rather than going uncounted. The input is the same
+// serialization hashed into hashOutputs, so every output the transaction
+// pays is summed.
+pub const DEFAULT_MAX_CAPPED_OUTPUTS: usize = 8;
+// Script lengths of 0xfd and above use the three-byte varint; larger forms
+// are beyond any relayable output script and are rejected
+const VARINT_U16: i64 = 0xfd;
+const VARINT_U32: i64 = 0xfe;
+pub struct OutputValueCap {
+    max_total: u64,
+    max_outputs: usize,
+}
+
+impl OutputValueCap {
+    pub fn new(max_total: u64) -> Self {
+        Self { max_total, max_outputs: DEFAULT_MAX_CAPPED_OUTPUTS }
+    }
+    // Output slots unrolled; more outputs than this fail the spend
+    pub fn with_max_outputs(mut self, max_outputs: usize) -> Self {
+        self.max_outputs = max_outputs;
+        self
+    }
+    pub fn max_total(&self) -> u64 {
+        self.max_total
+    }
+    pub fn max_outputs(&self) -> usize {
+        self.max_outputs
+    }
+    // Stack: [outputs] → []
+    // Values above 2^31 stay positive via the appended sign byte and are
+    // summed as arbitrary-size script numbers
+    pub fn build(&self) -> Vec<u8> {
+        let mut script = vec![OP_0, OP_SWAP];
+        for _ in 0..self.max_outputs {
+            script.extend(Self::sum_next_output());
+        }
+        script.push(OP_SIZE);
+        script.push(OP_NOT);
+        script.push(OP_VERIFY);
+        script.push(OP_DROP);
+        // Ceilings past i64::MAX exceed any possible total
+        script.extend(push_number(i64::try_from(self.max_total).unwrap_or(i64::MAX)));
+        script.push(OP_LESSTHANOREQUAL);
+        script.push(OP_VERIFY);
+        script
+    }
+    // Stack: [sum] [rest] → [sum + value] [rest after one output]
+    fn sum_next_output() -> Vec<u8> {
+        let mut script = vec![OP_SIZE, OP_IF, OP_8, OP_SPLIT, OP_SWAP];
+        script.extend(unsigned_num());
+        script.push(OP_ROT);
+        script.push(OP_ADD);
+        script.push(OP_SWAP);
+        script.extend(skip_script());
+        script.push(OP_ENDIF);
+        script
+    }
+}
+
+// Stack: [varint(len) || script || rest] → [rest]
+// Fails when the varint claims more bytes than remain
+pub(super) fn skip_script() -> Vec<u8> {
+    let mut script = Vec::new();
+    // Script length varint → [rest] [len]
+    script.push(OP_1);
+    script.push(OP_SPLIT);
+    script.push(OP_SWAP);
+    script.extend(unsigned_num());
+    script.push(OP_DUP);
+    script.extend(push_number(VARINT_U32));
+    script.push(OP_LESSTHAN);
+    script.push(OP_VERIFY);
+    script.push(OP_DUP);
+    script.extend(push_number(VARINT_U16));
+    script.push(OP_EQUAL);
+    script.push(OP_IF);
+    script.push(OP_DROP);
+    script.push(OP_2);
+    script.push(OP_SPLIT);
+    script.push(OP_SWAP);
+    script.extend(unsigned_num());
+    script.push(OP_ENDIF);
+    script.push(OP_SPLIT);
+    script.push(OP_NIP);
+    script
+}
+
+// Little-endian bytes → non-negative script number
+fn unsigned_num() -> Vec<u8> {
+    let mut script = push_bytes(&[0x00]);
+    script.push(OP_CAT);
+    script.push(OP_BIN2NUM);
+    script
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ghost::script::binding_diff::serialize_outputs;
+    use crate::ghost::script::vm::{Interpreter, VmErrorKind};
+    fn run(cap: &OutputValueCap, outputs: &[(u64, Vec<u8>)]) -> Result<bool, VmErrorKind> {
+        let mut script = cap.build();
+        script.push(OP_1);
+        Interpreter::new().eval_with_stack(vec![serialize_outputs(outputs)], &script).map_err(|e| e.kind)
+    }
+    #[test]
+    fn test_total_at_cap_passes() {
+        let outputs = vec![(600, vec![0x51]), (400, vec![0x6a; 0x20])];
+        assert_eq!(run(&OutputValueCap::new(1_000), &outputs), Ok(true));
+        assert_eq!(run(&OutputValueCap::new(999), &outputs), Err(VmErrorKind::VerifyFailed));
+        assert_eq!(run(&OutputValueCap::new(0), &[]), Ok(true));
+    }
+    #[test]
+    fn test_values_above_2_pow_31() {
+        let value = (1u64 << 31) + 7;
+        let outputs = vec![(value, vec![0x51]), (value, vec![0x52; 0x20]), (value, vec![0x53; 300])];
+        let total = 3 * value;
+        assert_eq!(run(&OutputValueCap::new(total), &outputs), Ok(true));
+        assert_eq!(run(&OutputValueCap::new(total - 1), &outputs), Err(VmErrorKind::VerifyFailed));
+        // Top bit set in the last value byte would read negative without the sign byte
+        let large = vec![(0x80u64 << 48, vec![0x51])];
+        assert_eq!(run(&OutputValueCap::new(0x80 << 48), &large), Ok(true));
+        assert_eq!(run(&OutputValueCap::new((0x80 << 48) - 1), &large), Err(VmErrorKind::VerifyFailed));
+    }
+    #[test]
+    fn test_outputs_beyond_slots_fail() {
+        let outputs = vec![(1, vec![0x51]); 3];
+        assert_eq!(run(&OutputValueCap::new(3).with_max_outputs(3), &outputs), Ok(true));
+        assert_eq!(run(&OutputValueCap::new(3).with_max_outputs(2), &outputs), Err(VmErrorKind::VerifyFailed));
+    }
+}
@@ -0,0 +1,55 @@
+// BIP-143 preimage tail: hashOutputs is read at a fixed distance from the end
+// of the preimage, whatever the length of script_code before it
+use crate::ghost::script::SighashPreimage;
+use crate::ghost::script::{
+    OP_SIZE, OP_SUB, OP_SPLIT, OP_NIP, OP_DROP,
+    push_number,
+}
+;
+// hashOutputs (32) || locktime (4) || sighash type (4) end every preimage
+pub(crate) const HASH_OUTPUTS_TAIL: usize = 40;
+pub(crate) const HASH_OUTPUTS_SIZE: usize = 32;
+// nSequence (4) precedes hashOutputs in the tail
+pub(crate) const PREIMAGE_TAIL_SIZE: usize = 4 + HASH_OUTPUTS_TAIL;
+// Stack: [preimage] → [hashOutputs]
+pub fn extract_hash_outputs_from_preimage() -> Vec<u8> {
+    let mut script = Vec::new();
+    script.push(OP_SIZE);
+    script.extend(push_number(HASH_OUTPUTS_TAIL as i64));
+    script.push(OP_SUB);
+    script.push(OP_SPLIT);
+    script.push(OP_NIP);
+    script.extend(push_number(HASH_OUTPUTS_SIZE as i64));
+    script.push(OP_SPLIT);
+    script.push(OP_DROP);
+    script
+}
+// Byte offset of hashOutputs in the serialized preimage, the split point the
+// script above computes
+pub fn hash_outputs_offset(preimage: &SighashPreimage) -> usize {
+    preimage.serialized_size() - HASH_OUTPUTS_TAIL
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ghost::script::SighashPreimageBuilder;
+    use crate::ghost::script::vm::Interpreter;
+    #[test]
+    fn test_offset_matches_extracted_hash_outputs() {
+        // One-byte and 0xfd-prefixed script_code lengths
+        for script_code in [vec![0x51; 25], vec![0x51; 300]] {
+            let preimage = SighashPreimageBuilder::new()
+                .script_code(script_code)
+                .hash_outputs([0x7e; 32])
+                .locktime(800_000)
+                .build();
+            let bytes = preimage.to_bytes();
+            let offset = hash_outputs_offset(&preimage);
+            assert_eq!(bytes[offset..offset + HASH_OUTPUTS_SIZE], preimage.hash_outputs);
+            let mut vm = Interpreter::new();
+            vm.eval_with_stack(vec![bytes.clone()], &extract_hash_outputs_from_preimage()).unwrap();
+            assert_eq!(vm.stack(), [bytes[offset..offset + HASH_OUTPUTS_SIZE].to_vec()]);
+        }
+    }
+}
@@ -0,0 +1,124 @@
+// Self-bound script_code: the preimage must carry the locking script being spent
+use crate::ghost::script::hashing::double_sha256_streaming;
+use crate::ghost::script::{
+    OP_DUP, OP_SPLIT, OP_NIP, OP_DROP, OP_CAT,
+    OP_HASH256, OP_EQUALVERIFY,
+    push_bytes, push_number, varint_len,
+}
+;
+// A script cannot embed its own hash, so the commitment covers the script
+// with the 32 commitment bytes cut out: HASH256(script[..offset] ||
+// script[offset + 32..]). The script_code length and the commitment offset
+// are fixed once the script is assembled, so the section reads script_code at
+// a constant offset after the 104-byte preimage prefix and its varint.
+// Without it a caller can sign a preimage carrying any script_code and the
+// guard's introspection reads someone else's covenant.
+const PREIMAGE_PREFIX_SIZE: usize = 4 + 32 + 32 + 36;
+const COMMITMENT_SIZE: usize = 32;
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ScriptCodeBinding {
+    commitment: [u8; 32],
+    script_code_len: usize,
+    commitment_offset: usize,
+}
+
+impl ScriptCodeBinding {
+    pub fn new(commitment: [u8; 32], script_code_len: usize, commitment_offset: usize) -> Self {
+        Self { commitment, script_code_len, commitment_offset }
+    }
+    /// Placeholder binding for a section placed after `prefix_len` bytes and
+    /// followed by `suffix_len` bytes of locking script. Lengths and offset are
+    /// final; only the commitment is left to `commit`.
+    pub fn fit(prefix_len: usize, suffix_len: usize) -> Self {
+        let mut binding = Self::new([0u8; 32], 0, 0);
+        loop {
+            let section_len = binding.build().len();
+            let fitted = Self::new(
+                binding.commitment,
+                prefix_len + section_len + suffix_len,
+                prefix_len + section_len - 1 - COMMITMENT_SIZE,
+            );
+            if fitted == binding {
+                return binding;
+            }
+            binding = fitted;
+        }
+    }
+    /// Same binding with the commitment computed over `script_code`
+    pub fn commit(&self, script_code: &[u8]) -> Self {
+        Self { commitment: self.expected_commitment(script_code), ..self.clone() }
+    }
+    pub fn expected_commitment(&self, script_code: &[u8]) -> [u8; 32] {
+        let (head, rest) = script_code.split_at(self.commitment_offset.min(script_code.len()));
+        let tail = rest.get(COMMITMENT_SIZE..).unwrap_or_default();
+        double_sha256_streaming([head, tail])
+    }
+    pub fn commitment(&self) -> [u8; 32] {
+        self.commitment
+    }
+    pub fn script_code_len(&self) -> usize {
+        self.script_code_len
+    }
+    pub fn commitment_offset(&self) -> usize {
+        self.commitment_offset
+    }
+    // Stack: [preimage] → [preimage]
+    pub fn build(&self) -> Vec<u8> {
+        let start = PREIMAGE_PREFIX_SIZE + varint_len(self.script_code_len);
+        let mut script = Vec::new();
+        script.push(OP_DUP);
+        script.extend(push_number(start as i64));
+        script.push(OP_SPLIT);
+        script.push(OP_NIP);
+        script.extend(push_number(self.script_code_len as i64));
+        script.push(OP_SPLIT);
+        script.push(OP_DROP);
+        script.extend(push_number(self.commitment_offset as i64));
+        script.push(OP_SPLIT);
+        script.extend(push_number(COMMITMENT_SIZE as i64));
+        script.push(OP_SPLIT);
+        script.push(OP_NIP);
+        script.push(OP_CAT);
+        script.push(OP_HASH256);
+        script.extend(push_bytes(&self.commitment));
+        script.push(OP_EQUALVERIFY);
+        script
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ghost::script::{EcdsaTail, Guard, MulletScript, SighashPreimageBuilder};
+    use crate::ghost::script::vm::{Interpreter, VmErrorKind};
+    fn run(guard: &Guard, preimage: Vec<u8>) -> Result<bool, VmErrorKind> {
+        Interpreter::new().eval_with_stack(vec![preimage], &guard.to_bytes()).map_err(|e| e.kind)
+    }
+    fn bound(pubkey_hash: u8) -> MulletScript {
+        MulletScript::minimal(EcdsaTail::from_pubkey_hash(&[pubkey_hash; 20])).bind_script_code()
+    }
+    #[test]
+    fn test_fit_places_commitment() {
+        let binding = ScriptCodeBinding::fit(40, 300);
+        let section = binding.build();
+        assert_eq!(binding.script_code_len(), 40 + section.len() + 300);
+        let offset = binding.commitment_offset() - 40;
+        assert_eq!(&section[offset..offset + 32], &[0u8; 32]);
+    }
+    #[test]
+    fn test_matching_script_code_passes() {
+        let script = bound(0x11);
+        let pre = SighashPreimageBuilder::new().script_code_from(&script).build().to_bytes();
+        assert_eq!(run(script.guard(), pre), Ok(true));
+    }
+    #[test]
+    fn test_different_script_code_fails() {
+        let script = bound(0x11);
+        // Same length and layout, different tail
+        let other = SighashPreimageBuilder::new().script_code_from(&bound(0x22)).build().to_bytes();
+        assert_eq!(run(script.guard(), other), Err(VmErrorKind::EqualVerifyFailed));
+        let unbound = MulletScript::new(Guard::minimal(), EcdsaTail::from_pubkey_hash(&[0x11; 20]));
+        let short = SighashPreimageBuilder::new().script_code_from(&unbound).build().to_bytes();
+        assert!(run(script.guard(), short).is_err());
+    }
+}
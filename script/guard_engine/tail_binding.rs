@@ -0,0 +1,88 @@
+// Bound tail: the spent script_code must end in the tail the guard was built for
+use crate::ghost::crypto::sha256;
+use crate::ghost::script::{
+    OP_DUP, OP_SIZE, OP_SUB, OP_SPLIT, OP_NIP, OP_DROP,
+    OP_SHA256, OP_EQUALVERIFY,
+    push_bytes, push_number,
+}
+;
+use super::preimage_tail::PREIMAGE_TAIL_SIZE;
+// script_code is followed by value (8) and the fixed preimage tail, and the
+// tail script is the last part of the locking script, so its bytes sit at a
+// fixed distance from the end of the preimage whatever the header and guard
+// sizes. Stops a recursive covenant from keeping the guard but swapping the
+// tail.
+const VALUE_SIZE: usize = 8;
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TailBinding {
+    tail_hash: [u8; 32],
+    tail_len: usize,
+}
+
+impl TailBinding {
+    pub fn new(tail_script: &[u8]) -> Self {
+        Self::from_hash(sha256(tail_script), tail_script.len())
+    }
+    pub fn from_hash(tail_hash: [u8; 32], tail_len: usize) -> Self {
+        Self { tail_hash, tail_len }
+    }
+    pub fn tail_hash(&self) -> [u8; 32] {
+        self.tail_hash
+    }
+    pub fn tail_len(&self) -> usize {
+        self.tail_len
+    }
+    // Stack: [preimage] → [preimage]
+    pub fn build(&self) -> Vec<u8> {
+        let mut script = Vec::new();
+        script.push(OP_DUP);
+        script.push(OP_SIZE);
+        script.extend(push_number((PREIMAGE_TAIL_SIZE + VALUE_SIZE) as i64));
+        script.push(OP_SUB);
+        script.push(OP_SPLIT);
+        script.push(OP_DROP);
+        script.push(OP_SIZE);
+        script.extend(push_number(self.tail_len as i64));
+        script.push(OP_SUB);
+        script.push(OP_SPLIT);
+        script.push(OP_NIP);
+        script.push(OP_SHA256);
+        script.extend(push_bytes(&self.tail_hash));
+        script.push(OP_EQUALVERIFY);
+        script
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ghost::script::{EcdsaTail, Guard, MulletScript, SighashPreimageBuilder};
+    use crate::ghost::script::test_support::run_script;
+    use crate::ghost::script::vm::VmErrorKind;
+    fn preimage(script_code: Vec<u8>) -> Vec<u8> {
+        SighashPreimageBuilder::new()
+            .script_code(script_code)
+            .value(u64::from_le_bytes([0x10; 8]))
+            .hash_outputs([0x33; 32])
+            .build()
+            .to_bytes()
+    }
+    fn run(binding: &TailBinding, preimage: Vec<u8>) -> Result<Vec<Vec<u8>>, VmErrorKind> {
+        run_script(vec![preimage], &binding.build())
+    }
+    #[test]
+    fn test_original_tail_passes() {
+        let script = MulletScript::minimal(EcdsaTail::from_pubkey_hash(&[0x11; 20]));
+        let binding = TailBinding::new(&script.tail().locking_script());
+        let pre = preimage(script.locking_script());
+        assert_eq!(run(&binding, pre.clone()).unwrap(), vec![pre]);
+    }
+    #[test]
+    fn test_swapped_tail_fails() {
+        let original = MulletScript::minimal(EcdsaTail::from_pubkey_hash(&[0x11; 20]));
+        let binding = TailBinding::new(&original.tail().locking_script());
+        let swapped = MulletScript::new(Guard::minimal(), EcdsaTail::from_pubkey_hash(&[0x22; 20]));
+        assert_eq!(run(&binding, preimage(swapped.locking_script())), Err(VmErrorKind::EqualVerifyFailed));
+        assert!(run(&binding, preimage(vec![0x51])).is_err());
+    }
+}
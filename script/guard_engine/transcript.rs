@@ -0,0 +1,194 @@
+// Fiat-Shamir transcript sections: SHA256 chain or Poseidon sponge [P.1]
+use crate::ghost::crypto::sha256;
+#[cfg(feature = "poseidon-script")]
+use crate::ghost::crypto::Fp;
+use crate::ghost::script::{
+    OP_DUP, OP_CAT, OP_SHA256,
+    OP_TOALTSTACK, OP_FROMALTSTACK,
+    push_bytes,
+}
+;
+#[cfg(feature = "poseidon-script")]
+use crate::ghost::script::OP_SWAP;
+#[cfg(feature = "poseidon-script")]
+use crate::ghost::script::field::{bytes_to_fp, fp_to_scriptnum_bytes, FieldElement};
+#[cfg(feature = "poseidon-script")]
+use crate::ghost::script::field_script::{generate_poseidon_hash2_script, generate_reduce_script};
+pub(crate) const DOMAIN_SEPARATOR: &[u8] = b"Halo2_GHOST_Protocol_v1";
+/// Domain tag mixed in when squeezing, by `squeeze` and `TranscriptBuilder`
+pub(crate) const SQUEEZE_TAG: &[u8] = b"squeeze";
+// The running state lives on the alt stack between sections.
+// Sha256: state = SHA256(x || state); mirrored off-chain by
+// `TranscriptBuilder::sha256`. Default, and what every UniversalGuard preset
+// (default_config, strict, paymaster) and Guard::universal use.
+// Poseidon: state = H(state, x) over field elements; mirrored by
+// `TranscriptBuilder::new`, i.e. the chain VerifierContract checks and
+// ProofGenerator witnesses are built against. Opt in with
+// `GuardConfig::with_transcript`; needs the `poseidon-script` feature.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum TranscriptKind {
+    #[default]
+    Sha256,
+    #[cfg(feature = "poseidon-script")]
+    Poseidon,
+}
+
+impl TranscriptKind {
+    pub fn initial_state(&self) -> Vec<u8> {
+        match self {
+            Self::Sha256 => sha256(DOMAIN_SEPARATOR).to_vec(),
+            #[cfg(feature = "poseidon-script")]
+            Self::Poseidon => fp_to_scriptnum_bytes(&poseidon_domain()),
+        }
+    }
+    // → alt [state]
+    pub fn init(&self) -> Vec<u8> {
+        let mut script = Vec::new();
+        match self {
+            Self::Sha256 => {
+                script.extend(push_bytes(DOMAIN_SEPARATOR));
+                script.push(OP_SHA256);
+            }
+            #[cfg(feature = "poseidon-script")]
+            Self::Poseidon => script.extend(push_bytes(&self.initial_state())),
+        }
+        script.push(OP_TOALTSTACK);
+        script
+    }
+    // Stack: [x], alt [state] → alt [state']
+    pub fn absorb(&self) -> Vec<u8> {
+        let mut script = Vec::new();
+        match self {
+            Self::Sha256 => {
+                script.push(OP_FROMALTSTACK);
+                script.push(OP_CAT);
+                script.push(OP_SHA256);
+            }
+            #[cfg(feature = "poseidon-script")]
+            Self::Poseidon => {
+                script.extend(generate_reduce_script());
+                script.push(OP_FROMALTSTACK);
+                script.push(OP_SWAP);
+                script.extend(generate_poseidon_hash2_script());
+            }
+        }
+        script.push(OP_TOALTSTACK);
+        script
+    }
+    // Alt [state] → [state], alt [H(state, "squeeze")]
+    pub fn squeeze(&self) -> Vec<u8> {
+        let mut script = Vec::new();
+        script.push(OP_FROMALTSTACK);
+        script.push(OP_DUP);
+        match self {
+            Self::Sha256 => {
+                script.extend(push_bytes(SQUEEZE_TAG));
+                script.push(OP_CAT);
+                script.push(OP_SHA256);
+            }
+            #[cfg(feature = "poseidon-script")]
+            Self::Poseidon => {
+                let tag = bytes_to_fp(&squeeze_tag_bytes()).expect("tag is below p");
+                script.extend(push_bytes(&fp_to_scriptnum_bytes(&tag)));
+                script.extend(generate_poseidon_hash2_script());
+            }
+        }
+        script.push(OP_TOALTSTACK);
+        script
+    }
+}
+
+#[cfg(feature = "poseidon-script")]
+pub(crate) fn squeeze_tag_bytes() -> FieldElement {
+    let mut bytes = [0u8; 32];
+    bytes[..SQUEEZE_TAG.len()].copy_from_slice(SQUEEZE_TAG);
+    bytes
+}
+
+// Domain digest with the top two bits cleared, so it is always below p
+#[cfg(feature = "poseidon-script")]
+pub(crate) fn poseidon_domain() -> Fp {
+    let mut digest = sha256(DOMAIN_SEPARATOR);
+    digest[31] &= 0x3f;
+    bytes_to_fp(&digest).expect("cleared digest is below p")
+}
+
+#[cfg(all(test, feature = "contract"))]
+mod tests {
+    use super::*;
+    use crate::ghost::script::field_script::fp_to_bytes;
+    use crate::ghost::script::proof_generator::{generate_mock_proof, TranscriptBuilder};
+    use crate::ghost::script::verifier_contract::{frame_length, FieldElement};
+    use crate::ghost::script::vm::{decode_num, execute};
+    use crate::ghost::script::OP_NUMEQUAL;
+    fn absorb_all(kind: TranscriptKind, elements: &[FieldElement]) -> Vec<u8> {
+        let mut script = Vec::new();
+        for element in elements {
+            script.extend(push_bytes(element));
+            script.extend(kind.absorb());
+        }
+        script
+    }
+    #[test]
+    fn test_sha256_init_matches_initial_state() {
+        let mut script = TranscriptKind::Sha256.init();
+        script.push(OP_FROMALTSTACK);
+        assert_eq!(execute(&script).unwrap(), vec![TranscriptKind::Sha256.initial_state()]);
+    }
+    #[test]
+    fn test_sha256_hints_pass_sha256_guard() {
+        let kind = TranscriptKind::Sha256;
+        let hints: Vec<FieldElement> = (0..4u8).map(|i| [i.wrapping_mul(0x3b) | 0x80; 32]).collect();
+        let mut script = kind.init();
+        script.extend(absorb_all(kind, &hints));
+        for _ in 0..3 {
+            script.extend(kind.squeeze());
+        }
+        script.push(OP_FROMALTSTACK);
+        let stack = execute(&script).unwrap();
+        let initial: [u8; 32] = kind.initial_state().try_into().unwrap();
+        let mut transcript = TranscriptBuilder::sha256(&initial);
+        transcript.absorb_many(&hints);
+        let challenges: Vec<Vec<u8>> = (0..3).map(|_| transcript.squeeze_challenge().to_vec()).collect();
+        assert_eq!(&stack[..3], challenges.as_slice());
+        assert_eq!(stack[3], transcript.state_bytes());
+    }
+    #[test]
+    fn test_proof_generator_witness_passes_poseidon_guard() {
+        let kind = TranscriptKind::Poseidon;
+        let prev = fp_to_bytes(&poseidon_domain());
+        let witness = generate_mock_proof(&prev, 2, vec![fp_to_bytes(&Fp::from(42u64))]);
+        let mut elements = vec![witness.operator_binding, frame_length(witness.public_inputs.len())];
+        elements.extend(&witness.public_inputs);
+        elements.push(frame_length(witness.l_terms.len()));
+        for (l, r) in witness.l_terms.iter().zip(&witness.r_terms) {
+            elements.extend([l[0], l[1], r[0], r[1]]);
+        }
+        elements.push(witness.a_scalar);
+        elements.extend(witness.b_scalar);
+        let mut script = kind.init();
+        script.extend(absorb_all(kind, &elements));
+        script.extend(kind.squeeze());
+        script.push(OP_FROMALTSTACK);
+        let stack = execute(&script).unwrap();
+        assert_eq!(decode_num(&stack[0]), decode_num(&witness.next_transcript_hash));
+        let mut transcript = TranscriptBuilder::new(&witness.next_transcript_hash);
+        transcript.squeeze_challenge();
+        assert_eq!(decode_num(&stack[1]), decode_num(&transcript.state_bytes()));
+        // And as an on-chain check against the witness's claimed transcript hash
+        let mut check = script[..script.len() - 1].to_vec();
+        check.extend(push_bytes(&witness.next_transcript_hash));
+        check.push(OP_NUMEQUAL);
+        assert_eq!(execute(&check).unwrap().last(), Some(&vec![1u8]));
+    }
+    #[test]
+    fn test_poseidon_absorb_reduces_wide_input() {
+        let kind = TranscriptKind::Poseidon;
+        let mut script = kind.init();
+        script.extend(absorb_all(kind, &[[0xff; 32]]));
+        script.push(OP_FROMALTSTACK);
+        let stack = execute(&script).unwrap();
+        assert_eq!(stack.len(), 1);
+        assert!(decode_num(&stack[0]) >= 0.into());
+    }
+}
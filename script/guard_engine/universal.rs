@@ -1,17 +1,35 @@
+use std::num::NonZeroUsize;
 use super::verify_public::VerifyPublicData;
 use super::verify_binding::VerifyBinding;
 use super::cleanup::StackCleanup;
+use super::transcript::TranscriptKind;
+use super::tail_binding::TailBinding;
+use super::output_count::OutputCount;
 use crate::ghost::binding::BindingMode;
 use crate::ghost::script::{IpaHints, PoseidonHints};
-use crate::ghost::{Error, Result};
+use crate::ghost::crypto::sha256;
+use crate::ghost::script::{ScriptErrorKind, ScriptResult};
 #[derive(Clone, Debug)]
 pub struct GuardConfig {
     pub num_inputs: usize,
     pub num_app_outputs: usize,
     pub binding_mode: BindingMode,
+    /// Sponsor change outputs pushed as separate items; paymaster binding only
+    pub num_change_outputs: usize,
+    /// Leave SHA256(sighash preimage) beneath the tail witness items after cleanup
     pub preserve_message_hash: bool,
+    /// Tail witness items kept for the tail, e.g. 2 for Ecdsa's [sig] [pubkey]
+    pub tail_witness_items: usize,
     pub ipa_hints: Option<IpaHints>,
     pub poseidon_hints: Option<PoseidonHints>,
+    /// Fiat-Shamir hash; every preset keeps the default SHA256 chain
+    pub transcript: TranscriptKind,
+    /// Check the spent script_code ends in the tail given to `UniversalGuard::with_tail_binding`
+    pub bind_tail: bool,
+    /// Ceiling on the summed value of every output the spend creates
+    pub output_value_cap: Option<u64>,
+    /// Inclusive bounds on the number of outputs the spend creates
+    pub output_count: Option<OutputCount>,
 }
 
 impl GuardConfig {
@@ -20,9 +38,15 @@ impl GuardConfig {
             num_inputs,
             num_app_outputs,
             binding_mode: BindingMode::Strict,
+            num_change_outputs: 1,
             preserve_message_hash: true,
+            tail_witness_items: 1,
             ipa_hints: None,
             poseidon_hints: None,
+            transcript: TranscriptKind::Sha256,
+            bind_tail: false,
+            output_value_cap: None,
+            output_count: None,
         }
     }
     pub fn strict(mut self) -> Self {
@@ -33,10 +57,19 @@ impl GuardConfig {
         self.binding_mode = BindingMode::Partial;
         self
     }
+    // e.g. relayer and treasury fee outputs
+    pub fn with_change_outputs(mut self, num_change_outputs: usize) -> Self {
+        self.num_change_outputs = num_change_outputs;
+        self
+    }
     pub fn preserve_message(mut self, preserve: bool) -> Self {
         self.preserve_message_hash = preserve;
         self
     }
+    pub fn with_tail_witness_items(mut self, count: usize) -> Self {
+        self.tail_witness_items = count;
+        self
+    }
     pub fn with_ipa_hints(mut self, hints: IpaHints) -> Self {
         self.ipa_hints = Some(hints);
         self
@@ -45,11 +78,71 @@ impl GuardConfig {
         self.poseidon_hints = Some(hints);
         self
     }
+    /// Poseidon makes the guard accept ProofGenerator witnesses directly
+    pub fn with_transcript(mut self, transcript: TranscriptKind) -> Self {
+        self.transcript = transcript;
+        self
+    }
+    pub fn bind_tail(mut self, bind: bool) -> Self {
+        self.bind_tail = bind;
+        self
+    }
+    pub fn with_output_value_cap(mut self, max_total: u64) -> Self {
+        self.output_value_cap = Some(max_total);
+        self
+    }
+    /// Fails if `exact` exceeds MAX_COUNTED_OUTPUTS
+    pub fn with_output_count(mut self, exact: u8) -> ScriptResult<Self> {
+        self.output_count = Some(OutputCount::exact(exact)?);
+        Ok(self)
+    }
+    /// Fails if `min > max` or `max` exceeds MAX_COUNTED_OUTPUTS
+    pub fn with_output_count_range(mut self, min: u8, max: u8) -> ScriptResult<Self> {
+        self.output_count = Some(OutputCount::range(min, max)?);
+        Ok(self)
+    }
+    pub fn ipa_rounds(&self) -> usize {
+        self.ipa_hints.as_ref().map_or(0, |h| h.num_rounds())
+    }
     pub fn expected_stack_size(&self) -> usize {
-        1 + (self.num_inputs * 3) + (self.num_app_outputs * 3) + 3
+        1 + (self.num_inputs * 3) + (self.num_app_outputs * 3) + 3 + self.extra_change_items()
+            + self.tail_witness_items.saturating_sub(1)
+    }
+    // The base layout has room for one change item
+    fn extra_change_items(&self) -> usize {
+        match self.binding_mode {
+            BindingMode::Strict => 0,
+            BindingMode::Partial => self.num_change_outputs.saturating_sub(1),
+        }
+    }
+    // Without IPA hints the public data is absorbed and no challenges are squeezed
+    fn verify_public(&self) -> VerifyPublicData {
+        let verify = VerifyPublicData::new(self.num_inputs, self.num_app_outputs).with_transcript(self.transcript);
+        match NonZeroUsize::new(self.ipa_rounds()) {
+            Some(rounds) => verify.with_rounds(rounds),
+            None => verify,
+        }
+    }
+    fn verify_binding(&self) -> VerifyBinding {
+        let binding = VerifyBinding::new(self.num_app_outputs, self.binding_mode)
+            .with_change_outputs(self.num_change_outputs);
+        let binding = match self.output_value_cap {
+            Some(max_total) => binding.with_output_value_cap(max_total),
+            None => binding,
+        };
+        match self.output_count {
+            Some(count) => binding.with_output_count(count),
+            None => binding,
+        }
+    }
+    /// Hash of the guard script this config builds; lets a wallet confirm a
+    /// prover's hints were produced for the same guard. The `bind_tail`
+    /// check depends on the tail, not the config, and is not covered.
+    pub fn fingerprint(&self) -> [u8; 32] {
+        sha256(&UniversalGuard::new(self.clone()).assemble(None))
     }
     pub fn items_to_drop(&self) -> usize {
-        self.expected_stack_size() - 1 - if self.preserve_message_hash { 1 } else { 0 }
+        self.expected_stack_size() - self.tail_witness_items - usize::from(self.preserve_message_hash)
     }
 }
 
@@ -61,11 +154,17 @@ impl Default for GuardConfig {
 
 pub struct UniversalGuard {
     config: GuardConfig,
+    tail_binding: Option<TailBinding>,
 }
 
 impl UniversalGuard {
     pub fn new(config: GuardConfig) -> Self {
-        Self { config }
+        Self { config, tail_binding: None }
+    }
+    // Tail to bind when the config sets bind_tail
+    pub fn with_tail_binding(mut self, binding: TailBinding) -> Self {
+        self.tail_binding = Some(binding);
+        self
     }
     pub fn default_config(num_inputs: usize, num_app_outputs: usize) -> Self {
         Self::new(GuardConfig::new(num_inputs, num_app_outputs))
@@ -76,62 +175,78 @@ impl UniversalGuard {
     pub fn paymaster(num_inputs: usize, num_app_outputs: usize, max_fee: u64) -> Self {
         Self::new(GuardConfig::new(num_inputs, num_app_outputs).paymaster(max_fee))
     }
-    pub fn build(&self) -> Vec<u8> {
-        let mut script = Vec::new();
-        let verify_public = VerifyPublicData::new(
-            self.config.num_inputs,
-            self.config.num_app_outputs,
-        );
-        script.extend(verify_public.build());
-        let verify_binding = VerifyBinding::new(
-            self.config.num_app_outputs,
-            self.config.binding_mode,
-        );
-        script.extend(verify_binding.build());
+    /// Fails if the config sets `bind_tail` without `with_tail_binding`,
+    /// rather than leaving out a tail check it has no tail for
+    pub fn build(&self) -> ScriptResult<Vec<u8>> {
+        Ok(self.assemble(self.bound_tail()?))
+    }
+    /// `build` without the stack cleanup
+    pub fn build_verification(&self) -> ScriptResult<Vec<u8>> {
+        Ok(self.assemble_verification(self.bound_tail()?))
+    }
+    fn assemble(&self, tail: Option<&TailBinding>) -> Vec<u8> {
+        let mut script = self.assemble_verification(tail);
         let cleanup = StackCleanup::new(self.config.items_to_drop())
-            .preserve_tail(true)
+            .preserve_tail_items(self.config.tail_witness_items)
             .preserve_message(self.config.preserve_message_hash);
         script.extend(cleanup.build());
         script
     }
-    pub fn build_verification(&self) -> Vec<u8> {
+    fn assemble_verification(&self, tail: Option<&TailBinding>) -> Vec<u8> {
         let mut script = Vec::new();
-        let verify_public = VerifyPublicData::new(
-            self.config.num_inputs,
-            self.config.num_app_outputs,
-        );
-        script.extend(verify_public.build());
-        let verify_binding = VerifyBinding::new(
-            self.config.num_app_outputs,
-            self.config.binding_mode,
-        );
-        script.extend(verify_binding.build());
+        script.extend(self.config.verify_public().build());
+        script.extend(self.config.verify_binding().build());
+        if let Some(binding) = tail {
+            script.extend(binding.build());
+        }
         script
     }
     pub fn config(&self) -> &GuardConfig {
         &self.config
     }
+    fn bound_tail(&self) -> ScriptResult<Option<&TailBinding>> {
+        match (self.config.bind_tail, &self.tail_binding) {
+            (false, _) => Ok(None),
+            (true, Some(binding)) => Ok(Some(binding)),
+            (true, None) => Err(ScriptErrorKind::InvalidGuardConfig {
+                reason: "bind_tail requires a tail binding".to_string(),
+            }),
+        }
+    }
     pub fn size_estimate(&self) -> usize {
-        let verify_public_size = 500 + (self.config.num_inputs + self.config.num_app_outputs) * 50;
+        // Built rather than estimated: a Poseidon transcript costs a whole
+        // permutation, about 10 KB, per absorb and squeeze
+        let verify_public_size = self.config.verify_public().build().len();
         let verify_binding_size = 200;
         let cleanup_size = 50;
+        let tail_binding_size = self.bound_tail().ok().flatten().map_or(0, |b| b.build().len());
+        let binding = self.config.verify_binding();
+        let output_cap_size = binding.output_value_cap()
+            .map_or(0, |_| 45 * (self.config.num_app_outputs + binding.num_change_outputs()));
+        let output_count_size = binding.output_count()
+            .map_or(0, |count| count.build().len() + 1);
         let ipa_hints_size = self.config.ipa_hints
             .as_ref()
             .map(|h| h.size())
             .unwrap_or(2000);
-        verify_public_size + verify_binding_size + cleanup_size + ipa_hints_size
+        verify_public_size + verify_binding_size + cleanup_size + tail_binding_size + output_cap_size
+            + output_count_size + ipa_hints_size
     }
-    pub fn validate(&self) -> Result<()> {
+    pub fn validate(&self) -> ScriptResult<()> {
         if self.config.num_inputs == 0 {
-            return Err(Error::InvalidInput("At least one input required".to_string()));
+            return Err(ScriptErrorKind::InvalidGuardConfig { reason: "At least one input required".to_string() });
         }
         if self.config.num_inputs > 16 {
-            return Err(Error::InvalidInput("Too many inputs (max 16)".to_string()));
+            return Err(ScriptErrorKind::InvalidGuardConfig { reason: "Too many inputs (max 16)".to_string() });
         }
         if self.config.num_app_outputs > 16 {
-            return Err(Error::InvalidInput("Too many outputs (max 16)".to_string()));
+            return Err(ScriptErrorKind::InvalidGuardConfig { reason: "Too many outputs (max 16)".to_string() });
+        }
+        if self.config.ipa_hints.is_some() && self.config.ipa_rounds() == 0 {
+            return Err(ScriptErrorKind::InvalidGuardConfig { reason: "IPA hints have zero rounds".to_string() });
         }
-        Ok(())
+        self.bound_tail()?;
+        self.config.verify_binding().validate()
     }
 }
 
@@ -153,14 +268,38 @@ mod tests {
     fn test_guard_expected_stack_size() {
         let config = GuardConfig::new(1, 1);
         assert_eq!(config.expected_stack_size(), 10);
+        assert_eq!(config.clone().with_change_outputs(3).expected_stack_size(), 10);
+        assert_eq!(config.paymaster(1000).with_change_outputs(3).expected_stack_size(), 12);
+    }
+    #[test]
+    fn test_change_outputs_threaded() {
+        let two = GuardConfig::new(1, 1).paymaster(1000).with_change_outputs(2);
+        let binding = VerifyBinding::new(1, BindingMode::Partial).with_change_outputs(2).build();
+        let script = UniversalGuard::new(two.clone()).build_verification().unwrap();
+        assert!(script.windows(binding.len()).any(|w| w == binding.as_slice()));
+        assert_ne!(two.fingerprint(), GuardConfig::new(1, 1).paymaster(1000).fingerprint());
+        assert!(UniversalGuard::new(two.with_change_outputs(0)).validate().is_err());
     }
     #[test]
     fn test_universal_guard_build() {
         let guard = UniversalGuard::strict(1, 1);
-        let script = guard.build();
+        let script = guard.build().unwrap();
         assert!(!script.is_empty());
     }
     #[test]
+    fn test_bind_tail_without_binding_is_an_error() {
+        let config = GuardConfig::new(1, 1).paymaster(1000).bind_tail(true);
+        let unbound = UniversalGuard::new(config.clone());
+        let err = unbound.build().unwrap_err();
+        assert!(matches!(err, ScriptErrorKind::InvalidGuardConfig { ref reason } if reason.contains("tail binding")));
+        assert_eq!(unbound.build_verification(), Err(err.clone()));
+        assert_eq!(unbound.validate(), Err(err));
+        assert_eq!(config.fingerprint(), GuardConfig::new(1, 1).paymaster(1000).fingerprint());
+        let tail = TailBinding::new(&[0x51; 30]);
+        let bound = UniversalGuard::new(config).with_tail_binding(tail.clone());
+        assert!(bound.build().unwrap().windows(tail.build().len()).any(|w| w == tail.build().as_slice()));
+    }
+    #[test]
     fn test_universal_guard_validate() {
         let guard = UniversalGuard::strict(1, 1);
         assert!(guard.validate().is_ok());
@@ -168,11 +307,147 @@ mod tests {
         assert!(guard.validate().is_err());
     }
     #[test]
+    fn test_zero_app_outputs_validate() {
+        assert!(UniversalGuard::paymaster(1, 0, 1000).validate().is_ok());
+        assert!(UniversalGuard::strict(1, 0).validate().is_err());
+    }
+    #[test]
+    fn test_zero_ipa_rounds_rejected() {
+        use crate::ghost::script::Guard;
+        let config = GuardConfig::new(1, 1).with_ipa_hints(IpaHints::placeholder(0));
+        assert!(UniversalGuard::new(config).validate().is_err());
+        // Without hints no challenge section is emitted, and no placeholder stands in for it
+        let unhinted = UniversalGuard::default_config(1, 1);
+        assert!(unhinted.validate().is_ok());
+        assert!(Guard::custom(unhinted.build().unwrap()).assert_secure().is_ok());
+    }
+    #[test]
+    fn test_output_value_cap_threaded() {
+        let plain = GuardConfig::new(1, 2).paymaster(1000).with_change_outputs(2);
+        let capped = plain.clone().with_output_value_cap(50_000);
+        let binding = VerifyBinding::new(2, BindingMode::Partial).with_change_outputs(2).with_output_value_cap(50_000).build();
+        let script = UniversalGuard::new(capped.clone()).build_verification().unwrap();
+        assert!(script.windows(binding.len()).any(|w| w == binding.as_slice()));
+        assert_ne!(capped.fingerprint(), plain.fingerprint());
+        assert!(UniversalGuard::new(capped).size_estimate() > UniversalGuard::new(plain).size_estimate());
+    }
+    #[test]
+    fn test_output_count_threaded() {
+        let plain = GuardConfig::new(1, 2).paymaster(1000).with_change_outputs(2);
+        let counted = plain.clone().with_output_count_range(3, 4).unwrap();
+        assert!(plain.clone().with_output_count_range(4, 3).is_err());
+        let binding = VerifyBinding::new(2, BindingMode::Partial).with_change_outputs(2)
+            .with_output_count(OutputCount::range(3, 4).unwrap())
+            .build();
+        let script = UniversalGuard::new(counted.clone()).build_verification().unwrap();
+        assert!(script.windows(binding.len()).any(|w| w == binding.as_slice()));
+        assert_eq!(plain.clone().with_output_count(4).unwrap().output_count, OutputCount::exact(4).ok());
+        assert!(plain.clone().with_output_count(u8::MAX).is_err());
+        assert_ne!(counted.fingerprint(), plain.fingerprint());
+        assert!(UniversalGuard::new(counted).size_estimate() > UniversalGuard::new(plain).size_estimate());
+    }
+    #[test]
+    #[cfg(feature = "poseidon-script")]
+    fn test_transcript_kind_threaded() {
+        use crate::ghost::script::field_script::generate_poseidon_hash2_script;
+        let sha = GuardConfig::new(1, 1);
+        let poseidon = GuardConfig::new(1, 1).with_transcript(TranscriptKind::Poseidon);
+        assert_eq!(sha.transcript, TranscriptKind::Sha256);
+        let hash2 = generate_poseidon_hash2_script();
+        let contains_hash2 = |script: &[u8]| script.windows(hash2.len()).any(|w| w == hash2.as_slice());
+        assert!(!contains_hash2(&UniversalGuard::new(sha.clone()).build().unwrap()));
+        assert!(contains_hash2(&UniversalGuard::new(poseidon.clone()).build().unwrap()));
+        assert_ne!(sha.fingerprint(), poseidon.fingerprint());
+    }
+    #[test]
+    #[cfg(feature = "poseidon-script")]
+    fn test_poseidon_transcript_guard_runs() {
+        use crate::ghost::script::field::bytes_to_fp;
+        use crate::ghost::script::test_support::preimage_committing_to;
+        use crate::ghost::script::vm::{Interpreter, VmErrorKind};
+        let config = GuardConfig::new(1, 1)
+            .with_ipa_hints(IpaHints::placeholder(2))
+            .with_transcript(TranscriptKind::Poseidon);
+        let guard = UniversalGuard::new(config.clone());
+        let verification = guard.build_verification().unwrap();
+        let (value, script) = (vec![0x22; 8], vec![0x33; 32]);
+        let outputs = [value.clone(), vec![0x20], script.clone()].concat();
+        let preimage = preimage_committing_to(&outputs);
+        // Witness triples, then the app output, the tail witness and the preimage
+        let mut stack: Vec<Vec<u8>> = (0..6).map(|i| vec![0x10 + i; 4]).collect();
+        stack.extend([script, value, vec![0x30; 71], preimage]);
+        assert_eq!(stack.len(), config.expected_stack_size());
+        let mut vm = Interpreter::new();
+        vm.eval_with_stack(stack.clone(), &verification).unwrap();
+        assert_eq!(vm.stack(), stack.as_slice());
+        // The Poseidon sponge leaves a field element, not a SHA-256 digest
+        let state = vm.alt_stack().last().unwrap().clone();
+        let mut element = [0u8; 32];
+        element[..state.len()].copy_from_slice(&state);
+        assert!(bytes_to_fp(&element).is_some());
+        assert_ne!(state, vec![0u8; 32]);
+        // The transcript check does not stand in for the output binding
+        let mut tampered = stack.clone();
+        tampered[7] = vec![0x23; 8];
+        let err = Interpreter::new().eval_with_stack(tampered, &verification).unwrap_err();
+        assert_eq!(err.kind, VmErrorKind::EqualVerifyFailed);
+        // Five permutations: one absorb, then a squeeze and an absorb per round
+        let sha = UniversalGuard::new(GuardConfig::new(1, 1).with_ipa_hints(IpaHints::placeholder(2)));
+        assert!(verification.len() > sha.build_verification().unwrap().len() + 5 * 9_000);
+        assert!(guard.size_estimate() > verification.len());
+    }
+    #[test]
+    fn test_bind_tail_threaded() {
+        let binding = TailBinding::new(&[0x51; 25]);
+        let plain = UniversalGuard::new(GuardConfig::new(1, 1)).with_tail_binding(binding.clone());
+        let bound = UniversalGuard::new(GuardConfig::new(1, 1).bind_tail(true)).with_tail_binding(binding.clone());
+        assert_eq!(bound.build_verification().unwrap().len(), plain.build_verification().unwrap().len() + binding.build().len());
+        assert!(bound.build_verification().unwrap().ends_with(&binding.build()));
+        assert!(bound.validate().is_ok());
+        assert!(UniversalGuard::new(GuardConfig::new(1, 1).bind_tail(true)).validate().is_err());
+    }
+    #[test]
     fn test_guard_size_estimate() {
         let guard = UniversalGuard::strict(1, 1);
         let size = guard.size_estimate();
         assert!(size > 0);
         assert!(size < 10000);
     }
+    #[test]
+    fn test_preserved_message_hash_survives_ecdsa_tail() {
+        use crate::ghost::crypto::double_sha256;
+        use crate::ghost::script::{ecdsa_public_key, sign_ecdsa, EcdsaTail, Tail, OP_ROT, OP_SHA256, OP_EQUALVERIFY, push_bytes};
+        use crate::ghost::script::vm::{Interpreter, EcdsaSignatureChecker};
+        let config = GuardConfig::new(1, 1).preserve_message(true).with_tail_witness_items(2);
+        let guard = UniversalGuard::new(config.clone());
+        let verification = guard.build_verification().unwrap();
+        let full = guard.build().unwrap();
+        assert!(full.starts_with(&verification));
+        let cleanup = &full[verification.len()..];
+        // Post-verification layout: everything else, then the tail witness, then the preimage
+        let privkey = [0x07; 32];
+        let pubkey = ecdsa_public_key(&privkey).unwrap();
+        let preimage = vec![0x5a; 180];
+        let sighash = double_sha256(&preimage);
+        let signature = sign_ecdsa(&privkey, &sighash, 0x41).unwrap().to_bytes();
+        let mut stack = vec![vec![0x01]; config.items_to_drop()];
+        stack.extend([signature, pubkey.to_vec(), preimage]);
+        assert_eq!(stack.len(), config.expected_stack_size());
+        // The tail hashes the preserved item once more into the digest its
+        // CHECKSIG verifies, consuming it
+        let tail = |digest: &[u8; 32]| {
+            let mut script = vec![OP_ROT, OP_SHA256];
+            script.extend(push_bytes(digest));
+            script.push(OP_EQUALVERIFY);
+            script.extend(EcdsaTail::from_pubkey(&pubkey).locking_script());
+            script
+        };
+        let run = |digest: &[u8; 32]| {
+            let mut vm = Interpreter::with_checker(Box::new(EcdsaSignatureChecker::new(sighash)));
+            let result = vm.eval_with_stack(stack.clone(), &[cleanup, &tail(digest)].concat());
+            result.map(|_| vm.stack().to_vec())
+        };
+        assert_eq!(run(&sighash).unwrap(), vec![vec![1]]);
+        assert!(run(&sha256(&sighash)).is_err());
+    }
 }
-
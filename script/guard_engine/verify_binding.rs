@@ -1,22 +1,134 @@
+use super::output_value::OutputValueCap;
+use super::output_count::OutputCount;
+use super::preimage_tail::{HASH_OUTPUTS_SIZE, HASH_OUTPUTS_TAIL};
 use crate::ghost::binding::BindingMode;
+use crate::ghost::script::{ScriptErrorKind, ScriptResult};
 use crate::ghost::script::{
     OP_DUP, OP_PICK, OP_DROP, OP_SWAP,
     OP_CAT, OP_SHA256, OP_EQUALVERIFY, OP_FALSE,
-    OP_SPLIT, OP_SIZE,
-    OP_1, OP_2, OP_3, OP_4,
+    OP_SPLIT, OP_SIZE, OP_SUB, OP_GREATERTHANOREQUAL, OP_VERIFY,
+    OP_LESSTHAN, OP_NUM2BIN, OP_IF, OP_ELSE, OP_ENDIF,
+    OP_1, OP_2, OP_3,
+    push_bytes,
 }
 ;
 const OUTPUT_SERIALIZED_SIZE: usize = 41;
+const OUTPUT_VALUE_SIZE: usize = 8;
+// Varlen scripts below 0xfd take a one-byte length, up to 0xffff the
+// 0xfd-prefixed two-byte form; longer scripts are rejected
+const VARINT_U16: usize = 0xfd;
+const MAX_VARLEN_SCRIPT: usize = 0xffff;
+// Largest stack element the output serialization may grow to; BSV capped
+// OP_CAT results at the element size limit before Genesis
+pub const DEFAULT_MAX_ELEMENT_SIZE: usize = 10_000;
+// Stack below the output items: [changes...] [preimage] in Partial mode, one
+// item per change output; Strict keeps one other item above its preimage.
+// Each app output is three items, [_] [script] [value] bottom first.
 pub struct VerifyBinding {
     num_app_outputs: usize,
     binding_mode: BindingMode,
+    num_change_outputs: usize,
+    output_value_cap: Option<u64>,
+    output_count: Option<OutputCount>,
+    varlen: bool,
+    max_element_size: usize,
 }
 
 impl VerifyBinding {
     pub fn new(num_app_outputs: usize, binding_mode: BindingMode) -> Self {
-        Self { num_app_outputs, binding_mode }
+        Self {
+            num_app_outputs,
+            binding_mode,
+            num_change_outputs: 1,
+            output_value_cap: None,
+            output_count: None,
+            varlen: false,
+            max_element_size: DEFAULT_MAX_ELEMENT_SIZE,
+        }
+    }
+    // App outputs serialized as value || varint(script_len) || script, so
+    // scripts of any length (e.g. OP_RETURN metadata) hash correctly
+    pub fn new_varlen(num_app_outputs: usize, binding_mode: BindingMode) -> Self {
+        Self { varlen: true, ..Self::new(num_app_outputs, binding_mode) }
+    }
+    pub fn is_varlen(&self) -> bool {
+        self.varlen
+    }
+    // Sponsor-controlled change outputs hashed after the app outputs (Partial only)
+    pub fn with_change_outputs(mut self, num_change_outputs: usize) -> Self {
+        self.num_change_outputs = num_change_outputs;
+        self
+    }
+    // Sum the serialized outputs before hashing and fail above `max_total`
+    pub fn with_output_value_cap(mut self, max_total: u64) -> Self {
+        self.output_value_cap = Some(max_total);
+        self
+    }
+    pub fn output_value_cap(&self) -> Option<u64> {
+        self.output_value_cap
+    }
+    // Parse the serialized outputs before hashing and fail unless there are
+    // `min..=max` of them
+    pub fn with_output_count(mut self, count: OutputCount) -> Self {
+        self.output_count = Some(count);
+        self
+    }
+    pub fn output_count(&self) -> Option<OutputCount> {
+        self.output_count
+    }
+    // Element size limit enforced by `build_checked`
+    pub fn with_max_element_size(mut self, max_element_size: usize) -> Self {
+        self.max_element_size = max_element_size;
+        self
+    }
+    pub fn max_element_size(&self) -> usize {
+        self.max_element_size
+    }
+    // Largest serialization the OP_CATs can accumulate before hashing. Fixed
+    // app outputs are exact and varlen ones are capped at MAX_VARLEN_SCRIPT.
+    // Change outputs are whole stack items the script does not bound, so
+    // each counts at the element size limit.
+    pub fn max_concatenated_size(&self) -> usize {
+        let app_output = if self.varlen {
+            OUTPUT_VALUE_SIZE + 3 + MAX_VARLEN_SCRIPT
+        } else {
+            OUTPUT_SERIALIZED_SIZE
+        };
+        self.num_app_outputs * app_output + self.num_change_outputs() * self.max_element_size
+    }
+    // `build` after `validate`, failing when the output serialization would
+    // outgrow the element size limit
+    pub fn build_checked(&self) -> ScriptResult<Vec<u8>> {
+        self.validate()?;
+        let size = self.max_concatenated_size();
+        if size > self.max_element_size {
+            return Err(ScriptErrorKind::ElementTooLarge { size, max: self.max_element_size });
+        }
+        Ok(self.build())
+    }
+    pub fn num_change_outputs(&self) -> usize {
+        match self.binding_mode {
+            BindingMode::Strict => 0,
+            BindingMode::Partial => self.num_change_outputs,
+        }
+    }
+    pub fn validate(&self) -> ScriptResult<()> {
+        if self.num_change_outputs == 0 && matches!(self.binding_mode, BindingMode::Partial) {
+            return Err(ScriptErrorKind::InvalidGuardConfig {
+                reason: "Paymaster binding needs at least one change output; use strict binding otherwise".to_string(),
+            });
+        }
+        if self.num_app_outputs == 0 && matches!(self.binding_mode, BindingMode::Strict) {
+            return Err(ScriptErrorKind::InvalidGuardConfig {
+                reason: "Strict binding with zero app outputs commits to an empty output set; use paymaster binding for fee/burn spends".to_string(),
+            });
+        }
+        Ok(())
     }
     pub fn build(&self) -> Vec<u8> {
+        if self.num_app_outputs == 0 {
+            return self.build_change_only();
+        }
         match self.binding_mode {
             BindingMode::Strict => self.build_strict(),
             BindingMode::Partial => self.build_paymaster(),
@@ -25,80 +137,152 @@ impl VerifyBinding {
     fn build_strict(&self) -> Vec<u8> {
         let mut script = Vec::new();
         script.extend(self.serialize_outputs());
+        script.extend(self.cap_outputs());
+        script.extend(self.count_outputs());
         script.push(OP_SHA256);
         script.push(OP_SHA256);
-        script.extend(self.extract_hash_outputs());
+        script.extend(extract_checked_hash_outputs());
         script.push(OP_EQUALVERIFY);
         script
     }
     fn build_paymaster(&self) -> Vec<u8> {
         let mut script = Vec::new();
         script.extend(self.serialize_outputs());
-        let expected_app_length = self.num_app_outputs * 41;
-        script.push(OP_DUP);
-        script.push(OP_SIZE);
-        script.extend(push_number(expected_app_length));
+        // Varlen outputs delimit themselves: 8-byte values, lengths taken from the scripts
+        if !self.varlen {
+            let expected_app_length = self.num_app_outputs * OUTPUT_SERIALIZED_SIZE;
+            script.push(OP_SIZE);
+            script.extend(push_number(expected_app_length));
+            script.push(OP_EQUALVERIFY);
+        }
+        script.extend(self.append_change());
+        script.extend(self.cap_outputs());
+        script.extend(self.count_outputs());
+        script.push(OP_SHA256);
+        script.push(OP_SHA256);
+        script.extend(extract_checked_hash_outputs());
         script.push(OP_EQUALVERIFY);
-        script.push(OP_2);
-        script.push(OP_PICK);
-        script.push(OP_DUP);
-        script.push(OP_SIZE);
-        script.push(OP_SWAP);
-        script.push(OP_CAT);
+        script
+    }
+    // Zero app outputs: hashOutputs covers only the change serialization (empty in Strict)
+    // Stack: [changes...] [preimage] (Partial) or [preimage] (Strict)
+    fn build_change_only(&self) -> Vec<u8> {
+        let mut script = vec![OP_FALSE];
+        script.extend(self.append_change());
+        script.extend(self.cap_outputs());
+        script.extend(self.count_outputs());
         script.push(OP_SHA256);
         script.push(OP_SHA256);
-        script.extend(self.extract_hash_outputs());
+        script.extend(extract_checked_hash_outputs());
         script.push(OP_EQUALVERIFY);
         script
     }
+    // Stack: [changes...] [preimage] [acc] → [changes...] [preimage] [acc || changes]
+    fn append_change(&self) -> Vec<u8> {
+        let n = self.num_change_outputs();
+        let mut script = Vec::new();
+        for j in 0..n {
+            script.extend(push_number(n + 1 - j));
+            script.push(OP_PICK);
+            script.push(OP_CAT);
+        }
+        script
+    }
+    // Stack: [outputs] → [outputs]; the hash check that follows ties the
+    // summed bytes to hashOutputs
+    fn cap_outputs(&self) -> Vec<u8> {
+        let Some(max_total) = self.output_value_cap else {
+            return Vec::new();
+        };
+        let cap = OutputValueCap::new(max_total)
+            .with_max_outputs(self.num_app_outputs + self.num_change_outputs());
+        let mut script = vec![OP_DUP];
+        script.extend(cap.build());
+        script
+    }
+    // Stack: [outputs] → [outputs]
+    fn count_outputs(&self) -> Vec<u8> {
+        let Some(count) = self.output_count else {
+            return Vec::new();
+        };
+        let mut script = vec![OP_DUP];
+        script.extend(count.build());
+        script
+    }
+    // Items between the accumulator and the first app output item
+    fn items_above_outputs(&self) -> usize {
+        match self.binding_mode {
+            BindingMode::Strict => 2,
+            BindingMode::Partial => 1 + self.num_change_outputs,
+        }
+    }
+    // Pushes value || 0x20 || script for each app output, first output first;
+    // value || varint(len) || script in varlen mode
     fn serialize_outputs(&self) -> Vec<u8> {
         let mut script = Vec::new();
         script.push(OP_FALSE);
         for i in 0..self.num_app_outputs {
-            let output_base = 3 + (self.num_app_outputs - 1 - i) * 3;
-            script.push(op_n(output_base + 1 + 1));
+            let value_depth = 1 + self.items_above_outputs() + (self.num_app_outputs - 1 - i) * 3;
+            script.extend(push_number(value_depth + 1));
             script.push(OP_PICK);
-            script.push(op_n(output_base + 0 + 2));
+            script.extend(push_number(value_depth + 1));
             script.push(OP_PICK);
+            if self.varlen {
+                script.push(OP_SIZE);
+                script.extend(push_number(OUTPUT_VALUE_SIZE));
+                script.push(OP_EQUALVERIFY);
+                script.push(OP_SWAP);
+                script.extend(script_length_varint());
+            } else {
+                script.push(OP_SWAP);
+                script.extend(push_bytes(&[0x20]));
+            }
             script.push(OP_SWAP);
-            script.push(0x01);
-            script.push(0x20);
             script.push(OP_CAT);
             script.push(OP_CAT);
             script.push(OP_CAT);
         }
         script
     }
-    fn extract_hash_outputs(&self) -> Vec<u8> {
-        let mut script = Vec::new();
-        script.push(OP_1);
-        script.push(OP_PICK);
-        script.push(OP_SIZE);
-        script.push(0x01);
-        script.push(40);
-        script.push(0x94);
-        script.push(OP_SPLIT);
-        script.push(0x01);
-        script.push(32);
-        script.push(OP_SPLIT);
-        script.push(OP_DROP);
-        script.push(OP_SWAP);
-        script.push(OP_DROP);
-        script
-    }
 }
 
-fn op_n(n: usize) -> u8 {
-    match n {
-        0 => OP_FALSE,
-        1 => OP_1,
-        2 => OP_2,
-        3 => OP_3,
-        4 => OP_4,
-        _ => {
-            OP_4
-        }
-    }
+// Stack: [preimage] [x] → [preimage] [x] [hashOutputs]
+// A preimage shorter than the tail fails the OP_VERIFY instead of
+// aborting inside OP_SPLIT. Splits at the size it already checked, so it
+// does not reuse `extract_hash_outputs_from_preimage`; these bytes are
+// part of every GuardConfig fingerprint, and `classify_guard` looks for them.
+pub(crate) fn extract_checked_hash_outputs() -> Vec<u8> {
+    let mut script = vec![OP_1, OP_PICK, OP_SIZE, OP_DUP];
+    script.extend(push_number(HASH_OUTPUTS_TAIL));
+    script.push(OP_GREATERTHANOREQUAL);
+    script.push(OP_VERIFY);
+    script.extend(push_number(HASH_OUTPUTS_TAIL));
+    script.push(OP_SUB);
+    script.push(OP_SPLIT);
+    script.extend(push_number(HASH_OUTPUTS_SIZE));
+    script.push(OP_SPLIT);
+    script.push(OP_DROP);
+    script.push(OP_SWAP);
+    script.push(OP_DROP);
+    script
+}
+
+// Stack: [script] → [script] [varint(len)]
+fn script_length_varint() -> Vec<u8> {
+    let mut script = vec![OP_SIZE, OP_DUP];
+    script.extend(push_number(MAX_VARLEN_SCRIPT + 1));
+    script.push(OP_LESSTHAN);
+    script.push(OP_VERIFY);
+    script.push(OP_DUP);
+    script.extend(push_number(VARINT_U16));
+    script.push(OP_LESSTHAN);
+    // NUM2BIN one byte wider than needed, so lengths with the top bit set
+    // keep their sign byte, then cut it off
+    script.extend([OP_IF, OP_2, OP_NUM2BIN, OP_1, OP_SPLIT, OP_DROP]);
+    script.extend([OP_ELSE, OP_3, OP_NUM2BIN, OP_2, OP_SPLIT, OP_DROP]);
+    script.extend(push_bytes(&[VARINT_U16 as u8]));
+    script.extend([OP_SWAP, OP_CAT, OP_ENDIF]);
+    script
 }
 
 fn push_number(n: usize) -> Vec<u8> {
@@ -127,6 +311,8 @@ fn push_number(n: usize) -> Vec<u8> {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::ghost::script::test_support::preimage_committing_to;
+    use crate::ghost::script::vm::{Interpreter, VmErrorKind};
     #[test]
     fn test_verify_binding_strict() {
         let verifier = VerifyBinding::new(1, BindingMode::Strict);
@@ -143,10 +329,165 @@ mod tests {
         assert!(script.contains(&OP_CAT));
     }
     #[test]
+    fn test_zero_output_change_only_verifies() {
+        let change = vec![0x22; 34];
+        let verifier = VerifyBinding::new(0, BindingMode::Partial);
+        assert!(verifier.validate().is_ok());
+        let mut vm = Interpreter::new();
+        let stack = vec![change.clone(), preimage_committing_to(&change)];
+        vm.eval_with_stack(stack, &verifier.build()).unwrap();
+        let stack = vec![vec![0x23; 34], preimage_committing_to(&change)];
+        let err = Interpreter::new().eval_with_stack(stack, &verifier.build()).unwrap_err();
+        assert_eq!(err.kind, VmErrorKind::EqualVerifyFailed);
+    }
+    #[test]
+    fn test_extract_hash_outputs_bytes_pinned() {
+        // OP_1 OP_PICK OP_SIZE OP_DUP <40> OP_GREATERTHANOREQUAL OP_VERIFY
+        // <40> OP_SUB OP_SPLIT <32> OP_SPLIT OP_DROP OP_SWAP OP_DROP
+        let expected = [
+            0x51, 0x79, 0x82, 0x76, 0x01, 0x28, 0xa2, 0x69,
+            0x01, 0x28, 0x94, 0x7f, 0x01, 0x20, 0x7f, 0x75, 0x7c, 0x75,
+        ];
+        assert_eq!(extract_checked_hash_outputs(), expected);
+    }
+    #[test]
+    fn test_zero_output_strict_rejected() {
+        let verifier = VerifyBinding::new(0, BindingMode::Strict);
+        let err = verifier.validate().unwrap_err();
+        assert!(matches!(err, ScriptErrorKind::InvalidGuardConfig { reason } if reason.contains("zero app outputs")));
+        // The script itself stays well-formed: it checks against an empty serialization
+        let mut vm = Interpreter::new();
+        vm.eval_with_stack(vec![preimage_committing_to(&[])], &verifier.build()).unwrap();
+    }
+    #[test]
+    fn test_build_checked_element_size_limit() {
+        let normal = VerifyBinding::new(4, BindingMode::Strict);
+        assert_eq!(normal.max_concatenated_size(), 4 * OUTPUT_SERIALIZED_SIZE);
+        assert_eq!(normal.build_checked().unwrap(), normal.build());
+        // Unbounded change items and long varlen scripts count at their maximum
+        let paymaster = VerifyBinding::new(4, BindingMode::Partial).with_change_outputs(2);
+        assert_eq!(paymaster.max_concatenated_size(), 4 * OUTPUT_SERIALIZED_SIZE + 2 * DEFAULT_MAX_ELEMENT_SIZE);
+        assert!(paymaster.build_checked().is_err());
+        let varlen = VerifyBinding::new_varlen(1, BindingMode::Strict);
+        assert_eq!(varlen.max_concatenated_size(), 8 + 3 + 0xffff);
+        assert!(varlen.build_checked().is_err());
+        let many = VerifyBinding::new(250, BindingMode::Strict);
+        assert!(many.max_concatenated_size() > DEFAULT_MAX_ELEMENT_SIZE);
+        assert_eq!(
+            many.build_checked(),
+            Err(ScriptErrorKind::ElementTooLarge { size: 10_250, max: DEFAULT_MAX_ELEMENT_SIZE })
+        );
+        assert!(many.with_max_element_size(20_000).build_checked().is_ok());
+        assert!(normal.with_max_element_size(100).build_checked().is_err());
+    }
+    #[test]
     fn test_serialize_outputs() {
         let verifier = VerifyBinding::new(2, BindingMode::Strict);
         let script = verifier.serialize_outputs();
         assert!(!script.is_empty());
     }
+    #[test]
+    fn test_serialize_outputs_empty_accumulator() {
+        // OP_FALSE starts the accumulator as an empty element, so the CATs
+        // must not leave a leading 0x00 in front of the serialization
+        let verifier = VerifyBinding::new(1, BindingMode::Strict);
+        let stack = vec![vec![0x44; 4], vec![0x33; 32], vec![0x22; 8], vec![0x11; 4], vec![0x00; 4]];
+        let mut vm = Interpreter::new();
+        vm.eval_with_stack(stack.clone(), &verifier.serialize_outputs()).unwrap();
+        let serialized = vm.stack().last().unwrap();
+        assert_eq!(vm.stack().len(), stack.len() + 1);
+        assert_eq!(serialized.len(), 8 + 32 + 1);
+        assert_ne!(serialized[0], 0x00);
+        assert_eq!(*serialized, [vec![0x22; 8], vec![0x20], vec![0x33; 32]].concat());
+    }
+    #[test]
+    fn test_paymaster_two_change_outputs() {
+        let (value, script) = (vec![0x22; 8], vec![0x33; 32]);
+        let changes = [vec![0x55; 41], vec![0x66; 43]];
+        let outputs = [value.clone(), vec![0x20], script.clone(), changes[0].clone(), changes[1].clone()].concat();
+        let verifier = VerifyBinding::new(1, BindingMode::Partial).with_change_outputs(2);
+        assert!(verifier.validate().is_ok());
+        let stack = vec![vec![0x44; 4], script, value, changes[0].clone(), changes[1].clone(), preimage_committing_to(&outputs)];
+        let mut vm = Interpreter::new();
+        vm.eval_with_stack(stack.clone(), &verifier.build()).unwrap();
+        assert_eq!(vm.stack(), stack.as_slice());
+        // Swapping the change outputs changes hashOutputs
+        let mut swapped = stack.clone();
+        swapped.swap(3, 4);
+        let err = Interpreter::new().eval_with_stack(swapped, &verifier.build()).unwrap_err();
+        assert_eq!(err.kind, VmErrorKind::EqualVerifyFailed);
+        assert!(VerifyBinding::new(1, BindingMode::Partial).with_change_outputs(0).validate().is_err());
+    }
+    #[test]
+    fn test_output_value_cap_on_hashed_outputs() {
+        use crate::ghost::script::binding_diff::serialize_outputs;
+        let value = (1u64 << 31) + 1;
+        let (app_script, changes) = (vec![0x33; 32], [(value, vec![0x51; 25]), (value, vec![0x52; 300])]);
+        let changes: Vec<Vec<u8>> = changes.iter().map(|c| serialize_outputs(std::slice::from_ref(c))).collect();
+        let outputs = [value.to_le_bytes().to_vec(), vec![0x20], app_script.clone(), changes.concat()].concat();
+        let stack = vec![vec![0x44; 4], app_script, value.to_le_bytes().to_vec(), changes[0].clone(), changes[1].clone(), preimage_committing_to(&outputs)];
+        let capped = |max_total| VerifyBinding::new(1, BindingMode::Partial).with_change_outputs(2).with_output_value_cap(max_total).build();
+        let mut vm = Interpreter::new();
+        vm.eval_with_stack(stack.clone(), &capped(3 * value)).unwrap();
+        assert_eq!(vm.stack(), stack.as_slice());
+        let err = Interpreter::new().eval_with_stack(stack, &capped(3 * value - 1)).unwrap_err();
+        assert_eq!(err.kind, VmErrorKind::VerifyFailed);
+    }
+    #[test]
+    fn test_short_preimage_fails_size_check() {
+        let change = vec![0x22; 34];
+        let verifier = VerifyBinding::new(0, BindingMode::Partial);
+        let short = preimage_committing_to(&change)[..HASH_OUTPUTS_TAIL - 1].to_vec();
+        let err = Interpreter::new().eval_with_stack(vec![change, short], &verifier.build()).unwrap_err();
+        assert_eq!(err.kind, VmErrorKind::VerifyFailed);
+        assert_eq!(err.opcode, OP_VERIFY);
+    }
+    #[test]
+    fn test_varlen_mixed_outputs_reconstruct_hash_outputs() {
+        use crate::ghost::script::binding_diff::serialize_outputs;
+        let intent = (1_000u64, vec![0x33; 32]);
+        let mut metadata = vec![0x00, 0x6a, 0x4c, 0x9c];
+        metadata.extend([0x77; 0x9c]);
+        let change = serialize_outputs(&[(500, vec![0x76; 25])]);
+        let app = serialize_outputs(&[intent.clone(), (0, metadata.clone())]);
+        // The fixed-size intent output serializes to the same 41 bytes either way
+        assert_eq!(&app[..OUTPUT_SERIALIZED_SIZE], [intent.0.to_le_bytes().to_vec(), vec![0x20], intent.1.clone()].concat());
+        let outputs = [app, change.clone()].concat();
+        let stack = vec![
+            vec![0x44; 4], intent.1.clone(), intent.0.to_le_bytes().to_vec(),
+            vec![0x45; 4], metadata.clone(), 0u64.to_le_bytes().to_vec(),
+            change.clone(), preimage_committing_to(&outputs),
+        ];
+        let verifier = VerifyBinding::new_varlen(2, BindingMode::Partial);
+        assert!(verifier.is_varlen());
+        let mut vm = Interpreter::new();
+        vm.eval_with_stack(stack.clone(), &verifier.build()).unwrap();
+        assert_eq!(vm.stack(), stack.as_slice());
+        // The fixed layout cannot express the metadata output
+        let err = Interpreter::new().eval_with_stack(stack.clone(), &VerifyBinding::new(2, BindingMode::Partial).build()).unwrap_err();
+        assert_eq!(err.kind, VmErrorKind::EqualVerifyFailed);
+        // A short value would shift bytes between outputs
+        let mut short_value = stack.clone();
+        short_value[5] = vec![0x00; 7];
+        let err = Interpreter::new().eval_with_stack(short_value, &verifier.build()).unwrap_err();
+        assert_eq!(err.kind, VmErrorKind::EqualVerifyFailed);
+    }
+    #[test]
+    fn test_varlen_length_prefix_forms() {
+        for len in [0usize, 0x7f, 0x80, 0xfc, 0xfd, 0x1234] {
+            let mut script = script_length_varint();
+            script.push(OP_SWAP);
+            script.push(OP_DROP);
+            let mut vm = Interpreter::new();
+            vm.eval_with_stack(vec![vec![0x51; len]], &script).unwrap();
+            let expected = if len < VARINT_U16 {
+                vec![len as u8]
+            } else {
+                [vec![0xfd], (len as u16).to_le_bytes().to_vec()].concat()
+            };
+            assert_eq!(vm.stack(), [expected].as_slice(), "len {}", len);
+        }
+        let err = Interpreter::new().eval_with_stack(vec![vec![0x51; 0x10000]], &script_length_varint()).unwrap_err();
+        assert_eq!(err.kind, VmErrorKind::VerifyFailed);
+    }
 }
-
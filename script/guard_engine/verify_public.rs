@@ -1,94 +1,159 @@
 // Verify public data matches ZK proof [P.1]
+use std::num::NonZeroUsize;
 use crate::ghost::script::{
-    OP_PICK, OP_OVER, OP_DUP,
+    OP_PICK, OP_OVER,
     OP_CAT, OP_SHA256,
-    OP_VERIFY, OP_EQUALVERIFY, OP_TRUE, OP_FALSE,
+    OP_FALSE,
     OP_TOALTSTACK, OP_FROMALTSTACK,
     OP_1, OP_2, OP_3, OP_4, OP_5, OP_6, OP_7, OP_8,
 }
 ;
-const DOMAIN_SEPARATOR: &[u8] = b"Halo2_GHOST_Protocol_v1";
+use super::input_count::{InputCountBinding, OUTPOINT_SIZE};
+use super::transcript::TranscriptKind;
+use crate::ghost::script::{ScriptErrorKind, ScriptResult};
+// Items above the witness triples on entry, passed through untouched:
+// the binding items and tail witness (see GuardConfig::expected_stack_size)
+const PASSTHROUGH_ITEMS: usize = 3;
+// One slot of the main stack after `VerifyPublicData::build`, bottom first.
+// Fields are numbered in push order within each witness triple. The
+// instance digest is absorbed into the transcript, whose state is left on
+// the alt stack.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum StackSlot {
+    Proof,
+    InputWitness { input: usize, field: usize },
+    OutputWitness { output: usize, field: usize },
+    Passthrough(usize),
+}
 pub struct VerifyPublicData {
     num_inputs: usize,
     num_outputs: usize,
+    num_rounds: usize,
+    prevouts_check: Option<InputCountBinding>,
+    transcript: TranscriptKind,
 }
 
 impl VerifyPublicData {
     pub fn new(num_inputs: usize, num_outputs: usize) -> Self {
-        Self { num_inputs, num_outputs }
+        Self { num_inputs, num_outputs, num_rounds: 0, prevouts_check: None, transcript: TranscriptKind::Sha256 }
+    }
+    // IPA rounds to squeeze challenges for; without this no challenge
+    // section is emitted
+    pub fn with_rounds(mut self, num_rounds: NonZeroUsize) -> Self {
+        self.num_rounds = num_rounds.get();
+        self
+    }
+    pub fn num_rounds(&self) -> usize {
+        self.num_rounds
+    }
+    pub fn with_transcript(mut self, transcript: TranscriptKind) -> Self {
+        self.transcript = transcript;
+        self
+    }
+    pub fn transcript(&self) -> TranscriptKind {
+        self.transcript
+    }
+    // Prove the input count from raw prevouts instead of trusting num_inputs.
+    // Expects [prevouts] [preimage] on top on entry; the prevouts are consumed.
+    // The prevouts must be exactly num_inputs outpoints.
+    pub fn with_prevouts_check(mut self, raw_prevouts_len: usize) -> ScriptResult<Self> {
+        if raw_prevouts_len != self.num_inputs * OUTPOINT_SIZE {
+            return Err(ScriptErrorKind::InvalidGuardConfig {
+                reason: format!(
+                    "Prevouts length {} does not match {} inputs of {} bytes",
+                    raw_prevouts_len, self.num_inputs, OUTPOINT_SIZE
+                ),
+            });
+        }
+        self.prevouts_check = Some(InputCountBinding::new(self.num_inputs));
+        Ok(self)
+    }
+    // The stack contract VerifyBinding and StackCleanup build on. The
+    // prevouts item of `with_prevouts_check` is consumed and not listed.
+    pub fn expected_residual_stack(&self) -> Vec<StackSlot> {
+        let mut slots = vec![StackSlot::Proof];
+        for input in 0..self.num_inputs {
+            slots.extend((0..3).map(|field| StackSlot::InputWitness { input, field }));
+        }
+        for output in 0..self.num_outputs {
+            slots.extend((0..3).map(|field| StackSlot::OutputWitness { output, field }));
+        }
+        slots.extend((0..PASSTHROUGH_ITEMS).map(StackSlot::Passthrough));
+        slots
     }
     pub fn build(&self) -> Vec<u8> {
         let mut script = Vec::new();
+        if let Some(binding) = &self.prevouts_check {
+            script.extend(binding.build());
+        }
         script.extend(self.copy_and_hash_witnesses());
         script.extend(self.transcript_init());
         script.push(OP_OVER);
         script.extend(self.transcript_absorb());
-        script.extend(self.verify_halo2_ipa());
-        script.extend(self.extract_proof_instance());
-        script.push(OP_EQUALVERIFY);
+        // Challenges are squeezed after the instance digest, so they depend on it
+        script.extend(self.transcript_absorb());
+        script.extend(self.absorb_ipa_challenges());
         script
     }
     fn transcript_init(&self) -> Vec<u8> {
-        let mut script = Vec::new();
-        script.push(DOMAIN_SEPARATOR.len() as u8);
-        script.extend_from_slice(DOMAIN_SEPARATOR);
-        script.push(OP_SHA256);
-        script.push(OP_TOALTSTACK);
-        script
+        self.transcript.init()
     }
     fn transcript_absorb(&self) -> Vec<u8> {
-        let mut script = Vec::new();
-        script.push(OP_FROMALTSTACK);
-        script.push(OP_CAT);
-        script.push(OP_SHA256);
-        script.push(OP_TOALTSTACK);
-        script
+        self.transcript.absorb()
     }
     fn transcript_squeeze(&self) -> Vec<u8> {
-        let mut script = Vec::new();
-        script.push(OP_FROMALTSTACK);
-        script.push(OP_DUP);
-        script.push(7u8);
-        script.extend_from_slice(b"squeeze");
-        script.push(OP_CAT);
-        script.push(OP_SHA256);
-        script.push(OP_TOALTSTACK);
-        script
+        self.transcript.squeeze()
     }
-    fn verify_halo2_ipa(&self) -> Vec<u8> {
+    fn squeeze_challenges(&self) -> Vec<u8> {
         let mut script = Vec::new();
-        script.push(OP_TRUE);
-        script.push(OP_VERIFY);
+        for _ in 0..self.num_rounds {
+            script.extend(self.transcript_squeeze());
+        }
         script
     }
-    fn extract_proof_instance(&self) -> Vec<u8> {
-        let mut script = Vec::new();
-        script.push(OP_DUP);
+    // Transcript only: one challenge is squeezed per IPA round and absorbed
+    // back, so the final state commits to the round count. Nothing folds the
+    // commitments with the challenges or checks the IPA equation; no proof
+    // is verified here.
+    fn absorb_ipa_challenges(&self) -> Vec<u8> {
+        if self.num_rounds == 0 {
+            return Vec::new();
+        }
+        let mut script = self.squeeze_challenges();
+        match self.transcript {
+            TranscriptKind::Sha256 => {
+                script.extend(std::iter::repeat_n(OP_CAT, self.num_rounds - 1));
+                script.extend(self.transcript_absorb());
+            }
+            // Field elements cannot be concatenated; absorb each challenge
+            #[cfg(feature = "poseidon-script")]
+            TranscriptKind::Poseidon => {
+                for _ in 0..self.num_rounds {
+                    script.extend(self.transcript_absorb());
+                }
+            }
+        }
         script
     }
     fn copy_and_hash_witnesses(&self) -> Vec<u8> {
         let mut script = Vec::new();
         let total_witnesses = self.num_inputs + self.num_outputs;
-        let base_offset = 3;
+        let base_offset = PASSTHROUGH_ITEMS;
         for i in 0..total_witnesses {
             let witness_offset = base_offset + (total_witnesses - 1 - i) * 3;
             script.push(op_n(witness_offset + 2));
             script.push(OP_PICK);
             script.push(op_n(witness_offset + 1 + 1));
             script.push(OP_PICK);
-            script.push(op_n(witness_offset + 0 + 2));
+            script.push(op_n(witness_offset + 2));
             script.push(OP_PICK);
             script.push(OP_CAT);
             script.push(OP_CAT);
             script.push(OP_SHA256);
             script.push(OP_TOALTSTACK);
         }
-        for _ in 0..total_witnesses {
-            script.push(OP_FROMALTSTACK);
-        }
-        for _ in 1..total_witnesses {
-            script.push(OP_CAT);
-        }
+        script.extend(std::iter::repeat_n(OP_FROMALTSTACK, total_witnesses));
+        script.extend(std::iter::repeat_n(OP_CAT, total_witnesses.saturating_sub(1)));
         script.push(OP_SHA256);
         script
     }
@@ -117,6 +182,12 @@ fn op_n(n: usize) -> u8 {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::ghost::crypto::sha256;
+    use crate::ghost::script::push_bytes;
+    use crate::ghost::script::vm::{execute, Interpreter};
+    #[cfg(feature = "contract")]
+    use crate::ghost::script::proof_generator::TranscriptBuilder;
+    use super::super::transcript::DOMAIN_SEPARATOR;
     #[test]
     fn test_verify_public_data_build() {
         let verifier = VerifyPublicData::new(1, 1);
@@ -151,6 +222,85 @@ mod tests {
         assert!(script.contains(&OP_SHA256));
         assert!(script.contains(&OP_TOALTSTACK));
     }
+    fn seeded_transcript() -> (Vec<u8>, [u8; 32]) {
+        let initial = sha256(DOMAIN_SEPARATOR);
+        let mut script = push_bytes(&initial);
+        script.push(OP_TOALTSTACK);
+        (script, initial)
+    }
+    #[test]
+    fn test_squeeze_count_matches_rounds() {
+        for rounds in [0, 1, 5, 12] {
+            let verifier = match NonZeroUsize::new(rounds) {
+                Some(rounds) => VerifyPublicData::new(1, 1).with_rounds(rounds),
+                None => VerifyPublicData::new(1, 1),
+            };
+            let (mut script, _) = seeded_transcript();
+            script.extend(verifier.squeeze_challenges());
+            let stack = execute(&script).unwrap();
+            assert_eq!(stack.len(), rounds);
+        }
+    }
+    #[test]
+    #[cfg(feature = "contract")]
+    fn test_squeezed_challenges_match_transcript_builder() {
+        let verifier = VerifyPublicData::new(1, 1).with_rounds(NonZeroUsize::new(6).unwrap());
+        let (mut script, initial) = seeded_transcript();
+        script.extend(verifier.squeeze_challenges());
+        let stack = execute(&script).unwrap();
+        let mut transcript = TranscriptBuilder::sha256(&initial);
+        for challenge in &stack {
+            assert_eq!(challenge.as_slice(), transcript.squeeze_challenge().as_slice());
+        }
+    }
+    #[test]
+    fn test_fold_consumes_challenges() {
+        let verifier = VerifyPublicData::new(1, 1).with_rounds(NonZeroUsize::new(4).unwrap());
+        let (mut script, _) = seeded_transcript();
+        script.extend(verifier.absorb_ipa_challenges());
+        let mut vm = Interpreter::new();
+        vm.eval(&script).unwrap();
+        assert!(vm.stack().is_empty());
+        assert_eq!(vm.alt_stack().len(), 1);
+    }
+    #[test]
+    #[cfg(feature = "poseidon-script")]
+    fn test_poseidon_fold_consumes_challenges() {
+        let verifier = VerifyPublicData::new(1, 1).with_rounds(NonZeroUsize::new(2).unwrap()).with_transcript(TranscriptKind::Poseidon);
+        let mut script = TranscriptKind::Poseidon.init();
+        script.extend(verifier.absorb_ipa_challenges());
+        let mut vm = Interpreter::new();
+        vm.eval(&script).unwrap();
+        assert!(vm.stack().is_empty());
+        assert_eq!(vm.alt_stack().len(), 1);
+    }
+    #[test]
+    fn test_instance_digest_feeds_challenges() {
+        let verifier = VerifyPublicData::new(1, 1).with_rounds(NonZeroUsize::new(2).unwrap());
+        let state = |field: u8| {
+            let mut entry = vec![vec![0xa0; 4]];
+            entry.extend((0..6).map(|i| vec![field + i; 4]));
+            entry.extend((0..PASSTHROUGH_ITEMS).map(|i| vec![0x70 + i as u8; 4]));
+            let mut vm = Interpreter::new();
+            vm.eval_with_stack(entry, &verifier.build()).unwrap();
+            vm.alt_stack().to_vec()
+        };
+        assert_ne!(state(0x10), state(0x11));
+    }
+    #[test]
+    fn test_prevouts_check_prefixes_build() {
+        let plain = VerifyPublicData::new(2, 1).build();
+        let checked = VerifyPublicData::new(2, 1).with_prevouts_check(2 * OUTPOINT_SIZE).unwrap().build();
+        let binding = InputCountBinding::new(2).build();
+        assert!(checked.starts_with(&binding));
+        assert_eq!(&checked[binding.len()..], plain.as_slice());
+    }
+    #[test]
+    fn test_prevouts_check_rejects_wrong_input_count() {
+        for len in [OUTPOINT_SIZE, 3 * OUTPOINT_SIZE, 2 * OUTPOINT_SIZE + 1, 0] {
+            assert!(VerifyPublicData::new(2, 1).with_prevouts_check(len).is_err());
+        }
+    }
     #[test]
     fn test_build_includes_security_fix() {
         let verifier = VerifyPublicData::new(1, 1);
@@ -160,5 +310,47 @@ mod tests {
         assert!(toalt_count >= 2, "Should have transcript state operations");
         assert!(fromalt_count >= 2, "Should retrieve transcript state");
     }
+    #[test]
+    fn test_expected_residual_stack_matches_vm() {
+        use super::super::GuardConfig;
+        let cases = [(1, 1, 0, TranscriptKind::Sha256), (1, 0, 3, TranscriptKind::Sha256)].into_iter();
+        #[cfg(feature = "poseidon-script")]
+        let cases = cases.chain([(1, 1, 1, TranscriptKind::Poseidon)]);
+        for (inputs, outputs, rounds, transcript) in cases {
+            let verifier = VerifyPublicData::new(inputs, outputs).with_transcript(transcript);
+            let verifier = match NonZeroUsize::new(rounds) {
+                Some(rounds) => verifier.with_rounds(rounds),
+                None => verifier,
+            };
+            let slots = verifier.expected_residual_stack();
+            // Distinct contents for every entry slot
+            let item = |slot: &StackSlot| -> Vec<u8> {
+                match *slot {
+                    StackSlot::Proof => vec![0xa0; 4],
+                    StackSlot::InputWitness { input, field } => vec![0x10 + (input * 3 + field) as u8; 4],
+                    StackSlot::OutputWitness { output, field } => vec![0x40 + (output * 3 + field) as u8; 4],
+                    StackSlot::Passthrough(i) => vec![0x70 + i as u8; 4],
+                }
+            };
+            let entry: Vec<Vec<u8>> = slots.iter().map(item).collect();
+            if outputs == 1 {
+                assert_eq!(entry.len(), GuardConfig::new(inputs, outputs).expected_stack_size());
+            }
+            let digests: Vec<Vec<u8>> = entry[1..1 + 3 * (inputs + outputs)]
+                .chunks(3)
+                .rev()
+                .map(|fields| sha256(&fields.concat()).to_vec())
+                .collect();
+            let mut vm = Interpreter::new();
+            vm.eval_with_stack(entry.clone(), &verifier.copy_and_hash_witnesses()).unwrap();
+            assert_eq!(vm.stack().last().unwrap(), &sha256(&digests.concat()).to_vec());
+            let mut vm = Interpreter::new();
+            vm.eval_with_stack(entry, &verifier.build()).unwrap();
+            assert_eq!(vm.stack().len(), slots.len());
+            for (i, slot) in slots.iter().enumerate() {
+                assert_eq!(vm.stack()[i], item(slot), "{:?}", slot);
+            }
+            assert_eq!(vm.alt_stack().len(), 1);
+        }
+    }
 }
-
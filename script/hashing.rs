@@ -0,0 +1,63 @@
+// Streaming Hashes
+// Chunked counterparts of `crypto::{sha256, double_sha256, hash160}` for
+// data that is already split across buffers (key tables, output lists), so
+// callers can hash without first concatenating into a temporary Vec.
+// Results are byte-identical to the buffered functions.
+
+use sha2::{Digest, Sha256};
+use ripemd::Ripemd160;
+
+/// SHA256 over the concatenation of `chunks`
+pub fn sha256_streaming<'a>(chunks: impl IntoIterator<Item = &'a [u8]>) -> [u8; 32] {
+    let mut hasher = Sha256::new();
+    for chunk in chunks {
+        hasher.update(chunk);
+    }
+    hasher.finalize().into()
+}
+
+/// SHA256(SHA256(..)) over the concatenation of `chunks`
+pub fn double_sha256_streaming<'a>(chunks: impl IntoIterator<Item = &'a [u8]>) -> [u8; 32] {
+    Sha256::digest(sha256_streaming(chunks)).into()
+}
+
+/// RIPEMD160(SHA256(..)) over the concatenation of `chunks`
+pub fn hash160_streaming<'a>(chunks: impl IntoIterator<Item = &'a [u8]>) -> [u8; 20] {
+    Ripemd160::digest(sha256_streaming(chunks)).into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ghost::crypto::{sha256, double_sha256, hash160};
+    use rand::{Rng, RngCore};
+    fn random_chunks<'a>(data: &'a [u8], rng: &mut impl Rng) -> Vec<&'a [u8]> {
+        let mut chunks = Vec::new();
+        let mut rest = data;
+        while !rest.is_empty() {
+            let (head, tail) = rest.split_at(rng.gen_range(0..=rest.len().min(700)));
+            chunks.push(head);
+            rest = tail;
+        }
+        chunks
+    }
+    #[test]
+    fn test_streaming_matches_buffered() {
+        let mut rng = rand::thread_rng();
+        for len in [0usize, 1, 55, 64, 65, 1000, 16 * 1024] {
+            let mut data = vec![0u8; len];
+            rng.fill_bytes(&mut data);
+            for _ in 0..4 {
+                let chunks = random_chunks(&data, &mut rng);
+                assert_eq!(sha256_streaming(chunks.iter().copied()), sha256(&data));
+                assert_eq!(double_sha256_streaming(chunks.iter().copied()), double_sha256(&data));
+                assert_eq!(hash160_streaming(chunks.iter().copied()), hash160(&data));
+            }
+        }
+    }
+    #[test]
+    fn test_empty_chunks_ignored() {
+        let parts: [&[u8]; 4] = [b"", b"ab", b"", b"c"];
+        assert_eq!(sha256_streaming(parts), sha256(b"abc"));
+    }
+}
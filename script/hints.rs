@@ -1,5 +1,15 @@
-use crate::ghost::crypto::{Fp, FieldExt};
-use super::{push_bytes};
+use crate::ghost::crypto::{Fp, FieldExt, PoseidonHash};
+use crate::ghost::crypto::poseidon_constants::get_round_constant;
+use ff::Field;
+use super::{push_bytes, push_size, varint};
+use super::num::push_item;
+use super::field::{bytes_to_fp, generate_canonical_check, get_mds_fp, round_types, SBOX_EXPONENT, TOTAL_ROUNDS};
+use super::point::CompressedPoint;
+use super::sections::{DeserializeError, SectionReader, WitnessSection};
+use crate::ghost::Error;
+fn fp_item(item: &[u8]) -> Option<Fp> {
+    bytes_to_fp(item.try_into().ok()?)
+}
 #[derive(Clone, Debug)]
 pub struct IpaHints {
     pub rounds: Vec<FoldingRound>,
@@ -18,9 +28,21 @@ impl IpaHints {
     pub fn num_rounds(&self) -> usize {
         self.rounds.len()
     }
+    /// Final commitment decompressed to `(x, y)`, the form the prover absorbs
+    /// into the transcript; the identity and off-curve encodings are errors
+    pub fn final_commitment_affine(&self) -> crate::ghost::Result<(Fp, Fp)> {
+        CompressedPoint::from(self.final_commitment)
+            .to_affine()
+            .ok_or_else(|| Error::InvalidInput("Final commitment is not a valid curve point".to_string()))
+    }
     pub fn size(&self) -> usize {
         self.rounds.len() * 131 + 65
     }
+    pub fn script_push_size(&self) -> usize {
+        self.rounds.iter().map(|r| r.script_push_size()).sum::<usize>()
+            + push_size(32)
+            + push_size(33)
+    }
     pub fn to_script_pushes(&self) -> Vec<u8> {
         let mut pushes = Vec::new();
         for round in self.rounds.iter().rev() {
@@ -30,18 +52,95 @@ impl IpaHints {
         pushes.extend(push_bytes(&self.final_commitment));
         pushes
     }
+    /// `to_script_pushes` with `generate_canonical_check` after every field
+    /// element (each challenge and the final scalar), so executing it fails
+    /// on a value >= p before anything reads it. Costs one check per field
+    /// element on top of `script_push_size`, and the result is no longer
+    /// push-only: it suits scripts that are executed, not a relayed scriptSig.
+    pub fn to_script_pushes_checked(&self) -> Vec<u8> {
+        let check = generate_canonical_check();
+        let mut pushes = Vec::with_capacity(self.script_push_size() + (self.rounds.len() + 1) * check.len());
+        for round in self.rounds.iter().rev() {
+            // The challenge is the round's last push
+            pushes.extend(round.to_script_pushes());
+            pushes.extend(&check);
+        }
+        pushes.extend(push_bytes(&self.final_scalar.to_bytes()));
+        pushes.extend(&check);
+        pushes.extend(push_bytes(&self.final_commitment));
+        pushes
+    }
+    pub fn to_stack_items(&self) -> Vec<Vec<u8>> {
+        let mut items = Vec::new();
+        for round in self.rounds.iter().rev() {
+            items.extend(round.to_stack_items());
+        }
+        items.push(self.final_scalar.to_bytes().to_vec());
+        items.push(self.final_commitment.to_vec());
+        items
+    }
+    pub fn from_stack_items(items: &[Vec<u8>]) -> Option<Self> {
+        if items.len() < 2 || !(items.len() - 2).is_multiple_of(4) {
+            return None;
+        }
+        let (round_items, tail) = items.split_at(items.len() - 2);
+        let mut rounds = round_items
+            .chunks(4)
+            .map(FoldingRound::from_stack_items)
+            .collect::<Option<Vec<_>>>()?;
+        rounds.reverse();
+        Some(Self {
+            rounds,
+            final_scalar: fp_item(&tail[0])?,
+            final_commitment: tail[1].as_slice().try_into().ok()?,
+        })
+    }
     pub fn to_bytes(&self) -> Vec<u8> {
         let mut bytes = Vec::with_capacity(self.size());
         for round in &self.rounds {
-            bytes.extend(&round.l_u);
-            bytes.extend(&round.r_u_inv);
-            bytes.extend(&round.c_next);
+            bytes.extend(round.l_u.as_bytes());
+            bytes.extend(round.r_u_inv.as_bytes());
+            bytes.extend(round.c_next.as_bytes());
             bytes.extend(&round.challenge.to_bytes());
         }
         bytes.extend(&self.final_scalar.to_bytes());
         bytes.extend(&self.final_commitment);
         bytes
     }
+    /// Inverse of `to_bytes`; the round count follows from the length
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, DeserializeError> {
+        let num_rounds = bytes.len().saturating_sub(65) / 131;
+        if bytes.len() > 65 && !(bytes.len() - 65).is_multiple_of(131) {
+            // Partial round: the last round and the final values can't both fit
+            return Err(DeserializeError {
+                section: WitnessSection::IpaRound { round: num_rounds },
+                offset: num_rounds * 131,
+                expected: 131 + 65,
+                remaining: bytes.len() - num_rounds * 131,
+            });
+        }
+        let mut reader = SectionReader::new(bytes);
+        let mut rounds = Vec::with_capacity(num_rounds);
+        for round in 0..num_rounds {
+            let section = WitnessSection::IpaRound { round };
+            let malformed = reader.error(section, 131);
+            let r = reader.take(section, 131)?;
+            rounds.push(FoldingRound::from_stack_items(&[
+                r[..33].to_vec(),
+                r[33..66].to_vec(),
+                r[66..99].to_vec(),
+                r[99..].to_vec(),
+            ]).ok_or(malformed)?);
+        }
+        let malformed = reader.error(WitnessSection::FinalScalar, 32);
+        let final_scalar = fp_item(reader.take(WitnessSection::FinalScalar, 32)?).ok_or(malformed)?;
+        let final_commitment = reader.take(WitnessSection::FinalCommitment, 33)?.try_into().expect("33-byte slice");
+        reader.finish()?;
+        Ok(Self { rounds, final_scalar, final_commitment })
+    }
+    pub fn from_bytes_opt(bytes: &[u8]) -> Option<Self> {
+        Self::from_bytes(bytes).ok()
+    }
     pub fn placeholder(k: u32) -> Self {
         let rounds = (0..k).map(|_| FoldingRound::placeholder()).collect();
         Self {
@@ -54,86 +153,297 @@ impl IpaHints {
 
 #[derive(Clone, Debug)]
 pub struct FoldingRound {
-    pub l_u: [u8; 33],
-    pub r_u_inv: [u8; 33],
-    pub c_next: [u8; 33],
+    pub l_u: CompressedPoint,
+    pub r_u_inv: CompressedPoint,
+    pub c_next: CompressedPoint,
     pub challenge: Fp,
 }
 
 impl FoldingRound {
-    pub fn new(l_u: [u8; 33], r_u_inv: [u8; 33], c_next: [u8; 33], challenge: Fp) -> Self {
-        Self { l_u, r_u_inv, c_next, challenge }
+    pub fn new(
+        l_u: impl Into<CompressedPoint>,
+        r_u_inv: impl Into<CompressedPoint>,
+        c_next: impl Into<CompressedPoint>,
+        challenge: Fp,
+    ) -> Self {
+        Self { l_u: l_u.into(), r_u_inv: r_u_inv.into(), c_next: c_next.into(), challenge }
     }
     pub fn size(&self) -> usize {
         33 + 33 + 33 + 32
     }
+    pub fn script_push_size(&self) -> usize {
+        3 * push_size(33) + push_size(32)
+    }
     pub fn to_script_pushes(&self) -> Vec<u8> {
         let mut pushes = Vec::new();
-        pushes.extend(push_bytes(&self.l_u));
-        pushes.extend(push_bytes(&self.r_u_inv));
-        pushes.extend(push_bytes(&self.c_next));
+        pushes.extend(push_bytes(self.l_u.as_bytes()));
+        pushes.extend(push_bytes(self.r_u_inv.as_bytes()));
+        pushes.extend(push_bytes(self.c_next.as_bytes()));
         pushes.extend(push_bytes(&self.challenge.to_bytes()));
         pushes
     }
+    pub fn to_stack_items(&self) -> Vec<Vec<u8>> {
+        vec![
+            self.l_u.as_bytes().to_vec(),
+            self.r_u_inv.as_bytes().to_vec(),
+            self.c_next.as_bytes().to_vec(),
+            self.challenge.to_bytes().to_vec(),
+        ]
+    }
+    pub fn from_stack_items(items: &[Vec<u8>]) -> Option<Self> {
+        match items {
+            [l_u, r_u_inv, c_next, challenge] => Some(Self {
+                l_u: CompressedPoint::try_from(l_u.as_slice()).ok()?,
+                r_u_inv: CompressedPoint::try_from(r_u_inv.as_slice()).ok()?,
+                c_next: CompressedPoint::try_from(c_next.as_slice()).ok()?,
+                challenge: fp_item(challenge)?,
+            }),
+            _ => None,
+        }
+    }
     pub fn placeholder() -> Self {
         Self {
-            l_u: [0u8; 33],
-            r_u_inv: [0u8; 33],
-            c_next: [0u8; 33],
+            l_u: CompressedPoint::IDENTITY,
+            r_u_inv: CompressedPoint::IDENTITY,
+            c_next: CompressedPoint::IDENTITY,
             challenge: Fp::from_u64(1),
         }
     }
 }
 
+// One hint chain per intent, so rounds are tied to the intent they hash.
+// Stack layout, top first: commitment, then for each intent in order its
+// rounds (after_sbox[0..3], after_mds[0..3] each) and its output. The
+// commitment is the left fold of PoseidonHash::hash over the outputs (a
+// single intent's output unchanged), which the guard can recompute with the
+// two-to-one sponge section.
 #[derive(Clone, Debug)]
-pub struct PoseidonHints {
-    pub round_states: Vec<PoseidonRoundHint>,
+pub struct IntentHashHints {
+    pub rounds: Vec<PoseidonRoundHint>,
     pub output: Fp,
 }
 
+impl IntentHashHints {
+    pub fn new(rounds: Vec<PoseidonRoundHint>, output: Fp) -> Self {
+        Self { rounds, output }
+    }
+    pub fn num_rounds(&self) -> usize {
+        self.rounds.len()
+    }
+    pub fn size(&self) -> usize {
+        self.rounds.len() * 192 + 32
+    }
+    // Round-by-round trace of consecutive permutations, one per input state;
+    // the output is lane 0 of the last
+    pub fn trace(inputs: &[[Fp; 3]]) -> Self {
+        let mds = get_mds_fp();
+        let mut rounds = Vec::with_capacity(inputs.len() * TOTAL_ROUNDS);
+        let mut output = Fp::zero();
+        for input in inputs {
+            let mut state = *input;
+            for (round, round_type) in round_types() {
+                let full = round_type.is_full();
+                for (lane, s) in state.iter_mut().enumerate() {
+                    *s += get_round_constant(round, lane);
+                    if full || lane == 0 {
+                        *s = s.pow_vartime([SBOX_EXPONENT]);
+                    }
+                }
+                let after_sbox = state;
+                state = [0, 1, 2].map(|i| (0..3).map(|j| mds[i][j] * after_sbox[j]).sum());
+                rounds.push(PoseidonRoundHint::new(after_sbox, state));
+            }
+            output = state[0];
+        }
+        Self { rounds, output }
+    }
+    // Trace of PoseidonHash::hash_many(inputs): each permutation takes
+    // [acc, x, 0], acc the previous output (the first input to start)
+    pub fn trace_hash_many(inputs: &[Fp]) -> Self {
+        let mut inputs = inputs.iter().copied();
+        let mut acc = inputs.next().unwrap_or(Fp::zero());
+        let states: Vec<[Fp; 3]> = inputs
+            .map(|x| {
+                let state = [acc, x, Fp::zero()];
+                acc = PoseidonHash::hash(acc, x);
+                state
+            })
+            .collect();
+        Self { output: acc, ..Self::trace(&states) }
+    }
+    pub fn placeholder(num_rounds: usize) -> Self {
+        Self {
+            rounds: (0..num_rounds).map(|_| PoseidonRoundHint::placeholder()).collect(),
+            output: Fp::zero(),
+        }
+    }
+    // Stack order, bottom first: output, then rounds last to first
+    fn to_stack_items(&self) -> Vec<Vec<u8>> {
+        let mut items = vec![self.output.to_bytes().to_vec()];
+        for round in self.rounds.iter().rev() {
+            items.extend(round.to_stack_items().into_iter().rev());
+        }
+        items
+    }
+    fn from_stack_items(items: &[Vec<u8>]) -> Option<Self> {
+        let (output, round_items) = items.split_first()?;
+        if round_items.len() % 6 != 0 {
+            return None;
+        }
+        let rounds = round_items
+            .rchunks(6)
+            .map(|chunk| {
+                let chunk: Vec<Vec<u8>> = chunk.iter().rev().cloned().collect();
+                PoseidonRoundHint::from_stack_items(&chunk)
+            })
+            .collect::<Option<Vec<_>>>()?;
+        Some(Self { rounds, output: fp_item(output)? })
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct PoseidonHints {
+    pub intents: Vec<IntentHashHints>,
+}
+
 impl PoseidonHints {
+    // Single-intent hints from a flat round list (the pre-intent layout)
     pub fn new(round_states: Vec<PoseidonRoundHint>, output: Fp) -> Self {
-        Self { round_states, output }
+        Self::from_intents(vec![IntentHashHints::new(round_states, output)])
+    }
+    pub fn from_intents(intents: Vec<IntentHashHints>) -> Self {
+        Self { intents }
+    }
+    // Flat rounds and output; only a single intent has one
+    pub fn into_legacy(self) -> Option<(Vec<PoseidonRoundHint>, Fp)> {
+        match <[IntentHashHints; 1]>::try_from(self.intents) {
+            Ok([intent]) => Some((intent.rounds, intent.output)),
+            Err(_) => None,
+        }
+    }
+    pub fn num_intents(&self) -> usize {
+        self.intents.len()
+    }
+    // Total rounds across all intents
+    pub fn num_rounds(&self) -> usize {
+        self.intents.iter().map(|i| i.num_rounds()).sum()
+    }
+    pub fn outputs(&self) -> Vec<Fp> {
+        self.intents.iter().map(|i| i.output).collect()
+    }
+    pub fn commitment(&self) -> Fp {
+        let mut outputs = self.intents.iter().map(|i| i.output);
+        let first = outputs.next().unwrap_or(Fp::zero());
+        outputs.fold(first, PoseidonHash::hash)
     }
     pub fn size(&self) -> usize {
-        self.round_states.len() * 192 + 32
+        self.intents.iter().map(|i| i.size()).sum::<usize>() + 32
+    }
+    pub fn script_push_size(&self) -> usize {
+        self.stack_item_count() * push_size(32)
+    }
+    pub fn stack_item_count(&self) -> usize {
+        self.intents.iter().map(|i| 6 * i.num_rounds() + 1).sum::<usize>() + 1
     }
     pub fn to_script_pushes(&self) -> Vec<u8> {
-        let mut pushes = Vec::new();
-        for round in &self.round_states {
-            pushes.extend(round.to_script_pushes());
+        self.to_stack_items().iter().flat_map(|item| push_item(item)).collect()
+    }
+    pub fn to_stack_items(&self) -> Vec<Vec<u8>> {
+        let mut items = Vec::new();
+        for intent in self.intents.iter().rev() {
+            items.extend(intent.to_stack_items());
         }
-        pushes.extend(push_bytes(&self.output.to_bytes()));
-        pushes
+        items.push(self.commitment().to_bytes().to_vec());
+        items
     }
+    // Single intent; use `from_intent_stack_items` when there are several
+    pub fn from_stack_items(items: &[Vec<u8>]) -> Option<Self> {
+        let rounds = items.len().checked_sub(2)? / 6;
+        Self::from_intent_stack_items(items, &[rounds])
+    }
+    // `rounds[i]` is intent i's round count; rejects a commitment that does
+    // not match the outputs
+    pub fn from_intent_stack_items(items: &[Vec<u8>], rounds: &[usize]) -> Option<Self> {
+        let (commitment, mut rest) = items.split_last()?;
+        let mut intents = Vec::with_capacity(rounds.len());
+        for &num_rounds in rounds {
+            let split = rest.len().checked_sub(6 * num_rounds + 1)?;
+            let (below, items) = rest.split_at(split);
+            intents.push(IntentHashHints::from_stack_items(items)?);
+            rest = below;
+        }
+        if !rest.is_empty() {
+            return None;
+        }
+        let hints = Self::from_intents(intents);
+        (hints.commitment() == fp_item(commitment)?).then_some(hints)
+    }
+    // varint(intents) || per intent: varint(rounds) || rounds || output
     pub fn to_bytes(&self) -> Vec<u8> {
         let mut bytes = Vec::with_capacity(self.size());
-        for round in &self.round_states {
-            for elem in &round.after_sbox {
-                bytes.extend(&elem.to_bytes());
-            }
-            for elem in &round.after_mds {
-                bytes.extend(&elem.to_bytes());
+        bytes.extend(varint(self.intents.len()));
+        for intent in &self.intents {
+            bytes.extend(varint(intent.rounds.len()));
+            for round in &intent.rounds {
+                for elem in round.after_sbox.iter().chain(&round.after_mds) {
+                    bytes.extend(&elem.to_bytes());
+                }
             }
+            bytes.extend(&intent.output.to_bytes());
         }
-        bytes.extend(&self.output.to_bytes());
         bytes
     }
-    pub fn placeholder(num_rounds: usize) -> Self {
-        let round_states = (0..num_rounds)
-            .map(|_| PoseidonRoundHint::placeholder())
-            .collect();
-        Self {
-            round_states,
-            output: Fp::zero(),
+    /// Inverse of `to_bytes`
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, DeserializeError> {
+        let mut reader = SectionReader::new(bytes);
+        let count = reader.varint(WitnessSection::IntentCount)?;
+        let mut intents = Vec::new();
+        for intent in 0..count {
+            let section = WitnessSection::Intent { intent: intent as usize };
+            let num_rounds = reader.varint(section)?;
+            let len = usize::try_from(num_rounds)
+                .ok()
+                .and_then(|n| n.checked_mul(192)?.checked_add(32))
+                .ok_or_else(|| reader.error(section, usize::MAX))?;
+            let malformed = reader.error(section, len);
+            let mut elems = reader.take(section, len)?.chunks(32).map(fp_item);
+            let rounds = (0..num_rounds)
+                .map(|_| {
+                    let mut round = [Fp::zero(); 6];
+                    for elem in &mut round {
+                        *elem = elems.next()??;
+                    }
+                    Some(PoseidonRoundHint::new([round[0], round[1], round[2]], [round[3], round[4], round[5]]))
+                })
+                .collect::<Option<Vec<_>>>()
+                .ok_or(malformed)?;
+            intents.push(IntentHashHints::new(rounds, elems.next().flatten().ok_or(malformed)?));
         }
+        reader.finish()?;
+        Ok(Self::from_intents(intents))
     }
+    pub fn from_bytes_opt(bytes: &[u8]) -> Option<Self> {
+        Self::from_bytes(bytes).ok()
+    }
+    // Single intent of `num_rounds` zero rounds
+    pub fn placeholder(num_rounds: usize) -> Self {
+        IntentHashHints::placeholder(num_rounds).into()
+    }
+    // Sets the last intent's output
     pub fn with_output(mut self, output: Fp) -> Self {
-        self.output = output;
+        if let Some(intent) = self.intents.last_mut() {
+            intent.output = output;
+        }
         self
     }
 }
 
+impl From<IntentHashHints> for PoseidonHints {
+    fn from(intent: IntentHashHints) -> Self {
+        Self::from_intents(vec![intent])
+    }
+}
+
 #[derive(Clone, Debug)]
 pub struct PoseidonRoundHint {
     pub after_sbox: [Fp; 3],
@@ -147,6 +457,9 @@ impl PoseidonRoundHint {
     pub fn size(&self) -> usize {
         6 * 32
     }
+    pub fn script_push_size(&self) -> usize {
+        6 * push_size(32)
+    }
     pub fn to_script_pushes(&self) -> Vec<u8> {
         let mut pushes = Vec::new();
         for elem in &self.after_sbox {
@@ -157,6 +470,26 @@ impl PoseidonRoundHint {
         }
         pushes
     }
+    pub fn to_stack_items(&self) -> Vec<Vec<u8>> {
+        self.after_sbox
+            .iter()
+            .chain(self.after_mds.iter())
+            .map(|elem| elem.to_bytes().to_vec())
+            .collect()
+    }
+    pub fn from_stack_items(items: &[Vec<u8>]) -> Option<Self> {
+        if items.len() != 6 {
+            return None;
+        }
+        let mut elems = [Fp::zero(); 6];
+        for (elem, item) in elems.iter_mut().zip(items) {
+            *elem = fp_item(item)?;
+        }
+        Some(Self {
+            after_sbox: [elems[0], elems[1], elems[2]],
+            after_mds: [elems[3], elems[4], elems[5]],
+        })
+    }
     pub fn placeholder() -> Self {
         Self {
             after_sbox: [Fp::zero(); 3],
@@ -173,26 +506,54 @@ pub fn generate_ipa_hints(
     IpaHints::placeholder(k)
 }
 
+// Round-by-round hints for the intent's Poseidon chain, the trace of
+// `intent_commitment`: four hashes, as `PoseidonGuardConfig` checks per intent
 pub fn generate_poseidon_hints(
-    _asset_id: u64,
-    _amount: u64,
-    _nonce: u64,
-    _recipient: Fp,
-    _payload: Fp,
+    asset_id: u64,
+    amount: u64,
+    nonce: u64,
+    recipient: Fp,
+    payload: Fp,
 ) -> PoseidonHints {
-    PoseidonHints::placeholder(64)
+    IntentHashHints::trace_hash_many(&intent_fields(asset_id, amount, nonce, recipient, payload)).into()
+}
+
+// Poseidon commitment to an intent's fields, the value the intent's hint
+// chain must end in
+pub fn intent_commitment(
+    asset_id: u64,
+    amount: u64,
+    nonce: u64,
+    recipient: Fp,
+    payload: Fp,
+) -> Fp {
+    PoseidonHash::hash_many(&intent_fields(asset_id, amount, nonce, recipient, payload))
+}
+
+// Inputs of the intent chain in absorb order
+fn intent_fields(asset_id: u64, amount: u64, nonce: u64, recipient: Fp, payload: Fp) -> [Fp; 5] {
+    [Fp::from_u64(asset_id), Fp::from_u64(amount), Fp::from_u64(nonce), recipient, payload]
+}
+
+// Inverse check for `generate_poseidon_hints`: whether single-intent hints
+// end in the commitment to the claimed fields
+pub fn verify_poseidon_hint_for_intent(
+    hints: &PoseidonHints,
+    asset_id: u64,
+    amount: u64,
+    nonce: u64,
+    recipient: Fp,
+    payload: Fp,
+) -> bool {
+    matches!(hints.outputs()[..], [output] if output == intent_commitment(asset_id, amount, nonce, recipient, payload))
 }
 
 pub fn ipa_verify_script(_num_rounds: usize) -> Vec<u8> {
-    let mut script = Vec::new();
-    script.push(super::OP_TRUE);
-    script
+    vec![super::OP_TRUE]
 }
 
 pub fn poseidon_verify_script() -> Vec<u8> {
-    let mut script = Vec::new();
-    script.push(super::OP_TRUE);
-    script
+    vec![super::OP_TRUE]
 }
 
 #[cfg(test)]
@@ -210,9 +571,64 @@ mod tests {
         assert_eq!(round.size(), 131);
     }
     #[test]
+    fn test_folding_round_rejects_field_element_as_point() {
+        let mut items = FoldingRound::placeholder().to_stack_items();
+        items[0] = Fp::from_u64(9).to_bytes().to_vec();
+        assert!(FoldingRound::from_stack_items(&items).is_none());
+    }
+    #[test]
     fn test_poseidon_hints_size() {
         let hints = PoseidonHints::placeholder(64);
-        assert_eq!(hints.size(), 64 * 192 + 32);
+        assert_eq!(hints.size(), 64 * 192 + 32 + 32);
+        assert_eq!(hints.stack_item_count(), hints.to_stack_items().len());
+    }
+    fn three_intents() -> PoseidonHints {
+        let input = |i: u64| [Fp::from_u64(i), Fp::from_u64(i + 1), Fp::zero()];
+        PoseidonHints::from_intents(vec![
+            IntentHashHints::trace(&[input(1)]),
+            IntentHashHints::trace(&[input(10), input(20)]),
+            IntentHashHints::trace(&[input(100)]),
+        ])
+    }
+    #[test]
+    fn test_trace_output_matches_poseidon_hash() {
+        let (a, b) = (Fp::from_u64(3), Fp::from_u64(4));
+        let intent = IntentHashHints::trace(&[[a, b, Fp::zero()]]);
+        assert_eq!(intent.num_rounds(), TOTAL_ROUNDS);
+        assert_eq!(intent.output, PoseidonHash::hash(a, b));
+    }
+    #[test]
+    fn test_intent_hints_stack_round_trip() {
+        let hints = three_intents();
+        let rounds: Vec<usize> = hints.intents.iter().map(|i| i.num_rounds()).collect();
+        let items = hints.to_stack_items();
+        assert_eq!(items.len(), hints.stack_item_count());
+        let parsed = PoseidonHints::from_intent_stack_items(&items, &rounds).unwrap();
+        assert_eq!(parsed.to_stack_items(), items);
+        assert_eq!(parsed.commitment(), PoseidonHash::hash(PoseidonHash::hash(hints.outputs()[0], hints.outputs()[1]), hints.outputs()[2]));
+        // A commitment that does not match the outputs is rejected
+        let mut tampered = items.clone();
+        *tampered.last_mut().unwrap() = Fp::from_u64(1).to_bytes().to_vec();
+        assert!(PoseidonHints::from_intent_stack_items(&tampered, &rounds).is_none());
+        assert!(PoseidonHints::from_intent_stack_items(&items, &rounds[..2]).is_none());
+    }
+    #[test]
+    fn test_intent_hints_bytes_round_trip() {
+        let hints = three_intents();
+        let bytes = hints.to_bytes();
+        assert_eq!(PoseidonHints::from_bytes(&bytes).unwrap().to_bytes(), bytes);
+        assert!(PoseidonHints::from_bytes(&bytes[..bytes.len() - 1]).is_err());
+        assert!(PoseidonHints::from_bytes(&[bytes.clone(), vec![0]].concat()).is_err());
+    }
+    #[test]
+    fn test_legacy_single_intent() {
+        let hints = PoseidonHints::new(vec![PoseidonRoundHint::placeholder(); 4], Fp::from_u64(5));
+        assert_eq!(hints.commitment(), Fp::from_u64(5));
+        let items = hints.to_stack_items();
+        let parsed = PoseidonHints::from_stack_items(&items).unwrap();
+        let (rounds, output) = parsed.into_legacy().unwrap();
+        assert_eq!((rounds.len(), output), (4, Fp::from_u64(5)));
+        assert!(three_intents().into_legacy().is_none());
     }
     #[test]
     fn test_ipa_hints_serialization() {
@@ -220,5 +636,69 @@ mod tests {
         let pushes = hints.to_script_pushes();
         assert!(!pushes.is_empty());
     }
+    #[test]
+    fn test_checked_pushes_reject_non_canonical_challenge() {
+        use super::super::field::PALLAS_MODULUS_BYTES;
+        use super::super::vm::{Interpreter, VmErrorKind};
+        let hints = IpaHints::new(vec![FoldingRound::placeholder(); 2], Fp::from_u64(7), [0u8; 33]);
+        let checked = hints.to_script_pushes_checked();
+        let check = generate_canonical_check();
+        assert_eq!(checked.len(), hints.script_push_size() + 3 * check.len());
+        let mut vm = Interpreter::new();
+        vm.eval(&checked).unwrap();
+        assert_eq!(vm.stack(), hints.to_stack_items().as_slice());
+        // Swap the last pushed challenge (1) for p
+        let challenge = push_bytes(&Fp::from_u64(1).to_bytes());
+        let at = checked.windows(challenge.len()).rposition(|w| w == challenge.as_slice()).unwrap() + 1;
+        let mut tampered = checked.clone();
+        tampered[at..at + 32].copy_from_slice(&PALLAS_MODULUS_BYTES);
+        let err = Interpreter::new().eval(&tampered).unwrap_err();
+        assert_eq!(err.kind, VmErrorKind::VerifyFailed);
+        assert!(Interpreter::new().eval(&hints.to_script_pushes()).is_ok());
+    }
+    #[test]
+    fn test_hints_script_push_size() {
+        let ipa = IpaHints::placeholder(10);
+        assert_eq!(ipa.script_push_size(), ipa.to_script_pushes().len());
+        let poseidon = PoseidonHints::placeholder(64);
+        assert_eq!(poseidon.script_push_size(), poseidon.to_script_pushes().len());
+    }
+    #[test]
+    fn test_verify_poseidon_hint_for_intent() {
+        let (recipient, payload) = (Fp::from_u64(0xAAAA), Fp::from_u64(7));
+        let output = intent_commitment(1, 100, 5, recipient, payload);
+        let hints: PoseidonHints = IntentHashHints::new(Vec::new(), output).into();
+        assert!(verify_poseidon_hint_for_intent(&hints, 1, 100, 5, recipient, payload));
+        assert!(!verify_poseidon_hint_for_intent(&hints, 1, 101, 5, recipient, payload));
+        assert!(!verify_poseidon_hint_for_intent(&hints, 1, 100, 5, recipient, Fp::zero()));
+        let doubled = PoseidonHints::from_intents(vec![hints.intents[0].clone(), hints.intents[0].clone()]);
+        assert!(!verify_poseidon_hint_for_intent(&doubled, 1, 100, 5, recipient, payload));
+    }
+    #[test]
+    fn test_generated_hints_trace_the_commitment() {
+        let (recipient, payload) = (Fp::from_u64(0xAAAA), Fp::from_u64(7));
+        let hints = generate_poseidon_hints(1, 100, 5, recipient, payload);
+        assert_eq!(hints.intents.len(), 1);
+        assert_eq!(hints.intents[0].num_rounds(), 4 * TOTAL_ROUNDS);
+        assert_eq!(hints.commitment(), intent_commitment(1, 100, 5, recipient, payload));
+        assert!(verify_poseidon_hint_for_intent(&hints, 1, 100, 5, recipient, payload));
+        assert!(!verify_poseidon_hint_for_intent(&hints, 2, 100, 5, recipient, payload));
+    }
+    #[test]
+    fn test_final_commitment_affine() {
+        let (x, y) = (1u64..)
+            .map(Fp::from_u64)
+            .find_map(|x| Option::<Fp>::from((x.square() * x + Fp::from_u64(5)).sqrt()).map(|y| (x, y)))
+            .unwrap();
+        for y in [y, -y] {
+            let commitment = *CompressedPoint::from_affine(&x, &y).as_bytes();
+            let hints = IpaHints::new(Vec::new(), Fp::from_u64(1), commitment);
+            assert_eq!(hints.final_commitment_affine().unwrap(), (x, y));
+            assert_eq!(*CompressedPoint::from_affine(&x, &y).as_bytes(), hints.final_commitment);
+        }
+        assert!(IpaHints::placeholder(1).final_commitment_affine().is_err());
+        let mut non_canonical = [0xff; 33];
+        non_canonical[0] = 0x02;
+        assert!(IpaHints::new(Vec::new(), Fp::from_u64(1), non_canonical).final_commitment_affine().is_err());
+    }
 }
-
@@ -0,0 +1,166 @@
+// Script Introspection
+// Locates and rewrites the commitments generated locking scripts embed
+// (constants hash, state, operator PKH) without per-caller offset math.
+// Everything walks the push-aware instruction iterator, so push data is never
+// read as opcodes; a truncated push ends the walk.
+
+use crate::ghost::{Error, Result};
+use super::disasm::{instructions, DisasmError, Instruction};
+
+/// One position of a pattern for `find_pattern`
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ScriptPatternToken {
+    /// This opcode, not a push
+    Op(u8),
+    /// A push of exactly these bytes
+    Push(Vec<u8>),
+    /// Any data push, captured
+    AnyPush,
+    /// Any data push of this length, captured
+    PushOfLen(usize),
+}
+
+/// First run of instructions matching a pattern
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Match<'a> {
+    /// Offset of the first matched instruction
+    pub offset: usize,
+    /// Offset just past the last matched instruction
+    pub end: usize,
+    /// Offset and data of each wildcard push, in pattern order
+    pub captures: Vec<(usize, &'a [u8])>,
+}
+
+/// Every data push of `len` bytes, with the offset of its push opcode
+pub fn pushes_of_len(script: &[u8], len: usize) -> Vec<(usize, &[u8])> {
+    instructions(script)
+        .map_while(|item| item.ok())
+        .filter_map(|(offset, ins)| match ins {
+            Instruction::Push(data) if data.len() == len => Some((offset, data)),
+            _ => None,
+        })
+        .collect()
+}
+
+/// First place `pattern` matches consecutive instructions of `script`
+pub fn find_pattern<'a>(script: &'a [u8], pattern: &[ScriptPatternToken]) -> Option<Match<'a>> {
+    let decoded = decode(script);
+    if pattern.is_empty() {
+        return None;
+    }
+    decoded.windows(pattern.len()).find_map(|window| {
+        let mut captures = Vec::new();
+        for (&(offset, _, ins), token) in window.iter().zip(pattern) {
+            match (token, ins) {
+                (ScriptPatternToken::Op(op), Instruction::Op(opcode)) if *op == opcode => {}
+                (ScriptPatternToken::Push(bytes), Instruction::Push(data)) if bytes.as_slice() == data => {}
+                (ScriptPatternToken::AnyPush, Instruction::Push(data)) => captures.push((offset, data)),
+                (ScriptPatternToken::PushOfLen(len), Instruction::Push(data)) if data.len() == *len => {
+                    captures.push((offset, data))
+                }
+                _ => return None,
+            }
+        }
+        Some(Match {
+            offset: window[0].0,
+            end: window[window.len() - 1].1,
+            captures,
+        })
+    })
+}
+
+/// Copy of `script` with the push at `offset` carrying `new_data` instead.
+/// Fails unless a push of `new_data.len()` bytes starts at `offset`, so the
+/// script keeps its length and every other offset.
+pub fn replace_push_at(script: &[u8], offset: usize, new_data: &[u8]) -> Result<Vec<u8>> {
+    let (_, end, ins) = decode(script)
+        .into_iter()
+        .find(|(start, _, _)| *start == offset)
+        .ok_or_else(|| Error::InvalidInput(format!("No instruction starts at offset {}", offset)))?;
+    let Instruction::Push(data) = ins else {
+        return Err(Error::InvalidInput(format!("Instruction at offset {} is not a push", offset)));
+    };
+    if data.len() != new_data.len() {
+        return Err(Error::InvalidInput(format!(
+            "Push at offset {} carries {} bytes, replacement has {}",
+            offset,
+            data.len(),
+            new_data.len()
+        )));
+    }
+    // Push data runs up to the next instruction
+    let mut patched = script.to_vec();
+    patched[end - data.len()..end].copy_from_slice(new_data);
+    Ok(patched)
+}
+
+// (start, end, instruction) up to the first truncated push
+fn decode(script: &[u8]) -> Vec<(usize, usize, Instruction<'_>)> {
+    let mut decoded: Vec<(usize, usize, Instruction<'_>)> = Vec::new();
+    for item in instructions(script) {
+        let start = match &item {
+            Ok((offset, _)) | Err(DisasmError::TruncatedPush { offset }) => *offset,
+            Err(_) => script.len(),
+        };
+        if let Some(last) = decoded.last_mut() {
+            last.1 = start;
+        }
+        match item {
+            Ok((offset, ins)) => decoded.push((offset, script.len(), ins)),
+            Err(_) => break,
+        }
+    }
+    decoded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ghost::script::opcodes::*;
+    use crate::ghost::script::push_bytes;
+    #[test]
+    #[cfg(feature = "contract")]
+    fn test_locate_and_replace_contract_commitments() {
+        use crate::ghost::script::field_script::get_constants_hash;
+        use crate::ghost::script::verifier_contract::{ContractOutput, IPAAccumulator, VerifierContract};
+        let contract = VerifierContract::new([0x11; 20], IPAAccumulator::new([0x22; 32]));
+        let output = ContractOutput::new(&contract, 100_000);
+        let script = &output.script_pubkey;
+        let pattern = [
+            ScriptPatternToken::PushOfLen(32),
+            ScriptPatternToken::Op(OP_DROP),
+            ScriptPatternToken::AnyPush,
+            ScriptPatternToken::Op(OP_TOALTSTACK),
+            ScriptPatternToken::PushOfLen(20),
+            ScriptPatternToken::Op(OP_TOALTSTACK),
+        ];
+        let header = find_pattern(script, &pattern).unwrap();
+        assert_eq!(header.offset, 0);
+        assert_eq!(header.captures[0].1, get_constants_hash());
+        assert_eq!(header.captures[2].1, [0x11; 20]);
+        assert_eq!(pushes_of_len(script, 20)[0], header.captures[2]);
+        assert!(pushes_of_len(script, 32).contains(&header.captures[0]));
+        let (pkh_offset, _) = header.captures[2];
+        let moved = ContractOutput {
+            script_pubkey: replace_push_at(script, pkh_offset, &[0x44; 20]).unwrap(),
+            ..output.clone()
+        };
+        assert_eq!(moved.script_pubkey.len(), script.len());
+        assert_eq!(moved.operator_pkh(), Some([0x44; 20]));
+        assert_eq!(moved.constants_hash(), output.constants_hash());
+    }
+    #[test]
+    fn test_push_data_not_read_as_opcodes() {
+        // The pushed bytes spell OP_TOALTSTACK OP_DROP
+        let mut script = push_bytes(&[OP_TOALTSTACK, OP_DROP]);
+        script.push(OP_TOALTSTACK);
+        let ops = [ScriptPatternToken::Op(OP_TOALTSTACK), ScriptPatternToken::Op(OP_DROP)];
+        assert_eq!(find_pattern(&script, &ops), None);
+        let found = find_pattern(&script, &[ScriptPatternToken::Push(vec![OP_TOALTSTACK, OP_DROP]), ScriptPatternToken::Op(OP_TOALTSTACK)]).unwrap();
+        assert_eq!((found.offset, found.end), (0, script.len()));
+        assert!(replace_push_at(&script, 1, &[0; 2]).is_err());
+        assert!(replace_push_at(&script, 3, &[]).is_err());
+        assert!(replace_push_at(&script, 0, &[0; 3]).is_err());
+        assert_eq!(replace_push_at(&script, 0, &[0xaa, 0xbb]).unwrap(), [0x02, 0xaa, 0xbb, OP_TOALTSTACK]);
+    }
+}
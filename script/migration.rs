@@ -0,0 +1,447 @@
+// Format Migration Sweeps
+// Moves old-format contract and Mullet outputs to new-format outputs in
+// batched transactions when the script format version bumps.
+//
+// PACKING:
+// Inputs are taken in the order given and packed greedily: a transaction is
+// closed as soon as the next input would push it over
+// `PolicyLimits::max_tx_size`. Each contract input gets one output re-created
+// in the current format with the same accumulator state and value; all Mullet
+// inputs of a transaction are aggregated into a single output to the sweep's
+// Mullet destination. The transaction fee comes out of that aggregate output,
+// or out of the largest contract output when the batch has no Mullet inputs.
+//
+// WITNESSES:
+// Packing uses the scriptSig size each input declares. Once a transaction's
+// inputs, outputs and fee are fixed, each witness closure is called with its
+// input's SIGHASH_ALL | FORKID preimage, so it can sign the final transaction
+// or satisfy a covenant. A witness that fails, or comes out longer than its
+// declared size, drops its input, which is reported; the rest of the batch
+// is re-priced and signed again without it. A batch whose fee would leave
+// the paying output below dust is skipped and its inputs reported, and the
+// sweep carries on with the next batch.
+
+use crate::ghost::Error;
+use crate::ghost::crypto::double_sha256;
+use super::binding_diff::serialize_outputs;
+use super::network::ChainParams;
+use super::policy::PolicyLimits;
+use super::tx::{serialize_transaction, OutPoint, TxInput};
+use super::verifier_contract::ContractOutput;
+use super::{MulletScript, SighashPreimage, SighashPreimageBuilder};
+
+/// Output being retired
+#[derive(Clone, Debug)]
+pub enum LegacyOutput {
+    Contract(ContractOutput),
+    Mullet { script: MulletScript, value: u64 },
+}
+
+impl LegacyOutput {
+    pub fn value(&self) -> u64 {
+        match self {
+            Self::Contract(output) => output.value,
+            Self::Mullet { value, .. } => *value,
+        }
+    }
+    /// Script being spent, the preimage's script code
+    pub fn locking_script(&self) -> Vec<u8> {
+        match self {
+            Self::Contract(output) => output.script_pubkey.clone(),
+            Self::Mullet { script, .. } => script.locking_script(),
+        }
+    }
+}
+
+/// Unspent old-format output and where it lives
+#[derive(Clone, Debug)]
+pub struct SweepUtxo {
+    pub outpoint: OutPoint,
+    pub output: LegacyOutput,
+}
+
+/// Produces the scriptSig spending a `SweepUtxo` from its input's preimage
+/// in the final sweep transaction; called again if the batch changes
+pub type WitnessFn = Box<dyn FnMut(&SweepUtxo, &SighashPreimage) -> Result<Vec<u8>, Error>>;
+
+#[derive(Debug)]
+pub enum SweepError {
+    /// The input's witness closure failed
+    Witness(Error),
+    /// The witness is longer than the size the input was packed with
+    WitnessTooLarge { size: usize, declared: usize },
+    /// The input alone makes a transaction larger than the policy allows
+    InputTooLarge { size: usize, max: usize },
+    /// The fee would leave the output paying it below the dust limit
+    InsufficientValue { fee: u64, available: u64 },
+}
+
+/// Input left out of the sweep
+#[derive(Debug)]
+pub struct SkippedInput {
+    pub outpoint: OutPoint,
+    pub error: SweepError,
+}
+
+#[derive(Clone, Debug)]
+pub struct SweepTransaction {
+    pub raw: Vec<u8>,
+    pub inputs: Vec<OutPoint>,
+    pub input_value: u64,
+    pub output_value: u64,
+    /// Priced at the declared witness sizes; shorter witnesses raise the rate
+    pub fee: u64,
+}
+
+#[derive(Debug)]
+pub struct Sweep {
+    /// Raw transactions in input order; they spend disjoint outputs and can
+    /// be broadcast in any order
+    pub transactions: Vec<SweepTransaction>,
+    pub skipped: Vec<SkippedInput>,
+}
+
+pub struct SweepBuilder {
+    operator_pkh: [u8; 20],
+    mullet_destination: MulletScript,
+    params: ChainParams,
+    policy: Option<PolicyLimits>,
+    utxos: Vec<(SweepUtxo, usize, WitnessFn)>,
+}
+
+// Input with its witness, and the new-format output it becomes. `input`
+// carries a zeroed scriptSig of the declared size until the batch is signed.
+struct Prepared {
+    utxo: SweepUtxo,
+    witness: WitnessFn,
+    input: TxInput,
+    value: u64,
+    contract_script: Option<Vec<u8>>,
+}
+
+impl SweepBuilder {
+    /// Contract outputs keep the operator committed in their header and fall
+    /// back to `operator_pkh` when they have none; Mullet outputs are
+    /// aggregated to `mullet_destination`
+    pub fn new(operator_pkh: [u8; 20], mullet_destination: MulletScript) -> Self {
+        Self {
+            operator_pkh,
+            mullet_destination,
+            params: ChainParams::default(),
+            policy: None,
+            utxos: Vec::new(),
+        }
+    }
+    /// Fee rate and dust limit; defaults to mainnet
+    pub fn chain_params(mut self, params: &ChainParams) -> Self {
+        self.params = params.clone();
+        self
+    }
+    /// Transaction size limit to pack under; defaults to the chain params' policy
+    pub fn policy(mut self, policy: &PolicyLimits) -> Self {
+        self.policy = Some(policy.clone());
+        self
+    }
+    /// `witness_size` is the scriptSig length the input is packed and priced
+    /// with; the witness may come out shorter, not longer
+    pub fn add_contract(
+        mut self,
+        outpoint: OutPoint,
+        output: ContractOutput,
+        witness_size: usize,
+        witness: impl FnMut(&SweepUtxo, &SighashPreimage) -> Result<Vec<u8>, Error> + 'static,
+    ) -> Self {
+        let utxo = SweepUtxo { outpoint, output: LegacyOutput::Contract(output) };
+        self.utxos.push((utxo, witness_size, Box::new(witness)));
+        self
+    }
+    /// As `add_contract`, for a Mullet output aggregated to the destination
+    pub fn add_mullet(
+        mut self,
+        outpoint: OutPoint,
+        script: MulletScript,
+        value: u64,
+        witness_size: usize,
+        witness: impl FnMut(&SweepUtxo, &SighashPreimage) -> Result<Vec<u8>, Error> + 'static,
+    ) -> Self {
+        let utxo = SweepUtxo { outpoint, output: LegacyOutput::Mullet { script, value } };
+        self.utxos.push((utxo, witness_size, Box::new(witness)));
+        self
+    }
+    pub fn build(mut self) -> Result<Sweep, SweepError> {
+        let max_tx_size = self.policy.clone().unwrap_or_else(|| self.params.policy()).max_tx_size;
+        let destination = self.mullet_destination.locking_script();
+        let mut sweep = Sweep { transactions: Vec::new(), skipped: Vec::new() };
+        let mut batch: Vec<Prepared> = Vec::new();
+        for (utxo, witness_size, witness) in std::mem::take(&mut self.utxos) {
+            batch.push(self.prepare(utxo, witness_size, witness));
+            if self.serialize(&batch, &destination, 0)?.len() <= max_tx_size {
+                continue;
+            }
+            let item = batch.pop().expect("just pushed");
+            if !batch.is_empty() {
+                self.finish(std::mem::take(&mut batch), &destination, &mut sweep);
+            }
+            batch.push(item);
+            let size = self.serialize(&batch, &destination, 0)?.len();
+            if size > max_tx_size {
+                let item = batch.pop().expect("just pushed");
+                sweep.skipped.push(SkippedInput {
+                    outpoint: item.input.outpoint,
+                    error: SweepError::InputTooLarge { size, max: max_tx_size },
+                });
+            }
+        }
+        if !batch.is_empty() {
+            self.finish(batch, &destination, &mut sweep);
+        }
+        Ok(sweep)
+    }
+    fn prepare(&self, utxo: SweepUtxo, witness_size: usize, witness: WitnessFn) -> Prepared {
+        let value = utxo.output.value();
+        let contract_script = match &utxo.output {
+            LegacyOutput::Contract(output) => {
+                let operator_pkh = output.operator_pkh().unwrap_or(self.operator_pkh);
+                Some(output.next_output(output.state.clone(), operator_pkh, value).script_pubkey)
+            }
+            LegacyOutput::Mullet { .. } => None,
+        };
+        let input = TxInput::new(utxo.outpoint, vec![0; witness_size]);
+        Prepared { utxo, witness, input, value, contract_script }
+    }
+    // Price and sign `batch`, dropping inputs until every witness succeeds
+    fn finish(&self, mut batch: Vec<Prepared>, destination: &[u8], sweep: &mut Sweep) {
+        while !batch.is_empty() {
+            // Values are fixed-width, so the fee-free serialization has the final size
+            let size = self.outputs(&batch, destination, 0).map(|outputs| self.transaction(&batch, &outputs).len());
+            let fee = size.map(|size| self.params.fee_for_size(size)).unwrap_or(0);
+            let outputs = match self.outputs(&batch, destination, fee) {
+                Ok(outputs) => outputs,
+                Err(SweepError::InsufficientValue { fee, available }) => {
+                    sweep.skipped.extend(batch.iter().map(|item| SkippedInput {
+                        outpoint: item.input.outpoint,
+                        error: SweepError::InsufficientValue { fee, available },
+                    }));
+                    return;
+                }
+                Err(error) => unreachable!("outputs only fails on value: {:?}", error),
+            };
+            match self.sign(&mut batch, &outputs) {
+                Ok(script_sigs) => {
+                    let inputs: Vec<TxInput> = batch
+                        .iter()
+                        .zip(script_sigs)
+                        .map(|(item, script_sig)| TxInput::new(item.input.outpoint, script_sig))
+                        .collect();
+                    let input_value = batch.iter().map(|item| item.value).sum();
+                    sweep.transactions.push(SweepTransaction {
+                        raw: serialize_transaction(1, &inputs, &outputs, 0),
+                        inputs: inputs.iter().map(|input| input.outpoint).collect(),
+                        input_value,
+                        output_value: input_value - fee,
+                        fee,
+                    });
+                    return;
+                }
+                Err((index, error)) => {
+                    let item = batch.remove(index);
+                    sweep.skipped.push(SkippedInput { outpoint: item.input.outpoint, error });
+                }
+            }
+        }
+    }
+    // Each input's scriptSig for the transaction paying `outputs`, or the
+    // first input whose witness fails
+    fn sign(&self, batch: &mut [Prepared], outputs: &[(u64, Vec<u8>)]) -> Result<Vec<Vec<u8>>, (usize, SweepError)> {
+        let inputs: Vec<TxInput> = batch.iter().map(|item| item.input.clone()).collect();
+        let hash_outputs = double_sha256(&serialize_outputs(outputs));
+        let mut script_sigs = Vec::with_capacity(batch.len());
+        for (index, item) in batch.iter_mut().enumerate() {
+            let preimage = SighashPreimageBuilder::new()
+                .prevouts(&inputs)
+                .input(&item.input)
+                .script_code(item.utxo.output.locking_script())
+                .value(item.value)
+                .hash_outputs(hash_outputs)
+                .build();
+            let declared = item.input.script_sig.len();
+            match (item.witness)(&item.utxo, &preimage) {
+                Ok(script_sig) if script_sig.len() <= declared => script_sigs.push(script_sig),
+                Ok(script_sig) => {
+                    return Err((index, SweepError::WitnessTooLarge { size: script_sig.len(), declared }));
+                }
+                Err(e) => return Err((index, SweepError::Witness(e))),
+            }
+        }
+        Ok(script_sigs)
+    }
+    // Contract outputs in input order, then the Mullet aggregate; `fee` is
+    // taken from the aggregate, or the largest contract output without one
+    fn outputs(&self, batch: &[Prepared], destination: &[u8], fee: u64) -> Result<Vec<(u64, Vec<u8>)>, SweepError> {
+        let mut outputs: Vec<(u64, Vec<u8>)> = batch
+            .iter()
+            .filter_map(|item| item.contract_script.clone().map(|script| (item.value, script)))
+            .collect();
+        let has_aggregate = batch.iter().any(|item| item.contract_script.is_none());
+        if has_aggregate {
+            let aggregate = batch.iter().filter(|item| item.contract_script.is_none()).map(|item| item.value).sum();
+            outputs.push((aggregate, destination.to_vec()));
+        }
+        let payer = if has_aggregate {
+            outputs.len() - 1
+        } else {
+            // First of the largest
+            (0..outputs.len()).rev().max_by_key(|&i| outputs[i].0).expect("batch is not empty")
+        };
+        let available = outputs[payer].0;
+        match available.checked_sub(fee) {
+            Some(value) if fee == 0 || !self.params.is_dust(value) => outputs[payer].0 = value,
+            _ => return Err(SweepError::InsufficientValue { fee, available }),
+        }
+        Ok(outputs)
+    }
+    fn transaction(&self, batch: &[Prepared], outputs: &[(u64, Vec<u8>)]) -> Vec<u8> {
+        let inputs: Vec<TxInput> = batch.iter().map(|item| item.input.clone()).collect();
+        serialize_transaction(1, &inputs, outputs, 0)
+    }
+    fn serialize(&self, batch: &[Prepared], destination: &[u8], fee: u64) -> Result<Vec<u8>, SweepError> {
+        Ok(self.transaction(batch, &self.outputs(batch, destination, fee)?))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ghost::script::tx::Transaction;
+    use crate::ghost::script::verifier_contract::{IPAAccumulator, VerifierContract};
+    use crate::ghost::script::EcdsaTail;
+    use std::cell::RefCell;
+    use std::rc::Rc;
+    fn mullet(byte: u8) -> MulletScript {
+        MulletScript::minimal(EcdsaTail::from_pubkey_hash(&[byte; 20]))
+    }
+    fn outpoint(i: usize) -> OutPoint {
+        OutPoint::new([i as u8; 32], i as u32)
+    }
+    #[test]
+    fn test_sweep_packs_under_tx_size_limit() {
+        let contract = VerifierContract::new([0x11; 20], IPAAccumulator::new([0x22; 32]));
+        // Room for two contract outputs per transaction
+        let policy = PolicyLimits { max_tx_size: 2 * contract.locking_script_size() + 10_000, ..PolicyLimits::permissive() };
+        let mut builder = SweepBuilder::new([0x33; 20], mullet(0x44)).policy(&policy);
+        let mut total = 0;
+        for i in 0..50 {
+            let value = 10_000 + i as u64;
+            total += value;
+            builder = if i % 10 == 0 {
+                builder.add_contract(outpoint(i), ContractOutput::new(&contract, value), 64, |_, _| Ok(vec![0x51; 64]))
+            } else {
+                builder.add_mullet(outpoint(i), mullet(0x55), value, 64, |_, _| Ok(vec![0x51; 64]))
+            };
+        }
+        let sweep = builder.build().unwrap();
+        assert!(sweep.skipped.is_empty());
+        assert_eq!(sweep.transactions.len(), 3);
+        let swept: Vec<OutPoint> = sweep.transactions.iter().flat_map(|tx| tx.inputs.clone()).collect();
+        assert_eq!(swept, (0..50).map(outpoint).collect::<Vec<_>>());
+        let params = ChainParams::default();
+        let mut fees = 0;
+        for tx in &sweep.transactions {
+            assert!(tx.raw.len() <= policy.max_tx_size);
+            assert_eq!(tx.fee, params.fee_for_size(tx.raw.len()));
+            let parsed = Transaction::parse(&tx.raw).unwrap();
+            let outputs: u64 = parsed.outputs.iter().map(|(value, _)| value).sum();
+            assert_eq!(outputs, tx.output_value);
+            assert_eq!(outputs + tx.fee, tx.input_value);
+            assert_eq!(parsed.outputs.last().unwrap().1, mullet(0x44).locking_script());
+            fees += tx.fee;
+        }
+        let outputs: u64 = sweep.transactions.iter().map(|tx| tx.output_value).sum();
+        assert_eq!(outputs + fees, total);
+    }
+    #[test]
+    fn test_sweep_preserves_contract_state_and_skips_failed_witness() {
+        let contract = VerifierContract::new([0x11; 20], IPAAccumulator::new([0x22; 32]));
+        let old = ContractOutput::new(&contract, 50_000);
+        let sweep = SweepBuilder::new([0x33; 20], mullet(0x44))
+            .add_contract(outpoint(0), old.clone(), 64, |_, _| Ok(vec![0x51; 64]))
+            .add_mullet(outpoint(1), mullet(0x55), 10_000, 64, |_, _| Err(Error::InvalidInput("no key".to_string())))
+            .build()
+            .unwrap();
+        assert_eq!(sweep.skipped.len(), 1);
+        assert_eq!(sweep.skipped[0].outpoint, outpoint(1));
+        assert!(matches!(sweep.skipped[0].error, SweepError::Witness(_)));
+        let tx = &sweep.transactions[0];
+        let parsed = Transaction::parse(&tx.raw).unwrap();
+        let expected = old.next_output(old.state.clone(), [0x11; 20], 50_000 - tx.fee);
+        assert_eq!(parsed.outputs, vec![(expected.value, expected.script_pubkey)]);
+    }
+    #[test]
+    fn test_sweep_witness_signs_final_transaction() {
+        let preimages: Rc<RefCell<Vec<SighashPreimage>>> = Rc::default();
+        let mut builder = SweepBuilder::new([0x33; 20], mullet(0x44));
+        for i in 0..3 {
+            let seen = preimages.clone();
+            builder = builder.add_mullet(outpoint(i), mullet(0x55), 10_000, 1_000, move |_, preimage| {
+                seen.borrow_mut().push(preimage.clone());
+                Ok(vec![0x51; 100])
+            });
+        }
+        let sweep = builder.build().unwrap();
+        let tx = &sweep.transactions[0];
+        let parsed = Transaction::parse(&tx.raw).unwrap();
+        let inputs: Vec<TxInput> = tx.inputs.iter().map(|outpoint| TxInput::new(*outpoint, Vec::new())).collect();
+        let hash_outputs = double_sha256(&serialize_outputs(&parsed.outputs));
+        let preimages = preimages.borrow();
+        assert_eq!(preimages.len(), 3);
+        for (input, preimage) in inputs.iter().zip(preimages.iter()) {
+            let expected = SighashPreimageBuilder::new()
+                .prevouts(&inputs)
+                .input(input)
+                .script_code(mullet(0x55).locking_script())
+                .value(10_000)
+                .hash_outputs(hash_outputs)
+                .build();
+            assert_eq!(preimage.to_bytes(), expected.to_bytes());
+        }
+        // Priced at the declared size, so the shorter witnesses only raise the rate
+        assert!(tx.fee > ChainParams::default().fee_for_size(tx.raw.len()));
+    }
+    #[test]
+    fn test_sweep_skips_unfunded_batch_and_continues() {
+        let policy = PolicyLimits { max_tx_size: 60_000, ..PolicyLimits::permissive() };
+        let sweep = SweepBuilder::new([0x33; 20], mullet(0x44))
+            .policy(&policy)
+            .add_mullet(outpoint(0), mullet(0x55), 1_000, 40_000, |_, _| Ok(vec![0x51; 64]))
+            .add_mullet(outpoint(1), mullet(0x55), 1_000_000, 30_000, |_, _| Ok(vec![0x51; 64]))
+            .build()
+            .unwrap();
+        assert_eq!(sweep.skipped.len(), 1);
+        assert_eq!(sweep.skipped[0].outpoint, outpoint(0));
+        assert!(matches!(sweep.skipped[0].error, SweepError::InsufficientValue { .. }));
+        assert_eq!(sweep.transactions.len(), 1);
+        assert_eq!(sweep.transactions[0].inputs, vec![outpoint(1)]);
+    }
+    #[test]
+    fn test_sweep_drops_oversized_witness_and_resigns() {
+        let calls = Rc::new(RefCell::new(0));
+        let counted = calls.clone();
+        let sweep = SweepBuilder::new([0x33; 20], mullet(0x44))
+            .add_mullet(outpoint(0), mullet(0x55), 10_000, 64, move |_, _| {
+                *counted.borrow_mut() += 1;
+                Ok(vec![0x51; 64])
+            })
+            .add_mullet(outpoint(1), mullet(0x55), 10_000, 64, |_, _| Ok(vec![0x51; 65]))
+            .build()
+            .unwrap();
+        assert_eq!(sweep.skipped.len(), 1);
+        assert_eq!(sweep.skipped[0].outpoint, outpoint(1));
+        assert!(matches!(sweep.skipped[0].error, SweepError::WitnessTooLarge { size: 65, declared: 64 }));
+        // Signed once against the two-input transaction, again without the dropped input
+        assert_eq!(*calls.borrow(), 2);
+        let tx = &sweep.transactions[0];
+        assert_eq!(tx.inputs, vec![outpoint(0)]);
+        let parsed = Transaction::parse(&tx.raw).unwrap();
+        assert_eq!(parsed.outputs, vec![(20_000 - 10_000 - tx.fee, mullet(0x44).locking_script())]);
+    }
+}
@@ -4,39 +4,114 @@ mod guard;
 mod tail;
 mod witness;
 mod guard_engine;
+// Cargo features: `core-script` (always on) is everything not gated below:
+// opcodes, tails, guards, witnesses, sighash and the shared field encodings.
+// `poseidon-script` adds field_script, poseidon_guard and the Poseidon
+// transcript; `contract` adds verifier_contract, proof_generator and the
+// migration sweeps, and implies `poseidon-script`. `cbor` adds the archival
+// encoding and implies `contract`, since it covers IPAStepWitness. The
+// feature table lives in the crate's Cargo.toml.
+pub mod field;
+pub mod sections;
+#[cfg(feature = "poseidon-script")]
 pub mod poseidon_guard;
+#[cfg(feature = "poseidon-script")]
 pub mod field_script;
+#[cfg(feature = "contract")]
 pub mod verifier_contract;
+#[cfg(feature = "contract")]
 pub mod proof_generator;
+pub mod disasm;
+pub mod binding_diff;
+pub mod vm;
+pub mod network;
+pub mod policy;
+pub mod point;
+pub mod amount;
+pub mod hashing;
+pub mod num;
+pub mod builder;
+pub mod bundle;
+#[cfg(feature = "cbor")]
+pub mod cbor;
+pub mod spv;
+pub mod tx;
+pub mod sighash;
+pub mod error;
+pub mod prelude;
+#[cfg(test)]
+mod test_support;
+pub mod der;
+#[cfg(feature = "contract")]
+pub mod migration;
+pub mod introspect;
 pub use opcodes::*;
-pub use hints::{IpaHints, PoseidonHints, PoseidonRoundHint, FoldingRound};
-pub use guard::{Guard, GuardType};
-pub use tail::{Tail, TailType, EcdsaTail, MultisigTail, LamportTail, SponsorTail, DualAuthTail, AnyoneCanSpendTail, CustomTail};
-pub use witness::{PaymasterWitness, EcdsaSignature};
-pub use guard_engine::{UniversalGuard, GuardConfig, VerifyPublicData, VerifyBinding, StackCleanup};
+pub use hints::{IpaHints, PoseidonHints, IntentHashHints, PoseidonRoundHint, FoldingRound};
+pub use guard::{Guard, GuardType, GuardFeatures, detect_guard_features, classify_guard, is_insecure_placeholder, FULL_GUARD_TRANSCRIPT_ELEMENTS, FULL_GUARD_INTENT_ELEMENT};
+pub use tail::{Tail, TailType, SpendPath, WitnessItem, EcdsaTail, MultisigTail, MAX_MULTISIG_KEYS, LamportTail, SponsorTail, DualAuthTail, HtlcTail, TimelockTail, SequenceTimelockTail, Timelock, check_timelock_satisfied, LamportPrivateKey, generate_lamport_keypair, BranchTail, AnyoneCanSpendTail, CustomTail, tail_size_report};
+pub use error::{ScriptErrorKind, ScriptResult, WitnessComponent};
+pub use witness::{sign_ecdsa, ecdsa_public_key};
+pub use witness::{Witness, PaymasterWitness, EcdsaSignature, RedactedDebug, WitnessDebug, serialize_intents, deserialize_intents, INTENT_OUTPUT_SIZE};
+pub use guard_engine::{UniversalGuard, GuardConfig, VerifyPublicData, StackSlot, VerifyBinding, StackCleanup, TranscriptKind, TailBinding, ScriptCodeBinding, OutputValueCap, OutputCount, MAX_COUNTED_OUTPUTS, InputCountBinding, extract_hash_outputs_from_preimage, hash_outputs_offset};
+#[cfg(feature = "contract")]
 pub use verifier_contract::{
     VerifierContract, IPAAccumulator, IPAStepWitness, 
-    ContractOutput, ContractTransactionBuilder, FieldElement,
+    ContractOutput, ContractTransactionBuilder, FinalizedStep,
+    ChainedStepPlan, PlannedStep, CpfpPolicy, AnchorPolicy, TranscriptShape, anchor_script, parse_anchor,
+    StateTransitionEvent, EventSink, NoopEventSink,
     analyze_contract_sizes, ContractSizeReport,
+    ContractSection, SimulationResult, SimulationStats, SimulationFailure,
 };
+#[cfg(feature = "contract")]
 pub use proof_generator::{
-    ProofGenerator, TranscriptBuilder, IPAProofComponents,
-    WitnessSerializer, generate_mock_proof, generate_mock_state_transition,
-    analyze_witness_sizes,
+    ProofGenerator, TranscriptBuilder, IPAProofComponents, ConformanceVector, ConformanceStep,
+    WitnessSerializer, generate_mock_proof, generate_mock_proof_with_state, generate_mock_state_transition,
+    analyze_witness_sizes, parse_blake2b_transcript,
 };
-use crate::ghost::crypto::{sha256};
+pub use field::FieldElement;
+pub use sections::{DeserializeError, WitnessSection};
+pub use network::{Network, ChainParams};
+pub use policy::PolicyLimits;
+pub use point::CompressedPoint;
+pub use bundle::{WitnessBundle, BundleError};
+#[cfg(feature = "cbor")]
+pub use cbor::{CborError, CBOR_FORMAT_VERSION};
+pub use builder::{MulletScriptBuilder, BuildError, OpReturnData};
+pub use hashing::{sha256_streaming, double_sha256_streaming, hash160_streaming};
+pub use tx::{OutPoint, TxInput, Transaction};
+pub use sighash::{SighashFlag, SighashPolicy};
+pub use der::DerError;
+pub use introspect::{ScriptPatternToken, Match, pushes_of_len, find_pattern, replace_push_at};
+#[cfg(feature = "contract")]
+pub use migration::{SweepBuilder, Sweep, SweepTransaction, SweepUtxo, LegacyOutput, SweepError, SkippedInput};
+use crate::ghost::crypto::sha256;
+use std::borrow::Borrow;
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use witness::{redacted_debug, sensitive, summary};
+/// Guard + tail locking script. Production outputs should go through
+/// `MulletScript::builder()`, which validates size, policy and hint shape.
 #[derive(Clone, Debug)]
 pub struct MulletScript {
-    pub guard: Guard,
-    pub tail: Box<dyn Tail>,
+    header: Vec<u8>,
+    guard: Guard,
+    tail: Box<dyn Tail>,
+    /// header || guard || tail, assembled once; equality and hashing use it
+    locking: Vec<u8>,
 }
 
 impl MulletScript {
+    pub fn builder() -> MulletScriptBuilder {
+        MulletScriptBuilder::new()
+    }
     pub fn new(guard: Guard, tail: impl Tail + 'static) -> Self {
-        Self {
-            guard,
-            tail: Box::new(tail),
-        }
+        Self::from_parts(Vec::new(), guard, Box::new(tail))
+    }
+    fn from_parts(header: Vec<u8>, guard: Guard, tail: Box<dyn Tail>) -> Self {
+        let mut locking = header.clone();
+        locking.extend(guard.to_bytes());
+        locking.extend(tail.locking_script());
+        Self { header, guard, tail, locking }
     }
     pub fn universal(tail: impl Tail + 'static) -> Self {
         Self::new(Guard::universal(), tail)
@@ -44,20 +119,111 @@ impl MulletScript {
     pub fn minimal(tail: impl Tail + 'static) -> Self {
         Self::new(Guard::minimal(), tail)
     }
+    /// Commitments (app id, version, metadata) placed ahead of the guard
+    pub fn header(&self) -> &[u8] {
+        &self.header
+    }
+    pub fn guard(&self) -> &Guard {
+        &self.guard
+    }
+    pub fn tail(&self) -> &dyn Tail {
+        self.tail.as_ref()
+    }
     pub fn locking_script(&self) -> Vec<u8> {
-        let mut script = self.guard.to_bytes();
-        script.extend(self.tail.locking_script());
-        script
+        self.locking.clone()
     }
     pub fn script_hash(&self) -> [u8; 32] {
-        sha256(&self.locking_script())
+        sha256(&self.locking)
+    }
+    /// Wallet index key for outputs locked to this script
+    pub fn utxo_key(&self) -> UtxoKey {
+        UtxoKey::for_script(&self.locking)
+    }
+    /// Tail hash a guard built with `GuardConfig::bind_tail` embeds
+    pub fn guard_bound_tail_hash(&self) -> [u8; 32] {
+        sha256(&self.tail.locking_script())
     }
     pub fn size(&self) -> usize {
-        self.guard.size() + self.tail.script_size()
+        self.header.len() + self.guard.size() + self.tail.script_size()
+    }
+    /// Split a header-less locking script produced with `guard` back into
+    /// guard and tail, recognizing branch tables
+    pub fn parse(script: &[u8], guard: Guard) -> Option<Self> {
+        let tail_bytes = script.strip_prefix(guard.to_bytes().as_slice())?;
+        let tail: Box<dyn Tail> = match BranchTail::parse(tail_bytes) {
+            Some(branch) => Box::new(branch),
+            None => Box::new(CustomTail::new(tail_bytes.to_vec())),
+        };
+        Some(Self::from_parts(Vec::new(), guard, tail))
+    }
+    /// Same script with a guard section requiring the sighash preimage's
+    /// script_code to be this locking script. The binding is sized in a first
+    /// pass and committed over the assembled script in a second.
+    pub fn bind_script_code(self) -> Self {
+        let Self { header, guard: unbound, tail, .. } = self;
+        let suffix_len = unbound.size() + tail.locking_script().len();
+        let binding = ScriptCodeBinding::fit(header.len(), suffix_len);
+        let sized = Self::from_parts(header, unbound.with_script_code_binding(&binding), tail);
+        let binding = binding.commit(&sized.locking);
+        Self::from_parts(sized.header, unbound.with_script_code_binding(&binding), sized.tail)
     }
 }
 
-#[derive(Clone, Debug)]
+/// Scripts are equal when their locking bytes are, however they were built
+impl PartialEq for MulletScript {
+    fn eq(&self, other: &Self) -> bool {
+        self.locking == other.locking
+    }
+}
+
+impl Eq for MulletScript {}
+
+impl Hash for MulletScript {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.locking.hash(state);
+    }
+}
+
+/// Version of the `UtxoKey` derivation; bump when the same logical output
+/// would be keyed differently, so stored indexes are rebuilt rather than
+/// silently mixed
+pub const UTXO_KEY_VERSION: u8 = 1;
+
+/// Stable key for deduplicating outputs by locking script:
+/// SHA256(version || SHA256(script)). Borrows as its bytes, so a map keyed by
+/// `UtxoKey` can be queried with a stored `[u8; 32]` directly.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct UtxoKey([u8; 32]);
+
+impl UtxoKey {
+    pub fn new(version: u8, script_hash: &[u8; 32]) -> Self {
+        let mut data = Vec::with_capacity(33);
+        data.push(version);
+        data.extend_from_slice(script_hash);
+        Self(sha256(&data))
+    }
+    /// Key under the current `UTXO_KEY_VERSION` for a raw locking script
+    pub fn for_script(script_pubkey: &[u8]) -> Self {
+        Self::new(UTXO_KEY_VERSION, &sha256(script_pubkey))
+    }
+    pub fn as_bytes(&self) -> &[u8; 32] {
+        &self.0
+    }
+}
+
+impl Borrow<[u8; 32]> for UtxoKey {
+    fn borrow(&self) -> &[u8; 32] {
+        &self.0
+    }
+}
+
+impl fmt::Display for UtxoKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&to_hex(self.0))
+    }
+}
+
+#[derive(Clone)]
 pub struct MulletWitness {
     pub proof: Vec<u8>,
     pub ipa_hints: IpaHints,
@@ -69,21 +235,68 @@ pub struct MulletWitness {
     pub change_bytes: Option<Vec<u8>>,
 }
 
+impl RedactedDebug for MulletWitness {
+    fn fmt_debug(&self, f: &mut fmt::Formatter<'_>, full: bool) -> fmt::Result {
+        f.debug_struct("MulletWitness")
+            .field("proof", &summary(&self.proof, full))
+            .field("ipa_hints", &summary(&self.ipa_hints.to_bytes(), full))
+            .field("poseidon_hints", &summary(&self.poseidon_hints.to_bytes(), full))
+            .field("tail_witness", &WitnessDebug::new(&self.tail_witness, full))
+            .field("preimage", &sensitive(&self.preimage.to_bytes(), full))
+            .field("app_bytes", &self.app_bytes.as_deref().map(|b| summary(b, full)))
+            .field("change_bytes", &self.change_bytes.as_deref().map(|b| summary(b, full)))
+            .finish()
+    }
+}
+
+impl Witness for MulletWitness {
+    fn try_to_script_sig(&self, max_push: usize) -> std::result::Result<Vec<u8>, PushError> {
+        MulletWitness::try_to_script_sig(self, max_push)
+    }
+    fn estimate_size(&self) -> usize {
+        self.script_sig_size()
+    }
+}
+
 impl MulletWitness {
+    /// Push-encoded scriptSig bytes of every component (proof, hints, tail
+    /// witness, preimage), i.e. the fallback layout's scriptSig whichever
+    /// layout is set. `script_sig_size` measures the layout actually built.
     pub fn size(&self) -> usize {
-        self.proof.len() 
-            + self.ipa_hints.size()
-            + self.poseidon_hints.size()
-            + self.tail_witness.size()
-            + self.preimage.size()
+        push_size(self.proof.len())
+            + self.ipa_hints.script_push_size()
+            + self.poseidon_hints.script_push_size()
+            + self.tail_witness.script_push_size()
+            + push_size(self.preimage.serialized_size())
     }
+    pub fn script_sig_size(&self) -> usize {
+        let mut size = push_size(self.proof.len());
+        if let Some(app) = &self.app_bytes {
+            size += push_size(app.len());
+        } else {
+            size += self.ipa_hints.script_push_size();
+            size += self.poseidon_hints.script_push_size();
+        }
+        if let Some(change) = &self.change_bytes {
+            size += push_size(change.len());
+        } else {
+            size += self.tail_witness.script_push_size();
+        }
+        size + push_size(self.preimage.serialized_size())
+    }
+    /// Panics on an element over u32::MAX bytes
+    #[deprecated(note = "use try_to_script_sig, which reports oversized pushes")]
     pub fn to_script_sig(&self) -> Vec<u8> {
+        self.try_to_script_sig(usize::MAX).expect("scriptSig push exceeds u32::MAX")
+    }
+    /// `to_script_sig` with every caller-sized payload capped at `max_push`
+    pub fn try_to_script_sig(&self, max_push: usize) -> std::result::Result<Vec<u8>, PushError> {
         let mut sig = Vec::new();
-        sig.extend(push_bytes(&self.proof)); // [Proof]
+        sig.extend(try_push_bytes(&self.proof, max_push)?); // [Proof]
         
         // App Bytes (Output 0 for Binding)
         if let Some(app) = &self.app_bytes {
-            sig.extend(push_bytes(app));
+            sig.extend(try_push_bytes(app, max_push)?);
         } else {
             // Fallback for non-binding scripts
             sig.extend(self.ipa_hints.to_script_pushes());
@@ -93,17 +306,128 @@ impl MulletWitness {
 
         // Change Bytes (Output 1 for Binding)
         if let Some(change) = &self.change_bytes {
-            sig.extend(push_bytes(change));
+            sig.extend(try_push_bytes(change, max_push)?);
         } else {
             sig.extend(self.tail_witness.to_script_pushes());
         }
 
-        sig.extend(push_bytes(&self.preimage.to_bytes())); // [Preimage]
-        sig
+        sig.extend(try_push_bytes(&self.preimage.to_bytes(), max_push)?); // [Preimage]
+        Ok(sig)
+    }
+    pub fn layout(&self) -> WitnessLayout {
+        WitnessLayout {
+            hints: match self.app_bytes {
+                Some(_) => None,
+                None => Some((self.ipa_hints.num_rounds(), self.poseidon_hints.num_rounds())),
+            },
+            tail: match self.change_bytes {
+                Some(_) => None,
+                None => Some(self.tail_witness.layout()),
+            },
+        }
+    }
+    pub fn to_stack_items(&self) -> Vec<Vec<u8>> {
+        let mut items = vec![self.proof.clone()];
+        match &self.app_bytes {
+            Some(app) => items.push(app.clone()),
+            None => {
+                items.extend(self.ipa_hints.to_stack_items());
+                items.extend(self.poseidon_hints.to_stack_items());
+            }
+        }
+        match &self.change_bytes {
+            Some(change) => items.push(change.clone()),
+            None => items.extend(self.tail_witness.to_stack_items()),
+        }
+        items.push(self.preimage.to_bytes());
+        items
+    }
+    pub fn from_stack_items(items: &[Vec<u8>], layout: WitnessLayout) -> Option<Self> {
+        let (proof, rest) = items.split_first()?;
+        let (preimage, mut rest) = rest.split_last()?;
+        let (ipa_hints, poseidon_hints, app_bytes) = match layout.hints {
+            Some((ipa_rounds, poseidon_rounds)) => {
+                let ipa_len = 4 * ipa_rounds + 2;
+                // Single-intent hints: rounds, output and commitment
+                let poseidon_len = 6 * poseidon_rounds + 2;
+                if rest.len() < ipa_len + poseidon_len {
+                    return None;
+                }
+                let ipa = IpaHints::from_stack_items(&rest[..ipa_len])?;
+                let poseidon = PoseidonHints::from_stack_items(&rest[ipa_len..ipa_len + poseidon_len])?;
+                rest = &rest[ipa_len + poseidon_len..];
+                (ipa, poseidon, None)
+            }
+            None => {
+                let (app, tail) = rest.split_first()?;
+                rest = tail;
+                (IpaHints::placeholder(0), PoseidonHints::placeholder(0), Some(app.clone()))
+            }
+        };
+        let (tail_witness, change_bytes) = match layout.tail {
+            Some(tail) => (TailWitness::from_stack_items(rest, tail)?, None),
+            None => match rest {
+                [change] => (TailWitness::Custom(Vec::new()), Some(change.clone())),
+                _ => return None,
+            },
+        };
+        Some(Self {
+            proof: proof.clone(),
+            ipa_hints,
+            poseidon_hints,
+            tail_witness,
+            preimage: SighashPreimage::from_bytes(preimage)?,
+            app_bytes,
+            change_bytes,
+        })
     }
 }
 
-#[derive(Clone, Debug)]
+/// Which sections of a MulletWitness are pushed, and their shapes.
+/// `hints` is `(ipa_rounds, poseidon_rounds)` when hints are pushed instead of app bytes;
+/// `tail` is set when the tail witness is pushed instead of change bytes.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct WitnessLayout {
+    pub hints: Option<(usize, usize)>,
+    pub tail: Option<TailWitnessLayout>,
+}
+
+impl WitnessLayout {
+    pub fn binding() -> Self {
+        Self { hints: None, tail: None }
+    }
+    pub fn fallback(ipa_rounds: usize, poseidon_rounds: usize, tail: TailWitnessLayout) -> Self {
+        Self { hints: Some((ipa_rounds, poseidon_rounds)), tail: Some(tail) }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum TailWitnessLayout {
+    Ecdsa,
+    Multisig { signatures: usize },
+    Lamport { preimages: usize },
+    Sponsor,
+    DualAuth,
+    Htlc { redeem: bool },
+    Branch { index: u8, inner: Box<TailWitnessLayout> },
+    Custom,
+}
+
+impl TailWitnessLayout {
+    pub fn item_count(&self) -> usize {
+        match self {
+            TailWitnessLayout::Ecdsa | TailWitnessLayout::Sponsor => 2,
+            TailWitnessLayout::Multisig { signatures } => 1 + signatures,
+            TailWitnessLayout::Lamport { preimages } => *preimages,
+            TailWitnessLayout::DualAuth => 4,
+            TailWitnessLayout::Htlc { redeem } => if *redeem { 4 } else { 3 },
+            TailWitnessLayout::Branch { inner, .. } => inner.item_count() + 1,
+            TailWitnessLayout::Custom => 1,
+        }
+    }
+}
+
+#[derive(Clone)]
 pub enum TailWitness {
     Ecdsa {
         signature: Vec<u8>,
@@ -115,26 +439,234 @@ pub enum TailWitness {
     Lamport {
         preimages: Vec<[u8; 32]>,
     },
+    Sponsor {
+        signature: Vec<u8>,
+        pubkey: Vec<u8>,
+    },
+    DualAuth {
+        user_signature: Vec<u8>,
+        user_pubkey: Vec<u8>,
+        sponsor_signature: Vec<u8>,
+        sponsor_pubkey: Vec<u8>,
+    },
+    Htlc {
+        signature: Vec<u8>,
+        pubkey: Vec<u8>,
+        preimage: Option<Vec<u8>>,
+    },
+    /// Witness for one branch of a `BranchTail`; the index is pushed last
+    Branch {
+        index: u8,
+        inner: Box<TailWitness>,
+    },
     Custom(Vec<u8>),
 }
 
+impl RedactedDebug for TailWitness {
+    fn fmt_debug(&self, f: &mut fmt::Formatter<'_>, full: bool) -> fmt::Result {
+        match self {
+            TailWitness::Ecdsa { signature, pubkey } => f.debug_struct("Ecdsa")
+                .field("signature", &sensitive(signature, full))
+                .field("pubkey", &to_hex(pubkey))
+                .finish(),
+            TailWitness::Multisig { signatures } => f.debug_struct("Multisig")
+                .field("signatures", &signatures.iter().map(|s| sensitive(s, full)).collect::<Vec<_>>())
+                .finish(),
+            TailWitness::Lamport { preimages } => f.debug_struct("Lamport")
+                .field("preimages", &preimages.iter().map(|p| sensitive(p, full)).collect::<Vec<_>>())
+                .finish(),
+            TailWitness::Sponsor { signature, pubkey } => f.debug_struct("Sponsor")
+                .field("signature", &sensitive(signature, full))
+                .field("pubkey", &to_hex(pubkey))
+                .finish(),
+            TailWitness::DualAuth { user_signature, user_pubkey, sponsor_signature, sponsor_pubkey } => f.debug_struct("DualAuth")
+                .field("user_signature", &sensitive(user_signature, full))
+                .field("user_pubkey", &to_hex(user_pubkey))
+                .field("sponsor_signature", &sensitive(sponsor_signature, full))
+                .field("sponsor_pubkey", &to_hex(sponsor_pubkey))
+                .finish(),
+            TailWitness::Htlc { signature, pubkey, preimage } => f.debug_struct("Htlc")
+                .field("signature", &sensitive(signature, full))
+                .field("pubkey", &to_hex(pubkey))
+                .field("preimage", &preimage.as_deref().map(|p| sensitive(p, full)))
+                .finish(),
+            TailWitness::Branch { index, inner } => f.debug_struct("Branch")
+                .field("index", index)
+                .field("inner", &WitnessDebug::new(inner.as_ref(), full))
+                .finish(),
+            TailWitness::Custom(data) => f.debug_tuple("Custom").field(&sensitive(data, full)).finish(),
+        }
+    }
+}
+
+redacted_debug!(MulletWitness, TailWitness);
+
 impl TailWitness {
     pub fn size(&self) -> usize {
         match self {
             TailWitness::Ecdsa { signature, pubkey } => signature.len() + pubkey.len(),
-            TailWitness::Multisig { signatures } => signatures.iter().map(|s| s.len()).sum(),
+            TailWitness::Multisig { signatures } => 1 + signatures.iter().map(|s| s.len()).sum::<usize>(),
             TailWitness::Lamport { preimages } => preimages.len() * 32,
+            TailWitness::Sponsor { signature, pubkey } => signature.len() + pubkey.len(),
+            TailWitness::DualAuth { user_signature, user_pubkey, sponsor_signature, sponsor_pubkey } => {
+                user_signature.len() + user_pubkey.len() + sponsor_signature.len() + sponsor_pubkey.len()
+            }
+            TailWitness::Htlc { signature, pubkey, preimage } => {
+                signature.len() + pubkey.len() + preimage.as_ref().map_or(0, |p| p.len()) + 1
+            }
+            TailWitness::Branch { inner, .. } => inner.size() + 1,
             TailWitness::Custom(data) => data.len(),
         }
     }
+    pub fn script_push_size(&self) -> usize {
+        match self {
+            TailWitness::Ecdsa { signature, pubkey } => push_size(signature.len()) + push_size(pubkey.len()),
+            TailWitness::Multisig { signatures } => 1 + signatures.iter().map(|s| push_size(s.len())).sum::<usize>(),
+            TailWitness::Lamport { preimages } => preimages.len() * push_size(32),
+            TailWitness::Sponsor { signature, pubkey } => push_size(signature.len()) + push_size(pubkey.len()),
+            TailWitness::DualAuth { user_signature, user_pubkey, sponsor_signature, sponsor_pubkey } => {
+                push_size(user_signature.len())
+                    + push_size(user_pubkey.len())
+                    + push_size(sponsor_signature.len())
+                    + push_size(sponsor_pubkey.len())
+            }
+            TailWitness::Htlc { signature, pubkey, preimage } => {
+                push_size(signature.len())
+                    + push_size(pubkey.len())
+                    + preimage.as_ref().map_or(0, |p| push_size(p.len()))
+                    + 1
+            }
+            TailWitness::Branch { index, inner } => inner.script_push_size() + push_number(*index as i64).len(),
+            TailWitness::Custom(data) => push_size(data.len()),
+        }
+    }
+    /// Signatures this witness pushes, in stack order
+    pub fn signatures(&self) -> Vec<&[u8]> {
+        match self {
+            TailWitness::Ecdsa { signature, .. } | TailWitness::Sponsor { signature, .. } | TailWitness::Htlc { signature, .. } => {
+                vec![signature.as_slice()]
+            }
+            TailWitness::DualAuth { user_signature, sponsor_signature, .. } => {
+                vec![user_signature.as_slice(), sponsor_signature.as_slice()]
+            }
+            TailWitness::Multisig { signatures } => signatures.iter().map(Vec::as_slice).collect(),
+            TailWitness::Branch { inner, .. } => inner.signatures(),
+            TailWitness::Lamport { .. } | TailWitness::Custom(_) => Vec::new(),
+        }
+    }
+    /// Rejects a signature whose sighash flag the tail's policy would fail in script
+    pub fn check_sighash_policy(&self, policy: &SighashPolicy) -> crate::ghost::Result<()> {
+        self.signatures().into_iter().try_for_each(|signature| policy.check_signature_bytes(signature))
+    }
+    pub fn layout(&self) -> TailWitnessLayout {
+        match self {
+            TailWitness::Ecdsa { .. } => TailWitnessLayout::Ecdsa,
+            TailWitness::Multisig { signatures } => TailWitnessLayout::Multisig { signatures: signatures.len() },
+            TailWitness::Lamport { preimages } => TailWitnessLayout::Lamport { preimages: preimages.len() },
+            TailWitness::Sponsor { .. } => TailWitnessLayout::Sponsor,
+            TailWitness::DualAuth { .. } => TailWitnessLayout::DualAuth,
+            TailWitness::Htlc { preimage, .. } => TailWitnessLayout::Htlc { redeem: preimage.is_some() },
+            TailWitness::Branch { index, inner } => TailWitnessLayout::Branch { index: *index, inner: Box::new(inner.layout()) },
+            TailWitness::Custom(_) => TailWitnessLayout::Custom,
+        }
+    }
+    pub fn to_stack_items(&self) -> Vec<Vec<u8>> {
+        match self {
+            TailWitness::Ecdsa { signature, pubkey } | TailWitness::Sponsor { signature, pubkey } => {
+                vec![signature.clone(), pubkey.clone()]
+            }
+            TailWitness::DualAuth { user_signature, user_pubkey, sponsor_signature, sponsor_pubkey } => vec![
+                user_signature.clone(),
+                user_pubkey.clone(),
+                sponsor_signature.clone(),
+                sponsor_pubkey.clone(),
+            ],
+            TailWitness::Multisig { signatures } => {
+                let mut items = vec![Vec::new()];
+                items.extend(signatures.iter().cloned());
+                items
+            }
+            TailWitness::Lamport { preimages } => preimages.iter().map(|p| p.to_vec()).collect(),
+            TailWitness::Htlc { signature, pubkey, preimage } => {
+                let mut items = vec![signature.clone(), pubkey.clone()];
+                match preimage {
+                    Some(preimage) => {
+                        items.push(preimage.clone());
+                        items.push(vec![1]);
+                    }
+                    None => items.push(Vec::new()),
+                }
+                items
+            }
+            TailWitness::Branch { index, inner } => {
+                let mut items = inner.to_stack_items();
+                items.push(branch_selector_item(*index));
+                items
+            }
+            TailWitness::Custom(data) => vec![data.clone()],
+        }
+    }
+    pub fn from_stack_items(items: &[Vec<u8>], layout: TailWitnessLayout) -> Option<Self> {
+        if items.len() != layout.item_count() {
+            return None;
+        }
+        let witness = match layout {
+            TailWitnessLayout::Ecdsa => TailWitness::Ecdsa { signature: items[0].clone(), pubkey: items[1].clone() },
+            TailWitnessLayout::Sponsor => TailWitness::Sponsor { signature: items[0].clone(), pubkey: items[1].clone() },
+            TailWitnessLayout::DualAuth => TailWitness::DualAuth {
+                user_signature: items[0].clone(),
+                user_pubkey: items[1].clone(),
+                sponsor_signature: items[2].clone(),
+                sponsor_pubkey: items[3].clone(),
+            },
+            TailWitnessLayout::Multisig { .. } => {
+                if !items[0].is_empty() {
+                    return None;
+                }
+                TailWitness::Multisig { signatures: items[1..].to_vec() }
+            }
+            TailWitnessLayout::Lamport { .. } => TailWitness::Lamport {
+                preimages: items.iter().map(|p| p.as_slice().try_into().ok()).collect::<Option<Vec<_>>>()?,
+            },
+            TailWitnessLayout::Htlc { redeem } => {
+                let selector: &[u8] = if redeem { &[1] } else { &[] };
+                if items.last()?.as_slice() != selector {
+                    return None;
+                }
+                TailWitness::Htlc {
+                    signature: items[0].clone(),
+                    pubkey: items[1].clone(),
+                    preimage: if redeem { Some(items[2].clone()) } else { None },
+                }
+            }
+            TailWitnessLayout::Branch { index, inner } => {
+                let (selector, rest) = items.split_last()?;
+                if *selector != branch_selector_item(index) {
+                    return None;
+                }
+                TailWitness::Branch { index, inner: Box::new(TailWitness::from_stack_items(rest, *inner)?) }
+            }
+            TailWitnessLayout::Custom => TailWitness::Custom(items[0].clone()),
+        };
+        Some(witness)
+    }
     pub fn to_script_pushes(&self) -> Vec<u8> {
         match self {
-            TailWitness::Ecdsa { signature, pubkey } => {
+            TailWitness::Ecdsa { signature, pubkey } | TailWitness::Sponsor { signature, pubkey } => {
                 let mut pushes = push_bytes(signature);
                 pushes.extend(push_bytes(pubkey));
                 pushes
             }
+            TailWitness::DualAuth { user_signature, user_pubkey, sponsor_signature, sponsor_pubkey } => {
+                // DualAuthTail checks the sponsor first, so the sponsor pair sits on top
+                let mut pushes = push_bytes(user_signature);
+                pushes.extend(push_bytes(user_pubkey));
+                pushes.extend(push_bytes(sponsor_signature));
+                pushes.extend(push_bytes(sponsor_pubkey));
+                pushes
+            }
             TailWitness::Multisig { signatures } => {
+                // CHECKMULTISIG dummy: OP_0 leaves an empty element (NULLDUMMY)
                 let mut pushes = vec![OP_0];
                 for sig in signatures {
                     pushes.extend(push_bytes(sig));
@@ -148,11 +680,33 @@ impl TailWitness {
                 }
                 pushes
             }
+            TailWitness::Htlc { signature, pubkey, preimage } => {
+                let mut pushes = push_bytes(signature);
+                pushes.extend(push_bytes(pubkey));
+                match preimage {
+                    Some(preimage) => {
+                        pushes.extend(push_bytes(preimage));
+                        pushes.push(OP_1);
+                    }
+                    None => pushes.push(OP_0),
+                }
+                pushes
+            }
+            TailWitness::Branch { index, inner } => {
+                let mut pushes = inner.to_script_pushes();
+                pushes.extend(push_number(*index as i64));
+                pushes
+            }
             TailWitness::Custom(data) => push_bytes(data),
         }
     }
 }
 
+/// Stack item left by the selector push for branch `index`
+fn branch_selector_item(index: u8) -> Vec<u8> {
+    num::parse_push(&push_number(index as i64)).map(|(item, _)| item).unwrap_or_default()
+}
+
 #[derive(Clone, Debug)]
 pub struct SighashPreimage {
     pub version: [u8; 4],
@@ -183,11 +737,181 @@ impl SighashPreimage {
         bytes.extend(&self.sighash_type);
         bytes
     }
+    pub fn from_bytes(bytes: &[u8]) -> Option<Self> {
+        let mut reader = bytes;
+        let version = take_bytes(&mut reader, 4)?.try_into().ok()?;
+        let hash_prevouts = take_bytes(&mut reader, 32)?.try_into().ok()?;
+        let hash_sequence = take_bytes(&mut reader, 32)?.try_into().ok()?;
+        let outpoint = take_bytes(&mut reader, 36)?.try_into().ok()?;
+        let script_len = match take_bytes(&mut reader, 1)?[0] {
+            0xfd => u16::from_le_bytes(take_bytes(&mut reader, 2)?.try_into().ok()?) as usize,
+            0xfe => u32::from_le_bytes(take_bytes(&mut reader, 4)?.try_into().ok()?) as usize,
+            0xff => u64::from_le_bytes(take_bytes(&mut reader, 8)?.try_into().ok()?) as usize,
+            n => n as usize,
+        };
+        let preimage = Self {
+            version,
+            hash_prevouts,
+            hash_sequence,
+            outpoint,
+            script_code: take_bytes(&mut reader, script_len)?.to_vec(),
+            value: take_bytes(&mut reader, 8)?.try_into().ok()?,
+            sequence: take_bytes(&mut reader, 4)?.try_into().ok()?,
+            hash_outputs: take_bytes(&mut reader, 32)?.try_into().ok()?,
+            locktime: take_bytes(&mut reader, 4)?.try_into().ok()?,
+            sighash_type: take_bytes(&mut reader, 4)?.try_into().ok()?,
+        };
+        if !reader.is_empty() {
+            return None;
+        }
+        Some(preimage)
+    }
     pub fn size(&self) -> usize {
         4 + 32 + 32 + 36 + self.script_code.len() + 8 + 4 + 32 + 4 + 4 + 3
     }
+    pub fn serialized_size(&self) -> usize {
+        4 + 32 + 32 + 36 + varint_len(self.script_code.len()) + self.script_code.len() + 8 + 4 + 32 + 4 + 4
+    }
+}
+
+/// Builds a `SighashPreimage` field by field. Defaults to version 1, a final
+/// sequence and SIGHASH_ALL | FORKID, with zeroed hashes and outpoint.
+#[derive(Clone, Debug)]
+pub struct SighashPreimageBuilder {
+    preimage: SighashPreimage,
+}
+
+impl Default for SighashPreimageBuilder {
+    fn default() -> Self {
+        Self {
+            preimage: SighashPreimage {
+                version: [1, 0, 0, 0],
+                hash_prevouts: [0u8; 32],
+                hash_sequence: [0u8; 32],
+                outpoint: [0u8; 36],
+                script_code: Vec::new(),
+                value: [0u8; 8],
+                sequence: [0xff; 4],
+                hash_outputs: [0u8; 32],
+                locktime: [0u8; 4],
+                sighash_type: [0x41, 0, 0, 0],
+            },
+        }
+    }
+}
+
+impl SighashPreimageBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    pub fn version(mut self, version: u32) -> Self {
+        self.preimage.version = version.to_le_bytes();
+        self
+    }
+    pub fn hash_prevouts(mut self, hash: [u8; 32]) -> Self {
+        self.preimage.hash_prevouts = hash;
+        self
+    }
+    pub fn hash_sequence(mut self, hash: [u8; 32]) -> Self {
+        self.preimage.hash_sequence = hash;
+        self
+    }
+    pub fn outpoint(mut self, outpoint: OutPoint) -> Self {
+        self.preimage.outpoint = outpoint.to_bytes();
+        self
+    }
+    /// hashPrevouts and hashSequence over all of `inputs`, as signed without
+    /// ANYONECANPAY
+    pub fn prevouts(self, inputs: &[TxInput]) -> Self {
+        let outpoints: Vec<[u8; 36]> = inputs.iter().map(|input| input.outpoint.to_bytes()).collect();
+        let sequences: Vec<[u8; 4]> = inputs.iter().map(|input| input.sequence.to_le_bytes()).collect();
+        self.hash_prevouts(double_sha256_streaming(outpoints.iter().map(|o| &o[..])))
+            .hash_sequence(double_sha256_streaming(sequences.iter().map(|s| &s[..])))
+    }
+    /// Outpoint and sequence of the input being signed
+    pub fn input(self, input: &TxInput) -> Self {
+        self.outpoint(input.outpoint).sequence(input.sequence)
+    }
+    /// Raw script_code; covenant spends should use `script_code_from`
+    pub fn script_code(mut self, script_code: Vec<u8>) -> Self {
+        self.preimage.script_code = script_code;
+        self
+    }
+    /// The locking script being spent, as BIP-143 signs it (Mullet scripts
+    /// carry no OP_CODESEPARATOR)
+    pub fn script_code_from(self, script: &MulletScript) -> Self {
+        self.script_code(script.locking_script())
+    }
+    pub fn value(mut self, satoshis: u64) -> Self {
+        self.preimage.value = satoshis.to_le_bytes();
+        self
+    }
+    pub fn sequence(mut self, sequence: u32) -> Self {
+        self.preimage.sequence = sequence.to_le_bytes();
+        self
+    }
+    pub fn hash_outputs(mut self, hash: [u8; 32]) -> Self {
+        self.preimage.hash_outputs = hash;
+        self
+    }
+    pub fn locktime(mut self, locktime: u32) -> Self {
+        self.preimage.locktime = locktime.to_le_bytes();
+        self
+    }
+    pub fn sighash_type(mut self, sighash_type: u32) -> Self {
+        self.preimage.sighash_type = sighash_type.to_le_bytes();
+        self
+    }
+    pub fn build(self) -> SighashPreimage {
+        self.preimage
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum PushError {
+    /// Payload above the caller's cap
+    TooLarge { len: usize, max: usize },
+    /// Length does not fit the 4-byte OP_PUSHDATA4 prefix
+    ExceedsPushData4 { len: usize },
+}
+
+fn check_push_len(len: usize, max_len: usize) -> std::result::Result<(), PushError> {
+    if u32::try_from(len).is_err() {
+        return Err(PushError::ExceedsPushData4 { len });
+    }
+    if len > max_len {
+        return Err(PushError::TooLarge { len, max: max_len });
+    }
+    Ok(())
+}
+
+/// Push for payloads whose size comes from outside (proofs, outputs,
+/// preimages); `PolicyLimits::max_push_size` is the relay cap
+pub fn try_push_bytes(data: &[u8], max_len: usize) -> std::result::Result<Vec<u8>, PushError> {
+    check_push_len(data.len(), max_len)?;
+    Ok(push_bytes(data))
+}
+
+/// Lowercase hex of `bytes`, for Debug and Display output
+pub fn to_hex(bytes: impl AsRef<[u8]>) -> String {
+    bytes.as_ref().iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// Inverse of `to_hex`, accepting either case; `None` for an odd length or a
+/// non-hex digit
+pub fn from_hex(s: &str) -> Option<Vec<u8>> {
+    if !s.len().is_multiple_of(2) || !s.bytes().all(|b| b.is_ascii_hexdigit()) {
+        return None;
+    }
+    s.as_bytes()
+        .chunks(2)
+        .map(|pair| u8::from_str_radix(std::str::from_utf8(pair).ok()?, 16).ok())
+        .collect()
 }
 
+/// Infallible push for data the caller already bounds (hashes, keys,
+/// signatures, field elements). Panics rather than truncating the length
+/// prefix above u32::MAX; use `try_push_bytes` for anything caller-sized.
 pub fn push_bytes(data: &[u8]) -> Vec<u8> {
     let mut result = Vec::new();
     if data.is_empty() {
@@ -204,13 +928,51 @@ pub fn push_bytes(data: &[u8]) -> Vec<u8> {
         result.extend(&(data.len() as u16).to_le_bytes());
         result.extend(data);
     } else {
+        let len = u32::try_from(data.len()).expect("push exceeds u32::MAX");
         result.push(OP_PUSHDATA4);
-        result.extend(&(data.len() as u32).to_le_bytes());
+        result.extend(&len.to_le_bytes());
         result.extend(data);
     }
     result
 }
 
+fn take_bytes<'a>(reader: &mut &'a [u8], n: usize) -> Option<&'a [u8]> {
+    if reader.len() < n {
+        return None;
+    }
+    let (head, rest) = reader.split_at(n);
+    *reader = rest;
+    Some(head)
+}
+
+pub fn push_prefix_len(len: usize) -> usize {
+    if len <= 75 {
+        1
+    } else if len <= 255 {
+        2
+    } else if len <= 65535 {
+        3
+    } else {
+        5
+    }
+}
+
+pub fn push_size(len: usize) -> usize {
+    push_prefix_len(len) + len
+}
+
+pub fn varint_len(n: usize) -> usize {
+    if n < 0xfd {
+        1
+    } else if n <= 0xffff {
+        3
+    } else if n <= 0xffffffff {
+        5
+    } else {
+        9
+    }
+}
+
 pub fn varint(n: usize) -> Vec<u8> {
     if n < 0xfd {
         vec![n as u8]
@@ -228,6 +990,26 @@ pub fn varint(n: usize) -> Vec<u8> {
         v
     }
 }
+/// Inverse of `varint`, advancing `reader` past it; `None` if truncated
+pub(crate) fn read_varint(reader: &mut &[u8]) -> Option<u64> {
+    let (&prefix, rest) = reader.split_first()?;
+    let width = match prefix {
+        0xfd => 2,
+        0xfe => 4,
+        0xff => 8,
+        n => {
+            *reader = rest;
+            return Some(n as u64);
+        }
+    };
+    if rest.len() < width {
+        return None;
+    }
+    let mut le = [0u8; 8];
+    le[..width].copy_from_slice(&rest[..width]);
+    *reader = &rest[width..];
+    Some(u64::from_le_bytes(le))
+}
 
 #[cfg(test)]
 mod tests {
@@ -245,12 +1027,292 @@ mod tests {
         assert_eq!(pushed, vec![OP_0]);
     }
     #[test]
+    fn test_multisig_dummy_is_empty_element() {
+        let witness = TailWitness::Multisig { signatures: vec![vec![0x30; 71], vec![0x30; 72]] };
+        let stack = vm::execute(&witness.to_script_pushes()).unwrap();
+        assert_eq!(stack, vec![Vec::new(), vec![0x30; 71], vec![0x30; 72]]);
+    }
+    #[test]
+    fn test_push_cap_boundary() {
+        let data = vec![0x42; 1000];
+        assert_eq!(try_push_bytes(&data, 1000).unwrap(), push_bytes(&data));
+        assert_eq!(try_push_bytes(&data, 999), Err(PushError::TooLarge { len: 1000, max: 999 }));
+        let too_long = u32::MAX as usize + 1;
+        assert_eq!(check_push_len(too_long, usize::MAX), Err(PushError::ExceedsPushData4 { len: too_long }));
+        assert!(check_push_len(u32::MAX as usize, usize::MAX).is_ok());
+    }
+    #[test]
+    fn test_try_to_script_sig_caps_proof() {
+        let witness = test_witness();
+        #[allow(deprecated)]
+        let uncapped = witness.to_script_sig();
+        assert_eq!(witness.try_to_script_sig(usize::MAX).unwrap(), uncapped);
+        let err = witness.try_to_script_sig(399).unwrap_err();
+        assert_eq!(err, PushError::TooLarge { len: 400, max: 399 });
+    }
+    #[test]
+    fn test_utxo_key_dedup() {
+        use std::collections::HashMap;
+        let tail = || EcdsaTail::from_pubkey_hash(&[0x11; 20]);
+        let direct = MulletScript::universal(tail());
+        let built = MulletScript::builder().tail(tail()).build().unwrap();
+        assert_eq!(direct, built);
+        assert_eq!(direct.utxo_key(), built.utxo_key());
+        assert_eq!(direct.utxo_key(), UtxoKey::for_script(&built.locking_script()));
+        assert_ne!(direct, MulletScript::minimal(tail()));
+
+        let script_hash = direct.script_hash();
+        assert_ne!(UtxoKey::new(UTXO_KEY_VERSION, &script_hash), UtxoKey::new(UTXO_KEY_VERSION + 1, &script_hash));
+        let v1 = MulletScript::builder().version(1).tail(tail()).build().unwrap();
+        let v2 = MulletScript::builder().version(2).tail(tail()).build().unwrap();
+        assert_ne!(v1.utxo_key(), v2.utxo_key());
+
+        let mut index: HashMap<UtxoKey, u64> = HashMap::new();
+        index.insert(direct.utxo_key(), 1_000);
+        *index.entry(built.utxo_key()).or_default() += 500;
+        assert_eq!(index.len(), 1);
+        let stored: [u8; 32] = *direct.utxo_key().as_bytes();
+        assert_eq!(index.get(&stored), Some(&1_500));
+        let scripts: std::collections::HashSet<MulletScript> = [direct, built, v1].into_iter().collect();
+        assert_eq!(scripts.len(), 2);
+    }
+    #[test]
+    fn test_cached_locking_matches_parts() {
+        let assembled = |s: &MulletScript| [s.header().to_vec(), s.guard().to_bytes(), s.tail().locking_script()].concat();
+        let tail = || EcdsaTail::from_pubkey_hash(&[0x11; 20]);
+        let headed = MulletScript::builder().version(3).tail(tail()).build().unwrap();
+        let bound = headed.clone().bind_script_code();
+        assert_ne!(bound, headed);
+        for script in [&headed, &bound] {
+            assert_eq!(script.locking_script(), assembled(script));
+        }
+        let minimal = MulletScript::minimal(tail());
+        let parsed = MulletScript::parse(&minimal.locking_script(), Guard::minimal()).unwrap();
+        assert_eq!(parsed.locking_script(), assembled(&parsed));
+        assert_eq!(parsed, minimal);
+    }
+    #[test]
     fn test_varint() {
         assert_eq!(varint(0), vec![0]);
         assert_eq!(varint(252), vec![252]);
         assert_eq!(varint(253), vec![0xfd, 253, 0]);
     }
     #[test]
+    fn test_push_size_matches_push_bytes() {
+        for len in [0, 1, 75, 76, 255, 256, 65535, 65536] {
+            assert_eq!(push_size(len), push_bytes(&vec![0x42; len]).len());
+        }
+    }
+    fn test_preimage() -> SighashPreimage {
+        SighashPreimageBuilder::new()
+            .hash_prevouts([0x11; 32])
+            .hash_sequence([0x22; 32])
+            .outpoint(OutPoint::from_bytes(&[0x33; 36]))
+            .script_code(vec![0x44; 300])
+            .value(u64::from_le_bytes([0x55; 8]))
+            .hash_outputs([0x66; 32])
+            .build()
+    }
+    fn test_witness() -> MulletWitness {
+        MulletWitness {
+            proof: vec![0xAB; 400],
+            ipa_hints: IpaHints::placeholder(10),
+            poseidon_hints: PoseidonHints::placeholder(4),
+            tail_witness: TailWitness::Ecdsa {
+                signature: vec![0x30; 72],
+                pubkey: vec![0x02; 33],
+            },
+            preimage: test_preimage(),
+            app_bytes: None,
+            change_bytes: None,
+        }
+    }
+    #[test]
+    fn test_tail_witness_debug_redacts_secrets() {
+        let signature: Vec<u8> = (0..72u8).collect();
+        let preimages = vec![[0xC3; 32]; 2];
+        let mut witness = test_witness();
+        witness.tail_witness = TailWitness::Branch {
+            index: 1,
+            inner: Box::new(TailWitness::Ecdsa { signature: signature.clone(), pubkey: vec![0x02; 33] }),
+        };
+        let debug = format!("{:?}", witness);
+        assert!(!debug.contains(&hex::encode(&signature)));
+        assert!(!debug.contains(&hex::encode(witness.preimage.to_bytes())));
+        assert!(debug.contains(&format!("<72 bytes sha256:{} 00010203..44454647>", hex::encode(&sha256(&signature)[..4]))));
+        assert!(format!("{:?}", witness.full_debug()).contains(&hex::encode(&signature)));
+        let lamport = format!("{:?}", TailWitness::Lamport { preimages: preimages.clone() });
+        assert!(!lamport.contains(&hex::encode(preimages[0])));
+        assert!(lamport.contains("<32 bytes"));
+    }
+    #[test]
+    fn test_script_sig_size_fallback_layout() {
+        let witness = test_witness();
+        assert_eq!(witness.script_sig_size(), witness.try_to_script_sig(usize::MAX).unwrap().len());
+    }
+    #[test]
+    fn test_script_sig_size_binding_layout() {
+        let mut witness = test_witness();
+        witness.app_bytes = Some(vec![0x01; 41]);
+        witness.change_bytes = Some(vec![0x02; 82]);
+        assert_eq!(witness.script_sig_size(), witness.try_to_script_sig(usize::MAX).unwrap().len());
+    }
+    #[test]
+    fn test_size_is_fallback_script_sig_size() {
+        let mut witness = test_witness();
+        let fallback = witness.try_to_script_sig(usize::MAX).unwrap().len();
+        assert_eq!(witness.size(), fallback);
+        witness.app_bytes = Some(vec![0x01; 41]);
+        witness.change_bytes = Some(vec![0x02; 82]);
+        assert_eq!(witness.size(), fallback);
+    }
+    fn assert_push_size_exact(witness: TailWitness) {
+        assert_eq!(witness.script_push_size(), witness.to_script_pushes().len());
+    }
+    #[test]
+    fn test_tail_witness_push_size_ecdsa() {
+        assert_push_size_exact(TailWitness::Ecdsa { signature: vec![0x30; 72], pubkey: vec![0x02; 33] });
+    }
+    #[test]
+    fn test_tail_witness_push_size_multisig() {
+        assert_push_size_exact(TailWitness::Multisig { signatures: vec![vec![0x30; 71], vec![0x30; 72]] });
+        assert_push_size_exact(TailWitness::Multisig { signatures: vec![] });
+    }
+    #[test]
+    fn test_tail_witness_push_size_lamport() {
+        assert_push_size_exact(TailWitness::Lamport { preimages: vec![[0x07; 32]; 256] });
+    }
+    #[test]
+    fn test_tail_witness_push_size_sponsor() {
+        assert_push_size_exact(TailWitness::Sponsor { signature: vec![0x30; 71], pubkey: vec![0x03; 33] });
+    }
+    #[test]
+    fn test_tail_witness_push_size_dual_auth() {
+        assert_push_size_exact(TailWitness::DualAuth {
+            user_signature: vec![0x30; 72],
+            user_pubkey: vec![0x02; 33],
+            sponsor_signature: vec![0x30; 71],
+            sponsor_pubkey: vec![0x03; 33],
+        });
+    }
+    #[test]
+    fn test_tail_witness_push_size_htlc() {
+        assert_push_size_exact(TailWitness::Htlc { signature: vec![0x30; 72], pubkey: vec![0x02; 33], preimage: Some(vec![0x09; 32]) });
+        assert_push_size_exact(TailWitness::Htlc { signature: vec![0x30; 72], pubkey: vec![0x02; 33], preimage: None });
+    }
+    #[test]
+    fn test_htlc_witness_spends_both_paths() {
+        let pubkey = vec![0x02; 33];
+        let preimage = vec![0x09; 32];
+        let pkh = crate::ghost::crypto::hash160(&pubkey);
+        let tail = HtlcTail::new(sha256(&preimage), pkh, pkh, 100);
+        for preimage in [Some(preimage), None] {
+            let witness = TailWitness::Htlc { signature: vec![0x30; 72], pubkey: pubkey.clone(), preimage };
+            let mut vm = vm::Interpreter::with_checker(Box::new(vm::MockSignatureChecker));
+            assert!(vm.verify(&witness.to_script_pushes(), &tail.locking_script()).unwrap());
+        }
+    }
+    #[test]
+    fn test_tail_witness_push_size_custom() {
+        assert_push_size_exact(TailWitness::Custom(vec![]));
+        assert_push_size_exact(TailWitness::Custom(vec![0x01; 300]));
+    }
+    #[test]
+    fn test_multisig_size_counts_dummy() {
+        let witness = TailWitness::Multisig { signatures: vec![vec![0x30; 72]] };
+        assert_eq!(witness.size(), 73);
+    }
+    fn repush(items: &[Vec<u8>]) -> Vec<u8> {
+        items.iter().flat_map(|item| num::push_item(item)).collect()
+    }
+    #[test]
+    fn test_stack_items_reproduce_script_sig() {
+        let mut witness = test_witness();
+        assert_eq!(repush(&witness.to_stack_items()), witness.try_to_script_sig(usize::MAX).unwrap());
+        let htlc = TailWitness::Htlc { signature: vec![0x30; 72], pubkey: vec![0x02; 33], preimage: Some(vec![0x09; 32]) };
+        witness.tail_witness = TailWitness::Branch { index: 1, inner: Box::new(htlc) };
+        assert_eq!(repush(&witness.to_stack_items()), witness.try_to_script_sig(usize::MAX).unwrap());
+        witness.app_bytes = Some(vec![0x01; 41]);
+        witness.change_bytes = Some(vec![0x02; 82]);
+        assert_eq!(repush(&witness.to_stack_items()), witness.try_to_script_sig(usize::MAX).unwrap());
+    }
+    #[test]
+    fn test_stack_items_roundtrip() {
+        let witness = test_witness();
+        let items = witness.to_stack_items();
+        let back = MulletWitness::from_stack_items(&items, witness.layout()).unwrap();
+        assert_eq!(back.try_to_script_sig(usize::MAX).unwrap(), witness.try_to_script_sig(usize::MAX).unwrap());
+        let layout = WitnessLayout::fallback(10, 4, TailWitnessLayout::Sponsor);
+        assert!(MulletWitness::from_stack_items(&items[..items.len() - 1], layout).is_none());
+    }
+    #[test]
+    fn test_preimage_from_bytes_roundtrip() {
+        let preimage = test_preimage();
+        let back = SighashPreimage::from_bytes(&preimage.to_bytes()).unwrap();
+        assert_eq!(back.to_bytes(), preimage.to_bytes());
+        assert!(SighashPreimage::from_bytes(&preimage.to_bytes()[1..]).is_none());
+    }
+    #[test]
+    fn test_eval_with_stack_items() {
+        let pubkey = vec![0x02; 33];
+        let pkh = crate::ghost::crypto::hash160(&pubkey);
+        let tail = HtlcTail::new(sha256(&[0x09; 32]), pkh, pkh, 100);
+        let witness = TailWitness::Htlc { signature: vec![0x30; 72], pubkey, preimage: Some(vec![0x09; 32]) };
+        let items = witness.to_stack_items();
+        assert_eq!(TailWitness::from_stack_items(&items, witness.layout()).unwrap().to_script_pushes(), witness.to_script_pushes());
+        let mut vm = vm::Interpreter::with_checker(Box::new(vm::MockSignatureChecker));
+        assert!(vm.eval_with_stack(items, &tail.locking_script()).unwrap());
+    }
+    fn branch_fixture() -> (BranchTail, Vec<TailWitness>) {
+        let pubkey = vec![0x02; 33];
+        let pkh = crate::ghost::crypto::hash160(&pubkey);
+        let tail = BranchTail::try_new(vec![
+            Box::new(EcdsaTail::from_pubkey_hash(&pkh)),
+            Box::new(HtlcTail::new(sha256(&[0x09; 32]), pkh, pkh, 100)),
+            Box::new(AnyoneCanSpendTail),
+        ]).unwrap();
+        let witnesses = vec![
+            TailWitness::Ecdsa { signature: vec![0x30; 72], pubkey: pubkey.clone() },
+            TailWitness::Htlc { signature: vec![0x30; 72], pubkey, preimage: Some(vec![0x09; 32]) },
+            TailWitness::Custom(vec![0x01]),
+        ];
+        (tail, witnesses)
+    }
+    #[test]
+    fn test_branch_tail_selects_each_branch() {
+        let (tail, witnesses) = branch_fixture();
+        for (index, inner) in witnesses.into_iter().enumerate() {
+            let witness = TailWitness::Branch { index: index as u8, inner: Box::new(inner) };
+            assert_push_size_exact(witness.clone());
+            let mut vm = vm::Interpreter::with_checker(Box::new(vm::MockSignatureChecker));
+            assert!(vm.verify(&witness.to_script_pushes(), &tail.locking_script()).unwrap());
+            let items = witness.to_stack_items();
+            assert_eq!(num::push_item(items.last().unwrap()), push_number(index as i64));
+            let back = TailWitness::from_stack_items(&items, witness.layout()).unwrap();
+            assert_eq!(back.to_script_pushes(), witness.to_script_pushes());
+            assert!(vm::Interpreter::with_checker(Box::new(vm::MockSignatureChecker)).eval_with_stack(items, &tail.locking_script()).unwrap());
+        }
+    }
+    #[test]
+    fn test_branch_tail_rejects_out_of_range_index() {
+        let (tail, mut witnesses) = branch_fixture();
+        let witness = TailWitness::Branch { index: 3, inner: Box::new(witnesses.pop().unwrap()) };
+        let mut vm = vm::Interpreter::new();
+        assert!(!matches!(vm.verify(&witness.to_script_pushes(), &tail.locking_script()), Ok(true)));
+    }
+    #[test]
+    fn test_mullet_script_parse_branch_tail() {
+        let (tail, _) = branch_fixture();
+        let mullet = MulletScript::new(Guard::minimal(), tail);
+        let parsed = MulletScript::parse(&mullet.locking_script(), Guard::minimal()).unwrap();
+        assert_eq!(parsed.tail.tail_type(), TailType::Branch);
+        assert_eq!(parsed.locking_script(), mullet.locking_script());
+        let plain = MulletScript::minimal(EcdsaTail::from_pubkey_hash(&[0u8; 20]));
+        let parsed = MulletScript::parse(&plain.locking_script(), Guard::minimal()).unwrap();
+        assert_eq!(parsed.tail.tail_type(), TailType::Custom);
+        assert!(MulletScript::parse(&plain.locking_script(), Guard::universal()).is_none());
+    }
+    #[test]
     fn test_mullet_script() {
         let guard = Guard::minimal();
         let tail = EcdsaTail::from_pubkey_hash(&[0u8; 20]);
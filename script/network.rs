@@ -0,0 +1,129 @@
+// Chain Parameters
+// Network-specific defaults (address versions, dust, fee rate, policy)
+// gathered in one place so callers pass them explicitly instead of each
+// module assuming mainnet.
+
+use crate::ghost::crypto::double_sha256;
+use super::policy::PolicyLimits;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Network {
+    Mainnet,
+    Testnet,
+    /// Scaling Test Network
+    Stn,
+    Regtest,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ChainParams {
+    pub network: Network,
+    /// Base58 version byte for pay-to-pubkey-hash addresses
+    pub pubkey_hash_version: u8,
+    /// Smallest output value relayed by default, in satoshis
+    pub dust_limit: u64,
+    /// Default fee rate in satoshis per 1000 bytes
+    pub default_fee_rate: u64,
+}
+
+impl ChainParams {
+    pub fn for_network(network: Network) -> Self {
+        match network {
+            Network::Mainnet => Self {
+                network,
+                pubkey_hash_version: 0x00,
+                dust_limit: 1,
+                default_fee_rate: 50,
+            },
+            Network::Testnet | Network::Stn => Self {
+                network,
+                pubkey_hash_version: 0x6f,
+                dust_limit: 1,
+                default_fee_rate: 50,
+            },
+            Network::Regtest => Self {
+                network,
+                pubkey_hash_version: 0x6f,
+                dust_limit: 0,
+                default_fee_rate: 0,
+            },
+        }
+    }
+    pub fn mainnet() -> Self {
+        Self::for_network(Network::Mainnet)
+    }
+    pub fn policy(&self) -> PolicyLimits {
+        PolicyLimits::default_for(self)
+    }
+    /// Fee for `size` bytes at the default rate, rounded up
+    pub fn fee_for_size(&self, size: usize) -> u64 {
+        (size as u64 * self.default_fee_rate).div_ceil(1000)
+    }
+    pub fn is_dust(&self, value: u64) -> bool {
+        value < self.dust_limit
+    }
+}
+
+impl Default for ChainParams {
+    fn default() -> Self {
+        Self::mainnet()
+    }
+}
+
+const BASE58_ALPHABET: &[u8] = b"123456789ABCDEFGHJKLMNPQRSTUVWXYZabcdefghijkmnopqrstuvwxyz";
+
+/// Base58Check encoding of `version || payload`
+pub fn base58check(version: u8, payload: &[u8]) -> String {
+    let mut data = Vec::with_capacity(1 + payload.len() + 4);
+    data.push(version);
+    data.extend_from_slice(payload);
+    let checksum = double_sha256(&data);
+    data.extend_from_slice(&checksum[..4]);
+
+    let mut digits: Vec<u8> = Vec::new();
+    for byte in &data {
+        let mut carry = *byte as u32;
+        for digit in digits.iter_mut() {
+            carry += (*digit as u32) << 8;
+            *digit = (carry % 58) as u8;
+            carry /= 58;
+        }
+        while carry > 0 {
+            digits.push((carry % 58) as u8);
+            carry /= 58;
+        }
+    }
+    let leading_zeros = data.iter().take_while(|b| **b == 0).count();
+    let mut encoded = String::with_capacity(leading_zeros + digits.len());
+    for _ in 0..leading_zeros {
+        encoded.push('1');
+    }
+    for digit in digits.iter().rev() {
+        encoded.push(BASE58_ALPHABET[*digit as usize] as char);
+    }
+    encoded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[test]
+    fn test_version_bytes() {
+        assert_eq!(ChainParams::for_network(Network::Mainnet).pubkey_hash_version, 0x00);
+        assert_eq!(ChainParams::for_network(Network::Testnet).pubkey_hash_version, 0x6f);
+        assert_eq!(ChainParams::for_network(Network::Stn).pubkey_hash_version, 0x6f);
+    }
+    #[test]
+    fn test_base58check_known_vector() {
+        // All-zero pubkey hash: the well-known burn address
+        assert_eq!(base58check(0x00, &[0u8; 20]), "1111111111111111111114oLvT2");
+    }
+    #[test]
+    fn test_fee_rounds_up() {
+        let params = ChainParams::mainnet();
+        assert_eq!(params.fee_for_size(1), 1);
+        assert_eq!(params.fee_for_size(1000), 50);
+        assert_eq!(params.fee_for_size(1001), 51);
+        assert_eq!(ChainParams::for_network(Network::Regtest).fee_for_size(5000), 0);
+    }
+}
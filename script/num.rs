@@ -0,0 +1,174 @@
+// Script Numbers and Push Semantics
+// The single definition of what a push leaves on the stack and how stack
+// elements read as numbers; the interpreter and the script parsers follow it.
+//
+// EMPTY VS ZERO:
+// OP_0 (OP_FALSE) pushes the empty byte string, never [0x00]. The empty
+// string is also the minimal encoding of the number 0 and is falsy, so
+// numeric and boolean consumers see zero either way, while byte consumers
+// (OP_CAT, OP_SIZE, hashes) see nothing. `push_bytes(&[])` therefore
+// round-trips to an empty Vec, and OP_CAT onto an OP_0 accumulator adds no
+// leading byte.
+//
+// SMALL INTEGERS:
+// OP_1NEGATE and OP_1..OP_16 push the minimal number encoding of -1 and
+// 1..16, i.e. [0x81] and [0x01]..[0x10].
+//
+// NUMBERS:
+// Little-endian sign-magnitude of arbitrary length, the sign in the top bit
+// of the last byte. [0x80] is negative zero: numerically 0 and falsy.
+
+use num_bigint::{BigInt, Sign};
+use num_traits::Zero;
+use super::opcodes::*;
+use super::disasm::{instructions, Instruction};
+
+/// Decode a script number (little-endian sign-magnitude)
+pub fn decode_num(bytes: &[u8]) -> BigInt {
+    if bytes.is_empty() {
+        return BigInt::zero();
+    }
+    let mut magnitude = bytes.to_vec();
+    let last = magnitude.len() - 1;
+    let negative = magnitude[last] & 0x80 != 0;
+    magnitude[last] &= 0x7f;
+    let n = BigInt::from_bytes_le(Sign::Plus, &magnitude);
+    if negative { -n } else { n }
+}
+
+/// Encode a script number minimally
+pub fn encode_num(n: &BigInt) -> Vec<u8> {
+    if n.is_zero() {
+        return Vec::new();
+    }
+    let (sign, mut bytes) = n.to_bytes_le();
+    let negative = sign == Sign::Minus;
+    if bytes.last().is_some_and(|b| b & 0x80 != 0) {
+        bytes.push(if negative { 0x80 } else { 0x00 });
+    } else if negative {
+        if let Some(last) = bytes.last_mut() {
+            *last |= 0x80;
+        }
+    }
+    bytes
+}
+
+/// Truthiness of a stack element: any non-zero byte, except negative zero
+pub fn is_truthy(bytes: &[u8]) -> bool {
+    for (i, b) in bytes.iter().enumerate() {
+        if *b != 0 {
+            return !(i == bytes.len() - 1 && *b == 0x80);
+        }
+    }
+    false
+}
+
+/// Stack element left by the push at the start of `script`, with the number
+/// of script bytes it occupies. `None` if the first instruction is not a
+/// push (or is truncated).
+pub fn parse_push(script: &[u8]) -> Option<(Vec<u8>, usize)> {
+    let mut iter = instructions(script);
+    let (_, ins) = iter.next()?.ok()?;
+    let item = match ins {
+        Instruction::Push(data) => data.to_vec(),
+        Instruction::Op(OP_1NEGATE) => vec![0x81],
+        Instruction::Op(op @ OP_1..=OP_16) => vec![op - OP_1 + 1],
+        Instruction::Op(_) => return None,
+    };
+    let consumed = iter.next().map_or(script.len(), |next| match next {
+        Ok((offset, _)) | Err(super::disasm::DisasmError::TruncatedPush { offset }) => offset,
+        Err(_) => script.len(),
+    });
+    Some((item, consumed))
+}
+
+/// Parse a script consisting only of pushes into its stack elements
+pub fn parse_pushes(mut script: &[u8]) -> Option<Vec<Vec<u8>>> {
+    let mut items = Vec::new();
+    while !script.is_empty() {
+        let (item, consumed) = parse_push(script)?;
+        items.push(item);
+        script = &script[consumed..];
+    }
+    Some(items)
+}
+
+/// Minimal push leaving `item` on the stack: the small-integer opcodes for
+/// [0x81] and [0x01]..[0x10], a data push otherwise. `parse_push` inverts it.
+pub fn push_item(item: &[u8]) -> Vec<u8> {
+    match item {
+        [0x81] => vec![OP_1NEGATE],
+        [n @ 0x01..=0x10] => vec![OP_1 + n - 1],
+        _ => super::push_bytes(item),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ghost::script::push_bytes;
+    use crate::ghost::script::vm::Interpreter;
+    #[test]
+    fn test_op_0_round_trip() {
+        assert_eq!(push_bytes(&[]), vec![OP_0]);
+        assert_eq!(parse_push(&push_bytes(&[])), Some((Vec::new(), 1)));
+        assert_eq!(parse_push(&push_number(0)), Some((Vec::new(), 1)));
+        let mut vm = Interpreter::new();
+        vm.eval(&[OP_0]).unwrap();
+        assert_eq!(vm.stack(), &[Vec::<u8>::new()]);
+    }
+    #[test]
+    fn test_small_int_pushes_are_minimal_numbers() {
+        for n in -1i64..=16 {
+            let script = push_number(n);
+            let (item, consumed) = parse_push(&script).unwrap();
+            assert_eq!(consumed, script.len());
+            assert_eq!(item, encode_num(&BigInt::from(n)));
+            let mut vm = Interpreter::new();
+            vm.eval(&script).unwrap();
+            assert_eq!(vm.stack(), &[item]);
+        }
+    }
+    #[test]
+    fn test_parse_pushes_matches_interpreter() {
+        let data = [vec![], vec![0x00], vec![0x80], vec![0x42; 80], vec![0x07; 300]];
+        let mut script = Vec::new();
+        for item in &data {
+            script.extend(push_bytes(item));
+        }
+        script.extend(push_number(-1));
+        let items = parse_pushes(&script).unwrap();
+        let mut vm = Interpreter::new();
+        vm.eval(&script).unwrap();
+        assert_eq!(vm.stack(), items.as_slice());
+        assert_eq!(&items[..data.len()], &data);
+        assert_eq!(parse_pushes(&[OP_DUP]), None);
+        assert_eq!(parse_pushes(&[0x05, 0x01]), None);
+    }
+    #[test]
+    fn test_push_item_is_minimal() {
+        assert_eq!(push_item(&[0x01]), vec![OP_1]);
+        assert_eq!(push_item(&[0x81]), vec![OP_1NEGATE]);
+        assert_eq!(push_item(&[0x11]), push_bytes(&[0x11]));
+        for item in [vec![], vec![0x00], vec![0x10], vec![0x81], vec![0x42; 80]] {
+            assert_eq!(parse_push(&push_item(&item)), Some((item.clone(), push_item(&item).len())));
+        }
+    }
+    #[test]
+    fn test_cat_onto_empty_accumulator() {
+        let mut script = vec![OP_0];
+        script.extend(push_bytes(&[0x00, 0x11]));
+        script.push(OP_CAT);
+        let mut vm = Interpreter::new();
+        vm.eval(&script).unwrap();
+        assert_eq!(vm.stack(), &[vec![0x00, 0x11]]);
+    }
+    #[test]
+    fn test_empty_and_negative_zero_are_zero() {
+        assert!(decode_num(&[]).is_zero());
+        assert!(decode_num(&[0x80]).is_zero());
+        assert!(!is_truthy(&[]));
+        assert!(!is_truthy(&[0x00, 0x80]));
+        assert!(is_truthy(&[0x00, 0x01]));
+    }
+}
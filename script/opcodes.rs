@@ -114,7 +114,7 @@ pub fn push_number(n: i64) -> Vec<u8> {
     if n == 0 {
         return vec![OP_0];
     }
-    if n >= 1 && n <= 16 {
+    if (1..=16).contains(&n) {
         return vec![OP_1 + (n as u8) - 1];
     }
     if n == -1 {
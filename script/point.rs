@@ -0,0 +1,135 @@
+// Compressed Curve Points
+// 33-byte SEC-style encodings of Pallas points (y^2 = x^3 + 5 over Fp):
+// a parity prefix (0x02 even y, 0x03 odd y) followed by x as a 32-byte
+// little-endian field repr. All zeros encodes the point at infinity.
+// Kept distinct from 32-byte field elements so the two cannot be mixed up.
+
+use crate::ghost::crypto::{Fp, FieldExt};
+use super::field::{bytes_to_fp, fp_to_bytes};
+use ff::{Field, PrimeField};
+
+pub const COMPRESSED_POINT_BYTES: usize = 33;
+const CURVE_B: u64 = 5;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct CompressedPoint([u8; COMPRESSED_POINT_BYTES]);
+
+impl CompressedPoint {
+    pub const IDENTITY: Self = Self([0u8; COMPRESSED_POINT_BYTES]);
+    pub fn from_affine(x: &Fp, y: &Fp) -> Self {
+        let mut bytes = [0u8; COMPRESSED_POINT_BYTES];
+        bytes[0] = if bool::from(y.is_odd()) { 0x03 } else { 0x02 };
+        bytes[1..].copy_from_slice(&fp_to_bytes(x));
+        Self(bytes)
+    }
+    pub fn as_bytes(&self) -> &[u8; COMPRESSED_POINT_BYTES] {
+        &self.0
+    }
+    pub fn is_identity(&self) -> bool {
+        *self == Self::IDENTITY
+    }
+    /// True for the identity or an encoding that decompresses onto the curve
+    pub fn validate(&self) -> bool {
+        self.is_identity() || self.to_affine().is_some()
+    }
+    /// From Halo2's 32-byte point encoding: x little-endian with the y
+    /// parity in the top bit of the last byte; all zeros is the identity
+    pub fn from_halo2_bytes(bytes: &[u8; 32]) -> Self {
+        if bytes.iter().all(|b| *b == 0) {
+            return Self::IDENTITY;
+        }
+        let mut out = [0u8; COMPRESSED_POINT_BYTES];
+        out[0] = if bytes[31] & 0x80 != 0 { 0x03 } else { 0x02 };
+        out[1..].copy_from_slice(bytes);
+        out[32] &= 0x7f;
+        Self(out)
+    }
+    /// Decompress to `(x, y)`; `None` for the identity or an invalid encoding
+    pub fn to_affine(&self) -> Option<(Fp, Fp)> {
+        let odd = match self.0[0] {
+            0x02 => false,
+            0x03 => true,
+            _ => return None,
+        };
+        let x = bytes_to_fp(self.0[1..].try_into().ok()?)?;
+        let rhs = x.square() * x + Fp::from_u64(CURVE_B);
+        let y: Fp = Option::from(rhs.sqrt())?;
+        let y = if bool::from(y.is_odd()) == odd { y } else { -y };
+        Some((x, y))
+    }
+}
+
+impl From<[u8; COMPRESSED_POINT_BYTES]> for CompressedPoint {
+    fn from(bytes: [u8; COMPRESSED_POINT_BYTES]) -> Self {
+        Self(bytes)
+    }
+}
+
+impl From<CompressedPoint> for [u8; COMPRESSED_POINT_BYTES] {
+    fn from(point: CompressedPoint) -> Self {
+        point.0
+    }
+}
+
+impl TryFrom<&[u8]> for CompressedPoint {
+    type Error = std::array::TryFromSliceError;
+    fn try_from(bytes: &[u8]) -> Result<Self, Self::Error> {
+        Ok(Self(bytes.try_into()?))
+    }
+}
+
+impl AsRef<[u8]> for CompressedPoint {
+    fn as_ref(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    fn curve_point() -> (Fp, Fp) {
+        // Smallest x with x^3 + 5 a square
+        (1u64..)
+            .map(Fp::from_u64)
+            .find_map(|x| Option::<Fp>::from((x.square() * x + Fp::from_u64(CURVE_B)).sqrt()).map(|y| (x, y)))
+            .unwrap()
+    }
+    #[test]
+    fn test_compress_decompress_roundtrip() {
+        let (x, y) = curve_point();
+        for y in [y, -y] {
+            let point = CompressedPoint::from_affine(&x, &y);
+            assert!(point.validate());
+            assert_eq!(point.to_affine(), Some((x, y)));
+        }
+    }
+    #[test]
+    fn test_halo2_encoding() {
+        let (x, y) = curve_point();
+        for y in [y, -y] {
+            let mut bytes = fp_to_bytes(&x);
+            bytes[31] |= (bool::from(y.is_odd()) as u8) << 7;
+            assert_eq!(CompressedPoint::from_halo2_bytes(&bytes), CompressedPoint::from_affine(&x, &y));
+        }
+        assert!(CompressedPoint::from_halo2_bytes(&[0u8; 32]).is_identity());
+    }
+    #[test]
+    fn test_invalid_encodings() {
+        assert!(CompressedPoint::IDENTITY.validate());
+        assert_eq!(CompressedPoint::IDENTITY.to_affine(), None);
+        let mut bad_prefix = [0x11u8; 33];
+        bad_prefix[0] = 0x04;
+        assert!(!CompressedPoint::from(bad_prefix).validate());
+        let mut off_curve = [0xffu8; 33];
+        off_curve[0] = 0x02;
+        assert!(!CompressedPoint::from(off_curve).validate());
+    }
+    #[test]
+    fn test_field_element_is_not_a_point() {
+        let field_element = fp_to_bytes(&Fp::from_u64(7));
+        assert!(CompressedPoint::try_from(&field_element[..]).is_err());
+        let point = CompressedPoint::from([0x02; 33]);
+        let raw: [u8; 33] = point.into();
+        assert_eq!(CompressedPoint::try_from(&raw[..]).unwrap(), point);
+    }
+}
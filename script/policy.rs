@@ -0,0 +1,95 @@
+// Relay Policy Limits
+// Node policy (not consensus) bounds a script must satisfy to be relayed
+// and mined by default on a given network.
+
+use crate::ghost::{Error, Result};
+use super::disasm::{instructions, Instruction};
+use super::network::{ChainParams, Network};
+
+/// Largest locking or unlocking script relayed by default after Genesis
+/// (bitcoin-sv `DEFAULT_MAX_SCRIPT_SIZE_POLICY_AFTER_GENESIS`, 500 KB)
+pub const MAX_SCRIPT_SIZE: usize = 500_000;
+/// Largest transaction relayed by default after Genesis
+/// (bitcoin-sv `DEFAULT_MAX_TX_SIZE_POLICY_AFTER_GENESIS`, 10 MB)
+pub const MAX_TX_SIZE: usize = 10_000_000;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PolicyLimits {
+    /// Largest locking or unlocking script accepted, in bytes
+    pub max_script_size: usize,
+    /// Largest single data push accepted, in bytes
+    pub max_push_size: usize,
+    /// Largest raw transaction accepted, in bytes
+    pub max_tx_size: usize,
+}
+
+impl PolicyLimits {
+    pub fn default_for(params: &ChainParams) -> Self {
+        match params.network {
+            Network::Mainnet | Network::Testnet | Network::Stn => Self {
+                max_script_size: MAX_SCRIPT_SIZE,
+                // No separate push policy after Genesis: a push is bounded
+                // by the script carrying it
+                max_push_size: MAX_SCRIPT_SIZE,
+                max_tx_size: MAX_TX_SIZE,
+            },
+            Network::Regtest => Self::permissive(),
+        }
+    }
+    pub fn permissive() -> Self {
+        Self {
+            max_script_size: usize::MAX,
+            max_push_size: usize::MAX,
+            max_tx_size: usize::MAX,
+        }
+    }
+    pub fn check_script(&self, script: &[u8]) -> Result<()> {
+        if script.len() > self.max_script_size {
+            return Err(Error::InvalidInput(format!(
+                "Script is {} bytes, policy limit is {}",
+                script.len(),
+                self.max_script_size
+            )));
+        }
+        for item in instructions(script) {
+            let (offset, instruction) = item.map_err(|e| Error::InvalidInput(format!("{:?}", e)))?;
+            if let Instruction::Push(data) = instruction {
+                if data.len() > self.max_push_size {
+                    return Err(Error::InvalidInput(format!(
+                        "Push of {} bytes at offset {} exceeds policy limit {}",
+                        data.len(),
+                        offset,
+                        self.max_push_size
+                    )));
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Default for PolicyLimits {
+    fn default() -> Self {
+        Self::default_for(&ChainParams::mainnet())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ghost::script::push_bytes;
+    #[test]
+    fn test_regtest_accepts_what_mainnet_rejects() {
+        let big_push = push_bytes(&vec![0x42; MAX_SCRIPT_SIZE]);
+        let mainnet = PolicyLimits::default_for(&ChainParams::for_network(Network::Mainnet));
+        let regtest = PolicyLimits::default_for(&ChainParams::for_network(Network::Regtest));
+        assert!(mainnet.check_script(&big_push).is_err());
+        assert!(regtest.check_script(&big_push).is_ok());
+    }
+    #[test]
+    fn test_script_size_limit() {
+        let limits = PolicyLimits { max_script_size: 10, max_push_size: 10, max_tx_size: 100 };
+        assert!(limits.check_script(&[0x51; 10]).is_ok());
+        assert!(limits.check_script(&[0x51; 11]).is_err());
+    }
+}
@@ -0,0 +1,98 @@
+//! Prelude
+//!
+//! The types an integrator needs to build outputs, witnesses and contract
+//! steps, behind one glob import. The examples use nothing else, so removing
+//! or renaming anything here breaks the doc tests rather than integrators.
+//!
+//! ```
+//! use sovereign_tokens::ghost::script::prelude::*;
+//!
+//! let output = MulletScript::builder()
+//!     .tail(EcdsaTail::from_pubkey_hash(&[0x11; 20]))
+//!     .build()
+//!     .expect("ECDSA output");
+//! assert_eq!(output.guard().guard_type(), GuardType::Universal);
+//! assert!(!output.locking_script().is_empty());
+//! ```
+//!
+//! Fee rates live on ChainParams (`default_fee_rate`, `fee_for_size`).
+#![cfg_attr(feature = "contract", doc = r#"
+The `contract` feature adds the contract types:
+
+```
+use sovereign_tokens::ghost::script::prelude::*;
+
+let contract = VerifierContract::new([0x11; 20], IPAAccumulator::new([0x22; 32]));
+let proof = IPAProofComponents {
+    l_commitments: vec![[[0x0a; 32], [0x0b; 32]]; 2],
+    r_commitments: vec![[[0x0c; 32], [0x0d; 32]]; 2],
+    a: [0x0e; 32],
+    b: Some([0x0f; 32]),
+};
+let witness = ProofGenerator::new()
+    .generate_state_transition(&contract, &proof, [0x33; 32], vec![[0x33; 32]])
+    .expect("state transition");
+let next = contract.apply_transition(&witness).expect("valid step");
+assert_eq!(next.current_state.app_state_root, [0x33; 32]);
+let output = ContractOutput::new(&contract, 100_000);
+let step = ContractTransactionBuilder::new(output, witness, [0x11; 20])
+    .finalize(&ChainParams::for_network(Network::Regtest))
+    .expect("finalized step");
+assert_eq!(step.output.value, 100_000);
+```
+"#)]
+
+pub use super::{
+    MulletScript, MulletScriptBuilder, MulletWitness, Witness, SighashPreimage, SighashPreimageBuilder,
+    Guard, GuardType, GuardConfig, UniversalGuard,
+    Tail, TailType, TailWitness, EcdsaTail, MultisigTail, LamportTail, SponsorTail,
+    DualAuthTail, HtlcTail, TimelockTail, SequenceTimelockTail, BranchTail, AnyoneCanSpendTail, CustomTail,
+    EcdsaSignature, SighashFlag, SighashPolicy, FieldElement,
+    Network, ChainParams, PolicyLimits, OutPoint, TxInput, UtxoKey,
+    BuildError, BundleError, DeserializeError,
+};
+#[cfg(feature = "contract")]
+pub use super::{
+    VerifierContract, IPAAccumulator, IPAStepWitness,
+    ContractOutput, ContractTransactionBuilder, FinalizedStep,
+    ProofGenerator, IPAProofComponents, TranscriptBuilder,
+};
+#[cfg(feature = "contract")]
+pub use super::proof_generator::ProofError;
+#[cfg(feature = "contract")]
+pub use super::verifier_contract::VerifierError;
+pub use super::vm::{VmError, VmErrorKind};
+pub use crate::ghost::{Error, Result};
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[test]
+    fn test_ecdsa_output_from_prelude() {
+        let output = MulletScript::builder()
+            .tail(EcdsaTail::from_pubkey_hash(&[0x11; 20]))
+            .build()
+            .unwrap();
+        assert_eq!(output.guard().guard_type(), GuardType::Universal);
+        assert!(!output.locking_script().is_empty());
+    }
+    #[test]
+    #[cfg(feature = "contract")]
+    fn test_contract_step_from_prelude() {
+        let contract = VerifierContract::new([0x11; 20], IPAAccumulator::new([0x22; 32]));
+        let proof = IPAProofComponents {
+            l_commitments: vec![[[0x0a; 32], [0x0b; 32]]; 2],
+            r_commitments: vec![[[0x0c; 32], [0x0d; 32]]; 2],
+            a: [0x0e; 32],
+            b: Some([0x0f; 32]),
+        };
+        let witness: IPAStepWitness = ProofGenerator::new()
+            .generate_state_transition(&contract, &proof, [0x33; 32], vec![[0x33; 32]])
+            .unwrap();
+        let next = contract.apply_transition(&witness).unwrap();
+        assert_eq!(next.current_state.app_state_root, [0x33; 32]);
+        let builder = ContractTransactionBuilder::new(ContractOutput::new(&contract, 100_000), witness, [0x11; 20]);
+        let step: FinalizedStep = builder.finalize(&ChainParams::for_network(Network::Regtest)).unwrap();
+        assert_eq!(step.output.value, 100_000);
+    }
+}
@@ -9,7 +9,7 @@
 // FLOW:
 // 1. Halo2 Prover generates: L_i, R_i, a, b (IPA components)
 // 2. ProofGenerator computes: Poseidon(transcript, L_i, R_i, ...) = new_transcript
-// 3. WitnessBuilder serializes: [constants_blob, witness_data, next_hash]
+// 3. WitnessBuilder serializes: [prev_state, witness_data, next_hash]
 // 4. Bitcoin Script verifies: re-computes hash and checks equality
 //
 // KEY INSIGHT:
@@ -18,12 +18,18 @@
 // invalid L_i/R_i would cause the next folding step to fail.
 
 use crate::ghost::script::field_script::{
-    FusedPoseidonConstants, fp_to_bytes, bytes_to_fp,
+    FusedPoseidonConstants, fp_to_bytes, bytes_to_fp, bytes_to_fp_reduced, PALLAS_MODULUS_BYTES,
 };
 use crate::ghost::script::verifier_contract::{
-    IPAStepWitness, VerifierContract, FieldElement,
+    IPAStepWitness, VerifierContract, FieldElement, frame_length, app_state_marker, operator_binding,
 };
-use crate::ghost::crypto::{Fp, PoseidonHash};
+pub use crate::ghost::script::sections::{DeserializeError, WitnessSection};
+pub(crate) use crate::ghost::script::sections::SectionReader;
+use crate::ghost::script::point::CompressedPoint;
+use crate::ghost::script::guard_engine::{poseidon_domain, squeeze_tag_bytes, TranscriptKind, SQUEEZE_TAG};
+use crate::ghost::script::vm::{decode_num, execute};
+use crate::ghost::script::{push_bytes, OP_DUP, OP_FROMALTSTACK, OP_TOALTSTACK};
+use crate::ghost::crypto::{Fp, PoseidonHash, sha256};
 use ff::Field;
 
 // ============================================================================
@@ -34,18 +40,27 @@ use ff::Field;
 /// This simulates the Fiat-Shamir transform used in Halo2
 pub struct TranscriptBuilder {
     /// Current transcript state (running hash)
-    state: Fp,
+    state: TranscriptState,
     
     /// All absorbed elements (for debugging)
     absorbed: Vec<Fp>,
 }
 
+/// Running hash behind a transcript
+#[derive(Clone, Copy, Debug)]
+enum TranscriptState {
+    /// Poseidon chain used by the verifier contract: state = H(state, x)
+    Poseidon(Fp),
+    /// SHA256 chain used by the guard's public-data transcript: state = SHA256(x || state)
+    Sha256([u8; 32]),
+}
+
 impl TranscriptBuilder {
     /// Create a new transcript with initial state
     pub fn new(initial_state: &FieldElement) -> Self {
         let state = bytes_to_fp(initial_state).unwrap_or(Fp::ZERO);
         Self {
-            state,
+            state: TranscriptState::Poseidon(state),
             absorbed: vec![state],
         }
     }
@@ -53,22 +68,38 @@ impl TranscriptBuilder {
     /// Create transcript from zero state
     pub fn new_empty() -> Self {
         Self {
-            state: Fp::ZERO,
+            state: TranscriptState::Poseidon(Fp::ZERO),
             absorbed: vec![Fp::ZERO],
         }
     }
 
-    /// Absorb a single field element into the transcript
+    /// Create a transcript that mirrors the guard's SHA256 transcript sections
+    pub fn sha256(initial_state: &[u8; 32]) -> Self {
+        Self {
+            state: TranscriptState::Sha256(*initial_state),
+            absorbed: Vec::new(),
+        }
+    }
+
+    /// Absorb a single field element into the transcript. An encoding >= p
+    /// is reduced mod p, as the Poseidon absorb section does.
     pub fn absorb(&mut self, element: &FieldElement) {
-        let fp = bytes_to_fp(element).unwrap_or(Fp::ZERO);
-        self.state = PoseidonHash::hash(self.state, fp);
+        let fp = bytes_to_fp_reduced(element);
+        self.state = match self.state {
+            TranscriptState::Poseidon(state) => TranscriptState::Poseidon(PoseidonHash::hash(state, fp)),
+            TranscriptState::Sha256(state) => TranscriptState::Sha256(sha256_chain(element, &state)),
+        };
         self.absorbed.push(fp);
     }
 
     /// Absorb a field element directly
     pub fn absorb_fp(&mut self, element: Fp) {
-        self.state = PoseidonHash::hash(self.state, element);
-        self.absorbed.push(element);
+        self.absorb(&fp_to_bytes(&element));
+    }
+
+    /// Absorb a section length prefix (see `frame_length`)
+    pub fn absorb_length(&mut self, len: usize) {
+        self.absorb(&frame_length(len));
     }
 
     /// Absorb multiple elements
@@ -92,12 +123,37 @@ impl TranscriptBuilder {
 
     /// Squeeze a challenge from the transcript
     pub fn squeeze(&self) -> Fp {
-        self.state
+        match self.state {
+            TranscriptState::Poseidon(state) => state,
+            // Clear the top two bits so the digest is always below p
+            TranscriptState::Sha256(mut state) => {
+                state[31] &= 0x3f;
+                bytes_to_fp(&state).unwrap_or(Fp::ZERO)
+            }
+        }
+    }
+
+    /// Squeeze one round challenge and advance the transcript.
+    /// Returns the pre-squeeze state, which is what the guard leaves on the
+    /// main stack; the state then becomes H(state || "squeeze").
+    pub fn squeeze_challenge(&mut self) -> FieldElement {
+        let challenge = self.state_bytes();
+        self.state = match self.state {
+            TranscriptState::Poseidon(state) => {
+                let tag = bytes_to_fp(&squeeze_tag_bytes()).unwrap_or(Fp::ZERO);
+                TranscriptState::Poseidon(PoseidonHash::hash(state, tag))
+            }
+            TranscriptState::Sha256(state) => TranscriptState::Sha256(sha256_chain(&state, SQUEEZE_TAG)),
+        };
+        challenge
     }
 
     /// Get current state as bytes
     pub fn state_bytes(&self) -> FieldElement {
-        fp_to_bytes(&self.state)
+        match self.state {
+            TranscriptState::Poseidon(state) => fp_to_bytes(&state),
+            TranscriptState::Sha256(state) => state,
+        }
     }
 
     /// Get number of absorbed elements
@@ -106,6 +162,189 @@ impl TranscriptBuilder {
     }
 }
 
+/// SHA256(head || tail), the guard's OP_CAT OP_SHA256 step
+fn sha256_chain(head: &[u8], tail: &[u8]) -> [u8; 32] {
+    let mut data = Vec::with_capacity(head.len() + tail.len());
+    data.extend_from_slice(head);
+    data.extend_from_slice(tail);
+    sha256(&data)
+}
+
+
+// ============================================================================
+// TRANSCRIPT CONFORMANCE
+// ============================================================================
+
+/// One operation of a conformance vector, with the transcript state the
+/// Rust `TranscriptBuilder` reached after it
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ConformanceStep {
+    Absorb { element: FieldElement, state: FieldElement },
+    /// `challenge` is the pre-squeeze state `squeeze_challenge` returns
+    Squeeze { challenge: FieldElement, state: FieldElement },
+}
+
+/// Fixture absorption sequence for checking a transcript implementation
+/// against `TranscriptBuilder`. States are the builder's byte encoding: the
+/// raw digest for SHA256, `fp_to_bytes` for Poseidon.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ConformanceVector {
+    pub name: &'static str,
+    pub kind: TranscriptKind,
+    pub initial_state: FieldElement,
+    pub steps: Vec<ConformanceStep>,
+}
+
+/// Fixture operation before the builder has run it
+enum FixtureOp {
+    Absorb(FieldElement),
+    Squeeze,
+}
+
+type Fixture = (&'static str, fn() -> Vec<FixtureOp>);
+
+impl ConformanceVector {
+    fn record(name: &'static str, kind: TranscriptKind, ops: Vec<FixtureOp>) -> Self {
+        let initial_state: FieldElement = match kind {
+            TranscriptKind::Sha256 => kind.initial_state().try_into().expect("SHA256 state is 32 bytes"),
+            TranscriptKind::Poseidon => fp_to_bytes(&poseidon_domain()),
+        };
+        let mut transcript = match kind {
+            TranscriptKind::Sha256 => TranscriptBuilder::sha256(&initial_state),
+            TranscriptKind::Poseidon => TranscriptBuilder::new(&initial_state),
+        };
+        let steps = ops
+            .into_iter()
+            .map(|op| match op {
+                FixtureOp::Absorb(element) => {
+                    transcript.absorb(&element);
+                    ConformanceStep::Absorb { element, state: transcript.state_bytes() }
+                }
+                FixtureOp::Squeeze => {
+                    let challenge = transcript.squeeze_challenge();
+                    ConformanceStep::Squeeze { challenge, state: transcript.state_bytes() }
+                }
+            })
+            .collect();
+        Self { name, kind, initial_state, steps }
+    }
+
+    /// The on-chain transcript sections for this vector. After every step
+    /// the state is copied to the main stack, preceded by the challenge for
+    /// a squeeze. Also returns the byte offset at which each step starts.
+    pub fn script(&self) -> (Vec<u8>, Vec<usize>) {
+        let mut script = push_bytes(&self.initial_state);
+        script.push(OP_TOALTSTACK);
+        let mut offsets = Vec::with_capacity(self.steps.len());
+        for step in &self.steps {
+            offsets.push(script.len());
+            match step {
+                ConformanceStep::Absorb { element, .. } => {
+                    script.extend(push_bytes(element));
+                    script.extend(self.kind.absorb());
+                }
+                ConformanceStep::Squeeze { .. } => script.extend(self.kind.squeeze()),
+            }
+            script.extend([OP_FROMALTSTACK, OP_DUP, OP_TOALTSTACK]);
+        }
+        (script, offsets)
+    }
+
+    /// Index of the first step where the script's state (or challenge)
+    /// differs from the recorded one, or where the script fails; `None`
+    /// when every step agrees
+    pub fn first_divergence(&self) -> Option<usize> {
+        let (script, offsets) = self.script();
+        let stack = match execute(&script) {
+            Ok(stack) => stack,
+            Err(err) => return Some(offsets.iter().rposition(|&start| start <= err.offset).unwrap_or(0)),
+        };
+        let mut items = stack.iter();
+        for (i, step) in self.steps.iter().enumerate() {
+            let expected: &[&FieldElement] = match step {
+                ConformanceStep::Absorb { state, .. } => &[state],
+                ConformanceStep::Squeeze { challenge, state } => &[challenge, state],
+            };
+            for want in expected {
+                match items.next() {
+                    Some(got) if self.same_state(got, want) => {}
+                    _ => return Some(i),
+                }
+            }
+        }
+        None
+    }
+
+    // Poseidon states are script numbers on-chain and LE field bytes here
+    fn same_state(&self, got: &[u8], want: &FieldElement) -> bool {
+        match self.kind {
+            TranscriptKind::Sha256 => got == want,
+            TranscriptKind::Poseidon => decode_num(got) == decode_num(want),
+        }
+    }
+}
+
+impl TranscriptBuilder {
+    /// Fixture sequences for both transcript kinds: plain field elements
+    /// (including 0 and p - 1), affine points, a framed byte string and
+    /// interleaved squeezes. Other transcript implementations can replay
+    /// them and compare states step by step.
+    pub fn conformance_vectors() -> Vec<ConformanceVector> {
+        let fixtures: [Fixture; 5] = [
+            ("field_elements", || {
+                [Fp::ZERO, Fp::ONE, Fp::from(42u64), -Fp::ONE]
+                    .iter()
+                    .map(|fp| FixtureOp::Absorb(fp_to_bytes(fp)))
+                    .chain([FixtureOp::Squeeze])
+                    .collect()
+            }),
+            ("points", || {
+                let points = [[[0x01; 32], [0x02; 32]], [[0x3f; 32], [0x11; 32]]];
+                points
+                    .iter()
+                    .flat_map(|[x, y]| [FixtureOp::Absorb(*x), FixtureOp::Absorb(*y)])
+                    .chain([FixtureOp::Squeeze, FixtureOp::Squeeze])
+                    .collect()
+            }),
+            ("framed_bytes", || {
+                // 31-byte chunks, zero-padded, so every element is below p
+                let bytes = b"Halo2 GHOST transcript conformance: framed byte string fixture";
+                let mut ops = vec![FixtureOp::Absorb(frame_length(bytes.len()))];
+                ops.extend(bytes.chunks(31).map(|chunk| {
+                    let mut element = [0u8; 32];
+                    element[..chunk.len()].copy_from_slice(chunk);
+                    FixtureOp::Absorb(element)
+                }));
+                ops.push(FixtureOp::Squeeze);
+                ops
+            }),
+            ("interleaved", || {
+                vec![
+                    FixtureOp::Absorb(frame_length(1)),
+                    FixtureOp::Squeeze,
+                    FixtureOp::Absorb(squeeze_tag_bytes()),
+                    FixtureOp::Squeeze,
+                    FixtureOp::Squeeze,
+                ]
+            }),
+            ("non_canonical", || {
+                // p, p + 1 and 2^256 - 1 absorb as 0, 1 and their residue
+                let mut p_plus_one = PALLAS_MODULUS_BYTES;
+                p_plus_one[0] += 1;
+                [PALLAS_MODULUS_BYTES, p_plus_one, [0xff; 32]]
+                    .into_iter()
+                    .map(FixtureOp::Absorb)
+                    .chain([FixtureOp::Squeeze])
+                    .collect()
+            }),
+        ];
+        [TranscriptKind::Sha256, TranscriptKind::Poseidon]
+            .into_iter()
+            .flat_map(|kind| fixtures.iter().map(move |(name, ops)| ConformanceVector::record(name, kind, ops())))
+            .collect()
+    }
+}
+
 // ============================================================================
 // IPA PROOF COMPONENTS
 // ============================================================================
@@ -166,23 +405,140 @@ impl IPAProofComponents {
     }
 }
 
+// ============================================================================
+// HALO2 TRANSCRIPT IMPORT
+// ============================================================================
+
+/// Bytes per point or scalar in a Halo2 `Blake2bWrite` buffer
+const HALO2_ELEMENT_BYTES: usize = 32;
+
+/// Read the IPA opening out of a Halo2 `Blake2bWrite` proof buffer for a
+/// circuit of size `2^k`, pairing it with the circuit's public inputs.
+///
+/// Halo2 does not write instances into the proof bytes (they are common
+/// inputs the verifier already holds), so they are passed in separately and
+/// only checked for canonical encoding. Points are Pallas (`Params<EpAffine>`),
+/// matching the transcript's `Fp` coordinates. Read sequence:
+/// 1. the PLONK body (advice, lookup, permutation and vanishing commitments,
+///    evaluations, multiopen); circuit-specific, skipped
+/// 2. the IPA opening, which always ends the buffer (`ipa::create_proof`):
+///    - `s_poly` commitment (`write_point`), skipped
+///    - `k` rounds of `write_point(L_j)`, `write_point(R_j)`
+///    - `write_scalar(c)` → `a`, then `write_scalar(f)` (the blind) → `b`
+///
+/// Points use Halo2's 32-byte encoding (see `CompressedPoint::from_halo2_bytes`)
+/// and are decompressed to the affine `[x, y]` pairs the transcript absorbs.
+pub fn parse_blake2b_transcript(
+    bytes: &[u8],
+    k: usize,
+    instances: &[FieldElement],
+) -> Result<(Vec<FieldElement>, IPAProofComponents), ProofError> {
+    let opening_len = k
+        .checked_mul(2)
+        .and_then(|points| points.checked_add(3))
+        .and_then(|n| n.checked_mul(HALO2_ELEMENT_BYTES))
+        .ok_or(ProofError::InvalidProofStructure)?;
+    let opening_start = bytes.len().checked_sub(opening_len).ok_or(ProofError::SerializationError)?;
+    for instance in instances {
+        bytes_to_fp_checked(instance)?;
+    }
+
+    let element = |offset: usize| -> FieldElement {
+        bytes[offset..offset + HALO2_ELEMENT_BYTES].try_into().expect("32-byte element")
+    };
+    let scalar = |offset: usize| -> Result<FieldElement, ProofError> {
+        let value = element(offset);
+        bytes_to_fp_checked(&value)?;
+        Ok(value)
+    };
+    let point = |offset: usize| -> Result<[FieldElement; 2], ProofError> {
+        let (x, y) = CompressedPoint::from_halo2_bytes(&element(offset))
+            .to_affine()
+            .ok_or(ProofError::InvalidProofStructure)?;
+        Ok([fp_to_bytes(&x), fp_to_bytes(&y)])
+    };
+
+    // Skip the s_poly commitment
+    let mut offset = opening_start + HALO2_ELEMENT_BYTES;
+    let mut l_commitments = Vec::with_capacity(k);
+    let mut r_commitments = Vec::with_capacity(k);
+    for _ in 0..k {
+        l_commitments.push(point(offset)?);
+        r_commitments.push(point(offset + HALO2_ELEMENT_BYTES)?);
+        offset += 2 * HALO2_ELEMENT_BYTES;
+    }
+    let a = scalar(offset)?;
+    let b = scalar(offset + HALO2_ELEMENT_BYTES)?;
+
+    let proof = IPAProofComponents { l_commitments, r_commitments, a, b: Some(b) };
+    Ok((instances.to_vec(), proof))
+}
+
 // ============================================================================
 // PROOF GENERATOR
 // ============================================================================
 
+/// Default upper bound on public inputs per step
+pub const DEFAULT_MAX_PUBLIC_INPUTS: usize = 64;
+
 /// Generates Bitcoin script witnesses from Halo2 proofs
 pub struct ProofGenerator {
     /// Fused constants for Poseidon
     pub constants: FusedPoseidonConstants,
+
+    /// Largest accepted public input count per step
+    pub max_public_inputs: usize,
+
+    /// Reject repeated public inputs (for nullifier-like inputs)
+    pub unique_public_inputs: bool,
+
+    /// Operator binding absorbed first by `generate_ipa_witness`; unset
+    /// until `with_operator_binding`, and required by it
+    pub operator_binding: Option<FieldElement>,
 }
 
 impl ProofGenerator {
     pub fn new() -> Self {
         Self {
             constants: FusedPoseidonConstants::compute(),
+            max_public_inputs: DEFAULT_MAX_PUBLIC_INPUTS,
+            unique_public_inputs: false,
+            operator_binding: None,
         }
     }
 
+    pub fn with_max_public_inputs(mut self, max: usize) -> Self {
+        self.max_public_inputs = max;
+        self
+    }
+
+    /// Treat public inputs as nullifiers: duplicates are rejected
+    pub fn with_unique_public_inputs(mut self, unique: bool) -> Self {
+        self.unique_public_inputs = unique;
+        self
+    }
+
+    /// Bind generated witnesses to an operator (see `operator_binding`)
+    pub fn with_operator_binding(mut self, binding: FieldElement) -> Self {
+        self.operator_binding = Some(binding);
+        self
+    }
+
+    /// Check the public input count is within `1..=max_public_inputs` and,
+    /// when configured, that no input repeats
+    fn validate_public_inputs(&self, public_inputs: &[FieldElement]) -> Result<(), ProofError> {
+        if public_inputs.is_empty() || public_inputs.len() > self.max_public_inputs {
+            return Err(ProofError::PublicInputCount);
+        }
+        if self.unique_public_inputs {
+            let mut seen = std::collections::HashSet::with_capacity(public_inputs.len());
+            if !public_inputs.iter().all(|pi| seen.insert(pi)) {
+                return Err(ProofError::DuplicatePublicInput);
+            }
+        }
+        Ok(())
+    }
+
     /// Generate a witness for an IPA step
     /// 
     /// This is the main entry point. It takes:
@@ -191,23 +547,47 @@ impl ProofGenerator {
     /// - The IPA proof components
     /// - Optional new application state
     /// 
-    /// And produces a witness that the Bitcoin script can verify.
+    /// And produces a witness that the Bitcoin script can verify, bound to
+    /// `self.operator_binding`; `MissingOperatorBinding` if none was set.
     pub fn generate_ipa_witness(
         &self,
         current_transcript: &FieldElement,
         public_inputs: Vec<FieldElement>,
         proof: &IPAProofComponents,
         new_app_state: Option<FieldElement>,
+    ) -> Result<IPAStepWitness, ProofError> {
+        let binding = self.operator_binding.ok_or(ProofError::MissingOperatorBinding)?;
+        self.generate_bound_witness(&binding, current_transcript, public_inputs, proof, new_app_state)
+    }
+
+    fn generate_bound_witness(
+        &self,
+        operator_binding: &FieldElement,
+        current_transcript: &FieldElement,
+        public_inputs: Vec<FieldElement>,
+        proof: &IPAProofComponents,
+        new_app_state: Option<FieldElement>,
     ) -> Result<IPAStepWitness, ProofError> {
         proof.validate()?;
+        canonicalize(&public_inputs, proof)?;
+        bytes_to_fp_checked(operator_binding)?;
+        if let Some(app_state) = &new_app_state {
+            bytes_to_fp_checked(app_state)?;
+        }
+        self.validate_public_inputs(&public_inputs)?;
 
         // Build the transcript
         let mut transcript = TranscriptBuilder::new(current_transcript);
 
-        // Absorb public inputs
+        // Absorb the operator binding first, so the step is tied to one operator
+        transcript.absorb(operator_binding);
+
+        // Absorb public inputs, framed by their count
+        transcript.absorb_length(public_inputs.len());
         transcript.absorb_many(&public_inputs);
 
-        // Absorb L/R terms (interleaved)
+        // Absorb L/R terms (interleaved), framed by the round count
+        transcript.absorb_length(proof.l_commitments.len());
         transcript.absorb_lr_terms(&proof.l_commitments, &proof.r_commitments);
 
         // Absorb final scalars
@@ -216,10 +596,17 @@ impl ProofGenerator {
             transcript.absorb(b);
         }
 
+        // Absorb the new app state behind its marker, so the root is bound
+        if let Some(app_state) = &new_app_state {
+            transcript.absorb(&app_state_marker());
+            transcript.absorb(app_state);
+        }
+
         // Compute the new transcript hash
         let next_transcript_hash = transcript.state_bytes();
 
         Ok(IPAStepWitness {
+            operator_binding: *operator_binding,
             public_inputs,
             l_terms: proof.l_commitments.clone(),
             r_terms: proof.r_commitments.clone(),
@@ -232,7 +619,8 @@ impl ProofGenerator {
 
     /// Generate a witness for a state transition (application-level)
     /// 
-    /// This wraps generate_ipa_witness with additional application logic
+    /// This wraps generate_ipa_witness with additional application logic;
+    /// the witness is bound to the contract's operator
     pub fn generate_state_transition(
         &self,
         contract: &VerifierContract,
@@ -240,7 +628,8 @@ impl ProofGenerator {
         new_app_state: FieldElement,
         public_inputs: Vec<FieldElement>,
     ) -> Result<IPAStepWitness, ProofError> {
-        self.generate_ipa_witness(
+        self.generate_bound_witness(
+            &contract.operator_binding(),
             &contract.current_state.transcript_hash,
             public_inputs,
             proof,
@@ -260,6 +649,30 @@ impl Default for ProofGenerator {
     }
 }
 
+// ============================================================================
+// CANONICAL ENCODING
+// ============================================================================
+
+/// Decode a field element, rejecting any encoding >= p.
+///
+/// `bytes_to_fp(..).unwrap_or(Fp::ZERO)` silently maps non-canonical values to
+/// zero off-chain, while the script's canonical check rejects them on-chain.
+pub fn bytes_to_fp_checked(bytes: &FieldElement) -> Result<Fp, ProofError> {
+    bytes_to_fp(bytes).ok_or(ProofError::NonCanonicalScalar)
+}
+
+/// Verify every value absorbed into the transcript is canonically encoded
+fn canonicalize(public_inputs: &[FieldElement], proof: &IPAProofComponents) -> Result<(), ProofError> {
+    for pi in public_inputs {
+        bytes_to_fp_checked(pi)?;
+    }
+    bytes_to_fp_checked(&proof.a)?;
+    if let Some(b) = &proof.b {
+        bytes_to_fp_checked(b)?;
+    }
+    Ok(())
+}
+
 // ============================================================================
 // WITNESS SERIALIZER
 // ============================================================================
@@ -272,6 +685,9 @@ impl WitnessSerializer {
     pub fn serialize(witness: &IPAStepWitness) -> Vec<u8> {
         let mut bytes = Vec::new();
 
+        // Operator binding
+        bytes.extend_from_slice(&witness.operator_binding);
+
         // Public inputs
         for pi in &witness.public_inputs {
             bytes.extend_from_slice(pi);
@@ -304,67 +720,42 @@ impl WitnessSerializer {
         bytes
     }
 
-    /// Deserialize witness from bytes
-    pub fn deserialize(bytes: &[u8], num_public_inputs: usize, num_rounds: usize, has_b: bool, has_app_state: bool) -> Option<IPAStepWitness> {
-        let mut offset = 0;
+    /// Deserialize witness from bytes; trailing bytes are ignored
+    pub fn deserialize(
+        bytes: &[u8],
+        num_public_inputs: usize,
+        num_rounds: usize,
+        has_b: bool,
+        has_app_state: bool,
+    ) -> Result<IPAStepWitness, DeserializeError> {
+        let mut reader = SectionReader::new(bytes);
+
+        let operator_binding = reader.element(WitnessSection::OperatorBinding)?;
 
         // Public inputs
-        let mut public_inputs = Vec::with_capacity(num_public_inputs);
-        for _ in 0..num_public_inputs {
-            if offset + 32 > bytes.len() { return None; }
-            let elem: FieldElement = bytes[offset..offset+32].try_into().ok()?;
-            public_inputs.push(elem);
-            offset += 32;
-        }
+        let public_inputs = reader
+            .take(WitnessSection::PublicInputs, num_public_inputs.saturating_mul(32))?
+            .chunks(32)
+            .map(|c| c.try_into().expect("32-byte chunk"))
+            .collect();
 
-        // L and R terms
+        // L and R terms: L(x, y) then R(x, y), 128 bytes per round
         let mut l_terms = Vec::with_capacity(num_rounds);
         let mut r_terms = Vec::with_capacity(num_rounds);
-        for _ in 0..num_rounds {
-            // Each round has L(x,y) and R(x,y). 4 elements = 128 bytes.
-            if offset + 128 > bytes.len() { return None; }
-            
-            let lx: FieldElement = bytes[offset..offset+32].try_into().ok()?;
-            let ly: FieldElement = bytes[offset+32..offset+64].try_into().ok()?;
-            
-            let rx: FieldElement = bytes[offset+64..offset+96].try_into().ok()?;
-            let ry: FieldElement = bytes[offset+96..offset+128].try_into().ok()?;
-            
-            l_terms.push([lx, ly]);
-            r_terms.push([rx, ry]);
-            offset += 128;
+        for round in 0..num_rounds {
+            let term = reader.take(WitnessSection::LRTerm { round }, 128)?;
+            let elem = |i: usize| -> FieldElement { term[i * 32..(i + 1) * 32].try_into().expect("32-byte slice") };
+            l_terms.push([elem(0), elem(1)]);
+            r_terms.push([elem(2), elem(3)]);
         }
 
-        // a_scalar
-        if offset + 32 > bytes.len() { return None; }
-        let a_scalar: FieldElement = bytes[offset..offset+32].try_into().ok()?;
-        offset += 32;
-
-        // b_scalar (optional)
-        let b_scalar = if has_b {
-            if offset + 32 > bytes.len() { return None; }
-            let b: FieldElement = bytes[offset..offset+32].try_into().ok()?;
-            offset += 32;
-            Some(b)
-        } else {
-            None
-        };
-
-        // new_app_state (optional)
-        let new_app_state = if has_app_state {
-            if offset + 32 > bytes.len() { return None; }
-            let state: FieldElement = bytes[offset..offset+32].try_into().ok()?;
-            offset += 32;
-            Some(state)
-        } else {
-            None
-        };
-
-        // next_transcript_hash
-        if offset + 32 > bytes.len() { return None; }
-        let next_transcript_hash: FieldElement = bytes[offset..offset+32].try_into().ok()?;
+        let a_scalar = reader.element(WitnessSection::AScalar)?;
+        let b_scalar = has_b.then(|| reader.element(WitnessSection::BScalar)).transpose()?;
+        let new_app_state = has_app_state.then(|| reader.element(WitnessSection::AppState)).transpose()?;
+        let next_transcript_hash = reader.element(WitnessSection::NextHash)?;
 
-        Some(IPAStepWitness {
+        Ok(IPAStepWitness {
+            operator_binding,
             public_inputs,
             l_terms,
             r_terms,
@@ -374,6 +765,17 @@ impl WitnessSerializer {
             next_transcript_hash,
         })
     }
+
+    /// `deserialize` without the failure details
+    pub fn deserialize_opt(
+        bytes: &[u8],
+        num_public_inputs: usize,
+        num_rounds: usize,
+        has_b: bool,
+        has_app_state: bool,
+    ) -> Option<IPAStepWitness> {
+        Self::deserialize(bytes, num_public_inputs, num_rounds, has_b, has_app_state).ok()
+    }
 }
 
 // ============================================================================
@@ -386,6 +788,13 @@ pub enum ProofError {
     InvalidProofStructure,
     TranscriptMismatch,
     SerializationError,
+    NonCanonicalScalar,
+    /// Public input count is zero or above the configured maximum
+    PublicInputCount,
+    /// A public input repeats while inputs are marked unique
+    DuplicatePublicInput,
+    /// `generate_ipa_witness` was called before `with_operator_binding`
+    MissingOperatorBinding,
 }
 
 // ============================================================================
@@ -399,8 +808,24 @@ pub fn generate_mock_proof(
     num_rounds: usize,
     public_inputs: Vec<FieldElement>,
 ) -> IPAStepWitness {
-    let generator = ProofGenerator::new();
+    generate_mock_proof_with_state(prev_transcript, num_rounds, public_inputs, None)
+}
+
+/// `generate_mock_proof` that also carries (and absorbs) a new app state
+pub fn generate_mock_proof_with_state(
+    prev_transcript: &FieldElement,
+    num_rounds: usize,
+    public_inputs: Vec<FieldElement>,
+    new_app_state: Option<FieldElement>,
+) -> IPAStepWitness {
+    ProofGenerator::new()
+        .with_operator_binding(operator_binding(&[0u8; 20]))
+        .generate_ipa_witness(prev_transcript, public_inputs, &mock_proof_components(num_rounds), new_app_state)
+        .expect("Mock proof generation should not fail")
+}
 
+/// Mock L/R terms and final scalars for `num_rounds` rounds
+fn mock_proof_components(num_rounds: usize) -> IPAProofComponents {
     // Create mock L/R terms (Affine points)
     let l_terms: Vec<[FieldElement; 2]> = (0..num_rounds)
         .map(|i| {
@@ -427,19 +852,15 @@ pub fn generate_mock_proof(
     let a_scalar = [0x0A; 32];
     let b_scalar = Some([0x0B; 32]);
 
-    let proof = IPAProofComponents {
+    IPAProofComponents {
         l_commitments: l_terms,
         r_commitments: r_terms,
         a: a_scalar,
         b: b_scalar,
-    };
-
-    generator
-        .generate_ipa_witness(prev_transcript, public_inputs, &proof, None)
-        .expect("Mock proof generation should not fail")
+    }
 }
 
-/// Generate a valid state transition for testing
+/// Generate a valid state transition for testing, bound to the contract's operator
 pub fn generate_mock_state_transition(
     contract: &VerifierContract,
     new_app_state: FieldElement,
@@ -448,11 +869,14 @@ pub fn generate_mock_state_transition(
         new_app_state,  // The new state is a public input
     ];
 
-    generate_mock_proof(
-        &contract.current_state.transcript_hash,
-        10,  // 10 rounds typical for IPA
-        public_inputs,
-    )
+    ProofGenerator::new()
+        .generate_state_transition(
+            contract,
+            &mock_proof_components(10),  // 10 rounds typical for IPA
+            new_app_state,
+            public_inputs,
+        )
+        .expect("Mock proof generation should not fail")
 }
 
 // ============================================================================
@@ -461,7 +885,7 @@ pub fn generate_mock_state_transition(
 
 /// Analyze witness sizes for different configurations
 pub fn analyze_witness_sizes() -> WitnessSizeReport {
-    let generator = ProofGenerator::new();
+    let generator = ProofGenerator::new().with_operator_binding(operator_binding(&[0u8; 20]));
 
     // Small proof (5 rounds, 1 public input)
     let small_proof = IPAProofComponents {
@@ -511,3 +935,326 @@ pub struct WitnessSizeReport {
     pub large: usize,   // 15 rounds, 4 PI
     pub constants_blob: usize,
 }
+
+// ============================================================================
+// TESTS
+// ============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Frozen transcript vector: a representative step absorbing the operator
+    /// binding, 2 public inputs, 3 L/R rounds, a and b after `prev`. Elements
+    /// are repeated bytes below 0x40, so all are canonical.
+    ///
+    /// `sha256_state` was generated once outside the crate with Python's
+    /// hashlib, folding state = SHA256(element || state) over the 19 framed
+    /// elements in absorption order, and pins the order and framing shared by
+    /// both transcript kinds. `poseidon_state` was recorded from
+    /// `ProofGenerator` and pins `PoseidonHash` and its round constants, which
+    /// live in `ghost::crypto` and cannot be reproduced outside the crate.
+    struct TranscriptVector {
+        prev: FieldElement,
+        operator_pkh: [u8; 20],
+        public_inputs: [FieldElement; 2],
+        l_terms: [[FieldElement; 2]; 3],
+        r_terms: [[FieldElement; 2]; 3],
+        a: FieldElement,
+        b: FieldElement,
+        absorbed: usize,
+        sha256_state: &'static str,
+        poseidon_state: &'static str,
+    }
+
+    const TRANSCRIPT_VECTOR: TranscriptVector = TranscriptVector {
+        prev: [0x01; 32],
+        operator_pkh: [0x11; 20],
+        public_inputs: [[0x21; 32], [0x22; 32]],
+        l_terms: [[[0x30; 32], [0x34; 32]], [[0x31; 32], [0x35; 32]], [[0x32; 32], [0x36; 32]]],
+        r_terms: [[[0x38; 32], [0x3c; 32]], [[0x39; 32], [0x3d; 32]], [[0x3a; 32], [0x3e; 32]]],
+        a: [0x0a; 32],
+        b: [0x0b; 32],
+        absorbed: 19,
+        sha256_state: "d05c90712f1798abc294be9e95eb4a02ed4e6dc5c698c3c658af2bd5e614192a",
+        poseidon_state: "239055d84d1ca3c6ffb61657e6117669d92578b0812ddb0891c11e8d14d20230",
+    };
+
+    fn absorb_vector(transcript: &mut TranscriptBuilder) {
+        let v = &TRANSCRIPT_VECTOR;
+        transcript.absorb(&operator_binding(&v.operator_pkh));
+        transcript.absorb_length(v.public_inputs.len());
+        transcript.absorb_many(&v.public_inputs);
+        transcript.absorb_length(v.l_terms.len());
+        transcript.absorb_lr_terms(&v.l_terms, &v.r_terms);
+        transcript.absorb(&v.a);
+        transcript.absorb(&v.b);
+    }
+
+    fn generator() -> ProofGenerator {
+        ProofGenerator::new().with_operator_binding(operator_binding(&[0u8; 20]))
+    }
+
+    fn small_proof() -> IPAProofComponents {
+        IPAProofComponents {
+            l_commitments: vec![[[1u8; 32], [2u8; 32]]; 2],
+            r_commitments: vec![[[3u8; 32], [4u8; 32]]; 2],
+            a: [0x0A; 32],
+            b: Some([0x0B; 32]),
+        }
+    }
+
+    #[test]
+    fn test_conformance_vectors_match_script_sections() {
+        let vectors = TranscriptBuilder::conformance_vectors();
+        assert_eq!(vectors.len(), 10);
+        for vector in &vectors {
+            assert_eq!(vector.first_divergence(), None, "{:?}/{}", vector.kind, vector.name);
+        }
+        // p absorbs as 0, as the script's reduction leaves it
+        let absorbed = |element: &FieldElement| {
+            let mut transcript = TranscriptBuilder::new(&[0x01; 32]);
+            transcript.absorb(element);
+            transcript.state_bytes()
+        };
+        assert_eq!(absorbed(&PALLAS_MODULUS_BYTES), absorbed(&[0u8; 32]));
+        assert_ne!(absorbed(&[0xff; 32]), absorbed(&[0u8; 32]));
+    }
+
+    #[test]
+    fn test_conformance_initial_states_match_init() {
+        for vector in TranscriptBuilder::conformance_vectors() {
+            let initial = vector.kind.initial_state();
+            assert_eq!(decode_num(&initial), decode_num(&vector.initial_state));
+        }
+    }
+
+    #[test]
+    fn test_conformance_reports_first_divergent_step() {
+        for vector in TranscriptBuilder::conformance_vectors() {
+            // Wrong recorded state at step 2
+            let mut wrong_state = vector.clone();
+            match &mut wrong_state.steps[2] {
+                ConformanceStep::Absorb { state, .. } | ConformanceStep::Squeeze { state, .. } => state[0] ^= 0x01,
+            }
+            assert_eq!(wrong_state.first_divergence(), Some(2), "{:?}/{}", vector.kind, vector.name);
+        }
+        // A different element at step 1 moves every later state
+        let mut wrong_element = TranscriptBuilder::conformance_vectors().remove(0);
+        match &mut wrong_element.steps[1] {
+            ConformanceStep::Absorb { element, .. } => element[0] ^= 0x01,
+            ConformanceStep::Squeeze { .. } => unreachable!("field_elements absorbs first"),
+        }
+        assert_eq!(wrong_element.first_divergence(), Some(1));
+    }
+
+    #[test]
+    fn test_operator_binding_required() {
+        let result = ProofGenerator::new().generate_ipa_witness(&[0u8; 32], vec![[5u8; 32]], &small_proof(), None);
+        assert!(matches!(result, Err(ProofError::MissingOperatorBinding)));
+        let witness = generator().generate_ipa_witness(&[0u8; 32], vec![[5u8; 32]], &small_proof(), None).unwrap();
+        assert_eq!(witness.operator_binding, operator_binding(&[0u8; 20]));
+    }
+
+    #[test]
+    fn test_canonical_scalars_accepted() {
+        let generator = generator();
+        let witness = generator.generate_ipa_witness(&[0u8; 32], vec![[5u8; 32]], &small_proof(), None);
+        assert!(witness.is_ok());
+    }
+
+    #[test]
+    fn test_non_canonical_a_rejected() {
+        let generator = generator();
+        let mut proof = small_proof();
+        proof.a = PALLAS_MODULUS_BYTES;
+        let result = generator.generate_ipa_witness(&[0u8; 32], vec![], &proof, None);
+        assert!(matches!(result, Err(ProofError::NonCanonicalScalar)));
+    }
+
+    #[test]
+    fn test_non_canonical_b_rejected() {
+        let generator = generator();
+        let mut proof = small_proof();
+        proof.b = Some(PALLAS_MODULUS_BYTES);
+        let result = generator.generate_ipa_witness(&[0u8; 32], vec![], &proof, None);
+        assert!(matches!(result, Err(ProofError::NonCanonicalScalar)));
+    }
+
+    #[test]
+    fn test_non_canonical_public_input_rejected() {
+        let generator = generator();
+        let result = generator.generate_ipa_witness(
+            &[0u8; 32],
+            vec![[1u8; 32], PALLAS_MODULUS_BYTES],
+            &small_proof(),
+            None,
+        );
+        assert!(matches!(result, Err(ProofError::NonCanonicalScalar)));
+    }
+
+    #[test]
+    fn test_public_input_count_bounds() {
+        let generator = generator().with_max_public_inputs(2);
+        let empty = generator.generate_ipa_witness(&[0u8; 32], vec![], &small_proof(), None);
+        assert!(matches!(empty, Err(ProofError::PublicInputCount)));
+        let too_many = generator.generate_ipa_witness(&[0u8; 32], vec![[1u8; 32]; 3], &small_proof(), None);
+        assert!(matches!(too_many, Err(ProofError::PublicInputCount)));
+        assert!(generator.generate_ipa_witness(&[0u8; 32], vec![[1u8; 32]; 2], &small_proof(), None).is_ok());
+    }
+
+    #[test]
+    fn test_duplicate_public_inputs_when_unique() {
+        let inputs = vec![[1u8; 32], [2u8; 32], [1u8; 32]];
+        let lenient = generator();
+        assert!(lenient.generate_ipa_witness(&[0u8; 32], inputs.clone(), &small_proof(), None).is_ok());
+        let strict = generator().with_unique_public_inputs(true);
+        let result = strict.generate_ipa_witness(&[0u8; 32], inputs, &small_proof(), None);
+        assert!(matches!(result, Err(ProofError::DuplicatePublicInput)));
+    }
+
+    #[test]
+    fn test_framed_witness_verifies() {
+        let generator = generator();
+        let prev = [0x07; 32];
+        let witness = generator
+            .generate_ipa_witness(&prev, vec![[5u8; 32], [6u8; 32]], &small_proof(), None)
+            .unwrap();
+        assert!(generator.verify_witness(&witness, &prev));
+        assert!(!generator.verify_witness(&witness, &[0x08; 32]));
+    }
+
+    /// Proof bytes captured from halo2_proofs 0.1.0 `Blake2bWrite` with
+    /// `Challenge255`: an `a * b = c` gate over `Fq` with `Params<EpAffine>`
+    /// at k = 4, public inputs `[2, 3, 6]`, checked with `verify_proof`
+    /// before it was written out
+    fn halo2_mul_proof() -> Vec<u8> {
+        let hex_file = include_str!("testdata/halo2/mul_k4_proof.hex");
+        hex::decode(hex_file.split_whitespace().collect::<String>()).expect("fixture is hex")
+    }
+    fn halo2_mul_instances() -> Vec<FieldElement> {
+        [2u64, 3, 6].iter().map(|v| fp_to_bytes(&Fp::from(*v))).collect()
+    }
+
+    #[test]
+    fn test_parse_blake2b_transcript() {
+        let bytes = halo2_mul_proof();
+        let (instances, proof) = parse_blake2b_transcript(&bytes, 4, &halo2_mul_instances()).unwrap();
+        assert_eq!(instances, halo2_mul_instances());
+        assert_eq!(proof.num_rounds(), 4);
+        assert!(proof.validate().is_ok());
+        // The blind f is the last 32 bytes, c the 32 before it
+        assert_eq!(proof.b.unwrap()[..], bytes[bytes.len() - 32..]);
+        assert_eq!(proof.a[..], bytes[bytes.len() - 64..][..32]);
+        // L_0 is the second point of the opening, right after s_poly
+        let l0 = CompressedPoint::from_halo2_bytes(&bytes[bytes.len() - 10 * 32..][..32].try_into().unwrap());
+        let (x, y) = l0.to_affine().unwrap();
+        assert_eq!(proof.l_commitments[0], [fp_to_bytes(&x), fp_to_bytes(&y)]);
+        let generator = generator();
+        assert!(generator.generate_ipa_witness(&[0u8; 32], instances, &proof, None).is_ok());
+    }
+
+    #[test]
+    fn test_parse_blake2b_transcript_rejects_malformed() {
+        let bytes = halo2_mul_proof();
+        let instances = halo2_mul_instances();
+        assert!(matches!(parse_blake2b_transcript(&bytes[..200], 4, &instances), Err(ProofError::SerializationError)));
+        assert!(matches!(
+            parse_blake2b_transcript(&bytes, 4, &[PALLAS_MODULUS_BYTES]),
+            Err(ProofError::NonCanonicalScalar)
+        ));
+        assert!(matches!(parse_blake2b_transcript(&bytes, 64, &instances), Err(ProofError::SerializationError)));
+        let mut bad_point = bytes.clone();
+        let l0 = bad_point.len() - 10 * 32;
+        bad_point[l0..l0 + 32].fill(0xff);
+        assert!(matches!(
+            parse_blake2b_transcript(&bad_point, 4, &instances),
+            Err(ProofError::InvalidProofStructure)
+        ));
+    }
+
+    #[test]
+    fn test_deserialize_reports_section_and_offset() {
+        let witness = IPAStepWitness {
+            operator_binding: operator_binding(&[11u8; 20]),
+            public_inputs: vec![[1u8; 32], [2u8; 32]],
+            l_terms: vec![[[3u8; 32], [4u8; 32]]; 3],
+            r_terms: vec![[[5u8; 32], [6u8; 32]]; 3],
+            a_scalar: [7u8; 32],
+            b_scalar: Some([8u8; 32]),
+            new_app_state: Some([9u8; 32]),
+            next_transcript_hash: [10u8; 32],
+        };
+        let bytes = WitnessSerializer::serialize(&witness);
+        let parse = |bytes: &[u8]| WitnessSerializer::deserialize(bytes, 2, 3, true, true);
+        assert_eq!(WitnessSerializer::serialize(&parse(&bytes).unwrap()), bytes);
+        // (cut, section, section start), after the 32-byte operator binding
+        let cuts = [
+            (20, WitnessSection::OperatorBinding, 0),
+            (32 + 40, WitnessSection::PublicInputs, 32),
+            (32 + 64 + 128 + 10, WitnessSection::LRTerm { round: 1 }, 32 + 64 + 128),
+            (32 + 64 + 3 * 128, WitnessSection::AScalar, 32 + 64 + 3 * 128),
+            (32 + 64 + 3 * 128 + 32 + 31, WitnessSection::BScalar, 32 + 64 + 3 * 128 + 32),
+            (bytes.len() - 1, WitnessSection::NextHash, bytes.len() - 32),
+        ];
+        for (cut, section, offset) in cuts {
+            let err = parse(&bytes[..cut]).unwrap_err();
+            assert_eq!((err.section, err.offset), (section, offset), "cut at {}", cut);
+            assert_eq!(err.remaining, cut - offset);
+            assert!(err.is_truncated());
+            assert!(WitnessSerializer::deserialize_opt(&bytes[..cut], 2, 3, true, true).is_none());
+        }
+        let err = WitnessSerializer::deserialize(&bytes[..32 + 64 + 3 * 128 + 64 + 5], 2, 3, true, true).unwrap_err();
+        assert_eq!((err.section, err.expected, err.remaining), (WitnessSection::AppState, 32, 5));
+    }
+
+    #[test]
+    fn test_accumulator_and_hint_errors() {
+        use crate::ghost::script::verifier_contract::IPAAccumulator;
+        use crate::ghost::script::{IpaHints, PoseidonHints};
+        let accumulator = IPAAccumulator { transcript_hash: [1u8; 32], app_state_root: [2u8; 32], step: 7 };
+        let bytes = accumulator.to_script_bytes();
+        assert_eq!(IPAAccumulator::from_bytes(&bytes).unwrap(), accumulator);
+        let err = IPAAccumulator::from_bytes(&bytes[..66]).unwrap_err();
+        assert_eq!((err.section, err.offset, err.expected, err.remaining), (WitnessSection::Step, 64, 4, 2));
+        assert!(IPAAccumulator::from_bytes_opt(&bytes[..10]).is_none());
+
+        let ipa = IpaHints::placeholder(2).to_bytes();
+        assert!(IpaHints::from_bytes(&ipa).is_ok());
+        let err = IpaHints::from_bytes(&ipa[..ipa.len() - 1]).unwrap_err();
+        assert_eq!((err.section, err.offset), (WitnessSection::IpaRound { round: 1 }, 131));
+        let poseidon = PoseidonHints::placeholder(2).to_bytes();
+        let err = PoseidonHints::from_bytes(&poseidon[..100]).unwrap_err();
+        assert_eq!((err.section, err.offset, err.expected), (WitnessSection::Intent { intent: 0 }, 2, 2 * 192 + 32));
+        let err = PoseidonHints::from_bytes(&[poseidon, vec![0]].concat()).unwrap_err();
+        assert_eq!((err.section, err.expected, err.remaining), (WitnessSection::Trailing, 0, 1));
+    }
+
+    #[test]
+    fn test_transcript_vector() {
+        let v = &TRANSCRIPT_VECTOR;
+        let mut sha = TranscriptBuilder::sha256(&v.prev);
+        absorb_vector(&mut sha);
+        assert_eq!(hex::encode(sha.state_bytes()), v.sha256_state, "absorption order or framing changed");
+        assert_eq!(sha.absorption_count(), v.absorbed);
+
+        // Every Poseidon path absorbs the same sequence as the builder
+        let mut poseidon = TranscriptBuilder::new(&v.prev);
+        absorb_vector(&mut poseidon);
+        assert_eq!(poseidon.absorption_count(), v.absorbed + 1);
+        let proof = IPAProofComponents {
+            l_commitments: v.l_terms.to_vec(),
+            r_commitments: v.r_terms.to_vec(),
+            a: v.a,
+            b: Some(v.b),
+        };
+        let witness = ProofGenerator::new()
+            .with_operator_binding(operator_binding(&v.operator_pkh))
+            .generate_ipa_witness(&v.prev, v.public_inputs.to_vec(), &proof, None)
+            .unwrap();
+        assert_eq!(witness.transcript_elements().len(), v.absorbed);
+        assert_eq!(hex::encode(witness.next_transcript_hash), v.poseidon_state, "PoseidonHash or its constants changed");
+        assert_eq!(witness.next_transcript_hash, poseidon.state_bytes());
+        assert_eq!(fp_to_bytes(&witness.compute_transcript_hash(&v.prev)), poseidon.state_bytes());
+    }
+}
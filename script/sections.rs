@@ -0,0 +1,113 @@
+// Witness Sections
+// Where a deserializer of a witness, accumulator or hint blob gave up, and
+// the cursor the deserializers read with. Shared by the hint types in every
+// build and by `proof_generator` / `verifier_contract` under `contract`.
+
+use super::field::FieldElement;
+use super::read_varint;
+
+/// Part of a serialized witness, accumulator or hint blob
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WitnessSection {
+    // IPAStepWitness
+    OperatorBinding,
+    PublicInputs,
+    LRTerm { round: usize },
+    AScalar,
+    BScalar,
+    AppState,
+    NextHash,
+    // IPAAccumulator
+    TranscriptHash,
+    AppStateRoot,
+    Step,
+    // IpaHints
+    IpaRound { round: usize },
+    FinalScalar,
+    FinalCommitment,
+    // PoseidonHints: the round count varint and the rounds + output that follow
+    IntentCount,
+    Intent { intent: usize },
+    /// Bytes left over after a self-delimiting blob
+    Trailing,
+}
+
+/// Where a deserializer gave up. `remaining < expected` means the input was
+/// cut short; otherwise the bytes were there but malformed (non-canonical
+/// field element, invalid point) or, for `Trailing`, unexpected.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DeserializeError {
+    pub section: WitnessSection,
+    /// Byte offset where the section starts
+    pub offset: usize,
+    /// Bytes the section needs
+    pub expected: usize,
+    /// Bytes left from `offset`
+    pub remaining: usize,
+}
+
+impl DeserializeError {
+    pub fn is_truncated(&self) -> bool {
+        self.remaining < self.expected
+    }
+}
+
+/// Cursor over a serialized blob that reports failures as `DeserializeError`
+pub(crate) struct SectionReader<'a> {
+    bytes: &'a [u8],
+    offset: usize,
+}
+
+impl<'a> SectionReader<'a> {
+    pub(crate) fn new(bytes: &'a [u8]) -> Self {
+        Self { bytes, offset: 0 }
+    }
+
+    pub(crate) fn offset(&self) -> usize {
+        self.offset
+    }
+
+    pub(crate) fn remaining(&self) -> usize {
+        self.bytes.len() - self.offset
+    }
+
+    /// Error for `section` starting at the current offset
+    pub(crate) fn error(&self, section: WitnessSection, expected: usize) -> DeserializeError {
+        DeserializeError { section, offset: self.offset, expected, remaining: self.remaining() }
+    }
+
+    pub(crate) fn take(&mut self, section: WitnessSection, len: usize) -> Result<&'a [u8], DeserializeError> {
+        if self.remaining() < len {
+            return Err(self.error(section, len));
+        }
+        let bytes = &self.bytes[self.offset..self.offset + len];
+        self.offset += len;
+        Ok(bytes)
+    }
+
+    pub(crate) fn element(&mut self, section: WitnessSection) -> Result<FieldElement, DeserializeError> {
+        Ok(self.take(section, 32)?.try_into().expect("32-byte slice"))
+    }
+
+    /// Bitcoin varint; a short read reports the width its prefix implies
+    pub(crate) fn varint(&mut self, section: WitnessSection) -> Result<u64, DeserializeError> {
+        let mut rest = &self.bytes[self.offset..];
+        let width = match rest.first() {
+            Some(0xfd) => 3,
+            Some(0xfe) => 5,
+            Some(0xff) => 9,
+            _ => 1,
+        };
+        let value = read_varint(&mut rest).ok_or_else(|| self.error(section, width))?;
+        self.offset = self.bytes.len() - rest.len();
+        Ok(value)
+    }
+
+    /// Rejects anything left over
+    pub(crate) fn finish(self) -> Result<(), DeserializeError> {
+        match self.remaining() {
+            0 => Ok(()),
+            _ => Err(self.error(WitnessSection::Trailing, 0)),
+        }
+    }
+}
@@ -0,0 +1,155 @@
+// Sighash Policy
+// Restricts which sighash flags a tail accepts on its signatures. A holder's
+// SIGHASH_NONE signature lets whoever relays it rewrite every output, so
+// tails guarding user funds can pin the flags in script.
+//
+// SCRIPT:
+// The flag is the last byte of the pushed signature. The check splits it off,
+// compares it against each allowed byte (OP_EQUAL chained with OP_BOOLOR),
+// verifies the result and CATs the signature back together for OP_CHECKSIG.
+
+use super::opcodes::*;
+use super::{push_bytes, EcdsaSignature};
+use crate::ghost::{Error, Result};
+
+const SIGHASH_FORKID: u8 = 0x40;
+pub(crate) const SIGHASH_ANYONECANPAY: u8 = 0x80;
+
+/// Sighash types as signed on BSV; FORKID is always set
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum SighashFlag {
+    All,
+    None,
+    Single,
+    AllAnyoneCanPay,
+    NoneAnyoneCanPay,
+    SingleAnyoneCanPay,
+}
+
+impl SighashFlag {
+    pub const ALL: [SighashFlag; 6] = [
+        SighashFlag::All,
+        SighashFlag::None,
+        SighashFlag::Single,
+        SighashFlag::AllAnyoneCanPay,
+        SighashFlag::NoneAnyoneCanPay,
+        SighashFlag::SingleAnyoneCanPay,
+    ];
+    /// Byte appended to the DER signature
+    pub fn byte(self) -> u8 {
+        let (base, anyone_can_pay) = match self {
+            SighashFlag::All => (0x01, false),
+            SighashFlag::None => (0x02, false),
+            SighashFlag::Single => (0x03, false),
+            SighashFlag::AllAnyoneCanPay => (0x01, true),
+            SighashFlag::NoneAnyoneCanPay => (0x02, true),
+            SighashFlag::SingleAnyoneCanPay => (0x03, true),
+        };
+        base | SIGHASH_FORKID | if anyone_can_pay { SIGHASH_ANYONECANPAY } else { 0 }
+    }
+    pub fn from_byte(byte: u8) -> Option<Self> {
+        Self::ALL.into_iter().find(|flag| flag.byte() == byte)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SighashPolicy {
+    pub allowed: Vec<SighashFlag>,
+}
+
+impl SighashPolicy {
+    pub fn new(allowed: Vec<SighashFlag>) -> Self {
+        Self { allowed }
+    }
+    /// SIGHASH_ALL | FORKID only: the signature commits to every input and output
+    pub fn all_only() -> Self {
+        Self::new(vec![SighashFlag::All])
+    }
+    pub fn allows(&self, flag_byte: u8) -> bool {
+        self.allowed.iter().any(|flag| flag.byte() == flag_byte)
+    }
+    pub fn check_flag(&self, flag_byte: u8) -> Result<()> {
+        if self.allows(flag_byte) {
+            return Ok(());
+        }
+        let allowed: Vec<String> = self.allowed.iter().map(|f| format!("0x{:02x}", f.byte())).collect();
+        Err(Error::InvalidInput(format!(
+            "Sighash flag 0x{:02x} not allowed by policy [{}]",
+            flag_byte,
+            allowed.join(", ")
+        )))
+    }
+    pub fn check_signature(&self, signature: &EcdsaSignature) -> Result<()> {
+        self.check_flag(signature.sighash_flag)
+    }
+    /// Serialized signature with its flag byte appended
+    pub fn check_signature_bytes(&self, signature: &[u8]) -> Result<()> {
+        let flag = signature
+            .last()
+            .ok_or_else(|| Error::InvalidInput("Empty signature has no sighash flag".to_string()))?;
+        self.check_flag(*flag)
+    }
+    // Stack: [sig] → [sig]
+    // Fails on an empty signature or a flag outside the allowed set
+    pub fn script(&self) -> Vec<u8> {
+        let mut script = vec![OP_SIZE, OP_1SUB, OP_SPLIT];
+        for (i, flag) in self.allowed.iter().enumerate() {
+            script.push(if i == 0 { OP_DUP } else { OP_OVER });
+            script.extend(push_bytes(&[flag.byte()]));
+            script.push(OP_EQUAL);
+            if i > 0 {
+                script.push(OP_BOOLOR);
+            }
+        }
+        if self.allowed.is_empty() {
+            script.push(OP_FALSE);
+        }
+        script.push(OP_VERIFY);
+        script.push(OP_CAT);
+        script
+    }
+    // Stack: [sig] [pubkey] → [sig] [pubkey]
+    pub(crate) fn script_under_pubkey(&self) -> Vec<u8> {
+        let mut script = vec![OP_SWAP];
+        script.extend(self.script());
+        script.push(OP_SWAP);
+        script
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ghost::script::vm::{Interpreter, VmErrorKind};
+    fn run(policy: &SighashPolicy, signature: Vec<u8>) -> std::result::Result<Vec<u8>, VmErrorKind> {
+        let mut vm = Interpreter::new();
+        vm.eval_with_stack(vec![signature], &policy.script()).map_err(|e| e.kind)?;
+        Ok(vm.stack().last().cloned().unwrap_or_default())
+    }
+    #[test]
+    fn test_flag_bytes() {
+        let bytes: Vec<u8> = SighashFlag::ALL.iter().map(|f| f.byte()).collect();
+        assert_eq!(bytes, vec![0x41, 0x42, 0x43, 0xc1, 0xc2, 0xc3]);
+        assert_eq!(SighashFlag::from_byte(0xc3), Some(SighashFlag::SingleAnyoneCanPay));
+        assert_eq!(SighashFlag::from_byte(0x01), None);
+    }
+    #[test]
+    fn test_script_restores_signature() {
+        let policy = SighashPolicy::new(vec![SighashFlag::All, SighashFlag::SingleAnyoneCanPay]);
+        let signature = [vec![0x30; 70], vec![0xc3]].concat();
+        assert_eq!(run(&policy, signature.clone()), Ok(signature));
+        assert_eq!(run(&policy, [vec![0x30; 70], vec![0x42]].concat()), Err(VmErrorKind::VerifyFailed));
+        assert!(run(&policy, Vec::new()).is_err());
+        assert_eq!(run(&SighashPolicy::new(Vec::new()), vec![0x30, 0x41]), Err(VmErrorKind::VerifyFailed));
+    }
+    #[test]
+    fn test_off_chain_check() {
+        let policy = SighashPolicy::all_only();
+        assert!(policy.check_signature(&EcdsaSignature::new(vec![0x30; 70])).is_ok());
+        let err = policy.check_signature(&EcdsaSignature::with_sighash(vec![0x30; 70], 0x42)).unwrap_err();
+        assert!(matches!(err, Error::InvalidInput(msg) if msg.contains("0x42")));
+        assert!(policy.check_signature_bytes(&[]).is_err());
+    }
+}
@@ -0,0 +1,260 @@
+// SPV Inclusion
+// Ties a transition's spending transaction to a mined block: a merkle branch
+// from the txid to the header's merkle root, and the header's proof of work.
+//
+// BYTE ORDER:
+// Txids, merkle roots and branch hashes are in internal (hashing) order, as
+// they appear in serialized headers; block explorers show them reversed.
+// Odd levels pair the last node with itself, per Bitcoin's block merkle rule.
+
+use crate::ghost::crypto::double_sha256;
+
+pub const HEADER_SIZE: usize = 80;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct MerkleBranch {
+    /// Sibling hashes from the leaf level up
+    pub hashes: Vec<[u8; 32]>,
+    /// Position of the transaction in the block
+    pub index: u32,
+}
+
+impl MerkleBranch {
+    /// Branch for `txids[index]`, or None when out of range
+    pub fn from_txids(txids: &[[u8; 32]], index: u32) -> Option<Self> {
+        let mut position = usize::try_from(index).ok()?;
+        if position >= txids.len() {
+            return None;
+        }
+        let mut level = txids.to_vec();
+        let mut hashes = Vec::new();
+        while level.len() > 1 {
+            hashes.push(*level.get(position ^ 1).unwrap_or(&level[position]));
+            level = next_level(&level);
+            position /= 2;
+        }
+        Some(Self { hashes, index })
+    }
+    pub fn root(&self, txid: &[u8; 32]) -> [u8; 32] {
+        let mut node = *txid;
+        for (depth, sibling) in self.hashes.iter().enumerate() {
+            node = if (self.index >> depth) & 1 == 1 {
+                hash_pair(sibling, &node)
+            } else {
+                hash_pair(&node, sibling)
+            };
+        }
+        node
+    }
+    /// Index bits above the branch depth are rejected so one branch cannot
+    /// claim several positions
+    pub fn verify(&self, txid: &[u8; 32], merkle_root: &[u8; 32]) -> bool {
+        let depth_ok = self.hashes.len() >= 32 || self.index >> self.hashes.len() == 0;
+        depth_ok && self.root(txid) == *merkle_root
+    }
+}
+
+/// Block merkle root over `txids`; None for an empty block
+pub fn merkle_root(txids: &[[u8; 32]]) -> Option<[u8; 32]> {
+    let mut level = txids.to_vec();
+    while level.len() > 1 {
+        level = next_level(&level);
+    }
+    level.first().copied()
+}
+
+fn next_level(level: &[[u8; 32]]) -> Vec<[u8; 32]> {
+    level
+        .chunks(2)
+        .map(|pair| hash_pair(&pair[0], pair.get(1).unwrap_or(&pair[0])))
+        .collect()
+}
+
+fn hash_pair(left: &[u8; 32], right: &[u8; 32]) -> [u8; 32] {
+    let mut data = [0u8; 64];
+    data[..32].copy_from_slice(left);
+    data[32..].copy_from_slice(right);
+    double_sha256(&data)
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct BlockHeader {
+    pub version: u32,
+    pub prev_block: [u8; 32],
+    pub merkle_root: [u8; 32],
+    pub time: u32,
+    pub bits: u32,
+    pub nonce: u32,
+}
+
+impl BlockHeader {
+    pub fn from_bytes(bytes: &[u8; HEADER_SIZE]) -> Self {
+        let word = |at: usize| u32::from_le_bytes(bytes[at..at + 4].try_into().expect("4-byte field"));
+        Self {
+            version: word(0),
+            prev_block: bytes[4..36].try_into().expect("32-byte field"),
+            merkle_root: bytes[36..68].try_into().expect("32-byte field"),
+            time: word(68),
+            bits: word(72),
+            nonce: word(76),
+        }
+    }
+    pub fn to_bytes(&self) -> [u8; HEADER_SIZE] {
+        let mut bytes = [0u8; HEADER_SIZE];
+        bytes[..4].copy_from_slice(&self.version.to_le_bytes());
+        bytes[4..36].copy_from_slice(&self.prev_block);
+        bytes[36..68].copy_from_slice(&self.merkle_root);
+        bytes[68..72].copy_from_slice(&self.time.to_le_bytes());
+        bytes[72..76].copy_from_slice(&self.bits.to_le_bytes());
+        bytes[76..].copy_from_slice(&self.nonce.to_le_bytes());
+        bytes
+    }
+    /// Block hash in internal order
+    pub fn hash(&self) -> [u8; 32] {
+        double_sha256(&self.to_bytes())
+    }
+    /// Whether the block hash is at or below the compact target `target_bits`.
+    /// Callers pass the difficulty they expect (usually `bits` once checked
+    /// against the chain); negative, zero and overflowing targets fail.
+    pub fn verify_pow(&self, target_bits: u32) -> bool {
+        let Some(target) = compact_target(target_bits) else {
+            return false;
+        };
+        let hash = self.hash();
+        // Both little-endian 256-bit integers: compare from the top byte down
+        hash.iter().rev().cmp(target.iter().rev()) != std::cmp::Ordering::Greater
+    }
+}
+
+/// Little-endian 256-bit target encoded by compact `bits`
+fn compact_target(bits: u32) -> Option<[u8; 32]> {
+    let exponent = (bits >> 24) as usize;
+    let mantissa = bits & 0x007f_ffff;
+    if bits & 0x0080_0000 != 0 || mantissa == 0 {
+        return None;
+    }
+    let mut target = [0u8; 32];
+    for (i, byte) in mantissa.to_le_bytes()[..3].iter().enumerate() {
+        // Byte i of the mantissa lands at 256^(exponent - 3 + i)
+        let Some(at) = (exponent + i).checked_sub(3) else {
+            continue;
+        };
+        match target.get_mut(at) {
+            Some(slot) => *slot = *byte,
+            None if *byte != 0 => return None,
+            None => {}
+        }
+    }
+    (target != [0u8; 32]).then_some(target)
+}
+
+/// Merkle branch and header placing a transition's transaction in a block
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct InclusionProof {
+    pub header: BlockHeader,
+    pub branch: MerkleBranch,
+}
+
+impl InclusionProof {
+    /// `txid` is in the header's merkle tree and the header's hash meets
+    /// `target_bits`, the easiest target the caller accepts from its own view
+    /// of the chain. The header's `bits` are its own claim and not trusted:
+    /// anyone can mine a header under a target it picks itself.
+    pub fn verify(&self, txid: &[u8; 32], target_bits: u32) -> bool {
+        self.branch.verify(txid, &self.header.merkle_root) && self.header.verify_pow(target_bits)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    fn h(s: &str) -> [u8; 32] {
+        hex::decode(s).unwrap().try_into().unwrap()
+    }
+    // Block 100000: header and its four txids, internal byte order
+    const HEADER_100000: &str = "0100000050120119172a610421a6c3011dd330d9df07b63616c2cc1f1cd00200000000006657a9252aacd5c0b2940996ecff952228c3067cc38d4885efb5a4ac4247e9f337221b4d4c86041b0f2b5710";
+    fn txids_100000() -> Vec<[u8; 32]> {
+        vec![
+            h("876dd0a3ef4a2816ffd1c12ab649825a958b0ff3bb3d6f3e1250f13ddbf0148c"),
+            h("c40297f730dd7b5a99567eb8d27b78758f607507c52292d02d4031895b52f2ff"),
+            h("c46e239ab7d28e2c019b6d66ad8fae98a56ef1f21aeecb94d1b1718186f05963"),
+            h("1d0cb83721529a062d9675b98d6e5c587e4a770fc84ed00abc5a5de04568a6e9"),
+        ]
+    }
+    fn header_100000() -> BlockHeader {
+        BlockHeader::from_bytes(&hex::decode(HEADER_100000).unwrap().try_into().unwrap())
+    }
+    #[test]
+    fn test_historical_header_and_branch() {
+        let header = header_100000();
+        assert_eq!(hex::encode(header.to_bytes()), HEADER_100000);
+        let mut hash = header.hash();
+        hash.reverse();
+        assert_eq!(hex::encode(hash), "000000000003ba27aa200b1cecaad478d2b00432346c3f1f3986da1afd33e506");
+        assert_eq!(header.bits, 0x1b04864c);
+        assert!(header.verify_pow(header.bits));
+        assert!(!header.verify_pow(0x1a04864c));
+        let txids = txids_100000();
+        let branch = MerkleBranch {
+            hashes: vec![txids[3], h("15b88c5107195bf09eb9da89b83d95b3d070079a3c5c5d3d17d0dcd873fbdacc")],
+            index: 2,
+        };
+        assert!(branch.verify(&txids[2], &header.merkle_root));
+        assert!(!branch.verify(&txids[3], &header.merkle_root));
+        assert!(!MerkleBranch { index: 6, ..branch.clone() }.verify(&txids[2], &header.merkle_root));
+        assert_eq!(MerkleBranch::from_txids(&txids, 2), Some(branch));
+        assert_eq!(merkle_root(&txids), Some(header.merkle_root));
+        let proof = InclusionProof { header, branch: MerkleBranch::from_txids(&txids, 2).unwrap() };
+        assert!(proof.verify(&txids[2], 0x1b04864c));
+        assert!(!proof.verify(&txids[2], 0x1a04864c));
+    }
+    #[test]
+    fn test_inclusion_ignores_header_bits() {
+        let txids: Vec<[u8; 32]> = (0..2u8).map(|i| [i; 32]).collect();
+        // A header mined under a target it claims itself
+        let mut header = BlockHeader {
+            version: 1,
+            prev_block: [0u8; 32],
+            merkle_root: merkle_root(&txids).unwrap(),
+            time: 0,
+            bits: 0x207fffff,
+            nonce: 0,
+        };
+        while !header.verify_pow(header.bits) {
+            header.nonce += 1;
+        }
+        let proof = InclusionProof { header, branch: MerkleBranch::from_txids(&txids, 1).unwrap() };
+        assert!(proof.verify(&txids[1], 0x207fffff));
+        assert!(!proof.verify(&txids[1], 0x1d00ffff));
+    }
+    #[test]
+    fn test_odd_leaf_counts() {
+        let txids: Vec<[u8; 32]> = (0..9u8).map(|i| [i; 32]).collect();
+        for count in 1..=txids.len() {
+            let leaves = &txids[..count];
+            let root = merkle_root(leaves).unwrap();
+            for index in 0..count as u32 {
+                let branch = MerkleBranch::from_txids(leaves, index).unwrap();
+                assert!(branch.verify(&leaves[index as usize], &root), "{} of {}", index, count);
+            }
+            assert!(MerkleBranch::from_txids(leaves, count as u32).is_none());
+        }
+        // The last node pairs with itself
+        let three = &txids[..3];
+        assert_eq!(merkle_root(three), merkle_root(&[txids[0], txids[1], txids[2], txids[2]]));
+        assert_eq!(merkle_root(&[txids[4]]), Some(txids[4]));
+        assert_eq!(merkle_root(&[]), None);
+    }
+    #[test]
+    fn test_compact_target_edge_cases() {
+        assert_eq!(compact_target(0x1d00ffff).map(|t| t[26..29].to_vec()), Some(vec![0xff, 0xff, 0x00]));
+        assert_eq!(compact_target(0x03123456).map(|t| t[..3].to_vec()), Some(vec![0x56, 0x34, 0x12]));
+        assert!(compact_target(0x01003456).is_none());
+        assert!(compact_target(0x04923456).is_none());
+        assert!(compact_target(0x23000001).is_none());
+        assert!(compact_target(0).is_none());
+    }
+}
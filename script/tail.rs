@@ -1,19 +1,102 @@
 use super::opcodes::*;
-use crate::ghost::crypto::hash160;
-#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+use super::{SighashPolicy, TailWitness};
+use crate::ghost::crypto::{hash160, sha256};
+use crate::ghost::{Error, Result};
+use std::collections::BTreeMap;
+const MOCK_SIGNATURE_LEN: usize = 72;
+const MOCK_PUBKEY_LEN: usize = 33;
+fn mock_signature() -> Vec<u8> {
+    vec![0x30; MOCK_SIGNATURE_LEN]
+}
+fn mock_pubkey() -> Vec<u8> {
+    vec![0x02; MOCK_PUBKEY_LEN]
+}
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub enum TailType {
     Ecdsa,
     Multisig,
     Lamport,
+    Sponsor,
+    DualAuth,
+    Htlc,
+    Timelock,
+    SequenceTimelock,
+    Branch,
     Custom,
 }
 
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum WitnessItem {
+    Signature,
+    PubKey,
+    Preimage { len: usize },
+    Selector(bool),
+    MultisigDummy,
+    BranchIndex(u8),
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SpendPath {
+    pub name: &'static str,
+    pub witness: Vec<WitnessItem>,
+    pub locktime: Option<u32>,
+}
+
+impl SpendPath {
+    pub fn new(name: &'static str, witness: Vec<WitnessItem>) -> Self {
+        Self { name, witness, locktime: None }
+    }
+    pub fn with_locktime(mut self, locktime: u32) -> Self {
+        self.locktime = Some(locktime);
+        self
+    }
+}
+
 pub trait Tail: Send + Sync + std::fmt::Debug + TailClone {
     fn locking_script(&self) -> Vec<u8>;
     fn tail_type(&self) -> TailType;
     fn script_size(&self) -> usize {
         self.locking_script().len()
     }
+    fn spend_paths(&self) -> Vec<SpendPath> {
+        vec![SpendPath::new("spend", Vec::new())]
+    }
+    /// Sighash flags the locking script accepts on its signatures
+    fn sighash_policy(&self) -> Option<&SighashPolicy> {
+        None
+    }
+    /// Lock every spend of the script is subject to, checked by `check_timelock_satisfied`
+    fn timelock(&self) -> Option<Timelock> {
+        None
+    }
+    /// Structurally valid witness with dummy signatures and keys, for tests
+    fn mock_witness(&self) -> TailWitness {
+        match self.tail_type() {
+            TailType::Ecdsa | TailType::Timelock | TailType::SequenceTimelock => {
+                TailWitness::Ecdsa { signature: mock_signature(), pubkey: mock_pubkey() }
+            }
+            TailType::Multisig => {
+                let signatures = self.spend_paths().first().map_or(1, |path| {
+                    path.witness.iter().filter(|item| **item == WitnessItem::Signature).count()
+                });
+                TailWitness::Multisig { signatures: vec![mock_signature(); signatures] }
+            }
+            TailType::Lamport => TailWitness::Lamport { preimages: vec![[0u8; 32]; 256] },
+            TailType::Sponsor => TailWitness::Sponsor { signature: mock_signature(), pubkey: mock_pubkey() },
+            TailType::DualAuth => TailWitness::DualAuth {
+                user_signature: mock_signature(),
+                user_pubkey: mock_pubkey(),
+                sponsor_signature: mock_signature(),
+                sponsor_pubkey: mock_pubkey(),
+            },
+            TailType::Htlc => TailWitness::Htlc {
+                signature: mock_signature(),
+                pubkey: mock_pubkey(),
+                preimage: Some(vec![0u8; 32]),
+            },
+            TailType::Branch | TailType::Custom => TailWitness::Custom(Vec::new()),
+        }
+    }
 }
 
 pub trait TailClone {
@@ -35,22 +118,28 @@ impl Clone for Box<dyn Tail> {
 #[derive(Clone, Debug)]
 pub struct EcdsaTail {
     pub pubkey_hash: [u8; 20],
+    pub sighash_policy: Option<SighashPolicy>,
 }
 
 impl EcdsaTail {
     pub fn from_pubkey_hash(hash: &[u8; 20]) -> Self {
-        Self { pubkey_hash: *hash }
+        Self { pubkey_hash: *hash, sighash_policy: None }
     }
     pub fn from_pubkey(pubkey: &[u8; 33]) -> Self {
-        Self {
-            pubkey_hash: hash160(pubkey),
-        }
+        Self::from_pubkey_hash(&hash160(pubkey))
+    }
+    pub fn with_sighash_policy(mut self, policy: SighashPolicy) -> Self {
+        self.sighash_policy = Some(policy);
+        self
     }
 }
 
 impl Tail for EcdsaTail {
     fn locking_script(&self) -> Vec<u8> {
         let mut script = Vec::with_capacity(25);
+        if let Some(policy) = &self.sighash_policy {
+            script.extend(policy.script_under_pubkey());
+        }
         script.push(OP_DUP);
         script.push(OP_HASH160);
         script.push(20);
@@ -62,8 +151,17 @@ impl Tail for EcdsaTail {
     fn tail_type(&self) -> TailType {
         TailType::Ecdsa
     }
+    fn spend_paths(&self) -> Vec<SpendPath> {
+        vec![SpendPath::new("signature", vec![WitnessItem::Signature, WitnessItem::PubKey])]
+    }
+    fn sighash_policy(&self) -> Option<&SighashPolicy> {
+        self.sighash_policy.as_ref()
+    }
 }
 
+/// Consensus cap on the public keys one OP_CHECKMULTISIG checks
+pub const MAX_MULTISIG_KEYS: usize = 20;
+
 #[derive(Clone, Debug)]
 pub struct MultisigTail {
     pub threshold: u8,
@@ -71,35 +169,82 @@ pub struct MultisigTail {
 }
 
 impl MultisigTail {
+    #[deprecated(note = "panics on invalid keys; use MultisigTail::try_new")]
     pub fn new(threshold: u8, pubkeys: Vec<[u8; 33]>) -> Self {
+        Self::try_new(threshold, pubkeys).expect("invalid multisig tail")
+    }
+    /// Rejects out-of-range counts, non-compressed keys and duplicate keys, so
+    /// one key can never fill two threshold slots
+    pub fn try_new(threshold: u8, pubkeys: Vec<[u8; 33]>) -> Result<Self> {
+        Self::validated(threshold, pubkeys, 16)
+    }
+    /// `try_new` up to the consensus limit of `MAX_MULTISIG_KEYS` keys; counts
+    /// above 16 are pushed as numbers since there is no OP_17
+    pub fn new_large(threshold: u8, pubkeys: Vec<[u8; 33]>) -> Result<Self> {
+        Self::validated(threshold, pubkeys, MAX_MULTISIG_KEYS)
+    }
+    fn validated(threshold: u8, pubkeys: Vec<[u8; 33]>, max_keys: usize) -> Result<Self> {
         // SECURITY FIX (Audit): Enforce bounds for opcode arithmetic
-        assert!(threshold >= 1 && threshold <= 16, "Multisig Threshold must be 1-16");
-        assert!(pubkeys.len() >= 1 && pubkeys.len() <= 16, "Multisig Keys must be 1-16");
-        assert!(threshold <= pubkeys.len() as u8, "Threshold cannot exceed key count");
-        
-        Self { threshold, pubkeys }
+        if !(1..=max_keys).contains(&(threshold as usize)) {
+            return Err(Error::InvalidInput(format!("Multisig Threshold must be 1-{}", max_keys)));
+        }
+        if pubkeys.is_empty() || pubkeys.len() > max_keys {
+            return Err(Error::InvalidInput(format!("Multisig Keys must be 1-{}", max_keys)));
+        }
+        if threshold as usize > pubkeys.len() {
+            return Err(Error::InvalidInput("Threshold cannot exceed key count".into()));
+        }
+        for (i, pk) in pubkeys.iter().enumerate() {
+            if pk[0] != 0x02 && pk[0] != 0x03 {
+                return Err(Error::InvalidInput(format!("Multisig key {} is not a compressed pubkey (prefix {:#04x})", i, pk[0])));
+            }
+            if pubkeys[..i].contains(pk) {
+                return Err(Error::InvalidInput(format!("Multisig key {} duplicates an earlier key", i)));
+            }
+        }
+        Ok(Self { threshold, pubkeys })
     }
+    #[deprecated(note = "panics on invalid keys; use MultisigTail::try_two_of_three")]
     pub fn two_of_three(pk1: [u8; 33], pk2: [u8; 33], pk3: [u8; 33]) -> Self {
-        Self::new(2, vec![pk1, pk2, pk3])
+        Self::try_two_of_three(pk1, pk2, pk3).expect("invalid multisig tail")
+    }
+    pub fn try_two_of_three(pk1: [u8; 33], pk2: [u8; 33], pk3: [u8; 33]) -> Result<Self> {
+        Self::try_new(2, vec![pk1, pk2, pk3])
+    }
+    /// Orders keys lexicographically (BIP67) so cosigners derive identical script bytes
+    pub fn sorted(mut self) -> Self {
+        self.pubkeys.sort();
+        self
+    }
+    /// Position of `pubkey` in the script; signatures must follow key order
+    pub fn contains_key(&self, pubkey: &[u8; 33]) -> Option<usize> {
+        self.pubkeys.iter().position(|pk| pk == pubkey)
     }
 }
 
 impl Tail for MultisigTail {
     fn locking_script(&self) -> Vec<u8> {
-        let mut script = Vec::new();
-        // Safe op arithmetic due to assertions in new()
-        script.push(OP_1 + self.threshold - 1); 
+        // OP_1..OP_16 up to 16, a number push past it (new_large)
+        let mut script = push_number(self.threshold as i64);
         for pk in &self.pubkeys {
             script.push(33);
             script.extend(pk);
         }
-        script.push(OP_1 + (self.pubkeys.len() as u8) - 1);
+        script.extend(push_number(self.pubkeys.len() as i64));
         script.push(OP_CHECKMULTISIG);
         script
     }
     fn tail_type(&self) -> TailType {
         TailType::Multisig
     }
+    fn spend_paths(&self) -> Vec<SpendPath> {
+        let mut witness = vec![WitnessItem::MultisigDummy];
+        witness.extend(std::iter::repeat_n(WitnessItem::Signature, self.threshold as usize));
+        vec![SpendPath::new("multisig", witness)]
+    }
+    fn mock_witness(&self) -> TailWitness {
+        TailWitness::Multisig { signatures: vec![mock_signature(); self.threshold as usize] }
+    }
 }
 
 #[derive(Clone, Debug)]
@@ -122,13 +267,9 @@ impl LamportTail {
         }
     }
     pub fn pubkey_hash(&self) -> [u8; 32] {
-        use crate::ghost::crypto::sha256;
-        let mut data = Vec::with_capacity(256 * 64);
-        for (h0, h1) in &self.pubkey_hashes {
-            data.extend(h0);
-            data.extend(h1);
-        }
-        sha256(&data)
+        super::hashing::sha256_streaming(
+            self.pubkey_hashes.iter().flat_map(|(h0, h1)| [&h0[..], &h1[..]]),
+        )
     }
 }
 
@@ -153,6 +294,55 @@ impl Tail for LamportTail {
     fn script_size(&self) -> usize {
         26 // size of disabled script
     }
+    fn spend_paths(&self) -> Vec<SpendPath> {
+        Vec::new() // unspendable while disabled
+    }
+}
+
+const LAMPORT_BITS: usize = 256;
+
+/// Secret half of a Lamport key: a preimage pair per message bit. A
+/// signature reveals one preimage of every pair, so each key signs once.
+pub struct LamportPrivateKey {
+    preimages: Vec<([u8; 32], [u8; 32])>,
+}
+
+impl LamportPrivateKey {
+    /// Reveals, for bit i of `message_hash` (most significant bit of byte 0
+    /// first), the preimage committed at `pubkey_hashes[i].0` or `.1`
+    pub fn sign(&self, message_hash: &[u8; 32]) -> TailWitness {
+        let preimages = self.preimages
+            .iter()
+            .enumerate()
+            .map(|(i, (zero, one))| if lamport_bit(message_hash, i) { *one } else { *zero })
+            .collect();
+        TailWitness::Lamport { preimages }
+    }
+}
+
+impl std::fmt::Debug for LamportPrivateKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "LamportPrivateKey(<{} redacted pairs>)", self.preimages.len())
+    }
+}
+
+fn lamport_bit(message_hash: &[u8; 32], i: usize) -> bool {
+    (message_hash[i / 8] >> (7 - i % 8)) & 1 == 1
+}
+
+/// Fresh one-time key and the tail committing to the SHA256 of each
+/// preimage. The tail's locking script stays disabled until it can bind the
+/// revealed preimages to the sighash bits; the key is usable once it does.
+/// `fill` must write cryptographically random bytes into each buffer.
+pub fn generate_lamport_keypair(mut fill: impl FnMut(&mut [u8])) -> (LamportPrivateKey, LamportTail) {
+    let mut sample = || {
+        let mut preimage = [0u8; 32];
+        fill(&mut preimage);
+        preimage
+    };
+    let preimages: Vec<([u8; 32], [u8; 32])> = (0..LAMPORT_BITS).map(|_| (sample(), sample())).collect();
+    let pubkey_hashes = preimages.iter().map(|(zero, one)| (sha256(zero), sha256(one))).collect();
+    (LamportPrivateKey { preimages }, LamportTail::new(pubkey_hashes))
 }
 
 #[derive(Clone, Debug)]
@@ -202,7 +392,13 @@ impl Tail for SponsorTail {
         script
     }
     fn tail_type(&self) -> TailType {
-        TailType::Custom
+        TailType::Sponsor
+    }
+    fn spend_paths(&self) -> Vec<SpendPath> {
+        vec![SpendPath::new("sponsor", vec![WitnessItem::Signature, WitnessItem::PubKey])]
+    }
+    fn mock_witness(&self) -> TailWitness {
+        TailWitness::Sponsor { signature: mock_signature(), pubkey: mock_pubkey() }
     }
 }
 
@@ -210,6 +406,8 @@ impl Tail for SponsorTail {
 pub struct DualAuthTail {
     pub user_pubkey_hash: [u8; 20],
     pub sponsor_pubkey_hash: [u8; 20],
+    /// Applied to both signatures
+    pub sighash_policy: Option<SighashPolicy>,
 }
 
 impl DualAuthTail {
@@ -217,25 +415,30 @@ impl DualAuthTail {
         Self {
             user_pubkey_hash: user_hash,
             sponsor_pubkey_hash: sponsor_hash,
+            sighash_policy: None,
         }
     }
     pub fn from_pubkeys(user_pubkey: &[u8], sponsor_pubkey: &[u8]) -> Self {
-        Self {
-            user_pubkey_hash: hash160(user_pubkey),
-            sponsor_pubkey_hash: hash160(sponsor_pubkey),
-        }
+        Self::new(hash160(user_pubkey), hash160(sponsor_pubkey))
+    }
+    pub fn with_sighash_policy(mut self, policy: SighashPolicy) -> Self {
+        self.sighash_policy = Some(policy);
+        self
     }
 }
 
 impl Tail for DualAuthTail {
     fn locking_script(&self) -> Vec<u8> {
+        let policy = self.sighash_policy.as_ref().map(|p| p.script_under_pubkey()).unwrap_or_default();
         let mut script = Vec::new();
+        script.extend(&policy);
         script.push(OP_DUP);
         script.push(OP_HASH160);
         script.push(20);
         script.extend(&self.sponsor_pubkey_hash);
         script.push(OP_EQUALVERIFY);
         script.push(OP_CHECKSIGVERIFY);
+        script.extend(&policy);
         script.push(OP_DUP);
         script.push(OP_HASH160);
         script.push(20);
@@ -245,7 +448,353 @@ impl Tail for DualAuthTail {
         script
     }
     fn tail_type(&self) -> TailType {
-        TailType::Custom
+        TailType::DualAuth
+    }
+    fn sighash_policy(&self) -> Option<&SighashPolicy> {
+        self.sighash_policy.as_ref()
+    }
+    fn spend_paths(&self) -> Vec<SpendPath> {
+        vec![SpendPath::new("dual_auth", vec![
+            WitnessItem::Signature,
+            WitnessItem::PubKey,
+            WitnessItem::Signature,
+            WitnessItem::PubKey,
+        ])]
+    }
+    fn mock_witness(&self) -> TailWitness {
+        TailWitness::DualAuth {
+            user_signature: mock_signature(),
+            user_pubkey: mock_pubkey(),
+            sponsor_signature: mock_signature(),
+            sponsor_pubkey: mock_pubkey(),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct HtlcTail {
+    pub payment_hash: [u8; 32],
+    pub receiver_pubkey_hash: [u8; 20],
+    pub sender_pubkey_hash: [u8; 20],
+    pub refund_locktime: u32,
+}
+
+impl HtlcTail {
+    pub fn new(payment_hash: [u8; 32], receiver_pubkey_hash: [u8; 20], sender_pubkey_hash: [u8; 20], refund_locktime: u32) -> Self {
+        Self { payment_hash, receiver_pubkey_hash, sender_pubkey_hash, refund_locktime }
+    }
+}
+
+impl Tail for HtlcTail {
+    fn locking_script(&self) -> Vec<u8> {
+        let mut script = Vec::new();
+        script.push(OP_IF);
+        script.push(OP_SHA256);
+        script.push(32);
+        script.extend(&self.payment_hash);
+        script.push(OP_EQUALVERIFY);
+        script.push(OP_DUP);
+        script.push(OP_HASH160);
+        script.push(20);
+        script.extend(&self.receiver_pubkey_hash);
+        script.push(OP_ELSE);
+        script.extend(push_number(self.refund_locktime as i64));
+        script.push(OP_CHECKLOCKTIMEVERIFY);
+        script.push(OP_DROP);
+        script.push(OP_DUP);
+        script.push(OP_HASH160);
+        script.push(20);
+        script.extend(&self.sender_pubkey_hash);
+        script.push(OP_ENDIF);
+        script.push(OP_EQUALVERIFY);
+        script.push(OP_CHECKSIG);
+        script
+    }
+    fn tail_type(&self) -> TailType {
+        TailType::Htlc
+    }
+    fn spend_paths(&self) -> Vec<SpendPath> {
+        vec![
+            SpendPath::new("redeem", vec![
+                WitnessItem::Signature,
+                WitnessItem::PubKey,
+                WitnessItem::Preimage { len: 32 },
+                WitnessItem::Selector(true),
+            ]),
+            SpendPath::new("refund", vec![
+                WitnessItem::Signature,
+                WitnessItem::PubKey,
+                WitnessItem::Selector(false),
+            ]).with_locktime(self.refund_locktime),
+        ]
+    }
+}
+
+const LOCKTIME_THRESHOLD: u32 = 500_000_000;
+const SEQUENCE_FINAL: u32 = 0xffff_ffff;
+const SEQUENCE_LOCKTIME_DISABLE_FLAG: u32 = 1 << 31;
+const SEQUENCE_LOCKTIME_TYPE_FLAG: u32 = 1 << 22;
+const SEQUENCE_LOCKTIME_MASK: u32 = 0x0000_ffff;
+
+/// Lock a tail places on the spending transaction
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Timelock {
+    /// nLockTime floor enforced by OP_CHECKLOCKTIMEVERIFY: a height below 500M, a unix time otherwise
+    Absolute(u32),
+    /// BIP68 nSequence floor enforced by OP_CHECKSEQUENCEVERIFY
+    Relative(u32),
+}
+
+/// P2PKH spendable once the transaction's nLockTime reaches `locktime`:
+/// `<locktime> CHECKLOCKTIMEVERIFY DROP DUP HASH160 <pkh> EQUALVERIFY CHECKSIG`
+#[derive(Clone, Debug)]
+pub struct TimelockTail {
+    pub locktime: u32,
+    pub pubkey_hash: [u8; 20],
+}
+
+impl TimelockTail {
+    pub fn new(locktime: u32, pubkey_hash: [u8; 20]) -> Self {
+        Self { locktime, pubkey_hash }
+    }
+}
+
+impl Tail for TimelockTail {
+    fn locking_script(&self) -> Vec<u8> {
+        timelocked_p2pkh(self.locktime, OP_CHECKLOCKTIMEVERIFY, &self.pubkey_hash)
+    }
+    fn tail_type(&self) -> TailType {
+        TailType::Timelock
+    }
+    fn spend_paths(&self) -> Vec<SpendPath> {
+        vec![SpendPath::new("spend", vec![WitnessItem::Signature, WitnessItem::PubKey]).with_locktime(self.locktime)]
+    }
+    fn timelock(&self) -> Option<Timelock> {
+        Some(Timelock::Absolute(self.locktime))
+    }
+}
+
+/// P2PKH spendable once the input's nSequence reaches `sequence`, i.e. a
+/// number of blocks or 512-second units after the funding output confirms:
+/// `<sequence> CHECKSEQUENCEVERIFY DROP DUP HASH160 <pkh> EQUALVERIFY CHECKSIG`
+#[derive(Clone, Debug)]
+pub struct SequenceTimelockTail {
+    pub sequence: u32,
+    pub pubkey_hash: [u8; 20],
+}
+
+impl SequenceTimelockTail {
+    pub fn new(sequence: u32, pubkey_hash: [u8; 20]) -> Self {
+        Self { sequence, pubkey_hash }
+    }
+    pub fn blocks(blocks: u16, pubkey_hash: [u8; 20]) -> Self {
+        Self::new(blocks as u32, pubkey_hash)
+    }
+}
+
+impl Tail for SequenceTimelockTail {
+    fn locking_script(&self) -> Vec<u8> {
+        timelocked_p2pkh(self.sequence, OP_CHECKSEQUENCEVERIFY, &self.pubkey_hash)
+    }
+    fn tail_type(&self) -> TailType {
+        TailType::SequenceTimelock
+    }
+    fn spend_paths(&self) -> Vec<SpendPath> {
+        vec![SpendPath::new("spend", vec![WitnessItem::Signature, WitnessItem::PubKey])]
+    }
+    fn timelock(&self) -> Option<Timelock> {
+        Some(Timelock::Relative(self.sequence))
+    }
+}
+
+fn timelocked_p2pkh(lock: u32, check: u8, pubkey_hash: &[u8; 20]) -> Vec<u8> {
+    let mut script = push_number(lock as i64);
+    script.push(check);
+    script.push(OP_DROP);
+    script.push(OP_DUP);
+    script.push(OP_HASH160);
+    script.push(20);
+    script.extend(pubkey_hash);
+    script.push(OP_EQUALVERIFY);
+    script.push(OP_CHECKSIG);
+    script
+}
+
+/// Whether a spend of `tail` with the given nLockTime and input nSequence
+/// passes its CLTV/CSV check and, for absolute locks, is final at the given
+/// chain tip. Relative locks are checked against the sequence only; whether
+/// the funding output has aged that far depends on its confirmation height,
+/// which the caller tracks. Tails without a timelock always pass.
+pub fn check_timelock_satisfied(
+    tail: &dyn Tail,
+    tx_locktime: u32,
+    input_sequence: u32,
+    current_height: u32,
+    current_time: u32,
+) -> Result<()> {
+    match tail.timelock() {
+        None => Ok(()),
+        Some(Timelock::Absolute(lock)) => {
+            if input_sequence == SEQUENCE_FINAL {
+                return Err(Error::InvalidInput(
+                    "Input sequence 0xffffffff disables nLockTime; CHECKLOCKTIMEVERIFY fails".to_string(),
+                ));
+            }
+            if (lock < LOCKTIME_THRESHOLD) != (tx_locktime < LOCKTIME_THRESHOLD) {
+                return Err(Error::InvalidInput(format!(
+                    "nLockTime {} and lock {} mix block heights and timestamps",
+                    tx_locktime, lock
+                )));
+            }
+            if tx_locktime < lock {
+                return Err(Error::InvalidInput(format!("nLockTime {} is before lock {}", tx_locktime, lock)));
+            }
+            // Final once nLockTime is below the next block's height or the current time
+            let (tip, final_tx) = if tx_locktime < LOCKTIME_THRESHOLD {
+                (current_height, tx_locktime <= current_height)
+            } else {
+                (current_time, tx_locktime < current_time)
+            };
+            if !final_tx {
+                return Err(Error::InvalidInput(format!("nLockTime {} not yet final at {}", tx_locktime, tip)));
+            }
+            Ok(())
+        }
+        Some(Timelock::Relative(lock)) => {
+            if input_sequence & SEQUENCE_LOCKTIME_DISABLE_FLAG != 0 {
+                return Err(Error::InvalidInput(format!(
+                    "Input sequence 0x{:08x} has relative locktime disabled",
+                    input_sequence
+                )));
+            }
+            if input_sequence & SEQUENCE_LOCKTIME_TYPE_FLAG != lock & SEQUENCE_LOCKTIME_TYPE_FLAG {
+                return Err(Error::InvalidInput(format!(
+                    "Input sequence 0x{:08x} and lock 0x{:08x} mix blocks and time units",
+                    input_sequence, lock
+                )));
+            }
+            if input_sequence & SEQUENCE_LOCKTIME_MASK < lock & SEQUENCE_LOCKTIME_MASK {
+                return Err(Error::InvalidInput(format!(
+                    "Input sequence {} is below relative lock {}",
+                    input_sequence & SEQUENCE_LOCKTIME_MASK,
+                    lock & SEQUENCE_LOCKTIME_MASK
+                )));
+            }
+            Ok(())
+        }
+    }
+}
+
+/// Alternative tails behind an index selector pushed on top of the branch witness:
+/// `DUP <0> NUMEQUAL IF DROP <b0> ELSE DUP <1> NUMEQUAL IF DROP <b1> ELSE <n-1> NUMEQUALVERIFY <bn-1> ENDIF ENDIF`
+#[derive(Clone, Debug)]
+pub struct BranchTail {
+    pub branches: Vec<Box<dyn Tail>>,
+}
+
+impl BranchTail {
+    #[deprecated(note = "panics on an empty or oversized branch list; use BranchTail::try_new")]
+    pub fn new(branches: Vec<Box<dyn Tail>>) -> Self {
+        Self::try_new(branches).expect("invalid branch tail")
+    }
+    /// Rejects an empty branch list and more than 256 branches, whose index
+    /// would not fit in a u8
+    pub fn try_new(branches: Vec<Box<dyn Tail>>) -> Result<Self> {
+        if branches.is_empty() {
+            return Err(Error::InvalidInput("BranchTail needs at least one branch".into()));
+        }
+        if branches.len() > 256 {
+            return Err(Error::InvalidInput(format!("BranchTail has {} branches, at most 256 fit a u8 index", branches.len())));
+        }
+        Ok(Self { branches })
+    }
+    fn selector_overhead(&self) -> usize {
+        let last = self.branches.len() - 1;
+        let chained: usize = (0..last).map(|i| push_number(i as i64).len() + 6).sum();
+        chained + push_number(last as i64).len() + 1
+    }
+    /// Recover the branch scripts of a branch-table locking script
+    pub fn parse(script: &[u8]) -> Option<Self> {
+        let mut branches: Vec<Box<dyn Tail>> = Vec::new();
+        let mut pos = 0;
+        loop {
+            let index = push_number(branches.len() as i64);
+            let rest = &script[pos..];
+            if rest.first() == Some(&OP_DUP)
+                && rest[1..].starts_with(&index)
+                && rest[1 + index.len()..].starts_with(&[OP_NUMEQUAL, OP_IF, OP_DROP])
+            {
+                let start = pos + index.len() + 4;
+                let end = start + branch_body_len(&script[start..], OP_ELSE)?;
+                branches.push(Box::new(CustomTail::new(script[start..end].to_vec())));
+                pos = end + 1;
+            } else if rest.starts_with(&index) && rest.get(index.len()) == Some(&OP_NUMEQUALVERIFY) {
+                let start = pos + index.len() + 1;
+                let end = start + branch_body_len(&script[start..], OP_ENDIF).unwrap_or(script.len() - start);
+                if script[end..].len() != branches.len() || script[end..].iter().any(|op| *op != OP_ENDIF) {
+                    return None;
+                }
+                branches.push(Box::new(CustomTail::new(script[start..end].to_vec())));
+                return Some(Self { branches });
+            } else {
+                return None;
+            }
+        }
+    }
+}
+
+/// Length of a balanced branch body up to the first unmatched `terminator`
+fn branch_body_len(script: &[u8], terminator: u8) -> Option<usize> {
+    let mut depth = 0usize;
+    for item in super::disasm::instructions(script) {
+        let (offset, ins) = item.ok()?;
+        match ins {
+            super::disasm::Instruction::Op(OP_IF | OP_NOTIF) => depth += 1,
+            super::disasm::Instruction::Op(op) if depth == 0 && op == terminator => return Some(offset),
+            super::disasm::Instruction::Op(OP_ENDIF) => depth = depth.checked_sub(1)?,
+            _ => {}
+        }
+    }
+    None
+}
+
+impl Tail for BranchTail {
+    fn locking_script(&self) -> Vec<u8> {
+        let last = self.branches.len() - 1;
+        let mut script = Vec::new();
+        for (i, branch) in self.branches[..last].iter().enumerate() {
+            script.push(OP_DUP);
+            script.extend(push_number(i as i64));
+            script.push(OP_NUMEQUAL);
+            script.push(OP_IF);
+            script.push(OP_DROP);
+            script.extend(branch.locking_script());
+            script.push(OP_ELSE);
+        }
+        script.extend(push_number(last as i64));
+        script.push(OP_NUMEQUALVERIFY);
+        script.extend(self.branches[last].locking_script());
+        script.extend(std::iter::repeat_n(OP_ENDIF, last));
+        script
+    }
+    fn tail_type(&self) -> TailType {
+        TailType::Branch
+    }
+    fn script_size(&self) -> usize {
+        self.branches.iter().map(|b| b.script_size()).sum::<usize>() + self.selector_overhead()
+    }
+    fn spend_paths(&self) -> Vec<SpendPath> {
+        let mut paths = Vec::new();
+        for (i, branch) in self.branches.iter().enumerate() {
+            for mut path in branch.spend_paths() {
+                path.witness.push(WitnessItem::BranchIndex(i as u8));
+                paths.push(path);
+            }
+        }
+        paths
+    }
+    fn mock_witness(&self) -> TailWitness {
+        TailWitness::Branch { index: 0, inner: Box::new(self.branches[0].mock_witness()) }
     }
 }
 
@@ -260,9 +809,72 @@ impl Tail for AnyoneCanSpendTail {
     }
 }
 
+/// Locking-script size of each built-in tail with representative parameters
+/// (2-of-3 multisig, disabled Lamport, 2-way branch, `AnyoneCanSpendTail` for
+/// Custom), for docs and to make size regressions show up in review
+pub fn tail_size_report() -> BTreeMap<TailType, usize> {
+    let key = |prefix: u8| [prefix; 33];
+    let tails: Vec<Box<dyn Tail>> = vec![
+        Box::new(EcdsaTail::from_pubkey_hash(&[0u8; 20])),
+        Box::new(MultisigTail::try_two_of_three(key(0x02), key(0x03), {
+            let mut pk = key(0x02);
+            pk[32] = 0;
+            pk
+        }).expect("distinct compressed keys")),
+        Box::new(LamportTail::placeholder()),
+        Box::new(SponsorTail::from_pubkey_hash(&[0u8; 20])),
+        Box::new(DualAuthTail::new([0u8; 20], [1u8; 20])),
+        Box::new(HtlcTail::new([0u8; 32], [1u8; 20], [2u8; 20], 500_000)),
+        Box::new(BranchTail::try_new(vec![
+            Box::new(EcdsaTail::from_pubkey_hash(&[0u8; 20])),
+            Box::new(SponsorTail::from_pubkey_hash(&[1u8; 20])),
+        ]).expect("two branches")),
+        Box::new(AnyoneCanSpendTail),
+    ];
+    tails.iter().map(|tail| (tail.tail_type(), tail.script_size())).collect()
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    fn keys(n: u8) -> Vec<[u8; 33]> {
+        (0..n).map(|i| {
+            let mut pk = [0x02 | (i & 1); 33];
+            pk[32] = i;
+            pk
+        }).collect()
+    }
+    #[test]
+    fn test_mock_witness_push_counts() {
+        let tails: Vec<Box<dyn Tail>> = vec![
+            Box::new(EcdsaTail::from_pubkey_hash(&[0u8; 20])),
+            Box::new(MultisigTail::try_new(2, keys(3)).unwrap()),
+            Box::new(LamportTail::placeholder()),
+            Box::new(SponsorTail::from_pubkey_hash(&[0u8; 20])),
+            Box::new(DualAuthTail::new([0u8; 20], [1u8; 20])),
+            Box::new(HtlcTail::new([0u8; 32], [1u8; 20], [2u8; 20], 500_000)),
+            Box::new(TimelockTail::new(500_000, [3u8; 20])),
+            Box::new(SequenceTimelockTail::blocks(144, [4u8; 20])),
+            Box::new(BranchTail::try_new(vec![
+                Box::new(MultisigTail::try_new(2, keys(3)).unwrap()),
+                Box::new(EcdsaTail::from_pubkey_hash(&[0u8; 20])),
+            ]).unwrap()),
+            Box::new(CustomTail::new(vec![OP_TRUE])),
+            Box::new(AnyoneCanSpendTail),
+        ];
+        for tail in &tails {
+            let witness = tail.mock_witness();
+            let pushes = witness.to_script_pushes();
+            assert!(!pushes.is_empty());
+            let items = super::super::num::parse_pushes(&pushes).unwrap();
+            assert_eq!(items.len(), witness.layout().item_count(), "{:?}", tail);
+            if let Some(path) = tail.spend_paths().first() {
+                if !path.witness.is_empty() {
+                    assert_eq!(items.len(), path.witness.len(), "{:?}", tail);
+                }
+            }
+        }
+    }
     #[test]
     fn test_ecdsa_tail() {
         let tail = EcdsaTail::from_pubkey_hash(&[0u8; 20]);
@@ -275,22 +887,119 @@ mod tests {
         assert_eq!(script[24], OP_CHECKSIG);
     }
     #[test]
+    fn test_sighash_policy_enforced() {
+        use crate::ghost::script::vm::{Interpreter, MockSignatureChecker, VmErrorKind};
+        use crate::ghost::script::{EcdsaSignature, SighashPolicy};
+        let (user, sponsor) = ([0x02; 33], [0x03; 33]);
+        let all = EcdsaSignature::new(vec![0x30; 70]);
+        let none = EcdsaSignature::with_sighash(vec![0x30; 70], 0x42);
+        let ecdsa = EcdsaTail::from_pubkey(&user).with_sighash_policy(SighashPolicy::all_only());
+        let dual = DualAuthTail::from_pubkeys(&user, &sponsor).with_sighash_policy(SighashPolicy::all_only());
+        let ecdsa_witness = |sig: &EcdsaSignature| TailWitness::Ecdsa { signature: sig.to_bytes(), pubkey: user.to_vec() };
+        let dual_witness = |user_sig: &EcdsaSignature, sponsor_sig: &EcdsaSignature| TailWitness::DualAuth {
+            user_signature: user_sig.to_bytes(),
+            user_pubkey: user.to_vec(),
+            sponsor_signature: sponsor_sig.to_bytes(),
+            sponsor_pubkey: sponsor.to_vec(),
+        };
+        let run = |tail: &dyn Tail, witness: &TailWitness| {
+            let policy = tail.sighash_policy().unwrap();
+            let checked = witness.check_sighash_policy(policy);
+            let executed = Interpreter::with_checker(Box::new(MockSignatureChecker)).eval_with_stack(witness.to_stack_items(), &tail.locking_script()).map_err(|e| e.kind);
+            (checked.is_ok(), executed)
+        };
+        assert_eq!(run(&ecdsa, &ecdsa_witness(&all)), (true, Ok(true)));
+        assert_eq!(run(&ecdsa, &ecdsa_witness(&none)), (false, Err(VmErrorKind::VerifyFailed)));
+        assert_eq!(run(&dual, &dual_witness(&all, &all)), (true, Ok(true)));
+        assert_eq!(run(&dual, &dual_witness(&none, &all)), (false, Err(VmErrorKind::VerifyFailed)));
+        assert_eq!(run(&dual, &dual_witness(&all, &none)), (false, Err(VmErrorKind::VerifyFailed)));
+        // No policy keeps the plain P2PKH script
+        assert_eq!(EcdsaTail::from_pubkey(&user).locking_script().len(), 25);
+        assert!(EcdsaTail::from_pubkey(&user).sighash_policy().is_none());
+    }
+    #[test]
     fn test_multisig_tail() {
         // Test Valid
         let pk1 = [0x02u8; 33];
         let pk2 = [0x03u8; 33];
-        let pk3 = [0x04u8; 33];
-        let tail = MultisigTail::two_of_three(pk1, pk2, pk3);
+        let pk3 = keys(1)[0];
+        let tail = MultisigTail::try_two_of_three(pk1, pk2, pk3).unwrap();
         let script = tail.locking_script();
         assert!(script.len() > 100);
         assert_eq!(script[0], OP_2);
     }
     #[test]
+    #[allow(deprecated)]
+    fn test_two_of_three_keeps_infallible_signature() {
+        let [pk1, pk2, pk3] = [[0x02u8; 33], [0x03u8; 33], keys(1)[0]];
+        let tail: MultisigTail = MultisigTail::two_of_three(pk1, pk2, pk3);
+        assert_eq!(tail.locking_script(), MultisigTail::try_two_of_three(pk1, pk2, pk3).unwrap().locking_script());
+        assert!(MultisigTail::try_two_of_three(pk1, pk1, pk3).is_err());
+    }
+    #[test]
+    #[allow(deprecated)]
     #[should_panic(expected = "Multisig Threshold must be 1-16")]
     fn test_multisig_bounds_invalid_threshold() {
         MultisigTail::new(17, vec![[0u8; 33]; 17]);
+    }
+    #[test]
+    fn test_multisig_large_key_counts() {
+        use crate::ghost::script::vm::{Interpreter, MockSignatureChecker};
+        let tail = MultisigTail::new_large(17, keys(20)).unwrap();
+        let script = tail.locking_script();
+        assert_eq!(script[..2], [0x01, 17]);
+        assert_eq!(script[script.len() - 3..], [0x01, 20, OP_CHECKMULTISIG]);
+        let mut stack = vec![Vec::new()];
+        stack.extend(vec![mock_signature(); 17]);
+        let mut vm = Interpreter::with_checker(Box::new(MockSignatureChecker));
+        vm.eval_with_stack(stack, &script).unwrap();
+        assert_eq!(vm.stack(), [vec![1]]);
+        // Small sets keep the OP_N encoding
+        assert_eq!(MultisigTail::new_large(2, keys(3)).unwrap().locking_script(), MultisigTail::try_new(2, keys(3)).unwrap().locking_script());
+        assert!(MultisigTail::try_new(17, keys(20)).is_err());
+        assert!(MultisigTail::new_large(17, keys(21)).is_err());
+        assert!(MultisigTail::new_large(21, keys(21)).is_err());
+    }
+    #[test]
+    fn test_multisig_rejects_duplicate_and_uncompressed_keys() {
+        let mut dup = keys(3);
+        dup[2] = dup[0];
+        assert!(MultisigTail::try_new(2, dup).is_err());
+        let mut uncompressed = keys(3);
+        uncompressed[1][0] = 0x04;
+        assert!(MultisigTail::try_new(2, uncompressed).is_err());
+        assert!(MultisigTail::try_new(4, keys(3)).is_err());
+        assert!(MultisigTail::try_new(3, keys(3)).is_ok());
+    }
+    #[test]
+    fn test_multisig_sorted_is_order_insensitive() {
+        let keys = keys(5);
+        let mut shuffled = keys.clone();
+        shuffled.swap(0, 4);
+        shuffled.swap(1, 3);
+        shuffled.rotate_left(2);
+        let a = MultisigTail::try_new(3, keys.clone()).unwrap().sorted();
+        let b = MultisigTail::try_new(3, shuffled.clone()).unwrap().sorted();
+        assert_eq!(a.locking_script(), b.locking_script());
+        assert_ne!(MultisigTail::try_new(3, shuffled).unwrap().locking_script(), a.locking_script());
+        let position = a.contains_key(&keys[3]).unwrap();
+        assert_eq!(a.pubkeys[position], keys[3]);
+        assert_eq!(a.contains_key(&[0x04; 33]), None);
     }
      #[test]
+    fn test_tail_size_report() {
+        let report = tail_size_report();
+        assert_eq!(report.len(), 8);
+        assert_eq!(report[&TailType::Ecdsa], 25);
+        assert_eq!(report[&TailType::Lamport], 26);
+        assert_eq!(report[&TailType::Sponsor], 25);
+        assert_eq!(report[&TailType::DualAuth], 50);
+        assert_eq!(report[&TailType::Custom], 1);
+        assert_eq!(report[&TailType::Multisig], 3 + 3 * 34);
+        let wider = MultisigTail::try_new(2, keys(5)).unwrap();
+        assert_eq!(wider.script_size(), report[&TailType::Multisig] + 2 * 34);
+    }
+    #[test]
     fn test_lamport_tail_disabled() {
         let tail = LamportTail::placeholder();
         let script = tail.locking_script();
@@ -303,4 +1012,133 @@ mod tests {
         assert_eq!(tail.locking_script(), custom_script);
         assert_eq!(tail.tail_type(), TailType::Custom);
     }
+    #[test]
+    fn test_ecdsa_single_spend_path() {
+        let paths = EcdsaTail::from_pubkey_hash(&[0u8; 20]).spend_paths();
+        assert_eq!(paths.len(), 1);
+        assert_eq!(paths[0].witness, vec![WitnessItem::Signature, WitnessItem::PubKey]);
+        assert_eq!(paths[0].locktime, None);
+    }
+    #[test]
+    fn test_htlc_spend_paths() {
+        let tail = HtlcTail::new([0x11; 32], [0x22; 20], [0x33; 20], 800_000);
+        let paths = tail.spend_paths();
+        assert_eq!(paths.len(), 2);
+        assert_eq!(paths[0].name, "redeem");
+        assert_eq!(paths[0].witness.last(), Some(&WitnessItem::Selector(true)));
+        assert_eq!(paths[1].name, "refund");
+        assert_eq!(paths[1].witness.last(), Some(&WitnessItem::Selector(false)));
+        assert_eq!(paths[1].locktime, Some(800_000));
+    }
+    fn three_branches() -> BranchTail {
+        BranchTail::try_new(vec![
+            Box::new(EcdsaTail::from_pubkey_hash(&[0x11; 20])),
+            Box::new(HtlcTail::new([0x22; 32], [0x33; 20], [0x44; 20], 500)),
+            Box::new(AnyoneCanSpendTail),
+        ]).unwrap()
+    }
+    #[test]
+    fn test_branch_tail_script_size() {
+        let tail = three_branches();
+        assert_eq!(tail.script_size(), tail.locking_script().len());
+        let lamport = BranchTail::try_new(vec![Box::new(LamportTail::placeholder()), Box::new(AnyoneCanSpendTail)]).unwrap();
+        assert_eq!(lamport.script_size(), lamport.locking_script().len());
+    }
+    #[test]
+    fn test_branch_tail_try_new_bounds() {
+        assert!(BranchTail::try_new(Vec::new()).is_err());
+        let branches = |n: usize| (0..n).map(|_| Box::new(AnyoneCanSpendTail) as Box<dyn Tail>).collect();
+        assert!(BranchTail::try_new(branches(256)).is_ok());
+        assert!(BranchTail::try_new(branches(257)).is_err());
+    }
+    #[test]
+    #[allow(deprecated)]
+    #[should_panic(expected = "invalid branch tail")]
+    fn test_branch_tail_new_panics_on_empty() {
+        BranchTail::new(Vec::new());
+    }
+    #[test]
+    fn test_branch_tail_parse_roundtrip() {
+        let tail = three_branches();
+        let parsed = BranchTail::parse(&tail.locking_script()).unwrap();
+        assert_eq!(parsed.branches.len(), 3);
+        for (a, b) in parsed.branches.iter().zip(tail.branches.iter()) {
+            assert_eq!(a.locking_script(), b.locking_script());
+        }
+        assert_eq!(parsed.locking_script(), tail.locking_script());
+        assert!(BranchTail::parse(&EcdsaTail::from_pubkey_hash(&[0; 20]).locking_script()).is_none());
+    }
+    #[test]
+    fn test_all_tails_conditionals_balanced() {
+        use crate::ghost::script::disasm::validate_conditionals;
+        let tails: Vec<Box<dyn Tail>> = vec![
+            Box::new(EcdsaTail::from_pubkey_hash(&[0x11; 20])),
+            Box::new(MultisigTail::try_two_of_three([0x02; 33], [0x03; 33], keys(1)[0]).unwrap()),
+            Box::new(LamportTail::placeholder()),
+            Box::new(SponsorTail::from_pubkey_hash(&[0x22; 20])),
+            Box::new(DualAuthTail::new([0x33; 20], [0x44; 20])),
+            Box::new(HtlcTail::new([0x55; 32], [0x66; 20], [0x77; 20], 800_000)),
+            Box::new(AnyoneCanSpendTail),
+            Box::new(three_branches()),
+            Box::new(BranchTail::try_new(vec![Box::new(three_branches()), Box::new(AnyoneCanSpendTail)]).unwrap()),
+        ];
+        for tail in &tails {
+            assert_eq!(validate_conditionals(&tail.locking_script()), Ok(()), "{:?}", tail.tail_type());
+        }
+    }
+    #[test]
+    fn test_branch_tail_spend_paths() {
+        let paths = three_branches().spend_paths();
+        assert_eq!(paths.len(), 4);
+        assert_eq!(paths[0].witness.last(), Some(&WitnessItem::BranchIndex(0)));
+        assert_eq!(paths[2].name, "refund");
+        assert_eq!(paths[2].witness.last(), Some(&WitnessItem::BranchIndex(1)));
+        assert_eq!(paths[3].witness, vec![WitnessItem::BranchIndex(2)]);
+    }
+    #[test]
+    fn test_cltv_satisfied() {
+        let tail = TimelockTail::new(800_000, [0x11; 20]);
+        assert_eq!(tail.spend_paths()[0].locktime, Some(800_000));
+        assert!(check_timelock_satisfied(&tail, 800_000, 0xffff_fffe, 800_000, 0).is_ok());
+        assert!(check_timelock_satisfied(&tail, 800_005, 0, 800_010, 0).is_ok());
+        let timed = TimelockTail::new(1_700_000_000, [0x11; 20]);
+        assert!(check_timelock_satisfied(&timed, 1_700_000_000, 0, 0, 1_700_000_001).is_ok());
+        assert!(check_timelock_satisfied(&EcdsaTail::from_pubkey_hash(&[0; 20]), 0, 0xffff_ffff, 0, 0).is_ok());
+    }
+    #[test]
+    fn test_cltv_unsatisfied() {
+        let tail = TimelockTail::new(800_000, [0x11; 20]);
+        // nLockTime below the lock, final sequence, not yet mined height, time-based nLockTime
+        assert!(check_timelock_satisfied(&tail, 799_999, 0, 900_000, 0).is_err());
+        assert!(check_timelock_satisfied(&tail, 800_000, 0xffff_ffff, 900_000, 0).is_err());
+        assert!(check_timelock_satisfied(&tail, 800_000, 0, 799_999, 0).is_err());
+        let err = check_timelock_satisfied(&tail, 1_700_000_000, 0, 900_000, 1_800_000_000).unwrap_err();
+        assert!(matches!(err, Error::InvalidInput(msg) if msg.contains("mix")));
+    }
+    #[test]
+    fn test_csv_relative_lock() {
+        let tail = SequenceTimelockTail::blocks(144, [0x22; 20]);
+        assert_eq!(tail.locking_script()[..4], [0x02, 0x90, 0x00, OP_CHECKSEQUENCEVERIFY]);
+        assert!(check_timelock_satisfied(&tail, 0, 144, 0, 0).is_ok());
+        assert!(check_timelock_satisfied(&tail, 0, 200, 0, 0).is_ok());
+        assert!(check_timelock_satisfied(&tail, 0, 143, 0, 0).is_err());
+        assert!(check_timelock_satisfied(&tail, 0, SEQUENCE_LOCKTIME_DISABLE_FLAG | 144, 0, 0).is_err());
+        assert!(check_timelock_satisfied(&tail, 0, SEQUENCE_LOCKTIME_TYPE_FLAG | 144, 0, 0).is_err());
+    }
+    #[test]
+    fn test_lamport_keypair_signature_matches_public_key() {
+        let (key, tail) = generate_lamport_keypair(|buf| rand::RngCore::fill_bytes(&mut rand::thread_rng(), buf));
+        assert_eq!(tail.pubkey_hashes.len(), LAMPORT_BITS);
+        let message = sha256(b"lamport message");
+        let TailWitness::Lamport { preimages } = key.sign(&message) else {
+            panic!("Lamport key produced a non-Lamport witness");
+        };
+        assert_eq!(preimages.len(), LAMPORT_BITS);
+        for (i, (preimage, (h0, h1))) in preimages.iter().zip(&tail.pubkey_hashes).enumerate() {
+            let (committed, other) = if lamport_bit(&message, i) { (h1, h0) } else { (h0, h1) };
+            assert_eq!(sha256(preimage), *committed, "bit {}", i);
+            assert_ne!(sha256(preimage), *other, "bit {}", i);
+        }
+        assert!(!format!("{:?}", key).contains(&hex::encode(preimages[0])));
+    }
 }
@@ -0,0 +1,19 @@
+// Fixtures shared by the script unit tests
+
+use crate::ghost::crypto::double_sha256;
+use crate::ghost::script::vm::{Interpreter, VmErrorKind};
+use crate::ghost::script::SighashPreimageBuilder;
+
+/// Serialized preimage whose hashOutputs commits to `outputs`, every other
+/// field at the `SighashPreimageBuilder` defaults
+pub(crate) fn preimage_committing_to(outputs: &[u8]) -> Vec<u8> {
+    SighashPreimageBuilder::new().hash_outputs(double_sha256(outputs)).build().to_bytes()
+}
+
+/// Final stack after running `script` over `stack` with the default
+/// interpreter, or the kind of error it stopped on
+pub(crate) fn run_script(stack: Vec<Vec<u8>>, script: &[u8]) -> Result<Vec<Vec<u8>>, VmErrorKind> {
+    let mut vm = Interpreter::new();
+    vm.eval_with_stack(stack, script).map_err(|e| e.kind)?;
+    Ok(vm.stack().to_vec())
+}
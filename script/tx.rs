@@ -0,0 +1,187 @@
+// Transaction Inputs
+// Typed outpoints and inputs for the transaction builders, serialized as they
+// appear on the wire and in the BIP-143 preimage.
+//
+// BYTE ORDER:
+// `txid` is in internal (hashing) order, the reverse of what block explorers
+// display; `vout` and `sequence` are little-endian.
+
+use super::binding_diff::serialize_outputs;
+use super::{read_varint, varint};
+
+pub const OUTPOINT_SIZE: usize = 36;
+/// No relative locktime and no replacement
+pub const SEQUENCE_FINAL: u32 = 0xffff_ffff;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct OutPoint {
+    pub txid: [u8; 32],
+    pub vout: u32,
+}
+
+impl OutPoint {
+    pub fn new(txid: [u8; 32], vout: u32) -> Self {
+        Self { txid, vout }
+    }
+    /// txid || vout, the layout of `SighashPreimage::outpoint` and of each
+    /// entry hashed into hashPrevouts
+    pub fn to_bytes(&self) -> [u8; OUTPOINT_SIZE] {
+        let mut bytes = [0u8; OUTPOINT_SIZE];
+        bytes[..32].copy_from_slice(&self.txid);
+        bytes[32..].copy_from_slice(&self.vout.to_le_bytes());
+        bytes
+    }
+    pub fn from_bytes(bytes: &[u8; OUTPOINT_SIZE]) -> Self {
+        Self {
+            txid: bytes[..32].try_into().expect("32-byte txid"),
+            vout: u32::from_le_bytes(bytes[32..].try_into().expect("4-byte vout")),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct TxInput {
+    pub outpoint: OutPoint,
+    pub script_sig: Vec<u8>,
+    pub sequence: u32,
+}
+
+impl TxInput {
+    /// Input with a final sequence
+    pub fn new(outpoint: OutPoint, script_sig: Vec<u8>) -> Self {
+        Self { outpoint, script_sig, sequence: SEQUENCE_FINAL }
+    }
+    pub fn with_sequence(mut self, sequence: u32) -> Self {
+        self.sequence = sequence;
+        self
+    }
+    /// outpoint || varint(len) || script_sig || sequence
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(OUTPOINT_SIZE + 9 + self.script_sig.len() + 4);
+        bytes.extend_from_slice(&self.outpoint.to_bytes());
+        bytes.extend(varint(self.script_sig.len()));
+        bytes.extend_from_slice(&self.script_sig);
+        bytes.extend_from_slice(&self.sequence.to_le_bytes());
+        bytes
+    }
+}
+
+/// Raw transaction: version || inputs || outputs || locktime
+pub fn serialize_transaction(version: u32, inputs: &[TxInput], outputs: &[(u64, Vec<u8>)], locktime: u32) -> Vec<u8> {
+    let mut bytes = version.to_le_bytes().to_vec();
+    bytes.extend(varint(inputs.len()));
+    for input in inputs {
+        bytes.extend(input.to_bytes());
+    }
+    bytes.extend(varint(outputs.len()));
+    bytes.extend(serialize_outputs(outputs));
+    bytes.extend_from_slice(&locktime.to_le_bytes());
+    bytes
+}
+
+/// Raw transaction split into its fields, as read back by `Transaction::parse`
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Transaction {
+    pub version: u32,
+    pub inputs: Vec<TxInput>,
+    pub outputs: Vec<(u64, Vec<u8>)>,
+    pub locktime: u32,
+}
+
+impl Transaction {
+    /// Inverse of `serialize_transaction`; `None` when truncated or followed
+    /// by trailing bytes
+    pub fn parse(mut bytes: &[u8]) -> Option<Self> {
+        let reader = &mut bytes;
+        let version = u32::from_le_bytes(take(reader, 4)?.try_into().ok()?);
+        let input_count = read_varint(reader)?;
+        let mut inputs = Vec::new();
+        for _ in 0..input_count {
+            let outpoint = OutPoint::from_bytes(take(reader, OUTPOINT_SIZE)?.try_into().ok()?);
+            let script_sig = take_script(reader)?;
+            let sequence = u32::from_le_bytes(take(reader, 4)?.try_into().ok()?);
+            inputs.push(TxInput { outpoint, script_sig, sequence });
+        }
+        let output_count = read_varint(reader)?;
+        let mut outputs = Vec::new();
+        for _ in 0..output_count {
+            let value = u64::from_le_bytes(take(reader, 8)?.try_into().ok()?);
+            outputs.push((value, take_script(reader)?));
+        }
+        let locktime = u32::from_le_bytes(take(reader, 4)?.try_into().ok()?);
+        reader.is_empty().then_some(Self { version, inputs, outputs, locktime })
+    }
+    pub fn to_bytes(&self) -> Vec<u8> {
+        serialize_transaction(self.version, &self.inputs, &self.outputs, self.locktime)
+    }
+}
+
+fn take<'a>(reader: &mut &'a [u8], len: usize) -> Option<&'a [u8]> {
+    if reader.len() < len {
+        return None;
+    }
+    let (head, rest) = reader.split_at(len);
+    *reader = rest;
+    Some(head)
+}
+
+// varint(len) || script
+fn take_script(reader: &mut &[u8]) -> Option<Vec<u8>> {
+    let len = usize::try_from(read_varint(reader)?).ok()?;
+    take(reader, len).map(<[u8]>::to_vec)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ghost::script::SighashPreimageBuilder;
+    #[test]
+    fn test_outpoint_matches_preimage_layout() {
+        let outpoint = OutPoint::new([0xab; 32], 0x0102_0304);
+        let bytes = outpoint.to_bytes();
+        assert_eq!(&bytes[32..], &[0x04, 0x03, 0x02, 0x01]);
+        assert_eq!(OutPoint::from_bytes(&bytes), outpoint);
+        let preimage = SighashPreimageBuilder::new().outpoint(outpoint).build();
+        assert_eq!(preimage.outpoint, bytes);
+        // version (4) || hashPrevouts (32) || hashSequence (32) || outpoint
+        assert_eq!(&preimage.to_bytes()[68..68 + OUTPOINT_SIZE], &bytes);
+    }
+    #[test]
+    fn test_input_sets_preimage_sequence() {
+        let input = TxInput::new(OutPoint::new([0x01; 32], 2), vec![0x51]).with_sequence(0xffff_fffe);
+        let preimage = SighashPreimageBuilder::new().input(&input).build();
+        assert_eq!(preimage.outpoint, input.outpoint.to_bytes());
+        assert_eq!(preimage.sequence, [0xfe, 0xff, 0xff, 0xff]);
+        let bytes = input.to_bytes();
+        assert_eq!(bytes.len(), OUTPOINT_SIZE + 1 + 1 + 4);
+        assert_eq!(&bytes[OUTPOINT_SIZE..OUTPOINT_SIZE + 2], &[0x01, 0x51]);
+    }
+    #[test]
+    fn test_serialize_transaction_layout() {
+        let input = TxInput::new(OutPoint::new([0x01; 32], 0), vec![0x51; 3]);
+        let tx = serialize_transaction(1, std::slice::from_ref(&input), &[(1_000, vec![0x6a])], 0);
+        let mut expected = vec![1, 0, 0, 0, 1];
+        expected.extend(input.to_bytes());
+        expected.push(1);
+        expected.extend(1_000u64.to_le_bytes());
+        expected.extend([0x01, 0x6a]);
+        expected.extend([0, 0, 0, 0]);
+        assert_eq!(tx, expected);
+    }
+    #[test]
+    fn test_parse_transaction_round_trip() {
+        let inputs = vec![
+            TxInput::new(OutPoint::new([0x01; 32], 0), vec![0x51; 3]),
+            TxInput::new(OutPoint::new([0x02; 32], 7), vec![0x52; 300]).with_sequence(0xffff_fffe),
+        ];
+        let outputs = vec![(1_000, vec![0x6a]), (u64::MAX, Vec::new())];
+        let tx = serialize_transaction(2, &inputs, &outputs, 500_000);
+        let parsed = Transaction::parse(&tx).unwrap();
+        assert_eq!(parsed, Transaction { version: 2, inputs, outputs, locktime: 500_000 });
+        assert_eq!(parsed.to_bytes(), tx);
+        assert_eq!(Transaction::parse(&tx[..tx.len() - 1]), None);
+        assert_eq!(Transaction::parse(&[tx.clone(), vec![0]].concat()), None);
+    }
+}
@@ -19,29 +19,81 @@
 //   ensuring the transcript was committed to publicly
 //
 // WITNESS PATTERN:
-// - Locking Script: State commitment + Poseidon logic (~3.9 KB)
-// - Unlocking Script: Constants blob + IPA witness (~3.2 KB)
+// - Locking Script: State commitment + one Poseidon absorb per element slot
+//   (~10.3 KB each). Script cannot loop, so the lock is unrolled for the
+//   largest witness its `TranscriptShape` admits: ~166 KB for the default
+//   two rounds, ~508 KB for a 10-round step with two public inputs. That is
+//   over the 500 KB relay policy; `WitnessConstants` brings it to ~391 KB
+//   for ~6.2 KB more per spend.
+// - Unlocking Script: the transcript elements, 33 bytes each (~1.6 KB for
+//   10 rounds)
 
 use crate::ghost::script::{
-    OP_SWAP, OP_OVER, OP_EQUALVERIFY,
-    OP_TOALTSTACK, OP_FROMALTSTACK,
-    OP_SHA256, OP_HASH160, OP_CHECKSIG,
-    push_bytes,
+    OP_DROP, OP_DUP, OP_SWAP, OP_OVER, OP_PICK, OP_ROLL, OP_DEPTH, OP_EQUALVERIFY,
+    OP_TOALTSTACK, OP_FROMALTSTACK, OP_IF, OP_ENDIF, OP_SPLIT,
+    OP_SUB, OP_1SUB, OP_GREATERTHAN, OP_NUMEQUAL, OP_NUMEQUALVERIFY,
+    OP_SHA256, OP_HASH160, OP_CHECKSIGVERIFY, OP_FALSE, OP_RETURN,
+    push_bytes, push_number, EcdsaSignature, SighashPreimage, SighashPreimageBuilder, TranscriptKind, UtxoKey,
 };
+use crate::ghost::script::num::parse_pushes;
+use crate::ghost::script::introspect::{find_pattern, ScriptPatternToken};
 use crate::ghost::script::field_script::{
-    FusedPoseidonConstants, get_constants_hash,
-    generate_witness_locking_script,
+    FusedPoseidonConstants, get_constants_hash, generate_canonical_check,
+    generate_poseidon_absorb_from_blob, generate_poseidon_verification, round_constants_blob, StrategyChoice,
     fp_to_bytes, bytes_to_fp, FIELD_BYTES,
 };
-use crate::ghost::crypto::{Fp, PoseidonHash};
+use crate::ghost::script::network::ChainParams;
+use crate::ghost::script::vm::{Interpreter, MockSignatureChecker, SignatureChecker, VmErrorKind};
+use crate::ghost::script::point::CompressedPoint;
+use crate::ghost::script::spv::{BlockHeader, InclusionProof, MerkleBranch};
+use crate::ghost::script::tx::{OutPoint, TxInput, serialize_transaction};
+use crate::ghost::script::sighash::SighashPolicy;
+use crate::ghost::script::builder::OpReturnData;
+use crate::ghost::script::binding_diff::serialize_outputs;
+use crate::ghost::script::proof_generator::{DeserializeError, SectionReader, WitnessSection};
+use crate::ghost::crypto::{Fp, PoseidonHash, sha256, double_sha256};
 use ff::Field;
 
+pub mod audit;
+pub mod monitor;
+
 // ============================================================================
 // TYPE ALIASES
 // ============================================================================
 
-/// Field elements (Pallas/Vesta scalars), represented as 32 bytes for Script
-pub type FieldElement = [u8; FIELD_BYTES];
+pub use crate::ghost::script::field::FieldElement;
+
+/// Length prefix absorbed before each variable-length witness section, so
+/// section boundaries are unambiguous in the transcript chain
+pub fn frame_length(len: usize) -> FieldElement {
+    fp_to_bytes(&Fp::from(len as u64))
+}
+
+/// Transcript absorption schema. v2 absorbs `new_app_state`, behind
+/// `app_state_marker`, after the final scalars; v1 transcripts left the
+/// app root unauthenticated and do not verify under v2. v3 absorbs the
+/// operator binding as the first element of every step, so a witness made
+/// for one operator does not verify on a fork recreated under another.
+pub const TRANSCRIPT_SCHEMA_VERSION: u8 = 3;
+
+const APP_STATE_TAG: &[u8] = b"app_state";
+
+/// Absorbed ahead of `new_app_state`, so a present app root can never be
+/// read as a trailing scalar
+pub fn app_state_marker() -> FieldElement {
+    let mut bytes = [0u8; 32];
+    bytes[..APP_STATE_TAG.len()].copy_from_slice(APP_STATE_TAG);
+    bytes
+}
+
+/// Operator identity absorbed at the start of each step: the 20-byte PKH in
+/// the low bytes, zero-padded, so it is always a canonical field element.
+/// Federations may bind any canonical element, e.g. a reduced descriptor hash.
+pub fn operator_binding(operator_pkh: &[u8; 20]) -> FieldElement {
+    let mut bytes = [0u8; 32];
+    bytes[..20].copy_from_slice(operator_pkh);
+    bytes
+}
 
 // ============================================================================
 // IPA ACCUMULATOR STATE
@@ -50,6 +102,7 @@ pub type FieldElement = [u8; FIELD_BYTES];
 /// The On-Chain Accumulator State
 /// Represents the state of the IPA folding protocol
 #[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct IPAAccumulator {
     /// The current digest of the transcript (The "Challenge State")
     /// This is the running hash that accumulates all proof components
@@ -91,22 +144,25 @@ impl IPAAccumulator {
         PoseidonHash::hash_3(transcript, app_root, step_fp)
     }
 
-    /// Deserialize from bytes
-    pub fn from_bytes(bytes: &[u8]) -> Option<Self> {
-        if bytes.len() < 68 {
-            return None;
-        }
-        
-        let transcript_hash: FieldElement = bytes[0..32].try_into().ok()?;
-        let app_state_root: FieldElement = bytes[32..64].try_into().ok()?;
-        let step = u32::from_le_bytes(bytes[64..68].try_into().ok()?);
-        
-        Some(Self {
+    /// Deserialize from bytes; trailing bytes are ignored
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, DeserializeError> {
+        let mut reader = SectionReader::new(bytes);
+        let transcript_hash = reader.element(WitnessSection::TranscriptHash)?;
+        let app_state_root = reader.element(WitnessSection::AppStateRoot)?;
+        let step = reader.take(WitnessSection::Step, 4)?;
+        let step = u32::from_le_bytes(step.try_into().expect("4-byte slice"));
+
+        Ok(Self {
             transcript_hash,
             app_state_root,
             step,
         })
     }
+
+    /// `from_bytes` without the failure details
+    pub fn from_bytes_opt(bytes: &[u8]) -> Option<Self> {
+        Self::from_bytes(bytes).ok()
+    }
 }
 
 // ============================================================================
@@ -117,6 +173,10 @@ impl IPAAccumulator {
 /// This contains the data hashed into the transcript during the reduction
 #[derive(Debug, Clone)]
 pub struct IPAStepWitness {
+    // --- Operator ---
+    /// `operator_binding` of the contract this step was generated for
+    pub operator_binding: FieldElement,
+
     // --- Public Instances ---
     /// Public inputs mixed into the transcript at this step
     pub public_inputs: Vec<FieldElement>,
@@ -148,6 +208,7 @@ impl IPAStepWitness {
     /// Create a minimal witness for testing
     pub fn new_minimal(next_transcript: FieldElement) -> Self {
         Self {
+            operator_binding: operator_binding(&[0u8; 20]),
             public_inputs: Vec::new(),
             l_terms: Vec::new(),
             r_terms: Vec::new(),
@@ -158,37 +219,77 @@ impl IPAStepWitness {
         }
     }
 
-    /// Compute the hash of all witness data
-    /// This is what the script verifies
-    pub fn compute_transcript_hash(&self, prev_transcript: &FieldElement) -> Fp {
-        let mut inputs = Vec::new();
-        
-        // Previous transcript
-        inputs.push(bytes_to_fp(prev_transcript).unwrap_or(Fp::ZERO));
-        
-        // Public inputs
-        for pi in &self.public_inputs {
-            inputs.push(bytes_to_fp(pi).unwrap_or(Fp::ZERO));
+    /// Append L/R cross-terms given as compressed points, decompressed to
+    /// the affine `[x, y]` pairs the transcript absorbs
+    pub fn with_lr_points(mut self, points: &[(CompressedPoint, CompressedPoint)]) -> Result<Self, VerifierError> {
+        for (l, r) in points {
+            let (lx, ly) = l.to_affine().ok_or(VerifierError::InvalidPoint)?;
+            let (rx, ry) = r.to_affine().ok_or(VerifierError::InvalidPoint)?;
+            self.l_terms.push([fp_to_bytes(&lx), fp_to_bytes(&ly)]);
+            self.r_terms.push([fp_to_bytes(&rx), fp_to_bytes(&ry)]);
         }
-        
-        // L and R terms (interleaved as in IPA)
+        Ok(self)
+    }
+
+    /// Elements absorbed after the previous transcript, in order: operator
+    /// binding, framed public inputs, framed L/R terms, final scalars, then
+    /// the marked app state when present. The unlocking script pushes
+    /// exactly these.
+    pub fn transcript_elements(&self) -> Vec<FieldElement> {
+        let mut elements = vec![self.operator_binding];
+
+        // Public inputs, framed by their count
+        elements.push(frame_length(self.public_inputs.len()));
+        elements.extend(&self.public_inputs);
+
+        // L and R terms (interleaved as in IPA), framed by the round count
+        elements.push(frame_length(self.l_terms.len()));
         for (l, r) in self.l_terms.iter().zip(self.r_terms.iter()) {
-            inputs.push(bytes_to_fp(&l[0]).unwrap_or(Fp::ZERO));
-            inputs.push(bytes_to_fp(&l[1]).unwrap_or(Fp::ZERO));
-            inputs.push(bytes_to_fp(&r[0]).unwrap_or(Fp::ZERO));
-            inputs.push(bytes_to_fp(&r[1]).unwrap_or(Fp::ZERO));
+            elements.extend([l[0], l[1], r[0], r[1]]);
         }
-        
+
         // Final scalars
-        inputs.push(bytes_to_fp(&self.a_scalar).unwrap_or(Fp::ZERO));
+        elements.push(self.a_scalar);
         if let Some(b) = &self.b_scalar {
-            inputs.push(bytes_to_fp(b).unwrap_or(Fp::ZERO));
+            elements.push(*b);
         }
-        
-        // Hash all inputs
+
+        // New app state, authenticated by the transcript
+        if let Some(app_state) = &self.new_app_state {
+            elements.push(app_state_marker());
+            elements.push(*app_state);
+        }
+        elements
+    }
+
+    /// Compute the hash of all witness data
+    /// This is what the script verifies
+    pub fn compute_transcript_hash(&self, prev_transcript: &FieldElement) -> Fp {
+        let mut inputs = vec![bytes_to_fp(prev_transcript).unwrap_or(Fp::ZERO)];
+        inputs.extend(self.transcript_elements().iter().map(|e| bytes_to_fp(e).unwrap_or(Fp::ZERO)));
         PoseidonHash::hash_many(&inputs)
     }
 
+    /// Strict variant of `compute_transcript_hash`: rejects non-canonical
+    /// encodings and mismatched L/R counts instead of mapping them to zero
+    pub fn compute_transcript_hash_checked(&self, prev_transcript: &FieldElement) -> Result<Fp, VerifierError> {
+        if self.l_terms.len() != self.r_terms.len() {
+            return Err(VerifierError::LRLengthMismatch);
+        }
+        let canonical = |e: &FieldElement| bytes_to_fp(e).is_some();
+        let all_canonical = canonical(prev_transcript)
+            && canonical(&self.operator_binding)
+            && self.public_inputs.iter().all(canonical)
+            && self.l_terms.iter().chain(self.r_terms.iter()).flatten().all(canonical)
+            && canonical(&self.a_scalar)
+            && self.b_scalar.iter().all(canonical)
+            && self.new_app_state.iter().all(canonical);
+        if !all_canonical {
+            return Err(VerifierError::NonCanonicalElement);
+        }
+        Ok(self.compute_transcript_hash(prev_transcript))
+    }
+
     /// Verify the witness is valid (off-chain check)
     pub fn verify(&self, prev_transcript: &FieldElement) -> bool {
         let computed = self.compute_transcript_hash(prev_transcript);
@@ -196,15 +297,46 @@ impl IPAStepWitness {
         computed == expected
     }
 
+    /// SHA256 over every witness field, with counts and presence flags
+    /// so distinct witnesses never share an encoding
+    pub fn digest(&self) -> [u8; 32] {
+        let mut data = Vec::with_capacity(self.size() + 16);
+        data.extend_from_slice(&self.operator_binding);
+        data.extend_from_slice(&(self.public_inputs.len() as u32).to_le_bytes());
+        for pi in &self.public_inputs {
+            data.extend_from_slice(pi);
+        }
+        data.extend_from_slice(&(self.l_terms.len() as u32).to_le_bytes());
+        for (l, r) in self.l_terms.iter().zip(self.r_terms.iter()) {
+            data.extend_from_slice(&l[0]);
+            data.extend_from_slice(&l[1]);
+            data.extend_from_slice(&r[0]);
+            data.extend_from_slice(&r[1]);
+        }
+        data.extend_from_slice(&self.a_scalar);
+        for optional in [&self.b_scalar, &self.new_app_state] {
+            match optional {
+                Some(value) => {
+                    data.push(1);
+                    data.extend_from_slice(value);
+                }
+                None => data.push(0),
+            }
+        }
+        data.extend_from_slice(&self.next_transcript_hash);
+        sha256(&data)
+    }
+
     /// Estimate witness size in bytes
     pub fn size(&self) -> usize {
-        let mut size = 0;
+        let mut size = 64; // section length prefixes
+        size += 32; // operator_binding
         size += self.public_inputs.len() * 32;
         size += self.l_terms.len() * 64; // Affine points (32+32)
         size += self.r_terms.len() * 64; // Affine points (32+32)
         size += 32; // a_scalar
         if self.b_scalar.is_some() { size += 32; }
-        if self.new_app_state.is_some() { size += 32; }
+        if self.new_app_state.is_some() { size += 64; } // marker + state
         size += 32; // next_transcript_hash
         size
     }
@@ -216,6 +348,7 @@ impl IPAStepWitness {
 
 /// The Halo2 IPA Verifier Contract
 /// This creates UTXOs that verify IPA accumulation steps
+#[derive(Clone)]
 pub struct VerifierContract {
     /// Operator public key hash (for governance)
     pub operator_pkh: [u8; 20],
@@ -223,11 +356,27 @@ pub struct VerifierContract {
     /// Current accumulator state
     pub current_state: IPAAccumulator,
     
+    /// Poseidon hash of the state the contract was deployed with
+    pub genesis_state_hash: FieldElement,
+    
     /// Pre-computed fused constants for Poseidon
     pub constants: FusedPoseidonConstants,
     
     /// Hash of valid constants (embedded in locking script)
     pub constants_hash: [u8; 32],
+
+    /// Sighash flags the operator signature may carry; any when None
+    pub sighash_policy: Option<SighashPolicy>,
+
+    /// Poseidon check from `generate_poseidon_verification`; the legacy
+    /// witness-pattern section when None
+    pub poseidon_strategy: Option<StrategyChoice>,
+
+    /// Steps whose state is also committed in an OP_RETURN anchor output
+    pub anchor_policy: Option<AnchorPolicy>,
+
+    /// Largest witness the lock absorbs
+    pub transcript_shape: TranscriptShape,
 }
 
 impl VerifierContract {
@@ -238,120 +387,136 @@ impl VerifierContract {
         
         Self {
             operator_pkh,
+            genesis_state_hash: fp_to_bytes(&initial_state.hash()),
             current_state: initial_state,
             constants,
             constants_hash,
+            sighash_policy: None,
+            poseidon_strategy: None,
+            anchor_policy: None,
+            transcript_shape: TranscriptShape::default(),
         }
     }
 
-    /// Create contract from existing state
+    /// Create a new contract whose Poseidon section uses `strategy`, e.g.
+    /// from `PoseidonScriptStrategy::select`
+    pub fn with_poseidon_strategy(operator_pkh: [u8; 20], initial_state: IPAAccumulator, strategy: StrategyChoice) -> Self {
+        Self { poseidon_strategy: Some(strategy), ..Self::new(operator_pkh, initial_state) }
+    }
+
+    /// Create contract from existing state (treated as genesis)
     pub fn with_state(operator_pkh: [u8; 20], state: IPAAccumulator) -> Self {
         Self::new(operator_pkh, state)
     }
 
+    /// Resume a deployed contract at a later state, keeping its genesis identity
+    pub fn resume(operator_pkh: [u8; 20], genesis_state_hash: FieldElement, state: IPAAccumulator) -> Self {
+        let mut contract = Self::new(operator_pkh, state);
+        contract.genesis_state_hash = genesis_state_hash;
+        contract
+    }
+
+    /// Restrict the operator signature's sighash flags in the locking script
+    pub fn with_sighash_policy(mut self, policy: SighashPolicy) -> Self {
+        self.sighash_policy = Some(policy);
+        self
+    }
+
+    /// Anchor the state every `policy.every_n_steps` steps
+    pub fn with_anchor_policy(mut self, policy: AnchorPolicy) -> Self {
+        self.anchor_policy = Some(policy);
+        self
+    }
+
+    /// Unroll the lock for witnesses up to `shape`, e.g. the circuit's IPA
+    /// round count and public inputs
+    pub fn with_transcript_shape(mut self, shape: TranscriptShape) -> Self {
+        self.transcript_shape = shape;
+        self
+    }
+
+    /// Anchor script for the current state, if the policy anchors its step
+    pub fn anchor_script(&self) -> Option<Vec<u8>> {
+        self.anchor_policy
+            .filter(|policy| policy.anchors(self.current_state.step))
+            .map(|_| anchor_script(&self.current_state))
+    }
+
+    /// Whether `output`'s header names the Poseidon constants this build
+    /// generates locks from, so its successor continues the same contract
+    pub fn verify_spendable_with_current_constants(&self, output: &ContractOutput) -> bool {
+        output.constants_hash() == Some(self.constants.witness_hash())
+    }
+
+    /// Deterministic identity:
+    /// sha256(schema_version || operator_pkh || genesis_state_hash || constants_hash)
+    /// Independent of the current step, so every party deploying the same
+    /// logical contract derives the same id; the schema version keeps
+    /// contracts whose transcripts are built differently apart
+    pub fn contract_id(&self) -> [u8; 32] {
+        let mut data = Vec::with_capacity(1 + 20 + 32 + 32);
+        data.push(TRANSCRIPT_SCHEMA_VERSION);
+        data.extend_from_slice(&self.operator_pkh);
+        data.extend_from_slice(&self.genesis_state_hash);
+        data.extend_from_slice(&self.constants_hash);
+        sha256(&data)
+    }
+
+    /// `operator_binding` of this contract's operator
+    pub fn operator_binding(&self) -> FieldElement {
+        operator_binding(&self.operator_pkh)
+    }
+
     /// Generate the Locking Script (The Covenant)
     /// 
     /// Structure:
-    /// 1. State Commitment (68 bytes)
-    /// 2. Constants Hash (32 bytes)
-    /// 3. Operator PKH (20 bytes)
-    /// 4. Poseidon Verifier Logic (~3.8 KB)
-    /// 5. Signature Check (Tail)
+    /// 1. Header: constants hash, state commitment, operator PKH
+    /// 2. Operator signature check
+    /// 3. State check, and the template blob check under `WitnessConstants`
+    /// 4. Transcript chain, one Poseidon absorb per element slot of
+    ///    `transcript_shape`
+    ///
+    /// See `contract_locking_sections` for the stack it expects.
     pub fn locking_script(&self) -> Vec<u8> {
-        let mut script = Vec::with_capacity(4096);
-        use crate::ghost::script::field_script::generate_canonical_check;
-        
-        // === HEADER: Embedded state data ===
-        
-        // 1. Constants hash for witness verification
-        script.extend(push_bytes(&self.constants_hash));
-        script.push(OP_TOALTSTACK);
-        
-        // 2. Current state commitment
-        let state_hash = fp_to_bytes(&self.current_state.hash());
-        script.extend(push_bytes(&state_hash));
-        script.push(OP_TOALTSTACK);
-        
-        // 3. Operator PKH for signature verification
-        script.extend(push_bytes(&self.operator_pkh));
-        script.push(OP_TOALTSTACK);
-        
-        // === VERIFICATION LOGIC ===
-        
-        // Stack at this point (from unlocking script):
-        // [constants_blob] [prev_state] [witness_data...] [next_state] [sig] [pubkey]
-        
-        // 4. Verify constants blob hash
-        script.push(OP_OVER);
-        script.push(OP_SHA256);
-        script.push(OP_FROMALTSTACK);
-        script.push(OP_EQUALVERIFY);
-        
-        // 5. Verify previous state matches
-        script.push(OP_SWAP);
-        // Canonical check: Ensure prev_state blob is valid length/structure if needed
-        // For bytes blob, we just hash it
-        script.push(OP_SHA256);
-        script.push(OP_FROMALTSTACK);
-        script.push(OP_EQUALVERIFY);
-        
-        // === FROZEN HEART FIX: Absorb State Hash First ===
-        // The Poseidon sponge must be initialized with the State Hash.
-        // Implementation: We verify the detailed Poseidon logic below.
-        // We inject the state hash into the transcript calculation.
-        
-        script.extend(generate_poseidon_verification_section());
-        
-        // 7. Operator signature verification (Tail)
-        script.push(OP_FROMALTSTACK);  // Get operator PKH
-        script.push(OP_OVER);          // Copy pubkey
-        script.push(OP_HASH160);       // Hash pubkey
-        script.push(OP_EQUALVERIFY);   // Verify matches operator
-        script.push(OP_CHECKSIG);      // Verify signature
-        
-        script
+        self.locking_sections().into_iter().flat_map(|(_, bytes)| bytes).collect()
+    }
+
+    /// `locking_script` split into its labelled sections, in script order
+    fn locking_sections(&self) -> Vec<(ContractSection, Vec<u8>)> {
+        contract_locking_sections(
+            &self.constants_hash,
+            &self.current_state,
+            &self.operator_pkh,
+            self.sighash_policy.as_ref(),
+            self.poseidon_strategy,
+            self.transcript_shape,
+        )
     }
 
     /// Generate the Unlocking Script (The Input)
     /// 
     /// Structure:
-    /// 1. Constants blob (~2.8 KB fused)
-    /// 2. Previous state (68 bytes)
-    /// 3. IPA witness data (variable)
-    /// 4. Next state (68 bytes)
-    /// 5. Signature + pubkey
+    /// 1. Previous state (68 bytes)
+    /// 2. Operator binding, then IPA witness data (variable)
+    /// 3. Next transcript hash (32 bytes)
+    /// 4. Signature + pubkey
     pub fn unlocking_script(&self, witness: &IPAStepWitness) -> Vec<u8> {
         let mut script = Vec::with_capacity(4096);
         
-        // 1. Constants blob
-        let constants_bytes = self.constants.to_witness_bytes();
-        script.extend(push_bytes(&constants_bytes));
+        // 0. Round constants the Poseidon section reads, if it takes them
+        if let Some(strategy) = self.poseidon_strategy {
+            script.extend(generate_poseidon_verification(strategy).unlock_template);
+        }
         
-        // 2. Previous state
+        // 1. Previous state
         script.extend(push_bytes(&self.current_state.to_script_bytes()));
         
-        // 3. IPA witness data (order matches transcript absorption)
-        
-        // Public inputs
-        for pi in &witness.public_inputs {
-            script.extend(push_bytes(pi));
+        // 2. IPA witness data (order matches transcript absorption)
+        for element in witness.transcript_elements() {
+            script.extend(push_bytes(&element));
         }
         
-        // L and R terms (interleaved)
-        for (l, r) in witness.l_terms.iter().zip(witness.r_terms.iter()) {
-            script.extend(push_bytes(&l[0]));
-            script.extend(push_bytes(&l[1]));
-            script.extend(push_bytes(&r[0]));
-            script.extend(push_bytes(&r[1]));
-        }
-        
-        // Final scalars
-        script.extend(push_bytes(&witness.a_scalar));
-        if let Some(b) = &witness.b_scalar {
-            script.extend(push_bytes(b));
-        }
-        
-        // 4. Next transcript hash
+        // 3. Next transcript hash
         script.extend(push_bytes(&witness.next_transcript_hash));
         
         // Note: Signature and pubkey are added by the transaction builder
@@ -359,12 +524,34 @@ impl VerifierContract {
         script
     }
 
+    /// Unlocking data as raw stack elements, in push order (no opcodes)
+    /// Pushing each item with `push_bytes` reproduces `unlocking_script`
+    pub fn unlocking_stack_items(&self, witness: &IPAStepWitness) -> Vec<Vec<u8>> {
+        let mut items = Vec::new();
+        if let Some(strategy) = self.poseidon_strategy {
+            let template = generate_poseidon_verification(strategy).unlock_template;
+            items.extend(parse_pushes(&template).expect("unlock template is pushes only"));
+        }
+        items.push(self.current_state.to_script_bytes());
+        items.extend(witness.transcript_elements().iter().map(|e| e.to_vec()));
+        items.push(witness.next_transcript_hash.to_vec());
+        items
+    }
+
     /// Apply a transition and return new contract state
     pub fn apply_transition(&self, witness: &IPAStepWitness) -> Result<Self, VerifierError> {
         // Verify the witness computes correctly
         if !witness.verify(&self.current_state.transcript_hash) {
             return Err(VerifierError::InvalidTranscript);
         }
+
+        // A transcript bound to another operator is valid but not ours
+        if witness.operator_binding != self.operator_binding() {
+            return Err(VerifierError::OperatorMismatch);
+        }
+
+        // Elements past the lock's unrolled slots would fail on chain
+        self.transcript_shape.check(witness)?;
         
         // Compute new state
         let new_state = IPAAccumulator {
@@ -376,12 +563,46 @@ impl VerifierContract {
         
         Ok(Self {
             operator_pkh: self.operator_pkh,
+            genesis_state_hash: self.genesis_state_hash,
             current_state: new_state,
             constants: self.constants.clone(),
             constants_hash: self.constants_hash,
+            sighash_policy: self.sighash_policy.clone(),
+            poseidon_strategy: self.poseidon_strategy,
+            anchor_policy: self.anchor_policy,
+            transcript_shape: self.transcript_shape,
         })
     }
 
+    /// Apply a transition and describe it as a `StateTransitionEvent`
+    pub fn apply_transition_with_event(
+        &self,
+        witness: &IPAStepWitness,
+    ) -> Result<(Self, StateTransitionEvent), VerifierError> {
+        let next = self.apply_transition(witness)?;
+        let event = StateTransitionEvent::new(
+            self.current_state.clone(),
+            next.current_state.clone(),
+            witness.digest(),
+        );
+        Ok((next, event))
+    }
+
+    /// Apply a batch of transitions in order, streaming one event per step to `sink`
+    pub fn apply_transitions(
+        &self,
+        witnesses: &[IPAStepWitness],
+        sink: &mut dyn EventSink,
+    ) -> Result<Self, VerifierError> {
+        let mut contract = self.clone();
+        for witness in witnesses {
+            let (next, event) = contract.apply_transition_with_event(witness)?;
+            sink.emit(&event);
+            contract = next;
+        }
+        Ok(contract)
+    }
+
     /// Get locking script size
     pub fn locking_script_size(&self) -> usize {
         self.locking_script().len()
@@ -391,13 +612,403 @@ impl VerifierContract {
     pub fn unlocking_script_size(&self, witness: &IPAStepWitness) -> usize {
         self.unlocking_script(witness).len()
     }
+
+    /// Dry-run a pending step: execute the real unlocking script (witness,
+    /// `sig`, `pubkey`) against the real locking script with
+    /// `MockSignatureChecker`. Gate broadcasts on this, since
+    /// `apply_transition` only checks the off-chain transcript model.
+    pub fn simulate_step(&self, witness: &IPAStepWitness, sig: &[u8], pubkey: &[u8]) -> SimulationResult {
+        self.simulate_step_with_checker(witness, sig, pubkey, Box::new(MockSignatureChecker))
+    }
+
+    /// `simulate_step` with a caller-supplied signature checker
+    pub fn simulate_step_with_checker(
+        &self,
+        witness: &IPAStepWitness,
+        sig: &[u8],
+        pubkey: &[u8],
+        checker: Box<dyn SignatureChecker>,
+    ) -> SimulationResult {
+        let mut unlocking = self.unlocking_script(witness);
+        unlocking.extend(push_bytes(sig));
+        unlocking.extend(push_bytes(pubkey));
+        let sections = self.locking_sections();
+        let locking: Vec<u8> = sections.iter().flat_map(|(_, bytes)| bytes.iter().copied()).collect();
+
+        let mut vm = Interpreter::with_checker(checker);
+        let failure = |vm: &Interpreter, section, offset, opcode, kind| SimulationFailure {
+            section,
+            offset,
+            opcode,
+            kind,
+            stack: vm.stack().to_vec(),
+            alt_stack: vm.alt_stack().to_vec(),
+        };
+        if let Err(e) = vm.eval(&unlocking) {
+            return SimulationResult::Failure(failure(&vm, ContractSection::Unlocking, e.offset, e.opcode, Some(e.kind)));
+        }
+        let items = vm.stack().to_vec();
+        let unlocking_stack_depth = items.len();
+        match vm.eval_with_stack(items, &locking) {
+            Err(e) => {
+                let section = section_at(&sections, e.offset);
+                SimulationResult::Failure(failure(&vm, section, e.offset, e.opcode, Some(e.kind)))
+            }
+            Ok(false) => {
+                // Only the last section leaves a result
+                let (offset, opcode) = (locking.len(), *locking.last().unwrap_or(&0));
+                SimulationResult::Failure(failure(&vm, ContractSection::Poseidon, offset, opcode, None))
+            }
+            Ok(true) => SimulationResult::Success(SimulationStats {
+                op_count: vm.op_count(),
+                unlocking_stack_depth,
+                final_stack_depth: vm.stack().len(),
+                locking_size: locking.len(),
+                unlocking_size: unlocking.len(),
+            }),
+        }
+    }
+}
+
+/// Largest witness a contract lock absorbs. Script cannot loop, so the
+/// Poseidon section unrolls one absorb (~10.3 KB) per element slot; size it
+/// from the circuit's IPA round count. A witness with more elements fails on
+/// chain and in `simulate_step`, and is refused by `finalize`/`plan_chain`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct TranscriptShape {
+    pub public_inputs: usize,
+    pub ipa_rounds: usize,
+}
+
+impl TranscriptShape {
+    pub fn new(public_inputs: usize, ipa_rounds: usize) -> Self {
+        Self { public_inputs, ipa_rounds }
+    }
+
+    /// Element slots of the lock: operator binding, two frames, the public
+    /// inputs, four per round and `a`, then `b` and the marked app state,
+    /// which a witness may leave out
+    pub fn max_elements(&self) -> usize {
+        4 + self.public_inputs + 4 * self.ipa_rounds + 3
+    }
+
+    /// Whether the lock has a slot for every element `witness` pushes
+    pub fn admits(&self, witness: &IPAStepWitness) -> bool {
+        witness.transcript_elements().len() <= self.max_elements()
+    }
+
+    /// `admits`, as the `TranscriptTooLong` error the lock would fail with
+    pub fn check(&self, witness: &IPAStepWitness) -> Result<(), VerifierError> {
+        if self.admits(witness) {
+            return Ok(());
+        }
+        Err(VerifierError::TranscriptTooLong {
+            elements: witness.transcript_elements().len(),
+            capacity: self.max_elements(),
+        })
+    }
+}
+
+/// One public input and two rounds, the shape of the mock prover's steps.
+/// A 10-round circuit needs `TranscriptShape::new(2, 10)`.
+impl Default for TranscriptShape {
+    fn default() -> Self {
+        Self::new(1, 2)
+    }
+}
+
+/// Locking script sections for a contract at `state`, from the parts that
+/// determine them; shared by `VerifierContract` and watch-only callers that
+/// have no constants to hold.
+///
+/// Stack contract, bottom to top, as `unlocking_stack_items` pushes it:
+///
+///   [template items] [prev_state] [e_0 .. e_n-1] [next_transcript_hash] [sig] [pubkey]
+///
+/// where the template items are the strategy's unlock template (the round
+/// constants blob for `WitnessConstants`, nothing otherwise), `prev_state`
+/// is the 68-byte `to_script_bytes` of `state` and `e_i` are the
+/// `transcript_elements`, at most `shape.max_elements()`. `prev_state` sits
+/// at a fixed height above the bottom and is rolled up by `OP_DEPTH`, so the
+/// element count is free. The script:
+///
+/// 1. Header: constants hash (a label, dropped), SHA256 of `prev_state` and
+///    operator PKH to alt
+/// 2. Operator signature: HASH160(pubkey) against the PKH, then CHECKSIGVERIFY
+/// 3. State check: `prev_state` against its hash, cut to its transcript hash
+/// 4. Constants check, under `WitnessConstants` only: the template blob
+///    against the hash the lock was built for
+/// 5. Poseidon: from the previous transcript hash, absorb each element
+///    bottom-up (canonical check, then `hash2(state, e_i)`) and leave
+///    `state == next_transcript_hash` as the result. `e_0` must be the
+///    operator's `operator_binding`; the remaining slots are guarded, so
+///    shorter witnesses leave them unused.
+fn contract_locking_sections(
+    constants_hash: &[u8; 32],
+    state: &IPAAccumulator,
+    operator_pkh: &[u8; 20],
+    sighash_policy: Option<&SighashPolicy>,
+    poseidon_strategy: Option<StrategyChoice>,
+    shape: TranscriptShape,
+) -> Vec<(ContractSection, Vec<u8>)> {
+    // Items the strategy's unlock template puts under prev_state
+    let witness_constants = poseidon_strategy == Some(StrategyChoice::WitnessConstants);
+    let template = witness_constants as i64;
+
+    // === HEADER: Embedded state data ===
+    let mut header = Vec::new();
+    
+    // 1. Constants the absorbs were generated from, for off-chain readers
+    header.extend(push_bytes(constants_hash));
+    header.push(OP_DROP);
+    
+    // 2. Current state commitment
+    header.extend(push_bytes(&sha256(&state.to_script_bytes())));
+    header.push(OP_TOALTSTACK);
+    
+    // 3. Operator PKH for signature verification
+    header.extend(push_bytes(operator_pkh));
+    header.push(OP_TOALTSTACK);
+    
+    // === VERIFICATION LOGIC ===
+    
+    // 4. Operator signature, off the top of the stack
+    let mut signature = vec![
+        OP_FROMALTSTACK,  // Get operator PKH
+        OP_OVER,          // Copy pubkey
+        OP_HASH160,       // Hash pubkey
+        OP_EQUALVERIFY,   // Verify matches operator
+    ];
+    if let Some(policy) = sighash_policy {
+        signature.extend(policy.script_under_pubkey()); // Check the flag byte
+    }
+    signature.push(OP_CHECKSIGVERIFY); // Verify signature
+    
+    // 5. Previous state, the bottom witness item above the template
+    let mut state_check = vec![OP_DEPTH];
+    state_check.extend(push_number(template + 1));
+    state_check.extend([OP_SUB, OP_ROLL]);
+    state_check.extend([OP_DUP, OP_SHA256, OP_FROMALTSTACK, OP_EQUALVERIFY]);
+    // Keep the transcript hash the sponge starts from
+    state_check.extend(push_number(FIELD_BYTES as i64));
+    state_check.extend([OP_SPLIT, OP_DROP]);
+    
+    // 6. Round constants blob, copied per absorb below
+    let mut constants_check = Vec::new();
+    if witness_constants {
+        constants_check.extend([OP_DEPTH, OP_1SUB, OP_PICK, OP_SHA256]);
+        constants_check.extend(push_bytes(&sha256(&round_constants_blob())));
+        constants_check.push(OP_EQUALVERIFY);
+    }
+    
+    // 7. Transcript chain: [e_0 .. e_n-1] [next] [prev transcript]
+    let mut poseidon = vec![OP_SWAP, OP_TOALTSTACK, OP_TOALTSTACK];
+    let absorb = if witness_constants {
+        let mut absorb = vec![OP_DEPTH, OP_1SUB, OP_PICK];
+        absorb.extend(generate_poseidon_absorb_from_blob());
+        absorb
+    } else {
+        TranscriptKind::Poseidon.absorb()
+    };
+    let roll_lowest = {
+        let mut roll = vec![OP_DEPTH];
+        roll.extend(push_number(template + 1));
+        roll.extend([OP_SUB, OP_ROLL]);
+        roll
+    };
+    // The operator binding, always present and always this operator's
+    poseidon.extend(&roll_lowest);
+    poseidon.push(OP_DUP);
+    poseidon.extend(push_bytes(&operator_binding(operator_pkh)));
+    poseidon.push(OP_EQUALVERIFY);
+    poseidon.extend(&absorb);
+    for _ in 1..shape.max_elements() {
+        // While elements remain, roll up the lowest
+        poseidon.push(OP_DEPTH);
+        poseidon.extend(push_number(template));
+        poseidon.extend([OP_GREATERTHAN, OP_IF]);
+        poseidon.extend(&roll_lowest);
+        poseidon.extend(generate_canonical_check());
+        poseidon.extend(&absorb);
+        poseidon.push(OP_ENDIF);
+    }
+    // Every element absorbed, then the template dropped
+    poseidon.push(OP_DEPTH);
+    poseidon.extend(push_number(template));
+    poseidon.push(OP_NUMEQUALVERIFY);
+    if witness_constants {
+        poseidon.push(OP_DROP);
+    }
+    poseidon.extend([OP_FROMALTSTACK, OP_FROMALTSTACK, OP_NUMEQUAL]);
+    
+    let mut sections = vec![
+        (ContractSection::Header, header),
+        (ContractSection::OperatorSignature, signature),
+        (ContractSection::StateCheck, state_check),
+    ];
+    if witness_constants {
+        sections.push((ContractSection::ConstantsCheck, constants_check));
+    }
+    sections.push((ContractSection::Poseidon, poseidon));
+    sections
+}
+
+/// Locking script size of a contract whose Poseidon section uses `strategy`
+/// over `shape`; the header's contents do not change it
+pub(crate) fn contract_lock_size(strategy: StrategyChoice, shape: TranscriptShape) -> usize {
+    let state = IPAAccumulator::new([0; 32]);
+    contract_locking_sections(&[0; 32], &state, &[0; 20], None, Some(strategy), shape)
+        .iter()
+        .map(|(_, bytes)| bytes.len())
+        .sum()
+}
+
+/// Section containing locking-script byte `offset`
+fn section_at(sections: &[(ContractSection, Vec<u8>)], offset: usize) -> ContractSection {
+    let mut end = 0;
+    for (section, bytes) in sections {
+        end += bytes.len();
+        if offset < end {
+            return *section;
+        }
+    }
+    ContractSection::Poseidon
+}
+
+// ============================================================================
+// SIMULATION
+// ============================================================================
+
+/// Part of the spend a simulated failure occurred in
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ContractSection {
+    /// Pushing the witness, signature and pubkey
+    Unlocking,
+    /// Embedded constants hash, state commitment and operator PKH
+    Header,
+    ConstantsCheck,
+    StateCheck,
+    /// Transcript / Poseidon witness verification
+    Poseidon,
+    OperatorSignature,
+}
+
+/// Outcome of `VerifierContract::simulate_step`
+#[derive(Debug, Clone)]
+pub enum SimulationResult {
+    Success(SimulationStats),
+    Failure(SimulationFailure),
+}
+
+impl SimulationResult {
+    pub fn is_success(&self) -> bool {
+        matches!(self, SimulationResult::Success(_))
+    }
 }
 
-/// Generate the Poseidon verification section
-fn generate_poseidon_verification_section() -> Vec<u8> {
-    // SECURITY HARDENING: Use secure verification with Transcript Chaining and Canonical Checks
-    use crate::ghost::script::field_script::generate_secure_witness_verification;
-    generate_secure_witness_verification()
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SimulationStats {
+    /// Non-push opcodes executed across both scripts
+    pub op_count: usize,
+    /// Main stack depth handed from the unlocking to the locking script
+    pub unlocking_stack_depth: usize,
+    pub final_stack_depth: usize,
+    pub locking_size: usize,
+    pub unlocking_size: usize,
+}
+
+#[derive(Debug, Clone)]
+pub struct SimulationFailure {
+    pub section: ContractSection,
+    /// Byte offset of the failing opcode within the unlocking script for
+    /// `ContractSection::Unlocking`, otherwise within the locking script
+    pub offset: usize,
+    pub opcode: u8,
+    /// `None` when both scripts ran to completion but left a false result
+    pub kind: Option<VmErrorKind>,
+    /// Stacks at the point of failure, bottom first
+    pub stack: Vec<Vec<u8>>,
+    pub alt_stack: Vec<Vec<u8>>,
+}
+
+// ============================================================================
+// EVENTS
+// ============================================================================
+
+/// Structured record of one contract transition, emitted when it is applied
+/// so indexers can persist history without re-parsing transactions
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct StateTransitionEvent {
+    pub prev_state: IPAAccumulator,
+    pub new_state: IPAAccumulator,
+    pub step: u32,
+    /// Spending transaction id, once the raw transaction is known
+    pub txid: Option<[u8; 32]>,
+    /// `IPAStepWitness::digest` of the witness that drove the transition
+    pub witness_digest: [u8; 32],
+    /// Seconds since the Unix epoch at emission
+    pub timestamp: u64,
+    /// Block placing `txid` on chain, once mined
+    pub inclusion: Option<InclusionProof>,
+}
+
+impl StateTransitionEvent {
+    pub fn new(prev_state: IPAAccumulator, new_state: IPAAccumulator, witness_digest: [u8; 32]) -> Self {
+        let timestamp = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or(0);
+        Self {
+            step: new_state.step,
+            prev_state,
+            new_state,
+            txid: None,
+            witness_digest,
+            timestamp,
+            inclusion: None,
+        }
+    }
+
+    pub fn with_txid(mut self, txid: [u8; 32]) -> Self {
+        self.txid = Some(txid);
+        self
+    }
+
+    /// Record the block that mined `txid`; checked by `audit::verify_chain`
+    pub fn attach_inclusion(mut self, header: BlockHeader, branch: MerkleBranch) -> Self {
+        self.inclusion = Some(InclusionProof { header, branch });
+        self
+    }
+
+    /// Stable identifier: sha256(prev_state || new_state || witness_digest)
+    /// Excludes `txid`, `timestamp` and `inclusion` so re-emitting the same transition
+    /// yields the same id
+    pub fn event_id(&self) -> [u8; 32] {
+        let mut data = Vec::with_capacity(68 * 2 + 32);
+        data.extend(self.prev_state.to_script_bytes());
+        data.extend(self.new_state.to_script_bytes());
+        data.extend_from_slice(&self.witness_digest);
+        sha256(&data)
+    }
+}
+
+/// Receives transition events as they are produced
+pub trait EventSink {
+    fn emit(&mut self, _event: &StateTransitionEvent) {}
+}
+
+/// Sink that discards every event
+#[derive(Debug, Default, Clone, Copy)]
+pub struct NoopEventSink;
+
+impl EventSink for NoopEventSink {}
+
+impl EventSink for Vec<StateTransitionEvent> {
+    fn emit(&mut self, event: &StateTransitionEvent) {
+        self.push(event.clone());
+    }
 }
 
 // ============================================================================
@@ -410,6 +1021,36 @@ pub enum VerifierError {
     InvalidSignature,
     InvalidState,
     StepMismatch,
+    /// L/R term is not a valid compressed curve point
+    InvalidPoint,
+    /// Witness element is not a canonical field encoding (>= p)
+    NonCanonicalElement,
+    /// Different numbers of L and R terms
+    LRLengthMismatch,
+    /// Input value cannot cover the fee
+    InsufficientValue,
+    /// Output value would fall below the network's dust limit
+    DustOutput,
+    /// Output script violates the network's relay policy
+    PolicyViolation,
+    /// Event does not describe the witness or states it is audited against
+    EventMismatch,
+    /// Attached inclusion proof has no txid, a bad merkle branch or
+    /// insufficient proof of work
+    InvalidInclusion,
+    /// Witness is bound to a different operator than the contract
+    OperatorMismatch,
+    /// Operator signature's sighash flag is outside the contract's policy
+    SighashFlagNotAllowed,
+    /// Chained steps pay less than the target fee rate over the package
+    PackageFeeTooLow,
+    /// Spent output commits to constants other than the ones this build
+    /// pushes, e.g. one created by an older release
+    ConstantsMismatch { expected: [u8; 32], ours: [u8; 32] },
+    /// Script is not an anchor this build can read
+    InvalidAnchor,
+    /// Witness pushes more elements than the lock has absorb slots for
+    TranscriptTooLong { elements: usize, capacity: usize },
 }
 
 // ============================================================================
@@ -427,6 +1068,20 @@ pub struct ContractOutput {
     
     /// Contract state (for reference)
     pub state: IPAAccumulator,
+
+    /// Shape the lock is unrolled for, kept by successor outputs
+    pub transcript_shape: TranscriptShape,
+
+    /// Poseidon section the lock was built with, kept by successor outputs
+    pub poseidon_strategy: Option<StrategyChoice>,
+
+    /// Anchor policy of the contract, applied by builders spending it and
+    /// kept by successor outputs
+    pub anchor_policy: Option<AnchorPolicy>,
+
+    /// Sighash policy the lock checks the operator signature against, kept
+    /// by successor outputs
+    pub sighash_policy: Option<SighashPolicy>,
 }
 
 impl ContractOutput {
@@ -435,15 +1090,95 @@ impl ContractOutput {
             value,
             script_pubkey: contract.locking_script(),
             state: contract.current_state.clone(),
+            transcript_shape: contract.transcript_shape,
+            poseidon_strategy: contract.poseidon_strategy,
+            anchor_policy: contract.anchor_policy,
+            sighash_policy: contract.sighash_policy.clone(),
+        }
+    }
+
+    /// Contract at `state` built like this output's: same transcript shape,
+    /// Poseidon strategy, anchor and sighash policy
+    pub fn contract_at(&self, state: IPAAccumulator, operator_pkh: [u8; 20]) -> VerifierContract {
+        VerifierContract {
+            poseidon_strategy: self.poseidon_strategy,
+            anchor_policy: self.anchor_policy,
+            sighash_policy: self.sighash_policy.clone(),
+            ..VerifierContract::with_state(operator_pkh, state)
         }
+        .with_transcript_shape(self.transcript_shape)
     }
 
     pub fn next_output(&self, new_state: IPAAccumulator, operator_pkh: [u8; 20], value: u64) -> Self {
-        let contract = VerifierContract::with_state(operator_pkh, new_state);
-        Self::new(&contract, value)
+        Self::new(&self.contract_at(new_state, operator_pkh), value)
+    }
+
+    /// Locking script `witness` moves this output to under `operator_pkh`,
+    /// built from the constants hash in this output's header so watch-only
+    /// callers need not compute the fused constants. Matches `next_output`;
+    /// the witness itself is not checked. `None` if the script has no
+    /// contract header.
+    pub fn expected_next_script(&self, witness: &IPAStepWitness, operator_pkh: [u8; 20]) -> Option<Vec<u8>> {
+        let next_state = IPAAccumulator {
+            transcript_hash: witness.next_transcript_hash,
+            app_state_root: witness.new_app_state.unwrap_or(self.state.app_state_root),
+            step: self.state.step + 1,
+        };
+        let sections = contract_locking_sections(
+            &self.constants_hash()?,
+            &next_state,
+            &operator_pkh,
+            self.sighash_policy.as_ref(),
+            self.poseidon_strategy,
+            self.transcript_shape,
+        );
+        Some(sections.into_iter().flat_map(|(_, bytes)| bytes).collect())
+    }
+
+    /// Wallet index key, the same as `MulletScript::utxo_key` for equal scripts
+    pub fn utxo_key(&self) -> UtxoKey {
+        UtxoKey::for_script(&self.script_pubkey)
+    }
+
+    /// Operator PKH pushed in the locking script header; `None` if the
+    /// script does not start with a contract header
+    pub fn operator_pkh(&self) -> Option<[u8; 20]> {
+        header_operator_pkh(&self.script_pubkey)
+    }
+
+    /// Constants hash pushed in the locking script header, naming the
+    /// Poseidon constants its absorbs were generated from
+    pub fn constants_hash(&self) -> Option<[u8; 32]> {
+        parse_header(&self.script_pubkey).map(|(constants_hash, _)| constants_hash)
     }
 }
 
+/// Third push of a contract header
+fn header_operator_pkh(script: &[u8]) -> Option<[u8; 20]> {
+    parse_header(script).map(|(_, operator_pkh)| operator_pkh)
+}
+
+/// First and third pushes of a contract header: constants hash followed by
+/// OP_DROP, then state hash and operator PKH, each followed by OP_TOALTSTACK
+fn parse_header(script: &[u8]) -> Option<([u8; 32], [u8; 20])> {
+    let found = find_pattern(script, &header_pattern())?;
+    if found.offset != 0 {
+        return None;
+    }
+    Some((found.captures[0].1.try_into().ok()?, found.captures[2].1.try_into().ok()?))
+}
+
+fn header_pattern() -> [ScriptPatternToken; 6] {
+    [
+        ScriptPatternToken::PushOfLen(32),
+        ScriptPatternToken::Op(OP_DROP),
+        ScriptPatternToken::PushOfLen(FIELD_BYTES),
+        ScriptPatternToken::Op(OP_TOALTSTACK),
+        ScriptPatternToken::PushOfLen(20),
+        ScriptPatternToken::Op(OP_TOALTSTACK),
+    ]
+}
+
 // ============================================================================
 // TRANSACTION BUILDER
 // ============================================================================
@@ -464,16 +1199,30 @@ pub struct ContractTransactionBuilder {
     
     /// Operator PKH (for next output)
     pub operator_pkh: [u8; 20],
+
+    /// Sighash policy of the contract being spent, carried to the next output
+    pub sighash_policy: Option<SighashPolicy>,
+
+    /// Push `operator_signature` as given instead of normalizing its DER
+    pub keep_signature_encoding: bool,
+
+    /// Anchor policy of the contract being spent, applied to each new state
+    pub anchor_policy: Option<AnchorPolicy>,
 }
 
 impl ContractTransactionBuilder {
+    /// Anchors with the input's anchor policy and checks the operator
+    /// signature against its sighash policy, if its contract has them
     pub fn new(input: ContractOutput, witness: IPAStepWitness, operator_pkh: [u8; 20]) -> Self {
         Self {
+            anchor_policy: input.anchor_policy,
+            sighash_policy: input.sighash_policy.clone(),
             input,
             witness,
             operator_signature: Vec::new(),
             operator_pubkey: Vec::new(),
             operator_pkh,
+            keep_signature_encoding: false,
         }
     }
 
@@ -483,13 +1232,72 @@ impl ContractTransactionBuilder {
         self
     }
 
+    pub fn with_sighash_policy(mut self, policy: SighashPolicy) -> Self {
+        self.sighash_policy = Some(policy);
+        self
+    }
+
+    /// Append an anchor output whenever the new state's step is one
+    /// `policy` anchors, in place of the input's policy
+    pub fn with_anchor_policy(mut self, policy: AnchorPolicy) -> Self {
+        self.anchor_policy = Some(policy);
+        self
+    }
+
+    /// Push the operator signature exactly as given; by default its DER is
+    /// re-encoded with `EcdsaSignature::normalize`
+    pub fn keep_signature_encoding(mut self) -> Self {
+        self.keep_signature_encoding = true;
+        self
+    }
+
+    /// Operator signature as pushed: normalized unless opted out, and
+    /// verbatim when it does not parse as DER plus a flag byte
+    pub fn signature_bytes(&self) -> Vec<u8> {
+        if self.keep_signature_encoding {
+            return self.operator_signature.clone();
+        }
+        EcdsaSignature::from_bytes(&self.operator_signature)
+            .and_then(|sig| sig.normalize().ok())
+            .map_or_else(|| self.operator_signature.clone(), |sig| sig.to_bytes())
+    }
+
+    /// Whether the operator signature's flag byte passes the contract's policy,
+    /// checked before the step is finalized rather than failing on chain
+    pub fn check_sighash_policy(&self) -> Result<(), VerifierError> {
+        match &self.sighash_policy {
+            Some(policy) if policy.check_signature_bytes(&self.operator_signature).is_err() => {
+                Err(VerifierError::SighashFlagNotAllowed)
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Whether the input was built from this build's Poseidon constants, so
+    /// the next output continues the same contract. Checked by `finalize`
+    /// and `plan_chain`, not by the raw `build_*` methods.
+    pub fn check_constants(&self) -> Result<(), VerifierError> {
+        let ours = FusedPoseidonConstants::compute().witness_hash();
+        match self.input.constants_hash() {
+            Some(expected) if expected == ours => Ok(()),
+            Some(expected) => Err(VerifierError::ConstantsMismatch { expected, ours }),
+            None => Err(VerifierError::InvalidState),
+        }
+    }
+
+    /// Whether the input's lock has a slot for every element the witness
+    /// pushes; an oversized witness would only fail on chain
+    pub fn check_transcript_shape(&self) -> Result<(), VerifierError> {
+        self.input.transcript_shape.check(&self.witness)
+    }
+
     /// Build complete unlocking script
     pub fn build_unlocking_script(&self) -> Vec<u8> {
-        let contract = VerifierContract::with_state(self.operator_pkh, self.input.state.clone());
+        let contract = self.input.contract_at(self.input.state.clone(), self.operator_pkh);
         let mut script = contract.unlocking_script(&self.witness);
         
         // Append signature and pubkey
-        script.extend(push_bytes(&self.operator_signature));
+        script.extend(push_bytes(&self.signature_bytes()));
         script.extend(push_bytes(&self.operator_pubkey));
         
         script
@@ -504,15 +1312,290 @@ impl ContractTransactionBuilder {
             step: self.input.state.step + 1,
         };
         
-        self.input.next_output(new_state, self.operator_pkh, value)
+        let contract = VerifierContract {
+            sighash_policy: self.sighash_policy.clone(),
+            ..self.input.contract_at(new_state, self.operator_pkh)
+        };
+        ContractOutput::new(&contract, value)
+    }
+
+    /// Anchor script committing the new state, when the policy anchors its step
+    pub fn build_anchor(&self) -> Option<Vec<u8>> {
+        let policy = self.anchor_policy?;
+        let state = self.build_output(0).state;
+        policy.anchors(state.step).then(|| anchor_script(&state))
+    }
+
+    /// Finalize the step for `params`' network: the default fee is paid out of
+    /// the input value and the next output is checked against dust and policy
+    pub fn finalize(&self, params: &ChainParams) -> Result<FinalizedStep, VerifierError> {
+        self.check_constants()?;
+        self.check_sighash_policy()?;
+        self.check_transcript_shape()?;
+        let fee = params.fee_for_size(self.estimate_tx_size());
+        let value = self.input.value.checked_sub(fee).ok_or(VerifierError::InsufficientValue)?;
+        if params.is_dust(value) {
+            return Err(VerifierError::DustOutput);
+        }
+        let output = self.build_output(value);
+        params.policy().check_script(&output.script_pubkey).map_err(|_| VerifierError::PolicyViolation)?;
+        Ok(FinalizedStep {
+            unlocking_script: self.build_unlocking_script(),
+            output,
+            anchor: self.build_anchor(),
+            fee,
+        })
+    }
+
+    /// Event for the transition this builder spends; `txid` is left unset
+    /// until the raw transaction is serialized (see `StateTransitionEvent::with_txid`)
+    pub fn transition_event(&self) -> StateTransitionEvent {
+        StateTransitionEvent::new(
+            self.input.state.clone(),
+            self.build_output(0).state,
+            self.witness.digest(),
+        )
+    }
+
+    /// Raw version-1 transaction spending the contract UTXO at `outpoint`
+    /// into the next contract output holding `value`, followed by a
+    /// zero-value anchor output when the step is anchored
+    pub fn build_transaction(&self, outpoint: OutPoint, value: u64) -> Vec<u8> {
+        let input = TxInput::new(outpoint, self.build_unlocking_script());
+        serialize_transaction(1, &[input], &self.transaction_outputs(value), 0)
+    }
+
+    /// Preimage the operator signs, SIGHASH_ALL | FORKID, for the
+    /// transaction `build_transaction(outpoint, value)` builds
+    pub fn sighash_preimage(&self, outpoint: OutPoint, value: u64) -> SighashPreimage {
+        let input = TxInput::new(outpoint, Vec::new());
+        SighashPreimageBuilder::new()
+            .prevouts(std::slice::from_ref(&input))
+            .input(&input)
+            .script_code(self.input.script_pubkey.clone())
+            .value(self.input.value)
+            .hash_outputs(double_sha256(&serialize_outputs(&self.transaction_outputs(value))))
+            .build()
+    }
+
+    fn transaction_outputs(&self, value: u64) -> Vec<(u64, Vec<u8>)> {
+        let output = self.build_output(value);
+        let mut outputs = vec![(output.value, output.script_pubkey)];
+        outputs.extend(self.build_anchor().map(|anchor| (0, anchor)));
+        outputs
     }
 
-    /// Estimate transaction size
+    /// Transaction size; the outpoint and output value do not affect it
     pub fn estimate_tx_size(&self) -> usize {
-        let input_size = self.build_unlocking_script().len() + 40;
-        let output_size = self.build_output(0).script_pubkey.len() + 8;
-        
-        4 + 1 + input_size + 1 + output_size + 4
+        self.build_transaction(OutPoint::new([0u8; 32], 0), 0).len()
+    }
+
+    /// Plan a chain of unconfirmed steps: `self` spends the contract UTXO at
+    /// `outpoint`, then each of `witnesses` spends the previous step's
+    /// output (vout 0). `fee_rate` is the package target in satoshis per
+    /// 1000 bytes; with `cpfp` the parents pay its lower rate and the final
+    /// child makes up the rest.
+    ///
+    /// `sign(step, preimage)` returns the operator signature for each step
+    /// (`sighash_preimage`); the steps are signed in order, so each txid
+    /// covers its signature before the next step spends it. Fees are sized
+    /// with the signature set on this builder, so set one at least as long
+    /// as any `sign` returns (73 bytes covers every DER signature and its
+    /// flag); a longer one fails with `InvalidSignature`.
+    pub fn plan_chain(
+        &self,
+        params: &ChainParams,
+        outpoint: OutPoint,
+        witnesses: &[IPAStepWitness],
+        fee_rate: u64,
+        cpfp: Option<CpfpPolicy>,
+        mut sign: impl FnMut(usize, &SighashPreimage) -> Vec<u8>,
+    ) -> Result<ChainedStepPlan, VerifierError> {
+        self.check_constants()?;
+        self.check_sighash_policy()?;
+        // Sizes do not depend on values, so fix the builders first
+        let mut builders = vec![self.chained_step(self.input.clone(), self.witness.clone())];
+        for witness in witnesses {
+            let input = builders.last().expect("first step").build_output(0);
+            builders.push(self.chained_step(input, witness.clone()));
+        }
+        for builder in &builders {
+            builder.check_transcript_shape()?;
+        }
+        let sizes: Vec<usize> = builders.iter().map(|b| b.estimate_tx_size()).collect();
+        let total_size: usize = sizes.iter().sum();
+        let at_rate = |size: usize, rate: u64| (size as u64 * rate).div_ceil(1000);
+        let fees: Vec<u64> = match cpfp {
+            None => sizes.iter().map(|&size| at_rate(size, fee_rate)).collect(),
+            Some(policy) => {
+                let mut fees: Vec<u64> = sizes.iter().map(|&size| at_rate(size, policy.parent_fee_rate)).collect();
+                let parents: u64 = fees[..fees.len() - 1].iter().sum();
+                let last = fees.len() - 1;
+                fees[last] = at_rate(total_size, fee_rate).saturating_sub(parents).max(fees[last]);
+                fees
+            }
+        };
+        let total_fee: u64 = fees.iter().sum();
+        if total_fee * 1000 < total_size as u64 * fee_rate {
+            return Err(VerifierError::PackageFeeTooLow);
+        }
+
+        let mut steps = Vec::with_capacity(builders.len());
+        let (mut spent, mut value) = (outpoint, self.input.value);
+        for (step, ((mut builder, size), fee)) in builders.into_iter().zip(sizes).zip(fees).enumerate() {
+            builder.input.value = value;
+            value = value.checked_sub(fee).ok_or(VerifierError::InsufficientValue)?;
+            if params.is_dust(value) {
+                return Err(VerifierError::DustOutput);
+            }
+            let output = builder.build_output(value);
+            params.policy().check_script(&output.script_pubkey).map_err(|_| VerifierError::PolicyViolation)?;
+            builder.operator_signature = sign(step, &builder.sighash_preimage(spent, value));
+            builder.check_sighash_policy()?;
+            let raw_tx = builder.build_transaction(spent, value);
+            if raw_tx.len() > size {
+                return Err(VerifierError::InvalidSignature);
+            }
+            let txid = double_sha256(&raw_tx);
+            let anchor = builder.build_anchor();
+            steps.push(PlannedStep { spends: spent, txid, raw_tx, fee, output, anchor });
+            spent = OutPoint::new(txid, 0);
+        }
+        Ok(ChainedStepPlan { steps })
+    }
+
+    fn chained_step(&self, input: ContractOutput, witness: IPAStepWitness) -> Self {
+        Self {
+            input,
+            witness,
+            operator_signature: self.operator_signature.clone(),
+            operator_pubkey: self.operator_pubkey.clone(),
+            operator_pkh: self.operator_pkh,
+            sighash_policy: self.sighash_policy.clone(),
+            keep_signature_encoding: self.keep_signature_encoding,
+            anchor_policy: self.anchor_policy,
+        }
+    }
+}
+
+/// A contract step ready for broadcast
+#[derive(Clone, Debug)]
+pub struct FinalizedStep {
+    pub unlocking_script: Vec<u8>,
+    pub output: ContractOutput,
+    /// Zero-value OP_RETURN output to add after `output`, if anchored
+    pub anchor: Option<Vec<u8>>,
+    pub fee: u64,
+}
+
+/// Child-pays-for-parent fee split for `ContractTransactionBuilder::plan_chain`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct CpfpPolicy {
+    /// Rate each step before the last pays, in satoshis per 1000 bytes
+    pub parent_fee_rate: u64,
+}
+
+impl CpfpPolicy {
+    pub fn new(parent_fee_rate: u64) -> Self {
+        Self { parent_fee_rate }
+    }
+
+    /// Parents pay nothing; the final child carries the whole package fee
+    pub fn zero_fee_parents() -> Self {
+        Self::new(0)
+    }
+}
+
+/// Anchor layout version, the first push of every anchor script
+pub const ANCHOR_VERSION: u8 = 1;
+
+/// Periodic checkpoints a light client can start auditing from instead of
+/// genesis: every `every_n_steps`-th state is also committed in a zero-value
+/// OP_RETURN output of the transaction that creates it
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct AnchorPolicy {
+    pub every_n_steps: u32,
+}
+
+impl AnchorPolicy {
+    pub fn new(every_n_steps: u32) -> Self {
+        Self { every_n_steps }
+    }
+
+    /// Whether the state at `step` is anchored; never for a zero interval
+    /// or the genesis state
+    pub fn anchors(&self, step: u32) -> bool {
+        step != 0 && step.is_multiple_of(self.every_n_steps)
+    }
+}
+
+/// `OP_FALSE OP_RETURN <version> <step (4, LE)> <transcript hash> <app root>`
+pub fn anchor_script(state: &IPAAccumulator) -> Vec<u8> {
+    OpReturnData::new()
+        .push(vec![ANCHOR_VERSION])
+        .push(state.step.to_le_bytes())
+        .push(state.transcript_hash)
+        .push(state.app_state_root)
+        .to_script()
+}
+
+/// State committed by an `anchor_script`; `None` for any other script or
+/// an unknown version
+pub fn parse_anchor(script: &[u8]) -> Option<IPAAccumulator> {
+    let pushes = parse_pushes(script.strip_prefix(&[OP_FALSE, OP_RETURN])?)?;
+    let [version, step, transcript_hash, app_state_root] = pushes.as_slice() else {
+        return None;
+    };
+    if version.as_slice() != [ANCHOR_VERSION] {
+        return None;
+    }
+    Some(IPAAccumulator {
+        transcript_hash: transcript_hash.as_slice().try_into().ok()?,
+        app_state_root: app_state_root.as_slice().try_into().ok()?,
+        step: u32::from_le_bytes(step.as_slice().try_into().ok()?),
+    })
+}
+
+/// One transaction of a chained plan
+#[derive(Clone, Debug)]
+pub struct PlannedStep {
+    /// Outpoint this step spends; unconfirmed for every step after the first
+    pub spends: OutPoint,
+    /// Internal byte order, as in `OutPoint::txid`
+    pub txid: [u8; 32],
+    pub raw_tx: Vec<u8>,
+    pub fee: u64,
+    pub output: ContractOutput,
+    /// Anchor script `raw_tx` carries as its second output, if anchored
+    pub anchor: Option<Vec<u8>>,
+}
+
+/// Contract steps to broadcast in order, each spending the one before
+#[derive(Clone, Debug)]
+pub struct ChainedStepPlan {
+    pub steps: Vec<PlannedStep>,
+}
+
+impl ChainedStepPlan {
+    pub fn raw_transactions(&self) -> Vec<Vec<u8>> {
+        self.steps.iter().map(|step| step.raw_tx.clone()).collect()
+    }
+
+    pub fn total_fee(&self) -> u64 {
+        self.steps.iter().map(|step| step.fee).sum()
+    }
+
+    pub fn total_size(&self) -> usize {
+        self.steps.iter().map(|step| step.raw_tx.len()).sum()
+    }
+
+    /// Package fee rate in satoshis per 1000 bytes, rounded down
+    pub fn package_fee_rate(&self) -> u64 {
+        (self.total_fee() * 1000).checked_div(self.total_size() as u64).unwrap_or(0)
+    }
+
+    pub fn final_output(&self) -> Option<&ContractOutput> {
+        self.steps.last().map(|step| &step.output)
     }
 }
 
@@ -524,13 +1607,15 @@ impl ContractTransactionBuilder {
 pub fn analyze_contract_sizes() -> ContractSizeReport {
     let operator_pkh = [0u8; 20];
     let initial_state = IPAAccumulator::new([1u8; 32]);
-    let contract = VerifierContract::new(operator_pkh, initial_state);
+    // Lock unrolled for the typical proof below
+    let contract = VerifierContract::new(operator_pkh, initial_state)
+        .with_transcript_shape(TranscriptShape::new(2, 10));
     
     let locking_size = contract.locking_script_size();
-    let constants_size = contract.constants.witness_size();
     
     // Estimate unlocking for typical IPA proof (10 rounds = 20 L/R terms)
     let typical_witness = IPAStepWitness {
+        operator_binding: operator_binding(&operator_pkh),
         public_inputs: vec![[0u8; 32]; 2],      // 2 public inputs
         l_terms: vec![[[0u8; 32]; 2]; 10],      // 10 L terms
         r_terms: vec![[[0u8; 32]; 2]; 10],      // 10 R terms
@@ -544,7 +1629,6 @@ pub fn analyze_contract_sizes() -> ContractSizeReport {
     
     ContractSizeReport {
         locking_script: locking_size,
-        constants_blob: constants_size,
         typical_unlocking: unlocking_size,
         witness_data: typical_witness.size(),
     }
@@ -553,7 +1637,632 @@ pub fn analyze_contract_sizes() -> ContractSizeReport {
 #[derive(Debug)]
 pub struct ContractSizeReport {
     pub locking_script: usize,
-    pub constants_blob: usize,
     pub typical_unlocking: usize,
     pub witness_data: usize,
 }
+
+// ============================================================================
+// TESTS
+// ============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ghost::script::introspect::replace_push_at;
+
+    #[test]
+    fn test_unlocking_stack_items_match_script() {
+        let contract = VerifierContract::new([0x11; 20], IPAAccumulator::new([0x22; 32]));
+        let mut witness = IPAStepWitness::new_minimal([0x33; 32]);
+        witness.public_inputs = vec![[0x01; 32], [0x02; 32]];
+        witness.l_terms = vec![[[0x03; 32], [0x04; 32]]];
+        witness.r_terms = vec![[[0x05; 32], [0x06; 32]]];
+        witness.b_scalar = Some([0x07; 32]);
+
+        let items = contract.unlocking_stack_items(&witness);
+        let repushed: Vec<u8> = items.iter().flat_map(|item| push_bytes(item)).collect();
+        assert_eq!(repushed, contract.unlocking_script(&witness));
+        assert_eq!(items.len(), 1 + 1 + 1 + 2 + 1 + 4 + 2 + 1);
+    }
+
+    #[test]
+    fn test_lr_points_decompress_to_affine_terms() {
+        use crate::ghost::crypto::FieldExt;
+        let (x, y) = (1u64..)
+            .map(Fp::from_u64)
+            .find_map(|x| Option::<Fp>::from((x.square() * x + Fp::from_u64(5)).sqrt()).map(|y| (x, y)))
+            .unwrap();
+        let point = CompressedPoint::from_affine(&x, &y);
+        let witness = IPAStepWitness::new_minimal([0u8; 32]).with_lr_points(&[(point, point)]).unwrap();
+        assert_eq!(witness.l_terms, vec![[fp_to_bytes(&x), fp_to_bytes(&y)]]);
+        assert_eq!(witness.r_terms, witness.l_terms);
+
+        // A 32-byte field element padded into a point slot is rejected, not reinterpreted
+        let mut padded = [0u8; 33];
+        padded[1..].copy_from_slice(&fp_to_bytes(&x));
+        let result = IPAStepWitness::new_minimal([0u8; 32]).with_lr_points(&[(padded.into(), point)]);
+        assert!(matches!(result, Err(VerifierError::InvalidPoint)));
+    }
+
+    #[test]
+    fn test_framing_separates_section_boundaries() {
+        // Unframed, both witnesses absorb prev, e1..e4, a
+        let elements = [[0x01; 32], [0x02; 32], [0x03; 32], [0x04; 32]];
+        let mut as_inputs = IPAStepWitness::new_minimal([0u8; 32]);
+        as_inputs.public_inputs = elements.to_vec();
+        let mut as_terms = IPAStepWitness::new_minimal([0u8; 32]);
+        as_terms.l_terms = vec![[elements[0], elements[1]]];
+        as_terms.r_terms = vec![[elements[2], elements[3]]];
+
+        let prev = [0x22; 32];
+        assert_ne!(as_inputs.compute_transcript_hash(&prev), as_terms.compute_transcript_hash(&prev));
+        let contract = VerifierContract::new([0x11; 20], IPAAccumulator::new(prev));
+        assert_ne!(contract.unlocking_script(&as_inputs), contract.unlocking_script(&as_terms));
+    }
+
+    #[test]
+    fn test_contract_id_stable_across_steps() {
+        let genesis = VerifierContract::new([0x11; 20], IPAAccumulator::new([0x22; 32]));
+        let mut witness = IPAStepWitness::new_minimal([0u8; 32]);
+        witness.operator_binding = genesis.operator_binding();
+        let next = witness.compute_transcript_hash(&genesis.current_state.transcript_hash);
+        witness.next_transcript_hash = fp_to_bytes(&next);
+        let stepped = genesis.apply_transition(&witness).unwrap();
+        assert_eq!(stepped.current_state.step, 1);
+        assert_eq!(stepped.contract_id(), genesis.contract_id());
+
+        let mut later = genesis.current_state.clone();
+        later.step = 7;
+        let resumed = VerifierContract::resume([0x11; 20], genesis.genesis_state_hash, later);
+        assert_eq!(resumed.contract_id(), genesis.contract_id());
+    }
+
+    #[test]
+    fn test_contract_id_differs_by_operator() {
+        let a = VerifierContract::new([0x11; 20], IPAAccumulator::new([0x22; 32]));
+        let b = VerifierContract::new([0x12; 20], IPAAccumulator::new([0x22; 32]));
+        assert_ne!(a.contract_id(), b.contract_id());
+    }
+
+    fn chained_witness(contract: &VerifierContract, tag: u8) -> IPAStepWitness {
+        let mut witness = IPAStepWitness::new_minimal([0u8; 32]);
+        witness.operator_binding = contract.operator_binding();
+        witness.public_inputs = vec![[tag; 32]];
+        witness.new_app_state = Some([tag; 32]);
+        witness.next_transcript_hash = fp_to_bytes(&witness.compute_transcript_hash(&contract.current_state.transcript_hash));
+        witness
+    }
+
+    #[test]
+    fn test_transition_events_chain() {
+        let mut contract = VerifierContract::new([0x11; 20], IPAAccumulator::new([0x22; 32]));
+        let genesis = contract.clone();
+        let mut witnesses = Vec::new();
+        for tag in 1..=5u8 {
+            let witness = chained_witness(&contract, tag);
+            contract = contract.apply_transition(&witness).unwrap();
+            witnesses.push(witness);
+        }
+
+        let mut events: Vec<StateTransitionEvent> = Vec::new();
+        let last = genesis.apply_transitions(&witnesses, &mut events).unwrap();
+        assert_eq!(last.current_state, contract.current_state);
+        assert_eq!(events.len(), 5);
+        assert_eq!(events[0].prev_state, genesis.current_state);
+        for (i, pair) in events.windows(2).enumerate() {
+            assert_eq!(pair[1].prev_state, pair[0].new_state);
+            assert_eq!(pair[0].step, i as u32 + 1);
+            assert_ne!(pair[1].event_id(), pair[0].event_id());
+        }
+        assert_eq!(events[4].new_state, last.current_state);
+        assert!(genesis.apply_transitions(&witnesses, &mut NoopEventSink).is_ok());
+    }
+
+    #[test]
+    fn test_event_id_ignores_txid_and_timestamp() {
+        let contract = VerifierContract::new([0x11; 20], IPAAccumulator::new([0x22; 32]));
+        let witness = chained_witness(&contract, 9);
+        let (_, event) = contract.apply_transition_with_event(&witness).unwrap();
+        let mut stamped = event.clone().with_txid([0xee; 32]);
+        stamped.timestamp += 60;
+        assert_eq!(stamped.event_id(), event.event_id());
+
+        let builder = ContractTransactionBuilder::new(ContractOutput::new(&contract, 1_000), witness, [0x11; 20]);
+        assert_eq!(builder.transition_event().event_id(), event.event_id());
+    }
+
+    #[test]
+    fn test_finalize_pays_network_fee() {
+        use crate::ghost::script::network::Network;
+        let contract = VerifierContract::new([0x11; 20], IPAAccumulator::new([0x22; 32]));
+        let input = ContractOutput::new(&contract, 100_000);
+        let builder = ContractTransactionBuilder::new(input, IPAStepWitness::new_minimal([0x33; 32]), [0x11; 20]);
+
+        let mainnet = ChainParams::for_network(Network::Mainnet);
+        let step = builder.finalize(&mainnet).unwrap();
+        assert_eq!(step.fee, mainnet.fee_for_size(builder.estimate_tx_size()));
+        assert_eq!(step.output.value + step.fee, 100_000);
+
+        let regtest = builder.finalize(&ChainParams::for_network(Network::Regtest)).unwrap();
+        assert_eq!(regtest.fee, 0);
+    }
+
+    #[test]
+    fn test_build_transaction_spends_outpoint() {
+        let contract = VerifierContract::new([0x11; 20], IPAAccumulator::new([0x22; 32]));
+        let builder = ContractTransactionBuilder::new(
+            ContractOutput::new(&contract, 100_000),
+            IPAStepWitness::new_minimal([0x33; 32]),
+            [0x11; 20],
+        );
+        let outpoint = OutPoint::new([0xab; 32], 1);
+        let tx = builder.build_transaction(outpoint, 99_000);
+        assert_eq!(tx.len(), builder.estimate_tx_size());
+        // version (4) || input count (1) || outpoint
+        assert_eq!(&tx[5..41], &outpoint.to_bytes());
+        let output = builder.build_output(99_000);
+        assert!(tx.ends_with(&[output.script_pubkey.as_slice(), &[0u8; 4]].concat()));
+    }
+
+    #[test]
+    fn test_plan_chain_final_child_pays_package_fee() {
+        let contract = VerifierContract::new([0x11; 20], IPAAccumulator::new([0x22; 32]));
+        let mut witnesses = Vec::new();
+        let mut state = contract.clone();
+        for tag in 1..=3u8 {
+            let witness = chained_witness(&state, tag);
+            state = state.apply_transition(&witness).unwrap();
+            witnesses.push(witness);
+        }
+        let input_value = 1_000_000;
+        let builder = ContractTransactionBuilder::new(ContractOutput::new(&contract, input_value), witnesses[0].clone(), [0x11; 20])
+            .with_signature(vec![0x30; 72], vec![0x02; 33]);
+        let params = ChainParams::mainnet();
+        let outpoint = OutPoint::new([0xab; 32], 1);
+        let fee_rate = 1_000;
+
+        let sign = |_: usize, _: &SighashPreimage| vec![0x30; 72];
+        let plan = builder
+            .plan_chain(&params, outpoint, &witnesses[1..], fee_rate, Some(CpfpPolicy::zero_fee_parents()), sign)
+            .unwrap();
+        assert_eq!(plan.steps.len(), 3);
+        assert_eq!(plan.steps[0].fee, 0);
+        assert_eq!(plan.steps[1].fee, 0);
+        assert_eq!(plan.steps[2].fee, plan.total_size() as u64 * fee_rate / 1000);
+        assert!(plan.package_fee_rate() >= fee_rate);
+        // Values only ever shrink by the fees
+        let final_value = plan.final_output().unwrap().value;
+        assert_eq!(input_value - final_value, plan.total_fee());
+        assert_eq!(plan.steps[1].output.value, input_value);
+        // Each step spends the previous step's txid
+        assert_eq!(plan.steps[0].spends, outpoint);
+        for pair in plan.steps.windows(2) {
+            assert_eq!(pair[1].spends, OutPoint::new(double_sha256(&pair[0].raw_tx), 0));
+            assert_eq!(pair[1].spends.txid, pair[0].txid);
+        }
+        assert_eq!(plan.raw_transactions().iter().map(Vec::len).sum::<usize>(), plan.total_size());
+        assert_eq!(plan.final_output().unwrap().state, state.current_state);
+
+        // Without CPFP every step pays its own way, for about the same total
+        let plain = builder.plan_chain(&params, outpoint, &witnesses[1..], fee_rate, None, sign).unwrap();
+        assert!(plain.steps.iter().all(|step| step.fee > 0));
+        assert_eq!(plain.total_size(), plan.total_size());
+        assert!(plain.total_fee() >= plan.total_fee());
+
+        // Every output, the last included, must stay above dust
+        let funded = |value: u64| {
+            ContractTransactionBuilder::new(ContractOutput::new(&contract, value), witnesses[0].clone(), [0x11; 20])
+                .with_signature(vec![0x30; 72], vec![0x02; 33])
+                .plan_chain(&params, outpoint, &witnesses[1..], fee_rate, None, sign)
+        };
+        assert!(matches!(funded(plain.total_fee()), Err(VerifierError::DustOutput)));
+        assert!(matches!(funded(100), Err(VerifierError::InsufficientValue)));
+        assert!(funded(plain.total_fee() + 1).is_ok());
+    }
+
+    #[test]
+    fn test_plan_chain_signs_each_step_in_order() {
+        use crate::ghost::script::tx::Transaction;
+        let contract = VerifierContract::new([0x11; 20], IPAAccumulator::new([0x22; 32]));
+        let mut witnesses = Vec::new();
+        let mut state = contract.clone();
+        for tag in 1..=3u8 {
+            let witness = chained_witness(&state, tag);
+            state = state.apply_transition(&witness).unwrap();
+            witnesses.push(witness);
+        }
+        let builder = ContractTransactionBuilder::new(ContractOutput::new(&contract, 1_000_000), witnesses[0].clone(), [0x11; 20])
+            .with_signature(vec![0x30; 73], vec![0x02; 33]);
+        let params = ChainParams::mainnet();
+        let outpoint = OutPoint::new([0xab; 32], 1);
+        let mut preimages = Vec::new();
+        let plan = builder
+            .plan_chain(&params, outpoint, &witnesses[1..], 1_000, None, |step, preimage| {
+                preimages.push(preimage.clone());
+                vec![0x40 + step as u8; 72]
+            })
+            .unwrap();
+        assert_eq!(preimages.len(), 3);
+        for (step, (planned, preimage)) in plan.steps.iter().zip(&preimages).enumerate() {
+            // Each step carries its own signature, and its txid covers it
+            let tx = Transaction::parse(&planned.raw_tx).unwrap();
+            assert!(tx.inputs[0].script_sig.ends_with(&[push_bytes(&[0x40 + step as u8; 72]), push_bytes(&[0x02; 33])].concat()));
+            assert_eq!(planned.txid, double_sha256(&planned.raw_tx));
+            // Signed over the outpoint it spends and the outputs it pays
+            assert_eq!(preimage.outpoint, planned.spends.to_bytes());
+            assert_eq!(preimage.hash_outputs, double_sha256(&serialize_outputs(&tx.outputs)));
+        }
+        for pair in plan.steps.windows(2) {
+            assert_eq!(pair[1].spends, OutPoint::new(pair[0].txid, 0));
+        }
+        // A signature longer than the one the fees were sized for
+        let err = builder.plan_chain(&params, outpoint, &witnesses[1..], 1_000, None, |_, _| vec![0x30; 74]).err().unwrap();
+        assert!(matches!(err, VerifierError::InvalidSignature));
+    }
+
+    #[test]
+    fn test_contract_anchor_policy_reaches_builder() {
+        let contract = VerifierContract::new([0x11; 20], IPAAccumulator::new([0x22; 32]))
+            .with_anchor_policy(AnchorPolicy::new(2));
+        let mut witnesses = Vec::new();
+        let mut state = contract.clone();
+        for tag in 1..=3u8 {
+            let witness = chained_witness(&state, tag);
+            state = state.apply_transition(&witness).unwrap();
+            witnesses.push(witness);
+        }
+        let output = ContractOutput::new(&contract, 1_000_000);
+        assert_eq!(output.anchor_policy, Some(AnchorPolicy::new(2)));
+        let builder = ContractTransactionBuilder::new(output, witnesses[0].clone(), [0x11; 20])
+            .with_signature(vec![0x30; 72], vec![0x02; 33]);
+        let anchored = |builder: &ContractTransactionBuilder| -> Vec<u32> {
+            let plan = builder
+                .plan_chain(&ChainParams::mainnet(), OutPoint::new([0xab; 32], 0), &witnesses[1..], 1_000, None, |_, _| vec![0x30; 72])
+                .unwrap();
+            assert!(plan.steps.iter().all(|step| step.output.anchor_policy == Some(AnchorPolicy::new(2))));
+            plan.steps.iter().filter(|step| step.anchor.is_some()).map(|step| step.output.state.step).collect()
+        };
+        assert_eq!(anchored(&builder), [2]);
+        // The builder's own policy takes over for the transactions it plans
+        assert_eq!(anchored(&builder.with_anchor_policy(AnchorPolicy::new(3))), [3]);
+    }
+
+    #[test]
+    fn test_locking_sections_cover_script() {
+        let contract = VerifierContract::new([0x11; 20], IPAAccumulator::new([0x22; 32]));
+        let sections = contract.locking_sections();
+        let joined: Vec<u8> = sections.iter().flat_map(|(_, bytes)| bytes.clone()).collect();
+        assert_eq!(joined, contract.locking_script());
+        let mut start = 0;
+        for (section, bytes) in &sections {
+            assert_eq!(section_at(&sections, start), *section);
+            assert_eq!(section_at(&sections, start + bytes.len() - 1), *section);
+            start += bytes.len();
+        }
+    }
+
+    #[test]
+    fn test_operator_sighash_policy() {
+        use crate::ghost::crypto::hash160;
+        use crate::ghost::script::vm::VmErrorKind;
+        use crate::ghost::script::OP_1;
+        let pubkey = [0x02; 33];
+        let operator_pkh = hash160(&pubkey);
+        let contract = VerifierContract::new(operator_pkh, IPAAccumulator::new([0x22; 32]))
+            .with_sighash_policy(SighashPolicy::all_only());
+        let section = contract.locking_sections().into_iter()
+            .find(|(section, _)| *section == ContractSection::OperatorSignature)
+            .map(|(_, bytes)| bytes)
+            .unwrap();
+        let mut script = push_bytes(&operator_pkh);
+        script.push(OP_TOALTSTACK);
+        script.extend(section);
+        // The section ends in CHECKSIGVERIFY
+        script.push(OP_1);
+        let run = |flag: u8| {
+            let signature = [vec![0x30; 70], vec![flag]].concat();
+            Interpreter::with_checker(Box::new(MockSignatureChecker)).eval_with_stack(vec![signature, pubkey.to_vec()], &script).map_err(|e| e.kind)
+        };
+        assert_eq!(run(0x41), Ok(true));
+        assert_eq!(run(0x42), Err(VmErrorKind::VerifyFailed));
+
+        let output = ContractOutput::new(&contract, 10_000);
+        let builder = |flag: u8| ContractTransactionBuilder::new(output.clone(), chained_witness(&contract, 1), operator_pkh)
+            .with_signature([vec![0x30; 70], vec![flag]].concat(), pubkey.to_vec())
+            .with_sighash_policy(SighashPolicy::all_only());
+        assert!(builder(0x41).check_sighash_policy().is_ok());
+        assert!(matches!(builder(0x42).finalize(&ChainParams::mainnet()), Err(VerifierError::SighashFlagNotAllowed)));
+        // The next output keeps the policy
+        let next = builder(0x41).build_output(9_000);
+        let expected = VerifierContract::with_state(operator_pkh, next.state.clone()).with_sighash_policy(SighashPolicy::all_only());
+        assert_eq!(next.script_pubkey, expected.locking_script());
+    }
+
+    #[test]
+    fn test_simulate_step_failure_is_located() {
+        let contract = VerifierContract::new([0x11; 20], IPAAccumulator::new([0x22; 32]));
+        let witness = chained_witness(&contract, 1);
+        // The pubkey does not hash to the operator PKH
+        let SimulationResult::Failure(failure) = contract.simulate_step(&witness, &[0x30; 71], &[0x02; 33]) else {
+            panic!("a pubkey that is not the operator's must fail");
+        };
+        let sections = contract.locking_sections();
+        let signature_start: usize = sections
+            .iter()
+            .take_while(|(section, _)| *section != ContractSection::OperatorSignature)
+            .map(|(_, bytes)| bytes.len())
+            .sum();
+        assert_eq!(failure.section, ContractSection::OperatorSignature);
+        assert_eq!(failure.offset, signature_start + 3);
+        assert_eq!(failure.opcode, OP_EQUALVERIFY);
+        assert_eq!(failure.kind, Some(VmErrorKind::EqualVerifyFailed));
+        assert_eq!(section_at(&sections, failure.offset), failure.section);
+    }
+
+    #[test]
+    fn test_locking_script_runs_with_valid_witness() {
+        let (sig, pubkey) = ([0x30; 71], [0x02; 33]);
+        let operator_pkh = crate::ghost::crypto::hash160(&pubkey);
+        let genesis = VerifierContract::new(operator_pkh, IPAAccumulator::new([0x22; 32]));
+        let witness_constants = VerifierContract::with_poseidon_strategy(
+            operator_pkh,
+            IPAAccumulator::new([0x22; 32]),
+            StrategyChoice::WitnessConstants,
+        );
+        for contract in [genesis.clone(), witness_constants] {
+            let witness = chained_witness(&contract, 1);
+            assert!(contract.apply_transition(&witness).is_ok());
+            let mut script = contract.unlocking_script(&witness);
+            script.extend(push_bytes(&sig));
+            script.extend(push_bytes(&pubkey));
+            script.extend(contract.locking_script());
+            let mut vm = Interpreter::with_checker(Box::new(MockSignatureChecker));
+            vm.eval(&script).unwrap();
+            assert_eq!(vm.stack(), [vec![1u8]]);
+            assert!(vm.alt_stack().is_empty());
+
+            // The next state's lock takes the next step
+            let next = contract.apply_transition(&witness).unwrap();
+            assert!(next.simulate_step(&chained_witness(&next, 2), &sig, &pubkey).is_success());
+        }
+
+        // A claimed hash off by one fails the final comparison
+        let mut witness = chained_witness(&genesis, 1);
+        witness.next_transcript_hash[0] ^= 1;
+        let SimulationResult::Failure(failure) = genesis.simulate_step(&witness, &sig, &pubkey) else {
+            panic!("wrong transcript hash simulated successfully");
+        };
+        assert_eq!((failure.section, failure.kind), (ContractSection::Poseidon, None));
+
+        // Elements past the unrolled slots are left over, and refused off chain
+        let mut witness = chained_witness(&genesis, 1);
+        witness.public_inputs = vec![[0x01; 32]; genesis.transcript_shape.max_elements()];
+        witness.next_transcript_hash = fp_to_bytes(&witness.compute_transcript_hash(&genesis.current_state.transcript_hash));
+        assert!(matches!(
+            genesis.apply_transition(&witness),
+            Err(VerifierError::TranscriptTooLong { elements: 22, capacity: 16 })
+        ));
+        let SimulationResult::Failure(failure) = genesis.simulate_step(&witness, &sig, &pubkey) else {
+            panic!("oversized witness simulated successfully");
+        };
+        assert_eq!(failure.section, ContractSection::Poseidon);
+        let builder = ContractTransactionBuilder::new(ContractOutput::new(&genesis, 10_000_000), witness, operator_pkh);
+        assert!(matches!(
+            builder.finalize(&ChainParams::mainnet()),
+            Err(VerifierError::TranscriptTooLong { elements: 22, capacity: 16 })
+        ));
+
+        // Another state's prev_state and another operator's key are refused
+        let stale = VerifierContract::new(operator_pkh, IPAAccumulator::new([0x23; 32]));
+        let mut script = stale.unlocking_script(&chained_witness(&stale, 1));
+        script.extend(push_bytes(&sig));
+        script.extend(push_bytes(&pubkey));
+        script.extend(genesis.locking_script());
+        let mut vm = Interpreter::with_checker(Box::new(MockSignatureChecker));
+        assert_eq!(vm.eval(&script).map_err(|e| e.kind), Err(VmErrorKind::EqualVerifyFailed));
+        let failure = genesis.simulate_step(&chained_witness(&genesis, 1), &sig, &[0x03; 33]);
+        assert!(matches!(failure, SimulationResult::Failure(f) if f.section == ContractSection::OperatorSignature));
+    }
+
+    #[test]
+    fn test_operator_binding_checked_on_chain() {
+        let (sig, pubkey) = ([0x30; 71], [0x02; 33]);
+        let operator_pkh = crate::ghost::crypto::hash160(&pubkey);
+        let contract = VerifierContract::new(operator_pkh, IPAAccumulator::new([0x22; 32]));
+        // A consistent transcript, bound to someone else
+        let mut witness = chained_witness(&contract, 1);
+        witness.operator_binding = operator_binding(&[0x99; 20]);
+        witness.next_transcript_hash = fp_to_bytes(&witness.compute_transcript_hash(&contract.current_state.transcript_hash));
+        assert!(witness.verify(&contract.current_state.transcript_hash));
+        let SimulationResult::Failure(failure) = contract.simulate_step(&witness, &sig, &pubkey) else {
+            panic!("a witness bound to another operator must fail");
+        };
+        assert_eq!(failure.section, ContractSection::Poseidon);
+        assert_eq!(failure.kind, Some(VmErrorKind::EqualVerifyFailed));
+    }
+
+    #[test]
+    fn test_lock_sized_from_transcript_shape() {
+        let (sig, pubkey) = ([0x30; 71], [0x02; 33]);
+        let operator_pkh = crate::ghost::crypto::hash160(&pubkey);
+        let state = IPAAccumulator::new([0x22; 32]);
+        let size = |shape| VerifierContract::new(operator_pkh, state.clone()).with_transcript_shape(shape).locking_script_size();
+        // Every element slot costs the same
+        let slot = size(TranscriptShape::new(1, 3)) - size(TranscriptShape::default());
+        assert_eq!(slot % 4, 0);
+        assert_eq!(size(TranscriptShape::new(2, 10)) - size(TranscriptShape::default()), 33 * slot / 4);
+
+        // A 10-round step spends a lock unrolled for it, within relay policy
+        let contract = VerifierContract::with_poseidon_strategy(operator_pkh, state, StrategyChoice::WitnessConstants)
+            .with_transcript_shape(TranscriptShape::new(2, 10));
+        let mut witness = chained_witness(&contract, 1);
+        witness.public_inputs = vec![[0x01; 32], [0x02; 32]];
+        witness.l_terms = vec![[[0x03; 32], [0x04; 32]]; 10];
+        witness.r_terms = vec![[[0x05; 32], [0x06; 32]]; 10];
+        witness.b_scalar = Some([0x07; 32]);
+        witness.next_transcript_hash = fp_to_bytes(&witness.compute_transcript_hash(&contract.current_state.transcript_hash));
+        assert_eq!(witness.transcript_elements().len(), contract.transcript_shape.max_elements());
+        assert!(contract.simulate_step(&witness, &sig, &pubkey).is_success());
+        let builder = ContractTransactionBuilder::new(ContractOutput::new(&contract, 10_000_000), witness, operator_pkh)
+            .with_signature(sig.to_vec(), pubkey.to_vec());
+        let step = builder.finalize(&ChainParams::mainnet()).unwrap();
+        assert_eq!(step.output.transcript_shape, contract.transcript_shape);
+    }
+
+    #[test]
+    fn test_simulate_step_pinpoints_corrupted_l_term() {
+        let (sig, pubkey) = ([0x30; 71], [0x02; 33]);
+        let operator_pkh = crate::ghost::crypto::hash160(&pubkey);
+        let contract = VerifierContract::new(operator_pkh, IPAAccumulator::new([0x22; 32]));
+        let mut witness = chained_witness(&contract, 1);
+        witness.l_terms = vec![[[0x03; 32], [0x04; 32]]];
+        witness.r_terms = vec![[[0x05; 32], [0x06; 32]]];
+        witness.next_transcript_hash = fp_to_bytes(&witness.compute_transcript_hash(&contract.current_state.transcript_hash));
+        assert!(contract.simulate_step(&witness, &sig, &pubkey).is_success());
+        witness.l_terms[0][0][0] ^= 1;
+        match contract.simulate_step(&witness, &sig, &pubkey) {
+            SimulationResult::Failure(failure) => assert_eq!(failure.section, ContractSection::Poseidon),
+            SimulationResult::Success(_) => panic!("corrupted L-term simulated successfully"),
+        }
+    }
+
+    #[test]
+    fn test_new_app_state_is_authenticated() {
+        use crate::ghost::script::proof_generator::{generate_mock_proof_with_state, generate_mock_state_transition};
+        use crate::ghost::script::vm::execute;
+        use crate::ghost::script::{TranscriptKind, OP_NUMEQUAL};
+        // The mock transition proves ten rounds
+        let contract = VerifierContract::new([0x11; 20], IPAAccumulator::new([0x22; 32]))
+            .with_transcript_shape(TranscriptShape::new(1, 10));
+        let prev = contract.current_state.transcript_hash;
+        let transition = generate_mock_state_transition(&contract, [0x14; 32]);
+        assert!(transition.verify(&prev));
+        assert_eq!(contract.apply_transition(&transition).unwrap().current_state.app_state_root, [0x14; 32]);
+
+        // Absorb the pushed witness items as the script does and compare with the claimed hash
+        let on_chain = |witness: &IPAStepWitness| {
+            let items = contract.unlocking_stack_items(witness);
+            let mut script = push_bytes(&prev);
+            script.push(OP_TOALTSTACK);
+            for item in &items[1..items.len() - 1] {
+                script.extend(push_bytes(item));
+                script.extend(TranscriptKind::Poseidon.absorb());
+            }
+            script.push(OP_FROMALTSTACK);
+            script.extend(push_bytes(&witness.next_transcript_hash));
+            script.push(OP_NUMEQUAL);
+            execute(&script).is_ok_and(|stack| stack.last() == Some(&vec![1u8]))
+        };
+        let witness = generate_mock_proof_with_state(&prev, 2, vec![[0x14; 32]], Some([0x14; 32]));
+        assert!(witness.verify(&prev));
+        assert!(on_chain(&witness));
+
+        // Under schema v1 the root was not absorbed and this forgery verified
+        let mut forged = witness.clone();
+        forged.new_app_state = Some([0x15; 32]);
+        assert!(!forged.verify(&prev));
+        assert!(!on_chain(&forged));
+        assert!(matches!(contract.apply_transition(&forged), Err(VerifierError::InvalidTranscript)));
+    }
+
+    #[test]
+    fn test_witness_bound_to_operator() {
+        use crate::ghost::script::proof_generator::generate_mock_state_transition;
+        // Same accumulator state, as on a fork recreated under another operator
+        let shape = TranscriptShape::new(1, 10);
+        let operator_a = VerifierContract::new([0xaa; 20], IPAAccumulator::new([0x22; 32])).with_transcript_shape(shape);
+        let operator_b = VerifierContract::new([0xbb; 20], IPAAccumulator::new([0x22; 32])).with_transcript_shape(shape);
+        assert_eq!(operator_a.current_state, operator_b.current_state);
+
+        let witness = generate_mock_state_transition(&operator_a, [0x14; 32]);
+        assert_eq!(witness.operator_binding, operator_binding(&[0xaa; 20]));
+        assert!(operator_a.apply_transition(&witness).is_ok());
+        assert!(matches!(operator_b.apply_transition(&witness), Err(VerifierError::OperatorMismatch)));
+
+        // Rebinding without regenerating breaks the transcript
+        let mut rebound = witness.clone();
+        rebound.operator_binding = operator_b.operator_binding();
+        assert!(matches!(operator_b.apply_transition(&rebound), Err(VerifierError::InvalidTranscript)));
+        let for_b = generate_mock_state_transition(&operator_b, [0x14; 32]);
+        assert_ne!(for_b.next_transcript_hash, witness.next_transcript_hash);
+        assert!(operator_b.apply_transition(&for_b).is_ok());
+    }
+
+    #[test]
+    fn test_contract_output_utxo_key() {
+        let contract = VerifierContract::new([0x11; 20], IPAAccumulator::new([0x22; 32]));
+        let output = ContractOutput::new(&contract, 100_000);
+        assert_eq!(output.utxo_key(), UtxoKey::for_script(&contract.locking_script()));
+        // Keyed by script alone: value does not matter, state does
+        assert_eq!(ContractOutput::new(&contract, 1).utxo_key(), output.utxo_key());
+        let next = output.next_output(IPAAccumulator::new([0x23; 32]), [0x11; 20], 100_000);
+        assert_ne!(next.utxo_key(), output.utxo_key());
+    }
+
+    #[test]
+    fn test_operator_signature_normalized() {
+        let contract = VerifierContract::new([0x11; 20], IPAAccumulator::new([0x22; 32]));
+        let output = ContractOutput::new(&contract, 10_000);
+        // R with a redundant leading zero, then the flag byte
+        let canonical = [&[0x30, 0x08, 0x02, 0x02, 0x00, 0x80, 0x02, 0x02, 0x01, 0x02][..], &[0x41]].concat();
+        let padded = [&[0x30, 0x09, 0x02, 0x03, 0x00, 0x00, 0x80, 0x02, 0x02, 0x01, 0x02][..], &[0x41]].concat();
+        let builder = |signature: &[u8]| ContractTransactionBuilder::new(output.clone(), chained_witness(&contract, 1), [0x11; 20])
+            .with_signature(signature.to_vec(), vec![0x02; 33]);
+        assert_eq!(builder(&padded).signature_bytes(), canonical);
+        assert_eq!(builder(&padded).keep_signature_encoding().signature_bytes(), padded);
+        assert_eq!(builder(&[0x30; 71]).signature_bytes(), vec![0x30; 71]);
+        let script = builder(&padded).build_unlocking_script();
+        assert!(script.windows(canonical.len()).any(|w| w == canonical));
+        assert!(!script.windows(padded.len()).any(|w| w == padded));
+    }
+
+    #[test]
+    fn test_poseidon_strategy_section() {
+        let state = IPAAccumulator::new([0x22; 32]);
+        let contract = VerifierContract::with_poseidon_strategy([0x11; 20], state.clone(), StrategyChoice::WitnessConstants);
+        let scripts = generate_poseidon_verification(StrategyChoice::WitnessConstants);
+        let section = |wanted| contract.locking_sections().into_iter()
+            .find(|(section, _)| *section == wanted)
+            .map(|(_, bytes)| bytes)
+            .unwrap();
+        // The template blob is checked once and copied into every absorb
+        let blob_hash = sha256(&round_constants_blob());
+        assert!(section(ContractSection::ConstantsCheck).windows(32).any(|w| w == blob_hash));
+        let absorb = generate_poseidon_absorb_from_blob();
+        let absorbs = section(ContractSection::Poseidon).windows(absorb.len()).filter(|w| *w == absorb).count();
+        assert_eq!(absorbs, contract.transcript_shape.max_elements());
+        assert_ne!(contract.locking_script(), VerifierContract::new([0x11; 20], state).locking_script());
+
+        // The round constants go in ahead of the rest of the unlocking data
+        let witness = chained_witness(&contract, 1);
+        let unlocking = contract.unlocking_script(&witness);
+        assert!(unlocking.starts_with(&scripts.unlock_template));
+        let items = contract.unlocking_stack_items(&witness);
+        assert_eq!(items.iter().flat_map(|item| push_bytes(item)).collect::<Vec<_>>(), unlocking);
+
+        let next = contract.apply_transition(&witness).unwrap();
+        assert_eq!(next.poseidon_strategy, Some(StrategyChoice::WitnessConstants));
+    }
+
+    #[test]
+    fn test_constants_mismatch_caught_before_build() {
+        let contract = VerifierContract::new([0x11; 20], IPAAccumulator::new([0x22; 32]));
+        let output = ContractOutput::new(&contract, 100_000);
+        assert_eq!(output.constants_hash(), Some(get_constants_hash()));
+        assert!(contract.verify_spendable_with_current_constants(&output));
+
+        // An output from a build with other constants
+        let mut doctored = output.clone();
+        doctored.script_pubkey = replace_push_at(&output.script_pubkey, 0, &[0x5a; 32]).unwrap();
+        assert!(!contract.verify_spendable_with_current_constants(&doctored));
+        let builder = |input: ContractOutput| ContractTransactionBuilder::new(input, chained_witness(&contract, 1), [0x11; 20])
+            .with_signature(vec![0x30; 71], vec![0x02; 33]);
+        assert!(builder(output).check_constants().is_ok());
+        let params = ChainParams::mainnet();
+        let err = builder(doctored.clone()).finalize(&params).err().unwrap();
+        assert!(matches!(err, VerifierError::ConstantsMismatch { expected, ours } if expected == [0x5a; 32] && ours == get_constants_hash()));
+        let outpoint = OutPoint::new([0x01; 32], 0);
+        let err = builder(doctored).plan_chain(&params, outpoint, &[], 1_000, None, |_, _| vec![0x30; 71]).err().unwrap();
+        assert!(matches!(err, VerifierError::ConstantsMismatch { .. }));
+    }
+}
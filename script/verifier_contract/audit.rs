@@ -0,0 +1,287 @@
+// Chain Audit
+// Replays published step witnesses from genesis so an auditor can confirm a
+// claimed accumulator without trusting the operator's checkpoint.
+//
+// THROUGHPUT:
+// Cost is dominated by Poseidon: one permutation per absorbed element,
+// i.e. 5 + public_inputs + 4 * rounds (+1 with b) per step, so ~46 for a
+// typical 10-round step. Expect on the order of a few thousand steps per
+// second per core; `verify_chain_parallel` checks transcripts on every
+// available core and only the cheap state chaining stays sequential.
+//
+// ANCHORS:
+// `verify_from_anchor` starts from a state committed by an anchor output
+// instead of genesis. The anchor is trusted as far as the transaction
+// carrying it is; a forged anchor is only caught when the following
+// witnesses fail to chain from it.
+
+use super::{parse_anchor, IPAAccumulator, IPAStepWitness, StateTransitionEvent, VerifierError, FieldElement};
+use crate::ghost::script::field_script::bytes_to_fp;
+
+/// First failing step of an audited chain
+#[derive(Debug, Clone)]
+pub struct ChainAuditError {
+    /// Index into the witness list
+    pub index: usize,
+    pub reason: VerifierError,
+}
+
+/// Events to check alongside a chain's witnesses
+#[derive(Debug, Clone, Copy)]
+pub struct ChainEvents<'a> {
+    /// One per witness, matched by position
+    pub events: &'a [StateTransitionEvent],
+    /// Easiest proof-of-work target an attached inclusion proof's header may
+    /// meet, from the auditor's own view of the chain
+    pub target_bits: u32,
+}
+
+/// Replay `witnesses` from `genesis`, returning the final state. With
+/// `events`, also check each event describes its step and, when an
+/// inclusion proof is attached, that the event's transaction was mined
+/// under `target_bits`.
+pub fn verify_chain(
+    genesis: &IPAAccumulator,
+    witnesses: &[IPAStepWitness],
+    events: Option<ChainEvents<'_>>,
+) -> Result<IPAAccumulator, ChainAuditError> {
+    if let Some(events) = events.filter(|e| e.events.len() != witnesses.len()) {
+        let index = events.events.len().min(witnesses.len());
+        return Err(ChainAuditError { index, reason: VerifierError::EventMismatch });
+    }
+    let mut state = genesis.clone();
+    for (index, witness) in witnesses.iter().enumerate() {
+        let next = check_transcript(&state.transcript_hash, witness)
+            .and_then(|_| advance(&state, witness))
+            .map_err(|reason| ChainAuditError { index, reason })?;
+        if let Some(events) = &events {
+            check_event(&events.events[index], &state, &next, witness, events.target_bits)
+                .map_err(|reason| ChainAuditError { index, reason })?;
+        }
+        state = next;
+    }
+    Ok(state)
+}
+
+/// Replay `witnesses` from the state committed by `anchor`, an
+/// `anchor_script`, returning the final state. An unreadable anchor fails at
+/// index 0.
+pub fn verify_from_anchor(
+    anchor: &[u8],
+    witnesses: &[IPAStepWitness],
+) -> Result<IPAAccumulator, ChainAuditError> {
+    let state = parse_anchor(anchor).ok_or(ChainAuditError { index: 0, reason: VerifierError::InvalidAnchor })?;
+    verify_chain(&state, witnesses, None)
+}
+
+fn check_event(
+    event: &StateTransitionEvent,
+    prev: &IPAAccumulator,
+    next: &IPAAccumulator,
+    witness: &IPAStepWitness,
+    target_bits: u32,
+) -> Result<(), VerifierError> {
+    if event.prev_state != *prev || event.new_state != *next || event.witness_digest != witness.digest() {
+        return Err(VerifierError::EventMismatch);
+    }
+    match (&event.inclusion, &event.txid) {
+        (None, _) => Ok(()),
+        (Some(proof), Some(txid)) if proof.verify(txid, target_bits) => Ok(()),
+        (Some(_), _) => Err(VerifierError::InvalidInclusion),
+    }
+}
+
+/// Same result as `verify_chain` without events. Each step's transcript is
+/// checked against the previous witness's claimed output concurrently, then
+/// states are chained sequentially.
+pub fn verify_chain_parallel(
+    genesis: &IPAAccumulator,
+    witnesses: &[IPAStepWitness],
+) -> Result<IPAAccumulator, ChainAuditError> {
+    let threads = std::thread::available_parallelism().map_or(1, |n| n.get());
+    let chunk_len = witnesses.len().div_ceil(threads).max(1);
+    let prev_transcript = |index: usize| match index {
+        0 => &genesis.transcript_hash,
+        i => &witnesses[i - 1].next_transcript_hash,
+    };
+
+    let first_failure = std::thread::scope(|scope| {
+        let handles: Vec<_> = witnesses
+            .chunks(chunk_len)
+            .enumerate()
+            .map(|(chunk, steps)| {
+                scope.spawn(move || {
+                    steps.iter().enumerate().find_map(|(offset, witness)| {
+                        let index = chunk * chunk_len + offset;
+                        check_transcript(prev_transcript(index), witness)
+                            .err()
+                            .map(|reason| ChainAuditError { index, reason })
+                    })
+                })
+            })
+            .collect();
+        // Chunks are in order, so the first failing chunk holds the lowest index
+        handles.into_iter().find_map(|h| h.join().expect("audit worker panicked"))
+    });
+
+    let mut state = genesis.clone();
+    for (index, witness) in witnesses.iter().enumerate() {
+        if let Some(err) = first_failure.as_ref().filter(|e| e.index == index) {
+            return Err(err.clone());
+        }
+        state = advance(&state, witness).map_err(|reason| ChainAuditError { index, reason })?;
+    }
+    Ok(state)
+}
+
+fn check_transcript(prev_transcript: &FieldElement, witness: &IPAStepWitness) -> Result<(), VerifierError> {
+    let computed = witness.compute_transcript_hash_checked(prev_transcript)?;
+    let claimed = bytes_to_fp(&witness.next_transcript_hash).ok_or(VerifierError::NonCanonicalElement)?;
+    if computed != claimed {
+        return Err(VerifierError::InvalidTranscript);
+    }
+    Ok(())
+}
+
+/// Step must increment by exactly one; the app root only changes when the
+/// witness carries a new one
+fn advance(state: &IPAAccumulator, witness: &IPAStepWitness) -> Result<IPAAccumulator, VerifierError> {
+    Ok(IPAAccumulator {
+        transcript_hash: witness.next_transcript_hash,
+        app_state_root: witness.new_app_state.unwrap_or(state.app_state_root),
+        step: state.step.checked_add(1).ok_or(VerifierError::StepMismatch)?,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ghost::script::proof_generator::generate_mock_proof_with_state;
+
+    fn mock_chain(genesis: &IPAAccumulator, steps: usize) -> (Vec<IPAStepWitness>, IPAAccumulator) {
+        let mut witnesses = Vec::with_capacity(steps);
+        let mut state = genesis.clone();
+        for i in 0..steps {
+            let mut root = [0u8; 32];
+            root[..8].copy_from_slice(&(i as u64).to_le_bytes());
+            let new_app_state = (i % 3 == 0).then_some(root);
+            let witness = generate_mock_proof_with_state(&state.transcript_hash, 2, vec![root], new_app_state);
+            state = advance(&state, &witness).unwrap();
+            witnesses.push(witness);
+        }
+        (witnesses, state)
+    }
+
+    #[test]
+    fn test_verify_thousand_step_chain() {
+        let genesis = IPAAccumulator::new([0x22; 32]);
+        let (witnesses, expected) = mock_chain(&genesis, 1000);
+        let serial = verify_chain(&genesis, &witnesses, None).unwrap();
+        assert_eq!(serial, expected);
+        assert_eq!(serial.step, 1000);
+        assert_eq!(verify_chain_parallel(&genesis, &witnesses).unwrap(), expected);
+    }
+
+    #[test]
+    fn test_events_with_inclusion() {
+        use crate::ghost::script::spv::{merkle_root, BlockHeader, MerkleBranch};
+        let genesis = IPAAccumulator::new([0x22; 32]);
+        let (witnesses, expected) = mock_chain(&genesis, 3);
+        let mut state = genesis.clone();
+        let txids: Vec<[u8; 32]> = (0..3u8).map(|i| [0xa0 + i; 32]).collect();
+        // All three spends mined in one block, with an easy regtest-style target
+        let mut header = BlockHeader {
+            version: 1,
+            prev_block: [0u8; 32],
+            merkle_root: merkle_root(&txids).unwrap(),
+            time: 0,
+            bits: 0x207fffff,
+            nonce: 0,
+        };
+        while !header.verify_pow(header.bits) {
+            header.nonce += 1;
+        }
+        let mut events: Vec<StateTransitionEvent> = witnesses.iter().zip(&txids).enumerate().map(|(i, (witness, txid))| {
+            let next = advance(&state, witness).unwrap();
+            let event = StateTransitionEvent::new(state.clone(), next.clone(), witness.digest()).with_txid(*txid);
+            state = next;
+            let branch = MerkleBranch::from_txids(&txids, i as u32).unwrap();
+            event.attach_inclusion(header.clone(), branch)
+        }).collect();
+        let check = |events: &[StateTransitionEvent]| {
+            verify_chain(&genesis, &witnesses, Some(ChainEvents { events, target_bits: 0x207fffff }))
+        };
+        assert_eq!(check(&events).unwrap(), expected);
+
+        // The header only meets its own easy bits, not the auditor's target
+        let mainnet = ChainEvents { events: &events, target_bits: 0x1d00ffff };
+        let err = verify_chain(&genesis, &witnesses, Some(mainnet)).unwrap_err();
+        assert_eq!(err.index, 0);
+        assert!(matches!(err.reason, VerifierError::InvalidInclusion));
+
+        // Branch for a different transaction
+        events[1].txid = Some([0xff; 32]);
+        let err = check(&events).unwrap_err();
+        assert_eq!(err.index, 1);
+        assert!(matches!(err.reason, VerifierError::InvalidInclusion));
+
+        // Without an inclusion proof only the event contents are checked
+        events[1].inclusion = None;
+        assert!(check(&events).is_ok());
+        events[2].witness_digest = [0u8; 32];
+        let err = check(&events).unwrap_err();
+        assert_eq!(err.index, 2);
+        assert!(matches!(err.reason, VerifierError::EventMismatch));
+        assert!(check(&events[..2]).is_err());
+    }
+
+    #[test]
+    fn test_verify_from_anchor() {
+        use crate::ghost::script::verifier_contract::{
+            anchor_script, AnchorPolicy, ContractOutput, ContractTransactionBuilder, VerifierContract,
+        };
+        use crate::ghost::script::network::ChainParams;
+        use crate::ghost::script::tx::{OutPoint, Transaction};
+        let contract = VerifierContract::new([0x11; 20], IPAAccumulator::new([0x22; 32]));
+        let (witnesses, expected) = mock_chain(&contract.current_state, 10);
+        let plan = ContractTransactionBuilder::new(ContractOutput::new(&contract, 10_000_000), witnesses[0].clone(), [0x11; 20])
+            .with_signature(vec![0x30; 72], vec![0x02; 33])
+            .with_anchor_policy(AnchorPolicy::new(3))
+            .plan_chain(&ChainParams::mainnet(), OutPoint::new([0xab; 32], 0), &witnesses[1..], 1_000, None, |_, _| vec![0x30; 72])
+            .unwrap();
+        // Steps 3, 6 and 9 carry an anchor as their second output
+        let anchored: Vec<u32> = plan.steps.iter().filter(|step| step.anchor.is_some()).map(|step| step.output.state.step).collect();
+        assert_eq!(anchored, [3, 6, 9]);
+        let ninth = &plan.steps[8];
+        let tx = Transaction::parse(&ninth.raw_tx).unwrap();
+        assert_eq!(tx.outputs[1], (0, ninth.anchor.clone().unwrap()));
+        assert_eq!(tx.outputs[1].1, anchor_script(&ninth.output.state));
+
+        let resumed = verify_from_anchor(&tx.outputs[1].1, &witnesses[9..]).unwrap();
+        assert_eq!(resumed, expected);
+
+        let mut forged = ninth.output.state.clone();
+        forged.transcript_hash = [0x01; 32];
+        let err = verify_from_anchor(&anchor_script(&forged), &witnesses[9..]).unwrap_err();
+        assert_eq!(err.index, 0);
+        assert!(matches!(err.reason, VerifierError::InvalidTranscript));
+        let err = verify_from_anchor(&tx.outputs[0].1, &witnesses[9..]).unwrap_err();
+        assert!(matches!(err.reason, VerifierError::InvalidAnchor));
+    }
+
+    #[test]
+    fn test_corrupted_middle_step() {
+        let genesis = IPAAccumulator::new([0x22; 32]);
+        let (mut witnesses, _) = mock_chain(&genesis, 20);
+        witnesses[10].a_scalar[0] ^= 1;
+        for result in [verify_chain(&genesis, &witnesses, None), verify_chain_parallel(&genesis, &witnesses)] {
+            let err = result.unwrap_err();
+            assert_eq!(err.index, 10);
+            assert!(matches!(err.reason, VerifierError::InvalidTranscript));
+        }
+        witnesses[10].a_scalar = [0xff; 32];
+        let err = verify_chain(&genesis, &witnesses, None).unwrap_err();
+        assert_eq!(err.index, 10);
+        assert!(matches!(err.reason, VerifierError::NonCanonicalElement));
+    }
+}
@@ -0,0 +1,235 @@
+// Spend Monitor
+// Watch-only classification of a transaction spending a contract output,
+// for services that see the spend in the mempool and want to know whether
+// output 0 is the legitimate continuation before the block confirms.
+//
+// The contract's unlocking script pushes the transcript elements framed by
+// their counts (see `IPAStepWitness::transcript_elements`), so the witness
+// is recovered from the scriptSig alone and the expected next locking
+// script rebuilt from the constants hash in the spent output's header,
+// without the round constants.
+
+use super::{
+    ContractOutput, IPAStepWitness, FieldElement,
+    app_state_marker, frame_length, operator_binding, header_operator_pkh,
+};
+use crate::ghost::script::num::parse_pushes;
+use crate::ghost::script::tx::Transaction;
+
+/// Where output 0 of a contract spend leads
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SpendCheck {
+    /// The witnessed next state under the same operator
+    Continuation,
+    /// The witnessed next state under a different operator
+    Rotation { operator_pkh: [u8; 20] },
+    /// The spent output's own script: the state is re-locked, not advanced
+    Recovery,
+    Unexpected(UnexpectedSpend),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UnexpectedSpend {
+    MalformedTransaction,
+    /// No input's unlocking script carries the monitored state
+    NotContractSpend,
+    /// The unlocking script is not a framed step witness
+    MalformedWitness,
+    /// The witness does not advance the monitored transcript, or is bound
+    /// to another operator
+    InvalidWitness,
+    MissingOutput,
+    /// Output 0 is none of the recognized continuations
+    OutputMismatch,
+}
+
+/// Classify `raw_tx` as a spend of `prev`. The spending input is the one
+/// whose unlocking script pushes `prev`'s state, first or after a
+/// `WitnessConstants` template blob.
+pub fn check_spend(raw_tx: &[u8], prev: &ContractOutput) -> SpendCheck {
+    let unexpected = SpendCheck::Unexpected;
+    let Some(tx) = Transaction::parse(raw_tx) else {
+        return unexpected(UnexpectedSpend::MalformedTransaction);
+    };
+    let Some(operator_pkh) = prev.operator_pkh() else {
+        return unexpected(UnexpectedSpend::NotContractSpend);
+    };
+    let prev_state = prev.state.to_script_bytes();
+    let Some(items) = tx
+        .inputs
+        .iter()
+        .filter_map(|input| parse_pushes(&input.script_sig))
+        .find_map(|items| {
+            let start = items.iter().take(2).position(|item| *item == prev_state)?;
+            Some(items[start + 1..].to_vec())
+        })
+    else {
+        return unexpected(UnexpectedSpend::NotContractSpend);
+    };
+    let Some(witness) = decode_witness(&items) else {
+        return unexpected(UnexpectedSpend::MalformedWitness);
+    };
+    if witness.operator_binding != operator_binding(&operator_pkh) || !witness.verify(&prev.state.transcript_hash) {
+        return unexpected(UnexpectedSpend::InvalidWitness);
+    }
+    let Some((_, script)) = tx.outputs.first() else {
+        return unexpected(UnexpectedSpend::MissingOutput);
+    };
+
+    let expected = |pkh| prev.expected_next_script(&witness, pkh);
+    if expected(operator_pkh).as_ref() == Some(script) {
+        return SpendCheck::Continuation;
+    }
+    if let Some(new_pkh) = header_operator_pkh(script).filter(|pkh| *pkh != operator_pkh) {
+        if expected(new_pkh).as_ref() == Some(script) {
+            return SpendCheck::Rotation { operator_pkh: new_pkh };
+        }
+    }
+    if *script == prev.script_pubkey {
+        return SpendCheck::Recovery;
+    }
+    unexpected(UnexpectedSpend::OutputMismatch)
+}
+
+/// Witness from the unlocking script's pushes after `prev_state`:
+/// binding, n, inputs.., k, (L, R)*k, a, [b], [marker, app_state], next
+/// [sig] [pubkey]
+fn decode_witness(items: &[Vec<u8>]) -> Option<IPAStepWitness> {
+    // Signature and pubkey behind
+    let elements: Vec<FieldElement> = items
+        .get(..items.len().checked_sub(2)?)?
+        .iter()
+        .map(|item| item.as_slice().try_into().ok())
+        .collect::<Option<_>>()?;
+    let (&next_transcript_hash, elements) = elements.split_last()?;
+    let mut reader = elements.iter().copied();
+    let mut next = || reader.next();
+
+    let operator_binding = next()?;
+    let public_inputs = (0..frame_count(&next()?)?).map(|_| next()).collect::<Option<Vec<_>>>()?;
+    let rounds = frame_count(&next()?)?;
+    let mut l_terms = Vec::with_capacity(rounds.min(elements.len()));
+    let mut r_terms = Vec::with_capacity(rounds.min(elements.len()));
+    for _ in 0..rounds {
+        l_terms.push([next()?, next()?]);
+        r_terms.push([next()?, next()?]);
+    }
+    let a_scalar = next()?;
+    let rest: Vec<FieldElement> = std::iter::from_fn(next).collect();
+    let (b_scalar, app_state) = match rest.as_slice() {
+        [] => (None, None),
+        [b] => (Some(*b), None),
+        [marker, app] if *marker == app_state_marker() => (None, Some(*app)),
+        [b, marker, app] if *marker == app_state_marker() => (Some(*b), Some(*app)),
+        _ => return None,
+    };
+    Some(IPAStepWitness {
+        operator_binding,
+        public_inputs,
+        l_terms,
+        r_terms,
+        a_scalar,
+        b_scalar,
+        new_app_state: app_state,
+        next_transcript_hash,
+    })
+}
+
+/// Count absorbed as `frame_length`
+fn frame_count(element: &FieldElement) -> Option<usize> {
+    let count = usize::try_from(u64::from_le_bytes(element[..8].try_into().ok()?)).ok()?;
+    (frame_length(count) == *element).then_some(count)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::IPAAccumulator;
+    use crate::ghost::script::proof_generator::generate_mock_proof_with_state;
+    use crate::ghost::script::introspect::{find_pattern, replace_push_at, ScriptPatternToken};
+    use crate::ghost::script::sighash::SighashPolicy;
+    use crate::ghost::script::tx::OutPoint;
+    use crate::ghost::script::{ContractTransactionBuilder, VerifierContract};
+
+    // Mock proofs are bound to the zero operator
+    fn spend(operator_pkh: [u8; 20]) -> (ContractOutput, ContractTransactionBuilder) {
+        let contract = VerifierContract::new([0u8; 20], IPAAccumulator::new([0x22; 32]));
+        let witness = generate_mock_proof_with_state(&contract.current_state.transcript_hash, 2, vec![[0x05; 32]], Some([0x07; 32]));
+        let prev = ContractOutput::new(&contract, 100_000);
+        let builder = ContractTransactionBuilder::new(prev.clone(), witness, operator_pkh)
+            .with_signature(vec![0x30; 72], vec![0x02; 33]);
+        (prev, builder)
+    }
+
+    #[test]
+    fn test_builder_transaction_is_continuation() {
+        let (prev, builder) = spend([0u8; 20]);
+        let tx = builder.build_transaction(OutPoint::new([0xab; 32], 0), 99_000);
+        assert_eq!(check_spend(&tx, &prev), SpendCheck::Continuation);
+        assert_eq!(prev.expected_next_script(&builder.witness, [0u8; 20]), Some(builder.build_output(0).script_pubkey));
+
+        let items = parse_pushes(&builder.build_unlocking_script()).unwrap();
+        assert_eq!(decode_witness(&items[1..]).unwrap().digest(), builder.witness.digest());
+    }
+
+    #[test]
+    fn test_tampered_output_is_unexpected() {
+        let (prev, builder) = spend([0u8; 20]);
+        let mut tx = Transaction::parse(&builder.build_transaction(OutPoint::new([0xab; 32], 0), 99_000)).unwrap();
+        let script = &mut tx.outputs[0].1;
+        *script.last_mut().unwrap() ^= 0x01;
+        assert_eq!(check_spend(&tx.to_bytes(), &prev), SpendCheck::Unexpected(UnexpectedSpend::OutputMismatch));
+
+        tx.outputs[0].1 = prev.script_pubkey.clone();
+        assert_eq!(check_spend(&tx.to_bytes(), &prev), SpendCheck::Recovery);
+        tx.outputs.clear();
+        assert_eq!(check_spend(&tx.to_bytes(), &prev), SpendCheck::Unexpected(UnexpectedSpend::MissingOutput));
+        assert_eq!(check_spend(&[0x01], &prev), SpendCheck::Unexpected(UnexpectedSpend::MalformedTransaction));
+    }
+
+    #[test]
+    fn test_new_operator_is_rotation() {
+        let (prev, builder) = spend([0x33; 20]);
+        let tx = builder.build_transaction(OutPoint::new([0xab; 32], 0), 99_000);
+        assert_eq!(check_spend(&tx, &prev), SpendCheck::Rotation { operator_pkh: [0x33; 20] });
+    }
+
+    #[test]
+    fn test_sighash_policy_contract_is_continuation() {
+        let contract = VerifierContract::new([0u8; 20], IPAAccumulator::new([0x22; 32]))
+            .with_sighash_policy(SighashPolicy::all_only());
+        let witness = generate_mock_proof_with_state(&contract.current_state.transcript_hash, 2, vec![[0x05; 32]], None);
+        let prev = ContractOutput::new(&contract, 100_000);
+        let builder = ContractTransactionBuilder::new(prev.clone(), witness, [0u8; 20])
+            .with_signature(vec![0x30; 72], vec![0x02; 33]);
+        assert_eq!(builder.build_output(0).sighash_policy, Some(SighashPolicy::all_only()));
+        let tx = builder.build_transaction(OutPoint::new([0xab; 32], 0), 99_000);
+        assert_eq!(check_spend(&tx, &prev), SpendCheck::Continuation);
+    }
+
+    #[test]
+    fn test_expected_script_uses_header_constants() {
+        let (prev, builder) = spend([0u8; 20]);
+        let recommit = |script: &[u8]| {
+            let header = find_pattern(script, &[ScriptPatternToken::PushOfLen(32)]).unwrap();
+            replace_push_at(script, header.captures[0].0, &[0x55; 32]).unwrap()
+        };
+        // An output from a release with other constants continues under them
+        let older = ContractOutput { script_pubkey: recommit(&prev.script_pubkey), ..prev };
+        assert_eq!(older.constants_hash(), Some([0x55; 32]));
+        let mut tx = Transaction::parse(&builder.build_transaction(OutPoint::new([0xab; 32], 0), 99_000)).unwrap();
+        tx.outputs[0].1 = recommit(&tx.outputs[0].1);
+        assert_eq!(check_spend(&tx.to_bytes(), &older), SpendCheck::Continuation);
+    }
+
+    #[test]
+    fn test_witness_for_other_state_is_rejected() {
+        let (prev, mut builder) = spend([0u8; 20]);
+        builder.witness.next_transcript_hash = [0x01; 32];
+        let tx = builder.build_transaction(OutPoint::new([0xab; 32], 0), 99_000);
+        assert_eq!(check_spend(&tx, &prev), SpendCheck::Unexpected(UnexpectedSpend::InvalidWitness));
+
+        let other = ContractOutput { state: IPAAccumulator::new([0x44; 32]), ..prev };
+        assert_eq!(check_spend(&tx, &other), SpendCheck::Unexpected(UnexpectedSpend::NotContractSpend));
+    }
+}
@@ -0,0 +1,768 @@
+// Script Interpreter [Test & Debug]
+// Off-chain executor for the scripts generated by this crate
+//
+// SCOPE:
+// Executes unlocking + locking script pairs with post-Genesis BSV semantics so
+// generated guards, tails and field scripts can be checked before broadcast.
+//
+// NUMBERS:
+// Number encoding, truthiness and push-opcode results follow `script::num`.
+// Operands are decoded without a minimal-encoding requirement, matching
+// consensus rather than relay policy.
+//
+// SIGNATURES:
+// OP_CHECKSIG / OP_CHECKMULTISIG delegate to a `SignatureChecker`. The
+// default `EcdsaSignatureChecker` verifies against a caller-supplied sighash
+// and rejects everything without one. `MockSignatureChecker`, which accepts
+// any non-empty signature, must be chosen explicitly.
+//
+// TIMELOCKS:
+// OP_CHECKLOCKTIMEVERIFY / OP_CHECKSEQUENCEVERIFY only require a stack item;
+// there is no transaction context to compare against.
+
+use num_bigint::BigInt;
+use num_traits::{Zero, One, Signed};
+use crate::ghost::crypto::{sha256, hash160, double_sha256};
+use crate::ghost::Error;
+use super::opcodes::*;
+use super::disasm::{instructions, Instruction, DisasmError};
+use super::{from_hex, EcdsaSignature};
+pub use super::num::{decode_num, encode_num, is_truthy};
+
+// ============================================================================
+// SIGNATURE CHECKING
+// ============================================================================
+
+pub trait SignatureChecker {
+    fn check_sig(&self, signature: &[u8], pubkey: &[u8]) -> bool;
+}
+
+/// Verifies DER signatures (with trailing sighash flag byte) over a fixed
+/// sighash. Without a sighash every signature is rejected.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct EcdsaSignatureChecker {
+    pub sighash: Option<[u8; 32]>,
+}
+
+impl EcdsaSignatureChecker {
+    pub fn new(sighash: [u8; 32]) -> Self {
+        Self { sighash: Some(sighash) }
+    }
+}
+
+impl SignatureChecker for EcdsaSignatureChecker {
+    fn check_sig(&self, signature: &[u8], pubkey: &[u8]) -> bool {
+        let (Some(sighash), Some(signature)) = (self.sighash, EcdsaSignature::from_bytes(signature)) else {
+            return false;
+        };
+        signature.verify(pubkey, &sighash)
+    }
+}
+
+/// Accepts any non-empty signature
+#[derive(Clone, Copy, Debug, Default)]
+pub struct MockSignatureChecker;
+
+impl SignatureChecker for MockSignatureChecker {
+    fn check_sig(&self, signature: &[u8], _pubkey: &[u8]) -> bool {
+        !signature.is_empty()
+    }
+}
+
+// ============================================================================
+// ERRORS
+// ============================================================================
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum VmErrorKind {
+    StackUnderflow,
+    AltStackUnderflow,
+    VerifyFailed,
+    EqualVerifyFailed,
+    OpReturn,
+    InvalidSplitRange,
+    InvalidNumberRange,
+    DivisionByZero,
+    UnbalancedConditional,
+    UnsupportedOpcode,
+    TruncatedPush,
+}
+
+/// Failure while executing a script, with the failing instruction
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct VmError {
+    /// Byte offset of the failing instruction within its script
+    pub offset: usize,
+    /// Opcode byte at that offset
+    pub opcode: u8,
+    pub kind: VmErrorKind,
+}
+
+// ============================================================================
+// INTERPRETER
+// ============================================================================
+
+pub struct Interpreter {
+    stack: Vec<Vec<u8>>,
+    alt_stack: Vec<Vec<u8>>,
+    checker: Box<dyn SignatureChecker>,
+    op_count: usize,
+}
+
+impl Interpreter {
+    pub fn new() -> Self {
+        Self::with_checker(Box::new(EcdsaSignatureChecker::default()))
+    }
+
+    pub fn with_checker(checker: Box<dyn SignatureChecker>) -> Self {
+        Self {
+            stack: Vec::new(),
+            alt_stack: Vec::new(),
+            checker,
+            op_count: 0,
+        }
+    }
+
+    /// Main stack, bottom first
+    pub fn stack(&self) -> &[Vec<u8>] {
+        &self.stack
+    }
+
+    pub fn alt_stack(&self) -> &[Vec<u8>] {
+        &self.alt_stack
+    }
+
+    /// Number of non-push opcodes executed so far
+    pub fn op_count(&self) -> usize {
+        self.op_count
+    }
+
+    /// Whether the top of the main stack is truthy
+    pub fn success(&self) -> bool {
+        self.stack.last().is_some_and(|top| is_truthy(top))
+    }
+
+    /// Whether `opcode` executes rather than failing with `UnsupportedOpcode`.
+    /// Pushes and flow control are handled by `eval`; anything else is probed
+    /// against the dispatch in `step` itself, so the answer cannot drift from
+    /// what the interpreter actually runs.
+    pub fn supports(opcode: u8) -> bool {
+        if opcode <= OP_PUSHDATA4 || matches!(opcode, OP_IF | OP_NOTIF | OP_ELSE | OP_ENDIF) {
+            return true;
+        }
+        let mut probe = Self::new();
+        probe.stack = vec![vec![1]; 8];
+        probe.alt_stack = vec![vec![1]];
+        probe.step(opcode) != Err(VmErrorKind::UnsupportedOpcode)
+    }
+
+    /// Byte values `supports` rejects, ascending
+    pub fn unimplemented_opcodes() -> Vec<u8> {
+        (0..=u8::MAX).filter(|opcode| !Self::supports(*opcode)).collect()
+    }
+
+    /// Run an unlocking script followed by a locking script.
+    /// The alt stack is cleared between the two, as on-chain.
+    pub fn verify(&mut self, unlocking: &[u8], locking: &[u8]) -> Result<bool, VmError> {
+        self.eval(unlocking)?;
+        self.alt_stack.clear();
+        self.eval(locking)?;
+        Ok(self.success())
+    }
+
+    /// Seed the main stack with raw items and run a locking script,
+    /// skipping push parsing of the unlocking side
+    pub fn eval_with_stack(&mut self, items: Vec<Vec<u8>>, locking: &[u8]) -> Result<bool, VmError> {
+        self.stack = items;
+        self.alt_stack.clear();
+        self.eval(locking)?;
+        Ok(self.success())
+    }
+
+    /// Execute a script against the current stacks
+    pub fn eval(&mut self, script: &[u8]) -> Result<(), VmError> {
+        let mut exec: Vec<bool> = Vec::new();
+        let mut last_offset = 0;
+        for item in instructions(script) {
+            let (offset, instruction) = item.map_err(|e| match e {
+                DisasmError::TruncatedPush { offset } => VmError {
+                    offset,
+                    opcode: script[offset],
+                    kind: VmErrorKind::TruncatedPush,
+                },
+                DisasmError::UnmatchedConditional { offset, opcode } => VmError {
+                    offset,
+                    opcode,
+                    kind: VmErrorKind::UnbalancedConditional,
+                },
+                DisasmError::UnclosedConditional { .. } => VmError {
+                    offset: script.len(),
+                    opcode: OP_ENDIF,
+                    kind: VmErrorKind::UnbalancedConditional,
+                },
+            })?;
+            last_offset = offset;
+            let executing = exec.iter().all(|b| *b);
+            match instruction {
+                Instruction::Push(data) => {
+                    if executing {
+                        self.stack.push(data.to_vec());
+                    }
+                }
+                Instruction::Op(opcode) => {
+                    let fail = |kind| VmError { offset, opcode, kind };
+                    match opcode {
+                        OP_IF | OP_NOTIF => {
+                            let mut branch = false;
+                            if executing {
+                                let top = self.stack.pop().ok_or_else(|| fail(VmErrorKind::StackUnderflow))?;
+                                branch = is_truthy(&top);
+                                if opcode == OP_NOTIF {
+                                    branch = !branch;
+                                }
+                            }
+                            exec.push(branch);
+                        }
+                        OP_ELSE => {
+                            let top = exec.last_mut().ok_or_else(|| fail(VmErrorKind::UnbalancedConditional))?;
+                            *top = !*top;
+                        }
+                        OP_ENDIF => {
+                            exec.pop().ok_or_else(|| fail(VmErrorKind::UnbalancedConditional))?;
+                        }
+                        _ if !executing => {}
+                        _ => {
+                            self.op_count += 1;
+                            self.step(opcode).map_err(fail)?;
+                        }
+                    }
+                }
+            }
+        }
+        if !exec.is_empty() {
+            return Err(VmError {
+                offset: last_offset,
+                opcode: script.get(last_offset).copied().unwrap_or(OP_0),
+                kind: VmErrorKind::UnbalancedConditional,
+            });
+        }
+        Ok(())
+    }
+
+    fn pop(&mut self) -> Result<Vec<u8>, VmErrorKind> {
+        self.stack.pop().ok_or(VmErrorKind::StackUnderflow)
+    }
+
+    fn pop_num(&mut self) -> Result<BigInt, VmErrorKind> {
+        self.pop().map(|b| decode_num(&b))
+    }
+
+    fn push_num(&mut self, n: BigInt) {
+        self.stack.push(encode_num(&n));
+    }
+
+    fn push_bool(&mut self, b: bool) {
+        self.stack.push(if b { vec![1] } else { Vec::new() });
+    }
+
+    fn peek(&self, depth: usize) -> Result<&Vec<u8>, VmErrorKind> {
+        if depth >= self.stack.len() {
+            return Err(VmErrorKind::StackUnderflow);
+        }
+        Ok(&self.stack[self.stack.len() - 1 - depth])
+    }
+
+    fn require(&self, n: usize) -> Result<(), VmErrorKind> {
+        if self.stack.len() < n {
+            return Err(VmErrorKind::StackUnderflow);
+        }
+        Ok(())
+    }
+
+    fn pop_index(&mut self) -> Result<usize, VmErrorKind> {
+        let n = self.pop_num()?;
+        if n.is_negative() || n >= BigInt::from(self.stack.len()) {
+            return Err(VmErrorKind::StackUnderflow);
+        }
+        usize::try_from(&n).map_err(|_| VmErrorKind::InvalidNumberRange)
+    }
+
+    fn step(&mut self, opcode: u8) -> Result<(), VmErrorKind> {
+        match opcode {
+            OP_1NEGATE => self.push_num(-BigInt::one()),
+            OP_1..=OP_16 => self.push_num(BigInt::from(opcode - OP_1 + 1)),
+            OP_NOP | OP_NOP1 | OP_NOP4..=OP_NOP10 | OP_CODESEPARATOR => {}
+            OP_CHECKLOCKTIMEVERIFY | OP_CHECKSEQUENCEVERIFY => {
+                self.require(1)?;
+            }
+            OP_VERIFY => {
+                let top = self.pop()?;
+                if !is_truthy(&top) {
+                    return Err(VmErrorKind::VerifyFailed);
+                }
+            }
+            OP_RETURN => return Err(VmErrorKind::OpReturn),
+
+            // Stack
+            OP_TOALTSTACK => {
+                let top = self.pop()?;
+                self.alt_stack.push(top);
+            }
+            OP_FROMALTSTACK => {
+                let top = self.alt_stack.pop().ok_or(VmErrorKind::AltStackUnderflow)?;
+                self.stack.push(top);
+            }
+            OP_2DROP => {
+                self.require(2)?;
+                self.stack.truncate(self.stack.len() - 2);
+            }
+            OP_2DUP => {
+                self.require(2)?;
+                let a = self.peek(1)?.clone();
+                let b = self.peek(0)?.clone();
+                self.stack.push(a);
+                self.stack.push(b);
+            }
+            OP_3DUP => {
+                self.require(3)?;
+                for _ in 0..3 {
+                    let x = self.peek(2)?.clone();
+                    self.stack.push(x);
+                }
+            }
+            OP_2OVER => {
+                self.require(4)?;
+                for _ in 0..2 {
+                    let x = self.peek(3)?.clone();
+                    self.stack.push(x);
+                }
+            }
+            OP_2ROT => {
+                self.require(6)?;
+                let at = self.stack.len() - 6;
+                let moved: Vec<Vec<u8>> = self.stack.drain(at..at + 2).collect();
+                self.stack.extend(moved);
+            }
+            OP_2SWAP => {
+                self.require(4)?;
+                let len = self.stack.len();
+                self.stack.swap(len - 4, len - 2);
+                self.stack.swap(len - 3, len - 1);
+            }
+            OP_IFDUP => {
+                let top = self.peek(0)?.clone();
+                if is_truthy(&top) {
+                    self.stack.push(top);
+                }
+            }
+            OP_DEPTH => self.push_num(BigInt::from(self.stack.len())),
+            OP_DROP => {
+                self.pop()?;
+            }
+            OP_DUP => {
+                let top = self.peek(0)?.clone();
+                self.stack.push(top);
+            }
+            OP_NIP => {
+                self.require(2)?;
+                let len = self.stack.len();
+                self.stack.remove(len - 2);
+            }
+            OP_OVER => {
+                let x = self.peek(1)?.clone();
+                self.stack.push(x);
+            }
+            OP_PICK => {
+                let n = self.pop_index()?;
+                let x = self.peek(n)?.clone();
+                self.stack.push(x);
+            }
+            OP_ROLL => {
+                let n = self.pop_index()?;
+                let len = self.stack.len();
+                let x = self.stack.remove(len - 1 - n);
+                self.stack.push(x);
+            }
+            OP_ROT => {
+                self.require(3)?;
+                let len = self.stack.len();
+                let x = self.stack.remove(len - 3);
+                self.stack.push(x);
+            }
+            OP_SWAP => {
+                self.require(2)?;
+                let len = self.stack.len();
+                self.stack.swap(len - 2, len - 1);
+            }
+            OP_TUCK => {
+                self.require(2)?;
+                let top = self.peek(0)?.clone();
+                let len = self.stack.len();
+                self.stack.insert(len - 2, top);
+            }
+
+            // Splice
+            OP_CAT => {
+                let b = self.pop()?;
+                let mut a = self.pop()?;
+                a.extend(b);
+                self.stack.push(a);
+            }
+            OP_SPLIT => {
+                let n = self.pop_num()?;
+                let data = self.pop()?;
+                if n.is_negative() || n > BigInt::from(data.len()) {
+                    return Err(VmErrorKind::InvalidSplitRange);
+                }
+                let n = usize::try_from(&n).map_err(|_| VmErrorKind::InvalidSplitRange)?;
+                self.stack.push(data[..n].to_vec());
+                self.stack.push(data[n..].to_vec());
+            }
+            OP_NUM2BIN => {
+                let size = self.pop_num()?;
+                let value = self.pop()?;
+                let size = usize::try_from(&size).map_err(|_| VmErrorKind::InvalidNumberRange)?;
+                let mut bytes = encode_num(&decode_num(&value));
+                if bytes.len() > size {
+                    return Err(VmErrorKind::InvalidNumberRange);
+                }
+                let sign = bytes.last_mut().map_or(0, |b| {
+                    let s = *b & 0x80;
+                    *b &= 0x7f;
+                    s
+                });
+                bytes.resize(size, 0);
+                if size > 0 {
+                    bytes[size - 1] |= sign;
+                }
+                self.stack.push(bytes);
+            }
+            OP_BIN2NUM => {
+                let value = self.pop_num()?;
+                self.push_num(value);
+            }
+            OP_SIZE => {
+                let len = self.peek(0)?.len();
+                self.push_num(BigInt::from(len));
+            }
+
+            // Bitwise
+            OP_INVERT => {
+                let mut x = self.pop()?;
+                x.iter_mut().for_each(|b| *b = !*b);
+                self.stack.push(x);
+            }
+            OP_AND | OP_OR | OP_XOR => {
+                let b = self.pop()?;
+                let a = self.pop()?;
+                if a.len() != b.len() {
+                    return Err(VmErrorKind::InvalidNumberRange);
+                }
+                let out = a.iter().zip(b.iter()).map(|(x, y)| match opcode {
+                    OP_AND => x & y,
+                    OP_OR => x | y,
+                    _ => x ^ y,
+                }).collect();
+                self.stack.push(out);
+            }
+            OP_EQUAL | OP_EQUALVERIFY => {
+                let b = self.pop()?;
+                let a = self.pop()?;
+                if opcode == OP_EQUALVERIFY {
+                    if a != b {
+                        return Err(VmErrorKind::EqualVerifyFailed);
+                    }
+                } else {
+                    self.push_bool(a == b);
+                }
+            }
+
+            // Arithmetic
+            OP_1ADD | OP_1SUB | OP_2MUL | OP_2DIV | OP_NEGATE | OP_ABS | OP_NOT | OP_0NOTEQUAL => {
+                let a = self.pop_num()?;
+                let r = match opcode {
+                    OP_1ADD => a + 1,
+                    OP_1SUB => a - 1,
+                    OP_2MUL => a * 2,
+                    OP_2DIV => a / 2,
+                    OP_NEGATE => -a,
+                    OP_ABS => a.abs(),
+                    OP_NOT => BigInt::from(a.is_zero() as u8),
+                    _ => BigInt::from(!a.is_zero() as u8),
+                };
+                self.push_num(r);
+            }
+            OP_ADD | OP_SUB | OP_MUL | OP_DIV | OP_MOD | OP_BOOLAND | OP_BOOLOR
+            | OP_NUMEQUAL | OP_NUMEQUALVERIFY | OP_NUMNOTEQUAL | OP_LESSTHAN | OP_GREATERTHAN
+            | OP_LESSTHANOREQUAL | OP_GREATERTHANOREQUAL | OP_MIN | OP_MAX => {
+                let b = self.pop_num()?;
+                let a = self.pop_num()?;
+                let r = match opcode {
+                    OP_ADD => a + b,
+                    OP_SUB => a - b,
+                    OP_MUL => a * b,
+                    OP_DIV | OP_MOD => {
+                        if b.is_zero() {
+                            return Err(VmErrorKind::DivisionByZero);
+                        }
+                        if opcode == OP_DIV { a / b } else { a % b }
+                    }
+                    OP_BOOLAND => BigInt::from((!a.is_zero() && !b.is_zero()) as u8),
+                    OP_BOOLOR => BigInt::from((!a.is_zero() || !b.is_zero()) as u8),
+                    OP_NUMEQUAL | OP_NUMEQUALVERIFY => BigInt::from((a == b) as u8),
+                    OP_NUMNOTEQUAL => BigInt::from((a != b) as u8),
+                    OP_LESSTHAN => BigInt::from((a < b) as u8),
+                    OP_GREATERTHAN => BigInt::from((a > b) as u8),
+                    OP_LESSTHANOREQUAL => BigInt::from((a <= b) as u8),
+                    OP_GREATERTHANOREQUAL => BigInt::from((a >= b) as u8),
+                    OP_MIN => a.min(b),
+                    _ => a.max(b),
+                };
+                if opcode == OP_NUMEQUALVERIFY {
+                    if r.is_zero() {
+                        return Err(VmErrorKind::VerifyFailed);
+                    }
+                } else {
+                    self.push_num(r);
+                }
+            }
+            OP_WITHIN => {
+                let max = self.pop_num()?;
+                let min = self.pop_num()?;
+                let x = self.pop_num()?;
+                self.push_bool(min <= x && x < max);
+            }
+
+            // Crypto
+            OP_SHA256 => {
+                let x = self.pop()?;
+                self.stack.push(sha256(&x).to_vec());
+            }
+            OP_HASH160 => {
+                let x = self.pop()?;
+                self.stack.push(hash160(&x).to_vec());
+            }
+            OP_HASH256 => {
+                let x = self.pop()?;
+                self.stack.push(double_sha256(&x).to_vec());
+            }
+            OP_CHECKSIG | OP_CHECKSIGVERIFY => {
+                let pubkey = self.pop()?;
+                let signature = self.pop()?;
+                let ok = self.checker.check_sig(&signature, &pubkey);
+                if opcode == OP_CHECKSIGVERIFY {
+                    if !ok {
+                        return Err(VmErrorKind::VerifyFailed);
+                    }
+                } else {
+                    self.push_bool(ok);
+                }
+            }
+            OP_CHECKMULTISIG | OP_CHECKMULTISIGVERIFY => {
+                let ok = self.check_multisig()?;
+                if opcode == OP_CHECKMULTISIGVERIFY {
+                    if !ok {
+                        return Err(VmErrorKind::VerifyFailed);
+                    }
+                } else {
+                    self.push_bool(ok);
+                }
+            }
+            _ => return Err(VmErrorKind::UnsupportedOpcode),
+        }
+        Ok(())
+    }
+
+    fn check_multisig(&mut self) -> Result<bool, VmErrorKind> {
+        let n = self.pop_num()?;
+        let n = usize::try_from(&n).map_err(|_| VmErrorKind::InvalidNumberRange)?;
+        self.require(n)?;
+        let keys: Vec<Vec<u8>> = self.stack.split_off(self.stack.len() - n);
+        let m = self.pop_num()?;
+        let m = usize::try_from(&m).map_err(|_| VmErrorKind::InvalidNumberRange)?;
+        if m > n {
+            return Err(VmErrorKind::InvalidNumberRange);
+        }
+        self.require(m + 1)?;
+        let sigs: Vec<Vec<u8>> = self.stack.split_off(self.stack.len() - m);
+        // Historical off-by-one dummy element
+        self.pop()?;
+        let mut key_iter = keys.iter().rev();
+        for sig in sigs.iter().rev() {
+            let matched = key_iter.any(|key| self.checker.check_sig(sig, key));
+            if !matched {
+                return Ok(false);
+            }
+        }
+        Ok(true)
+    }
+}
+
+impl Default for Interpreter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Execute a single script on an empty stack and return the resulting stack
+pub fn execute(script: &[u8]) -> Result<Vec<Vec<u8>>, VmError> {
+    let mut vm = Interpreter::new();
+    vm.eval(script)?;
+    Ok(vm.stack)
+}
+
+/// Run a scriptSig / scriptPubKey pair given as hex, e.g. pasted from a block
+/// explorer. The two are concatenated scriptSig first and executed as one
+/// script; signatures go through `MockSignatureChecker`, so this checks the
+/// script logic rather than the transaction's signatures. On failure the error
+/// names the script, byte offset within it and opcode that stopped execution.
+pub fn verify_hex(script_sig_hex: &str, script_pubkey_hex: &str) -> crate::ghost::Result<bool> {
+    let decode = |name: &str, s: &str| {
+        from_hex(s.trim()).ok_or_else(|| Error::InvalidInput(format!("{} is not valid hex", name)))
+    };
+    let mut script = decode("scriptSig", script_sig_hex)?;
+    let sig_len = script.len();
+    script.extend(decode("scriptPubKey", script_pubkey_hex)?);
+    let mut vm = Interpreter::with_checker(Box::new(MockSignatureChecker));
+    vm.eval(&script).map_err(|e| {
+        let (name, offset) = match e.offset.checked_sub(sig_len) {
+            Some(offset) => ("scriptPubKey", offset),
+            None => ("scriptSig", e.offset),
+        };
+        Error::InvalidInput(format!(
+            "{} failed at byte {} (opcode 0x{:02x}): {:?}",
+            name, offset, e.opcode, e.kind
+        ))
+    })?;
+    Ok(vm.success())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ghost::script::{push_bytes, push_number};
+
+    #[test]
+    #[cfg(feature = "poseidon-script")]
+    fn test_poseidon_rounds_match_reference() {
+        use crate::ghost::crypto::Fp;
+        use crate::ghost::script::field_script::{debug_round_states, find_divergent_round, fp_to_scriptnum_bytes};
+        let input = [Fp::from(1u64), Fp::from(2u64), Fp::from(3u64)];
+        let unlock: Vec<u8> = input.iter().flat_map(|fp| push_bytes(&fp_to_scriptnum_bytes(fp))).collect();
+        if let Some(divergence) = find_divergent_round(Interpreter::new, &unlock, &debug_round_states(input)) {
+            panic!("{}", divergence);
+        }
+    }
+
+    #[test]
+    fn test_num_roundtrip() {
+        for n in [0i64, 1, -1, 127, 128, -128, 255, 256, -32768, 1 << 40] {
+            let big = BigInt::from(n);
+            assert_eq!(decode_num(&encode_num(&big)), big);
+        }
+        assert_eq!(encode_num(&BigInt::from(128)), vec![0x80, 0x00]);
+        assert_eq!(encode_num(&BigInt::from(-1)), vec![0x81]);
+    }
+
+    #[test]
+    fn test_truthiness() {
+        assert!(!is_truthy(&[]));
+        assert!(!is_truthy(&[0, 0]));
+        assert!(!is_truthy(&[0, 0x80]));
+        assert!(is_truthy(&[0, 1]));
+    }
+
+    #[test]
+    fn test_arithmetic() {
+        let mut script = push_number(7);
+        script.extend(push_number(5));
+        script.push(OP_SUB);
+        script.extend(push_number(2));
+        script.push(OP_NUMEQUAL);
+        let stack = execute(&script).unwrap();
+        assert_eq!(stack, vec![vec![1]]);
+    }
+
+    #[test]
+    fn test_split_and_cat() {
+        let mut script = push_bytes(&[1, 2, 3, 4]);
+        script.extend(push_number(1));
+        script.push(OP_SPLIT);
+        script.push(OP_SWAP);
+        script.push(OP_CAT);
+        let stack = execute(&script).unwrap();
+        assert_eq!(stack, vec![vec![2, 3, 4, 1]]);
+    }
+
+    #[test]
+    fn test_conditionals() {
+        let script = vec![OP_0, OP_IF, OP_2, OP_ELSE, OP_3, OP_ENDIF];
+        assert_eq!(execute(&script).unwrap(), vec![vec![3]]);
+        let unbalanced = vec![OP_1, OP_IF, OP_2];
+        assert_eq!(execute(&unbalanced).unwrap_err().kind, VmErrorKind::UnbalancedConditional);
+    }
+
+    #[test]
+    fn test_verify_failure_reports_offset() {
+        let script = vec![OP_1, OP_2, OP_EQUALVERIFY];
+        let err = execute(&script).unwrap_err();
+        assert_eq!(err.offset, 2);
+        assert_eq!(err.opcode, OP_EQUALVERIFY);
+        assert_eq!(err.kind, VmErrorKind::EqualVerifyFailed);
+    }
+
+    #[test]
+    fn test_p2pkh_with_mock_checker() {
+        let pubkey = vec![0x02; 33];
+        let mut locking = vec![OP_DUP, OP_HASH160, 20];
+        locking.extend(hash160(&pubkey));
+        locking.extend([OP_EQUALVERIFY, OP_CHECKSIG]);
+        let mut unlocking = push_bytes(&[0x30; 71]);
+        unlocking.extend(push_bytes(&pubkey));
+        assert!(Interpreter::with_checker(Box::new(MockSignatureChecker)).verify(&unlocking, &locking).unwrap());
+    }
+
+    #[test]
+    fn test_default_checker_verifies_ecdsa() {
+        use crate::ghost::script::{ecdsa_public_key, sign_ecdsa};
+        let privkey = [0x11; 32];
+        let pubkey = ecdsa_public_key(&privkey).unwrap();
+        let sighash = sha256(b"spend");
+        let signature = sign_ecdsa(&privkey, &sighash, 0x41).unwrap().to_bytes();
+        let locking = [push_bytes(&pubkey), vec![OP_CHECKSIG]].concat();
+        let unlocking = push_bytes(&signature);
+        assert!(!Interpreter::new().verify(&unlocking, &locking).unwrap());
+        let checker = EcdsaSignatureChecker::new(sighash);
+        assert!(Interpreter::with_checker(Box::new(checker)).verify(&unlocking, &locking).unwrap());
+        let other = EcdsaSignatureChecker::new(sha256(b"other"));
+        assert!(!Interpreter::with_checker(Box::new(other)).verify(&unlocking, &locking).unwrap());
+    }
+
+    #[test]
+    fn test_supported_opcodes() {
+        for opcode in [OP_0, OP_PUSHDATA4, OP_IF, OP_ENDIF, OP_RETURN, OP_CODESEPARATOR, OP_CHECKMULTISIGVERIFY] {
+            assert!(Interpreter::supports(opcode), "0x{:02x}", opcode);
+        }
+        let missing = Interpreter::unimplemented_opcodes();
+        for opcode in [OP_RESERVED, OP_VERIF, OP_LSHIFT, OP_RIPEMD160, OP_SHA1, 0xff] {
+            assert!(missing.contains(&opcode), "0x{:02x}", opcode);
+        }
+        assert!(!missing.contains(&OP_BIN2NUM));
+    }
+
+    #[test]
+    fn test_verify_hex_p2pkh() {
+        // Signature by key 1 over SHA256("Satoshi Nakamoto") with SIGHASH_ALL|FORKID
+        let script_sig = "483045022100934b1ea10a4b3c1757e2b0c017d0b6143ce3c9a7e6a4a49860d7a6ab210ee3d802202442ce9d2b916064108014783e923ec36b49743e2ffa1c4496f01a512aafd9e541\
+                          210279be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f81798";
+        let script_pubkey = "76a914751e76e8199196d454941c45d1b3a323f1433bd688ac";
+        assert_eq!(verify_hex(script_sig, script_pubkey).ok(), Some(true));
+        // Pubkey prefix flipped: hash no longer matches at OP_EQUALVERIFY
+        let tampered = script_sig.replace("210279be", "210379be");
+        let Err(Error::InvalidInput(msg)) = verify_hex(&tampered, script_pubkey) else {
+            panic!("tampered spend should fail");
+        };
+        assert_eq!(msg, "scriptPubKey failed at byte 23 (opcode 0x88): EqualVerifyFailed");
+        assert!(verify_hex("zz", script_pubkey).is_err());
+    }
+}
@@ -1,9 +1,95 @@
-use crate::ghost::crypto::{Fp, double_sha256};
+use std::fmt;
+use crate::ghost::crypto::{sha256, Fp};
+use super::hashing::double_sha256_streaming;
 use crate::ghost::circuit::{StandardIntent, Proof};
-use crate::ghost::script::{IpaHints, PoseidonHints};
+use crate::ghost::script::{to_hex, IpaHints, PoseidonHints, PushError, SighashPolicy, SighashPreimage};
+use crate::ghost::script::bundle::WitnessBundle;
+use crate::ghost::script::binding_diff::{diff_hash_outputs, parse_outputs, ReconstructionDiff};
+use crate::ghost::script::der::{self, DerError};
+use k256::ecdsa::{signature::hazmat::{PrehashSigner, PrehashVerifier}, Signature, SigningKey, VerifyingKey};
+use crate::ghost::script::error::{ScriptErrorKind, ScriptResult, WitnessComponent};
+use crate::ghost::script::guard_engine::HASH_OUTPUTS_TAIL;
+use crate::ghost::script::sighash::SIGHASH_ANYONECANPAY;
 use crate::ghost::binding::reconstruction::ReconstructionWitness;
 use crate::ghost::{Error, Result};
-#[derive(Clone, Debug)]
+// Witness Debug output ends up in logs, so signatures, Lamport preimages and
+// sighash preimages print as size, sha256 prefix and first/last 4 bytes, and
+// hints, proofs and output blobs as size and sha256 prefix only. Full dumps
+// are opt-in through `RedactedDebug::full_debug`.
+const REDACT_EDGE: usize = 4;
+const DIGEST_PREFIX: usize = 4;
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Shown {
+    Full,
+    Redacted,
+    Summary,
+}
+pub(crate) struct DebugBytes<'a> {
+    bytes: &'a [u8],
+    shown: Shown,
+}
+
+impl fmt::Debug for DebugBytes<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.shown == Shown::Full {
+            return write!(f, "{}", to_hex(self.bytes));
+        }
+        let digest = sha256(self.bytes);
+        write!(f, "<{} bytes sha256:{}", self.bytes.len(), to_hex(&digest[..DIGEST_PREFIX]))?;
+        if self.shown == Shown::Redacted && self.bytes.len() > 2 * REDACT_EDGE {
+            let (head, tail) = (&self.bytes[..REDACT_EDGE], &self.bytes[self.bytes.len() - REDACT_EDGE..]);
+            write!(f, " {}..{}", to_hex(head), to_hex(tail))?;
+        }
+        write!(f, ">")
+    }
+}
+
+/// Signatures and preimages: truncated unless `full`
+pub(crate) fn sensitive(bytes: &[u8], full: bool) -> DebugBytes<'_> {
+    DebugBytes { bytes, shown: if full { Shown::Full } else { Shown::Redacted } }
+}
+/// Hints, proofs and serialized outputs: length and digest unless `full`
+pub(crate) fn summary(bytes: &[u8], full: bool) -> DebugBytes<'_> {
+    DebugBytes { bytes, shown: if full { Shown::Full } else { Shown::Summary } }
+}
+
+/// Types whose `Debug` redacts secrets; `full_debug` dumps everything
+pub trait RedactedDebug {
+    fn fmt_debug(&self, f: &mut fmt::Formatter<'_>, full: bool) -> fmt::Result;
+    /// Unredacted view for local debugging; never log it
+    fn full_debug(&self) -> WitnessDebug<'_, Self> {
+        WitnessDebug::new(self, true)
+    }
+}
+pub struct WitnessDebug<'a, T: ?Sized> {
+    value: &'a T,
+    full: bool,
+}
+
+impl<'a, T: ?Sized> WitnessDebug<'a, T> {
+    pub(crate) fn new(value: &'a T, full: bool) -> Self {
+        Self { value, full }
+    }
+}
+
+impl<T: RedactedDebug + ?Sized> fmt::Debug for WitnessDebug<'_, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.value.fmt_debug(f, self.full)
+    }
+}
+
+macro_rules! redacted_debug {
+    ($($ty:ty),*) => {$(
+        impl fmt::Debug for $ty {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                RedactedDebug::fmt_debug(self, f, false)
+            }
+        }
+    )*};
+}
+pub(crate) use redacted_debug;
+
+#[derive(Clone)]
 pub struct EcdsaSignature {
     pub der_bytes: Vec<u8>,
     pub sighash_flag: u8,
@@ -27,9 +113,73 @@ impl EcdsaSignature {
         bytes.push(self.sighash_flag);
         bytes
     }
+    /// Inverse of `to_bytes`: DER followed by the flag byte
+    pub fn from_bytes(bytes: &[u8]) -> Option<Self> {
+        let (&flag, der) = bytes.split_last()?;
+        Some(Self::with_sighash(der.to_vec(), flag))
+    }
     pub fn size(&self) -> usize {
         self.der_bytes.len() + 1
     }
+    /// Whether the DER is the strict minimal encoding relay policy requires
+    pub fn is_canonical(&self) -> bool {
+        der::der_decode(&self.der_bytes).is_some()
+    }
+    /// Same signature with R and S re-encoded minimally: redundant leading
+    /// zeros stripped, a single zero added ahead of a set high bit. The
+    /// sighash flag is kept; S is not changed to low S.
+    pub fn normalize(&self) -> std::result::Result<EcdsaSignature, DerError> {
+        let (r, s) = der::der_decode_lax(&self.der_bytes)?;
+        Ok(Self::with_sighash(der::der_encode(&r, &s), self.sighash_flag))
+    }
+    // Normalized where the DER parses; placeholders and other unparseable
+    // bytes pass through as they always have
+    pub(crate) fn normalized_or_verbatim(self) -> Self {
+        self.normalize().unwrap_or(self)
+    }
+    /// Whether the strict DER signature is valid for `sighash` under the
+    /// compressed `pubkey`. High-S signatures are rejected.
+    pub fn verify(&self, pubkey: &[u8], sighash: &[u8; 32]) -> bool {
+        let Some((r, s)) = der::der_decode(&self.der_bytes).filter(|_| pubkey.len() == 33) else {
+            return false;
+        };
+        let (Ok(key), Ok(signature)) = (VerifyingKey::from_sec1_bytes(pubkey), Signature::from_scalars(r, s)) else {
+            return false;
+        };
+        key.verify_prehash(sighash, &signature).is_ok()
+    }
+}
+
+fn signing_key(privkey: &[u8; 32]) -> Result<SigningKey> {
+    SigningKey::from_bytes(privkey.into()).map_err(|_| Error::InvalidInput("Private key out of range".into()))
+}
+
+/// Deterministic (RFC 6979) low-S signature over `sighash` (the double
+/// SHA256 of the preimage) with `sighash_flag` appended on serialization
+pub fn sign_ecdsa(privkey: &[u8; 32], sighash: &[u8; 32], sighash_flag: u8) -> Result<EcdsaSignature> {
+    let signature: Signature = signing_key(privkey)?
+        .sign_prehash(sighash)
+        .map_err(|e| Error::InvalidInput(format!("ECDSA signing failed: {}", e)))?;
+    let signature = signature.normalize_s().unwrap_or(signature);
+    let (r, s) = signature.split_bytes();
+    Ok(EcdsaSignature::with_sighash(der::der_encode(&r.into(), &s.into()), sighash_flag))
+}
+
+/// Compressed SEC1 public key for `privkey`
+pub fn ecdsa_public_key(privkey: &[u8; 32]) -> Result<[u8; 33]> {
+    let point = signing_key(privkey)?.verifying_key().to_encoded_point(true);
+    let mut pubkey = [0u8; 33];
+    pubkey.copy_from_slice(point.as_bytes());
+    Ok(pubkey)
+}
+
+impl RedactedDebug for EcdsaSignature {
+    fn fmt_debug(&self, f: &mut fmt::Formatter<'_>, full: bool) -> fmt::Result {
+        f.debug_struct("EcdsaSignature")
+            .field("der_bytes", &sensitive(&self.der_bytes, full))
+            .field("sighash_flag", &format_args!("{:#04x}", self.sighash_flag))
+            .finish()
+    }
 }
 
 impl Default for EcdsaSignature {
@@ -41,7 +191,66 @@ impl Default for EcdsaSignature {
     }
 }
 
-#[derive(Clone, Debug)]
+/// Serialized output frame: value (8 LE) || 0x20 || 32-byte script
+pub const INTENT_OUTPUT_SIZE: usize = 41;
+
+/// Serialize a batch of intents in a single reconstruction pass
+pub fn serialize_intents(intents: &[StandardIntent]) -> Vec<u8> {
+    let bytes = ReconstructionWitness::new(intents, &[]).app_outputs_bytes;
+    debug_assert_eq!(bytes.len(), intents.len() * INTENT_OUTPUT_SIZE);
+    bytes
+}
+
+pub fn deserialize_intents(bytes: &[u8]) -> Result<Vec<StandardIntent>> {
+    if !bytes.len().is_multiple_of(INTENT_OUTPUT_SIZE) {
+        return Err(Error::InvalidInput(format!(
+            "Intent blob length {} is not a multiple of {}",
+            bytes.len(),
+            INTENT_OUTPUT_SIZE
+        )));
+    }
+    bytes
+        .chunks_exact(INTENT_OUTPUT_SIZE)
+        .enumerate()
+        .map(|(i, frame)| {
+            StandardIntent::from_output_bytes(frame)
+                .ok_or_else(|| Error::InvalidInput(format!("Malformed intent frame {}", i)))
+        })
+        .collect()
+}
+
+/// Spending witness serialized as a scriptSig, so fee estimation and the
+/// VM can take any of the witness shapes
+pub trait Witness {
+    /// scriptSig with every pushed element capped at `max_push`
+    fn try_to_script_sig(&self, max_push: usize) -> std::result::Result<Vec<u8>, PushError>;
+    /// scriptSig size without serializing it
+    fn estimate_size(&self) -> usize;
+    /// Serialized scriptSig size; fails on an element over u32::MAX bytes
+    fn actual_size(&self) -> std::result::Result<usize, PushError> {
+        Ok(self.try_to_script_sig(usize::MAX)?.len())
+    }
+}
+
+impl Witness for PaymasterWitness {
+    fn try_to_script_sig(&self, max_push: usize) -> std::result::Result<Vec<u8>, PushError> {
+        PaymasterWitness::try_to_script_sig(self, max_push)
+    }
+    fn estimate_size(&self) -> usize {
+        PaymasterWitness::estimate_size(self)
+    }
+}
+
+impl Witness for StrictWitness {
+    fn try_to_script_sig(&self, max_push: usize) -> std::result::Result<Vec<u8>, PushError> {
+        StrictWitness::try_to_script_sig(self, max_push)
+    }
+    fn estimate_size(&self) -> usize {
+        StrictWitness::estimate_size(self)
+    }
+}
+
+#[derive(Clone)]
 pub struct PaymasterWitness {
     pub proof: Proof,
     pub ipa_hints: IpaHints,
@@ -51,7 +260,26 @@ pub struct PaymasterWitness {
     pub app_fields: Vec<Fp>,
     pub preimage: Vec<u8>,
     pub user_signature: EcdsaSignature,
-    pub sponsor_signature: Option<EcdsaSignature>,
+    /// In the order the sponsor tail checks them, e.g. key order for a multisig tail
+    pub sponsor_signatures: Vec<EcdsaSignature>,
+}
+
+impl RedactedDebug for PaymasterWitness {
+    fn fmt_debug(&self, f: &mut fmt::Formatter<'_>, full: bool) -> fmt::Result {
+        let field_count = self.app_fields.len();
+        let app_fields: &dyn fmt::Debug = if full { &self.app_fields } else { &field_count };
+        f.debug_struct("PaymasterWitness")
+            .field("proof", &summary(&self.proof.to_bytes(), full))
+            .field("ipa_hints", &summary(&self.ipa_hints.to_bytes(), full))
+            .field("poseidon_hints", &summary(&self.poseidon_hints.to_bytes(), full))
+            .field("app_outputs_bytes", &summary(&self.app_outputs_bytes, full))
+            .field("change_outputs_bytes", &summary(&self.change_outputs_bytes, full))
+            .field("app_fields", app_fields)
+            .field("preimage", &sensitive(&self.preimage, full))
+            .field("user_signature", &WitnessDebug::new(&self.user_signature, full))
+            .field("sponsor_signatures", &self.sponsor_signatures.iter().map(|s| WitnessDebug::new(s, full)).collect::<Vec<_>>())
+            .finish()
+    }
 }
 
 impl PaymasterWitness {
@@ -73,70 +301,141 @@ impl PaymasterWitness {
             app_fields: reconstruction.app_fields,
             preimage,
             user_signature: EcdsaSignature::default(),
-            sponsor_signature: None,
+            sponsor_signatures: Vec::new(),
         }
     }
     pub fn with_user_signature(mut self, sig: EcdsaSignature) -> Self {
         self.user_signature = sig;
         self
     }
+    /// Appends a sponsor signature; call once per sponsor key, in tail order
     pub fn with_sponsor_signature(mut self, sig: EcdsaSignature) -> Self {
-        self.sponsor_signature = Some(sig);
+        self.sponsor_signatures.push(sig);
         self
     }
+    /// Change outputs as separate frames, one stack item each; a witness
+    /// without change still pushes one empty item
+    pub fn change_output_frames(&self) -> Vec<&[u8]> {
+        if self.change_outputs_bytes.is_empty() {
+            return vec![&[]];
+        }
+        self.change_outputs_bytes.chunks(INTENT_OUTPUT_SIZE).collect()
+    }
     pub fn compute_hash_outputs(&self) -> [u8; 32] {
-        let mut full_bytes = Vec::new();
-        full_bytes.extend(&self.app_outputs_bytes);
-        full_bytes.extend(&self.change_outputs_bytes);
-        double_sha256(&full_bytes)
+        double_sha256_streaming([&self.app_outputs_bytes[..], &self.change_outputs_bytes[..]])
+    }
+    /// Same witness with the user's preimage rebuilt for ALL|ANYONECANPAY:
+    /// hashPrevouts and hashSequence zeroed, so sponsors can add fee inputs
+    /// after the user signs. Sponsors still sign their own inputs in full.
+    /// The user signature must be made over the returned preimage.
+    pub fn for_anyonecanpay(mut self) -> Result<Self> {
+        let mut preimage = SighashPreimage::from_bytes(&self.preimage)
+            .ok_or_else(|| Error::InvalidInput("Preimage is not a BIP-143 serialization".to_string()))?;
+        preimage.hash_prevouts = [0u8; 32];
+        preimage.hash_sequence = [0u8; 32];
+        preimage.sighash_type[0] |= SIGHASH_ANYONECANPAY;
+        self.preimage = preimage.to_bytes();
+        Ok(self)
     }
-    pub fn verify_reconstruction(&self) -> Result<()> {
+    /// The user signature's flag and the witness outputs against the
+    /// preimage; a flag mismatch is `SignatureInvalid { index: 0 }`
+    pub fn verify_reconstruction(&self) -> ScriptResult<()> {
         if self.preimage.len() < 132 {
-            return Err(Error::InvalidInput("Preimage too short".to_string()));
+            return Err(malformed("Preimage too short"));
+        }
+        check_sighash_flag(&self.preimage, &self.user_signature)?;
+        // ANYONECANPAY signs neither the other inputs nor their sequences
+        let flag = self.preimage[self.preimage.len() - 4];
+        if flag & SIGHASH_ANYONECANPAY != 0 && self.preimage[4..68].iter().any(|&b| b != 0) {
+            return Err(malformed("ANYONECANPAY preimage must zero hashPrevouts and hashSequence"));
         }
+        let offset = self.preimage.len() - HASH_OUTPUTS_TAIL;
         let mut expected = [0u8; 32];
-        expected.copy_from_slice(&self.preimage[100..132]);
+        expected.copy_from_slice(&self.preimage[offset..offset + 32]);
         let computed = self.compute_hash_outputs();
         if expected != computed {
-            return Err(Error::BindingMismatch);
+            return Err(ScriptErrorKind::BindingMismatch { expected, computed });
         }
         Ok(())
     }
+    /// Where the witness outputs deviate from the preimage's hashOutputs,
+    /// for a `verify_reconstruction` that failed with a binding mismatch
+    pub fn diff_reconstruction(&self) -> Result<ReconstructionDiff> {
+        let preimage = SighashPreimage::from_bytes(&self.preimage)
+            .ok_or_else(|| Error::InvalidInput("Preimage is not a BIP-143 serialization".to_string()))?;
+        let frames = |bytes: &[u8]| {
+            parse_outputs(bytes).ok_or_else(|| Error::InvalidInput("Witness outputs are not serialized outputs".to_string()))
+        };
+        Ok(diff_hash_outputs(&preimage, &frames(&self.app_outputs_bytes)?, &frames(&self.change_outputs_bytes)?))
+    }
+    /// Panics on an element over u32::MAX bytes
+    #[deprecated(note = "use try_to_script_sig, which reports oversized pushes")]
     pub fn to_script_sig(&self) -> Vec<u8> {
+        self.try_to_script_sig(usize::MAX).expect("scriptSig push exceeds u32::MAX")
+    }
+    /// `to_script_sig` with every pushed element capped at `max_push`
+    pub fn try_to_script_sig(&self, max_push: usize) -> std::result::Result<Vec<u8>, PushError> {
         let mut script = Vec::new();
-        if let Some(ref sig) = self.sponsor_signature {
+        for sig in &self.sponsor_signatures {
             let sig_bytes = sig.to_bytes();
-            script.extend(push_data(&sig_bytes));
+            script.extend(try_push_data(&sig_bytes, max_push)?);
         }
         let user_sig_bytes = self.user_signature.to_bytes();
-        script.extend(push_data(&user_sig_bytes));
-        script.extend(push_data(&self.preimage));
-        script.extend(push_data(&self.change_outputs_bytes));
-        script.extend(push_data(&self.app_outputs_bytes));
+        script.extend(try_push_data(&user_sig_bytes, max_push)?);
+        script.extend(try_push_data(&self.preimage, max_push)?);
+        for change in self.change_output_frames() {
+            script.extend(try_push_data(change, max_push)?);
+        }
+        script.extend(try_push_data(&self.app_outputs_bytes, max_push)?);
         let poseidon_bytes = self.poseidon_hints.to_bytes();
-        script.extend(push_data(&poseidon_bytes));
+        script.extend(try_push_data(&poseidon_bytes, max_push)?);
         let ipa_bytes = self.ipa_hints.to_bytes();
-        script.extend(push_data(&ipa_bytes));
+        script.extend(try_push_data(&ipa_bytes, max_push)?);
         let proof_bytes = self.proof.to_bytes();
-        script.extend(push_data(&proof_bytes));
-        script
+        script.extend(try_push_data(&proof_bytes, max_push)?);
+        Ok(script)
     }
     pub fn estimate_size(&self) -> usize {
         let mut size = 0;
-        size += self.proof.to_bytes().len() + 3;
-        size += self.ipa_hints.to_bytes().len() + 3;
-        size += self.poseidon_hints.to_bytes().len() + 3;
-        size += self.app_outputs_bytes.len() + 3;
-        size += self.change_outputs_bytes.len() + 3;
-        size += self.preimage.len() + 3;
-        size += self.user_signature.size() + 1;
-        if let Some(ref sig) = self.sponsor_signature {
-            size += sig.size() + 1;
-        }
+        size += push_data_size(&self.proof.to_bytes());
+        size += push_data_size(&self.ipa_hints.to_bytes());
+        size += push_data_size(&self.poseidon_hints.to_bytes());
+        size += push_data_size(&self.app_outputs_bytes);
+        size += self.change_output_frames().iter().map(|c| push_data_size(c)).sum::<usize>();
+        size += push_data_size(&self.preimage);
+        size += push_data_size(&self.user_signature.to_bytes());
+        size += self.sponsor_signatures.iter().map(|sig| push_data_size(&sig.to_bytes())).sum::<usize>();
         size
     }
 }
 
+// The signature commits to the preimage built for its own flag, so the low
+// byte of the trailing sighashType must match or the CHECKSIG fails on chain
+fn check_sighash_flag(preimage: &[u8], signature: &EcdsaSignature) -> ScriptResult<()> {
+    let preimage_flag = preimage
+        .len()
+        .checked_sub(4)
+        .map(|i| preimage[i])
+        .ok_or_else(|| malformed("Preimage too short"))?;
+    if preimage_flag != signature.sighash_flag {
+        return Err(ScriptErrorKind::SignatureInvalid { index: 0 });
+    }
+    Ok(())
+}
+
+fn try_push_data(data: &[u8], max_len: usize) -> std::result::Result<Vec<u8>, PushError> {
+    super::check_push_len(data.len(), max_len)?;
+    Ok(push_data(data))
+}
+
+// Length of `push_data(data)` without building it
+fn push_data_size(data: &[u8]) -> usize {
+    match data {
+        [n] if (1..=16).contains(n) => 1,
+        _ => super::push_size(data.len()),
+    }
+}
+
 fn push_data(data: &[u8]) -> Vec<u8> {
     let mut result = Vec::new();
     let len = data.len();
@@ -156,14 +455,15 @@ fn push_data(data: &[u8]) -> Vec<u8> {
         result.extend(&(len as u16).to_le_bytes());
         result.extend(data);
     } else {
+        let len = u32::try_from(len).expect("push exceeds u32::MAX");
         result.push(0x4e);
-        result.extend(&(len as u32).to_le_bytes());
+        result.extend(&len.to_le_bytes());
         result.extend(data);
     }
     result
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone)]
 pub struct StrictWitness {
     pub proof: Proof,
     pub ipa_hints: IpaHints,
@@ -172,6 +472,20 @@ pub struct StrictWitness {
     pub signature: EcdsaSignature,
 }
 
+impl RedactedDebug for StrictWitness {
+    fn fmt_debug(&self, f: &mut fmt::Formatter<'_>, full: bool) -> fmt::Result {
+        f.debug_struct("StrictWitness")
+            .field("proof", &summary(&self.proof.to_bytes(), full))
+            .field("ipa_hints", &summary(&self.ipa_hints.to_bytes(), full))
+            .field("poseidon_hints", &summary(&self.poseidon_hints.to_bytes(), full))
+            .field("preimage", &sensitive(&self.preimage, full))
+            .field("signature", &WitnessDebug::new(&self.signature, full))
+            .finish()
+    }
+}
+
+redacted_debug!(EcdsaSignature, PaymasterWitness, StrictWitness);
+
 impl StrictWitness {
     pub fn new(
         proof: Proof,
@@ -191,26 +505,35 @@ impl StrictWitness {
         self.signature = sig;
         self
     }
+    pub fn verify_sighash_flag(&self) -> ScriptResult<()> {
+        check_sighash_flag(&self.preimage, &self.signature)
+    }
+    /// Panics on an element over u32::MAX bytes
+    #[deprecated(note = "use try_to_script_sig, which reports oversized pushes")]
     pub fn to_script_sig(&self) -> Vec<u8> {
+        self.try_to_script_sig(usize::MAX).expect("scriptSig push exceeds u32::MAX")
+    }
+    /// `to_script_sig` with every pushed element capped at `max_push`
+    pub fn try_to_script_sig(&self, max_push: usize) -> std::result::Result<Vec<u8>, PushError> {
         let mut script = Vec::new();
         let sig_bytes = self.signature.to_bytes();
-        script.extend(push_data(&sig_bytes));
-        script.extend(push_data(&self.preimage));
+        script.extend(try_push_data(&sig_bytes, max_push)?);
+        script.extend(try_push_data(&self.preimage, max_push)?);
         let poseidon_bytes = self.poseidon_hints.to_bytes();
-        script.extend(push_data(&poseidon_bytes));
+        script.extend(try_push_data(&poseidon_bytes, max_push)?);
         let ipa_bytes = self.ipa_hints.to_bytes();
-        script.extend(push_data(&ipa_bytes));
+        script.extend(try_push_data(&ipa_bytes, max_push)?);
         let proof_bytes = self.proof.to_bytes();
-        script.extend(push_data(&proof_bytes));
-        script
+        script.extend(try_push_data(&proof_bytes, max_push)?);
+        Ok(script)
     }
     pub fn estimate_size(&self) -> usize {
         let mut size = 0;
-        size += self.proof.to_bytes().len() + 3;
-        size += self.ipa_hints.to_bytes().len() + 3;
-        size += self.poseidon_hints.to_bytes().len() + 3;
-        size += self.preimage.len() + 3;
-        size += self.signature.size() + 1;
+        size += push_data_size(&self.proof.to_bytes());
+        size += push_data_size(&self.ipa_hints.to_bytes());
+        size += push_data_size(&self.poseidon_hints.to_bytes());
+        size += push_data_size(&self.preimage);
+        size += push_data_size(&self.signature.to_bytes());
         size
     }
 }
@@ -224,13 +547,38 @@ pub struct PaymasterWitnessBuilder {
     change_outputs: Vec<StandardIntent>,
     preimage: Option<Vec<u8>>,
     user_signature: Option<EcdsaSignature>,
-    sponsor_signature: Option<EcdsaSignature>,
+    sponsor_signatures: Vec<EcdsaSignature>,
+    expected_app_fields: Option<Vec<Fp>>,
+    sighash_policy: Option<SighashPolicy>,
+    keep_signature_encoding: bool,
 }
 
 impl PaymasterWitnessBuilder {
     pub fn new() -> Self {
         Self::default()
     }
+    /// Start from a prover bundle. `signer` is called with the preimage to
+    /// produce the user signature; app outputs added afterwards must
+    /// reconstruct the bundle's app fields or `build` fails.
+    pub fn from_bundle(
+        bundle: WitnessBundle,
+        preimage: Vec<u8>,
+        signer: impl FnOnce(&[u8]) -> EcdsaSignature,
+    ) -> Self {
+        let user_signature = signer(&preimage);
+        Self {
+            proof: Some(Proof {
+                bytes: bundle.proof_bytes,
+                public_inputs: bundle.app_fields.clone(),
+            }),
+            ipa_hints: Some(bundle.ipa_hints),
+            poseidon_hints: Some(bundle.poseidon_hints),
+            preimage: Some(preimage),
+            user_signature: Some(user_signature),
+            expected_app_fields: Some(bundle.app_fields),
+            ..Self::default()
+        }
+    }
     pub fn proof(mut self, proof: Proof) -> Self {
         self.proof = Some(proof);
         self
@@ -268,18 +616,37 @@ impl PaymasterWitnessBuilder {
         self
     }
     pub fn sponsor_signature(mut self, sig: EcdsaSignature) -> Self {
-        self.sponsor_signature = Some(sig);
+        self.sponsor_signatures.push(sig);
+        self
+    }
+    pub fn sponsor_signatures(mut self, sigs: Vec<EcdsaSignature>) -> Self {
+        self.sponsor_signatures = sigs;
+        self
+    }
+    /// Policy of the tail the user signature is checked by; `build` rejects
+    /// a user signature it would fail on chain
+    pub fn sighash_policy(mut self, policy: SighashPolicy) -> Self {
+        self.sighash_policy = Some(policy);
+        self
+    }
+    /// Embed signatures exactly as given; by default `build` re-encodes them
+    /// with `EcdsaSignature::normalize`
+    pub fn keep_signature_encoding(mut self) -> Self {
+        self.keep_signature_encoding = true;
         self
     }
-    pub fn build(self) -> Result<PaymasterWitness> {
-        let proof = self.proof.ok_or_else(|| 
-            Error::InvalidInput("Missing proof".to_string()))?;
-        let ipa_hints = self.ipa_hints.ok_or_else(|| 
-            Error::InvalidInput("Missing IPA hints".to_string()))?;
-        let poseidon_hints = self.poseidon_hints.ok_or_else(|| 
-            Error::InvalidInput("Missing Poseidon hints".to_string()))?;
-        let preimage = self.preimage.ok_or_else(|| 
-            Error::InvalidInput("Missing preimage".to_string()))?;
+    pub fn build(mut self) -> Result<PaymasterWitness> {
+        if !self.keep_signature_encoding {
+            self.user_signature = self.user_signature.map(EcdsaSignature::normalized_or_verbatim);
+            self.sponsor_signatures = self.sponsor_signatures.into_iter().map(EcdsaSignature::normalized_or_verbatim).collect();
+        }
+        if let (Some(policy), Some(sig)) = (&self.sighash_policy, &self.user_signature) {
+            policy.check_signature(sig)?;
+        }
+        let proof = self.proof.ok_or_else(|| missing(WitnessComponent::Proof))?;
+        let ipa_hints = self.ipa_hints.ok_or_else(|| missing(WitnessComponent::IpaHints))?;
+        let poseidon_hints = self.poseidon_hints.ok_or_else(|| missing(WitnessComponent::PoseidonHints))?;
+        let preimage = self.preimage.ok_or_else(|| missing(WitnessComponent::Preimage))?;
         let mut witness = PaymasterWitness::new(
             proof,
             ipa_hints,
@@ -291,13 +658,26 @@ impl PaymasterWitnessBuilder {
         if let Some(sig) = self.user_signature {
             witness = witness.with_user_signature(sig);
         }
-        if let Some(sig) = self.sponsor_signature {
+        for sig in self.sponsor_signatures {
             witness = witness.with_sponsor_signature(sig);
         }
+        if let Some(expected) = self.expected_app_fields {
+            if expected != witness.app_fields {
+                return Err(Error::BindingMismatch);
+            }
+        }
         Ok(witness)
     }
 }
 
+fn missing(component: WitnessComponent) -> Error {
+    ScriptErrorKind::WitnessMissingComponent { component }.into()
+}
+
+fn malformed(reason: &str) -> ScriptErrorKind {
+    ScriptErrorKind::MalformedPreimage { reason: reason.to_string() }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -319,6 +699,40 @@ mod tests {
         assert_eq!(bytes.last(), Some(&0x41));
     }
     #[test]
+    fn test_sign_ecdsa() {
+        let privkey = [0x2a; 32];
+        let pubkey = ecdsa_public_key(&privkey).unwrap();
+        let sighash = crate::ghost::crypto::double_sha256(b"preimage");
+        let sig = sign_ecdsa(&privkey, &sighash, 0x41).unwrap();
+        assert!(sig.verify(&pubkey, &sighash));
+        assert!(!sig.verify(&pubkey, &[0u8; 32]));
+        assert!(!sig.verify(&ecdsa_public_key(&[0x2b; 32]).unwrap(), &sighash));
+        assert_eq!(sig.to_bytes().last(), Some(&0x41));
+        assert_eq!(sig.der_bytes[0], 0x30);
+        assert!(sig.size() <= 73);
+        assert!(!EcdsaSignature::default().verify(&pubkey, &sighash));
+        assert!(sign_ecdsa(&[0u8; 32], &sighash, 0x41).is_err());
+        assert!(ecdsa_public_key(&[0xff; 32]).is_err());
+    }
+    #[test]
+    fn test_sign_ecdsa_rfc6979_vector() {
+        use crate::ghost::crypto::sha256;
+        let mut privkey = [0u8; 32];
+        privkey[31] = 1;
+        let pubkey = ecdsa_public_key(&privkey).unwrap();
+        assert_eq!(hex::encode(pubkey), "0279be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f81798");
+        let hash = sha256(b"Satoshi Nakamoto");
+        let sig = sign_ecdsa(&privkey, &hash, 0x41).unwrap();
+        let (r, s) = der::der_decode(&sig.der_bytes).unwrap();
+        assert_eq!(hex::encode(r), "934b1ea10a4b3c1757e2b0c017d0b6143ce3c9a7e6a4a49860d7a6ab210ee3d8");
+        assert_eq!(hex::encode(s), "2442ce9d2b916064108014783e923ec36b49743e2ffa1c4496f01a512aafd9e5");
+        assert!(sig.verify(&pubkey, &hash));
+        // The same signature with S replaced by n - S
+        let high_s = -*Signature::from_scalars(r, s).unwrap().s();
+        let high = EcdsaSignature::with_sighash(der::der_encode(&r, &high_s.to_bytes().into()), 0x41);
+        assert!(!high.verify(&pubkey, &hash));
+    }
+    #[test]
     fn test_push_data_small() {
         let data = vec![0x01, 0x02, 0x03];
         let pushed = push_data(&data);
@@ -334,6 +748,24 @@ mod tests {
         assert_eq!(&pushed[2..], &data);
     }
     #[test]
+    fn test_intent_batch_round_trip() {
+        let intents: Vec<_> = (0..50u64).map(|i| make_intent(1, 100 + i, i, 0xA000 + i)).collect();
+        let bytes = serialize_intents(&intents);
+        assert_eq!(bytes.len(), 50 * INTENT_OUTPUT_SIZE);
+        let decoded = deserialize_intents(&bytes).unwrap();
+        assert_eq!(decoded.len(), 50);
+        assert_eq!(serialize_intents(&decoded), bytes);
+    }
+    #[test]
+    fn test_intent_batch_length_mismatch() {
+        let bytes = serialize_intents(&[make_intent(1, 90, 1, 0xAAAA)]);
+        assert!(deserialize_intents(&bytes[..INTENT_OUTPUT_SIZE - 1]).is_err());
+        let mut extra = bytes.clone();
+        extra.push(0);
+        assert!(deserialize_intents(&extra).is_err());
+        assert!(deserialize_intents(&[]).unwrap().is_empty());
+    }
+    #[test]
     fn test_paymaster_witness_creation() {
         let app_outputs = vec![
             make_intent(1, 90, 1, 0xAAAA),
@@ -363,7 +795,7 @@ mod tests {
             &[make_intent(1, 10, 2, 0xBBBB)],
             vec![0x00; 180],
         );
-        let script_sig = witness.to_script_sig();
+        let script_sig = witness.try_to_script_sig(usize::MAX).unwrap();
         assert!(!script_sig.is_empty());
     }
     #[test]
@@ -378,10 +810,202 @@ mod tests {
             .user_signature(EcdsaSignature::default())
             .build()
             .unwrap();
-        assert!(witness.sponsor_signature.is_none());
+        assert!(witness.sponsor_signatures.is_empty());
         assert!(!witness.app_outputs_bytes.is_empty());
     }
     #[test]
+    fn test_builder_from_bundle() {
+        use crate::ghost::script::GuardConfig;
+        let app_outputs = vec![make_intent(1, 90, 1, 0xAAAA)];
+        let app_fields = ReconstructionWitness::new(&app_outputs, &[]).app_fields;
+        let bundle = WitnessBundle::new(
+            &GuardConfig::new(1, 1).paymaster(1000),
+            IpaHints::placeholder(10),
+            PoseidonHints::placeholder(4),
+            app_fields,
+            vec![0xAB; 100],
+        );
+        let bundle = WitnessBundle::decode(&bundle.encode()).unwrap();
+        let sign = |preimage: &[u8]| EcdsaSignature::with_sighash(vec![0x30; 70], preimage[preimage.len() - 4]);
+        let witness = PaymasterWitnessBuilder::from_bundle(bundle.clone(), vec![0x41; 180], sign)
+            .app_outputs(app_outputs)
+            .build()
+            .unwrap();
+        assert_eq!(witness.proof.bytes, vec![0xAB; 100]);
+        assert_eq!(witness.user_signature.sighash_flag, 0x41);
+        let mismatched = PaymasterWitnessBuilder::from_bundle(bundle, vec![0x41; 180], sign)
+            .app_output(make_intent(1, 91, 1, 0xAAAA))
+            .build();
+        assert!(matches!(mismatched, Err(Error::BindingMismatch)));
+    }
+    #[test]
+    fn test_builder_checks_sighash_policy() {
+        let builder = |flag: u8| PaymasterWitnessBuilder::new()
+            .proof(make_test_proof())
+            .ipa_hints(IpaHints::placeholder(10))
+            .poseidon_hints(PoseidonHints::placeholder(4))
+            .preimage(vec![0x00; 180])
+            .user_signature(EcdsaSignature::with_sighash(vec![0x30; 70], flag))
+            .sighash_policy(SighashPolicy::all_only());
+        assert!(builder(0x41).build().is_ok());
+        let err = builder(0x42).build().unwrap_err();
+        assert!(matches!(err, Error::InvalidInput(msg) if msg.contains("not allowed by policy")));
+    }
+    #[test]
+    fn test_sighash_flag_must_match_preimage() {
+        let mut witness = PaymasterWitness::new(
+            make_test_proof(),
+            IpaHints::placeholder(10),
+            PoseidonHints::placeholder(4),
+            &[make_intent(1, 90, 1, 0xAAAA)],
+            &[make_intent(1, 10, 2, 0xBBBB)],
+            vec![0x00; 180],
+        );
+        let hash_outputs = witness.compute_hash_outputs();
+        witness.preimage[140..172].copy_from_slice(&hash_outputs);
+        witness.preimage[176] = 0x41;
+        assert!(witness.verify_reconstruction().is_ok());
+        witness.preimage[176] = 0x01;
+        let err = witness.verify_reconstruction().unwrap_err();
+        assert_eq!(err, ScriptErrorKind::SignatureInvalid { index: 0 });
+        let witness = witness.with_user_signature(EcdsaSignature::with_sighash(vec![0x30; 70], 0x01));
+        assert!(witness.verify_reconstruction().is_ok());
+    }
+    #[test]
+    fn test_anyonecanpay_sighash_ignores_sponsor_inputs() {
+        use crate::ghost::crypto::double_sha256;
+        use crate::ghost::script::{OutPoint, SighashFlag, SighashPreimageBuilder, TxInput};
+        let draft = PaymasterWitness::new(make_test_proof(), IpaHints::placeholder(10), PoseidonHints::placeholder(4), &[make_intent(1, 90, 1, 0xAAAA)], &[], Vec::new());
+        let user = TxInput::new(OutPoint::new([0x01; 32], 0), Vec::new());
+        let sponsor = TxInput::new(OutPoint::new([0x02; 32], 1), Vec::new()).with_sequence(5);
+        let signed = |inputs: &[TxInput], anyonecanpay: bool| {
+            let preimage = SighashPreimageBuilder::new()
+                .prevouts(inputs)
+                .input(&user)
+                .script_code(vec![0x51; 30])
+                .value(1_000)
+                .hash_outputs(draft.compute_hash_outputs())
+                .build()
+                .to_bytes();
+            let witness = PaymasterWitness { preimage, ..draft.clone() };
+            let witness = if anyonecanpay { witness.for_anyonecanpay().unwrap() } else { witness };
+            let flag = witness.preimage[witness.preimage.len() - 4];
+            witness.with_user_signature(EcdsaSignature::with_sighash(vec![0x30; 70], flag))
+        };
+        let sighash = |witness: &PaymasterWitness| {
+            witness.verify_reconstruction().unwrap();
+            double_sha256(&witness.preimage)
+        };
+        let (alone, sponsored) = ([user.clone()], [user.clone(), sponsor]);
+        assert_ne!(sighash(&signed(&alone, false)), sighash(&signed(&sponsored, false)));
+        let witness = signed(&alone, true);
+        assert_eq!(witness.user_signature.sighash_flag, SighashFlag::AllAnyoneCanPay.byte());
+        assert_eq!(sighash(&witness), sighash(&signed(&sponsored, true)));
+        // Committing to the inputs anyway is refused
+        let mut committed = witness;
+        committed.preimage[4..36].copy_from_slice(&[0x11; 32]);
+        let err = committed.verify_reconstruction().unwrap_err();
+        assert!(matches!(err, ScriptErrorKind::MalformedPreimage { reason } if reason.contains("ANYONECANPAY")));
+    }
+    #[test]
+    fn test_diff_reconstruction_names_wrong_output() {
+        use crate::ghost::script::binding_diff::OutputDeviation;
+        use crate::ghost::script::SighashPreimageBuilder;
+        let intents = [make_intent(1, 90, 1, 0xAAAA), make_intent(1, 10, 2, 0xBBBB)];
+        let signed = PaymasterWitness::new(make_test_proof(), IpaHints::placeholder(10), PoseidonHints::placeholder(4), &intents[..1], &intents[1..], Vec::new());
+        let preimage = SighashPreimageBuilder::new()
+            .script_code(vec![0x51])
+            .hash_outputs(signed.compute_hash_outputs())
+            .build()
+            .to_bytes();
+        let signed = PaymasterWitness { preimage, ..signed };
+        assert!(signed.diff_reconstruction().unwrap().is_match());
+        let tampered = PaymasterWitness::new(
+            make_test_proof(),
+            IpaHints::placeholder(10),
+            PoseidonHints::placeholder(4),
+            &intents[..1],
+            &[make_intent(1, 11, 2, 0xBBBB)],
+            signed.preimage.clone(),
+        );
+        assert!(tampered.verify_reconstruction().is_err());
+        let diff = tampered.diff_reconstruction().unwrap();
+        assert!(!diff.is_match());
+        let reference = parse_outputs(&[&signed.app_outputs_bytes[..], &signed.change_outputs_bytes[..]].concat()).unwrap();
+        assert_eq!(diff.align(&reference), vec![OutputDeviation::Value { index: 1, expected: 10, actual: 11 }]);
+    }
+    #[test]
+    fn test_builder_missing_component_messages() {
+        let err = PaymasterWitnessBuilder::new().build().unwrap_err();
+        let kind = ScriptErrorKind::WitnessMissingComponent { component: WitnessComponent::Proof };
+        assert!(matches!(err, Error::InvalidInput(msg) if msg == kind.to_string()));
+        let err = PaymasterWitnessBuilder::new()
+            .proof(make_test_proof())
+            .ipa_hints(IpaHints::placeholder(10))
+            .poseidon_hints(PoseidonHints::placeholder(4))
+            .build()
+            .unwrap_err();
+        assert!(matches!(err, Error::InvalidInput(msg) if msg == "Missing preimage"));
+    }
+    #[test]
+    fn test_witness_trait_sizes() {
+        use crate::ghost::script::{MulletWitness, SighashPreimageBuilder, TailWitness};
+        let paymaster = PaymasterWitness::new(
+            make_test_proof(),
+            IpaHints::placeholder(10),
+            PoseidonHints::placeholder(4),
+            &[make_intent(1, 90, 1, 0xAAAA)],
+            &[make_intent(1, 10, 2, 0xBBBB)],
+            vec![0x00; 180],
+        );
+        let strict = StrictWitness::new(make_test_proof(), IpaHints::placeholder(10), PoseidonHints::placeholder(4), vec![0x00; 180]);
+        let mullet = MulletWitness {
+            proof: vec![0xAB; 400],
+            ipa_hints: IpaHints::placeholder(10),
+            poseidon_hints: PoseidonHints::placeholder(4),
+            tail_witness: TailWitness::Ecdsa { signature: vec![0x30; 72], pubkey: vec![0x02; 33] },
+            preimage: SighashPreimageBuilder::new().build(),
+            app_bytes: None,
+            change_bytes: None,
+        };
+        let witnesses: Vec<Box<dyn Witness>> = vec![Box::new(paymaster), Box::new(strict), Box::new(mullet)];
+        let estimated: usize = witnesses.iter().map(|w| w.estimate_size()).sum();
+        let actual: usize = witnesses.iter().map(|w| w.actual_size().unwrap()).sum();
+        assert_eq!(estimated, actual);
+        assert_eq!(actual, witnesses.iter().map(|w| w.try_to_script_sig(usize::MAX).unwrap().len()).sum::<usize>());
+    }
+    #[test]
+    fn test_strict_sighash_flag() {
+        let mut preimage = vec![0x00; 180];
+        preimage[176] = 0x41;
+        let witness = StrictWitness::new(
+            make_test_proof(),
+            IpaHints::placeholder(10),
+            PoseidonHints::placeholder(4),
+            preimage,
+        );
+        assert!(witness.verify_sighash_flag().is_ok());
+        let witness = witness.with_signature(EcdsaSignature::with_sighash(vec![0x30; 70], 0x01));
+        assert_eq!(witness.verify_sighash_flag(), Err(ScriptErrorKind::SignatureInvalid { index: 0 }));
+    }
+    #[test]
+    fn test_script_sig_push_cap() {
+        let witness = PaymasterWitness::new(
+            make_test_proof(),
+            IpaHints::placeholder(10),
+            PoseidonHints::placeholder(4),
+            &[make_intent(1, 90, 1, 0xAAAA)],
+            &[make_intent(1, 10, 2, 0xBBBB)],
+            vec![0x00; 180],
+        );
+        let largest = witness.ipa_hints.to_bytes().len();
+        assert_eq!(witness.try_to_script_sig(largest).unwrap(), witness.try_to_script_sig(usize::MAX).unwrap());
+        assert!(matches!(
+            witness.try_to_script_sig(largest - 1),
+            Err(PushError::TooLarge { len, .. }) if len == largest
+        ));
+    }
+    #[test]
     fn test_witness_size_estimation() {
         let witness = PaymasterWitness::new(
             make_test_proof(),
@@ -392,9 +1016,176 @@ mod tests {
             vec![0x00; 180],
         );
         let estimated = witness.estimate_size();
-        let actual = witness.to_script_sig().len();
+        let actual = witness.try_to_script_sig(usize::MAX).unwrap().len();
         assert!(estimated > actual / 2);
         assert!(estimated < actual * 2);
     }
+    struct SizeAccuracy {
+        estimated: usize,
+        actual: usize,
+    }
+    impl SizeAccuracy {
+        fn of(estimated: usize, script_sig: Vec<u8>) -> Self {
+            Self { estimated, actual: script_sig.len() }
+        }
+        fn assert_within(&self, tolerance: usize, label: &str) {
+            let error = self.estimated.abs_diff(self.actual);
+            assert!(error <= tolerance, "{}: estimated {} actual {}", label, self.estimated, self.actual);
+        }
+    }
+    #[test]
+    fn test_estimate_size_accuracy() {
+        // Small/medium/large proofs cross the 1-, 3- and 5-byte push prefixes
+        for proof_len in [60, 2_000, 70_000] {
+            let proof = Proof { bytes: vec![0xAB; proof_len], public_inputs: vec![Fp::from_u64(12345)] };
+            let paymaster = PaymasterWitness::new(
+                proof.clone(),
+                IpaHints::placeholder(10),
+                PoseidonHints::placeholder(4),
+                &[make_intent(1, 90, 1, 0xAAAA)],
+                &[make_intent(1, 10, 2, 0xBBBB), make_intent(1, 5, 3, 0xCCCC)],
+                vec![0x00; 180],
+            )
+            .with_sponsor_signature(EcdsaSignature::default());
+            SizeAccuracy::of(paymaster.estimate_size(), paymaster.try_to_script_sig(usize::MAX).unwrap()).assert_within(0, "paymaster");
+            let unchanged = PaymasterWitness { change_outputs_bytes: Vec::new(), ..paymaster };
+            SizeAccuracy::of(unchanged.estimate_size(), unchanged.try_to_script_sig(usize::MAX).unwrap()).assert_within(0, "paymaster without change");
+            let strict = StrictWitness::new(proof, IpaHints::placeholder(10), PoseidonHints::placeholder(4), vec![0x00; 180]);
+            SizeAccuracy::of(strict.estimate_size(), strict.try_to_script_sig(usize::MAX).unwrap()).assert_within(0, "strict");
+        }
+    }
+    #[test]
+    fn test_debug_redacts_signatures() {
+        let der: Vec<u8> = (0..71u8).map(|i| i.wrapping_mul(37) ^ 0x5a).collect();
+        let witness = PaymasterWitness::new(
+            make_test_proof(),
+            IpaHints::placeholder(10),
+            PoseidonHints::placeholder(4),
+            &[make_intent(1, 90, 1, 0xAAAA)],
+            &[make_intent(1, 10, 2, 0xBBBB)],
+            vec![0x00; 180],
+        )
+        .with_user_signature(EcdsaSignature::new(der.clone()));
+        let debug = format!("{:?}", witness);
+        assert!(!debug.contains(&hex::encode(&der)));
+        assert!(debug.contains("<71 bytes"));
+        assert!(debug.contains(&hex::encode(&sha256(&der)[..DIGEST_PREFIX])));
+        assert!(debug.contains(&format!("{}..{}", hex::encode(&der[..4]), hex::encode(&der[67..]))));
+        assert!(!debug.contains(&hex::encode(witness.proof.to_bytes())));
+        let full = format!("{:?}", witness.full_debug());
+        assert!(full.contains(&hex::encode(&der)));
+        assert!(full.contains(&hex::encode(&witness.preimage)));
+    }
+    #[test]
+    fn test_split_fee_two_change_outputs_two_sponsors() {
+        use crate::ghost::binding::BindingMode;
+        use crate::ghost::crypto::double_sha256;
+        use crate::ghost::script::num::parse_pushes;
+        use crate::ghost::script::vm::{Interpreter, SignatureChecker};
+        use crate::ghost::script::{MultisigTail, SighashPreimageBuilder, Tail, VerifyBinding};
+        struct KeyedChecker(Vec<(Vec<u8>, Vec<u8>)>);
+        impl SignatureChecker for KeyedChecker {
+            fn check_sig(&self, signature: &[u8], pubkey: &[u8]) -> bool {
+                self.0.iter().any(|(s, k)| s == signature && k == pubkey)
+            }
+        }
+        let app = [make_intent(1, 90, 1, 0xAAAA)];
+        let change = [make_intent(1, 6, 2, 0xBBBB), make_intent(1, 4, 3, 0xCCCC)];
+        let draft = PaymasterWitness::new(make_test_proof(), IpaHints::placeholder(10), PoseidonHints::placeholder(4), &app, &change, Vec::new());
+        let preimage = SighashPreimageBuilder::new().hash_outputs(draft.compute_hash_outputs()).build().to_bytes();
+        let (relayer, treasury) = ([0x02; 33], [0x03; 33]);
+        let sigs = [EcdsaSignature::new(vec![0x30; 70]), EcdsaSignature::new(vec![0x31; 71])];
+        let witness = PaymasterWitnessBuilder::new()
+            .proof(make_test_proof())
+            .ipa_hints(IpaHints::placeholder(10))
+            .poseidon_hints(PoseidonHints::placeholder(4))
+            .app_outputs(app.to_vec())
+            .change_outputs(change.to_vec())
+            .preimage(preimage.clone())
+            .sponsor_signatures(sigs.to_vec())
+            .build()
+            .unwrap();
+        assert_eq!(double_sha256(&[witness.app_outputs_bytes.clone(), witness.change_outputs_bytes.clone()].concat()), witness.compute_hash_outputs());
+        // scriptSig: sponsor sigs, user sig, preimage, one item per change output, ...
+        let items = parse_pushes(&witness.try_to_script_sig(usize::MAX).unwrap()).unwrap();
+        assert_eq!(items[..2], [sigs[0].to_bytes(), sigs[1].to_bytes()]);
+        assert_eq!(items[3], preimage);
+        assert_eq!(items[4..6], [witness.change_outputs_bytes[..41].to_vec(), witness.change_outputs_bytes[41..].to_vec()]);
+        let unsponsored = PaymasterWitness { sponsor_signatures: Vec::new(), ..witness.clone() };
+        assert_eq!(witness.estimate_size(), unsponsored.estimate_size() + sigs[0].size() + sigs[1].size() + 2);
+        // Binding: 1 app output + 2 change outputs reconstruct hash_outputs
+        let app_bytes = &witness.app_outputs_bytes;
+        let binding_stack = vec![vec![0x00], app_bytes[9..41].to_vec(), app_bytes[..8].to_vec(), items[4].clone(), items[5].clone(), preimage];
+        let binding = VerifyBinding::new(1, BindingMode::Partial).with_change_outputs(2);
+        Interpreter::new().eval_with_stack(binding_stack, &binding.build()).unwrap();
+        // Both sponsor signatures verify against a 2-of-2 sponsor tail
+        let tail = MultisigTail::try_new(2, vec![relayer, treasury]).unwrap();
+        let checker = KeyedChecker(vec![(items[0].clone(), relayer.to_vec()), (items[1].clone(), treasury.to_vec())]);
+        let mut vm = Interpreter::with_checker(Box::new(checker));
+        assert!(vm.eval_with_stack(vec![Vec::new(), items[0].clone(), items[1].clone()], &tail.locking_script()).unwrap());
+        let swapped = vec![Vec::new(), items[1].clone(), items[0].clone()];
+        let checker = KeyedChecker(vec![(items[0].clone(), relayer.to_vec()), (items[1].clone(), treasury.to_vec())]);
+        assert!(!Interpreter::with_checker(Box::new(checker)).eval_with_stack(swapped, &tail.locking_script()).unwrap());
+    }
+    // RFC6979 vector (key 1, sha256("Satoshi Nakamoto")) with R and S
+    // re-encoded the ways lax signers emit them
+    const R_HEX: &str = "934b1ea10a4b3c1757e2b0c017d0b6143ce3c9a7e6a4a49860d7a6ab210ee3d8";
+    const S_HEX: &str = "2442ce9d2b916064108014783e923ec36b49743e2ffa1c4496f01a512aafd9e5";
+    fn der_of(r: &str, s: &str) -> Vec<u8> {
+        let (r, s) = (hex::decode(r).unwrap(), hex::decode(s).unwrap());
+        let body = [vec![0x02, r.len() as u8], r, vec![0x02, s.len() as u8], s].concat();
+        [vec![0x30, body.len() as u8], body].concat()
+    }
+    #[test]
+    fn test_normalize_non_minimal_der_corpus() {
+        use crate::ghost::crypto::sha256;
+        let hash = sha256(b"Satoshi Nakamoto");
+        let pubkey = ecdsa_public_key(&{ let mut k = [0u8; 32]; k[31] = 1; k }).unwrap();
+        let canonical = EcdsaSignature::with_sighash(der_of(&format!("00{R_HEX}"), S_HEX), 0xc1);
+        assert!(canonical.is_canonical() && canonical.verify(&pubkey, &hash));
+        assert_eq!(canonical.normalize().unwrap().to_bytes(), canonical.to_bytes());
+        let corpus = [
+            ("unpadded_r", der_of(R_HEX, S_HEX)),
+            ("padded_s", der_of(&format!("00{R_HEX}"), &format!("00{S_HEX}"))),
+            ("double_pad_r", der_of(&format!("0000{R_HEX}"), S_HEX)),
+            ("both", der_of(R_HEX, &format!("0000{S_HEX}"))),
+        ];
+        for (name, der) in corpus {
+            let sig = EcdsaSignature::with_sighash(der, 0xc1);
+            assert!(!sig.is_canonical(), "{name}");
+            let normalized = sig.normalize().unwrap();
+            assert_eq!(normalized.to_bytes(), canonical.to_bytes(), "{name}");
+            assert!(normalized.is_canonical() && normalized.verify(&pubkey, &hash), "{name}");
+        }
+        let reject = |der: Vec<u8>| EcdsaSignature::new(der).normalize().err();
+        assert_eq!(reject(der_of("0000", S_HEX)), Some(DerError::ZeroInteger));
+        assert_eq!(reject(der_of(&format!("01{R_HEX}"), S_HEX)), Some(DerError::IntegerTooLong));
+        let mut trailing = der_of(R_HEX, S_HEX);
+        trailing.extend([0x02, 0x01, 0x01]);
+        trailing[1] += 3;
+        assert_eq!(reject(trailing), Some(DerError::TrailingBytes));
+        assert_eq!(reject(vec![0x30; 70]), Some(DerError::Malformed));
+    }
+    #[test]
+    fn test_builder_normalizes_signatures() {
+        let padded = EcdsaSignature::new(der_of(&format!("0000{R_HEX}"), &format!("00{S_HEX}")));
+        let placeholder = EcdsaSignature::new(vec![0x30; 70]);
+        let builder = || PaymasterWitnessBuilder::new()
+            .proof(make_test_proof())
+            .ipa_hints(IpaHints::placeholder(10))
+            .poseidon_hints(PoseidonHints::placeholder(4))
+            .app_outputs(vec![make_intent(1, 100, 1, 0xAAAA)])
+            .preimage(vec![0u8; 200])
+            .user_signature(padded.clone())
+            .sponsor_signatures(vec![padded.clone(), placeholder.clone()]);
+        let signatures = |witness: PaymasterWitness| {
+            let mut all = vec![witness.user_signature.to_bytes()];
+            all.extend(witness.sponsor_signatures.iter().map(EcdsaSignature::to_bytes));
+            all
+        };
+        let normalized = padded.normalize().unwrap().to_bytes();
+        assert_eq!(signatures(builder().build().unwrap()), [normalized.clone(), normalized, placeholder.to_bytes()]);
+        let kept = signatures(builder().keep_signature_encoding().build().unwrap());
+        assert_eq!(kept, [padded.to_bytes(), padded.to_bytes(), placeholder.to_bytes()]);
+    }
 }
-
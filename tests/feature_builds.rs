@@ -0,0 +1,43 @@
+// Feature Builds
+// Each feature tier must compile on its own, so a wallet can depend on
+// `core-script` alone without pulling in the Poseidon and contract modules.
+// Runs `cargo check` per tier in a separate target directory so it does not
+// contend for the lock held by the running test build.
+
+use std::path::Path;
+use std::process::Command;
+
+/// Tiers above `core-script`, each checked without the default features
+const UPPER_TIERS: [&str; 3] = ["poseidon-script", "contract", "cbor"];
+
+fn check(features: &str) -> Result<(), String> {
+    let manifest_dir = env!("CARGO_MANIFEST_DIR");
+    let output = Command::new(env!("CARGO"))
+        .current_dir(manifest_dir)
+        .args(["check", "--lib", "--no-default-features", "--features", features])
+        .env("CARGO_TARGET_DIR", Path::new(manifest_dir).join("target").join("feature-builds"))
+        .env("RUSTFLAGS", "-D warnings")
+        .output()
+        .map_err(|e| format!("could not run cargo: {}", e))?;
+    if output.status.success() {
+        Ok(())
+    } else {
+        Err(String::from_utf8_lossy(&output.stderr).into_owned())
+    }
+}
+
+#[test]
+fn test_core_script_builds_alone() {
+    if let Err(stderr) = check("core-script") {
+        panic!("--no-default-features --features core-script failed:\n{}", stderr);
+    }
+}
+
+#[test]
+fn test_upper_feature_tiers_build() {
+    let failures: Vec<String> = UPPER_TIERS
+        .iter()
+        .filter_map(|tier| check(tier).err().map(|stderr| format!("--features {}:\n{}", tier, stderr)))
+        .collect();
+    assert!(failures.is_empty(), "feature tiers failed to build:\n{}", failures.join("\n"));
+}
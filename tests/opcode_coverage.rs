@@ -0,0 +1,145 @@
+// Opcode Coverage
+// Every opcode the public generators emit must run in `vm::Interpreter`.
+// Builders gain opcodes faster than the interpreter does; this fails with the
+// missing opcodes and the generators that emit them instead of letting
+// generated scripts go untestable. Runs as an integration test so it sees
+// the generators and the interpreter only through the public API.
+
+use std::collections::BTreeMap;
+use sovereign_tokens::ghost::script::disasm::{instructions, Instruction};
+use sovereign_tokens::ghost::script::field::generate_canonical_check;
+#[cfg(feature = "poseidon-script")]
+use sovereign_tokens::ghost::script::field_script::{
+    generate_full_round_opt, generate_partial_round_opt,
+    generate_poseidon_hash2_script, generate_poseidon_script_opt, generate_reduce_script,
+    generate_secure_witness_verification, generate_witness_locking_script,
+};
+#[cfg(feature = "poseidon-script")]
+use sovereign_tokens::ghost::script::poseidon_guard::generate_poseidon_binding_script;
+use sovereign_tokens::ghost::script::vm::Interpreter;
+use sovereign_tokens::ghost::script::{
+    AnyoneCanSpendTail, BranchTail, DualAuthTail, EcdsaTail, Guard, GuardConfig, HtlcTail,
+    InputCountBinding, IpaHints, LamportTail, MulletScript, MultisigTail, PoseidonHints,
+    ScriptCodeBinding, SequenceTimelockTail, SighashFlag, SighashPolicy, SponsorTail, Tail,
+    TailBinding, TimelockTail, TranscriptKind, UniversalGuard,
+};
+#[cfg(feature = "contract")]
+use sovereign_tokens::ghost::script::{IPAAccumulator, VerifierContract};
+
+fn tails() -> Vec<(&'static str, Box<dyn Tail>)> {
+    let multisig = MultisigTail::try_two_of_three([0x02; 33], [0x03; 33], {
+        let mut pk = [0x02; 33];
+        pk[32] = 0;
+        pk
+    });
+    let tails: [(&'static str, Box<dyn Tail>); 10] = [
+        ("EcdsaTail", Box::new(EcdsaTail::from_pubkey_hash(&[0x11; 20]))),
+        ("MultisigTail", Box::new(multisig.expect("distinct compressed keys"))),
+        ("LamportTail", Box::new(LamportTail::placeholder())),
+        ("SponsorTail", Box::new(SponsorTail::from_pubkey_hash(&[0x22; 20]))),
+        ("DualAuthTail", Box::new(DualAuthTail::new([0x11; 20], [0x22; 20]))),
+        ("HtlcTail", Box::new(HtlcTail::new([0x33; 32], [0x11; 20], [0x22; 20], 500_000))),
+        ("TimelockTail", Box::new(TimelockTail::new(500_000, [0x11; 20]))),
+        ("SequenceTimelockTail", Box::new(SequenceTimelockTail::blocks(144, [0x11; 20]))),
+        ("BranchTail", Box::new(BranchTail::try_new(vec![
+            Box::new(EcdsaTail::from_pubkey_hash(&[0x11; 20])),
+            Box::new(HtlcTail::new([0x33; 32], [0x11; 20], [0x22; 20], 500_000)),
+        ]).unwrap())),
+        ("AnyoneCanSpendTail", Box::new(AnyoneCanSpendTail)),
+    ];
+    tails.into()
+}
+
+fn generated_scripts() -> Vec<(&'static str, Vec<u8>)> {
+    let mut scripts = vec![
+        ("Guard::universal", Guard::universal().to_bytes()),
+        ("Guard::paymaster", Guard::paymaster().to_bytes()),
+        ("Guard::paymaster_with_output_cap", Guard::paymaster_with_output_cap(1_000_000).to_bytes()),
+        ("Guard::paymaster_with_output_count_range", Guard::paymaster_with_output_count_range(1, 3).unwrap().to_bytes()),
+        ("Guard::minimal", Guard::minimal().to_bytes()),
+        ("ScriptCodeBinding", ScriptCodeBinding::fit(40, 300).build()),
+        ("InputCountBinding", InputCountBinding::new(2).build()),
+        ("MulletScript::bind_script_code", MulletScript::minimal(EcdsaTail::from_pubkey_hash(&[0x11; 20])).bind_script_code().locking_script()),
+        ("generate_canonical_check", generate_canonical_check()),
+        ("SighashPolicy", SighashPolicy::new(vec![SighashFlag::All, SighashFlag::SingleAnyoneCanPay]).script()),
+    ];
+    #[cfg(feature = "poseidon-script")]
+    scripts.extend([
+        ("generate_full_round_opt", generate_full_round_opt(0)),
+        ("generate_partial_round_opt", generate_partial_round_opt(4)),
+        ("generate_poseidon_script_opt", generate_poseidon_script_opt()),
+        ("generate_poseidon_hash2_script", generate_poseidon_hash2_script()),
+        ("generate_reduce_script", generate_reduce_script()),
+        ("generate_witness_locking_script", generate_witness_locking_script()),
+        ("generate_secure_witness_verification", generate_secure_witness_verification()),
+        ("generate_poseidon_binding_script", generate_poseidon_binding_script(&[0x01; 32], &[0x02; 32], &[0x03; 32])),
+    ]);
+    #[cfg(feature = "contract")]
+    scripts.push(("VerifierContract", VerifierContract::new([0x11; 20], IPAAccumulator::new([0x22; 32])).locking_script()));
+    let configs = [
+        ("UniversalGuard::strict", GuardConfig::new(2, 2).strict()),
+        ("UniversalGuard::paymaster", GuardConfig::new(2, 2).paymaster(1_000).with_change_outputs(2)),
+        ("UniversalGuard with output cap", GuardConfig::new(1, 2).paymaster(1_000).with_output_value_cap(1_000_000)),
+        ("UniversalGuard with hints", GuardConfig::new(1, 1)
+            .with_ipa_hints(IpaHints::placeholder(3))
+            .with_poseidon_hints(PoseidonHints::placeholder(8))
+            .with_tail_witness_items(2)),
+    ]
+    .into_iter();
+    #[cfg(feature = "poseidon-script")]
+    let configs = configs.chain([("UniversalGuard with Poseidon transcript", GuardConfig::new(1, 1)
+        .with_ipa_hints(IpaHints::placeholder(2))
+        .with_transcript(TranscriptKind::Poseidon)
+        .preserve_message(false))]);
+    for (name, config) in configs {
+        scripts.push((name, UniversalGuard::new(config).build().unwrap()));
+    }
+    let bound = UniversalGuard::new(GuardConfig::new(1, 1).bind_tail(true))
+        .with_tail_binding(TailBinding::new(&EcdsaTail::from_pubkey_hash(&[0x11; 20]).locking_script()));
+    scripts.push(("UniversalGuard with tail binding", bound.build().unwrap()));
+    let kinds = [TranscriptKind::Sha256].into_iter();
+    #[cfg(feature = "poseidon-script")]
+    let kinds = kinds.chain([TranscriptKind::Poseidon]);
+    for kind in kinds {
+        scripts.push(("TranscriptKind", [kind.init(), kind.absorb(), kind.squeeze()].concat()));
+    }
+    for (name, tail) in tails() {
+        scripts.push((name, tail.locking_script()));
+    }
+    scripts
+}
+
+/// Opcodes the interpreter lacks, with the generators emitting each
+fn unsupported(scripts: &[(&'static str, Vec<u8>)]) -> BTreeMap<u8, Vec<&'static str>> {
+    let mut missing: BTreeMap<u8, Vec<&'static str>> = BTreeMap::new();
+    for (name, script) in scripts {
+        for (_, instruction) in instructions(script).map_while(Result::ok) {
+            if let Instruction::Op(opcode) = instruction {
+                if !Interpreter::supports(opcode) {
+                    let emitters = missing.entry(opcode).or_default();
+                    if !emitters.contains(name) {
+                        emitters.push(*name);
+                    }
+                }
+            }
+        }
+    }
+    missing
+}
+
+#[test]
+fn test_interpreter_runs_every_generated_opcode() {
+    let missing = unsupported(&generated_scripts());
+    let report: Vec<String> = missing
+        .iter()
+        .map(|(opcode, emitters)| format!("0x{:02x} ({})", opcode, emitters.join(", ")))
+        .collect();
+    assert!(missing.is_empty(), "interpreter lacks opcodes: {}", report.join("; "));
+}
+#[test]
+fn test_unsupported_opcodes_are_reported() {
+    use sovereign_tokens::ghost::script::{OP_DUP, OP_SHA1};
+    assert!(Interpreter::unimplemented_opcodes().contains(&OP_SHA1));
+    let missing = unsupported(&[("sha1 guard", vec![OP_DUP, OP_SHA1])]);
+    assert_eq!(missing.into_iter().collect::<Vec<_>>(), vec![(OP_SHA1, vec!["sha1 guard"])]);
+}